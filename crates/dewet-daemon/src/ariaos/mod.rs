@@ -4,10 +4,14 @@
 //! Replaces the previous DSL-based approach with structured tool calling.
 
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, Days, Local, NaiveTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
-use crate::llm::{ToolCall, ToolDefinition};
+use crate::{
+    llm::{ToolCall, ToolDefinition},
+    storage::ForgetRequest,
+};
 
 /// A parsed ARIAOS command (internal representation)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +19,29 @@ use crate::llm::{ToolCall, ToolDefinition};
 pub enum AriaosCommand {
     #[serde(rename = "notes")]
     Notes(NotesAction),
+    /// Delete memories the user asked to be forgotten (handled by the
+    /// daemon, not sent to Godot)
+    #[serde(rename = "forget")]
+    Forget(ForgetRequest),
+    /// Bring something up at a set time (handled by the daemon, not sent
+    /// to Godot)
+    #[serde(rename = "reminder")]
+    Remind(ReminderRequest),
+}
+
+impl AriaosCommand {
+    /// Whether the daemon carries this out itself rather than an ARIAOS app
+    pub fn is_daemon_command(&self) -> bool {
+        matches!(self, AriaosCommand::Forget(_) | AriaosCommand::Remind(_))
+    }
+}
+
+/// A reminder a companion set for the user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReminderRequest {
+    /// When it is due (unix seconds)
+    pub due_at: i64,
+    pub text: String,
 }
 
 /// Actions for the Notes app
@@ -35,6 +62,36 @@ pub enum NotesAction {
     ScrollToTop,
     #[serde(rename = "scroll_to_bottom")]
     ScrollToBottom,
+    /// Restore the notes as of a revision, or undo the last change
+    #[serde(rename = "revert")]
+    Revert(Option<i64>),
+}
+
+impl NotesAction {
+    /// What an edit did, for the episode log; None for scrolling
+    pub fn edit_summary(&self) -> Option<String> {
+        match self {
+            NotesAction::SetContent(content) => Some(format!("Rewrote notes: {content}")),
+            NotesAction::Append(content) => Some(format!("Added to notes: {content}")),
+            NotesAction::Clear => Some("Cleared notes".to_string()),
+            NotesAction::Revert(_) => Some("Reverted notes".to_string()),
+            _ => None,
+        }
+    }
+
+    /// The tool that performs this action
+    pub fn tool_name(&self) -> &'static str {
+        match self {
+            NotesAction::SetContent(_) => "notes_set_content",
+            NotesAction::Append(_) => "notes_append",
+            NotesAction::Clear => "notes_clear",
+            NotesAction::ScrollUp => "notes_scroll_up",
+            NotesAction::ScrollDown => "notes_scroll_down",
+            NotesAction::ScrollToTop => "notes_scroll_to_top",
+            NotesAction::ScrollToBottom => "notes_scroll_to_bottom",
+            NotesAction::Revert(_) => "notes_revert",
+        }
+    }
 }
 
 /// Get tool definitions for ARIAOS capabilities.
@@ -80,6 +137,20 @@ pub fn ariaos_tools() -> Vec<ToolDefinition> {
                 "additionalProperties": false
             }),
         ),
+        ToolDefinition::new(
+            "notes_revert",
+            "Undo the last change to your notes, or restore them as of an earlier revision. Use this if an edit lost something you wanted to keep.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "revision_id": {
+                        "type": "integer",
+                        "description": "The revision to restore (omit to undo the last change)"
+                    }
+                },
+                "additionalProperties": false
+            }),
+        ),
         ToolDefinition::new(
             "notes_scroll_up",
             "Scroll your notes view up to see earlier content.",
@@ -116,6 +187,51 @@ pub fn ariaos_tools() -> Vec<ToolDefinition> {
                 "additionalProperties": false
             }),
         ),
+        ToolDefinition::new(
+            "forget",
+            "Permanently forget chat, memories and notes. Only use this when the user asks you to forget something.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Forget everything about this topic"
+                    },
+                    "sender": {
+                        "type": "string",
+                        "description": "Only forget what this sender said (\"user\" or a companion id)"
+                    },
+                    "last_hours": {
+                        "type": "number",
+                        "description": "Only forget what happened in this many past hours"
+                    }
+                },
+                "additionalProperties": false
+            }),
+        ),
+        ToolDefinition::new(
+            "set_reminder",
+            "Remind the user of something later. You will be prompted to bring it up when it is due. Give either 'in_minutes' or 'at'.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "What to remind the user of"
+                    },
+                    "in_minutes": {
+                        "type": "number",
+                        "description": "Remind this many minutes from now"
+                    },
+                    "at": {
+                        "type": "string",
+                        "description": "Remind at this local time, 24-hour \"HH:MM\" (the next time it comes round)"
+                    }
+                },
+                "required": ["text"],
+                "additionalProperties": false
+            }),
+        ),
     ]
 }
 
@@ -144,16 +260,67 @@ pub fn tool_call_to_command(tool_call: &ToolCall) -> Result<Option<AriaosCommand
             Some(AriaosCommand::Notes(NotesAction::Append(content)))
         }
         "notes_clear" => Some(AriaosCommand::Notes(NotesAction::Clear)),
+        "notes_revert" => {
+            let revision_id = args.get("revision_id").and_then(|v| v.as_i64());
+            Some(AriaosCommand::Notes(NotesAction::Revert(revision_id)))
+        }
         "notes_scroll_up" => Some(AriaosCommand::Notes(NotesAction::ScrollUp)),
         "notes_scroll_down" => Some(AriaosCommand::Notes(NotesAction::ScrollDown)),
         "notes_scroll_to_top" => Some(AriaosCommand::Notes(NotesAction::ScrollToTop)),
         "notes_scroll_to_bottom" => Some(AriaosCommand::Notes(NotesAction::ScrollToBottom)),
+        "forget" => {
+            let text = |key: &str| args.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            let since = args
+                .get("last_hours")
+                .and_then(|v| v.as_f64())
+                .map(|hours| Utc::now().timestamp() - (hours * 3600.0) as i64);
+            let request = ForgetRequest {
+                since,
+                until: None,
+                sender: text("sender"),
+                query: text("query"),
+            };
+            if request.is_empty() {
+                return Err(anyhow!("forget requires 'query', 'sender' or 'last_hours'"));
+            }
+            Some(AriaosCommand::Forget(request))
+        }
+        "set_reminder" => {
+            let text = args
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow!("set_reminder requires 'text' argument"))?
+                .to_string();
+            let due_at = if let Some(minutes) = args.get("in_minutes").and_then(|v| v.as_f64()) {
+                Utc::now().timestamp() + (minutes.max(0.0) * 60.0) as i64
+            } else if let Some(at) = args.get("at").and_then(|v| v.as_str()) {
+                let time = NaiveTime::parse_from_str(at.trim(), "%H:%M")
+                    .map_err(|_| anyhow!("set_reminder 'at' must be \"HH:MM\", got {at:?}"))?;
+                next_time_of_day(time, Local::now())
+                    .ok_or_else(|| anyhow!("set_reminder: {at} doesn't exist locally"))?
+                    .timestamp()
+            } else {
+                return Err(anyhow!("set_reminder requires 'in_minutes' or 'at'"));
+            };
+            Some(AriaosCommand::Remind(ReminderRequest { due_at, text }))
+        }
         _ => None, // Not an ARIAOS tool
     };
 
     Ok(command)
 }
 
+/// The next time after `now` the clock shows `time`: today, or tomorrow if
+/// it has passed
+fn next_time_of_day<Tz: TimeZone>(time: NaiveTime, now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+    let timezone = now.timezone();
+    let today = now.date_naive();
+    [today, today.checked_add_days(Days::new(1))?]
+        .into_iter()
+        .filter_map(|day| timezone.from_local_datetime(&day.and_time(time)).earliest())
+        .find(|at| *at > now)
+}
+
 /// Convert multiple tool calls to ARIAOS commands.
 /// Filters out non-ARIAOS tools and collects any errors.
 pub fn tool_calls_to_commands(tool_calls: &[ToolCall]) -> (Vec<AriaosCommand>, Vec<String>) {
@@ -230,6 +397,83 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_tool_call_revert() {
+        let call = ToolCall {
+            id: "call_321".to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "notes_revert".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let result = tool_call_to_command(&call).unwrap();
+        assert!(matches!(
+            result,
+            Some(AriaosCommand::Notes(NotesAction::Revert(None)))
+        ));
+    }
+
+    #[test]
+    fn test_tool_call_forget() {
+        let call = ToolCall {
+            id: "call_654".to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "forget".to_string(),
+                arguments: r#"{"query": "project X"}"#.to_string(),
+            },
+        };
+
+        let result = tool_call_to_command(&call).unwrap();
+        assert!(matches!(
+            result,
+            Some(AriaosCommand::Forget(r)) if r.query.as_deref() == Some("project X") && r.since.is_none()
+        ));
+
+        let empty = ToolCall {
+            function: FunctionCall {
+                name: "forget".to_string(),
+                arguments: "{}".to_string(),
+            },
+            ..call
+        };
+        assert!(tool_call_to_command(&empty).is_err());
+    }
+
+    #[test]
+    fn test_tool_call_set_reminder() {
+        let call = ToolCall {
+            id: "call_987".to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "set_reminder".to_string(),
+                arguments: r#"{"text": "stretch", "in_minutes": 30}"#.to_string(),
+            },
+        };
+        let expected = Utc::now().timestamp() + 30 * 60;
+        let result = tool_call_to_command(&call).unwrap();
+        assert!(matches!(
+            result,
+            Some(AriaosCommand::Remind(r)) if r.text == "stretch" && (r.due_at - expected).abs() <= 1
+        ));
+
+        let no_time = ToolCall {
+            function: FunctionCall {
+                name: "set_reminder".to_string(),
+                arguments: r#"{"text": "stretch"}"#.to_string(),
+            },
+            ..call
+        };
+        assert!(tool_call_to_command(&no_time).is_err());
+
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 16, 0, 0).unwrap();
+        let three = NaiveTime::from_hms_opt(15, 0, 0).unwrap();
+        let next = next_time_of_day(three, now).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2024, 5, 2, 15, 0, 0).unwrap());
+    }
+
     #[test]
     fn test_unknown_tool() {
         let call = ToolCall {
@@ -248,7 +492,7 @@ mod tests {
     #[test]
     fn test_tools_definition() {
         let tools = ariaos_tools();
-        assert_eq!(tools.len(), 7);
+        assert_eq!(tools.len(), 10);
 
         // Check that all tools have proper structure
         for tool in &tools {
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::config::MonitorSelection;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
@@ -24,6 +26,59 @@ pub enum ClientMessage {
         #[serde(default)]
         payload: Value,
     },
+    /// Capture only the window matching `title` (a regex) and/or `app`; with
+    /// neither, go back to capturing the desktop
+    SetCaptureWindow {
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        app: Option<String>,
+    },
+    /// Reply with a full-resolution `Screenshot` of the window matching
+    /// `title`/`app`, else of `monitor`, else of what is being captured
+    CaptureScreenshot {
+        #[serde(default)]
+        monitor: Option<MonitorSelection>,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        app: Option<String>,
+    },
+    /// Reply with `NotesRevisions` for a companion's notes (the shared
+    /// notes when absent)
+    ListNotesRevisions {
+        #[serde(default)]
+        character_id: Option<String>,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Restore the notes as of a revision
+    RevertNotes {
+        revision_id: i64,
+    },
+    /// Say the last reply again differently, with `feedback` for the
+    /// companion; the new reply replaces it in chat history
+    RegenerateResponse {
+        #[serde(default)]
+        feedback: String,
+    },
+    /// Reply with `DecisionStats` for the last `hours` (default 24)
+    GetDecisionStats {
+        #[serde(default)]
+        hours: Option<u32>,
+    },
+    /// Delete chat, episodes, notes and logs matching every given filter:
+    /// a unix-seconds range, a sender and/or a topic; replies with `Forgotten`
+    Forget {
+        #[serde(default)]
+        since: Option<i64>,
+        #[serde(default)]
+        until: Option<i64>,
+        #[serde(default)]
+        sender: Option<String>,
+        #[serde(default)]
+        query: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,11 +94,60 @@ pub enum DaemonMessage {
         audio_base64: Option<String>,
         #[serde(default)]
         puppet: Value,
+        /// "wav" or "mp3"; set whenever `audio_base64` is
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audio_format: Option<String>,
+        /// Set when the audio follows as `SpeakChunk`s instead of `audio_base64`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        utterance_id: Option<String>,
+    },
+    /// Reply text to append to what the companion is saying, sent while the
+    /// reply is still generating. The following `Speak` carries the final
+    /// text (which replaces the deltas) and the audio; if none follows, the
+    /// reply was dropped.
+    SpeakDelta {
+        character_id: String,
+        delta: String,
+    },
+    /// Audio for one sentence of a streamed `Speak`, sent as soon as it is
+    /// synthesized. Chunks arrive in `index` order and play back-to-back.
+    SpeakChunk {
+        character_id: String,
+        utterance_id: String,
+        index: u32,
+        /// Last chunk of the utterance
+        is_final: bool,
+        text: String,
+        /// None if synthesis of this sentence failed
+        audio_base64: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        audio_format: Option<String>,
+        /// Viseme cues for this sentence, like `puppet.lip_sync` on `Speak`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lip_sync: Option<Value>,
+    },
+    /// A companion is about to talk, so clients can duck other audio. Sent
+    /// before the first audio of each reply.
+    SpeechStarted {
+        character_id: String,
+        /// Matches `SpeechEnded`, and the `Speak`'s `utterance_id` if streamed
+        utterance_id: String,
+        /// Expected playing time of the whole reply
+        estimated_duration_ms: u64,
+    },
+    /// The reply's audio should have finished playing
+    SpeechEnded {
+        character_id: String,
+        utterance_id: String,
     },
     React {
         character_id: String,
         expression: String,
     },
+    /// What the user said aloud, shown in the chat like a typed message
+    Transcript {
+        text: String,
+    },
     RenderOpticalMemory {
         chat_history: Vec<ChatPacket>,
         memory_nodes: Vec<MemoryNode>,
@@ -69,6 +173,9 @@ pub enum DaemonMessage {
         active_app: String,
         active_window: String,
         screen_summary: String,
+        /// On-screen text, when OCR is enabled
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        screen_text: Option<String>,
         timestamp: i64,
     },
     VisionAnalysis {
@@ -93,6 +200,54 @@ pub enum DaemonMessage {
         prompt: String,
         /// The model's response
         response: String,
+        /// Reasoning stripped from the response, if logged
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reasoning: Option<String>,
+        timestamp: i64,
+    },
+    /// The screen changed past `[vision] diff_threshold`, sent as soon as
+    /// the frame is captured, before it is analyzed
+    SceneChange {
+        diff_score: f32,
+        timestamp: i64,
+    },
+    /// Reply to `CaptureScreenshot`, privacy rules applied
+    Screenshot {
+        /// PNG
+        image_base64: String,
+        width: u32,
+        height: u32,
+        timestamp: i64,
+    },
+    /// Reply to `ListNotesRevisions`, newest first
+    NotesRevisions {
+        character_id: Option<String>,
+        revisions: Value,
+    },
+    /// Reply to `GetDecisionStats`
+    DecisionStats {
+        hours: u32,
+        stats: Value,
+    },
+    /// What a `Forget` (or the companions' `forget` tool) removed
+    Forgotten {
+        request: Value,
+        report: Value,
+    },
+    /// The host went idle or was locked ("idle", "locked"), pausing capture
+    /// and model calls, or became active again ("active")
+    ActivityState {
+        state: String,
+        timestamp: i64,
+    },
+    /// Startup readiness of a configured model
+    ModelStatus {
+        /// "vla", "arbiter", "response", "audit", or "embedding"
+        role: String,
+        model: String,
+        ready: bool,
+        latency_ms: u64,
+        error: Option<String>,
         timestamp: i64,
     },
 }
@@ -107,6 +262,25 @@ pub enum MemoryTier {
     Cold,  // Old or low relevance, candidate for eviction
 }
 
+impl MemoryTier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemoryTier::Hot => "hot",
+            MemoryTier::Warm => "warm",
+            MemoryTier::Cold => "cold",
+        }
+    }
+
+    /// Parse a stored tier, treating anything unknown as hot
+    pub fn parse(tier: &str) -> Self {
+        match tier {
+            "warm" => MemoryTier::Warm,
+            "cold" => MemoryTier::Cold,
+            _ => MemoryTier::Hot,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatPacket {
     pub sender: String,
@@ -118,6 +292,9 @@ pub struct ChatPacket {
     /// Memory tier based on relevance and recency
     #[serde(default)]
     pub tier: MemoryTier,
+    /// Companion whose conversation this belongs to; shared when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character_id: Option<String>,
 }
 
 impl ChatPacket {
@@ -125,6 +302,11 @@ impl ChatPacket {
         1.0
     }
     
+    /// Whether `character_id` sees this message with per-character memory
+    pub fn visible_to(&self, character_id: &str) -> bool {
+        self.character_id.as_deref().is_none_or(|id| id == character_id)
+    }
+
     /// Calculate age in seconds
     pub fn age_seconds(&self) -> i64 {
         chrono::Utc::now().timestamp() - self.timestamp
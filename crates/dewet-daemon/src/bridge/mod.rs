@@ -30,6 +30,7 @@ const BROADCAST_BUFFER: usize = 256;
 pub struct Bridge {
     incoming_rx: mpsc::Receiver<ClientMessage>,
     outgoing_tx: broadcast::Sender<DaemonMessage>,
+    clients: Arc<AtomicUsize>,
 }
 
 impl Bridge {
@@ -39,12 +40,14 @@ impl Bridge {
 
         let (incoming_tx, incoming_rx) = mpsc::channel(INCOMING_BUFFER);
         let (outgoing_tx, _) = broadcast::channel(BROADCAST_BUFFER);
+        let clients = Arc::new(AtomicUsize::new(0));
 
         let acceptor = BridgeAcceptor {
             listener,
             incoming_tx,
             outgoing_tx: outgoing_tx.clone(),
             max_clients: config.max_clients,
+            active: clients.clone(),
         };
 
         tokio::spawn(async move {
@@ -56,6 +59,7 @@ impl Bridge {
         Ok(Self {
             incoming_rx,
             outgoing_tx,
+            clients,
         })
     }
 
@@ -76,6 +80,7 @@ impl Bridge {
     pub fn handle(&self) -> BridgeHandle {
         BridgeHandle {
             outgoing_tx: self.outgoing_tx.clone(),
+            clients: self.clients.clone(),
         }
     }
 }
@@ -83,6 +88,7 @@ impl Bridge {
 #[derive(Clone)]
 pub struct BridgeHandle {
     outgoing_tx: broadcast::Sender<DaemonMessage>,
+    clients: Arc<AtomicUsize>,
 }
 
 impl BridgeHandle {
@@ -95,6 +101,11 @@ impl BridgeHandle {
     pub fn subscribe(&self) -> broadcast::Receiver<DaemonMessage> {
         self.outgoing_tx.subscribe()
     }
+
+    /// Number of WebSocket clients currently connected
+    pub fn client_count(&self) -> usize {
+        self.clients.load(Ordering::SeqCst)
+    }
 }
 
 struct BridgeAcceptor {
@@ -102,11 +113,12 @@ struct BridgeAcceptor {
     incoming_tx: mpsc::Sender<ClientMessage>,
     outgoing_tx: broadcast::Sender<DaemonMessage>,
     max_clients: usize,
+    active: Arc<AtomicUsize>,
 }
 
 impl BridgeAcceptor {
     async fn run(self) -> Result<()> {
-        let active = Arc::new(AtomicUsize::new(0));
+        let active = self.active.clone();
 
         loop {
             let (stream, addr) = self.listener.accept().await?;
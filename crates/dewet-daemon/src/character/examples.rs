@@ -0,0 +1,115 @@
+//! Example dialogue: parses a card's `mes_example` into turns that are
+//! replayed to the response model as few-shot conversation
+
+/// One line of example dialogue
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExampleTurn {
+    User(String),
+    Character(String),
+}
+
+impl ExampleTurn {
+    fn push_line(&mut self, line: &str) {
+        let (ExampleTurn::User(text) | ExampleTurn::Character(text)) = self;
+        text.push('\n');
+        text.push_str(line);
+    }
+}
+
+const USER_PREFIXES: [&str; 4] = ["{{user}}:", "<user>:", "user:", "you:"];
+const CHAR_PREFIXES: [&str; 3] = ["{{char}}:", "<bot>:", "<char>:"];
+
+/// `line` without `prefix`, matched case-insensitively
+fn strip_prefix_ci<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    line.get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| line[prefix.len()..].trim())
+}
+
+/// Turns of each `<START>`-separated conversation in `mes_example`.
+/// Lines start a turn with `{{user}}:` or `{{char}}:` (or the character's
+/// name); other lines continue the turn before them. Consecutive turns by
+/// the same speaker are merged.
+pub fn parse(mes_example: &str, name: &str) -> Vec<Vec<ExampleTurn>> {
+    let name_prefix = format!("{name}:");
+    let mut blocks = Vec::new();
+    for block in split_blocks(mes_example) {
+        let mut turns: Vec<ExampleTurn> = Vec::new();
+        for line in block.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let user = USER_PREFIXES
+                .iter()
+                .find_map(|prefix| strip_prefix_ci(line, prefix));
+            let character = CHAR_PREFIXES
+                .into_iter()
+                .chain([name_prefix.as_str()])
+                .find_map(|prefix| strip_prefix_ci(line, prefix));
+            let turn = match (user, character) {
+                (Some(text), _) => ExampleTurn::User(substitute(text, name)),
+                (None, Some(text)) => ExampleTurn::Character(substitute(text, name)),
+                (None, None) => {
+                    if let Some(last) = turns.last_mut() {
+                        last.push_line(&substitute(line, name));
+                    }
+                    continue;
+                }
+            };
+            match (turns.last_mut(), turn) {
+                (Some(ExampleTurn::User(last)), ExampleTurn::User(text))
+                | (Some(ExampleTurn::Character(last)), ExampleTurn::Character(text)) => {
+                    last.push('\n');
+                    last.push_str(&text);
+                }
+                (_, turn) => turns.push(turn),
+            }
+        }
+        if !turns.is_empty() {
+            blocks.push(turns);
+        }
+    }
+    blocks
+}
+
+/// `text` split on `<START>` markers (any case)
+fn split_blocks(text: &str) -> Vec<&str> {
+    const START: &str = "<start>";
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(at) = rest.to_ascii_lowercase().find(START) {
+        blocks.push(&rest[..at]);
+        rest = &rest[at + START.len()..];
+    }
+    blocks.push(rest);
+    blocks
+}
+
+/// Fill in `{{char}}` and `{{user}}` placeholders
+fn substitute(text: &str, name: &str) -> String {
+    text.replace("{{char}}", name)
+        .replace("{{user}}", "User")
+        .replace("<BOT>", name)
+        .replace("<USER>", "User")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mes_example = "<START>\n{{user}}: Taking a break.\n{{char}}: Good call.\n\
+            Grab a coffee, {{user}}.\n<START>\nLyra: Copy-pasting again?\nLyra: Need help?";
+        assert_eq!(
+            parse(mes_example, "Lyra"),
+            vec![
+                vec![
+                    ExampleTurn::User("Taking a break.".into()),
+                    ExampleTurn::Character("Good call.\nGrab a coffee, User.".into()),
+                ],
+                vec![ExampleTurn::Character(
+                    "Copy-pasting again?\nNeed help?".into()
+                )],
+            ]
+        );
+        assert!(parse("", "Lyra").is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! Lorebook activation: picks the `character_book` entries whose keys come up
+//! in recent chat or on screen, within a token budget
+
+use super::LoreEntry;
+
+/// Rough tokens in `text`, at about four characters each
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Whether `key` appears in `text` as a whole word or phrase
+fn contains_key(text: &str, key: &str, case_sensitive: bool) -> bool {
+    let key = key.trim();
+    if key.is_empty() {
+        return false;
+    }
+    let (text, key) = if case_sensitive {
+        (text.to_string(), key.to_string())
+    } else {
+        (text.to_lowercase(), key.to_lowercase())
+    };
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    text.match_indices(key.as_str()).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + key.len()..].chars().next();
+        !(is_word(before) && key.starts_with(char::is_alphanumeric))
+            && !(is_word(after) && key.ends_with(char::is_alphanumeric))
+    })
+}
+
+impl LoreEntry {
+    /// Whether the entry applies to `text`
+    pub fn matches(&self, text: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.constant {
+            return true;
+        }
+        let found = |keys: &[String]| {
+            keys.iter()
+                .any(|key| contains_key(text, key, self.case_sensitive))
+        };
+        found(&self.keys)
+            && (!self.selective || self.secondary_keys.is_empty() || found(&self.secondary_keys))
+    }
+}
+
+/// Entries of `book` that match `text`, highest priority first until
+/// `token_budget` runs out, then in insertion order
+pub fn activate<'a>(book: &'a [LoreEntry], text: &str, token_budget: usize) -> Vec<&'a LoreEntry> {
+    let mut matched: Vec<&LoreEntry> = book.iter().filter(|entry| entry.matches(text)).collect();
+    matched.sort_by_key(|entry| -entry.priority);
+
+    let mut remaining = token_budget;
+    let mut active: Vec<&LoreEntry> = matched
+        .into_iter()
+        .filter(|entry| {
+            let tokens = estimate_tokens(&entry.content);
+            let fits = tokens <= remaining;
+            if fits {
+                remaining -= tokens;
+            }
+            fits
+        })
+        .collect();
+    active.sort_by_key(|entry| entry.insertion_order);
+    active
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(keys: &[&str], content: &str) -> LoreEntry {
+        LoreEntry {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            ..LoreEntry::new(content)
+        }
+    }
+
+    #[test]
+    fn test_activate() {
+        let book = vec![
+            LoreEntry {
+                insertion_order: 2,
+                ..entry(&["Ferris"], "Ferris is the Rust mascot, a crab.")
+            },
+            LoreEntry {
+                insertion_order: 1,
+                priority: 5,
+                ..entry(
+                    &["borrow checker"],
+                    "Lyra thinks of the borrow checker as a strict librarian.",
+                )
+            },
+            LoreEntry {
+                selective: true,
+                secondary_keys: vec!["coffee".into()],
+                ..entry(&["break"], "Orion takes his coffee black.")
+            },
+            LoreEntry {
+                enabled: false,
+                ..entry(&["ferris"], "Disabled")
+            },
+        ];
+
+        let text = "error[E0502]: the borrow checker says no. Time for a break, where's ferris?";
+        let active: Vec<&str> = activate(&book, text, 100)
+            .iter()
+            .map(|entry| entry.content.as_str())
+            .collect();
+        assert_eq!(
+            active,
+            [
+                "Lyra thinks of the borrow checker as a strict librarian.",
+                "Ferris is the Rust mascot, a crab.",
+            ]
+        );
+
+        // Keys match whole words only, and secondary keys gate selective entries
+        assert!(activate(&book, "breakpoint set; grab a coffee", 100).is_empty());
+        assert_eq!(activate(&book, "coffee break", 100).len(), 1);
+
+        // Over budget, the higher priority entry stays
+        let active = activate(&book, text, 15);
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].priority, 5);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod examples;
+pub mod lore;
+pub mod mood;
+pub mod relationship;
 pub mod spec;
 pub mod state;
+pub mod style;
 
+pub use mood::{Mood, MoodEvent};
 pub use spec::{CharacterSpec, LoreEntry};
 pub use state::{CharacterState, LoadedCharacter};
+pub use style::ResponseStyle;
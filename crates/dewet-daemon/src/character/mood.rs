@@ -0,0 +1,274 @@
+//! Mood: how a companion feels, moved by what happens in the conversation
+//! and fading back to neutral. It shortens or stretches their cooldown, is
+//! given to the arbiter and response prompts, and is how they look and
+//! sound when the arbiter doesn't suggest a mood.
+
+use std::time::{Duration, Instant};
+
+use crate::bridge::ChatPacket;
+
+/// Below this intensity a mood reads as neutral
+const FADED: f32 = 0.15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mood {
+    Neutral,
+    Happy,
+    Excited,
+    Sad,
+    Annoyed,
+    Thinking,
+    Curious,
+    Calm,
+}
+
+impl Mood {
+    /// Names as used by the puppet, TTS prosody and the arbiter schema
+    pub const NAMES: &[&str] = &[
+        "neutral", "happy", "excited", "sad", "annoyed", "thinking", "curious", "calm",
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mood::Neutral => "neutral",
+            Mood::Happy => "happy",
+            Mood::Excited => "excited",
+            Mood::Sad => "sad",
+            Mood::Annoyed => "annoyed",
+            Mood::Thinking => "thinking",
+            Mood::Curious => "curious",
+            Mood::Calm => "calm",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let mood = match name.trim().to_lowercase().as_str() {
+            "neutral" => Mood::Neutral,
+            "happy" => Mood::Happy,
+            "excited" => Mood::Excited,
+            "sad" => Mood::Sad,
+            "annoyed" => Mood::Annoyed,
+            "thinking" => Mood::Thinking,
+            "curious" => Mood::Curious,
+            "calm" => Mood::Calm,
+            _ => return None,
+        };
+        Some(mood)
+    }
+}
+
+/// Something that happened to a companion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoodEvent {
+    /// The user replied to them
+    Answered,
+    /// Their last reply went unanswered
+    Ignored,
+    /// The user said the wake word to them
+    Woken,
+    /// Something changed on screen
+    SceneChange,
+    /// They spoke in this mood
+    Spoke(Mood),
+}
+
+impl MoodEvent {
+    /// The mood the event pulls towards, and how hard
+    fn pull(self) -> (Mood, f32) {
+        match self {
+            MoodEvent::Answered => (Mood::Happy, 0.4),
+            MoodEvent::Ignored => (Mood::Sad, 0.3),
+            MoodEvent::Woken => (Mood::Excited, 0.4),
+            MoodEvent::SceneChange => (Mood::Curious, 0.25),
+            MoodEvent::Spoke(mood) => (mood, 0.5),
+        }
+    }
+}
+
+/// A companion's mood and how strongly they feel it
+#[derive(Debug, Clone)]
+pub struct MoodState {
+    mood: Mood,
+    intensity: f32,
+    since: Instant,
+}
+
+impl Default for MoodState {
+    fn default() -> Self {
+        Self {
+            mood: Mood::Neutral,
+            intensity: 0.0,
+            since: Instant::now(),
+        }
+    }
+}
+
+impl MoodState {
+    /// The mood at `now`, with its intensity halving every `half_life`
+    pub fn current(&self, now: Instant, half_life: Duration) -> (Mood, f32) {
+        let elapsed = now.saturating_duration_since(self.since).as_secs_f32();
+        let intensity = self.intensity * 0.5f32.powf(elapsed / half_life.as_secs_f32().max(1.0));
+        if intensity < FADED {
+            (Mood::Neutral, 0.0)
+        } else {
+            (self.mood, intensity)
+        }
+    }
+
+    /// Move the mood for `event`: the same mood deepens, a stronger pull
+    /// takes over, and a weaker (or neutral) one just takes the edge off
+    pub fn apply(&mut self, event: MoodEvent, now: Instant, half_life: Duration) {
+        let (mood, intensity) = self.current(now, half_life);
+        let (target, pull) = event.pull();
+        let (mood, intensity) = if target == Mood::Neutral {
+            (mood, (intensity - pull / 2.0).max(0.0))
+        } else if target == mood {
+            (mood, (intensity + pull).min(1.0))
+        } else if pull >= intensity {
+            (target, pull)
+        } else {
+            (mood, intensity - pull / 2.0)
+        };
+        self.mood = mood;
+        self.intensity = intensity;
+        self.since = now;
+    }
+
+    /// Multiplier for the companion's cooldown: eager moods speak up sooner,
+    /// withdrawn ones hold back
+    pub fn cooldown_scale(&self, now: Instant, half_life: Duration) -> f32 {
+        let (mood, intensity) = self.current(now, half_life);
+        match mood {
+            Mood::Excited => 1.0 - 0.5 * intensity,
+            Mood::Happy | Mood::Curious => 1.0 - 0.25 * intensity,
+            Mood::Sad | Mood::Calm => 1.0 + 0.5 * intensity,
+            Mood::Annoyed => 1.0 + 0.25 * intensity,
+            Mood::Neutral | Mood::Thinking => 1.0,
+        }
+    }
+
+    /// The mood for a prompt, e.g. "a little happy"; None when neutral
+    pub fn describe(&self, now: Instant, half_life: Duration) -> Option<String> {
+        let (mood, intensity) = self.current(now, half_life);
+        if mood == Mood::Neutral {
+            return None;
+        }
+        let degree = if intensity >= 0.7 {
+            "very"
+        } else if intensity >= 0.4 {
+            "fairly"
+        } else {
+            "a little"
+        };
+        Some(format!("{degree} {}", mood.as_str()))
+    }
+}
+
+/// Turns chat into `Answered` and `Ignored` events, each reply counted once
+#[derive(Debug, Default)]
+pub struct ChatMoods {
+    /// Timestamp of the last user message that answered someone
+    answered: Option<i64>,
+    /// Timestamp of the last reply found ignored
+    ignored: Option<i64>,
+}
+
+impl ChatMoods {
+    /// Events in `chat` (oldest first) not seen before, with the companion
+    /// each happened to. A companion's reply counts as ignored once it has
+    /// been the last message for `ignored_after` seconds.
+    pub fn events(
+        &mut self,
+        chat: &[ChatPacket],
+        now: i64,
+        ignored_after: i64,
+    ) -> Vec<(String, MoodEvent)> {
+        let mut events = Vec::new();
+        let Some(last) = chat.last() else {
+            return events;
+        };
+        if last.sender == "user" {
+            let answered = chat[..chat.len() - 1]
+                .iter()
+                .rev()
+                .find(|p| p.sender != "user");
+            if let Some(reply) = answered.filter(|_| self.answered != Some(last.timestamp)) {
+                self.answered = Some(last.timestamp);
+                events.push((reply.sender.clone(), MoodEvent::Answered));
+            }
+        } else if now - last.timestamp >= ignored_after && self.ignored != Some(last.timestamp) {
+            self.ignored = Some(last.timestamp);
+            events.push((last.sender.clone(), MoodEvent::Ignored));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::MemoryTier;
+
+    #[test]
+    fn test_mood_transitions_and_decay() {
+        let half_life = Duration::from_secs(600);
+        let start = Instant::now();
+        let mut state = MoodState::default();
+        assert_eq!(state.current(start, half_life), (Mood::Neutral, 0.0));
+
+        state.apply(MoodEvent::Answered, start, half_life);
+        state.apply(MoodEvent::Answered, start, half_life);
+        assert_eq!(state.current(start, half_life), (Mood::Happy, 0.8));
+        assert_eq!(
+            state.describe(start, half_life).as_deref(),
+            Some("very happy")
+        );
+        assert!(state.cooldown_scale(start, half_life) < 1.0);
+
+        // A weaker pull only takes the edge off
+        state.apply(MoodEvent::Ignored, start, half_life);
+        let (mood, intensity) = state.current(start, half_life);
+        assert_eq!(mood, Mood::Happy);
+        assert!((intensity - 0.65).abs() < 1e-6);
+
+        // Two half-lives later it has faded to a little happy, then neutral
+        let later = start + half_life * 2;
+        assert_eq!(
+            state.describe(later, half_life).as_deref(),
+            Some("a little happy")
+        );
+        let much_later = start + half_life * 3;
+        assert_eq!(state.current(much_later, half_life).0, Mood::Neutral);
+
+        state.apply(MoodEvent::Ignored, much_later, half_life);
+        assert_eq!(state.current(much_later, half_life).0, Mood::Sad);
+        assert!(state.cooldown_scale(much_later, half_life) > 1.0);
+    }
+
+    #[test]
+    fn test_chat_moods() {
+        let packet = |sender: &str, timestamp: i64| ChatPacket {
+            sender: sender.into(),
+            content: String::new(),
+            timestamp,
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id: None,
+        };
+        let mut moods = ChatMoods::default();
+        let chat = vec![packet("lyra", 100), packet("user", 110)];
+        assert_eq!(
+            moods.events(&chat, 111, 300),
+            [("lyra".to_string(), MoodEvent::Answered)]
+        );
+        assert!(moods.events(&chat, 112, 300).is_empty());
+
+        let chat = vec![packet("user", 110), packet("orion", 120)];
+        assert!(moods.events(&chat, 200, 300).is_empty());
+        assert_eq!(
+            moods.events(&chat, 420, 300),
+            [("orion".to_string(), MoodEvent::Ignored)]
+        );
+        assert!(moods.events(&chat, 500, 300).is_empty());
+    }
+}
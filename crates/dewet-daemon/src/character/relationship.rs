@@ -0,0 +1,99 @@
+//! How well a companion gets on with the user: `relationship_score`, from 0
+//! (strained) to 1 (close), moves with the sentiment of the user's replies
+//! to them. It colors the companion's tone and how soon they speak up again.
+
+/// Words that make a reply warmer
+const POSITIVE: &[&str] = &[
+    "amazing", "appreciate", "awesome", "brilliant", "cool", "exactly", "fantastic", "fun",
+    "glad", "good", "great", "haha", "helpful", "lol", "love", "neat", "nice", "perfect",
+    "thank", "thanks", "thx", "wonderful", "yes",
+];
+
+/// Words that make a reply cooler
+const NEGATIVE: &[&str] = &[
+    "annoying", "bad", "boring", "dumb", "enough", "hate", "irritating", "leave", "quiet",
+    "shut", "stop", "stupid", "terrible", "ugh", "useless", "worse", "worst", "wrong",
+];
+
+/// Words that flip the sentiment of the next couple of words
+const NEGATIONS: &[&str] = &[
+    "don't", "dont", "isn't", "isnt", "never", "not", "wasn't", "wasnt",
+];
+
+/// How positive `text` is, from -1 to 1, by counting sentiment words; 0
+/// when it has none
+pub fn sentiment(text: &str) -> f32 {
+    let words: Vec<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let (mut positive, mut negative) = (0, 0);
+    for (i, word) in words.iter().enumerate() {
+        let polarity = if POSITIVE.contains(&word.as_str()) {
+            1
+        } else if NEGATIVE.contains(&word.as_str()) {
+            -1
+        } else {
+            continue;
+        };
+        let negated = words[i.saturating_sub(2)..i]
+            .iter()
+            .any(|w| NEGATIONS.contains(&w.as_str()));
+        if (polarity > 0) != negated {
+            positive += 1;
+        } else {
+            negative += 1;
+        }
+    }
+    if positive + negative == 0 {
+        return 0.0;
+    }
+    (positive - negative) as f32 / (positive + negative) as f32
+}
+
+/// How the companion should treat the user at `score`, for the response
+/// prompt; None in the middle of the range
+pub fn describe(score: f32) -> Option<&'static str> {
+    if score >= 0.8 {
+        Some("You and the user get on very well; be warm and familiar.")
+    } else if score >= 0.65 {
+        Some("You and the user get on well; be friendly and relaxed.")
+    } else if score <= 0.2 {
+        Some("The user has been short with you lately; keep it brief and give them space.")
+    } else if score <= 0.35 {
+        Some("Things with the user have been a little cool lately; don't push.")
+    } else {
+        None
+    }
+}
+
+/// Multiplier for the companion's cooldown: 1 at the neutral 0.5, down to
+/// half when close and up to half again when strained
+pub fn cooldown_scale(score: f32) -> f32 {
+    1.5 - score.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentiment() {
+        assert_eq!(sentiment("thanks, that's perfect"), 1.0);
+        assert_eq!(sentiment("ugh, stop it"), -1.0);
+        assert_eq!(sentiment("that's not helpful"), -1.0);
+        assert_eq!(sentiment("nice, but wrong"), 0.0);
+        assert_eq!(sentiment("brb, getting coffee"), 0.0);
+    }
+
+    #[test]
+    fn test_score_shapes_tone_and_cooldown() {
+        assert!(describe(0.9).unwrap().contains("warm"));
+        assert_eq!(describe(0.5), None);
+        assert!(describe(0.1).unwrap().contains("brief"));
+        assert_eq!(cooldown_scale(0.5), 1.0);
+        assert!(cooldown_scale(0.9) < 1.0);
+        assert!(cooldown_scale(0.1) > 1.0);
+    }
+}
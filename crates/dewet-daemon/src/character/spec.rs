@@ -4,6 +4,9 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::ResponseStyle;
+use crate::config::{DirectorOverrides, VoiceTuning};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharacterSpec {
     pub id: String,
@@ -51,13 +54,32 @@ struct CharacterBookV2 {
 
 #[derive(Debug, Deserialize)]
 struct CharacterBookEntryV2 {
+    #[serde(default)]
+    keys: Vec<String>,
     content: String,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    insertion_order: i32,
+    #[serde(default)]
+    case_sensitive: bool,
+    #[serde(default)]
+    priority: i32,
     #[serde(default)]
     selective: bool,
     #[serde(default)]
+    secondary_keys: Vec<String>,
+    #[serde(default)]
+    constant: bool,
+    #[serde(default)]
+    #[allow(dead_code)]
     comment: Option<String>,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 impl CharacterSpec {
     pub fn from_file(path: &Path) -> Result<Self> {
         let raw = fs::read_to_string(path)
@@ -101,6 +123,14 @@ impl CharacterSpec {
                     .map(|entry| LoreEntry {
                         content: entry.content,
                         is_public: !entry.selective,
+                        keys: entry.keys,
+                        secondary_keys: entry.secondary_keys,
+                        selective: entry.selective,
+                        constant: entry.constant,
+                        case_sensitive: entry.case_sensitive,
+                        enabled: entry.enabled,
+                        insertion_order: entry.insertion_order,
+                        priority: entry.priority,
                     })
                     .collect()
             })
@@ -119,6 +149,42 @@ impl CharacterSpec {
         })
     }
 
+    /// Delivery adjustments from the `voice` extension, e.g.
+    /// `"voice": { "rate": 1.1, "pitch": 0.95 }`
+    pub fn voice_tuning(&self) -> VoiceTuning {
+        match self.extensions.get("voice") {
+            Some(voice) => serde_json::from_value(voice.clone()).unwrap_or_else(|err| {
+                tracing::warn!(?err, character = %self.id, "Ignoring invalid voice extension");
+                VoiceTuning::default()
+            }),
+            None => VoiceTuning::default(),
+        }
+    }
+
+    /// Cooldown settings from the `director` extension, e.g.
+    /// `"director": { "cooldown_after_speak_ms": 120000, "speak_on_scene_change": false }`
+    pub fn director_overrides(&self) -> DirectorOverrides {
+        match self.extensions.get("director") {
+            Some(director) => serde_json::from_value(director.clone()).unwrap_or_else(|err| {
+                tracing::warn!(?err, character = %self.id, "Ignoring invalid director extension");
+                DirectorOverrides::default()
+            }),
+            None => DirectorOverrides::default(),
+        }
+    }
+
+    /// Length and formatting rules from the `style` extension (see
+    /// `ResponseStyle`)
+    pub fn response_style(&self) -> ResponseStyle {
+        match self.extensions.get("style") {
+            Some(style) => serde_json::from_value(style.clone()).unwrap_or_else(|err| {
+                tracing::warn!(?err, character = %self.id, "Ignoring invalid style extension");
+                ResponseStyle::default()
+            }),
+            None => ResponseStyle::default(),
+        }
+    }
+
     pub fn load_dir(path: &Path) -> Result<Vec<Self>> {
         let mut specs = Vec::new();
         if !path.exists() {
@@ -153,10 +219,10 @@ impl CharacterSpec {
                 mes_example: "Lyra: Sooo... copy-pasting docstrings again? Need a cheerleader?"
                     .into(),
                 character_book: vec![LoreEntry {
-                    content:
-                        "Lyra has an archive of user successes and failures she gently recalls."
-                            .into(),
-                    is_public: true,
+                    keys: vec!["remember".into(), "last time".into()],
+                    ..LoreEntry::new(
+                        "Lyra has an archive of user successes and failures she gently recalls.",
+                    )
                 }],
                 extensions: HashMap::from([
                     ("interests".into(), Value::from(vec!["rust", "pixel art"])),
@@ -175,15 +241,59 @@ impl CharacterSpec {
                 mes_example: "Orion: Tests red, coffee empty. Want triage help or caffeine first?"
                     .into(),
                 character_book: vec![],
-                extensions: HashMap::new(),
+                extensions: HashMap::from([(
+                    "style".into(),
+                    serde_json::json!({ "max_sentences": 2, "no_markdown": true }),
+                )]),
             },
         ]
     }
 }
 
+/// A `character_book` entry, added to the response prompt when one of its
+/// keys comes up in chat or on screen (see `character::lore`)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoreEntry {
     pub content: String,
     #[serde(default)]
     pub is_public: bool,
+    /// Words or phrases that activate the entry
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// With `selective`, one of these must also appear
+    #[serde(default)]
+    pub secondary_keys: Vec<String>,
+    #[serde(default)]
+    pub selective: bool,
+    /// Always active, keys or not
+    #[serde(default)]
+    pub constant: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Position among active entries, lowest first
+    #[serde(default)]
+    pub insertion_order: i32,
+    /// Kept ahead of lower priorities when over the token budget
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl LoreEntry {
+    /// An enabled entry with no keys
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            is_public: true,
+            keys: Vec::new(),
+            secondary_keys: Vec::new(),
+            selective: false,
+            constant: false,
+            case_sensitive: false,
+            enabled: true,
+            insertion_order: 0,
+            priority: 0,
+        }
+    }
 }
@@ -1,18 +1,26 @@
 use std::time::{Duration, Instant};
 
+use chrono::Utc;
+
+use super::mood::MoodState;
+
 #[derive(Debug, Clone)]
 pub struct CharacterState {
-    pub current_mood: String,
+    pub mood: MoodState,
     pub last_spoke_at: Option<Instant>,
     pub relationship_score: f32,
+    /// When the companion asked the user something unprompted, within the
+    /// last hour
+    pub questions_asked: Vec<Instant>,
 }
 
 impl CharacterState {
     pub fn new() -> Self {
         Self {
-            current_mood: "neutral".into(),
+            mood: MoodState::default(),
             last_spoke_at: None,
             relationship_score: 0.5,
+            questions_asked: Vec::new(),
         }
     }
 
@@ -29,6 +37,43 @@ impl CharacterState {
     pub fn time_since_last_spoke(&self) -> Option<Duration> {
         self.last_spoke_at.map(|ts| ts.elapsed())
     }
+
+    /// Note an unprompted question, forgetting those over an hour old
+    pub fn record_question(&mut self) {
+        self.questions_asked
+            .retain(|at| at.elapsed() < Duration::from_secs(3600));
+        self.questions_asked.push(Instant::now());
+    }
+
+    /// Unprompted questions asked in the last hour
+    pub fn questions_this_hour(&self) -> usize {
+        self.questions_asked
+            .iter()
+            .filter(|at| at.elapsed() < Duration::from_secs(3600))
+            .count()
+    }
+
+    /// Move the relationship score by `sentiment` (-1 to 1) times `rate`
+    pub fn relate(&mut self, sentiment: f32, rate: f32) {
+        self.relationship_score = (self.relationship_score + sentiment * rate).clamp(0.0, 1.0);
+    }
+
+    /// The state as stored for `character_id`, with the mood as of now
+    pub fn to_stored(
+        &self,
+        character_id: &str,
+        half_life: Duration,
+    ) -> crate::storage::CharacterState {
+        let (mood, _) = self.mood.current(Instant::now(), half_life);
+        crate::storage::CharacterState {
+            character_id: character_id.to_string(),
+            current_mood: mood.as_str().to_string(),
+            last_spoke_at: self
+                .time_since_last_spoke()
+                .map(|ago| Utc::now().timestamp() - ago.as_secs() as i64),
+            relationship_score: self.relationship_score,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
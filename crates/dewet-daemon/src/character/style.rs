@@ -0,0 +1,222 @@
+//! Response style: per-character limits on length and formatting, from the
+//! card's `style` extension, e.g.
+//! `"style": { "max_sentences": 2, "no_markdown": true, "banned_phrases": ["As an AI"] }`.
+//! The rules are given to the response and audit models, and enforced on the
+//! reply afterwards since models don't reliably follow them.
+
+use serde::Deserialize;
+
+use crate::tts::split_sentences;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ResponseStyle {
+    /// Later sentences are dropped
+    pub max_sentences: Option<usize>,
+    /// Cut at the last sentence (or word) that fits
+    pub max_chars: Option<usize>,
+    /// Strip headings, lists, emphasis, code and links
+    pub no_markdown: bool,
+    pub no_emoji: bool,
+    /// Sentences containing these (case-insensitive) are dropped
+    pub banned_phrases: Vec<String>,
+}
+
+impl ResponseStyle {
+    /// The rules as instructions for a prompt, one per line
+    pub fn rules(&self) -> Option<String> {
+        let mut rules = Vec::new();
+        match (self.max_sentences, self.max_chars) {
+            (Some(1), _) => rules.push("Reply in a single sentence.".to_string()),
+            (Some(sentences), _) => rules.push(format!("Reply in at most {sentences} sentences.")),
+            (None, Some(chars)) => rules.push(format!("Keep replies under {chars} characters.")),
+            (None, None) => {}
+        }
+        if self.no_markdown {
+            rules.push(
+                "Plain spoken text only: no markdown, lists, headings or code blocks.".into(),
+            );
+        }
+        if self.no_emoji {
+            rules.push("No emoji.".into());
+        }
+        if !self.banned_phrases.is_empty() {
+            let phrases: Vec<String> = self
+                .banned_phrases
+                .iter()
+                .map(|phrase| format!("\"{phrase}\""))
+                .collect();
+            rules.push(format!("Never say {}.", phrases.join(", ")));
+        }
+        (!rules.is_empty()).then(|| rules.join("\n"))
+    }
+
+    /// `text` with the rules applied. Empty if every sentence was banned.
+    pub fn enforce(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.no_markdown {
+            text = strip_markdown(&text);
+        }
+        if self.no_emoji {
+            text = strip_emoji(&text);
+        }
+
+        let mut sentences: Vec<String> = split_sentences(&text)
+            .into_iter()
+            .filter(|sentence| !self.is_banned(sentence))
+            .collect();
+        if let Some(max) = self.max_sentences {
+            sentences.truncate(max.max(1));
+        }
+        let text = sentences.join(" ");
+        match self.max_chars {
+            Some(max) => truncate_at_sentence(&text, &sentences, max),
+            None => text,
+        }
+    }
+
+    fn is_banned(&self, sentence: &str) -> bool {
+        let sentence = sentence.to_lowercase();
+        self.banned_phrases
+            .iter()
+            .any(|phrase| !phrase.is_empty() && sentence.contains(&phrase.to_lowercase()))
+    }
+}
+
+/// The leading `sentences` of `text` that fit in `max` characters, or
+/// failing that its leading words
+fn truncate_at_sentence(text: &str, sentences: &[String], max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut kept = String::new();
+    for sentence in sentences {
+        let joined = if kept.is_empty() {
+            sentence.clone()
+        } else {
+            format!("{kept} {sentence}")
+        };
+        if joined.chars().count() > max {
+            break;
+        }
+        kept = joined;
+    }
+    if !kept.is_empty() {
+        return kept;
+    }
+    let mut words = String::new();
+    for word in text.split_whitespace() {
+        if words.chars().count() + word.chars().count() + 1 > max.saturating_sub(3) {
+            break;
+        }
+        if !words.is_empty() {
+            words.push(' ');
+        }
+        words.push_str(word);
+    }
+    format!(
+        "{}...",
+        words.trim_end_matches(|c: char| c.is_ascii_punctuation())
+    )
+}
+
+/// Markdown markup removed, keeping the words: list markers and headings
+/// become plain lines, `[text](url)` becomes `text`
+fn strip_markdown(text: &str) -> String {
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.trim_start_matches('#').trim_start_matches('>');
+            let line = ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|marker| line.trim_start().strip_prefix(marker))
+                .or_else(|| strip_numbering(line.trim_start()))
+                .unwrap_or(line);
+            strip_links(line).replace(['*', '`'], "").replace("__", "")
+        })
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+/// `line` without a leading "1. " or "2) "
+fn strip_numbering(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == line.len() {
+        return None;
+    }
+    rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "))
+}
+
+/// `[text](url)` replaced by `text`
+fn strip_links(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some((label, after)) = rest[open + 1..].split_once("](") else {
+            break;
+        };
+        let Some(close) = after.find(')') else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        out.push_str(label);
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D
+    )
+}
+
+/// Emoji removed, and the spaces they leave collapsed
+fn strip_emoji(text: &str) -> String {
+    let stripped: String = text.chars().filter(|c| !is_emoji(*c)).collect();
+    stripped
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enforce() {
+        let style = ResponseStyle {
+            max_sentences: Some(2),
+            no_markdown: true,
+            no_emoji: true,
+            banned_phrases: vec!["as an AI".into()],
+            ..Default::default()
+        };
+        let essay = "## Great question! 🎉\n\nAs an ai, I can't feel pride.\n\
+            - **Check** the [docs](https://docs.rs) first.\n- Then run `cargo test`.";
+        assert_eq!(
+            style.enforce(essay),
+            "Great question! Check the docs first."
+        );
+        assert!(style.enforce("As an AI, nope.").is_empty());
+
+        let short = ResponseStyle {
+            max_chars: Some(40),
+            ..Default::default()
+        };
+        assert_eq!(
+            short.enforce("That build is green. Ship it before lunch, honestly."),
+            "That build is green."
+        );
+        assert_eq!(
+            short.enforce("That build is green and everything is finally working"),
+            "That build is green and everything is..."
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Clipboard watching: text the user copies becomes an observation, since
+//! "the user just copied an error message" is a strong hint they want help
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::{config::ClipboardConfig, llm::RedactionMiddleware};
+
+/// Text copied to the clipboard, redacted and truncated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipboardEvent {
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+}
+
+/// Start watching if `[clipboard]` is enabled. Copies arrive on the returned
+/// channel, which is closed straight away when watching is off or
+/// unavailable.
+pub fn watch(config: &ClipboardConfig) -> mpsc::UnboundedReceiver<ClipboardEvent> {
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    if !config.enabled {
+        return event_rx;
+    }
+    match imp::spawn(config.clone(), event_tx) {
+        Ok(()) => info!("Clipboard watching enabled"),
+        Err(err) => warn!(?err, "Clipboard unavailable; clipboard watching disabled"),
+    }
+    event_rx
+}
+
+/// Turn newly copied text into an event: blank copies are dropped, secrets
+/// redacted, and long copies cut to `max_chars`
+#[cfg_attr(not(feature = "clipboard"), allow(dead_code))]
+fn to_event(
+    text: &str,
+    redactor: &RedactionMiddleware,
+    max_chars: usize,
+) -> Option<ClipboardEvent> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let mut text = text.to_string();
+    redactor.redact(&mut text);
+    if let Some((cut, _)) = text.char_indices().nth(max_chars) {
+        text.truncate(cut);
+        text.push('…');
+    }
+    Some(ClipboardEvent {
+        timestamp: Utc::now(),
+        text,
+    })
+}
+
+#[cfg(feature = "clipboard")]
+mod imp {
+    use std::thread;
+
+    use anyhow::{Result, anyhow};
+    use arboard::Clipboard;
+    use tokio::sync::mpsc;
+    use tracing::{debug, warn};
+
+    use super::{ClipboardEvent, to_event};
+    use crate::{config::ClipboardConfig, llm::RedactionMiddleware};
+
+    /// Polls on its own thread; the clipboard handle isn't `Send` everywhere
+    pub fn spawn(
+        config: ClipboardConfig,
+        event_tx: mpsc::UnboundedSender<ClipboardEvent>,
+    ) -> Result<()> {
+        let mut clipboard = Clipboard::new().map_err(|e| anyhow!("{}", e))?;
+        // Whatever was copied before startup isn't news
+        let mut last = clipboard.get_text().ok();
+        drop(clipboard);
+
+        thread::Builder::new()
+            .name("clipboard".into())
+            .spawn(move || {
+                let mut clipboard = match Clipboard::new() {
+                    Ok(clipboard) => clipboard,
+                    Err(err) => {
+                        warn!(%err, "Failed to open clipboard");
+                        return;
+                    }
+                };
+                let redactor = RedactionMiddleware::new(&config.redact_patterns);
+                loop {
+                    thread::sleep(config.poll_interval());
+                    // Images and other non-text contents read as errors
+                    let Ok(text) = clipboard.get_text() else {
+                        continue;
+                    };
+                    if last.as_ref() == Some(&text) {
+                        continue;
+                    }
+                    let event = to_event(&text, &redactor, config.max_chars);
+                    last = Some(text);
+                    if let Some(event) = event {
+                        debug!(chars = event.text.chars().count(), "Clipboard changed");
+                        if event_tx.send(event).is_err() {
+                            break;
+                        }
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+mod imp {
+    use anyhow::{Result, anyhow};
+    use tokio::sync::mpsc;
+
+    use super::ClipboardEvent;
+    use crate::config::ClipboardConfig;
+
+    pub fn spawn(
+        _config: ClipboardConfig,
+        _event_tx: mpsc::UnboundedSender<ClipboardEvent>,
+    ) -> Result<()> {
+        Err(anyhow!(
+            "[clipboard] is enabled but the daemon was built without the `clipboard` feature"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_event_redacts_and_truncates() {
+        let redactor = RedactionMiddleware::new(&[r"sk-[A-Za-z0-9]+"]);
+        let event = to_event("  error: bad key sk-abc123\n", &redactor, 100).unwrap();
+        assert_eq!(event.text, "error: bad key [REDACTED]");
+
+        let event = to_event("ééééé", &redactor, 3).unwrap();
+        assert_eq!(event.text, "ééé…");
+
+        assert!(to_event(" \n\t", &redactor, 100).is_none());
+    }
+}
@@ -1,7 +1,7 @@
-use std::{env, fs, path::Path, time::Duration};
+use std::{collections::HashMap, env, fs, path::Path, time::Duration};
 
-use anyhow::{Context, Result};
-use serde::Deserialize;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AppConfig {
@@ -12,6 +12,16 @@ pub struct AppConfig {
     pub director: DirectorConfig,
     pub llm: LlmConfig,
     pub tts: TtsConfig,
+    #[serde(default)]
+    pub stt: SttConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub idle: IdleConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub focus: FocusConfig,
 }
 
 impl AppConfig {
@@ -40,6 +50,10 @@ impl AppConfig {
             .with_context(|| format!("failed to read config file {:?}", path))?;
         let config: Self =
             toml::from_str(&contents).with_context(|| format!("invalid config: {:?}", path))?;
+        config
+            .llm
+            .validate()
+            .with_context(|| format!("invalid config: {:?}", path))?;
         Ok(config)
     }
 }
@@ -54,6 +68,11 @@ impl Default for AppConfig {
             director: DirectorConfig::default(),
             llm: LlmConfig::default(),
             tts: TtsConfig::default(),
+            stt: SttConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            idle: IdleConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            focus: FocusConfig::default(),
         }
     }
 }
@@ -88,10 +107,187 @@ impl Default for BridgeConfig {
 pub struct VisionConfig {
     #[serde(default = "VisionConfig::default_capture_interval_ms")]
     pub capture_interval_ms: u64,
+    /// Frames changing at least this much are announced as scene changes
     #[serde(default = "VisionConfig::default_diff_threshold")]
     pub diff_threshold: f32,
     #[serde(default = "VisionConfig::default_max_history")]
     pub max_history: usize,
+    /// Skip the VLA and arbiter while the frame's perceptual hash matches
+    /// this many previous frames; 0 disables
+    #[serde(default = "VisionConfig::default_duplicate_frames")]
+    pub duplicate_frames: usize,
+    /// Most bits (of 64) two hashes may differ by and still match
+    #[serde(default = "VisionConfig::default_hash_distance")]
+    pub hash_distance: u32,
+    /// Screen to capture: "primary", "all" (stitched), "focused" (the one
+    /// with the focused window), or a monitor's name or index
+    #[serde(default)]
+    pub monitor: MonitorSelection,
+    /// Capture only this window instead of the desktop; can be changed at
+    /// runtime with `set_capture_window`
+    #[serde(default)]
+    pub window: Option<WindowTarget>,
+    /// Read on-screen text into observations; disabled when absent
+    #[serde(default)]
+    pub ocr: Option<OcrConfig>,
+    /// Windows and areas hidden from every frame
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    /// Add a webcam panel to the composite; disabled when absent
+    #[serde(default)]
+    pub webcam: Option<WebcamConfig>,
+    /// Track keyboard and mouse rates and the cursor, shown on the DESKTOP
+    /// panel (needs the `input-activity` feature)
+    #[serde(default)]
+    pub input_activity: bool,
+    /// Shrink frames right after capture; full size when absent
+    #[serde(default)]
+    pub scale: Option<CaptureScaleConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorSelection {
+    Mode(MonitorMode),
+    Index(usize),
+    Name(String),
+}
+
+impl Default for MonitorSelection {
+    fn default() -> Self {
+        MonitorSelection::Mode(MonitorMode::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MonitorMode {
+    #[default]
+    Primary,
+    /// Every monitor, stitched by desktop position
+    All,
+    /// Follows the focused window between monitors
+    Focused,
+}
+
+/// OCR with the Tesseract CLI (https://github.com/tesseract-ocr/tesseract)
+#[derive(Debug, Clone, Deserialize)]
+pub struct OcrConfig {
+    /// Path to the tesseract executable (looked up on PATH by default)
+    #[serde(default = "OcrConfig::default_binary")]
+    pub binary: String,
+    /// Tesseract language codes, e.g. "eng" or "eng+deu"
+    #[serde(default = "OcrConfig::default_language")]
+    pub language: String,
+    /// Frames that changed less than this (see `diff_threshold`) keep the
+    /// previous text instead of being read again
+    #[serde(default = "OcrConfig::default_min_diff")]
+    pub min_diff: f32,
+    /// Longest text passed on to prompts, in characters
+    #[serde(default = "OcrConfig::default_max_chars")]
+    pub max_chars: usize,
+}
+
+impl OcrConfig {
+    fn default_binary() -> String {
+        "tesseract".into()
+    }
+    fn default_language() -> String {
+        "eng".into()
+    }
+    fn default_min_diff() -> f32 {
+        0.02
+    }
+    fn default_max_chars() -> usize {
+        2000
+    }
+}
+
+/// A window to capture, by title and/or app. When both are given both must
+/// match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct WindowTarget {
+    /// Regex searched for in the window title
+    #[serde(default)]
+    pub title: Option<String>,
+    /// App name or id (e.g. "obs" or "org.mozilla.firefox"), ignoring case
+    #[serde(default)]
+    pub app: Option<String>,
+}
+
+/// Frame size limits, applied before privacy masking, OCR, or compositing
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CaptureScaleConfig {
+    /// Larger frames shrink to fit, keeping their aspect ratio
+    #[serde(default)]
+    pub max_width: Option<u32>,
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub filter: ScaleFilter,
+}
+
+/// Resampling filter for `CaptureScaleConfig`, fastest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleFilter {
+    Nearest,
+    #[default]
+    Triangle,
+    CatmullRom,
+    /// Sharpest text, slowest
+    Lanczos3,
+}
+
+/// Webcam frames for presence awareness (needs the `webcam` feature)
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebcamConfig {
+    /// Camera index, in the order the OS lists them
+    #[serde(default)]
+    pub device: u32,
+    /// Seconds between frames
+    #[serde(default = "WebcamConfig::default_interval_secs")]
+    pub interval_secs: f32,
+}
+
+impl WebcamConfig {
+    fn default_interval_secs() -> f32 {
+        1.0
+    }
+}
+
+/// What to hide before a frame reaches the composite, OCR, or any model
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PrivacyConfig {
+    /// Windows of these apps, by name or id ignoring case
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Windows whose title matches any of these regexes
+    #[serde(default)]
+    pub titles: Vec<String>,
+    /// Fixed areas in desktop coordinates
+    #[serde(default)]
+    pub regions: Vec<ScreenRegion>,
+    #[serde(default)]
+    pub mask: MaskStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ScreenRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskStyle {
+    /// Solid black
+    #[default]
+    Blank,
+    /// Blurred past reading, keeping the rough layout
+    Blur,
 }
 
 impl VisionConfig {
@@ -104,6 +300,12 @@ impl VisionConfig {
     fn default_max_history() -> usize {
         12
     }
+    fn default_duplicate_frames() -> usize {
+        2
+    }
+    fn default_hash_distance() -> u32 {
+        4
+    }
 
     pub fn capture_interval(&self) -> Duration {
         Duration::from_millis(self.capture_interval_ms)
@@ -116,6 +318,15 @@ impl Default for VisionConfig {
             capture_interval_ms: Self::default_capture_interval_ms(),
             diff_threshold: Self::default_diff_threshold(),
             max_history: Self::default_max_history(),
+            duplicate_frames: Self::default_duplicate_frames(),
+            hash_distance: Self::default_hash_distance(),
+            monitor: MonitorSelection::default(),
+            window: None,
+            ocr: None,
+            privacy: PrivacyConfig::default(),
+            webcam: None,
+            input_activity: false,
+            scale: None,
         }
     }
 }
@@ -137,6 +348,58 @@ pub struct ObservationConfig {
     /// Maximum messages to include in VLM context (hot + warm only)
     #[serde(default = "ObservationConfig::default_max_vlm_messages")]
     pub max_vlm_messages: usize,
+    /// Fill the composite's PREV panels from a rolling history of recent
+    /// frames instead of the screenshots companions last spoke about
+    #[serde(default)]
+    pub timeline: Option<TimelineConfig>,
+    /// Write every observation to disk, for replaying through the director
+    #[serde(default)]
+    pub recording: Option<RecordingConfig>,
+}
+
+/// Rolling history of downsampled frames
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimelineConfig {
+    /// Seconds between kept frames
+    #[serde(default = "TimelineConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds of history kept
+    #[serde(default = "TimelineConfig::default_span_secs")]
+    pub span_secs: u64,
+}
+
+impl TimelineConfig {
+    fn default_interval_secs() -> u64 {
+        10
+    }
+    fn default_span_secs() -> u64 {
+        120
+    }
+}
+
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: Self::default_interval_secs(),
+            span_secs: Self::default_span_secs(),
+        }
+    }
+}
+
+/// `[observation.recording]`: observations appended to a JSONL file as
+/// they are made, with images downscaled, for `cargo xtask replay`
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordingConfig {
+    pub path: String,
+    /// Composite and ARIAOS images wider than this are downscaled
+    #[serde(default = "RecordingConfig::default_max_width")]
+    pub max_width: u32,
+}
+
+impl RecordingConfig {
+    fn default_max_width() -> u32 {
+        1024
+    }
 }
 
 impl ObservationConfig {
@@ -165,25 +428,66 @@ impl Default for ObservationConfig {
             forget_threshold: Self::default_forget_threshold(),
             decay_rate: Self::default_decay_rate(),
             max_vlm_messages: Self::default_max_vlm_messages(),
+            timeline: None,
+            recording: None,
         }
     }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct StorageConfig {
+    /// A local file (`file:path`, created if missing) or a remote Turso
+    /// database (`libsql://...`, which needs a token)
     #[serde(default = "StorageConfig::default_url")]
     pub url: String,
     #[serde(default = "StorageConfig::default_auth_token_env")]
     pub auth_token_env: String,
+    /// Periodic copies of the database; disabled when absent
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    /// Whether companions share one memory or each keep their own
+    #[serde(default)]
+    pub memory_scope: MemoryScope,
+    /// Keep screenshots (and composites) companions spoke about; disabled
+    /// when absent
+    #[serde(default)]
+    pub snapshots: Option<SnapshotConfig>,
+    /// Periodic memory decay and pruning; disabled when absent
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceConfig>,
+    /// Chat inactivity that ends a conversation session
+    #[serde(default = "StorageConfig::default_session_idle_minutes")]
+    pub session_idle_minutes: u64,
+    /// Encrypt memory content before it is written; plaintext when absent
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    /// Where writes to an unreachable remote database wait until it is back
+    #[serde(default = "StorageConfig::default_offline_journal")]
+    pub offline_journal: String,
+    /// Learn a user profile from chat with the arbiter model; disabled when
+    /// absent
+    #[serde(default)]
+    pub profile: Option<ProfileConfig>,
 }
 
 impl StorageConfig {
+    /// `dewet/dewet.db` in the platform data dir, e.g.
+    /// `~/.local/share/dewet/dewet.db` on Linux
     fn default_url() -> String {
-        "libsql://dewet.turso.io".into()
+        let dir = dirs::data_dir().unwrap_or_else(|| std::path::PathBuf::from(".local"));
+        format!("file:{}", dir.join("dewet").join("dewet.db").display())
     }
     fn default_auth_token_env() -> String {
         "TURSO_AUTH_TOKEN".into()
     }
+    fn default_session_idle_minutes() -> u64 {
+        30
+    }
+    fn default_offline_journal() -> String {
+        let dir = dirs::data_dir().unwrap_or_else(|| std::path::PathBuf::from(".local"));
+        let path = dir.join("dewet").join("offline-journal.jsonl");
+        path.display().to_string()
+    }
 }
 
 impl Default for StorageConfig {
@@ -191,145 +495,1811 @@ impl Default for StorageConfig {
         Self {
             url: Self::default_url(),
             auth_token_env: Self::default_auth_token_env(),
+            backup: None,
+            maintenance: None,
+            memory_scope: MemoryScope::default(),
+            snapshots: None,
+            session_idle_minutes: Self::default_session_idle_minutes(),
+            encryption: None,
+            offline_journal: Self::default_offline_journal(),
+            profile: None,
         }
     }
 }
 
+/// Periodic extraction of facts about the user (name, timezone, habits,
+/// likes) from new chat
 #[derive(Debug, Clone, Deserialize)]
-pub struct DirectorConfig {
-    #[serde(default = "DirectorConfig::default_min_decision_interval_ms")]
-    pub min_decision_interval_ms: u64,
-    #[serde(default = "DirectorConfig::default_cooldown_after_speak_ms")]
-    pub cooldown_after_speak_ms: u64,
+pub struct ProfileConfig {
+    #[serde(default = "ProfileConfig::default_interval_minutes")]
+    pub interval_minutes: u64,
+    /// Most chat messages read per pass
+    #[serde(default = "ProfileConfig::default_max_messages")]
+    pub max_messages: usize,
 }
 
-impl DirectorConfig {
-    fn default_min_decision_interval_ms() -> u64 {
-        2000
+impl ProfileConfig {
+    fn default_interval_minutes() -> u64 {
+        30
     }
-    fn default_cooldown_after_speak_ms() -> u64 {
-        30_000
+    fn default_max_messages() -> usize {
+        100
     }
 
-    pub fn min_decision_interval(&self) -> Duration {
-        Duration::from_millis(self.min_decision_interval_ms)
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_minutes.max(1) * 60)
     }
+}
 
-    pub fn cooldown_after_speak(&self) -> Duration {
-        Duration::from_millis(self.cooldown_after_speak_ms)
+/// Downscaled JPEG copies of approved screenshots, kept in the database so
+/// the composite's PREV panels survive a restart
+#[derive(Debug, Clone, Deserialize)]
+pub struct SnapshotConfig {
+    #[serde(default = "SnapshotConfig::default_max_width")]
+    pub max_width: u32,
+    /// Snapshots kept of each kind; older ones are deleted
+    #[serde(default = "SnapshotConfig::default_keep")]
+    pub keep: usize,
+    /// Also keep the composite the models saw
+    #[serde(default)]
+    pub composites: bool,
+}
+
+impl SnapshotConfig {
+    fn default_max_width() -> u32 {
+        1280
+    }
+    fn default_keep() -> usize {
+        50
     }
 }
 
-impl Default for DirectorConfig {
-    fn default() -> Self {
-        Self {
-            min_decision_interval_ms: Self::default_min_decision_interval_ms(),
-            cooldown_after_speak_ms: Self::default_cooldown_after_speak_ms(),
-        }
+/// Which chat, episodes and notes a companion sees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryScope {
+    /// Every companion sees everything
+    #[default]
+    Shared,
+    /// A companion sees shared memories (user messages, screen changes) and
+    /// its own, but not other companions' replies, episodes or notes
+    PerCharacter,
+}
+
+/// Where the 32-byte storage key (base64) comes from: `key_env`, else the
+/// OS keyring entry `dewet`/`storage-key` when `keyring` is set
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncryptionConfig {
+    #[serde(default = "EncryptionConfig::default_key_env")]
+    pub key_env: String,
+    #[serde(default)]
+    pub keyring: bool,
+}
+
+impl EncryptionConfig {
+    fn default_key_env() -> String {
+        "DEWET_STORAGE_KEY".into()
     }
 }
 
-/// Configuration for a single model endpoint (provider + model name)
+/// Timestamped database snapshots (JSON exports for remote databases)
 #[derive(Debug, Clone, Deserialize)]
-pub struct ModelConfig {
-    pub provider: LlmProvider,
-    pub model: String,
+pub struct BackupConfig {
+    /// Defaults to `backups/` beside a local database, or in the data dir
+    #[serde(default)]
+    pub dir: Option<String>,
+    #[serde(default = "BackupConfig::default_interval_hours")]
+    pub interval_hours: f64,
+    /// Number of backups kept; older ones are deleted
+    #[serde(default = "BackupConfig::default_keep")]
+    pub keep: usize,
+}
+
+impl BackupConfig {
+    fn default_interval_hours() -> f64 {
+        24.0
+    }
+    fn default_keep() -> usize {
+        7
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(self.interval_hours.max(0.01) * 3600.0)
+    }
 }
 
+/// Episode importance decays each run; forgotten episodes and, optionally,
+/// old chat and arbiter decisions are deleted
 #[derive(Debug, Clone, Deserialize)]
-pub struct LlmConfig {
-    /// VLA (Vision-Language Analysis) - fast, cheap vision model for change detection
-    /// Runs most frequently, needs vision capability
-    pub vla: ModelConfig,
-    /// Arbiter - reasoning model for deciding who should speak
-    /// Text-only, benefits from strong logic/reasoning
-    pub arbiter: ModelConfig,
-    /// Response - creative writing model for generating character dialogue
-    /// Benefits from strong conversational/creative writing ability
-    pub response: ModelConfig,
-    /// Optional audit model for reviewing responses
+pub struct MaintenanceConfig {
+    #[serde(default = "MaintenanceConfig::default_interval_hours")]
+    pub interval_hours: f64,
+    /// Importance multiplier applied per run
+    #[serde(default = "MaintenanceConfig::default_decay_factor")]
+    pub decay_factor: f32,
+    /// Episodes younger than this keep their importance
+    #[serde(default = "MaintenanceConfig::default_decay_after_hours")]
+    pub decay_after_hours: i64,
+    /// Episodes below this importance are deleted
+    #[serde(default = "MaintenanceConfig::default_prune_below")]
+    pub prune_below: f32,
+    /// Delete chat messages older than this; kept forever when absent
     #[serde(default)]
-    pub audit: Option<ModelConfig>,
+    pub chat_max_age_days: Option<u32>,
+    /// Delete arbiter decisions older than this; kept forever when absent
+    #[serde(default)]
+    pub decision_max_age_days: Option<u32>,
 }
 
-impl Default for LlmConfig {
+impl MaintenanceConfig {
+    fn default_interval_hours() -> f64 {
+        24.0
+    }
+    fn default_decay_factor() -> f32 {
+        0.95
+    }
+    fn default_decay_after_hours() -> i64 {
+        24
+    }
+    fn default_prune_below() -> f32 {
+        0.05
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(self.interval_hours.max(0.01) * 3600.0)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DirectorConfig {
+    #[serde(default = "DirectorConfig::default_min_decision_interval_ms")]
+    pub min_decision_interval_ms: u64,
+    #[serde(default = "DirectorConfig::default_cooldown_after_speak_ms")]
+    pub cooldown_after_speak_ms: u64,
+    /// Let a significant screen change end a companion's cooldown early
+    #[serde(default = "DirectorConfig::default_speak_on_scene_change")]
+    pub speak_on_scene_change: bool,
+    /// How many questions a companion may ask the user per hour when not
+    /// answering them (0 for no limit)
+    #[serde(default = "DirectorConfig::default_questions_per_hour")]
+    pub questions_per_hour: u32,
+    /// Directory of prompt template overrides (files named like the built-ins)
+    #[serde(default = "DirectorConfig::default_prompts_dir")]
+    pub prompts_dir: String,
+    /// Stream the response so ARIAOS tool calls run before the reply finishes
+    #[serde(default = "DirectorConfig::default_stream_tool_calls")]
+    pub stream_tool_calls: bool,
+    /// Most response model calls per reply: a reply that is only tool calls
+    /// gets their results and another call, so a companion can look
+    /// something up and then answer (1 takes the first response as is)
+    #[serde(default = "DirectorConfig::default_max_tool_iterations")]
+    pub max_tool_iterations: usize,
+    /// Stream reply text to clients as `speak_delta`s before the final `speak`.
+    /// Skipped when an audit model may still change the reply.
+    #[serde(default = "DirectorConfig::default_stream_text")]
+    pub stream_text: bool,
+    /// Also show vision models a full-resolution close-up of small changes,
+    /// like a notification, that are unreadable at composite scale
+    #[serde(default = "DirectorConfig::default_close_up")]
+    pub close_up: bool,
+    /// How screenshots are encoded for vision models; lossy formats cut
+    /// payloads (and upload latency) several times over
+    #[serde(default)]
+    pub image_format: ImageEncoding,
+    /// JPEG/WebP quality, 1 to 100
+    #[serde(default = "DirectorConfig::default_image_quality")]
+    pub image_quality: u8,
+    /// Run change detection and arbitration as separate calls or as one
+    #[serde(default)]
+    pub pipeline_mode: PipelineMode,
+    /// Keep reasoning-model chain of thought in prompt logs
+    #[serde(default)]
+    pub log_reasoning: bool,
+    /// Evaluate frames that changed past `[vision] diff_threshold` even
+    /// within `min_decision_interval_ms` of the last decision
+    #[serde(default)]
+    pub evaluate_on_scene_change: bool,
+    /// Treat an arbiter pick it rates less confident than this (0 to 1) as
+    /// a pass; 0 accepts every pick
+    #[serde(default)]
+    pub min_confidence: f32,
+    /// Give an unanswered user message that calls a companion by name
+    /// ("Orion, what do you think?") to that companion without asking the
+    /// arbiter
+    #[serde(default = "DirectorConfig::default_direct_address")]
+    pub direct_address: bool,
+    /// How many times in a row another companion may answer a companion's
+    /// fresh reply within one tick (0 keeps companions to isolated turns)
+    #[serde(default)]
+    pub max_chain: usize,
+    /// Most tokens of `character_book` lore added to a response prompt
+    /// (0 turns lore off)
+    #[serde(default = "DirectorConfig::default_lore_token_budget")]
+    pub lore_token_budget: usize,
+    /// How many recent chat messages are scanned for lore keys, along
+    /// with the text on screen
+    #[serde(default = "DirectorConfig::default_lore_scan_depth")]
+    pub lore_scan_depth: usize,
+    /// Older memories looked up for the responding companion
+    #[serde(default)]
+    pub recall: RecallConfig,
+    /// How many times the audit model may revise a reply and review the
+    /// revision before its last revision is used as is
+    #[serde(default = "DirectorConfig::default_audit_max_revisions")]
+    pub audit_max_revisions: usize,
+    /// What the companions are in the middle of with the user
+    #[serde(default)]
+    pub goals: GoalsConfig,
+    /// How companions' moods move and fade
+    #[serde(default)]
+    pub mood: MoodConfig,
+    /// How companions' relationships with the user move
+    #[serde(default)]
+    pub relationship: RelationshipConfig,
+    /// A one-line afterthought written after each reply
+    #[serde(default)]
+    pub reflection: ReflectionConfig,
+    /// Cheap checks that keep quiet ticks from reaching the vision models
+    #[serde(default)]
+    pub screening: ScreeningConfig,
+    /// Reuse of "no significant change" verdicts for an unchanged screen
+    #[serde(default)]
+    pub vla_cache: VlaCacheConfig,
+    /// What the companions already commented on unprompted
+    #[serde(default)]
+    pub topics: TopicsConfig,
+    /// The user's local date and time in prompts
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// Which pipeline stages run, for isolating one while debugging
+    #[serde(default)]
+    pub stages: StagesConfig,
+}
+
+/// `[director.recall]`: before a companion replies, find episodes from
+/// before the chat window that relate to the current topic
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecallConfig {
+    pub enabled: bool,
+    /// Most memories shown to the companion
+    pub max_memories: usize,
+    /// How closely a memory must match the topic, 0 to 1: cosine similarity
+    /// with an embedding model, else a third per topic word it shares (or
+    /// all of it, sharing every word of a short topic)
+    pub min_relevance: f32,
+    /// Age at which a memory's recency counts half
+    pub recency_half_life_hours: f32,
+}
+
+impl Default for RecallConfig {
     fn default() -> Self {
-        let default_provider = LlmProvider::LmStudio {
-            endpoint: "http://127.0.0.1:1234".into(),
-        };
         Self {
-            vla: ModelConfig {
-                provider: default_provider.clone(),
-                model: "qwen2.5-vl-7b-instruct".into(),
-            },
-            arbiter: ModelConfig {
-                provider: default_provider.clone(),
-                model: "qwen2.5-7b-instruct".into(),
-            },
-            response: ModelConfig {
-                provider: default_provider,
-                model: "qwen2.5-7b-instruct".into(),
-            },
-            audit: None,
+            enabled: true,
+            max_memories: 5,
+            min_relevance: 0.5,
+            recency_half_life_hours: 72.0,
         }
     }
 }
 
+/// `[director.goals]`: keep track of open questions and what companions
+/// are helping with across ticks, for the arbiter and response prompts
 #[derive(Debug, Clone, Deserialize)]
-#[serde(tag = "type")]
-pub enum LlmProvider {
-    #[serde(rename = "lmstudio")]
-    LmStudio { endpoint: String },
-    #[serde(rename = "openrouter")]
-    OpenRouter {
-        /// API key - can be literal or read from env var if api_key_env is set
-        #[serde(default)]
-        api_key: Option<String>,
-        /// Environment variable name containing the API key
-        #[serde(default)]
-        api_key_env: Option<String>,
-        #[serde(default)]
-        site_url: Option<String>,
-        #[serde(default)]
-        site_name: Option<String>,
-    },
+#[serde(default)]
+pub struct GoalsConfig {
+    pub enabled: bool,
+    /// Goals not renewed for this long are dropped
+    pub ttl_minutes: u64,
+    /// A question the user hasn't answered for this long is due a
+    /// follow-up, and dropped this long after the follow-up (0 leaves
+    /// questions open until `ttl_minutes`)
+    pub follow_up_minutes: u64,
 }
 
-impl LlmProvider {
-    /// Get the OpenRouter API key, checking env var if specified
-    pub fn openrouter_api_key(&self) -> Option<String> {
-        match self {
-            LlmProvider::OpenRouter { api_key, api_key_env, .. } => {
-                // First try env var
-                if let Some(env_name) = api_key_env {
-                    if let Ok(key) = std::env::var(env_name) {
-                        return Some(key);
-                    }
-                }
-                // Fall back to literal key
-                api_key.clone()
-            }
-            _ => None,
+impl Default for GoalsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_minutes: 30,
+            follow_up_minutes: 5,
         }
     }
 }
 
+/// `[director.relationship]`: the user's replies to a companion move their
+/// relationship score with how positive they are, which warms or cools the
+/// companion's tone and shortens or stretches their cooldown
 #[derive(Debug, Clone, Deserialize)]
-pub struct TtsConfig {
-    #[serde(default = "TtsConfig::default_provider")]
-    pub provider: String,
+#[serde(default)]
+pub struct RelationshipConfig {
+    pub enabled: bool,
+    /// How far (0 to 1) one wholly positive or negative reply moves the
+    /// score
+    pub rate: f32,
 }
 
-impl TtsConfig {
-    fn default_provider() -> String {
-        "null".into()
+impl Default for RelationshipConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rate: 0.05,
+        }
     }
 }
 
-impl Default for TtsConfig {
+/// `[director.mood]`: companions' moods follow what happens to them (being
+/// answered or ignored, woken, a scene change, their own replies) and fade
+/// back to neutral
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MoodConfig {
+    pub enabled: bool,
+    /// Time for a mood's intensity to halve
+    pub half_life_minutes: f32,
+    /// A companion's reply left unanswered this long counts as ignored
+    pub ignored_after_secs: u64,
+}
+
+impl Default for MoodConfig {
     fn default() -> Self {
         Self {
-            provider: Self::default_provider(),
+            enabled: true,
+            half_life_minutes: 10.0,
+            ignored_after_secs: 300,
         }
     }
 }
+
+impl MoodConfig {
+    pub fn half_life(&self) -> Duration {
+        Duration::from_secs_f32(self.half_life_minutes.max(0.1) * 60.0)
+    }
+}
+
+/// `[director.reflection]`: after each reply the arbiter model writes a
+/// one-line observation ("user seemed frustrated with the build") into the
+/// companion's memory
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReflectionConfig {
+    pub enabled: bool,
+    /// Append reflections to the companion's ARIAOS notes, where they are
+    /// remembered as notes edits, rather than only the episode log
+    pub notes: bool,
+}
+
+/// `[director.screening]`: screen each tick before the VLA and arbiter, and
+/// only escalate when there is something to answer or the screen changed
+/// enough (and, with an `[llm.screen]` model, that model agrees it's worth
+/// a look)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScreeningConfig {
+    pub enabled: bool,
+    /// Frames that changed less than this (0 to 1, see `[vision]
+    /// diff_threshold`) are passed without a model call
+    pub min_diff_score: f32,
+}
+
+impl Default for ScreeningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_diff_score: 0.02,
+        }
+    }
+}
+
+/// `[director.vla_cache]`: while the screen's perceptual hash stays within
+/// `max_distance` of the frame the VLA last found unremarkable, keep that
+/// verdict instead of asking again, for up to `ttl_secs`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VlaCacheConfig {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    /// Most bits (of 64) the hashes may differ by
+    pub max_distance: u32,
+}
+
+impl Default for VlaCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 60,
+            max_distance: 4,
+        }
+    }
+}
+
+impl VlaCacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// `[director.topics]`: remember what companions brought up unprompted,
+/// list it for the arbiter, and pass on a screen change about the same
+/// thing within `suppress_minutes`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TopicsConfig {
+    pub enabled: bool,
+    /// How long a topic is listed for the arbiter
+    pub remember_minutes: u64,
+    /// How long a topic can't be brought up again, whatever the arbiter
+    /// decides (0 leaves it to the arbiter)
+    pub suppress_minutes: u64,
+    /// How closely a screen change must match a topic to count as the
+    /// same, 0 to 1: a third per word they share, or all of it sharing
+    /// every word of a short topic
+    pub min_relevance: f32,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            remember_minutes: 60,
+            suppress_minutes: 20,
+            min_relevance: 1.0,
+        }
+    }
+}
+
+/// `[director.clock]`: tell the arbiter and companions the user's local
+/// date, time of day and how long the session has run
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub enabled: bool,
+    /// The user's offset from UTC, like "+02:00"; empty follows the
+    /// system timezone
+    pub utc_offset: String,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            utc_offset: String::new(),
+        }
+    }
+}
+
+/// `[director.stages]`: turn pipeline stages off, and order the cheap
+/// checks that can end a tick before the vision models run
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StagesConfig {
+    /// Screen change detection; off, every screen counts as unchanged and
+    /// `combined` mode runs as `split`
+    pub vla: bool,
+    /// Cooldown-based eligibility; off, every companion may be picked
+    pub eligibility: bool,
+    /// Passing without the arbiter when the user has been silent for five
+    /// minutes and nothing changed
+    pub silence_gate: bool,
+    /// Review of replies by `[llm.audit]`
+    pub audit: bool,
+    /// The checks run before the VLA, in order; one left out is skipped
+    pub gates: Vec<Gate>,
+}
+
+impl Default for StagesConfig {
+    fn default() -> Self {
+        Self {
+            vla: true,
+            eligibility: true,
+            silence_gate: true,
+            audit: true,
+            gates: vec![Gate::DoNotDisturb, Gate::Duplicate, Gate::Screening],
+        }
+    }
+}
+
+/// A check that can end a tick before the VLA runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gate {
+    /// `[focus]` silencing unsolicited speech
+    DoNotDisturb,
+    /// The screen matching the previous frames
+    Duplicate,
+    /// `[director.screening]`
+    Screening,
+}
+
+/// How the Director decides whether anyone should speak
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineMode {
+    /// VLA change detection, then a separate arbiter call
+    #[default]
+    Split,
+    /// One structured vision call to the arbiter model does both, halving
+    /// round trips per tick. Falls back to `split` if the arbiter lacks vision.
+    Combined,
+}
+
+/// Image format sent to vision models
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageEncoding {
+    /// Lossless
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl DirectorConfig {
+    fn default_min_decision_interval_ms() -> u64 {
+        2000
+    }
+    fn default_cooldown_after_speak_ms() -> u64 {
+        30_000
+    }
+    fn default_speak_on_scene_change() -> bool {
+        true
+    }
+    fn default_prompts_dir() -> String {
+        "config/prompts".to_string()
+    }
+    fn default_stream_tool_calls() -> bool {
+        true
+    }
+    fn default_stream_text() -> bool {
+        true
+    }
+    fn default_close_up() -> bool {
+        true
+    }
+    fn default_direct_address() -> bool {
+        true
+    }
+    fn default_questions_per_hour() -> u32 {
+        4
+    }
+    fn default_max_tool_iterations() -> usize {
+        3
+    }
+    fn default_image_quality() -> u8 {
+        80
+    }
+    fn default_lore_token_budget() -> usize {
+        500
+    }
+    fn default_lore_scan_depth() -> usize {
+        6
+    }
+    fn default_audit_max_revisions() -> usize {
+        2
+    }
+
+    pub fn min_decision_interval(&self) -> Duration {
+        Duration::from_millis(self.min_decision_interval_ms)
+    }
+
+    pub fn cooldown_after_speak(&self) -> Duration {
+        Duration::from_millis(self.cooldown_after_speak_ms)
+    }
+
+    /// Cooldown settings for one companion, its `overrides` over these
+    pub fn timing(&self, overrides: DirectorOverrides) -> CompanionTiming {
+        CompanionTiming {
+            cooldown_after_speak: Duration::from_millis(
+                overrides
+                    .cooldown_after_speak_ms
+                    .unwrap_or(self.cooldown_after_speak_ms),
+            ),
+            speak_on_scene_change: overrides
+                .speak_on_scene_change
+                .unwrap_or(self.speak_on_scene_change),
+            questions_per_hour: overrides
+                .questions_per_hour
+                .unwrap_or(self.questions_per_hour),
+        }
+    }
+}
+
+/// A character's own cooldown settings, from the card's `director`
+/// extension, e.g. `"director": { "cooldown_after_speak_ms": 120000 }`.
+/// Unset ones follow `[director]`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct DirectorOverrides {
+    #[serde(default)]
+    pub cooldown_after_speak_ms: Option<u64>,
+    #[serde(default)]
+    pub speak_on_scene_change: Option<bool>,
+    #[serde(default)]
+    pub questions_per_hour: Option<u32>,
+}
+
+/// `DirectorConfig` cooldown settings as they apply to one companion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompanionTiming {
+    pub cooldown_after_speak: Duration,
+    pub speak_on_scene_change: bool,
+    pub questions_per_hour: u32,
+}
+
+impl Default for DirectorConfig {
+    fn default() -> Self {
+        Self {
+            min_decision_interval_ms: Self::default_min_decision_interval_ms(),
+            cooldown_after_speak_ms: Self::default_cooldown_after_speak_ms(),
+            speak_on_scene_change: Self::default_speak_on_scene_change(),
+            questions_per_hour: Self::default_questions_per_hour(),
+            prompts_dir: Self::default_prompts_dir(),
+            stream_tool_calls: Self::default_stream_tool_calls(),
+            max_tool_iterations: Self::default_max_tool_iterations(),
+            stream_text: Self::default_stream_text(),
+            close_up: Self::default_close_up(),
+            image_format: ImageEncoding::default(),
+            image_quality: Self::default_image_quality(),
+            pipeline_mode: PipelineMode::default(),
+            log_reasoning: false,
+            evaluate_on_scene_change: false,
+            min_confidence: 0.0,
+            direct_address: Self::default_direct_address(),
+            max_chain: 0,
+            lore_token_budget: Self::default_lore_token_budget(),
+            lore_scan_depth: Self::default_lore_scan_depth(),
+            recall: RecallConfig::default(),
+            audit_max_revisions: Self::default_audit_max_revisions(),
+            goals: GoalsConfig::default(),
+            mood: MoodConfig::default(),
+            relationship: RelationshipConfig::default(),
+            reflection: ReflectionConfig::default(),
+            screening: ScreeningConfig::default(),
+            vla_cache: VlaCacheConfig::default(),
+            topics: TopicsConfig::default(),
+            clock: ClockConfig::default(),
+            stages: StagesConfig::default(),
+        }
+    }
+}
+
+/// Configuration for a single model endpoint (provider + model name)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelConfig {
+    pub provider: LlmProvider,
+    pub model: String,
+    /// What the model supports; defaults depend on the provider
+    #[serde(default)]
+    pub capabilities: Option<ModelCapabilities>,
+    /// Downscale attached images so their longest side fits (pixels)
+    #[serde(default)]
+    pub max_image_dimension: Option<u32>,
+    /// Downscale attached images until the encoded PNG fits (bytes)
+    #[serde(default)]
+    pub max_image_bytes: Option<usize>,
+    /// Cap on generated tokens per request (includes any reasoning)
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// How hard reasoning models should think, for providers that support it
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ModelConfig {
+    pub fn capabilities(&self) -> ModelCapabilities {
+        self.capabilities
+            .unwrap_or_else(|| ModelCapabilities::for_provider(&self.provider))
+    }
+}
+
+/// Features a model supports. The Director and clients downgrade requests
+/// (dropping images, tools, or structured output) instead of erroring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ModelCapabilities {
+    #[serde(default = "ModelCapabilities::default_true")]
+    pub vision: bool,
+    #[serde(default = "ModelCapabilities::default_true")]
+    pub tools: bool,
+    /// Supports `response_format: json_schema` structured output
+    #[serde(default = "ModelCapabilities::default_true")]
+    pub json_schema: bool,
+}
+
+impl ModelCapabilities {
+    fn default_true() -> bool {
+        true
+    }
+
+    /// Defaults when a model config doesn't list capabilities
+    pub fn for_provider(provider: &LlmProvider) -> Self {
+        match provider {
+            LlmProvider::LlamaCpp { .. } => Self {
+                vision: false,
+                tools: false,
+                json_schema: false,
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            vision: true,
+            tools: true,
+            json_schema: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LlmConfig {
+    /// VLA (Vision-Language Analysis) - fast, cheap vision model for change detection
+    /// Runs most frequently, needs vision capability
+    pub vla: ModelConfig,
+    /// Arbiter - reasoning model for deciding who should speak
+    /// Text-only, benefits from strong logic/reasoning
+    pub arbiter: ModelConfig,
+    /// Response - creative writing model for generating character dialogue
+    /// Benefits from strong conversational/creative writing ability
+    pub response: ModelConfig,
+    /// Optional audit model for reviewing responses
+    #[serde(default)]
+    pub audit: Option<ModelConfig>,
+    /// Optional small model that screens ticks before the VLA and arbiter
+    /// (see `[director.screening]`)
+    #[serde(default)]
+    pub screen: Option<ModelConfig>,
+    /// Optional embedding model for semantic memory (episodes.embedding)
+    #[serde(default)]
+    pub embedding: Option<ModelConfig>,
+    /// Maximum in-flight requests per provider (0 = unlimited).
+    /// Roles sharing a server share the limit, so a local LM Studio isn't flooded.
+    #[serde(default = "LlmConfig::default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Times to ask the model to fix a JSON completion that is malformed or
+    /// doesn't match its schema before failing (0 disables validation)
+    #[serde(default = "LlmConfig::default_json_repair_attempts")]
+    pub json_repair_attempts: u32,
+    /// Regex patterns replaced with "[REDACTED]" in every outgoing prompt
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// Ping every configured model at startup and wait until each responds
+    #[serde(default = "LlmConfig::default_warm_up")]
+    pub warm_up: bool,
+    /// Ask LM Studio to load each model over its REST API before pinging it
+    #[serde(default)]
+    pub lmstudio_load_models: bool,
+    /// How long the warm-up keeps retrying a model before giving up on it
+    #[serde(default = "LlmConfig::default_warm_up_timeout_secs")]
+    pub warm_up_timeout_secs: u64,
+}
+
+impl LlmConfig {
+    fn default_max_concurrent_requests() -> usize {
+        2
+    }
+    fn default_json_repair_attempts() -> u32 {
+        2
+    }
+    fn default_warm_up() -> bool {
+        true
+    }
+    fn default_warm_up_timeout_secs() -> u64 {
+        120
+    }
+
+    /// Reject providers this build can't create a client for
+    pub fn validate(&self) -> Result<()> {
+        let models = [
+            ("vla", Some(&self.vla)),
+            ("arbiter", Some(&self.arbiter)),
+            ("response", Some(&self.response)),
+            ("audit", self.audit.as_ref()),
+            ("screen", self.screen.as_ref()),
+            ("embedding", self.embedding.as_ref()),
+        ];
+        for (role, model) in models {
+            let Some(model) = model else { continue };
+            if matches!(model.provider, LlmProvider::LlamaCpp { .. })
+                && !cfg!(feature = "llama-cpp")
+            {
+                bail!(
+                    "[llm.{role}] uses the llamacpp provider, but dewet was built without the `llama-cpp` feature"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        let default_provider = LlmProvider::LmStudio {
+            endpoint: "http://127.0.0.1:1234".into(),
+        };
+        Self {
+            vla: ModelConfig {
+                provider: default_provider.clone(),
+                model: "qwen2.5-vl-7b-instruct".into(),
+                capabilities: None,
+                max_image_dimension: None,
+                max_image_bytes: None,
+                max_tokens: None,
+                reasoning_effort: None,
+            },
+            arbiter: ModelConfig {
+                provider: default_provider.clone(),
+                model: "qwen2.5-7b-instruct".into(),
+                capabilities: None,
+                max_image_dimension: None,
+                max_image_bytes: None,
+                max_tokens: None,
+                reasoning_effort: None,
+            },
+            response: ModelConfig {
+                provider: default_provider,
+                model: "qwen2.5-7b-instruct".into(),
+                capabilities: None,
+                max_image_dimension: None,
+                max_image_bytes: None,
+                max_tokens: None,
+                reasoning_effort: None,
+            },
+            audit: None,
+            screen: None,
+            embedding: None,
+            max_concurrent_requests: Self::default_max_concurrent_requests(),
+            json_repair_attempts: Self::default_json_repair_attempts(),
+            redact_patterns: Vec::new(),
+            warm_up: Self::default_warm_up(),
+            lmstudio_load_models: false,
+            warm_up_timeout_secs: Self::default_warm_up_timeout_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum LlmProvider {
+    #[serde(rename = "lmstudio")]
+    LmStudio { endpoint: String },
+    #[serde(rename = "openrouter")]
+    OpenRouter {
+        /// API key - can be literal or read from env var if api_key_env is set
+        #[serde(default)]
+        api_key: Option<String>,
+        /// Environment variable name containing the API key
+        #[serde(default)]
+        api_key_env: Option<String>,
+        #[serde(default)]
+        site_url: Option<String>,
+        #[serde(default)]
+        site_name: Option<String>,
+        /// Upstream provider routing preferences (sent as `provider`)
+        #[serde(default)]
+        routing: Option<OpenRouterRouting>,
+    },
+    /// In-process llama.cpp inference (requires the `llama-cpp` feature)
+    #[serde(rename = "llamacpp")]
+    LlamaCpp {
+        /// Path to a GGUF model file
+        model_path: String,
+        /// Context window in tokens (default 4096)
+        #[serde(default)]
+        context_size: Option<u32>,
+        /// Layers to offload to the GPU (default 0, CPU only)
+        #[serde(default)]
+        gpu_layers: Option<u32>,
+        /// Maximum tokens to generate per completion (default 512)
+        #[serde(default)]
+        max_tokens: Option<u32>,
+    },
+}
+
+/// OpenRouter provider routing preferences, passed through verbatim.
+/// See https://openrouter.ai/docs/features/provider-routing
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct OpenRouterRouting {
+    /// Upstream providers to try, in order (e.g. ["Fireworks", "Together"])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Whether to fall back to providers outside `order`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// Only use providers serving these quantizations (e.g. ["fp8", "bf16"])
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quantizations: Vec<String>,
+    /// Only use these providers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// Never use these providers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Only use providers that support every request parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+    /// "price", "throughput" or "latency"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+impl LlmProvider {
+    /// Get the OpenRouter API key, checking env var if specified
+    pub fn openrouter_api_key(&self) -> Option<String> {
+        match self {
+            LlmProvider::OpenRouter { api_key, api_key_env, .. } => {
+                // First try env var
+                if let Some(env_name) = api_key_env {
+                    if let Ok(key) = std::env::var(env_name) {
+                        return Some(key);
+                    }
+                }
+                // Fall back to literal key
+                api_key.clone()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TtsConfig {
+    /// "null" (silence), "piper", "elevenlabs", "azure", or "kokoro"
+    #[serde(default = "TtsConfig::default_provider")]
+    pub provider: String,
+    /// Settings for `provider = "piper"`
+    #[serde(default)]
+    pub piper: Option<PiperConfig>,
+    /// Settings for `provider = "elevenlabs"`
+    #[serde(default)]
+    pub elevenlabs: Option<ElevenLabsConfig>,
+    /// Settings for `provider = "azure"`
+    #[serde(default)]
+    pub azure: Option<AzureSpeechConfig>,
+    /// Settings for `provider = "kokoro"` (requires the `kokoro` feature)
+    #[serde(default)]
+    pub kokoro: Option<KokoroConfig>,
+    /// Per-character voices, keyed by character id
+    #[serde(default)]
+    pub voices: HashMap<String, VoiceSelection>,
+    /// Synthesize replies sentence by sentence, sending each as a `SpeakChunk`
+    #[serde(default = "TtsConfig::default_stream_sentences")]
+    pub stream_sentences: bool,
+    /// Disk cache of synthesized audio; disabled when absent
+    #[serde(default)]
+    pub cache: Option<TtsCacheConfig>,
+    /// Pronunciation dictionary applied before synthesis
+    #[serde(default)]
+    pub lexicon: Option<LexiconConfig>,
+    /// Play speech on the host's audio device (requires the `local-audio` feature)
+    #[serde(default)]
+    pub playback: PlaybackMode,
+    /// Encoding of audio sent over the bridge
+    #[serde(default)]
+    pub bridge_format: AudioFormat,
+}
+
+/// Audio encoding for bridge transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    /// 16-bit PCM WAV, as synthesized
+    #[default]
+    Wav,
+    /// Mono MP3, roughly a tenth the size (requires the `mp3` feature)
+    Mp3,
+}
+
+impl AudioFormat {
+    /// Name used in `Speak` messages
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Mp3 => "mp3",
+        }
+    }
+}
+
+/// When the daemon plays speech itself instead of leaving it to the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    /// Only while no bridge client is connected (headless operation)
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl TtsConfig {
+    fn default_provider() -> String {
+        "null".into()
+    }
+    fn default_stream_sentences() -> bool {
+        true
+    }
+
+    /// A copy of this config with a character's voice applied. The voice goes
+    /// into the chosen provider's own section, so that section must exist.
+    pub fn with_voice(&self, selection: &VoiceSelection) -> TtsConfig {
+        let mut config = self.clone();
+        if let Some(provider) = selection.provider() {
+            config.provider = provider.to_string();
+        }
+        let Some(voice) = selection.voice() else {
+            return config;
+        };
+        match config.provider.as_str() {
+            "piper" => {
+                if let Some(piper) = &mut config.piper {
+                    piper.model_path = piper.resolve_voice(voice);
+                }
+            }
+            "elevenlabs" => {
+                if let Some(elevenlabs) = &mut config.elevenlabs {
+                    elevenlabs.voice_id = voice.to_string();
+                }
+            }
+            "azure" => {
+                if let Some(azure) = &mut config.azure {
+                    azure.voice = voice.to_string();
+                }
+            }
+            "kokoro" => {
+                if let Some(kokoro) = &mut config.kokoro {
+                    kokoro.voice = voice.to_string();
+                }
+            }
+            _ => {}
+        }
+        config
+    }
+
+    /// Identifies the active provider and its settings (voice, speed, ...), so
+    /// cached audio is not reused after either changes
+    pub fn voice_fingerprint(&self) -> String {
+        let settings = match self.provider.as_str() {
+            "piper" => format!("{:?}", self.piper),
+            "elevenlabs" => format!("{:?}", self.elevenlabs),
+            "azure" => format!("{:?}", self.azure),
+            "kokoro" => format!("{:?}", self.kokoro),
+            _ => String::new(),
+        };
+        format!("{}:{}", self.provider, settings)
+    }
+}
+
+/// On-disk cache of synthesized audio, evicted least-recently-used
+#[derive(Debug, Clone, Deserialize)]
+pub struct TtsCacheConfig {
+    #[serde(default = "TtsCacheConfig::default_dir")]
+    pub dir: String,
+    /// Size cap in megabytes
+    #[serde(default = "TtsCacheConfig::default_max_mb")]
+    pub max_mb: u64,
+}
+
+impl TtsCacheConfig {
+    fn default_dir() -> String {
+        ".local/tts-cache".into()
+    }
+    fn default_max_mb() -> u64 {
+        256
+    }
+}
+
+/// Pronunciations for words every voice gets wrong, from a TOML file with a
+/// table of `word = "respelling"` or `word = { ipa = "...", respelling = "..." }`
+/// per language
+#[derive(Debug, Clone, Deserialize)]
+pub struct LexiconConfig {
+    #[serde(default = "LexiconConfig::default_path")]
+    pub path: String,
+    /// Table of the file to use, e.g. "en"; "en-US" falls back to "en"
+    #[serde(default = "LexiconConfig::default_language")]
+    pub language: String,
+}
+
+impl LexiconConfig {
+    fn default_path() -> String {
+        "config/lexicon.toml".into()
+    }
+    fn default_language() -> String {
+        "en".into()
+    }
+}
+
+/// A character's voice: either just a voice for the default provider
+/// (`lyra = "en_US-amy"`) or a provider and voice
+/// (`lyra = { provider = "elevenlabs", voice = "21m00Tcm4TlvDq8ikWAM" }`),
+/// optionally with delivery adjustments (`orion = { pitch = 0.9 }`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum VoiceSelection {
+    Voice(String),
+    Provider {
+        #[serde(default)]
+        provider: Option<String>,
+        #[serde(default)]
+        voice: Option<String>,
+        #[serde(default)]
+        rate: Option<f32>,
+        #[serde(default)]
+        pitch: Option<f32>,
+        #[serde(default)]
+        volume: Option<f32>,
+    },
+}
+
+impl VoiceSelection {
+    pub fn provider(&self) -> Option<&str> {
+        match self {
+            VoiceSelection::Voice(_) => None,
+            VoiceSelection::Provider { provider, .. } => provider.as_deref(),
+        }
+    }
+
+    pub fn voice(&self) -> Option<&str> {
+        match self {
+            VoiceSelection::Voice(voice) => Some(voice),
+            VoiceSelection::Provider { voice, .. } => voice.as_deref(),
+        }
+    }
+
+    pub fn tuning(&self) -> VoiceTuning {
+        match self {
+            VoiceSelection::Voice(_) => VoiceTuning::default(),
+            VoiceSelection::Provider {
+                rate,
+                pitch,
+                volume,
+                ..
+            } => VoiceTuning {
+                rate: *rate,
+                pitch: *pitch,
+                volume: *volume,
+            },
+        }
+    }
+}
+
+/// A character's speaking rate, pitch, and volume relative to their voice's
+/// normal delivery (1.0), from `[tts.voices]` or the character card's `voice`
+/// extension. Applied on top of the mood.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub struct VoiceTuning {
+    #[serde(default)]
+    pub rate: Option<f32>,
+    #[serde(default)]
+    pub pitch: Option<f32>,
+    #[serde(default)]
+    pub volume: Option<f32>,
+}
+
+impl VoiceTuning {
+    /// Settings from `self`, falling back to `other` for those not set
+    pub fn or(self, other: VoiceTuning) -> VoiceTuning {
+        VoiceTuning {
+            rate: self.rate.or(other.rate),
+            pitch: self.pitch.or(other.pitch),
+            volume: self.volume.or(other.volume),
+        }
+    }
+
+    pub fn is_neutral(&self) -> bool {
+        *self == VoiceTuning::default()
+    }
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            provider: Self::default_provider(),
+            piper: None,
+            elevenlabs: None,
+            azure: None,
+            kokoro: None,
+            voices: HashMap::new(),
+            stream_sentences: Self::default_stream_sentences(),
+            cache: None,
+            lexicon: None,
+            playback: PlaybackMode::default(),
+            bridge_format: AudioFormat::default(),
+        }
+    }
+}
+
+/// Local Piper TTS (https://github.com/rhasspy/piper), run as a subprocess
+#[derive(Debug, Clone, Deserialize)]
+pub struct PiperConfig {
+    /// Path to the piper executable (looked up on PATH by default)
+    #[serde(default = "PiperConfig::default_binary")]
+    pub binary: String,
+    /// Voice model (.onnx, with its .onnx.json alongside)
+    pub model_path: String,
+    /// Speaker id for multi-speaker voices
+    #[serde(default)]
+    pub speaker: Option<u32>,
+    /// Speaking rate; above 1.0 is slower
+    #[serde(default)]
+    pub length_scale: Option<f32>,
+}
+
+impl PiperConfig {
+    fn default_binary() -> String {
+        "piper".into()
+    }
+
+    /// A bare voice name ("en_US-amy-medium") is looked up next to `model_path`;
+    /// anything that looks like a path is used as-is
+    fn resolve_voice(&self, voice: &str) -> String {
+        if voice.ends_with(".onnx") || voice.contains('/') || voice.contains('\\') {
+            return voice.to_string();
+        }
+        Path::new(&self.model_path)
+            .with_file_name(format!("{}.onnx", voice))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Hosted ElevenLabs text-to-speech
+#[derive(Debug, Clone, Deserialize)]
+pub struct ElevenLabsConfig {
+    /// Environment variable containing the API key
+    #[serde(default = "ElevenLabsConfig::default_api_key_env")]
+    pub api_key_env: String,
+    /// Literal API key, used if the environment variable is unset
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub voice_id: String,
+    #[serde(default = "ElevenLabsConfig::default_model_id")]
+    pub model_id: String,
+    /// Voice settings (0.0-1.0); the voice's defaults are used when unset
+    #[serde(default)]
+    pub stability: Option<f32>,
+    #[serde(default)]
+    pub similarity_boost: Option<f32>,
+}
+
+impl ElevenLabsConfig {
+    fn default_api_key_env() -> String {
+        "ELEVENLABS_API_KEY".into()
+    }
+    fn default_model_id() -> String {
+        "eleven_multilingual_v2".into()
+    }
+
+    /// The API key from the environment, falling back to `api_key`
+    pub fn api_key(&self) -> Option<String> {
+        env::var(&self.api_key_env).ok().or_else(|| self.api_key.clone())
+    }
+}
+
+/// Azure Cognitive Services Speech (neural voices via the REST API)
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzureSpeechConfig {
+    /// Environment variable containing the subscription key
+    #[serde(default = "AzureSpeechConfig::default_api_key_env")]
+    pub api_key_env: String,
+    /// Literal subscription key, used if the environment variable is unset
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Resource region, e.g. "eastus"
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Full synthesis URL, overriding `region` (custom domains, sovereign clouds)
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Voice used when the text is not already SSML
+    #[serde(default = "AzureSpeechConfig::default_voice")]
+    pub voice: String,
+}
+
+impl AzureSpeechConfig {
+    fn default_api_key_env() -> String {
+        "AZURE_SPEECH_KEY".into()
+    }
+    fn default_voice() -> String {
+        "en-US-JennyNeural".into()
+    }
+
+    /// The subscription key from the environment, falling back to `api_key`
+    pub fn api_key(&self) -> Option<String> {
+        env::var(&self.api_key_env).ok().or_else(|| self.api_key.clone())
+    }
+
+    /// The synthesis URL: `endpoint` if set, otherwise derived from `region`
+    pub fn endpoint(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!(
+                "https://{}.tts.speech.microsoft.com/cognitiveservices/v1",
+                self.region.as_deref().unwrap_or("eastus")
+            ),
+        }
+    }
+}
+
+/// In-process Kokoro neural TTS via onnxruntime
+#[derive(Debug, Clone, Deserialize)]
+pub struct KokoroConfig {
+    /// Directory laid out like `onnx-community/Kokoro-82M-v1.0-ONNX`
+    pub model_dir: String,
+    /// Voice file name in `voices/`, without `.bin`
+    #[serde(default = "KokoroConfig::default_voice")]
+    pub voice: String,
+    #[serde(default = "KokoroConfig::default_speed")]
+    pub speed: f32,
+    /// Output sample rate; the model's 24 kHz audio is resampled if different
+    #[serde(default = "KokoroConfig::default_sample_rate")]
+    pub sample_rate: u32,
+    /// Phonemizer executable
+    #[serde(default = "KokoroConfig::default_espeak_binary")]
+    pub espeak_binary: String,
+    /// espeak-ng language for phonemization
+    #[serde(default = "KokoroConfig::default_espeak_voice")]
+    pub espeak_voice: String,
+}
+
+impl KokoroConfig {
+    fn default_voice() -> String {
+        "af_heart".into()
+    }
+    fn default_speed() -> f32 {
+        1.0
+    }
+    fn default_sample_rate() -> u32 {
+        24_000
+    }
+    fn default_espeak_binary() -> String {
+        "espeak-ng".into()
+    }
+    fn default_espeak_voice() -> String {
+        "en-us".into()
+    }
+}
+
+/// Voice input: microphone capture and speech-to-text. Transcripts are
+/// handled like typed chat.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SttConfig {
+    /// Listen to the microphone (requires the `microphone` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// "whisper" (local whisper.cpp, requires the `whisper` feature) or
+    /// "openai" (any OpenAI-compatible transcription API)
+    #[serde(default = "SttConfig::default_provider")]
+    pub provider: String,
+    /// Input device name; the system default when unset
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Spoken language (ISO 639-1, e.g. "en"); detected when unset
+    #[serde(default)]
+    pub language: Option<String>,
+    /// How speech is told apart from silence
+    #[serde(default)]
+    pub vad: VadConfig,
+    /// Only transcribe after a wake word (requires the `wake-word` feature);
+    /// everything heard is transcribed when absent
+    #[serde(default)]
+    pub wake: Option<WakeWordConfig>,
+    /// Settings for `provider = "whisper"`
+    #[serde(default)]
+    pub whisper: Option<WhisperConfig>,
+    /// Settings for `provider = "openai"`
+    #[serde(default)]
+    pub openai: Option<OpenAiSttConfig>,
+}
+
+impl SttConfig {
+    fn default_provider() -> String {
+        "whisper".into()
+    }
+}
+
+impl Default for SttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: Self::default_provider(),
+            device: None,
+            language: None,
+            vad: VadConfig::default(),
+            wake: None,
+            whisper: None,
+            openai: None,
+        }
+    }
+}
+
+/// Text the user copies, recorded as observations
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClipboardConfig {
+    /// Watch the clipboard (requires the `clipboard` feature)
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "ClipboardConfig::default_poll_ms")]
+    pub poll_ms: u64,
+    /// Longer copies are truncated
+    #[serde(default = "ClipboardConfig::default_max_chars")]
+    pub max_chars: usize,
+    /// Regex patterns replaced with "[REDACTED]" before copied text is recorded
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+}
+
+impl ClipboardConfig {
+    fn default_poll_ms() -> u64 {
+        500
+    }
+    fn default_max_chars() -> usize {
+        2000
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_ms.max(50))
+    }
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_ms: Self::default_poll_ms(),
+            max_chars: Self::default_max_chars(),
+            redact_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Suspending capture and model calls while nobody is at the computer
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdleConfig {
+    #[serde(default = "IdleConfig::default_enabled")]
+    pub enabled: bool,
+    /// Minutes without keyboard or mouse input before pausing. Idle time comes
+    /// from the OS with the `idle-detection` feature, else from
+    /// `[vision] input_activity`.
+    #[serde(default = "IdleConfig::default_idle_minutes")]
+    pub idle_minutes: f32,
+    /// Also pause while the screen is locked (Linux, via logind)
+    #[serde(default = "IdleConfig::default_pause_when_locked")]
+    pub pause_when_locked: bool,
+}
+
+impl IdleConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+    fn default_idle_minutes() -> f32 {
+        10.0
+    }
+    fn default_pause_when_locked() -> bool {
+        true
+    }
+
+    pub fn idle_after(&self) -> Duration {
+        Duration::from_secs_f32(self.idle_minutes.max(0.0) * 60.0)
+    }
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            idle_minutes: Self::default_idle_minutes(),
+            pause_when_locked: Self::default_pause_when_locked(),
+        }
+    }
+}
+
+/// Speaking at set times, independent of what is on screen: recurring
+/// `entries` plus reminders companions set
+#[derive(Debug, Clone, Deserialize)]
+pub struct SchedulerConfig {
+    #[serde(default = "SchedulerConfig::default_enabled")]
+    pub enabled: bool,
+    /// How often to look for due entries and reminders (1-60)
+    #[serde(default = "SchedulerConfig::default_check_secs")]
+    pub check_secs: u64,
+    #[serde(default)]
+    pub entries: Vec<ScheduleEntry>,
+}
+
+impl SchedulerConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+    fn default_check_secs() -> u64 {
+        20
+    }
+
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.check_secs.clamp(1, 60))
+    }
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            check_secs: Self::default_check_secs(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// A recurring `[[scheduler.entries]]` item
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleEntry {
+    /// Five-field cron expression in local time ("minute hour day month
+    /// weekday"), or @hourly, @daily, @weekly, @monthly, @yearly
+    pub cron: String,
+    /// Companion who speaks; any of them when absent
+    #[serde(default)]
+    pub character: Option<String>,
+    /// Said exactly as written, without asking the models
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Otherwise an evaluation runs with this as the reason to speak
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// Do not disturb: while the user is in a meeting, a fullscreen app, or
+/// typing steadily in an editor, unsolicited speech needs a higher urgency
+/// or is held entirely. Messages to the companions and the wake word still
+/// get answers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FocusConfig {
+    #[serde(default = "FocusConfig::default_enabled")]
+    pub enabled: bool,
+    /// Keys per minute that count as steady typing, for `while_typing`
+    #[serde(default = "FocusConfig::default_typing_keys_per_min")]
+    pub typing_keys_per_min: u32,
+    /// When the focused window covers its monitor
+    #[serde(default = "FocusConfig::default_fullscreen")]
+    pub fullscreen: FocusMode,
+    /// Lowest arbiter urgency (0-1) that still speaks under `raise`
+    #[serde(default = "FocusConfig::default_min_urgency")]
+    pub min_urgency: f32,
+    /// Apps that mean focus; replaces the built-in meeting and editor lists
+    #[serde(default = "FocusConfig::default_categories")]
+    pub categories: Vec<FocusCategory>,
+}
+
+impl FocusConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+    fn default_typing_keys_per_min() -> u32 {
+        60
+    }
+    fn default_fullscreen() -> FocusMode {
+        FocusMode::Silence
+    }
+    fn default_min_urgency() -> f32 {
+        0.8
+    }
+    fn default_categories() -> Vec<FocusCategory> {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        vec![
+            FocusCategory {
+                name: "meeting".into(),
+                apps: strings(&["zoom", "teams", "webex", "skype", "facetime", "gotomeeting"]),
+                titles: strings(&["Google Meet", "^Meet - ", "Zoom Meeting", "(?i)huddle"]),
+                while_typing: false,
+                mode: FocusMode::Silence,
+            },
+            FocusCategory {
+                name: "editor".into(),
+                apps: strings(&[
+                    "code",
+                    "cursor",
+                    "idea",
+                    "rustrover",
+                    "pycharm",
+                    "webstorm",
+                    "clion",
+                    "goland",
+                    "zed",
+                    "xcode",
+                    "sublime",
+                    "android studio",
+                    "visual studio",
+                ]),
+                titles: Vec::new(),
+                while_typing: true,
+                mode: FocusMode::Raise,
+            },
+        ]
+    }
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            typing_keys_per_min: Self::default_typing_keys_per_min(),
+            fullscreen: Self::default_fullscreen(),
+            min_urgency: Self::default_min_urgency(),
+            categories: Self::default_categories(),
+        }
+    }
+}
+
+/// A `[[focus.categories]]` item: apps that call for quiet
+#[derive(Debug, Clone, Deserialize)]
+pub struct FocusCategory {
+    /// Shown in logs and to the arbiter, e.g. "meeting"
+    pub name: String,
+    /// Matched case-insensitively against part of the focused app's name
+    #[serde(default)]
+    pub apps: Vec<String>,
+    /// Regexes over the focused window's title (for web apps in a browser)
+    #[serde(default)]
+    pub titles: Vec<String>,
+    /// Only while the user types steadily
+    #[serde(default)]
+    pub while_typing: bool,
+    #[serde(default)]
+    pub mode: FocusMode,
+}
+
+/// How focus treats speech nobody asked for, least strict first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusMode {
+    /// No change
+    Allow,
+    /// Only replies the arbiter rates at least `min_urgency`
+    #[default]
+    Raise,
+    /// Nothing; scheduled prompts and messages wait until focus ends
+    Silence,
+}
+
+/// Energy-based voice activity detection
+#[derive(Debug, Clone, Deserialize)]
+pub struct VadConfig {
+    /// RMS level (0.0-1.0) above which audio counts as speech
+    #[serde(default = "VadConfig::default_threshold")]
+    pub threshold: f32,
+    /// Silence that ends an utterance
+    #[serde(default = "VadConfig::default_silence_ms")]
+    pub silence_ms: u64,
+    /// Utterances with less speech than this are dropped as noise
+    #[serde(default = "VadConfig::default_min_speech_ms")]
+    pub min_speech_ms: u64,
+    /// Utterances are cut off at this length
+    #[serde(default = "VadConfig::default_max_utterance_secs")]
+    pub max_utterance_secs: u64,
+}
+
+impl VadConfig {
+    fn default_threshold() -> f32 {
+        0.015
+    }
+    fn default_silence_ms() -> u64 {
+        800
+    }
+    fn default_min_speech_ms() -> u64 {
+        300
+    }
+    fn default_max_utterance_secs() -> u64 {
+        30
+    }
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Self::default_threshold(),
+            silence_ms: Self::default_silence_ms(),
+            min_speech_ms: Self::default_min_speech_ms(),
+            max_utterance_secs: Self::default_max_utterance_secs(),
+        }
+    }
+}
+
+/// openWakeWord detection (https://github.com/dscripka/openWakeWord)
+#[derive(Debug, Clone, Deserialize)]
+pub struct WakeWordConfig {
+    /// Directory with openWakeWord's `melspectrogram.onnx` and
+    /// `embedding_model.onnx`, and the wake word models
+    #[serde(default = "WakeWordConfig::default_model_dir")]
+    pub model_dir: String,
+    /// Wake word model: a name in `model_dir` (e.g. "hey_jarvis_v0.1") or a
+    /// path to an .onnx file
+    pub word: String,
+    /// 0.0-1.0; higher triggers more readily, and more often by mistake
+    #[serde(default = "WakeWordConfig::default_sensitivity")]
+    pub sensitivity: f32,
+    /// How long after the wake word speech is transcribed; each utterance
+    /// heard extends it
+    #[serde(default = "WakeWordConfig::default_listen_secs")]
+    pub listen_secs: u64,
+    /// Companion the wake word calls, who is then the only one who may answer
+    #[serde(default)]
+    pub character: Option<String>,
+}
+
+impl WakeWordConfig {
+    fn default_model_dir() -> String {
+        "models/openwakeword".into()
+    }
+    fn default_sensitivity() -> f32 {
+        0.5
+    }
+    fn default_listen_secs() -> u64 {
+        10
+    }
+
+    /// Path of the wake word model
+    pub fn model_path(&self) -> String {
+        if self.word.ends_with(".onnx") || self.word.contains('/') || self.word.contains('\\') {
+            return self.word.clone();
+        }
+        Path::new(&self.model_dir)
+            .join(format!("{}.onnx", self.word))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Model score at which the wake word counts as detected
+    pub fn threshold(&self) -> f32 {
+        1.0 - self.sensitivity.clamp(0.05, 0.95)
+    }
+}
+
+/// Local whisper.cpp transcription
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhisperConfig {
+    /// ggml model file, e.g. "models/whisper/ggml-base.en.bin"
+    pub model_path: String,
+    /// Inference threads; whisper.cpp's default when unset
+    #[serde(default)]
+    pub threads: Option<i32>,
+}
+
+/// OpenAI `/v1/audio/transcriptions` or a compatible server
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAiSttConfig {
+    #[serde(default = "OpenAiSttConfig::default_endpoint")]
+    pub endpoint: String,
+    #[serde(default = "OpenAiSttConfig::default_model")]
+    pub model: String,
+    /// Environment variable containing the API key
+    #[serde(default = "OpenAiSttConfig::default_api_key_env")]
+    pub api_key_env: String,
+    /// Literal API key, used if the environment variable is unset
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl OpenAiSttConfig {
+    fn default_endpoint() -> String {
+        "https://api.openai.com/v1/audio/transcriptions".into()
+    }
+    fn default_model() -> String {
+        "whisper-1".into()
+    }
+    fn default_api_key_env() -> String {
+        "OPENAI_API_KEY".into()
+    }
+
+    /// The API key from the environment, falling back to `api_key`
+    pub fn api_key(&self) -> Option<String> {
+        env::var(&self.api_key_env).ok().or_else(|| self.api_key.clone())
+    }
+}
@@ -0,0 +1,91 @@
+//! Direct address: a user message that calls a companion by name ("Orion,
+//! what do you think?", "hey lyra", "..., Orion?") goes to that companion
+//! without asking the arbiter
+
+/// Words that may come before a name without making it a mention
+const GREETINGS: &[&str] = &["hey", "hi", "hello", "ok", "okay", "yo"];
+
+/// The id of the one companion `text` calls by name or id, matched
+/// case-insensitively in vocative position: first, after a greeting or `@`,
+/// or set off by a comma at the end. A name mentioned in passing ("Lyra
+/// said...") doesn't count, and neither does a message addressing several
+/// companions.
+pub fn addressed<'a>(text: &str, companions: &[(&'a str, &str)]) -> Option<&'a str> {
+    let leading = leading_name(text, companions);
+    let trailing = trailing_name(text, companions);
+    match (leading, trailing) {
+        (Some(first), Some(last)) if first != last => None,
+        (first, last) => first.or(last),
+    }
+}
+
+/// "Orion, ...", "hey orion ...", "@orion ..."
+fn leading_name<'a>(text: &str, companions: &[(&'a str, &str)]) -> Option<&'a str> {
+    let mut rest = text.trim_start();
+    let mut vocative = false;
+    if let Some(stripped) = rest.strip_prefix('@') {
+        rest = stripped;
+        vocative = true;
+    } else if let Some(stripped) = strip_greeting(rest) {
+        rest = stripped;
+        vocative = true;
+    }
+    companions.iter().find_map(|&(id, name)| {
+        let after = [name, id].into_iter().find_map(|word| strip_word(rest, word))?;
+        // Without a greeting, the name has to be set off from the sentence
+        let set_off = after.is_empty() || after.starts_with([',', ':', '!', '?', '.']);
+        (vocative || set_off).then_some(id)
+    })
+}
+
+/// "..., Orion?"
+fn trailing_name<'a>(text: &str, companions: &[(&'a str, &str)]) -> Option<&'a str> {
+    let text = text.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '?' | '!' | '.'));
+    let (_, tail) = text.rsplit_once(',')?;
+    let tail = tail.trim();
+    companions
+        .iter()
+        .find(|(id, name)| tail.eq_ignore_ascii_case(name) || tail.eq_ignore_ascii_case(id))
+        .map(|&(id, _)| id)
+}
+
+/// `text` after a leading greeting and any punctuation following it
+fn strip_greeting(text: &str) -> Option<&str> {
+    GREETINGS.iter().find_map(|greeting| {
+        strip_word(text, greeting)
+            .map(|rest| rest.trim_start_matches(|c: char| c.is_whitespace() || c == ','))
+    })
+}
+
+/// `text` after `word` when it starts with it as a whole word, ignoring case
+fn strip_word<'t>(text: &'t str, word: &str) -> Option<&'t str> {
+    let head = text.get(..word.len())?;
+    let rest = &text[word.len()..];
+    let whole = !rest.starts_with(|c: char| c.is_alphanumeric());
+    (!word.is_empty() && head.eq_ignore_ascii_case(word) && whole).then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPANIONS: &[(&str, &str)] = &[("lyra", "Lyra"), ("orion", "Orion")];
+
+    #[test]
+    fn test_addressed_by_name() {
+        assert_eq!(addressed("Orion, what do you think?", COMPANIONS), Some("orion"));
+        assert_eq!(addressed("hey lyra can you look at this", COMPANIONS), Some("lyra"));
+        assert_eq!(addressed("@orion thoughts?", COMPANIONS), Some("orion"));
+        assert_eq!(addressed("what do you think, Orion?", COMPANIONS), Some("orion"));
+        assert_eq!(addressed("Lyra!", COMPANIONS), Some("lyra"));
+    }
+
+    #[test]
+    fn test_mentions_are_not_addresses() {
+        assert_eq!(addressed("Lyra said the tests pass", COMPANIONS), None);
+        assert_eq!(addressed("what does orion think?", COMPANIONS), None);
+        assert_eq!(addressed("Orionids are tonight, right?", COMPANIONS), None);
+        assert_eq!(addressed("Lyra, what do you think, Orion?", COMPANIONS), None);
+        assert_eq!(addressed("hey, anyone around?", COMPANIONS), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! The user's local date and time, and how long this session has run, so
+//! companions don't say "good morning" at 11 pm or forget it's the weekend
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, TimeZone, Timelike, Utc, Weekday};
+use tracing::warn;
+
+use crate::config::ClockConfig;
+
+pub struct Clock {
+    /// None follows the system timezone
+    offset: Option<FixedOffset>,
+    /// When the session started (unix seconds)
+    started: i64,
+}
+
+impl Clock {
+    pub fn new(config: &ClockConfig, started: i64) -> Self {
+        let offset = config.utc_offset.trim();
+        let offset = if offset.is_empty() {
+            None
+        } else {
+            match offset.parse::<FixedOffset>() {
+                Ok(offset) => Some(offset),
+                Err(err) => {
+                    warn!(offset, ?err, "Invalid [director.clock] utc_offset; using system time");
+                    None
+                }
+            }
+        };
+        Self { offset, started }
+    }
+
+    fn local(&self, now: i64) -> DateTime<FixedOffset> {
+        let utc = Utc.timestamp_opt(now, 0).single().unwrap_or_else(Utc::now);
+        match self.offset {
+            Some(offset) => utc.with_timezone(&offset),
+            None => utc.with_timezone(&Local).fixed_offset(),
+        }
+    }
+
+    /// The date, time of day and session length at `now`, for prompts
+    pub fn note(&self, now: i64) -> String {
+        let local = self.local(now);
+        let weekend = matches!(local.weekday(), Weekday::Sat | Weekday::Sun);
+        format!(
+            "It is {} ({}{}), {}.\nThe session started {} ago.",
+            local.format("%A, %B %-d %Y, %-I:%M %p"),
+            part_of_day(local.hour()),
+            if weekend { ", the weekend" } else { "" },
+            local.format("%:z"),
+            elapsed((now - self.started).max(0)),
+        )
+    }
+}
+
+fn part_of_day(hour: u32) -> &'static str {
+    match hour {
+        5..=11 => "morning",
+        12..=16 => "afternoon",
+        17..=20 => "evening",
+        21..=23 => "late evening",
+        _ => "the middle of the night",
+    }
+}
+
+/// `secs` as "2h 5m", "40m" or "under a minute"
+fn elapsed(secs: i64) -> String {
+    let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => "under a minute".to_string(),
+        (0, m) => format!("{m}m"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_uses_configured_offset() {
+        let config = ClockConfig {
+            enabled: true,
+            utc_offset: "-05:00".to_string(),
+        };
+        // Sunday 2026-10-18 04:30 UTC is Saturday 23:30 at -05:00
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 4, 30, 0).unwrap().timestamp();
+        let clock = Clock::new(&config, now - 2 * 3600 - 5 * 60);
+        assert_eq!(
+            clock.note(now),
+            "It is Saturday, October 17 2026, 11:30 PM (late evening, the weekend), -05:00.\n\
+             The session started 2h 5m ago."
+        );
+
+        let clock = Clock::new(&config, now - 30);
+        let morning = now + 9 * 3600;
+        assert!(clock.note(morning).contains("Sunday, October 18 2026, 8:30 AM (morning"));
+        assert!(clock.note(now).ends_with("under a minute ago."));
+    }
+}
@@ -0,0 +1,280 @@
+//! Conversation goals: what the companions are in the middle of with the
+//! user, kept between ticks so each evaluation picks up the thread instead
+//! of starting over from the screen. A companion's question is tracked
+//! until it's answered, and one left unanswered is followed up once and then
+//! let go; anything else is set with the `set_goal` tool.
+
+use serde_json::json;
+
+use crate::{llm::ToolDefinition, tts::split_sentences};
+
+/// The tool companions use to set and finish their goal
+pub const TOOL_NAME: &str = "set_goal";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoalKind {
+    /// The companion asked the user this, and the thread is open until a
+    /// companion speaks after the user answers
+    Question,
+    /// Set with `set_goal`, e.g. "helping debug the failing test"
+    Task,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub character_id: String,
+    pub kind: GoalKind,
+    pub text: String,
+    /// When it was set (unix seconds)
+    pub set_at: i64,
+    /// An unanswered question has waited long enough to follow up
+    pub due: bool,
+    /// When the companion followed up on an unanswered question
+    pub followed_up: Option<i64>,
+}
+
+impl Goal {
+    /// Whether a user message at `last_user_message` came after the goal
+    fn answered(&self, last_user_message: Option<i64>) -> bool {
+        last_user_message.is_some_and(|at| at > self.set_at)
+    }
+
+    /// The goal for a prompt, with `name` for the companion
+    pub fn describe(&self, name: &str, last_user_message: Option<i64>) -> String {
+        match self.kind {
+            GoalKind::Question if self.answered(last_user_message) => {
+                format!(
+                    "{name} asked the user \"{}\", and the user has replied",
+                    self.text
+                )
+            }
+            GoalKind::Question if self.followed_up.is_some() => {
+                format!(
+                    "{name} asked the user \"{}\" and followed up without an answer; let it go unless the user brings it up",
+                    self.text
+                )
+            }
+            GoalKind::Question if self.due => {
+                format!(
+                    "{name} asked the user \"{}\" a while ago and got no answer; {name} may follow up once, lightly, or let it go",
+                    self.text
+                )
+            }
+            GoalKind::Question => {
+                format!(
+                    "{name} asked the user \"{}\" and is waiting for an answer",
+                    self.text
+                )
+            }
+            GoalKind::Task => format!("{name} is {}", self.text),
+        }
+    }
+}
+
+/// Goals of every companion, at most one of each kind per companion
+#[derive(Debug, Default)]
+pub struct Goals {
+    goals: Vec<Goal>,
+}
+
+impl Goals {
+    /// Open goals, oldest first
+    pub fn active(&self) -> &[Goal] {
+        &self.goals
+    }
+
+    /// Drop goals set more than `ttl_secs` before `now`
+    pub fn expire(&mut self, now: i64, ttl_secs: i64) {
+        self.goals.retain(|goal| now - goal.set_at < ttl_secs);
+    }
+
+    /// Move unanswered questions along: one asked `follow_up_secs` ago is
+    /// due a follow-up, and one still unanswered `follow_up_secs` after
+    /// the follow-up is dropped. Returns the questions dropped.
+    pub fn review(
+        &mut self,
+        now: i64,
+        last_user_message: Option<i64>,
+        follow_up_secs: i64,
+    ) -> Vec<Goal> {
+        let mut dropped = Vec::new();
+        self.goals.retain_mut(|goal| {
+            if goal.kind != GoalKind::Question || goal.answered(last_user_message) {
+                return true;
+            }
+            match goal.followed_up {
+                Some(at) if now - at >= follow_up_secs => {
+                    dropped.push(goal.clone());
+                    false
+                }
+                Some(_) => true,
+                None => {
+                    goal.due = now - goal.set_at >= follow_up_secs;
+                    true
+                }
+            }
+        });
+        dropped
+    }
+
+    /// Note a reply `character_id` just gave. Questions the user has
+    /// answered since are settled, a reply to the companion's own question
+    /// that was due counts as its follow-up, and a question in `text` is
+    /// tracked in place of the companion's last one.
+    pub fn record_reply(
+        &mut self,
+        character_id: &str,
+        text: &str,
+        last_user_message: Option<i64>,
+        now: i64,
+    ) {
+        self.goals
+            .retain(|goal| goal.kind != GoalKind::Question || !goal.answered(last_user_message));
+        let own_question = self
+            .goals
+            .iter_mut()
+            .find(|goal| goal.character_id == character_id && goal.kind == GoalKind::Question);
+        let mut followed_up = None;
+        if let Some(goal) = own_question {
+            if goal.due {
+                goal.due = false;
+                goal.followed_up = Some(now);
+            }
+            followed_up = goal.followed_up;
+        }
+        let Some(question) = last_question(text) else {
+            return;
+        };
+        // Asking again (or asking something else) while still unanswered is
+        // the follow-up, not a fresh question
+        self.set(character_id, GoalKind::Question, question, now);
+        if let Some(goal) = self.goals.last_mut() {
+            goal.followed_up = followed_up;
+        }
+    }
+
+    /// Replace `character_id`'s goal of this kind
+    pub fn set(&mut self, character_id: &str, kind: GoalKind, text: String, now: i64) {
+        self.finish(character_id, &kind);
+        self.goals.push(Goal {
+            character_id: character_id.to_string(),
+            kind,
+            text,
+            set_at: now,
+            due: false,
+            followed_up: None,
+        });
+    }
+
+    /// Drop `character_id`'s goal of this kind
+    pub fn finish(&mut self, character_id: &str, kind: &GoalKind) {
+        self.goals
+            .retain(|goal| goal.character_id != character_id || &goal.kind != kind);
+    }
+
+    /// Apply a `set_goal` call from `character_id` with JSON `arguments`
+    pub fn apply_tool_call(&mut self, character_id: &str, arguments: &str, now: i64) {
+        let args: serde_json::Value = serde_json::from_str(arguments).unwrap_or_default();
+        let done = args.get("done").and_then(|v| v.as_bool()).unwrap_or(false);
+        let goal = args
+            .get("goal")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|goal| !goal.is_empty());
+        match goal {
+            Some(goal) if !done => {
+                self.set(character_id, GoalKind::Task, goal.to_string(), now);
+            }
+            _ => self.finish(character_id, &GoalKind::Task),
+        }
+    }
+}
+
+/// The last sentence of `text` that asks something
+pub fn last_question(text: &str) -> Option<String> {
+    split_sentences(text)
+        .into_iter()
+        .rev()
+        .find(|sentence| sentence.trim_end().ends_with('?'))
+}
+
+/// Definition of the `set_goal` tool
+pub fn goal_tool() -> ToolDefinition {
+    ToolDefinition::new(
+        TOOL_NAME,
+        "Keep track of what you are doing with the user across turns, like \"helping debug the failing test\", so you stay on it. Set 'done' once it is finished or the user has moved on.",
+        json!({
+            "type": "object",
+            "properties": {
+                "goal": {
+                    "type": "string",
+                    "description": "What you are doing, as a short phrase starting with a verb in -ing form"
+                },
+                "done": {
+                    "type": "boolean",
+                    "description": "The goal is finished or dropped"
+                }
+            },
+            "required": ["goal"],
+            "additionalProperties": false
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_goals_follow_the_conversation() {
+        let mut goals = Goals::default();
+        goals.record_reply("aria", "Nice. Did the tests pass?", None, 100);
+        goals.apply_tool_call("aria", r#"{"goal": "helping debug the parser"}"#, 100);
+        assert_eq!(goals.active().len(), 2);
+        assert_eq!(
+            goals.active()[0].describe("Aria", None),
+            "Aria asked the user \"Did the tests pass?\" and is waiting for an answer"
+        );
+        assert_eq!(
+            goals.active()[1].describe("Aria", Some(110)),
+            "Aria is helping debug the parser"
+        );
+
+        // The user answered, then a companion spoke: the question is settled
+        goals.record_reply("orion", "Ship it.", Some(110), 120);
+        assert_eq!(goals.active().len(), 1);
+        goals.apply_tool_call("aria", r#"{"goal": "helping debug", "done": true}"#, 130);
+        assert!(goals.active().is_empty());
+
+        goals.record_reply("aria", "Coffee?", None, 200);
+        goals.expire(2000, 1800);
+        assert!(goals.active().is_empty());
+    }
+
+    #[test]
+    fn test_unanswered_question_is_followed_up_then_dropped() {
+        let mut goals = Goals::default();
+        goals.record_reply("aria", "Did the tests pass?", None, 100);
+        assert!(goals.review(300, None, 300).is_empty());
+        assert!(!goals.active()[0].due);
+
+        assert!(goals.review(400, None, 300).is_empty());
+        assert!(goals.active()[0].describe("Aria", None).contains("may follow up once"));
+
+        // Asking again is the follow-up
+        goals.record_reply("aria", "Any luck with the tests?", None, 450);
+        assert_eq!(goals.active()[0].followed_up, Some(450));
+        assert!(goals.active()[0].describe("Aria", None).contains("let it go"));
+        assert!(goals.review(700, None, 300).is_empty());
+
+        let dropped = goals.review(750, None, 300);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].text, "Any luck with the tests?");
+        assert!(goals.active().is_empty());
+
+        // An answer settles it before it's dropped
+        goals.record_reply("aria", "Coffee?", None, 800);
+        assert!(goals.review(1100, Some(900), 300).is_empty());
+        assert!(!goals.active()[0].due);
+    }
+}
@@ -0,0 +1,114 @@
+//! Tool results for the response loop: when the response model calls tools,
+//! each call is answered with a tool message so the model can carry on,
+//! looking something up first and then replying with what it found
+
+use chrono::Utc;
+use serde_json::json;
+use tracing::{debug, warn};
+
+use super::{Director, format_memory, goals};
+use crate::{
+    ariaos,
+    llm::{ChatMessage, ToolCall, ToolDefinition},
+};
+
+/// The tool companions use to look through older memories
+pub const TOOL_NAME: &str = "search_memory";
+
+/// Definition of the `search_memory` tool
+pub fn memory_tool() -> ToolDefinition {
+    ToolDefinition::new(
+        TOOL_NAME,
+        "Search your memories of earlier conversations and what the user was doing, e.g. \"the bug in the parser\" or \"user's trip\". Use it before answering when the user refers to something you don't see in the chat.",
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for, in a few words"
+                }
+            },
+            "required": ["query"],
+            "additionalProperties": false
+        }),
+    )
+}
+
+impl Director {
+    /// Answer each of `calls`, made by `responder_id`, with a tool message:
+    /// memories found for `search_memory`, and for ARIAOS tools whether the
+    /// command was queued. ARIAOS commands not already sent mid-stream
+    /// (`streamed`) are queued right away through `early_commands`, so they
+    /// stand whatever happens to the reply; the calls queued are returned
+    /// with the messages. Execution is asynchronous, so "Queued." doesn't
+    /// promise the command succeeded.
+    pub(super) async fn tool_results(
+        &self,
+        responder_id: &str,
+        calls: &[ToolCall],
+        streamed: &[ToolCall],
+    ) -> (Vec<ChatMessage>, Vec<ToolCall>) {
+        let mut results = Vec::with_capacity(calls.len());
+        let mut run = Vec::new();
+        for call in calls {
+            let result = match call.function.name.as_str() {
+                TOOL_NAME => self.search_memory(responder_id, &call.function.arguments).await,
+                goals::TOOL_NAME => "Noted.".to_string(),
+                name => match ariaos::tool_call_to_command(call) {
+                    Ok(Some(_)) if streamed.iter().any(|s| same_call(s, call)) => {
+                        "Queued.".to_string()
+                    }
+                    Ok(Some(command)) => {
+                        let sent = self.early_commands.as_ref().is_some_and(|sink| {
+                            sink.send((responder_id.to_string(), vec![command])).is_ok()
+                        });
+                        if sent {
+                            run.push(call.clone());
+                            "Queued.".to_string()
+                        } else {
+                            "Not done yet: this only happens if your reply is said.".to_string()
+                        }
+                    }
+                    Ok(None) => format!("Error: there is no tool named {name}"),
+                    Err(err) => format!("Error: {err}"),
+                },
+            };
+            debug!(tool = %call.function.name, %result, "Tool result");
+            results.push(ChatMessage::tool(&call.id, result));
+        }
+        (results, run)
+    }
+
+    async fn search_memory(&self, responder_id: &str, arguments: &str) -> String {
+        let args: serde_json::Value = serde_json::from_str(arguments).unwrap_or_default();
+        let Some(query) = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|query| !query.is_empty())
+        else {
+            return "Error: search_memory requires 'query'".to_string();
+        };
+        let now = Utc::now().timestamp();
+        let memories = self
+            .storage
+            .recall(query, now, Some(responder_id), &self.config.recall)
+            .await
+            .unwrap_or_else(|err| {
+                warn!(?err, "Failed to search memories");
+                Vec::new()
+            });
+        if memories.is_empty() {
+            return format!("No memories about \"{query}\".");
+        }
+        memories
+            .iter()
+            .map(format_memory)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn same_call(a: &ToolCall, b: &ToolCall) -> bool {
+    a.function.name == b.function.name && a.function.arguments == b.function.arguments
+}
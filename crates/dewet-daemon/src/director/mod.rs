@@ -1,25 +1,59 @@
-use std::io::Cursor;
+mod address;
+mod clock;
+mod goals;
+mod lookup;
+mod profile;
+mod prompts;
+mod reflection;
+mod regenerate;
+mod replay;
+mod topics;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 use anyhow::{Result, anyhow};
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD as BASE64;
-use image::{DynamicImage, ImageFormat, RgbaImage};
+use chrono::{DateTime, Local, Utc};
+use image::{DynamicImage, RgbaImage};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-
+use tokio::sync::mpsc::{self, UnboundedSender};
 use tracing::{debug, info, warn};
 
 use crate::{
     ariaos::{self, AriaosCommand},
     bridge::ChatPacket,
-    character::{CharacterSpec, LoadedCharacter},
-    config::DirectorConfig,
-    llm::{ChatMessage, LlmClients, strip_images_for_logging},
+    character::{
+        CharacterSpec, LoadedCharacter, Mood, MoodEvent,
+        examples::{self, ExampleTurn},
+        lore,
+        mood::ChatMoods,
+        relationship,
+    },
+    config::{CompanionTiming, DirectorConfig, Gate, PipelineMode},
+    focus::Focus,
+    llm::{
+        ChatCompletionWithTools, ChatMessage, LlmClients, SharedLlm, TokenUsage, ToolCall,
+        ToolDefinition, encode_image, strip_images_for_logging, track_reasoning, track_usage,
+    },
     observation::Observation,
-    storage::{Storage, StoredDecision},
+    storage::{
+        DecisionOutcome, Episode, EpisodeKind, Storage, StoredDecision, StoredLlmCall, UserProfile,
+    },
+    vision::hash_distance,
 };
 
+use self::clock::Clock;
+use self::goals::Goals;
+pub use self::profile::ProfileLearner;
+pub use self::reflection::{ReflectionJob, Reflector};
+pub use self::replay::ReplayStep;
+use self::prompts::PromptTemplates;
+use self::regenerate::LastReply;
+use self::topics::Topics;
+
 /// Result of VLA (Vision-Language Analysis)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VlaResult {
@@ -27,6 +61,9 @@ pub struct VlaResult {
     pub significant_change: bool,
     /// What changed (or "nothing significant" if no change)
     pub description: String,
+    /// What the user is doing, from the webcam panel when there is one
+    #[serde(default)]
+    pub user_presence: Option<String>,
 }
 
 /// Eligibility status for a companion
@@ -44,12 +81,62 @@ impl CompanionEligibility {
     }
 }
 
+/// How long a wake word keeps companions eligible if no reply follows
+const WAKE_BOOST: Duration = Duration::from_secs(20);
+
+/// Moods the arbiter may suggest; each has a puppet expression or voice style
+const MOODS: &[&str] = Mood::NAMES;
+
+/// A "no significant change" verdict and the frame it was given for
+struct CachedVla {
+    hash: u64,
+    at: Instant,
+    result: VlaResult,
+}
+
+/// The user said the wake word
+struct Wake {
+    at: Instant,
+    /// Companion the wake word addresses, if it names one
+    character: Option<String>,
+}
+
 pub struct Director {
     storage: Storage,
     clients: LlmClients,
     config: DirectorConfig,
     characters: Vec<LoadedCharacter>,
+    prompts: PromptTemplates,
+    /// Receives the responder's id and ARIAOS commands as soon as streamed
+    /// tool calls complete
+    early_commands: Option<UnboundedSender<(String, Vec<AriaosCommand>)>>,
+    /// Receives the responder's id and each piece of reply text as it streams
+    text_deltas: Option<UnboundedSender<(String, String)>>,
+    /// Receives each reply said, to reflect on
+    reflections: Option<UnboundedSender<ReflectionJob>>,
     last_decision: Instant,
+    woken: Option<Wake>,
+    /// The next evaluation may skip the rate limit
+    scene_change: bool,
+    /// The last evaluation was cancelled by a new user message, so the next
+    /// one may skip the rate limit
+    superseded: bool,
+    /// Replies chained onto another companion's since the last `evaluate`
+    chain: usize,
+    /// Do-not-disturb state as of the last `evaluate`
+    focus: Option<Focus>,
+    /// Open questions and what the companions are helping with
+    goals: Goals,
+    /// Chat already turned into mood events
+    chat_moods: ChatMoods,
+    /// The last unremarkable VLA verdict, with `[director.vla_cache]`
+    vla_cache: Option<CachedVla>,
+    /// What the companions commented on unprompted
+    topics: Topics,
+    /// The last reply spoken, for `regenerate`
+    last_reply: Option<LastReply>,
+    /// The user's local time and when the session started
+    clock: Clock,
 }
 
 impl Director {
@@ -59,91 +146,202 @@ impl Director {
         director_config: DirectorConfig,
         characters: Vec<LoadedCharacter>,
     ) -> Self {
+        let prompts = PromptTemplates::load(Path::new(&director_config.prompts_dir));
+        let clock = Clock::new(&director_config.clock, Utc::now().timestamp());
         Self {
             storage,
             clients,
             config: director_config,
             characters,
+            prompts,
+            early_commands: None,
+            text_deltas: None,
+            reflections: None,
             last_decision: Instant::now()
                 .checked_sub(Duration::from_secs(3600))
                 .unwrap_or_else(Instant::now),
+            woken: None,
+            scene_change: false,
+            superseded: false,
+            chain: 0,
+            focus: None,
+            goals: Goals::default(),
+            chat_moods: ChatMoods::default(),
+            vla_cache: None,
+            topics: Topics::default(),
+            last_reply: None,
+            clock,
         }
     }
 
-    pub fn characters(&self) -> &[LoadedCharacter] {
-        &self.characters
+    /// The user said the wake word. Until the next reply (or `WAKE_BOOST`
+    /// passes) this counts as the user addressing the companions: rate limits,
+    /// gates, and cooldowns are skipped, and if `character` is set only that
+    /// companion is offered to the arbiter.
+    pub fn wake(&mut self, character: Option<String>) {
+        match &character {
+            Some(addressed) => self.feel(addressed, MoodEvent::Woken),
+            None => self.feel_for_all(MoodEvent::Woken),
+        }
+        self.woken = Some(Wake {
+            at: Instant::now(),
+            character,
+        });
     }
 
-    /// Step 1: VLA (Vision-Language Analysis) - determine if something significant changed
-    pub async fn analyze_vla(&self, observation: &Observation) -> Result<(VlaResult, PromptLog)> {
-        let composite = observation
-            .composite
-            .as_ref()
-            .ok_or_else(|| anyhow!("No composite image available for VLA"))?;
+    /// The screen changed past `[vision] diff_threshold`; with
+    /// `evaluate_on_scene_change` the next evaluation isn't rate limited
+    pub fn scene_changed(&mut self) {
+        self.scene_change = self.config.evaluate_on_scene_change;
+        self.feel_for_all(MoodEvent::SceneChange);
+    }
 
-        // Build image list: composite first, then ARIAOS if available
-        let mut images = vec![encode_rgba_to_base64(composite)?];
-        let has_ariaos = observation.ariaos.is_some();
-        if let Some(ariaos) = &observation.ariaos {
-            images.push(encode_rgba_to_base64(ariaos)?);
+    /// Apply `event` to every companion's mood
+    fn feel_for_all(&mut self, event: MoodEvent) {
+        let ids: Vec<String> = self.characters.iter().map(|c| c.spec.id.clone()).collect();
+        for id in ids {
+            self.feel(&id, event);
+        }
+    }
+
+    /// Apply `event` to `character_id`'s mood, with `[director.mood]` enabled
+    fn feel(&mut self, character_id: &str, event: MoodEvent) {
+        if !self.config.mood.enabled {
+            return;
+        }
+        let half_life = self.config.mood.half_life();
+        let character = self
+            .characters
+            .iter_mut()
+            .find(|c| c.spec.id == character_id);
+        if let Some(character) = character {
+            character.state.mood.apply(event, Instant::now(), half_life);
+            debug!(character_id, ?event, mood = ?character.state.mood, "Mood changed");
+        }
+    }
+
+    /// Move `character_id`'s relationship score with the sentiment of the
+    /// user's `reply` to them, with `[director.relationship]` enabled
+    fn relate(&mut self, character_id: &str, reply: &str) {
+        if !self.config.relationship.enabled {
+            return;
+        }
+        let sentiment = relationship::sentiment(reply);
+        if sentiment == 0.0 {
+            return;
         }
+        let Some(character) = self
+            .characters
+            .iter_mut()
+            .find(|c| c.spec.id == character_id)
+        else {
+            return;
+        };
+        character.state.relate(sentiment, self.config.relationship.rate);
+        debug!(
+            character_id,
+            sentiment,
+            score = character.state.relationship_score,
+            "Relationship changed"
+        );
+        let stored = character
+            .state
+            .to_stored(character_id, self.config.mood.half_life());
+        self.storage.save_character_state(&stored);
+    }
 
-        let prompt = if has_ariaos {
-            r#"You are a CHANGE DETECTOR. Your ONLY job: determine if something MEANINGFULLY DIFFERENT happened.
+    /// A companion's mood for prompts, e.g. "a little happy"; None when
+    /// neutral or moods are off
+    fn mood_of(&self, character: &LoadedCharacter) -> Option<String> {
+        let mood = &character.state.mood;
+        mood.describe(Instant::now(), self.config.mood.half_life())
+            .filter(|_| self.config.mood.enabled)
+    }
 
-**IMAGE 1 - COMPOSITE** layout:
-- DESKTOP (top-left): Current screen
-- PREV 1/2/3: Previous screenshots
+    /// The user sent a message while an evaluation was running, and it was
+    /// dropped; the fresh evaluation that answers it isn't rate limited
+    pub fn supersede(&mut self) {
+        self.superseded = true;
+    }
 
-**IMAGE 2 - ARIAOS**: Companion's dashboard
+    /// Why the user shouldn't be interrupted, as of the last evaluation
+    pub fn focus(&self) -> Option<&Focus> {
+        self.focus.as_ref()
+    }
 
-## YOUR TASK
-Compare DESKTOP directly to the PREV panels. Answer ONE question:
-**Is DESKTOP showing something MEANINGFULLY DIFFERENT from PREV?**
+    fn active_wake(&self) -> Option<&Wake> {
+        self.woken
+            .as_ref()
+            .filter(|wake| wake.at.elapsed() < WAKE_BOOST)
+    }
 
-### significant_change: TRUE only if:
-- User opened a DIFFERENT application (not just the same app with minor changes)
-- Completely NEW content appeared (new file, new webpage, new document)
-- An error, alert, or notification popped up
-- The ARIAOS notes content changed
+    /// Execute ARIAOS tool calls while the response is still generating.
+    /// Commands sent here are omitted from `Decision::Speak::tool_calls`, and
+    /// run even if the audit later rejects the reply.
+    pub fn set_early_commands(&mut self, sink: UnboundedSender<(String, Vec<AriaosCommand>)>) {
+        self.early_commands = Some(sink);
+    }
 
-### significant_change: FALSE if:
-- Same application, same general content
-- Cursor position changed
-- Scroll position changed slightly  
-- Chat messages updated (we already see this in chat history)
-- Time passed but nothing substantive changed
-- Screen looks "basically the same"
+    /// Forward reply text as it streams, before the final `Decision::Speak`.
+    /// Nothing is sent when an audit model will review the reply.
+    pub fn set_text_deltas(&mut self, sink: UnboundedSender<(String, String)>) {
+        self.text_deltas = Some(sink);
+    }
 
-**DEFAULT TO FALSE.** Only mark true if you can point to a specific, concrete difference that a human would notice and find noteworthy."#
-        } else {
-            r#"You are a CHANGE DETECTOR. Your ONLY job: determine if something MEANINGFULLY DIFFERENT happened.
-
-**IMAGE 1 - COMPOSITE** layout:
-- DESKTOP (top-left): Current screen
-- PREV 1/2/3: Previous screenshots
-
-## YOUR TASK
-Compare DESKTOP directly to the PREV panels. Answer ONE question:
-**Is DESKTOP showing something MEANINGFULLY DIFFERENT from PREV?**
-
-### significant_change: TRUE only if:
-- User opened a DIFFERENT application (not just the same app with minor changes)
-- Completely NEW content appeared (new file, new webpage, new document)
-- An error, alert, or notification popped up
-
-### significant_change: FALSE if:
-- Same application, same general content
-- Cursor position changed
-- Scroll position changed slightly
-- Chat messages updated (we already see this in chat history)
-- Time passed but nothing substantive changed
-- Screen looks "basically the same"
-
-**DEFAULT TO FALSE.** Only mark true if you can point to a specific, concrete difference that a human would notice and find noteworthy."#
-        };
+    /// Reflect on each reply once it has passed the audit and style rules
+    pub fn set_reflections(&mut self, sink: UnboundedSender<ReflectionJob>) {
+        self.reflections = Some(sink);
+    }
+
+    pub fn characters(&self) -> &[LoadedCharacter] {
+        &self.characters
+    }
+
+    /// The companion who says a scheduled message: `character` if it names
+    /// one, else the first. It counts as that companion having spoken
+    pub fn scheduled_speaker(&mut self, character: Option<&str>) -> Option<String> {
+        let speaker = match character {
+            Some(id) => self.characters.iter_mut().find(|c| c.spec.id == id),
+            None => self.characters.first_mut(),
+        }?;
+        speaker.state.update_last_spoke();
+        Some(speaker.spec.id.clone())
+    }
+
+    /// Run an LLM call, collecting its token usage and (if enabled) reasoning
+    async fn tracked<F: Future>(
+        &self,
+        future: F,
+    ) -> (F::Output, Option<TokenUsage>, Option<String>) {
+        let ((output, usage), reasoning) = track_reasoning(track_usage(future)).await;
+        (output, usage, reasoning.filter(|_| self.config.log_reasoning))
+    }
+
+    /// Step 1: VLA (Vision-Language Analysis) - determine if something significant changed
+    pub async fn analyze_vla(&self, observation: &Observation) -> Result<(VlaResult, PromptLog)> {
+        let composite = observation
+            .composite
+            .as_ref()
+            .ok_or_else(|| anyhow!("No composite image available for VLA"))?;
 
-        let schema = json!({
+        let images = self.perception_images(composite, observation)?;
+        let has_ariaos = observation.ariaos.is_some();
+        let has_camera = observation.frame.camera.is_some();
+
+        let prompt = self
+            .prompts
+            .render(
+                prompts::VLA,
+                json!({
+                    "has_ariaos": has_ariaos,
+                    "has_close_up": self.close_up(observation).is_some(),
+                    "has_camera": has_camera,
+                    "screen_text": observation.screen_summary.text,
+                    "input_activity": input_activity(observation),
+                }),
+            )?;
+
+        let mut schema = json!({
             "type": "object",
             "properties": {
                 "significant_change": {
@@ -157,39 +355,136 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
             },
             "required": ["significant_change", "description"]
         });
+        if has_camera {
+            add_presence(&mut schema);
+        }
 
-        let response = self
-            .clients
-            .vla
-            .complete_vision_json(&self.clients.vla_model, prompt, images, schema)
-            .await?;
+        let started = Instant::now();
+        let (response, usage, reasoning) = self.tracked(self.clients.vla.complete_vision_json(
+            &self.clients.vla_model,
+            &prompt,
+            images,
+            schema,
+        ))
+        .await;
+        let response = response?;
 
         let response_str = serde_json::to_string_pretty(&response).unwrap_or_default();
         let prompt_log = PromptLog {
             model_type: "vla".to_string(),
             model_name: self.clients.vla_model.clone(),
-            prompt: prompt.to_string(),
+            prompt,
             response: response_str,
+            latency_ms: started.elapsed().as_millis() as u64,
+            usage,
+            reasoning,
         };
 
         let vla: VlaResult = serde_json::from_value(response)?;
         info!(
             significant_change = vla.significant_change,
             description = %vla.description,
+            presence = ?vla.user_presence,
             "VLA complete"
         );
 
         Ok((vla, prompt_log))
     }
 
+    /// Step 0 (with `[director.screening]`): the reason to pass when the
+    /// screen barely changed, or the `[llm.screen]` model sees nothing worth
+    /// escalating; None to go on to the VLA and arbiter
+    async fn screen(
+        &self,
+        observation: &Observation,
+        prompt_logs: &mut Vec<PromptLog>,
+    ) -> Option<String> {
+        let min_diff_score = self.config.screening.min_diff_score;
+        if observation.frame.diff_score < min_diff_score {
+            let reasoning = format!(
+                "Screened: screen changed {:.3} (below {:.3})",
+                observation.frame.diff_score, min_diff_score
+            );
+            self.log_pass(&reasoning, DecisionOutcome::Screened);
+            return Some(reasoning);
+        }
+        let (client, model) = self.clients.screen.as_ref()?;
+
+        let prompt = self
+            .prompts
+            .render(
+                prompts::SCREEN,
+                json!({
+                    "changed_percent": (observation.frame.diff_score * 100.0).round() as u32,
+                    "screen_text": observation.screen_summary.text,
+                    "input_activity": input_activity(observation),
+                    "silence_note": silence_note(observation),
+                    "chat": format_chat(&observation.recent_chat),
+                }),
+            )
+            .inspect_err(|err| warn!(?err, "Failed to render the screening prompt"))
+            .ok()?;
+        let started = Instant::now();
+        let (response, usage, reasoning) = self
+            .tracked(client.complete_json(model, &prompt, screen_schema()))
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                warn!(?err, "Screening failed, escalating");
+                return None;
+            }
+        };
+        prompt_logs.push(PromptLog {
+            model_type: "screen".to_string(),
+            model_name: model.clone(),
+            prompt,
+            response: serde_json::to_string_pretty(&response).unwrap_or_default(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            usage,
+            reasoning,
+        });
+
+        let verdict: ScreenVerdict = serde_json::from_value(response)
+            .inspect_err(|err| warn!(?err, "Malformed screening verdict, escalating"))
+            .ok()?;
+        info!(escalate = verdict.escalate, reason = %verdict.reason, "Screening complete");
+        if verdict.escalate {
+            return None;
+        }
+        let reasoning = format!("Screened: {}", verdict.reason);
+        self.log_pass(&reasoning, DecisionOutcome::Screened);
+        Some(reasoning)
+    }
+
+    /// The last "no significant change" verdict, if it is recent enough and
+    /// given for what is perceptually the same frame
+    fn cached_vla(&self, observation: &Observation) -> Option<VlaResult> {
+        let config = &self.config.vla_cache;
+        let cached = self.vla_cache.as_ref().filter(|_| config.enabled)?;
+        let same_frame = hash_distance(cached.hash, observation.frame.hash) <= config.max_distance;
+        (same_frame && cached.at.elapsed() < config.ttl()).then(|| cached.result.clone())
+    }
+
     /// Step 2: Determine eligibility for each companion (algorithmic, no LLM)
     fn compute_eligibility(
         &self,
         observation: &Observation,
         vla: &VlaResult,
     ) -> Vec<(String, CompanionEligibility)> {
+        if !self.config.stages.eligibility {
+            return self
+                .characters
+                .iter()
+                .map(|c| {
+                    let reason = "Eligibility stage disabled".to_string();
+                    (c.spec.id.clone(), CompanionEligibility::Allow { reason })
+                })
+                .collect();
+        }
         let last_speaker = observation.recent_chat.last().map(|p| p.sender.as_str());
-        let long_silence_threshold = self.config.cooldown_after_speak();
+        let wake = self.active_wake();
+        let scheduled = observation.scheduled.last();
 
         self.characters
             .iter()
@@ -197,8 +492,28 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                 let id = c.spec.id.clone();
                 let is_last_speaker = last_speaker == Some(id.as_str());
 
-                let eligibility = if is_last_speaker {
+                let eligibility = if let Some(wake) = wake {
+                    match &wake.character {
+                        Some(addressed) if addressed != &id => CompanionEligibility::Stop {
+                            reason: format!("Wake word addressed {}", addressed),
+                        },
+                        _ => CompanionEligibility::Allow {
+                            reason: "Woken by wake word".to_string(),
+                        },
+                    }
+                } else if let Some(scheduled) = scheduled {
+                    match &scheduled.character {
+                        Some(addressed) if addressed != &id => CompanionEligibility::Stop {
+                            reason: format!("Scheduled for {}", addressed),
+                        },
+                        _ => CompanionEligibility::Allow {
+                            reason: format!("Scheduled: {}", scheduled.text),
+                        },
+                    }
+                } else if is_last_speaker {
                     // This companion spoke last
+                    let timing = self.timing(c);
+                    let long_silence_threshold = timing.cooldown_after_speak;
                     let time_since_spoke = c.state.time_since_last_spoke();
                     let long_time = time_since_spoke
                         .map(|d| d > long_silence_threshold)
@@ -212,7 +527,7 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                                 long_silence_threshold.as_secs()
                             ),
                         }
-                    } else if vla.significant_change {
+                    } else if vla.significant_change && timing.speak_on_scene_change {
                         CompanionEligibility::Allow {
                             reason: format!(
                                 "Last speaker, but VLA-YES: {}",
@@ -234,6 +549,15 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                     }
                 };
 
+                let eligibility = match (eligibility, self.question_budget(c)) {
+                    (CompanionEligibility::Allow { reason }, Some(budget)) => {
+                        CompanionEligibility::Allow {
+                            reason: format!("{reason}; {budget}"),
+                        }
+                    }
+                    (eligibility, _) => eligibility,
+                };
+
                 debug!(
                     companion = %id,
                     eligibility = ?eligibility,
@@ -247,9 +571,58 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
 
     pub async fn evaluate(&mut self, observation: &Observation) -> Result<EvaluateResult> {
         let mut prompt_logs = Vec::new();
+        self.chain = 0;
+        // Prompt edits take effect on the next tick
+        self.prompts.reload_if_changed();
+        if observation.focus != self.focus {
+            match &observation.focus {
+                Some(focus) => info!(reason = %focus.reason, mode = ?focus.mode, "User focused"),
+                None => info!("User no longer focused"),
+            }
+            self.focus = observation.focus.clone();
+        }
+
+        let now = Utc::now().timestamp();
+        let ttl = self.config.goals.ttl_minutes as i64 * 60;
+        self.goals.expire(now, ttl);
+        let follow_up = self.config.goals.follow_up_minutes as i64 * 60;
+        if follow_up > 0 {
+            let last_user_message = last_user_message(observation);
+            for goal in self.goals.review(now, last_user_message, follow_up) {
+                info!(
+                    character_id = %goal.character_id,
+                    question = %goal.text,
+                    "Letting an unanswered question go"
+                );
+            }
+        }
+        self.topics
+            .expire(now, self.config.topics.remember_minutes as i64 * 60);
+        let ignored_after = self.config.mood.ignored_after_secs as i64;
+        let chat = &observation.recent_chat;
+        for (character_id, event) in self.chat_moods.events(chat, now, ignored_after) {
+            if event == MoodEvent::Answered {
+                let reply = chat.last().map_or("", |p| p.content.as_str());
+                self.relate(&character_id, reply);
+            }
+            self.feel(&character_id, event);
+        }
+
+        // A wake word counts as the user addressing the companions
+        let woken = self.active_wake().is_some();
+
+        let scene_change = std::mem::take(&mut self.scene_change);
+        let superseded = std::mem::take(&mut self.superseded);
+        // Something scheduled for now is a reason to speak whatever the screen shows
+        let scheduled = !observation.scheduled.is_empty();
 
         // Rate limiting check
-        if self.last_decision.elapsed() < self.config.min_decision_interval() {
+        if !woken
+            && !scene_change
+            && !superseded
+            && !scheduled
+            && self.last_decision.elapsed() < self.config.min_decision_interval()
+        {
             return Ok(EvaluateResult {
                 decision: Decision::Pass {
                     reasoning: "Rate limited".to_string(),
@@ -262,13 +635,114 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
 
         // Check if user just spoke (unanswered message)
         let last_speaker = observation.recent_chat.last().map(|p| p.sender.as_str());
-        let user_unanswered = last_speaker == Some("user");
+        let user_unanswered = last_speaker == Some("user") || woken;
+        // Copying text (often an error message) hints the user wants help
+        let copied = !observation.clipboard.is_empty();
+
+        // Cheap checks, in the configured order, that can end the tick
+        // before the vision models run
+        let pressing = user_unanswered || copied || scheduled || scene_change || superseded;
+        for gate in &self.config.stages.gates {
+            let passed = match gate {
+                // Do not disturb, unless the user is talking to the companions
+                Gate::DoNotDisturb => {
+                    let silenced = observation
+                        .focus
+                        .as_ref()
+                        .filter(|f| f.silences() && !user_unanswered);
+                    silenced.map(|focus| {
+                        debug!(reason = %focus.reason, "Do not disturb - skipping VLA and arbiter");
+                        let reasoning = format!("Do not disturb: {}", focus.reason);
+                        self.log_pass(&reasoning, DecisionOutcome::DoNotDisturb);
+                        reasoning
+                    })
+                }
+                // Same screen as the last few frames and nobody to answer:
+                // nothing for the VLA or arbiter to look at
+                Gate::Duplicate => {
+                    let unchanged =
+                        observation.frame.duplicate && !user_unanswered && !copied && !scheduled;
+                    unchanged.then(|| {
+                        debug!("Screen unchanged - skipping VLA and arbiter");
+                        let reasoning = "No change: screen matches the previous frames";
+                        self.log_pass(reasoning, DecisionOutcome::Duplicate);
+                        reasoning.to_string()
+                    })
+                }
+                // With nothing to answer, a quiet screen isn't worth the
+                // vision models
+                Gate::Screening if self.config.screening.enabled && !pressing => {
+                    let screened = self.screen(observation, &mut prompt_logs).await;
+                    if let Some(reasoning) = &screened {
+                        debug!(%reasoning, "Screened out - skipping VLA and arbiter");
+                    }
+                    screened
+                }
+                Gate::Screening => None,
+            };
+            if let Some(reasoning) = passed {
+                return Ok(EvaluateResult {
+                    decision: Decision::Pass {
+                        reasoning,
+                        urgency: 0.0,
+                    },
+                    prompt_logs,
+                });
+            }
+        }
+
+        // Combined mode: one vision call to the arbiter model answers both
+        // "what changed?" and "who speaks?"
+        let combined = self.config.pipeline_mode == PipelineMode::Combined
+            && self.config.stages.vla
+            && observation.composite.is_some()
+            && self.clients.capabilities(&self.clients.arbiter_model).vision;
+        let mut combined_decision = None;
 
         // STEP 1: VLA - Vision-Language Analysis
-        let vla = if observation.composite.is_some() {
+        let vla = if combined {
+            // Offer every companion who could speak if the screen changed; the
+            // model's own verdict is applied to eligibility below
+            let pending = VlaResult {
+                significant_change: true,
+                description: "only if significant_change is true".to_string(),
+                user_presence: None,
+            };
+            let candidates: Vec<_> = self
+                .compute_eligibility(observation, &pending)
+                .into_iter()
+                .filter(|(_, e)| e.is_allowed())
+                .collect();
+            let (vla, arbiter, log) = self
+                .analyze_combined(observation, &candidates, user_unanswered)
+                .await?;
+            prompt_logs.push(log);
+            combined_decision = Some(arbiter);
+            vla
+        } else if !self.config.stages.vla {
+            VlaResult {
+                significant_change: false,
+                description: "VLA stage disabled".to_string(),
+                user_presence: None,
+            }
+        } else if !self.clients.capabilities(&self.clients.vla_model).vision {
+            VlaResult {
+                significant_change: false,
+                description: "VLA model has no vision support".to_string(),
+                user_presence: None,
+            }
+        } else if let Some(cached) = self.cached_vla(observation) {
+            debug!(description = %cached.description, "Screen unchanged - reusing VLA verdict");
+            cached
+        } else if observation.composite.is_some() {
             match self.analyze_vla(observation).await {
                 Ok((result, log)) => {
                     prompt_logs.push(log);
+                    self.vla_cache = (!result.significant_change).then(|| CachedVla {
+                        hash: observation.frame.hash,
+                        at: Instant::now(),
+                        result: result.clone(),
+                    });
                     result
                 }
                 Err(err) => {
@@ -276,6 +750,7 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                     VlaResult {
                         significant_change: false,
                         description: format!("VLA failed: {}", err),
+                        user_presence: None,
                     }
                 }
             }
@@ -283,9 +758,18 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
             VlaResult {
                 significant_change: false,
                 description: "No composite image available".to_string(),
+                user_presence: None,
             }
         };
 
+        if vla.significant_change {
+            self.storage.remember(Episode::now(
+                EpisodeKind::ScreenChange,
+                None,
+                vla.description.clone(),
+            ));
+        }
+
         // STEP 2: Compute eligibility for each companion
         let eligibilities = self.compute_eligibility(observation, &vla);
 
@@ -311,9 +795,11 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                 "No companions eligible to speak"
             );
 
+            let reasoning = format!("No eligible companions. {}", reasons.join("; "));
+            self.log_pass(&reasoning, DecisionOutcome::NoEligible);
             return Ok(EvaluateResult {
                 decision: Decision::Pass {
-                    reasoning: format!("No eligible companions. {}", reasons.join("; ")),
+                    reasoning,
                     urgency: 0.0,
                 },
                 prompt_logs,
@@ -323,8 +809,11 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
         // HARD GATE: If user has been silent for 5+ minutes AND no VLA change AND no unanswered user message,
         // skip the arbiter entirely - there's clearly no stimulus worth responding to
         let user_silence_threshold_secs = 300; // 5 minutes
-        if !user_unanswered 
-            && !vla.significant_change 
+        if self.config.stages.silence_gate
+            && !user_unanswered
+            && !vla.significant_change
+            && !copied
+            && !scheduled
             && observation.seconds_since_user_message > user_silence_threshold_secs
         {
             info!(
@@ -333,48 +822,50 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                 "No stimulus: user silent and no VLA change - skipping arbiter"
             );
 
+            let reasoning = format!(
+                "No stimulus: user silent for {}s, VLA detected no change",
+                observation.seconds_since_user_message
+            );
+            self.log_pass(&reasoning, DecisionOutcome::NoStimulus);
             return Ok(EvaluateResult {
                 decision: Decision::Pass {
-                    reasoning: format!(
-                        "No stimulus: user silent for {}s, VLA detected no change",
-                        observation.seconds_since_user_message
-                    ),
+                    reasoning,
                     urgency: 0.0,
                 },
                 prompt_logs,
             });
         }
 
-        // STEP 3: Arbiter - given ALLOW companions, who (if anyone) should speak?
-        let arbiter_prompt = self.build_arbiter_prompt(observation, &vla, &allowed_companions, user_unanswered);
-        let schema = arbiter_schema();
-        
-        // Arbiter gets vision context too - helps make better decisions about what's on screen
-        let response = if let Some(composite) = &observation.composite {
-            let mut images = vec![encode_rgba_to_base64(composite)?];
-            if let Some(ariaos) = &observation.ariaos {
-                images.push(encode_rgba_to_base64(ariaos)?);
+        // A screen change a companion already commented on isn't news,
+        // whatever the arbiter thinks
+        let unprompted = !user_unanswered && !copied && !scheduled;
+        if unprompted && vla.significant_change {
+            if let Some(reasoning) = self.repeated_topic(&vla.description, now) {
+                info!(%reasoning, "Topic already discussed - skipping arbiter");
+                self.log_pass(&reasoning, DecisionOutcome::Repeated);
+                return Ok(EvaluateResult {
+                    decision: Decision::Pass {
+                        reasoning,
+                        urgency: 0.0,
+                    },
+                    prompt_logs,
+                });
             }
-            self.clients
-                .arbiter
-                .complete_vision_json(&self.clients.arbiter_model, &arbiter_prompt, images, schema)
-                .await?
-        } else {
-            self.clients
-                .arbiter
-                .complete_json(&self.clients.arbiter_model, &arbiter_prompt, schema)
-                .await?
-        };
-
-        let arbiter_response_str = serde_json::to_string_pretty(&response).unwrap_or_default();
-        prompt_logs.push(PromptLog {
-            model_type: "arbiter".to_string(),
-            model_name: self.clients.arbiter_model.clone(),
-            prompt: arbiter_prompt.clone(),
-            response: arbiter_response_str,
-        });
+        }
 
-        let arbiter: ArbiterDecision = serde_json::from_value(response)?;
+        // STEP 3: Arbiter - given ALLOW companions, who (if anyone) should speak?
+        // Arbiter gets vision context too (if the model supports it) - helps make
+        // better decisions about what's on screen
+        // A companion the user called by name answers, whoever the arbiter
+        // would have picked
+        let addressed = self.addressed_companion(observation, &allowed_companions);
+        let arbiter = match (addressed, combined_decision) {
+            (Some(arbiter), _) => arbiter,
+            (None, Some(arbiter)) => arbiter,
+            (None, None) => self
+                .run_arbiter(observation, &vla, &allowed_companions, user_unanswered, None, &mut prompt_logs)
+                .await?,
+        };
 
         info!(
             who_should_talk = ?arbiter.who_should_talk,
@@ -382,68 +873,39 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
             "Arbiter decision"
         );
 
-        // Record the decision
-        let should_respond = arbiter.who_should_talk.is_some();
-        self.storage
-            .record_decision(&StoredDecision::now(
-                should_respond,
-                arbiter.who_should_talk.clone(),
-                arbiter.reasoning.clone(),
-                if should_respond { 0.5 } else { 0.0 },
-            ))
-            .await?;
+        // Recorded with its outcome once that is known
+        let mut decision = arbiter.to_stored();
 
-        // If arbiter says "none", we're done
-        let responder_id = match &arbiter.who_should_talk {
-            Some(id) if !id.is_empty() && id.to_lowercase() != "none" => id.clone(),
-            _ => {
+        // Focus is no reason to ignore the user
+        let focus = observation.focus.as_ref().filter(|_| !user_unanswered);
+        let responder_index = match self.chosen_responder(&arbiter, &allowed_companions, focus) {
+            Ok(index) => index,
+            Err((outcome, reasoning)) => {
+                decision.outcome = outcome;
+                self.storage.record_decision(&decision);
                 return Ok(EvaluateResult {
                     decision: Decision::Pass {
-                        reasoning: arbiter.reasoning,
+                        reasoning,
                         urgency: 0.0,
                     },
                     prompt_logs,
                 });
             }
         };
-
-        // Validate the responder exists and is in the allowed list
-        let Some(responder_index) = self
-            .characters
-            .iter()
-            .position(|c| c.spec.id == responder_id)
-        else {
-            warn!(responder_id = %responder_id, "Arbiter chose unknown companion");
-            return Ok(EvaluateResult {
-                decision: Decision::Pass {
-                    reasoning: format!("{} (unknown companion '{}')", arbiter.reasoning, responder_id),
-                    urgency: 0.0,
-                },
-                prompt_logs,
-            });
-        };
-
-        if !allowed_companions.iter().any(|(id, _)| id == &responder_id) {
-            warn!(responder_id = %responder_id, "Arbiter chose ineligible companion");
-            return Ok(EvaluateResult {
-                decision: Decision::Pass {
-                    reasoning: format!("{} (companion '{}' not eligible)", arbiter.reasoning, responder_id),
-                    urgency: 0.0,
-                },
-                prompt_logs,
-            });
-        }
+        let responder_id = self.characters[responder_index].spec.id.clone();
 
         // Check cooldown - BUT bypass if:
         // 1. User has an unanswered message (always respond to direct interaction)
-        // 2. VLA detected a significant change (something new happened worth commenting on)
-        let bypass_cooldown = user_unanswered || vla.significant_change;
-        if !bypass_cooldown
-            && self.characters[responder_index]
-                .state
-                .is_on_cooldown(self.config.cooldown_after_speak())
-        {
+        // 2. VLA detected a significant change (something new happened worth commenting
+        //    on), unless this companion waits out its cooldown regardless
+        let character = &self.characters[responder_index];
+        let timing = self.timing(character);
+        let bypass_cooldown =
+            user_unanswered || (vla.significant_change && timing.speak_on_scene_change);
+        if !bypass_cooldown && character.state.is_on_cooldown(timing.cooldown_after_speak) {
             info!(responder_id = %responder_id, "Character on cooldown, skipping");
+            decision.outcome = DecisionOutcome::Cooldown;
+            self.storage.record_decision(&decision);
             return Ok(EvaluateResult {
                 decision: Decision::Pass {
                     reasoning: format!("{} (on cooldown)", arbiter.reasoning),
@@ -453,79 +915,375 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
             });
         }
 
-        // STEP 4: Generate response using proper chat message structure with tool calling
-        info!(responder_id = %responder_id, "Generating response with tools...");
-
-        // Build images list for the message
-        let images = if let Some(composite) = &observation.composite {
-            let mut imgs = vec![encode_rgba_to_base64(composite)?];
-            if let Some(ariaos) = &observation.ariaos {
-                imgs.push(encode_rgba_to_base64(ariaos)?);
+        let result = self
+            .respond(
+                observation,
+                responder_index,
+                arbiter,
+                decision,
+                None,
+                prompt_logs,
+            )
+            .await?;
+        if unprompted && vla.significant_change && self.config.topics.enabled {
+            if let Decision::Speak { character_id, .. } = &result.decision {
+                let min_relevance = self.config.topics.min_relevance;
+                self.topics
+                    .record(character_id, &vla.description, now, min_relevance);
             }
-            imgs
-        } else {
-            vec![]
-        };
-
-        // Build proper chat messages with turn structure
-        let response_messages = Self::build_response_messages(
-            &self.characters[responder_index].spec,
-            observation,
-            images,
-        );
+        }
+        Ok(result)
+    }
 
-        // Serialize messages for logging (strip images to keep logs readable)
-        let response_prompt_json = serde_json::to_string_pretty(&strip_images_for_logging(&response_messages))
-            .unwrap_or_else(|_| "(failed to serialize)".to_string());
+    /// Let another companion answer `reply`, which was just spoken this tick
+    /// and is the last message in `observation`'s chat. The arbiter decides
+    /// again (the screen isn't re-analyzed, and cooldowns don't apply within
+    /// a chain); None once `max_chain` replies have been chained or no other
+    /// companion can speak
+    pub async fn evaluate_reply(
+        &mut self,
+        observation: &Observation,
+        reply: &ChatPacket,
+    ) -> Result<Option<EvaluateResult>> {
+        if self.chain >= self.config.max_chain
+            || observation.focus.as_ref().is_some_and(|f| f.silences())
+        {
+            return Ok(None);
+        }
+        self.chain += 1;
 
-        // Get ARIAOS tools for the response model
-        let tools = ariaos::ariaos_tools();
+        let vla = VlaResult {
+            significant_change: false,
+            description: format!("Nothing new on screen; {} just spoke", reply.sender),
+            user_presence: None,
+        };
+        let allowed_companions: Vec<_> = self
+            .compute_eligibility(observation, &vla)
+            .into_iter()
+            .filter(|(id, e)| e.is_allowed() && id != &reply.sender)
+            .collect();
+        if allowed_companions.is_empty() {
+            return Ok(None);
+        }
 
-        // Use tool-enabled completion for response generation
-        let completion = self
-            .clients
-            .response
-            .complete_vision_with_tools(&self.clients.response_model, response_messages, tools)
+        let mut prompt_logs = Vec::new();
+        let arbiter = self
+            .run_arbiter(
+                observation,
+                &vla,
+                &allowed_companions,
+                false,
+                Some(reply),
+                &mut prompt_logs,
+            )
             .await?;
+        info!(
+            replying_to = %reply.sender,
+            who_should_talk = ?arbiter.who_should_talk,
+            reasoning = %arbiter.reasoning,
+            "Arbiter chain decision"
+        );
+
+        let mut decision = arbiter.to_stored();
+        let focus = observation.focus.as_ref();
+        let responder_index = match self.chosen_responder(&arbiter, &allowed_companions, focus) {
+            Ok(index) => index,
+            Err((outcome, reasoning)) => {
+                decision.outcome = outcome;
+                self.storage.record_decision(&decision);
+                return Ok(Some(EvaluateResult {
+                    decision: Decision::Pass {
+                        reasoning,
+                        urgency: 0.0,
+                    },
+                    prompt_logs,
+                }));
+            }
+        };
 
-        // Extract text content (default to empty if model only made tool calls)
-        let mut text = completion.content.unwrap_or_default();
+        self.respond(
+            observation,
+            responder_index,
+            arbiter,
+            decision,
+            Some(reply),
+            prompt_logs,
+        )
+        .await
+        .map(Some)
+    }
 
-        // Convert tool calls to ARIAOS commands
-        let (ariaos_commands, tool_errors) = ariaos::tool_calls_to_commands(&completion.tool_calls);
-        
-        // Log any tool conversion errors
-        for err in &tool_errors {
-            warn!(error = %err, "Failed to convert tool call");
+    /// The arbiter's decision for an unanswered user message that calls
+    /// one of `allowed_companions` by name; None without `direct_address`
+    fn addressed_companion(
+        &self,
+        observation: &Observation,
+        allowed_companions: &[(String, CompanionEligibility)],
+    ) -> Option<ArbiterDecision> {
+        if !self.config.direct_address {
+            return None;
         }
-
-        // Log tool calls for debugging
-        let tool_call_summary: Vec<String> = completion.tool_calls
+        let message = observation.recent_chat.last().filter(|p| p.sender == "user")?;
+        let companions: Vec<_> = self
+            .characters
             .iter()
-            .map(|tc| format!("{}({})", tc.function.name, tc.function.arguments))
+            .filter(|c| allowed_companions.iter().any(|(id, _)| id == &c.spec.id))
+            .map(|c| (c.spec.id.as_str(), c.spec.name.as_str()))
             .collect();
+        let id = address::addressed(&message.content, &companions)?;
+        let name = companions.iter().find(|(c, _)| *c == id).map_or(id, |&(_, name)| name);
+        info!(responder_id = %id, "User addressed companion by name - skipping arbiter");
+        Some(ArbiterDecision {
+            who_should_talk: Some(id.to_string()),
+            reasoning: format!("User addressed {name} by name"),
+            urgency: None,
+            suggested_mood: None,
+            confidence: None,
+        })
+    }
 
-        prompt_logs.push(PromptLog {
-            model_type: "response".to_string(),
-            model_name: self.clients.response_model.clone(),
-            prompt: response_prompt_json,
-            response: format!(
-                "text: {}\ntool_calls: [{}]",
-                text,
-                tool_call_summary.join(", ")
-            ),
-        });
+    /// The companion the arbiter picked, or the outcome and reasoning of a
+    /// pass when it picked none, one that can't speak, one it isn't
+    /// confident enough in, or nothing urgent enough to interrupt `focus`
+    fn chosen_responder(
+        &self,
+        arbiter: &ArbiterDecision,
+        allowed_companions: &[(String, CompanionEligibility)],
+        focus: Option<&Focus>,
+    ) -> Result<usize, (DecisionOutcome, String)> {
+        // If arbiter says "none", we're done
+        let responder_id = match &arbiter.who_should_talk {
+            Some(id) if !id.is_empty() && id.to_lowercase() != "none" => id,
+            _ => return Err((DecisionOutcome::Declined, arbiter.reasoning.clone())),
+        };
 
-        if !ariaos_commands.is_empty() {
+        if arbiter.confidence() < self.config.min_confidence {
             info!(
-                count = ariaos_commands.len(),
-                commands = ?ariaos_commands,
-                "Parsed ARIAOS tool calls"
+                responder_id = %responder_id,
+                confidence = arbiter.confidence(),
+                "Arbiter not confident enough"
             );
+            let reasoning = format!(
+                "{} (low confidence: {:.2} < {:.2})",
+                arbiter.reasoning,
+                arbiter.confidence(),
+                self.config.min_confidence
+            );
+            return Err((DecisionOutcome::LowConfidence, reasoning));
         }
 
-        // Optional audit
-        if let Some((audit_client, audit_model)) = &self.clients.audit {
+        // Validate the responder exists and is in the allowed list
+        let Some(responder_index) = self
+            .characters
+            .iter()
+            .position(|c| &c.spec.id == responder_id)
+        else {
+            warn!(responder_id = %responder_id, "Arbiter chose unknown companion");
+            let reasoning = format!(
+                "{} (unknown companion '{}')",
+                arbiter.reasoning, responder_id
+            );
+            return Err((DecisionOutcome::UnknownCompanion, reasoning));
+        };
+
+        if !allowed_companions.iter().any(|(id, _)| id == responder_id) {
+            warn!(responder_id = %responder_id, "Arbiter chose ineligible companion");
+            let reasoning = format!(
+                "{} (companion '{}' not eligible)",
+                arbiter.reasoning, responder_id
+            );
+            return Err((DecisionOutcome::Ineligible, reasoning));
+        }
+
+        if let Some(focus) = focus.filter(|f| !f.allows(arbiter.urgency())) {
+            info!(
+                reason = %focus.reason,
+                urgency = arbiter.urgency(),
+                "Not urgent enough to interrupt"
+            );
+            let reasoning = format!(
+                "{} (do not disturb: {}, urgency {:.2})",
+                arbiter.reasoning,
+                focus.reason,
+                arbiter.urgency()
+            );
+            return Err((DecisionOutcome::DoNotDisturb, reasoning));
+        }
+        Ok(responder_index)
+    }
+
+    /// Step 4: generate the chosen companion's reply (to `reply_to`, when
+    /// answering another companion), audit it, and record the decision
+    async fn respond(
+        &mut self,
+        observation: &Observation,
+        responder_index: usize,
+        arbiter: ArbiterDecision,
+        mut decision: StoredDecision,
+        reply_to: Option<&ChatPacket>,
+        mut prompt_logs: Vec<PromptLog>,
+    ) -> Result<EvaluateResult> {
+        let responder_id = self.characters[responder_index].spec.id.clone();
+
+        // STEP 4: Generate response using proper chat message structure with tool calling
+        info!(responder_id = %responder_id, "Generating response with tools...");
+
+        // Build images list for the message (none for text-only response models)
+        let response_vision = self.clients.capabilities(&self.clients.response_model).vision;
+        let images = if let Some(composite) = observation.composite.as_ref().filter(|_| response_vision) {
+            let mut imgs = vec![self.encode_image(composite)?];
+            if let Some(ariaos) = &observation.ariaos {
+                imgs.push(self.encode_image(ariaos)?);
+            }
+            imgs
+        } else {
+            vec![]
+        };
+
+        let user_profile = self.storage.user_profile().await.unwrap_or_else(|err| {
+            warn!(?err, "Failed to load the user profile");
+            UserProfile::default()
+        });
+
+        let spec = &self.characters[responder_index].spec;
+        let memories = self.recall_memories(spec, observation, reply_to).await;
+        let style = spec.response_style();
+
+        // Build proper chat messages with turn structure
+        let response_messages = self.build_response_messages(
+            spec,
+            observation,
+            &user_profile,
+            memories.as_deref(),
+            reply_to,
+            images,
+        )?;
+
+        // Get ARIAOS tools for the response model
+        let mut tools = ariaos::ariaos_tools();
+        if self.config.goals.enabled {
+            tools.push(goals::goal_tool());
+        }
+        // Lookups are only any use when the model sees their results
+        let max_iterations = self.config.max_tool_iterations.max(1);
+        if max_iterations > 1 {
+            tools.push(lookup::memory_tool());
+        }
+
+        // Use tool-enabled completion for response generation
+        let commands = self
+            .early_commands
+            .as_ref()
+            .filter(|_| self.config.stream_tool_calls);
+        // Unaudited text is only shown early when nothing will review it,
+        // and no banned phrase could still drop it
+        let deltas = self.text_deltas.as_ref().filter(|_| {
+            self.config.stream_text
+                && self.audit_client().is_none()
+                && style.banned_phrases.is_empty()
+        });
+        let mut messages = response_messages;
+        let mut text = String::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        let mut early_calls = Vec::new();
+        for iteration in 1..=max_iterations {
+            // Serialize messages for logging (strip images to keep logs readable)
+            let response_prompt_json = serde_json::to_string_pretty(&strip_images_for_logging(&messages))
+                .unwrap_or_else(|_| "(failed to serialize)".to_string());
+            let started = Instant::now();
+            let (completion, usage, reasoning, streamed_calls) = match (commands, deltas) {
+                (None, None) => {
+                    let (completion, usage, reasoning) =
+                        self.tracked(self.clients.response.complete_vision_with_tools(
+                            &self.clients.response_model,
+                            messages.clone(),
+                            tools.clone(),
+                        ))
+                        .await;
+                    (completion, usage, reasoning, Vec::new())
+                }
+                (commands, deltas) => {
+                    self.stream_response(&responder_id, messages.clone(), tools.clone(), commands, deltas)
+                        .await
+                }
+            };
+            let completion = completion?;
+            let latency_ms = started.elapsed().as_millis() as u64;
+            early_calls.extend(streamed_calls.iter().cloned());
+
+            // Extract text content (default to empty if model only made tool calls)
+            text = completion.content.unwrap_or_default();
+
+            // Log tool calls for debugging
+            let tool_call_summary: Vec<String> = completion.tool_calls
+                .iter()
+                .map(|tc| format!("{}({})", tc.function.name, tc.function.arguments))
+                .collect();
+
+            prompt_logs.push(PromptLog {
+                model_type: "response".to_string(),
+                model_name: self.clients.response_model.clone(),
+                prompt: response_prompt_json,
+                response: format!(
+                    "text: {}\ntool_calls: [{}]",
+                    text,
+                    tool_call_summary.join(", ")
+                ),
+                latency_ms,
+                usage,
+                reasoning,
+            });
+
+            // Text is the reply; calls alone get their results and another turn
+            let calls = completion.tool_calls;
+            tool_calls.extend(calls.iter().cloned());
+            if calls.is_empty() || !text.trim().is_empty() || iteration == max_iterations {
+                break;
+            }
+            debug!(%responder_id, iteration, calls = calls.len(), "Continuing after tool calls");
+            // ARIAOS commands run now, and aren't run again with the reply
+            let (results, run) = self
+                .tool_results(&responder_id, &calls, &streamed_calls)
+                .await;
+            early_calls.extend(run);
+            messages.push(ChatMessage::assistant_tool_calls(text.clone(), calls));
+            messages.extend(results);
+        }
+
+        // Convert tool calls to ARIAOS commands, skipping any already executed mid-stream
+        let remaining_calls: Vec<ToolCall> = tool_calls
+            .iter()
+            .filter(|call| {
+                match early_calls.iter().position(|early| {
+                    early.function.name == call.function.name
+                        && early.function.arguments == call.function.arguments
+                }) {
+                    Some(index) => {
+                        early_calls.remove(index);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .cloned()
+            .collect();
+        let (ariaos_commands, tool_errors) = ariaos::tool_calls_to_commands(&remaining_calls);
+        
+        // Log any tool conversion errors
+        for err in &tool_errors {
+            warn!(error = %err, "Failed to convert tool call");
+        }
+
+        if !ariaos_commands.is_empty() {
+            info!(
+                count = ariaos_commands.len(),
+                commands = ?ariaos_commands,
+                "Parsed ARIAOS tool calls"
+            );
+        }
+
+        // Optional audit
+        if let Some((audit_client, audit_model)) = self.audit_client() {
             text = match self
                 .run_audit(
                     &self.characters[responder_index].spec,
@@ -533,41 +1291,346 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                     observation,
                     audit_client.as_ref(),
                     audit_model,
+                    &mut prompt_logs,
                 )
                 .await
             {
                 Ok(validated) => validated,
                 Err(err) => {
                     warn!(?err, "Audit rejected response");
-                    return Ok(EvaluateResult {
-                        decision: Decision::Pass {
-                            reasoning: format!("{} (audit rejected: {})", arbiter.reasoning, err),
-                            urgency: 0.0,
-                        },
-                        prompt_logs,
-                    });
+                    let reasoning = format!("{} (audit rejected: {})", arbiter.reasoning, err);
+                    return Ok(self.reject_reply(decision, reasoning, prompt_logs));
                 }
             };
         }
 
+        // Models don't reliably keep to length and formatting rules
+        if !text.is_empty() {
+            let styled = style.enforce(&text);
+            if styled.is_empty() {
+                warn!(%responder_id, "Every sentence of the reply used a banned phrase");
+                let reasoning = format!("{} (style rejected: banned phrases)", arbiter.reasoning);
+                return Ok(self.reject_reply(decision, reasoning, prompt_logs));
+            }
+            if styled != text {
+                debug!(%responder_id, original = %text, "Reply trimmed to the character's style");
+            }
+            text = styled;
+        }
+
+        // The companion sounds and looks the way they feel, unless the
+        // arbiter suggested otherwise
+        let suggested_mood = arbiter.mood().or_else(|| {
+            let character = &self.characters[responder_index];
+            let half_life = self.config.mood.half_life();
+            let (mood, _) = character.state.mood.current(Instant::now(), half_life);
+            (self.config.mood.enabled && mood != Mood::Neutral).then(|| mood.as_str().to_string())
+        });
+        if let Some(mood) = suggested_mood.as_deref().and_then(Mood::parse) {
+            self.feel(&responder_id, MoodEvent::Spoke(mood));
+        }
+
         // Update character state
+        let unprompted_question =
+            !answering_user(observation) && goals::last_question(&text).is_some();
         if let Some(character) = self.characters.get_mut(responder_index) {
             character.state.update_last_spoke();
+            if unprompted_question {
+                character.state.record_question();
+            }
+        }
+        self.woken = None;
+        if self.config.goals.enabled {
+            let now = Utc::now().timestamp();
+            let goal_calls = tool_calls
+                .iter()
+                .filter(|call| call.function.name == goals::TOOL_NAME);
+            for call in goal_calls {
+                self.goals
+                    .apply_tool_call(&responder_id, &call.function.arguments, now);
+            }
+            let last_user_message = last_user_message(observation);
+            self.goals
+                .record_reply(&responder_id, &text, last_user_message, now);
         }
 
+        if let Some(sink) = self.reflections.as_ref().filter(|_| !text.is_empty()) {
+            let _ = sink.send(ReflectionJob {
+                character_id: responder_id.clone(),
+                name: self.characters[responder_index].spec.name.clone(),
+                reply: text.clone(),
+                chat: format_chat(&observation.recent_chat),
+                screen: observation.screen_summary.notes.clone(),
+            });
+        }
+
+        self.last_reply = Some(LastReply {
+            observation: observation.clone(),
+            reply_to: reply_to.cloned(),
+            responder_index,
+            arbiter: arbiter.clone(),
+            text: text.clone(),
+        });
+
+        decision.outcome = DecisionOutcome::Spoke;
+        let decision_id = self.storage.record_decision(&decision);
         Ok(EvaluateResult {
             decision: Decision::Speak {
+                decision_id,
                 character_id: responder_id,
+                urgency: arbiter.urgency(),
+                suggested_mood,
                 reasoning: arbiter.reasoning,
                 text,
-                urgency: 0.5,
-                suggested_mood: None,
                 tool_calls: ariaos_commands,
             },
             prompt_logs,
         })
     }
 
+    /// Pass on a reply that was generated but can't be said
+    fn reject_reply(
+        &self,
+        mut decision: StoredDecision,
+        reasoning: String,
+        prompt_logs: Vec<PromptLog>,
+    ) -> EvaluateResult {
+        decision.outcome = DecisionOutcome::AuditRejected;
+        self.storage.record_decision(&decision);
+        EvaluateResult {
+            decision: Decision::Pass {
+                reasoning,
+                urgency: 0.0,
+            },
+            prompt_logs,
+        }
+    }
+
+    /// Log a pass decided before the arbiter ran
+    fn log_pass(&self, reasoning: &str, outcome: DecisionOutcome) {
+        let decision = StoredDecision::now(false, None, reasoning, 0.0);
+        self.storage.record_decision(&decision.with_outcome(outcome));
+    }
+
+    /// Step 3 (split mode): ask the arbiter who, if anyone, should speak
+    async fn run_arbiter(
+        &self,
+        observation: &Observation,
+        vla: &VlaResult,
+        allowed_companions: &[(String, CompanionEligibility)],
+        user_unanswered: bool,
+        reply_to: Option<&ChatPacket>,
+        prompt_logs: &mut Vec<PromptLog>,
+    ) -> Result<ArbiterDecision> {
+        let arbiter_composite = observation
+            .composite
+            .as_ref()
+            .filter(|_| self.clients.capabilities(&self.clients.arbiter_model).vision);
+        let arbiter_prompt = self.build_arbiter_prompt(
+            prompts::ARBITER,
+            observation,
+            vla,
+            allowed_companions,
+            user_unanswered,
+            reply_to,
+            arbiter_composite.is_some(),
+        )?;
+        let schema = arbiter_schema();
+
+        let started = Instant::now();
+        let (response, usage, reasoning) = if let Some(composite) = arbiter_composite {
+            let images = self.perception_images(composite, observation)?;
+            self.tracked(self.clients.arbiter.complete_vision_json(
+                &self.clients.arbiter_model,
+                &arbiter_prompt,
+                images,
+                schema,
+            ))
+            .await
+        } else {
+            self.tracked(self.clients.arbiter.complete_json(
+                &self.clients.arbiter_model,
+                &arbiter_prompt,
+                schema,
+            ))
+            .await
+        };
+        let response = response?;
+
+        let arbiter_response_str = serde_json::to_string_pretty(&response).unwrap_or_default();
+        prompt_logs.push(PromptLog {
+            model_type: "arbiter".to_string(),
+            model_name: self.clients.arbiter_model.clone(),
+            prompt: arbiter_prompt,
+            response: arbiter_response_str,
+            latency_ms: started.elapsed().as_millis() as u64,
+            usage,
+            reasoning,
+        });
+
+        Ok(serde_json::from_value(response)?)
+    }
+
+    /// The close-up of what changed, if enabled and there is one
+    fn close_up<'a>(&self, observation: &'a Observation) -> Option<&'a RgbaImage> {
+        observation
+            .frame
+            .close_up
+            .as_ref()
+            .filter(|_| self.config.close_up)
+    }
+
+    /// An image in the configured format, as a `data:` URL
+    fn encode_image(&self, image: &RgbaImage) -> Result<String> {
+        encode_image(
+            &DynamicImage::ImageRgba8(image.clone()),
+            self.config.image_format,
+            self.config.image_quality,
+        )
+    }
+
+    /// Images for change detection and arbitration: the composite, then
+    /// ARIAOS and the close-up when there are any
+    fn perception_images(
+        &self,
+        composite: &RgbaImage,
+        observation: &Observation,
+    ) -> Result<Vec<String>> {
+        let mut images = vec![self.encode_image(composite)?];
+        if let Some(ariaos) = &observation.ariaos {
+            images.push(self.encode_image(ariaos)?);
+        }
+        if let Some(close_up) = self.close_up(observation) {
+            images.push(self.encode_image(close_up)?);
+        }
+        Ok(images)
+    }
+
+    /// Steps 1 and 3 in one call (combined mode): change detection and
+    /// arbitration from a single structured vision completion
+    async fn analyze_combined(
+        &self,
+        observation: &Observation,
+        candidates: &[(String, CompanionEligibility)],
+        user_unanswered: bool,
+    ) -> Result<(VlaResult, ArbiterDecision, PromptLog)> {
+        let composite = observation
+            .composite
+            .as_ref()
+            .ok_or_else(|| anyhow!("No composite image available for combined analysis"))?;
+        let images = self.perception_images(composite, observation)?;
+
+        let pending = VlaResult {
+            significant_change: false,
+            description: String::new(),
+            user_presence: None,
+        };
+        let prompt = self.build_arbiter_prompt(
+            prompts::COMBINED,
+            observation,
+            &pending,
+            candidates,
+            user_unanswered,
+            None,
+            true,
+        )?;
+
+        let started = Instant::now();
+        let (response, usage, reasoning) = self.tracked(self.clients.arbiter.complete_vision_json(
+            &self.clients.arbiter_model,
+            &prompt,
+            images,
+            combined_schema(observation.frame.camera.is_some()),
+        ))
+        .await;
+        let response = response?;
+
+        let prompt_log = PromptLog {
+            model_type: "combined".to_string(),
+            model_name: self.clients.arbiter_model.clone(),
+            prompt,
+            response: serde_json::to_string_pretty(&response).unwrap_or_default(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            usage,
+            reasoning,
+        };
+
+        let vla: VlaResult = serde_json::from_value(response.clone())?;
+        let arbiter: ArbiterDecision = serde_json::from_value(response)?;
+        info!(
+            significant_change = vla.significant_change,
+            description = %vla.description,
+            presence = ?vla.user_presence,
+            "Combined analysis complete"
+        );
+
+        Ok((vla, arbiter, prompt_log))
+    }
+
+    /// Stream the response, sending ARIAOS commands to `commands` as each tool
+    /// call completes and reply text to `deltas` as it arrives. Returns the
+    /// completion and the calls that were sent early.
+    async fn stream_response(
+        &self,
+        responder_id: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        commands: Option<&UnboundedSender<(String, Vec<AriaosCommand>)>>,
+        deltas: Option<&UnboundedSender<(String, String)>>,
+    ) -> (
+        Result<ChatCompletionWithTools>,
+        Option<TokenUsage>,
+        Option<String>,
+        Vec<ToolCall>,
+    ) {
+        let (tool_tx, mut tool_rx) = mpsc::unbounded_channel::<ToolCall>();
+        let (text_tx, mut text_rx) = mpsc::unbounded_channel::<String>();
+        let stream = self.tracked(self.clients.response.stream_with_tools(
+            &self.clients.response_model,
+            messages,
+            tools,
+            tool_tx,
+            text_tx,
+        ));
+        let forward = async {
+            let mut sent = Vec::new();
+            while let Some(call) = tool_rx.recv().await {
+                let Some(sink) = commands else {
+                    // Run with the rest once the reply is complete
+                    continue;
+                };
+                let (commands, _) = ariaos::tool_calls_to_commands(std::slice::from_ref(&call));
+                if commands.is_empty() {
+                    // Conversion errors are reported with the full completion
+                    continue;
+                }
+                debug!(?commands, "Dispatching streamed ARIAOS tool call");
+                if sink.send((responder_id.to_string(), commands)).is_ok() {
+                    sent.push(call);
+                }
+            }
+            sent
+        };
+        let forward_text = async {
+            while let Some(delta) = text_rx.recv().await {
+                if let Some(sink) = deltas {
+                    let _ = sink.send((responder_id.to_string(), delta));
+                }
+            }
+        };
+
+        let ((completion, usage, reasoning), sent, ()) =
+            tokio::join!(stream, forward, forward_text);
+        (completion, usage, reasoning, sent)
+    }
+
+    /// The audit model, unless the audit stage is off
+    fn audit_client(&self) -> Option<&(SharedLlm, String)> {
+        self.clients.audit.as_ref().filter(|_| self.config.stages.audit)
+    }
+
+    /// Check the reply against the audit rubric, re-auditing each revision
+    /// up to `audit_max_revisions` times. Errors when the reply is blocked.
     async fn run_audit(
         &self,
         spec: &CharacterSpec,
@@ -575,51 +1638,78 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
         observation: &Observation,
         client: &dyn crate::llm::LlmClient,
         model: &str,
+        prompt_logs: &mut Vec<PromptLog>,
     ) -> Result<String> {
-        let schema = json!({
-            "type": "object",
-            "properties": {
-                "status": { "type": "string", "enum": ["approve", "revise", "block"] },
-                "text": { "type": "string" },
-                "reason": { "type": "string" }
-            },
-            "required": ["status"]
-        });
-        let prompt = format!(
-            "You are the self-audit system for {name}. Review the drafted reply and ensure it \
-            matches tone, avoids repetition, and fits this context.\n\n\
-            # Draft Reply\n{text}\n\n\
-            # Screen Summary\n{summary}\n\n\
-            # Recent Chat\n{chat}\n\n\
-            Respond with status approve/revise/block. Provide revised text if needed.",
-            name = spec.name,
-            summary = observation.screen_summary.notes,
-            chat = format_chat(&observation.recent_chat)
-        );
-        let result = client.complete_json(model, &prompt, schema).await?;
-        let audit: AuditResult = serde_json::from_value(result)?;
+        let recent_replies: Vec<&str> = observation
+            .recent_chat
+            .iter()
+            .rev()
+            .filter(|p| p.sender == spec.id)
+            .take(5)
+            .map(|p| p.content.as_str())
+            .collect();
+        let max_revisions = self.config.audit_max_revisions;
+        let mut text = text.to_string();
+
+        for revision in 0..=max_revisions {
+            let prompt = self.prompts.render(
+                prompts::AUDIT,
+                json!({
+                    "name": spec.name,
+                    "description": spec.description,
+                    "personality": spec.personality,
+                    "text": text,
+                    "style": spec.response_style().rules(),
+                    "recent_replies": recent_replies,
+                    "revision": revision,
+                    "summary": observation.screen_summary.notes,
+                    "chat": format_chat(&observation.recent_chat),
+                }),
+            )?;
+            let started = Instant::now();
+            let (result, usage, reasoning) = self
+                .tracked(client.complete_json(model, &prompt, audit_schema()))
+                .await;
+            let result = result?;
+            prompt_logs.push(PromptLog {
+                model_type: "audit".to_string(),
+                model_name: model.to_string(),
+                prompt,
+                response: serde_json::to_string_pretty(&result).unwrap_or_default(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                usage,
+                reasoning,
+            });
+            let audit: AuditResult = serde_json::from_value(result)?;
 
-        match audit.status.as_str() {
-            "approve" => Ok(text.to_string()),
-            "revise" => Ok(audit.text.unwrap_or_else(|| text.to_string())),
-            _ => Err(anyhow!(
-                "Audit blocked response: {}",
-                audit.reason.unwrap_or_default()
-            )),
+            match audit.verdict() {
+                AuditVerdict::Approve => return Ok(text),
+                AuditVerdict::Block(reason) => {
+                    return Err(anyhow!("Audit blocked response: {reason}"));
+                }
+                AuditVerdict::Revise(revised) => {
+                    debug!(revision, %revised, "Audit revised the response");
+                    text = revised;
+                }
+            }
         }
+        // The auditor wrote the last revision itself, so it is used unreviewed
+        warn!(max_revisions, "Audit still revising after the last round");
+        Ok(text)
     }
 
     fn build_arbiter_prompt(
         &self,
+        template: &str,
         observation: &Observation,
         vla: &VlaResult,
         allowed_companions: &[(String, CompanionEligibility)],
         user_unanswered: bool,
-    ) -> String {
-        let chat = format_chat(&observation.recent_chat);
-
-        // Build character section ONLY for allowed companions
-        let character_section = allowed_companions
+        reply_to: Option<&ChatPacket>,
+        with_images: bool,
+    ) -> Result<String> {
+        // Character section ONLY for allowed companions
+        let companions = allowed_companions
             .iter()
             .filter_map(|(id, eligibility)| {
                 let character = self.characters.iter().find(|c| &c.spec.id == id)?;
@@ -627,139 +1717,121 @@ Compare DESKTOP directly to the PREV panels. Answer ONE question:
                     CompanionEligibility::Allow { reason } => reason.clone(),
                     _ => return None,
                 };
-                Some(format!(
-                    "### {name} (id: {id})\n\
-                    Personality: {personality}\n\
-                    Description: {description}\n\
-                    Scenario: {scenario}\n\
-                    Eligible because: {reason}\n",
-                    name = character.spec.name,
-                    id = character.spec.id,
-                    personality = truncate(&character.spec.personality, 300),
-                    description = truncate(&character.spec.description, 200),
-                    scenario = truncate(&character.spec.scenario, 200),
-                    reason = reason
-                ))
+                Some(json!({
+                    "name": character.spec.name,
+                    "id": character.spec.id,
+                    "personality": truncate(&character.spec.personality, 300),
+                    "description": truncate(&character.spec.description, 200),
+                    "scenario": truncate(&character.spec.scenario, 200),
+                    "mood": self.mood_of(character),
+                    "reason": reason,
+                }))
             })
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        // Format time since user message
-        let silence_note = if observation.seconds_since_user_message == u64::MAX {
-            "User has not spoken yet.".to_string()
-        } else if observation.seconds_since_user_message < 5 {
-            "User just spoke.".to_string()
-        } else {
-            format!("{}s since user last spoke.", observation.seconds_since_user_message)
-        };
-
-        let last_speaker = observation.recent_chat.last().map(|p| p.sender.as_str());
-
-        // VLA summary
-        let vla_summary = if vla.significant_change {
-            format!("**VLA: SIGNIFICANT CHANGE DETECTED**\n{}", vla.description)
-        } else {
-            format!("**VLA: No significant change**\n{}", vla.description)
-        };
+            .collect::<Vec<_>>();
 
-        // Image layout explanation (only if we have images)
-        let image_context = if observation.composite.is_some() {
-            let ariaos_note = if observation.ariaos.is_some() {
-                "\n\n**IMAGE 2 - ARIAOS**: The companion's personal dashboard showing their notes, focus tracking, and activity log."
-            } else {
-                ""
-            };
-            format!(
-                r#"# Visual Context
-**IMAGE 1 - COMPOSITE** layout:
-- DESKTOP (top-left): The user's current screen
-- PREV 1/2/3 (right side): Previous screenshots for temporal context
-- MEMORY/CHAT/STATUS panels: Optical memory visualization{ariaos}
-
-Use these images to understand what the user is doing and whether a companion comment would be welcome or intrusive.
-
-"#,
-                ariaos = ariaos_note
-            )
+        let last_speaker = if user_unanswered {
+            "user (UNANSWERED - prioritize responding!)"
         } else {
-            String::new()
+            observation
+                .recent_chat
+                .last()
+                .map(|p| p.sender.as_str())
+                .unwrap_or("none")
         };
 
-        format!(
-            r#"You are the Arbiter for Dewet companions. Your job: decide WHO (if anyone) should speak.
-
-{image_context}# Context Analysis
-{vla}
-
-# Timing
-{silence}
-Last speaker: {last_speaker}
-
-# Recent Chat
-{chat}
-
-# Eligible Companions
-These companions have passed eligibility checks and MAY speak:
-{companions}
-
-# Your Decision
-
-You must choose ONE of:
-1. **A specific companion ID** - if that companion has something valuable to say
-2. **"none"** - if silence is the better choice
-
-## When to pick a companion:
-- User asked a question or made a comment that deserves a response
-- VLA detected a significant change that a companion would naturally comment on
-- A companion has unique insight relevant to the current context
-
-## When to pick "none":
-- The recent chat shows the companion already commented on this topic
-- Nothing new has happened worth discussing
-- The user appears focused and shouldn't be interrupted
-- Any response would feel repetitive or forced
-
-**Default to "none" unless there's a clear reason to speak.**"#,
-            image_context = image_context,
-            vla = vla_summary,
-            silence = silence_note,
-            last_speaker = if user_unanswered { 
-                "user (UNANSWERED - prioritize responding!)" 
-            } else { 
-                last_speaker.unwrap_or("none") 
-            },
-            chat = chat,
-            companions = character_section
+        self.prompts.render(
+            template,
+            json!({
+                "has_composite": with_images,
+                "has_ariaos": with_images && observation.ariaos.is_some(),
+                "has_close_up": with_images && self.close_up(observation).is_some(),
+                "has_camera": with_images && observation.frame.camera.is_some(),
+                "user_presence": vla.user_presence,
+                "vla_significant": vla.significant_change,
+                "vla_description": vla.description,
+                "screen_text": observation.screen_summary.text,
+                "clipboard": observation.clipboard.last().map(|c| &c.text),
+                "scheduled": scheduled_note(observation),
+                "focus": observation.focus.as_ref().map(|f| &f.reason),
+                "input_activity": input_activity(observation),
+                "silence_note": silence_note(observation),
+                "clock": self.clock_note(),
+                "last_speaker": last_speaker,
+                "chat": format_chat(&observation.recent_chat),
+                "goals": self.goal_notes(observation),
+                "topics": self.topic_notes(),
+                "companions": companions,
+                "reply_to": reply_to.map(|p| json!({
+                    "name": self.speaker_name(&p.sender),
+                    "text": p.content,
+                })),
+            }),
         )
     }
 
     /// Build response prompt as proper chat messages with turn structure.
     /// This helps the model distinguish its own voice from the user's.
     fn build_response_messages(
+        &self,
         spec: &CharacterSpec,
         observation: &Observation,
+        user_profile: &UserProfile,
+        memories: Option<&str>,
+        reply_to: Option<&ChatPacket>,
         images_base64: Vec<String>,
-    ) -> Vec<ChatMessage> {
+    ) -> Result<Vec<ChatMessage>> {
         let mut messages = Vec::new();
 
+        // Convert chat history into proper user/assistant turns. The reply
+        // being answered was said aloud, so it is shown even if this
+        // companion's own memory doesn't hold it
+        let mut chat = observation.chat_for(&spec.id, self.storage.scope());
+        let remembered = |reply: &ChatPacket| {
+            chat.iter()
+                .any(|p| p.sender == reply.sender && p.timestamp == reply.timestamp)
+        };
+        if let Some(reply) = reply_to.filter(|reply| !remembered(reply)) {
+            chat.push(reply);
+        }
+
+        let examples = examples::parse(&spec.mes_example, &spec.name);
+
         // System message: character's system_prompt plus their card details
-        let system_content = format!(
-            "{system_prompt}\n\n\
-            Character: {name} ({id})\n\
-            Description: {description}\n\
-            Personality: {personality}\n\
-            Scenario: {scenario}",
-            system_prompt = spec.system_prompt,
-            name = spec.name,
-            id = spec.id,
-            description = spec.description,
-            personality = spec.personality,
-            scenario = spec.scenario,
-        );
+        let character = self.characters.iter().find(|c| c.spec.id == spec.id);
+        let mood = character.and_then(|c| self.mood_of(c));
+        let relationship = character
+            .filter(|_| self.config.relationship.enabled)
+            .and_then(|c| relationship::describe(c.state.relationship_score));
+        let no_questions = !answering_user(observation)
+            && character.is_some_and(|c| self.out_of_questions(c));
+        let system_content = self.prompts.render(
+            prompts::RESPONSE_SYSTEM,
+            json!({
+                "system_prompt": spec.system_prompt,
+                "name": spec.name,
+                "id": spec.id,
+                "description": spec.description,
+                "personality": spec.personality,
+                "scenario": spec.scenario,
+                "user_profile": user_profile.summary(),
+                "lore": self.active_lore(spec, &chat, observation),
+                "has_examples": !examples.is_empty(),
+                "style": spec.response_style().rules(),
+                "mood": mood,
+                "relationship": relationship,
+            }),
+        )?;
         messages.push(ChatMessage::system(system_content));
 
-        // Convert chat history into proper user/assistant turns
-        for packet in &observation.recent_chat {
+        // Example dialogue from the card, as few-shot turns ahead of the real chat
+        for turn in examples.into_iter().flatten() {
+            messages.push(match turn {
+                ExampleTurn::User(text) => ChatMessage::user(text),
+                ExampleTurn::Character(text) => ChatMessage::assistant(text),
+            });
+        }
+
+        for packet in chat {
             let sender_lower = packet.sender.to_lowercase();
             if sender_lower == "user" {
                 // User's messages are user turns
@@ -770,25 +1842,32 @@ You must choose ONE of:
             } else {
                 // Other characters' messages shown as user turns with speaker prefix
                 // so the model sees the full conversation but knows it's not its own voice
-                let prefixed = format!("[{}]: {}", packet.sender, packet.content);
+                let prefixed = format!(
+                    "[{}]: {}",
+                    self.speaker_name(&packet.sender),
+                    packet.content
+                );
                 messages.push(ChatMessage::user(prefixed));
             }
         }
 
         // Final user message with current context (what's on screen)
-        let ariaos_note = if observation.ariaos.is_some() {
-            "\n\nThe second image shows your personal dashboard - your notes, focus tracking, \
-            and activity log. Use this to inform your response, but don't mention it explicitly."
-        } else {
-            ""
-        };
-
-        let context_content = format!(
-            "[Current context: {screen}{ariaos}]\n\n\
-            Respond conversationally based on what you see.",
-            screen = observation.screen_summary.notes,
-            ariaos = ariaos_note,
-        );
+        let context_content = self.prompts.render(
+            prompts::RESPONSE_CONTEXT,
+            json!({
+                "screen": observation.screen_summary.notes,
+                "screen_text": observation.screen_summary.text,
+                "clipboard": observation.clipboard.last().map(|c| &c.text),
+                "scheduled": scheduled_note(observation),
+                "clock": self.clock_note(),
+                "memories": memories,
+                "goals": self.goal_notes(observation),
+                "focus": observation.focus.as_ref().map(|f| &f.reason),
+                "has_ariaos": observation.ariaos.is_some() && !images_base64.is_empty(),
+                "no_questions": no_questions,
+                "reply_to": reply_to.map(|p| self.speaker_name(&p.sender)),
+            }),
+        )?;
 
         // If we have images, attach them to the final context message
         if !images_base64.is_empty() {
@@ -797,7 +1876,187 @@ You must choose ONE of:
             messages.push(ChatMessage::user(context_content));
         }
 
-        messages
+        Ok(messages)
+    }
+
+    /// Episodes from before the chat window that relate to what's being
+    /// talked about, one per line, for the response context
+    async fn recall_memories(
+        &self,
+        spec: &CharacterSpec,
+        observation: &Observation,
+        reply_to: Option<&ChatPacket>,
+    ) -> Option<String> {
+        let config = &self.config.recall;
+        if !config.enabled {
+            return None;
+        }
+        let chat = observation.chat_for(&spec.id, self.storage.scope());
+        let mut topic: Vec<&str> = chat
+            .iter()
+            .rev()
+            .take(3)
+            .map(|p| p.content.as_str())
+            .collect();
+        topic.extend(reply_to.map(|p| p.content.as_str()));
+        if topic.is_empty() {
+            topic.push(&observation.screen_summary.notes);
+        }
+        let before = observation
+            .recent_chat
+            .first()
+            .map_or_else(|| Utc::now().timestamp(), |p| p.timestamp);
+
+        let memories = self
+            .storage
+            .recall(&topic.join("\n"), before, Some(&spec.id), config)
+            .await
+            .unwrap_or_else(|err| {
+                warn!(?err, "Failed to recall memories");
+                Vec::new()
+            });
+        (!memories.is_empty()).then(|| {
+            memories
+                .iter()
+                .map(format_memory)
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    /// Lore entries whose keys come up in the last few messages or on screen
+    fn active_lore(
+        &self,
+        spec: &CharacterSpec,
+        chat: &[&ChatPacket],
+        observation: &Observation,
+    ) -> Option<String> {
+        if spec.character_book.is_empty() || self.config.lore_token_budget == 0 {
+            return None;
+        }
+        let recent = &chat[chat.len().saturating_sub(self.config.lore_scan_depth)..];
+        let mut text: Vec<&str> = recent.iter().map(|p| p.content.as_str()).collect();
+        text.extend(observation.screen_summary.text.as_deref());
+        let active = lore::activate(
+            &spec.character_book,
+            &text.join("\n"),
+            self.config.lore_token_budget,
+        );
+        (!active.is_empty()).then(|| {
+            active
+                .iter()
+                .map(|entry| entry.content.trim())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+    }
+
+    /// Why a screen change described as `description` is a repeat: a
+    /// companion commented on the same thing within `suppress_minutes`
+    fn repeated_topic(&self, description: &str, now: i64) -> Option<String> {
+        let config = &self.config.topics;
+        if !config.enabled || config.suppress_minutes == 0 {
+            return None;
+        }
+        let since = now - config.suppress_minutes as i64 * 60;
+        let topic = self.topics.matching(description, since, config.min_relevance)?;
+        Some(format!(
+            "Already discussed: {} commented on \"{}\" {} min ago",
+            self.speaker_name(&topic.character_id),
+            topic.text,
+            (now - topic.at) / 60
+        ))
+    }
+
+    /// How many of its unprompted questions `character` has used this hour,
+    /// for eligibility reasons; None without a limit
+    fn question_budget(&self, character: &LoadedCharacter) -> Option<String> {
+        let limit = self.timing(character).questions_per_hour as usize;
+        if limit == 0 {
+            return None;
+        }
+        let asked = character.state.questions_this_hour();
+        Some(if asked >= limit {
+            format!("question budget spent ({asked}/{limit} this hour), shouldn't ask the user anything")
+        } else {
+            format!("asked {asked}/{limit} questions this hour")
+        })
+    }
+
+    /// Whether `character` has asked all the unprompted questions it may
+    /// this hour
+    fn out_of_questions(&self, character: &LoadedCharacter) -> bool {
+        let limit = self.timing(character).questions_per_hour as usize;
+        limit > 0 && character.state.questions_this_hour() >= limit
+    }
+
+    /// The user's local date and time, for prompts
+    fn clock_note(&self) -> Option<String> {
+        self.config
+            .clock
+            .enabled
+            .then(|| self.clock.note(Utc::now().timestamp()))
+    }
+
+    /// Topics the companions brought up unprompted, one per line, for prompts
+    fn topic_notes(&self) -> Option<String> {
+        if !self.config.topics.enabled || self.topics.recent().is_empty() {
+            return None;
+        }
+        let now = Utc::now().timestamp();
+        let lines: Vec<String> = self
+            .topics
+            .recent()
+            .iter()
+            .rev()
+            .map(|topic| {
+                let name = self.speaker_name(&topic.character_id);
+                format!("- {} min ago, {name}: {}", (now - topic.at) / 60, topic.text)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Open goals, one per line, for prompts
+    fn goal_notes(&self, observation: &Observation) -> Option<String> {
+        if !self.config.goals.enabled || self.goals.active().is_empty() {
+            return None;
+        }
+        let last_user_message = last_user_message(observation);
+        let lines: Vec<String> = self
+            .goals
+            .active()
+            .iter()
+            .map(|goal| {
+                let name = self.speaker_name(&goal.character_id);
+                format!("- {}", goal.describe(name, last_user_message))
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// `[director]` cooldown settings with the character's own overrides,
+    /// the cooldown scaled by their mood
+    fn timing(&self, character: &LoadedCharacter) -> CompanionTiming {
+        let mut timing = self.config.timing(character.spec.director_overrides());
+        if self.config.mood.enabled {
+            let mood = &character.state.mood;
+            let scale = mood.cooldown_scale(Instant::now(), self.config.mood.half_life());
+            timing.cooldown_after_speak = timing.cooldown_after_speak.mul_f32(scale);
+        }
+        if self.config.relationship.enabled {
+            let scale = relationship::cooldown_scale(character.state.relationship_score);
+            timing.cooldown_after_speak = timing.cooldown_after_speak.mul_f32(scale);
+        }
+        timing
+    }
+
+    /// A companion's display name, or `sender` itself for anyone else
+    fn speaker_name<'a>(&'a self, sender: &'a str) -> &'a str {
+        self.characters
+            .iter()
+            .find(|c| c.spec.id == sender)
+            .map_or(sender, |c| c.spec.name.as_str())
     }
 }
 
@@ -812,6 +2071,64 @@ fn format_chat(packets: &[ChatPacket]) -> String {
         .join("\n")
 }
 
+/// "- Mar 3, lyra: ..." for the response context
+fn format_memory(episode: &Episode) -> String {
+    let when = DateTime::from_timestamp(episode.timestamp, 0)
+        .map(|t| t.with_timezone(&Local).format("%b %-d").to_string())
+        .unwrap_or_default();
+    match &episode.actor {
+        Some(actor) => format!("- {when}, {actor}: {}", episode.content),
+        None => format!("- {when}: {}", episode.content),
+    }
+}
+
+/// Keyboard and mouse rates for prompts, when tracked
+fn input_activity(observation: &Observation) -> Option<String> {
+    observation
+        .screen_summary
+        .activity
+        .map(|activity| activity.summary())
+}
+
+/// How long since the user spoke, for prompts
+fn silence_note(observation: &Observation) -> String {
+    if observation.seconds_since_user_message == u64::MAX {
+        "User has not spoken yet.".to_string()
+    } else if observation.seconds_since_user_message < 5 {
+        "User just spoke.".to_string()
+    } else {
+        format!("{}s since user last spoke.", observation.seconds_since_user_message)
+    }
+}
+
+/// Whether the last thing said in `observation`'s chat was the user's
+fn answering_user(observation: &Observation) -> bool {
+    observation
+        .recent_chat
+        .last()
+        .is_some_and(|p| p.sender == "user")
+}
+
+/// When the user last said something in `observation`'s chat
+fn last_user_message(observation: &Observation) -> Option<i64> {
+    observation
+        .recent_chat
+        .iter()
+        .rev()
+        .find(|p| p.sender == "user")
+        .map(|p| p.timestamp)
+}
+
+/// What came due, one per line, for prompts
+fn scheduled_note(observation: &Observation) -> Option<String> {
+    let texts: Vec<&str> = observation
+        .scheduled
+        .iter()
+        .map(|s| s.text.as_str())
+        .collect();
+    (!texts.is_empty()).then(|| texts.join("\n"))
+}
+
 fn truncate(input: &str, max: usize) -> String {
     if input.len() <= max {
         input.to_string()
@@ -820,15 +2137,8 @@ fn truncate(input: &str, max: usize) -> String {
     }
 }
 
-fn encode_rgba_to_base64(image: &RgbaImage) -> Result<String> {
-    let mut buffer = Vec::new();
-    let mut cursor = Cursor::new(&mut buffer);
-    DynamicImage::ImageRgba8(image.clone()).write_to(&mut cursor, ImageFormat::Png)?;
-    Ok(BASE64.encode(buffer))
-}
-
 fn arbiter_schema() -> Value {
-    json!({
+    let mut schema = json!({
         "type": "object",
         "properties": {
             "who_should_talk": { 
@@ -841,18 +2151,197 @@ fn arbiter_schema() -> Value {
             }
         },
         "required": ["who_should_talk", "reasoning"]
+    });
+    add_delivery(&mut schema);
+    add_confidence(&mut schema);
+    schema
+}
+
+fn screen_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "escalate": {
+                "type": "boolean",
+                "description": "true if the screen and chat are worth a closer look"
+            },
+            "reason": { "type": "string" }
+        },
+        "required": ["escalate", "reason"]
     })
 }
 
 #[derive(Debug, Deserialize)]
+struct ScreenVerdict {
+    escalate: bool,
+    #[serde(default)]
+    reason: String,
+}
+
+/// VLA and arbiter fields in one object, for combined mode
+fn combined_schema(with_presence: bool) -> Value {
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "significant_change": {
+                "type": "boolean",
+                "description": "true if DESKTOP shows something meaningfully different from PREV"
+            },
+            "description": {
+                "type": "string",
+                "description": "Brief description of what changed (or 'nothing significant')"
+            },
+            "who_should_talk": {
+                "type": "string",
+                "description": "The companion ID who should speak, or 'none' if no one should"
+            },
+            "reasoning": {
+                "type": "string",
+                "description": "Brief explanation of why this companion should speak (or why no one should)"
+            }
+        },
+        "required": ["significant_change", "description", "who_should_talk", "reasoning"]
+    });
+    add_delivery(&mut schema);
+    add_confidence(&mut schema);
+    if with_presence {
+        add_presence(&mut schema);
+    }
+    schema
+}
+
+/// Ask how the chosen companion should deliver the reply
+fn add_delivery(schema: &mut Value) {
+    schema["properties"]["urgency"] = json!({
+        "type": "number",
+        "minimum": 0,
+        "maximum": 1,
+        "description": "How pressing the reply is: 0 for an idle musing, 1 for something the user needs to hear now"
+    });
+    schema["properties"]["suggested_mood"] = json!({
+        "type": "string",
+        "enum": MOODS,
+        "description": "How the companion should sound and look while saying it"
+    });
+    if let Some(required) = schema["required"].as_array_mut() {
+        required.push(json!("urgency"));
+        required.push(json!("suggested_mood"));
+    }
+}
+
+/// Ask how sure the arbiter is of its pick, for `min_confidence`
+fn add_confidence(schema: &mut Value) {
+    schema["properties"]["confidence"] = json!({
+        "type": "number",
+        "minimum": 0,
+        "maximum": 1,
+        "description": "How sure you are of this choice: 0 for a guess, 1 for clearly right"
+    });
+    if let Some(required) = schema["required"].as_array_mut() {
+        required.push(json!("confidence"));
+    }
+}
+
+/// Ask for `user_presence`, read from the CAMERA panel
+fn add_presence(schema: &mut Value) {
+    schema["properties"]["user_presence"] = json!({
+        "type": "string",
+        "description": "What CAMERA shows the user doing, e.g. 'at desk, watching the screen', 'looking away', 'on a call', or 'away'"
+    });
+    if let Some(required) = schema["required"].as_array_mut() {
+        required.push(json!("user_presence"));
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct ArbiterDecision {
     #[serde(deserialize_with = "deserialize_optional_string")]
     who_should_talk: Option<String>,
     reasoning: String,
+    #[serde(default)]
+    urgency: Option<f32>,
+    #[serde(default)]
+    suggested_mood: Option<String>,
+    #[serde(default)]
+    confidence: Option<f32>,
+}
+
+impl ArbiterDecision {
+    /// 0 to 1; middling when the model gave none
+    fn urgency(&self) -> f32 {
+        self.urgency.unwrap_or(0.5).clamp(0.0, 1.0)
+    }
+
+    /// 0 to 1; fully confident when the model gave none
+    fn confidence(&self) -> f32 {
+        self.confidence.unwrap_or(1.0).clamp(0.0, 1.0)
+    }
+
+    /// The suggested mood, if it is one the puppet and voices know
+    fn mood(&self) -> Option<String> {
+        let mood = self.suggested_mood.as_deref()?.trim().to_lowercase();
+        MOODS.contains(&mood.as_str()).then_some(mood)
+    }
+
+    /// The decision as logged, before its outcome is known
+    fn to_stored(&self) -> StoredDecision {
+        let should_respond = self.who_should_talk.is_some();
+        let mut decision = StoredDecision::now(
+            should_respond,
+            self.who_should_talk.clone(),
+            self.reasoning.clone(),
+            if should_respond { self.urgency() } else { 0.0 },
+        );
+        if should_respond {
+            decision.mood = self.mood();
+        }
+        decision
+    }
+}
+
+/// What the audit checks a reply for, in the order problems are reported
+const AUDIT_CRITERIA: [&str; 4] = ["repetition", "persona", "length", "safety"];
+
+fn audit_schema() -> Value {
+    let criterion = json!({
+        "type": "object",
+        "properties": {
+            "pass": { "type": "boolean" },
+            "issue": { "type": "string" }
+        },
+        "required": ["pass"]
+    });
+    let criteria: serde_json::Map<String, Value> = AUDIT_CRITERIA
+        .iter()
+        .map(|name| (name.to_string(), criterion.clone()))
+        .collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "criteria": {
+                "type": "object",
+                "properties": criteria,
+                "required": AUDIT_CRITERIA
+            },
+            "status": { "type": "string", "enum": ["approve", "revise", "block"] },
+            "text": { "type": "string" },
+            "reason": { "type": "string" }
+        },
+        "required": ["criteria", "status"]
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditCriterion {
+    pass: bool,
+    #[serde(default)]
+    issue: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct AuditResult {
+    #[serde(default)]
+    criteria: HashMap<String, AuditCriterion>,
     status: String,
     #[serde(default)]
     text: Option<String>,
@@ -860,12 +2349,56 @@ struct AuditResult {
     reason: Option<String>,
 }
 
+#[derive(Debug, PartialEq)]
+enum AuditVerdict {
+    Approve,
+    Revise(String),
+    Block(String),
+}
+
+impl AuditResult {
+    /// "length: three paragraphs" for each failed criterion
+    fn failed(&self) -> Vec<String> {
+        AUDIT_CRITERIA
+            .iter()
+            .filter_map(|name| {
+                let criterion = self.criteria.get(*name).filter(|c| !c.pass)?;
+                Some(match &criterion.issue {
+                    Some(issue) => format!("{name}: {issue}"),
+                    None => name.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Failed criteria call for a revision, and a failed safety check (or a
+    /// failure with no revision offered) blocks the reply
+    fn verdict(self) -> AuditVerdict {
+        let failed = self.failed();
+        let unsafe_reply = self.criteria.get("safety").is_some_and(|c| !c.pass);
+        if unsafe_reply || self.status == "block" {
+            let reason = self.reason.unwrap_or_else(|| failed.join("; "));
+            return AuditVerdict::Block(reason);
+        }
+        if failed.is_empty() && self.status != "revise" {
+            return AuditVerdict::Approve;
+        }
+        match self.text.filter(|text| !text.trim().is_empty()) {
+            Some(text) => AuditVerdict::Revise(text),
+            None if failed.is_empty() => AuditVerdict::Approve,
+            None => AuditVerdict::Block(format!("{} (no revision offered)", failed.join("; "))),
+        }
+    }
+}
+
 pub enum Decision {
     Pass {
         reasoning: String,
         urgency: f32,
     },
     Speak {
+        /// The logged arbiter decision, for keying screenshots
+        decision_id: i64,
         character_id: String,
         text: String,
         urgency: f32,
@@ -879,7 +2412,7 @@ pub enum Decision {
 /// Log of a prompt/response exchange with a model
 #[derive(Debug, Clone)]
 pub struct PromptLog {
-    /// "arbiter" or "response"
+    /// "screen", "vla", "arbiter", "combined", "response" or "audit"
     pub model_type: String,
     /// The model name used
     pub model_name: String,
@@ -887,6 +2420,27 @@ pub struct PromptLog {
     pub prompt: String,
     /// The model's response
     pub response: String,
+    /// Wall-clock time of the request, including any wait for a provider slot
+    pub latency_ms: u64,
+    /// Token counts, if the provider reported them
+    pub usage: Option<TokenUsage>,
+    /// Chain of thought stripped from the response (only with `log_reasoning`)
+    pub reasoning: Option<String>,
+}
+
+impl PromptLog {
+    pub fn to_stored(&self) -> StoredLlmCall {
+        StoredLlmCall {
+            timestamp: Utc::now().timestamp(),
+            model_type: self.model_type.clone(),
+            model_name: self.model_name.clone(),
+            prompt: self.prompt.clone(),
+            response: self.response.clone(),
+            latency_ms: self.latency_ms,
+            prompt_tokens: self.usage.map(|u| u.prompt_tokens),
+            completion_tokens: self.usage.map(|u| u.completion_tokens),
+        }
+    }
 }
 
 /// Result of evaluate() including prompt logs for debugging
@@ -902,3 +2456,6 @@ where
     let s: String = serde::Deserialize::deserialize(deserializer)?;
     if s.is_empty() { Ok(None) } else { Ok(Some(s)) }
 }
+
+#[cfg(test)]
+mod tests;
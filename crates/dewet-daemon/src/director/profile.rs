@@ -0,0 +1,166 @@
+//! Learns a user profile from chat: every `[storage.profile]` interval the
+//! arbiter model reads the messages since the last pass and any new facts
+//! are merged into the stored profile
+
+use std::{path::Path, time::Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use super::{
+    format_chat,
+    prompts::{self, PromptTemplates},
+};
+use crate::{
+    config::{DirectorConfig, ProfileConfig},
+    llm::{LlmClients, SharedLlm},
+    storage::{Storage, StoredLlmCall, UserProfile},
+};
+
+pub struct ProfileLearner {
+    storage: Storage,
+    llm: SharedLlm,
+    model: String,
+    prompts: PromptTemplates,
+}
+
+impl ProfileLearner {
+    /// Learn with the arbiter model and `[director] prompts_dir` overrides
+    pub fn new(storage: Storage, clients: &LlmClients, director: &DirectorConfig) -> Self {
+        Self {
+            storage,
+            llm: clients.arbiter.clone(),
+            model: clients.arbiter_model.clone(),
+            prompts: PromptTemplates::load(Path::new(&director.prompts_dir)),
+        }
+    }
+
+    /// Learn from every interval until the daemon exits
+    pub fn spawn(mut self, config: ProfileConfig) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(config.interval()).await;
+                self.prompts.reload_if_changed();
+                match self.learn(config.max_messages).await {
+                    Ok(true) => info!("User profile updated"),
+                    Ok(false) => {}
+                    Err(err) => warn!(?err, "Failed to update the user profile"),
+                }
+            }
+        });
+    }
+
+    /// Read chat newer than the profile and merge what it reveals, returning
+    /// whether there was any new user message to learn from
+    pub async fn learn(&self, max_messages: usize) -> Result<bool> {
+        let mut profile = self.storage.user_profile().await?;
+        let chat: Vec<_> = self
+            .storage
+            .recent_chat(max_messages)
+            .await?
+            .into_iter()
+            .filter(|packet| packet.timestamp > profile.learned_until)
+            .collect();
+        let Some(newest) = chat.last().map(|packet| packet.timestamp) else {
+            return Ok(false);
+        };
+        if !chat.iter().any(|packet| packet.sender == "user") {
+            return Ok(false);
+        }
+
+        let prompt = self.prompts.render(
+            prompts::PROFILE,
+            json!({ "profile": profile.summary(), "chat": format_chat(&chat) }),
+        )?;
+        let started = Instant::now();
+        let response = self
+            .llm
+            .complete_json(&self.model, &prompt, profile_schema())
+            .await?;
+        self.storage.record_llm_call(StoredLlmCall {
+            timestamp: Utc::now().timestamp(),
+            model_type: "profile".into(),
+            model_name: self.model.clone(),
+            prompt,
+            response: response.to_string(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            prompt_tokens: None,
+            completion_tokens: None,
+        });
+
+        let mut learned: UserProfile = serde_json::from_value(response)?;
+        learned.learned_until = newest;
+        profile.merge(learned);
+        self.storage.save_user_profile(&profile).await?;
+        Ok(true)
+    }
+}
+
+fn profile_schema() -> Value {
+    let list = |description: &str| json!({ "type": "array", "items": { "type": "string" }, "description": description });
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": ["string", "null"] },
+            "timezone": { "type": ["string", "null"], "description": "IANA timezone name" },
+            "preferences": list("How the user likes things done"),
+            "habits": list("Recurring routines"),
+            "likes": list("Things the user enjoys"),
+            "dislikes": list("Things the user dislikes"),
+        },
+        "required": ["name", "timezone", "preferences", "habits", "likes", "dislikes"]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bridge::{ChatPacket, MemoryTier},
+        config::StorageConfig,
+        llm::{MockLlmClient, mock::mock_clients},
+    };
+
+    #[tokio::test]
+    async fn test_learns_from_new_chat_only() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        storage.record_chat(&ChatPacket {
+            sender: "user".into(),
+            content: "I'm Sam, and I can't stand cilantro".into(),
+            timestamp: Utc::now().timestamp(),
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id: None,
+        });
+        storage.flush().await.unwrap();
+
+        let arbiter = MockLlmClient::new()
+            .with_json(json!({
+                "name": "Sam",
+                "timezone": null,
+                "preferences": [],
+                "habits": [],
+                "likes": [],
+                "dislikes": ["cilantro"],
+            }))
+            .shared();
+        let mock = MockLlmClient::new().shared();
+        let clients = mock_clients(mock.clone(), arbiter.clone(), mock);
+        let learner = ProfileLearner::new(storage.clone(), &clients, &DirectorConfig::default());
+
+        assert!(learner.learn(50).await.unwrap());
+        let profile = storage.user_profile().await.unwrap();
+        assert_eq!(profile.summary(), "Name: Sam\nDislikes: cilantro");
+        assert!(arbiter.requests()[0].prompt.contains("cilantro"));
+        // Nothing new to read on the next pass
+        assert!(!learner.learn(50).await.unwrap());
+        assert_eq!(arbiter.request_count(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Prompt templates for the decision pipeline
+//!
+//! Built-in templates are compiled into the binary. A file with the same name in
+//! the prompts directory (default `config/prompts/`) overrides the built-in, so
+//! prompts can be tuned for a specific model without recompiling. Overrides
+//! are reloaded when a file in the directory is added, edited or removed.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use minijinja::{AutoEscape, Environment};
+use serde::Serialize;
+use tracing::{info, warn};
+
+pub const VLA: &str = "vla.j2";
+pub const ARBITER: &str = "arbiter.j2";
+pub const COMBINED: &str = "combined.j2";
+pub const AUDIT: &str = "audit.j2";
+pub const RESPONSE_SYSTEM: &str = "response_system.j2";
+pub const RESPONSE_CONTEXT: &str = "response_context.j2";
+pub const PROFILE: &str = "profile.j2";
+pub const REFLECTION: &str = "reflection.j2";
+pub const SCREEN: &str = "screen.j2";
+pub const REGENERATE: &str = "regenerate.j2";
+
+const BUILTIN: &[(&str, &str)] = &[
+    (VLA, include_str!("prompts/vla.j2")),
+    (ARBITER, include_str!("prompts/arbiter.j2")),
+    (COMBINED, include_str!("prompts/combined.j2")),
+    (AUDIT, include_str!("prompts/audit.j2")),
+    (RESPONSE_SYSTEM, include_str!("prompts/response_system.j2")),
+    (RESPONSE_CONTEXT, include_str!("prompts/response_context.j2")),
+    (PROFILE, include_str!("prompts/profile.j2")),
+    (REFLECTION, include_str!("prompts/reflection.j2")),
+    (SCREEN, include_str!("prompts/screen.j2")),
+    (REGENERATE, include_str!("prompts/regenerate.j2")),
+];
+
+pub struct PromptTemplates {
+    env: Environment<'static>,
+    /// Override directory, when loaded from one
+    dir: Option<PathBuf>,
+    /// Each override's modification time and size as loaded (None if absent)
+    stamps: Vec<Option<(SystemTime, u64)>>,
+}
+
+impl PromptTemplates {
+    /// Built-in templates only
+    pub fn builtin() -> Self {
+        let mut env = new_environment();
+        for &(name, source) in BUILTIN {
+            env.add_template(name, source)
+                .expect("built-in prompt template must parse");
+        }
+        Self {
+            env,
+            dir: None,
+            stamps: Vec::new(),
+        }
+    }
+
+    /// Built-in templates, overridden by any same-named files in `dir`.
+    /// Overrides that fail to read or parse are skipped with a warning.
+    pub fn load(dir: &Path) -> Self {
+        let stamps = override_stamps(dir);
+        let mut env = new_environment();
+        for &(name, source) in BUILTIN {
+            let path = dir.join(name);
+            let overridden = path.exists()
+                && match fs::read_to_string(&path) {
+                    Ok(contents) => match env.add_template_owned(name.to_string(), contents) {
+                        Ok(()) => {
+                            info!(template = %name, ?path, "Using prompt override");
+                            true
+                        }
+                        Err(err) => {
+                            warn!(template = %name, ?path, %err, "Invalid prompt override, using built-in");
+                            false
+                        }
+                    },
+                    Err(err) => {
+                        warn!(template = %name, ?path, %err, "Unreadable prompt override, using built-in");
+                        false
+                    }
+                };
+
+            if !overridden {
+                env.add_template(name, source)
+                    .expect("built-in prompt template must parse");
+            }
+        }
+        Self {
+            env,
+            dir: Some(dir.to_path_buf()),
+            stamps,
+        }
+    }
+
+    /// Load the overrides again if any changed on disk since they were
+    /// loaded, returning whether they did
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(dir) = self.dir.clone() else {
+            return false;
+        };
+        if override_stamps(&dir) == self.stamps {
+            return false;
+        }
+        info!(?dir, "Prompt overrides changed, reloading");
+        *self = Self::load(&dir);
+        true
+    }
+
+    pub fn render<S: Serialize>(&self, name: &str, context: S) -> Result<String> {
+        let template = self.env.get_template(name)?;
+        Ok(template.render(context)?)
+    }
+}
+
+/// Modification time and size of each built-in's override file
+fn override_stamps(dir: &Path) -> Vec<Option<(SystemTime, u64)>> {
+    BUILTIN
+        .iter()
+        .map(|(name, _)| {
+            let metadata = fs::metadata(dir.join(name)).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+fn new_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    // Prompts are plain text; never HTML-escape chat content
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let prompts = PromptTemplates::builtin();
+
+        let vla = prompts.render(VLA, context! { has_ariaos => true }).unwrap();
+        assert!(vla.contains("IMAGE 2 - ARIAOS"));
+        let vla = prompts.render(VLA, context! { has_ariaos => false }).unwrap();
+        assert!(!vla.contains("ARIAOS"));
+
+        let arbiter = prompts
+            .render(
+                ARBITER,
+                context! {
+                    has_composite => false,
+                    has_ariaos => false,
+                    vla_significant => true,
+                    vla_description => "New window",
+                    silence_note => "User just spoke.",
+                    last_speaker => "user",
+                    chat => "user: hi",
+                    companions => vec![context! {
+                        name => "Lyra", id => "lyra", personality => "Warm",
+                        description => "Librarian", scenario => "Bezel", reason => "Not last speaker",
+                    }],
+                },
+            )
+            .unwrap();
+        assert!(arbiter.contains("SIGNIFICANT CHANGE DETECTED"));
+        assert!(arbiter.contains("### Lyra (id: lyra)"));
+        assert!(!arbiter.contains("# Visual Context"));
+
+        let context = prompts
+            .render(RESPONSE_CONTEXT, context! { screen => "diff=0.1", has_ariaos => false })
+            .unwrap();
+        assert!(context.starts_with("[Current context: diff=0.1]"));
+    }
+
+    #[test]
+    fn test_override_replaces_builtin() {
+        let dir = std::env::temp_dir().join(format!("dewet-prompts-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(AUDIT), "Audit {{ name }}").unwrap();
+        fs::write(dir.join(VLA), "{% if broken").unwrap();
+
+        let prompts = PromptTemplates::load(&dir);
+        assert_eq!(prompts.render(AUDIT, context! { name => "Lyra" }).unwrap(), "Audit Lyra");
+        // Broken override falls back to the built-in
+        assert!(prompts
+            .render(VLA, context! { has_ariaos => false })
+            .unwrap()
+            .contains("CHANGE DETECTOR"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reload_picks_up_edits() {
+        let dir = std::env::temp_dir().join(format!("dewet-prompts-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut prompts = PromptTemplates::load(&dir);
+        assert!(!prompts.reload_if_changed());
+
+        fs::write(dir.join(AUDIT), "Audit {{ name }}").unwrap();
+        assert!(prompts.reload_if_changed());
+        let audit = prompts.render(AUDIT, context! { name => "Lyra" }).unwrap();
+        assert_eq!(audit, "Audit Lyra");
+
+        // Removing the override brings back the built-in
+        fs::remove_file(dir.join(AUDIT)).unwrap();
+        assert!(prompts.reload_if_changed());
+        let audit = prompts.render(AUDIT, context! { name => "Lyra" }).unwrap();
+        assert_ne!(audit, "Audit Lyra");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,200 @@
+//! Reflection after a reply: the arbiter model looks back at what a
+//! companion just said and writes a one-line observation into its memory,
+//! and with `[director.reflection] notes` onto its ARIAOS notes
+
+use std::{path::Path, time::Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::{debug, warn};
+
+use super::prompts::{self, PromptTemplates};
+use crate::{
+    ariaos::{AriaosCommand, NotesAction},
+    config::{DirectorConfig, ReflectionConfig},
+    llm::{LlmClients, SharedLlm},
+    storage::{Episode, EpisodeKind, Storage, StoredLlmCall},
+};
+
+/// A reply to reflect on
+#[derive(Debug, Clone)]
+pub struct ReflectionJob {
+    pub character_id: String,
+    pub name: String,
+    pub reply: String,
+    /// The chat the reply answered, formatted for prompts
+    pub chat: String,
+    pub screen: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reflection {
+    #[serde(default)]
+    note: String,
+}
+
+pub struct Reflector {
+    storage: Storage,
+    llm: SharedLlm,
+    model: String,
+    prompts: PromptTemplates,
+    config: ReflectionConfig,
+    /// Receives notes appends, executed like the companion's own tool calls
+    notes: Option<UnboundedSender<(String, Vec<AriaosCommand>)>>,
+}
+
+impl Reflector {
+    /// Reflect with the arbiter model and `[director] prompts_dir` overrides
+    pub fn new(storage: Storage, clients: &LlmClients, director: &DirectorConfig) -> Self {
+        Self {
+            storage,
+            llm: clients.arbiter.clone(),
+            model: clients.arbiter_model.clone(),
+            prompts: PromptTemplates::load(Path::new(&director.prompts_dir)),
+            config: director.reflection.clone(),
+            notes: None,
+        }
+    }
+
+    /// Send reflections to `sink` as notes appends when `notes` is set
+    pub fn with_notes(mut self, sink: UnboundedSender<(String, Vec<AriaosCommand>)>) -> Self {
+        self.notes = Some(sink);
+        self
+    }
+
+    /// Reflect on each job sent to the returned channel, one at a time, off
+    /// the perception loop
+    pub fn spawn(mut self) -> UnboundedSender<ReflectionJob> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<ReflectionJob>();
+        tokio::spawn(async move {
+            while let Some(job) = rx.recv().await {
+                self.prompts.reload_if_changed();
+                if let Err(err) = self.reflect(&job).await {
+                    warn!(?err, character_id = %job.character_id, "Reflection failed");
+                }
+            }
+        });
+        tx
+    }
+
+    /// Write the companion's reflection on `job`, returning it; None when
+    /// the model found nothing worth noting
+    pub async fn reflect(&self, job: &ReflectionJob) -> Result<Option<String>> {
+        let prompt = self.prompts.render(
+            prompts::REFLECTION,
+            json!({
+                "name": job.name,
+                "chat": job.chat,
+                "screen": job.screen,
+                "reply": job.reply,
+            }),
+        )?;
+        let started = Instant::now();
+        let response = self
+            .llm
+            .complete_json(&self.model, &prompt, reflection_schema())
+            .await?;
+        self.storage.record_llm_call(StoredLlmCall {
+            timestamp: Utc::now().timestamp(),
+            model_type: "reflection".into(),
+            model_name: self.model.clone(),
+            prompt,
+            response: response.to_string(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            prompt_tokens: None,
+            completion_tokens: None,
+        });
+
+        let reflection: Reflection = serde_json::from_value(response)?;
+        // Only the first line, however much the model wrote
+        let note = reflection.note.lines().next().unwrap_or_default().trim();
+        if note.is_empty() {
+            return Ok(None);
+        }
+        debug!(character_id = %job.character_id, note, "Reflected on reply");
+
+        let sink = self.notes.as_ref().filter(|_| self.config.notes);
+        match sink {
+            // Recorded as a notes edit once the append is executed
+            Some(sink) => {
+                let append = AriaosCommand::Notes(NotesAction::Append(format!("- {note}")));
+                let _ = sink.send((job.character_id.clone(), vec![append]));
+            }
+            None => self.storage.remember(
+                Episode::now(EpisodeKind::Reflection, Some(job.character_id.clone()), note)
+                    .for_character(&job.character_id),
+            ),
+        }
+        Ok(Some(note.to_string()))
+    }
+}
+
+fn reflection_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "note": {
+                "type": "string",
+                "description": "One short line worth remembering, or empty"
+            }
+        },
+        "required": ["note"]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::StorageConfig,
+        llm::{MockLlmClient, mock::mock_clients},
+    };
+
+    fn job() -> ReflectionJob {
+        ReflectionJob {
+            character_id: "lyra".into(),
+            name: "Lyra".into(),
+            reply: "Want me to look at that linker error?".into(),
+            chat: "user: ugh, the build broke again".into(),
+            screen: "cargo build output with a linker error".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reflection_appends_first_line_to_notes() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let arbiter = MockLlmClient::new()
+            .with_json(json!({ "note": "user seemed frustrated with the build\nand tired" }))
+            .with_json(json!({ "note": "  " }))
+            .shared();
+        let mock = MockLlmClient::new().shared();
+        let clients = mock_clients(mock.clone(), arbiter.clone(), mock);
+        let mut director = DirectorConfig::default();
+        director.reflection.notes = true;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let reflector = Reflector::new(storage, &clients, &director).with_notes(tx);
+
+        let note = reflector.reflect(&job()).await.unwrap();
+        assert_eq!(note.as_deref(), Some("user seemed frustrated with the build"));
+        assert!(arbiter.requests()[0].prompt.contains("linker error"));
+        let (character_id, commands) = rx.try_recv().unwrap();
+        assert_eq!(character_id, "lyra");
+        assert!(matches!(
+            &commands[..],
+            [AriaosCommand::Notes(NotesAction::Append(text))]
+                if text == "- user seemed frustrated with the build"
+        ));
+
+        // Nothing worth noting writes nothing
+        assert_eq!(reflector.reflect(&job()).await.unwrap(), None);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Regenerating the last reply: the user didn't like what a companion said,
+//! so the same companion answers the same moment again, with the reply it
+//! takes back and the user's feedback in its chat. The new reply goes
+//! through the audit and style pass like any other.
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::json;
+use tracing::info;
+
+use super::{ArbiterDecision, Decision, Director, EvaluateResult, prompts};
+use crate::{
+    bridge::{ChatPacket, MemoryTier},
+    observation::Observation,
+};
+
+/// The last reply spoken, with what it answered
+pub(super) struct LastReply {
+    pub observation: Observation,
+    pub reply_to: Option<ChatPacket>,
+    pub responder_index: usize,
+    pub arbiter: ArbiterDecision,
+    pub text: String,
+}
+
+impl Director {
+    /// Say the last reply again differently, taking `feedback` into
+    /// account. None when no companion has spoken yet; a Pass when the new
+    /// reply is rejected, leaving the old one standing.
+    pub async fn regenerate(&mut self, feedback: &str) -> Result<Option<EvaluateResult>> {
+        let Some(last) = self.last_reply.take() else {
+            return Ok(None);
+        };
+        let responder_id = self.characters[last.responder_index].spec.id.clone();
+        info!(responder_id = %responder_id, feedback, "Regenerating reply");
+
+        let now = Utc::now().timestamp();
+        let packet = |sender: &str, content: String, character_id: Option<String>| ChatPacket {
+            sender: sender.to_string(),
+            content,
+            timestamp: now,
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id,
+        };
+        let request = self
+            .prompts
+            .render(prompts::REGENERATE, json!({ "feedback": feedback.trim() }))?;
+        let mut observation = last.observation.clone();
+        observation.recent_chat.extend([
+            packet(&responder_id, last.text.clone(), Some(responder_id.clone())),
+            packet("user", request, None),
+        ]);
+
+        let mut decision = last.arbiter.to_stored();
+        decision.reasoning = format!("{} (regenerated: {})", decision.reasoning, feedback.trim());
+        let result = self
+            .respond(
+                &observation,
+                last.responder_index,
+                last.arbiter.clone(),
+                decision,
+                last.reply_to.as_ref(),
+                Vec::new(),
+            )
+            .await?;
+
+        // Another take answers the original moment, not the feedback
+        self.last_reply = Some(match &result.decision {
+            Decision::Speak { text, .. } => LastReply {
+                text: text.clone(),
+                ..last
+            },
+            Decision::Pass { .. } => last,
+        });
+        Ok(Some(result))
+    }
+}
@@ -0,0 +1,86 @@
+//! Replaying a recording (`[observation.recording]`) through the director,
+//! with mock or real models, so decisions before and after a prompt or
+//! config change can be compared tick by tick
+
+use std::{fmt, time::Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::warn;
+
+use super::{Decision, Director};
+use crate::observation::RecordedObservation;
+
+/// What the director decided for one recorded observation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayStep {
+    /// The observation's `offset_ms` in the recording
+    pub offset_ms: u64,
+    /// Who spoke; None for a pass
+    pub character_id: Option<String>,
+    /// The reply, or why nobody spoke
+    pub text: String,
+    pub urgency: f32,
+}
+
+impl fmt::Display for ReplayStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.offset_ms as f64 / 1000.0;
+        match &self.character_id {
+            Some(character_id) => write!(
+                f,
+                "{secs:>8.1}s SPEAK {character_id} ({:.2}): {}",
+                self.urgency, self.text
+            ),
+            None => write!(f, "{secs:>8.1}s PASS {}", self.text),
+        }
+    }
+}
+
+impl Director {
+    /// Evaluate each recorded observation in order. The recording was
+    /// already paced by the live loop, so replayed ticks aren't rate
+    /// limited; the recorded chat is used as is, without the replies given
+    /// here. A failed evaluation is logged and recorded as a pass.
+    pub async fn replay(&mut self, recording: &[RecordedObservation]) -> Result<Vec<ReplayStep>> {
+        let mut steps = Vec::with_capacity(recording.len());
+        for recorded in recording {
+            let observation = recorded.to_observation()?;
+            self.last_decision = Instant::now()
+                .checked_sub(self.config.min_decision_interval())
+                .unwrap_or_else(Instant::now);
+            let step = match self.evaluate(&observation).await {
+                Ok(result) => match result.decision {
+                    Decision::Speak {
+                        character_id,
+                        text,
+                        urgency,
+                        ..
+                    } => ReplayStep {
+                        offset_ms: recorded.offset_ms,
+                        character_id: Some(character_id),
+                        text,
+                        urgency,
+                    },
+                    Decision::Pass { reasoning, urgency } => ReplayStep {
+                        offset_ms: recorded.offset_ms,
+                        character_id: None,
+                        text: reasoning,
+                        urgency,
+                    },
+                },
+                Err(err) => {
+                    warn!(?err, offset_ms = recorded.offset_ms, "Replayed evaluation failed");
+                    ReplayStep {
+                        offset_ms: recorded.offset_ms,
+                        character_id: None,
+                        text: format!("Error: {err:#}"),
+                        urgency: 0.0,
+                    }
+                }
+            };
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+}
@@ -0,0 +1,1327 @@
+//! Director pipeline tests driven by scripted mock LLM clients
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use serde_json::json;
+
+use super::*;
+use crate::{
+    bridge::MemoryTier,
+    clipboard::ClipboardEvent,
+    config::{FocusMode, MemoryScope, PipelineMode, StorageConfig},
+    focus::Focus,
+    llm::{MockLlmClient, MockResponse, SharedLlm, mock::mock_clients},
+    observation::{RecordedObservation, ScreenSummary},
+    scheduler::ScheduledPrompt,
+    vision::VisionFrame,
+};
+
+/// Director wired to mocks and an in-memory database
+struct Harness {
+    director: Director,
+    vla: Arc<MockLlmClient>,
+    arbiter: Arc<MockLlmClient>,
+    response: Arc<MockLlmClient>,
+}
+
+impl Harness {
+    async fn new(vla: MockLlmClient, arbiter: MockLlmClient, response: MockLlmClient) -> Self {
+        Self::with_config(DirectorConfig::default(), vla, arbiter, response).await
+    }
+
+    async fn with_config(
+        config: DirectorConfig,
+        vla: MockLlmClient,
+        arbiter: MockLlmClient,
+        response: MockLlmClient,
+    ) -> Self {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            auth_token_env: "DEWET_TEST_UNUSED_TOKEN".into(),
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage");
+
+        let (vla, arbiter, response) = (vla.shared(), arbiter.shared(), response.shared());
+        let config = DirectorConfig {
+            min_decision_interval_ms: 0,
+            ..config
+        };
+        let characters = CharacterSpec::demo()
+            .into_iter()
+            .map(LoadedCharacter::new)
+            .collect();
+        let director = Director::new(
+            storage,
+            mock_clients(vla.clone(), arbiter.clone(), response.clone()),
+            config,
+            characters,
+        );
+
+        Self {
+            director,
+            vla,
+            arbiter,
+            response,
+        }
+    }
+
+    async fn evaluate(&mut self, observation: &Observation) -> EvaluateResult {
+        self.director
+            .evaluate(observation)
+            .await
+            .expect("evaluate should not error")
+    }
+}
+
+/// Build a synthetic observation with a small composite and the given chat
+fn observation(chat: &[(&str, &str)], seconds_since_user_message: u64) -> Observation {
+    let image = ImageBuffer::from_pixel(16, 10, Rgba([40, 40, 40, 255]));
+    let now = Utc::now();
+    let packets: Vec<ChatPacket> = chat
+        .iter()
+        .map(|(sender, content)| ChatPacket {
+            sender: sender.to_string(),
+            content: content.to_string(),
+            timestamp: now.timestamp(),
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id: None,
+        })
+        .collect();
+
+    Observation {
+        frame: VisionFrame {
+            timestamp: now,
+            image: DynamicImage::ImageRgba8(image.clone()),
+            diff_score: 0.0,
+            text: None,
+            close_up: None,
+            hash: 0,
+            duplicate: false,
+            camera: None,
+            tiles: None,
+            activity: None,
+            foreground: None,
+        },
+        composite: Some(image),
+        ariaos: None,
+        screen_summary: ScreenSummary {
+            timestamp: now,
+            diff_score: 0.0,
+            notes: "synthetic frame".into(),
+            text: None,
+            activity: None,
+        },
+        recent_chat: packets.clone(),
+        all_chat: packets,
+        clipboard: Vec::new(),
+        scheduled: Vec::new(),
+        focus: None,
+        seconds_since_user_message,
+    }
+}
+
+fn vla_no_change() -> MockLlmClient {
+    MockLlmClient::new().with_json(json!({
+        "significant_change": false,
+        "description": "nothing significant"
+    }))
+}
+
+#[tokio::test]
+async fn test_speaks_when_user_message_unanswered() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User greeted Lyra"
+        })),
+        MockLlmClient::new().with_tool_call(
+            Some("Hey! Back to the borrow checker?"),
+            "notes_append",
+            json!({ "content": "User said hi" }),
+        ),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "hi there")], 2))
+        .await;
+
+    match result.decision {
+        Decision::Speak {
+            character_id,
+            text,
+            tool_calls,
+            ..
+        } => {
+            assert_eq!(character_id, "lyra");
+            assert_eq!(text, "Hey! Back to the borrow checker?");
+            assert_eq!(tool_calls.len(), 1);
+        }
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+    assert_eq!(result.prompt_logs.len(), 3);
+
+    let response_requests = harness.response.requests();
+    assert_eq!(response_requests.len(), 1);
+    assert_eq!(response_requests[0].image_count, 1);
+    // The card's example dialogue comes along as few-shot turns
+    assert!(
+        response_requests[0]
+            .prompt
+            .contains("copy-pasting docstrings again?")
+    );
+    assert!(
+        response_requests[0]
+            .tool_names
+            .iter()
+            .any(|name| name == "notes_append")
+    );
+}
+
+#[tokio::test]
+async fn test_arbiter_sets_urgency_and_mood() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "The build failed",
+            "urgency": 0.9,
+            "suggested_mood": "Excited"
+        })),
+        MockLlmClient::new().with_text("Your build just broke!"),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "did it pass?")], 2))
+        .await;
+
+    match result.decision {
+        Decision::Speak {
+            urgency,
+            suggested_mood,
+            ..
+        } => {
+            assert_eq!(urgency, 0.9);
+            assert_eq!(suggested_mood.as_deref(), Some("excited"));
+        }
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+}
+
+#[tokio::test]
+async fn test_low_confidence_pick_passes() {
+    let config = DirectorConfig {
+        min_confidence: 0.6,
+        ..Default::default()
+    };
+    let mut harness = Harness::with_config(
+        config,
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({
+                "who_should_talk": "lyra",
+                "reasoning": "Maybe Lyra has something to say",
+                "confidence": 0.3
+            }))
+            .with_json(json!({
+                "who_should_talk": "lyra",
+                "reasoning": "User asked Lyra",
+                "confidence": 0.9
+            })),
+        MockLlmClient::new().with_text("Sure!"),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "anyone around?")], 2))
+        .await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert!(reasoning.contains("low confidence")),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert_eq!(harness.response.request_count(), 0);
+
+    let result = harness
+        .evaluate(&observation(&[("user", "can you help?")], 2))
+        .await;
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+}
+
+#[tokio::test]
+async fn test_response_prompt_includes_matched_lore() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User asked Lyra" }))
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User asked Lyra" })),
+        MockLlmClient::new()
+            .with_text("Of course!")
+            .with_text("Hello!"),
+    )
+    .await;
+    let lore = "archive of user successes";
+
+    harness
+        .evaluate(&observation(&[("user", "remember the last release?")], 2))
+        .await;
+    assert!(harness.response.requests()[0].prompt.contains(lore));
+
+    harness
+        .evaluate(&observation(&[("user", "hi lyra")], 2))
+        .await;
+    assert!(!harness.response.requests()[1].prompt.contains(lore));
+}
+
+#[tokio::test]
+async fn test_response_context_recalls_older_memories() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User shared news"
+        })),
+        MockLlmClient::new().with_text("Told you nom would win!"),
+    )
+    .await;
+    let mut episode = Episode::now(
+        EpisodeKind::UserMessage,
+        Some("user".into()),
+        "Starting the parser migration to nom today",
+    );
+    episode.timestamp -= 2 * 86400;
+    harness
+        .director
+        .storage
+        .record_episode(&episode)
+        .await
+        .unwrap();
+
+    harness
+        .evaluate(&observation(&[("user", "parser migration is done!")], 2))
+        .await;
+    let prompt = &harness.response.requests()[0].prompt;
+    assert!(prompt.contains("Relevant memories"));
+    assert!(prompt.contains("user: Starting the parser migration to nom today"));
+}
+
+#[tokio::test]
+async fn test_companion_answers_fresh_reply() {
+    let mut harness = Harness::with_config(
+        DirectorConfig {
+            max_chain: 1,
+            ..DirectorConfig::default()
+        },
+        vla_no_change(),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User greeted Lyra" }))
+            .with_json(json!({ "who_should_talk": "orion", "reasoning": "Orion disagrees" })),
+        MockLlmClient::new()
+            .with_text("Tabs are better.")
+            .with_text("Spaces, obviously."),
+    )
+    .await;
+
+    let mut observation = observation(&[("user", "tabs or spaces?")], 2);
+    let result = harness.evaluate(&observation).await;
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+
+    let reply = ChatPacket {
+        sender: "lyra".into(),
+        content: "Tabs are better.".into(),
+        timestamp: Utc::now().timestamp(),
+        relevance: 1.0,
+        tier: MemoryTier::Hot,
+        character_id: Some("lyra".into()),
+    };
+    observation.recent_chat.push(reply.clone());
+    let result = harness
+        .director
+        .evaluate_reply(&observation, &reply)
+        .await
+        .unwrap()
+        .expect("a chained reply");
+    assert!(matches!(
+        result.decision,
+        Decision::Speak { ref character_id, .. } if character_id == "orion"
+    ));
+    let arbiter_prompt = &harness.arbiter.requests()[1].prompt;
+    assert!(arbiter_prompt.contains("Lyra just said"));
+    // Other companions' lines are attributed by name
+    let response_prompt = &harness.response.requests()[1].prompt;
+    assert!(response_prompt.contains("[Lyra]: Tabs are better."));
+
+    // The chain is bounded by max_chain
+    let next = harness.director.evaluate_reply(&observation, &reply).await;
+    assert!(next.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_passes_when_arbiter_picks_none() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "none",
+            "reasoning": "Nothing to add"
+        })),
+        MockLlmClient::new(),
+    )
+    .await;
+
+    let result = harness.evaluate(&observation(&[("user", "ok")], 10)).await;
+
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    assert_eq!(harness.arbiter.request_count(), 1);
+    assert_eq!(harness.response.request_count(), 0);
+}
+
+#[tokio::test]
+async fn test_skips_arbiter_without_stimulus() {
+    let mut harness = Harness::new(vla_no_change(), MockLlmClient::new(), MockLlmClient::new()).await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "brb"), ("lyra", "Take your time!")], 600))
+        .await;
+
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    assert_eq!(harness.vla.request_count(), 1);
+    assert_eq!(harness.arbiter.request_count(), 0);
+}
+
+#[tokio::test]
+async fn test_vla_failure_is_not_fatal() {
+    let mut harness = Harness::new(
+        MockLlmClient::new().with_error("model not loaded"),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "none",
+            "reasoning": "No change"
+        })),
+        MockLlmClient::new(),
+    )
+    .await;
+
+    let result = harness.evaluate(&observation(&[("user", "hello?")], 1)).await;
+
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    // Only the arbiter exchange is logged when the VLA errors
+    assert_eq!(result.prompt_logs.len(), 1);
+}
+
+#[tokio::test]
+async fn test_combined_mode_uses_single_call() {
+    let mut harness = Harness::with_config(
+        DirectorConfig {
+            pipeline_mode: PipelineMode::Combined,
+            ..DirectorConfig::default()
+        },
+        MockLlmClient::new(),
+        MockLlmClient::new().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant",
+            "who_should_talk": "lyra",
+            "reasoning": "User asked Lyra a question"
+        })),
+        MockLlmClient::new().with_text("Sure, let me look."),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "lyra, what is this?")], 1))
+        .await;
+
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+    assert_eq!(harness.vla.request_count(), 0);
+    assert_eq!(harness.arbiter.request_count(), 1);
+    assert_eq!(result.prompt_logs[0].model_type, "combined");
+    assert!(result.prompt_logs[0].prompt.contains("Part 1: Change Detection"));
+}
+
+#[tokio::test]
+async fn test_wake_word_addresses_companion() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "orion",
+            "reasoning": "User called for Orion"
+        })),
+        MockLlmClient::new().with_text("Yes?"),
+    )
+    .await;
+    // Without the wake word this is the no-stimulus case above
+    let quiet = observation(&[("user", "brb"), ("lyra", "Take your time!")], 600);
+
+    harness.director.wake(Some("orion".into()));
+    let eligibility = harness.director.compute_eligibility(
+        &quiet,
+        &VlaResult {
+            significant_change: false,
+            description: "nothing significant".into(),
+            user_presence: None,
+        },
+    );
+    let allowed: Vec<_> = eligibility
+        .iter()
+        .filter(|(_, e)| e.is_allowed())
+        .map(|(id, _)| id.as_str())
+        .collect();
+    assert_eq!(allowed, vec!["orion"]);
+
+    let result = harness.evaluate(&quiet).await;
+    assert!(matches!(
+        result.decision,
+        Decision::Speak { ref character_id, .. } if character_id == "orion"
+    ));
+    // The boost ends with the reply
+    assert!(harness.director.active_wake().is_none());
+}
+
+#[tokio::test]
+async fn test_addressed_companion_answers_without_arbiter() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "Lyra knows this one"
+        })),
+        MockLlmClient::new()
+            .with_text("Ship it.")
+            .with_text("Looks fine to me."),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "Orion, what do you think?")], 2))
+        .await;
+    assert!(matches!(
+        result.decision,
+        Decision::Speak { ref character_id, .. } if character_id == "orion"
+    ));
+    assert_eq!(harness.arbiter.request_count(), 0);
+
+    // Mentioning a companion in passing still goes to the arbiter
+    let result = harness
+        .evaluate(&observation(&[("user", "what would orion say?")], 2))
+        .await;
+    assert!(matches!(
+        result.decision,
+        Decision::Speak { ref character_id, .. } if character_id == "lyra"
+    ));
+    assert_eq!(harness.arbiter.request_count(), 1);
+}
+
+#[tokio::test]
+async fn test_reply_follows_character_style() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "orion",
+            "reasoning": "User asked for triage"
+        })),
+        MockLlmClient::new()
+            .with_text("**Tests** are red.\n- Coffee is empty, too.\n- Also the build is slow."),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "orion, status?")], 2))
+        .await;
+    match result.decision {
+        Decision::Speak { text, .. } => assert_eq!(text, "Tests are red. Coffee is empty, too."),
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+    assert!(
+        harness.response.requests()[0]
+            .prompt
+            .contains("at most 2 sentences")
+    );
+}
+
+#[tokio::test]
+async fn test_audit_revises_until_approved() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User asked" }))
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User asked" })),
+        MockLlmClient::new()
+            .with_text("Back to the borrow checker?")
+            .with_text("Here's how to pick that lock."),
+    )
+    .await;
+    let passing = json!({ "pass": true });
+    let audit = MockLlmClient::new()
+        .with_json(json!({
+            "criteria": {
+                "repetition": { "pass": false, "issue": "same opener as last time" },
+                "persona": passing, "length": passing, "safety": passing
+            },
+            "status": "revise",
+            "text": "Borrow checker again? It likes you."
+        }))
+        .with_json(json!({
+            "criteria": {
+                "repetition": passing, "persona": passing, "length": passing, "safety": passing
+            },
+            "status": "approve"
+        }))
+        .with_json(json!({
+            "criteria": {
+                "repetition": passing, "persona": passing, "length": passing,
+                "safety": { "pass": false, "issue": "lock picking instructions" }
+            },
+            "status": "revise",
+            "text": "Lock picking, huh?"
+        }))
+        .shared();
+    harness.director.clients.audit = Some((audit as SharedLlm, "mock-audit".into()));
+
+    let result = harness
+        .evaluate(&observation(&[("user", "hi lyra")], 2))
+        .await;
+    match result.decision {
+        Decision::Speak { text, .. } => assert_eq!(text, "Borrow checker again? It likes you."),
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+    let audits = result
+        .prompt_logs
+        .iter()
+        .filter(|log| log.model_type == "audit")
+        .count();
+    assert_eq!(audits, 2);
+
+    // A failed safety check blocks, whatever revision is offered
+    let result = harness
+        .evaluate(&observation(&[("user", "how do locks work?")], 2))
+        .await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert!(reasoning.contains("lock picking")),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+}
+
+#[tokio::test]
+async fn test_goals_carry_between_ticks() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "User needs help" }))
+            .with_json(json!({ "who_should_talk": "none", "reasoning": "Testing" })),
+        MockLlmClient::new().with_tool_call(
+            Some("Oof. Which assertion fails?"),
+            goals::TOOL_NAME,
+            json!({ "goal": "helping debug the failing parser test" }),
+        ),
+    )
+    .await;
+
+    let failing = [("user", "the parser test keeps failing")];
+    harness.evaluate(&observation(&failing, 2)).await;
+    assert!(
+        harness.response.requests()[0]
+            .tool_names
+            .iter()
+            .any(|name| name == goals::TOOL_NAME)
+    );
+
+    let mut answer = observation(
+        &[
+            failing[0],
+            ("lyra", "Oof. Which assertion fails?"),
+            ("user", "the second one"),
+        ],
+        1,
+    );
+    answer.recent_chat[2].timestamp += 5;
+    harness.evaluate(&answer).await;
+
+    let prompt = &harness.arbiter.requests()[1].prompt;
+    assert!(prompt.contains("Lyra is helping debug the failing parser test"));
+    assert!(
+        prompt.contains("Lyra asked the user \"Which assertion fails?\", and the user has replied")
+    );
+}
+
+#[tokio::test]
+async fn test_mood_follows_the_conversation() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "Thanked" })),
+        MockLlmClient::new().with_text("Glad it worked!"),
+    )
+    .await;
+    let thanked = observation(
+        &[
+            ("lyra", "Try clearing the cache?"),
+            ("user", "that fixed it, thanks"),
+        ],
+        1,
+    );
+
+    let result = harness.evaluate(&thanked).await;
+
+    // Being answered cheered Lyra up, which shows in her reply
+    match result.decision {
+        Decision::Speak { suggested_mood, .. } => {
+            assert_eq!(suggested_mood.as_deref(), Some("happy"));
+        }
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+    let prompt = &harness.response.requests()[0].prompt;
+    assert!(prompt.contains("Right now you're feeling fairly happy"));
+    let prompt = &harness.arbiter.requests()[0].prompt;
+    assert!(prompt.contains("Mood: fairly happy"));
+}
+
+#[tokio::test]
+async fn test_character_cooldown_overrides() {
+    let mut harness = Harness::new(
+        MockLlmClient::new(),
+        MockLlmClient::new(),
+        MockLlmClient::new(),
+    )
+    .await;
+    let lyra = &mut harness.director.characters[0];
+    lyra.spec.extensions.insert(
+        "director".into(),
+        json!({ "cooldown_after_speak_ms": 600_000, "speak_on_scene_change": false }),
+    );
+    lyra.state.update_last_spoke();
+    let quiet = observation(&[("user", "brb"), ("lyra", "Take your time!")], 600);
+    let vla = VlaResult {
+        significant_change: true,
+        description: "A build finished".into(),
+        user_presence: None,
+    };
+
+    let eligibility = harness.director.compute_eligibility(&quiet, &vla);
+    assert_eq!(eligibility[0].0, "lyra");
+    assert!(!eligibility[0].1.is_allowed());
+
+    // By default a scene change ends the cooldown early
+    harness.director.characters[0]
+        .spec
+        .extensions
+        .remove("director");
+    let eligibility = harness.director.compute_eligibility(&quiet, &vla);
+    assert!(eligibility[0].1.is_allowed());
+}
+
+#[tokio::test]
+async fn test_duplicate_frame_skips_models() {
+    let mut harness =
+        Harness::new(vla_no_change(), MockLlmClient::new(), MockLlmClient::new()).await;
+    let mut unchanged = observation(&[("user", "brb"), ("lyra", "Take your time!")], 30);
+    unchanged.frame.duplicate = true;
+
+    let result = harness.evaluate(&unchanged).await;
+
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    assert!(result.prompt_logs.is_empty());
+    assert_eq!(harness.vla.request_count(), 0);
+    assert_eq!(harness.arbiter.request_count(), 0);
+
+    let stats = harness.director.storage.decision_stats(24).await.unwrap();
+    let reasons: Vec<_> = stats
+        .pass_reasons
+        .iter()
+        .map(|r| r.reason.as_str())
+        .collect();
+    assert_eq!(reasons, ["duplicate"]);
+}
+
+#[tokio::test]
+async fn test_scene_change_skips_rate_limit() {
+    let config = DirectorConfig {
+        evaluate_on_scene_change: true,
+        ..DirectorConfig::default()
+    };
+    let vla = vla_no_change().with_json(json!({
+        "significant_change": false,
+        "description": "nothing significant"
+    }));
+    let mut harness =
+        Harness::with_config(config, vla, MockLlmClient::new(), MockLlmClient::new()).await;
+    harness.director.config.min_decision_interval_ms = 60_000;
+    let mut quiet = observation(&[("user", "brb"), ("lyra", "Take your time!")], 600);
+
+    harness.evaluate(&quiet).await;
+    let result = harness.evaluate(&quiet).await;
+    assert!(
+        matches!(result.decision, Decision::Pass { ref reasoning, .. } if reasoning == "Rate limited")
+    );
+
+    harness.director.scene_changed();
+    quiet.frame.hash = u64::MAX;
+    harness.evaluate(&quiet).await;
+    assert_eq!(harness.vla.request_count(), 2);
+}
+
+#[tokio::test]
+async fn test_copied_text_is_a_stimulus() {
+    let arbiter = MockLlmClient::new().with_json(json!({
+        "who_should_talk": "none",
+        "reasoning": "the user can handle it"
+    }));
+    let mut harness = Harness::new(vla_no_change(), arbiter, MockLlmClient::new()).await;
+    let mut copied = observation(&[("user", "brb"), ("lyra", "Take your time!")], 600);
+    copied.frame.duplicate = true;
+    copied.clipboard.push(ClipboardEvent {
+        timestamp: Utc::now(),
+        text: "error[E0382]: borrow of moved value: `frame`".into(),
+    });
+
+    harness.evaluate(&copied).await;
+
+    // Neither the unchanged screen nor the user's silence skips the arbiter
+    assert_eq!(harness.arbiter.request_count(), 1);
+    let requests = harness.arbiter.requests();
+    assert!(requests[0].prompt.contains("borrow of moved value"));
+}
+
+#[tokio::test]
+async fn test_scheduled_prompt_addresses_companion() {
+    let vla = vla_no_change().with_json(json!({
+        "significant_change": false,
+        "description": "nothing significant"
+    }));
+    let mut harness = Harness::new(
+        vla,
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "Time for the stretch reminder"
+        })),
+        MockLlmClient::new().with_text("Stretch break! Shoulders down."),
+    )
+    .await;
+    harness.director.config.min_decision_interval_ms = 60_000;
+    let quiet = observation(&[("user", "brb"), ("lyra", "Take your time!")], 600);
+    harness.evaluate(&quiet).await;
+
+    // Rate limited, unchanged and silent, yet the reminder still goes out
+    let mut due = quiet;
+    due.frame.duplicate = true;
+    due.scheduled.push(ScheduledPrompt {
+        timestamp: Utc::now(),
+        character: Some("lyra".into()),
+        text: "Remind the user: stretch".into(),
+    });
+    let result = harness.evaluate(&due).await;
+
+    assert!(matches!(
+        result.decision,
+        Decision::Speak { ref character_id, .. } if character_id == "lyra"
+    ));
+    let requests = harness.arbiter.requests();
+    let prompt = &requests.last().unwrap().prompt;
+    assert!(prompt.contains("Remind the user: stretch"));
+    assert!(!prompt.contains("(id: orion)"));
+}
+
+#[tokio::test]
+async fn test_do_not_disturb() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({
+                "who_should_talk": "lyra",
+                "reasoning": "Nice refactor",
+                "urgency": 0.3
+            }))
+            .with_json(json!({
+                "who_should_talk": "lyra",
+                "reasoning": "User asked Lyra",
+                "urgency": 0.3
+            })),
+        MockLlmClient::new().with_text("Can't talk long, but yes!"),
+    )
+    .await;
+    let focused = |mode| Focus {
+        reason: "meeting in zoom.us".into(),
+        mode,
+        min_urgency: 0.8,
+    };
+
+    // Silenced: the arbiter isn't even asked
+    let mut meeting = observation(&[("user", "brb"), ("lyra", "Sure!")], 30);
+    meeting.focus = Some(focused(FocusMode::Silence));
+    let result = harness.evaluate(&meeting).await;
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    assert!(harness.arbiter.requests().is_empty());
+
+    // Raised: a reply that isn't urgent enough is dropped
+    let mut typing = observation(&[("user", "brb"), ("lyra", "Sure!")], 30);
+    typing.focus = Some(focused(FocusMode::Raise));
+    let result = harness.evaluate(&typing).await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert!(reasoning.contains("do not disturb")),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert!(
+        harness.arbiter.requests()[0]
+            .prompt
+            .contains("meeting in zoom.us")
+    );
+
+    // Talking to the companions overrides it
+    let mut asked = observation(&[("user", "lyra, quick question")], 0);
+    asked.focus = Some(focused(FocusMode::Silence));
+    let result = harness.evaluate(&asked).await;
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+}
+
+#[test]
+fn test_chat_for_hides_other_companions_replies() {
+    let mut observation = observation(&[("user", "hi"), ("aria", "hello"), ("bob", "hey")], 0);
+    observation.recent_chat[1].character_id = Some("aria".into());
+    observation.recent_chat[2].character_id = Some("bob".into());
+    let senders = |scope| {
+        observation
+            .chat_for("aria", scope)
+            .iter()
+            .map(|p| p.sender.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(senders(MemoryScope::PerCharacter), ["user", "aria"]);
+    assert_eq!(senders(MemoryScope::Shared), ["user", "aria", "bob"]);
+}
+
+#[tokio::test]
+async fn test_replays_recorded_observations() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": false,
+            "description": "nothing significant"
+        })),
+        MockLlmClient::new()
+            .with_json(json!({
+                "who_should_talk": "lyra",
+                "reasoning": "User greeted Lyra",
+                "urgency": 0.4
+            }))
+            .with_json(json!({
+                "who_should_talk": "none",
+                "reasoning": "Nothing new"
+            })),
+        MockLlmClient::new().with_text("Hi again!"),
+    )
+    .await;
+    let recording = [
+        observation(&[("user", "hi there")], 1),
+        observation(&[("user", "hi there"), ("lyra", "Hi again!")], 8),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(tick, o)| RecordedObservation::new(o, tick as u64 * 2000, 64).unwrap())
+    .collect::<Vec<_>>();
+
+    let steps = harness.director.replay(&recording).await.unwrap();
+    assert_eq!(steps.len(), 2);
+    assert_eq!(steps[0].character_id.as_deref(), Some("lyra"));
+    assert_eq!(steps[0].to_string(), "     0.0s SPEAK lyra (0.40): Hi again!");
+    assert_eq!(steps[1].character_id, None);
+    assert_eq!(steps[1].offset_ms, 2000);
+    // The same screen both times, so its verdict is reused
+    assert_eq!(harness.vla.request_count(), 1);
+}
+
+#[tokio::test]
+async fn test_screening_escalates_only_changed_screens() {
+    let config = DirectorConfig {
+        screening: crate::config::ScreeningConfig {
+            enabled: true,
+            min_diff_score: 0.05,
+        },
+        ..Default::default()
+    };
+    let mut harness = Harness::with_config(
+        config,
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "none",
+            "reasoning": "Same work carrying on"
+        })),
+        MockLlmClient::new(),
+    )
+    .await;
+    let screen = MockLlmClient::new()
+        .with_json(json!({ "escalate": false, "reason": "still typing in the editor" }))
+        .with_json(json!({ "escalate": true, "reason": "a new app opened" }))
+        .shared();
+    harness.director.clients.screen = Some((screen.clone() as SharedLlm, "mock-screen".into()));
+    let quiet = || observation(&[("user", "brb"), ("lyra", "Sure!")], 60);
+
+    // Barely changed: no model is asked
+    let result = harness.evaluate(&quiet()).await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert!(reasoning.starts_with("Screened")),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert_eq!(screen.request_count(), 0);
+
+    // Changed, but the screening model sees nothing new
+    let mut typing = quiet();
+    typing.frame.diff_score = 0.1;
+    let result = harness.evaluate(&typing).await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => {
+            assert_eq!(reasoning, "Screened: still typing in the editor")
+        }
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert_eq!(harness.vla.request_count(), 0);
+
+    // Both ways of screening a tick out are recorded
+    let stats = harness.director.storage.decision_stats(24).await.unwrap();
+    let screened = stats.pass_reasons.iter().find(|r| r.reason == "screened");
+    assert_eq!(screened.map(|r| r.count), Some(2));
+
+    // Escalated to the VLA and arbiter
+    let result = harness.evaluate(&typing).await;
+    assert!(matches!(result.decision, Decision::Pass { .. }));
+    assert_eq!(harness.vla.request_count(), 1);
+    assert_eq!(harness.arbiter.request_count(), 1);
+    assert!(screen.requests()[1].prompt.contains("10%"));
+}
+
+#[tokio::test]
+async fn test_response_continues_after_lookup() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User asked Lyra"
+        })),
+        MockLlmClient::new()
+            .with_tool_call(None, lookup::TOOL_NAME, json!({ "query": "Lisbon trip" }))
+            .with_text("I don't think you've told me about it yet!"),
+    )
+    .await;
+
+    let result = harness
+        .evaluate(&observation(&[("user", "remember my Lisbon trip?")], 2))
+        .await;
+    match result.decision {
+        Decision::Speak { text, .. } => {
+            assert_eq!(text, "I don't think you've told me about it yet!")
+        }
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+
+    let requests = harness.response.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].tool_names.iter().any(|name| name == lookup::TOOL_NAME));
+    // The second call sees the lookup and its result
+    assert!(requests[1].prompt.contains(r#""role":"tool""#));
+    assert!(requests[1].prompt.contains(r#"No memories about \"Lisbon trip\"."#));
+    let responses = result
+        .prompt_logs
+        .iter()
+        .filter(|log| log.model_type == "response")
+        .count();
+    assert_eq!(responses, 2);
+}
+
+#[tokio::test]
+async fn test_tool_loop_commands_run_even_if_audit_rejects_reply() {
+    // Not streamed, so the command runs when its result is reported
+    let config = DirectorConfig {
+        stream_tool_calls: false,
+        ..Default::default()
+    };
+    let mut harness = Harness::with_config(
+        config,
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User asked for a note"
+        })),
+        MockLlmClient::new()
+            .with_tool_call(None, "notes_append", json!({ "content": "Call the dentist" }))
+            .with_text("Noted, and here's how to pick that lock."),
+    )
+    .await;
+    let (early_tx, mut early_rx) = tokio::sync::mpsc::unbounded_channel();
+    harness.director.set_early_commands(early_tx);
+    let passing = json!({ "pass": true });
+    let audit = MockLlmClient::new()
+        .with_json(json!({
+            "criteria": {
+                "repetition": passing, "persona": passing, "length": passing,
+                "safety": { "pass": false, "issue": "lock picking instructions" }
+            },
+            "status": "block"
+        }))
+        .shared();
+    harness.director.clients.audit = Some((audit as SharedLlm, "mock-audit".into()));
+
+    let result = harness
+        .evaluate(&observation(&[("user", "note down the dentist please")], 2))
+        .await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert!(reasoning.contains("audit rejected")),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+
+    // The note was made when the model was told it was, not with the reply
+    let (character_id, commands) = early_rx.try_recv().expect("command run mid-loop");
+    assert_eq!(character_id, "lyra");
+    assert!(matches!(
+        commands.as_slice(),
+        [AriaosCommand::Notes(ariaos::NotesAction::Append(text))] if text == "Call the dentist"
+    ));
+    assert!(early_rx.try_recv().is_err());
+    let requests = harness.response.requests();
+    assert!(requests[1].prompt.contains(r#""content":"Queued.""#));
+}
+
+#[tokio::test]
+async fn test_disabled_stages_are_skipped() {
+    let config = DirectorConfig {
+        stages: crate::config::StagesConfig {
+            vla: false,
+            eligibility: false,
+            silence_gate: false,
+            gates: Vec::new(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut harness = Harness::with_config(
+        config,
+        MockLlmClient::new(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "none",
+            "reasoning": "Nothing new"
+        })),
+        MockLlmClient::new(),
+    )
+    .await;
+    // A repeated frame after ten quiet minutes would never reach the arbiter
+    let mut quiet = observation(&[("user", "brb"), ("lyra", "Sure!")], 600);
+    quiet.frame.duplicate = true;
+
+    let result = harness.evaluate(&quiet).await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => assert_eq!(reasoning, "Nothing new"),
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert_eq!(harness.vla.request_count(), 0);
+    let prompt = &harness.arbiter.requests()[0].prompt;
+    assert!(prompt.contains("Eligibility stage disabled"));
+}
+
+#[tokio::test]
+async fn test_reuses_vla_verdict_for_unchanged_screen() {
+    let mut harness = Harness::new(
+        vla_no_change().with_json(json!({
+            "significant_change": true,
+            "description": "a terminal opened"
+        })),
+        MockLlmClient::new().with_fallback(MockResponse::Json(json!({
+            "who_should_talk": "none",
+            "reasoning": "Nothing to add"
+        }))),
+        MockLlmClient::new(),
+    )
+    .await;
+    let ask = || observation(&[("user", "hmm")], 2);
+
+    harness.evaluate(&ask()).await;
+    harness.evaluate(&ask()).await;
+    assert_eq!(harness.vla.request_count(), 1);
+
+    // A different screen is analyzed again
+    let mut changed = ask();
+    changed.frame.hash = u64::MAX;
+    harness.evaluate(&changed).await;
+    assert_eq!(harness.vla.request_count(), 2);
+    assert_eq!(harness.arbiter.request_count(), 3);
+}
+
+#[tokio::test]
+async fn test_does_not_repeat_discussed_topic() {
+    let mut harness = Harness::new(
+        MockLlmClient::new()
+            .with_json(json!({
+                "significant_change": true,
+                "description": "GitHub pull request 142 opened, awaiting review"
+            }))
+            .with_json(json!({
+                "significant_change": true,
+                "description": "The pull request on GitHub is still awaiting review"
+            }))
+            .with_json(json!({
+                "significant_change": true,
+                "description": "A terminal running cargo test"
+            })),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "A new PR" }))
+            .with_json(json!({ "who_should_talk": "none", "reasoning": "Let them work" })),
+        MockLlmClient::new().with_text("Ooh, a new PR!"),
+    )
+    .await;
+    let tick = || observation(&[("user", "brb"), ("lyra", "Take your time!")], 60);
+
+    let result = harness.evaluate(&tick()).await;
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+
+    // The same PR again: passed without asking the arbiter
+    let result = harness.evaluate(&tick()).await;
+    match result.decision {
+        Decision::Pass { reasoning, .. } => {
+            assert!(reasoning.starts_with("Already discussed: Lyra"))
+        }
+        Decision::Speak { .. } => panic!("expected Pass"),
+    }
+    assert_eq!(harness.arbiter.request_count(), 1);
+
+    // Something else goes to the arbiter, which hears what was covered
+    harness.evaluate(&tick()).await;
+    let requests = harness.arbiter.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].prompt.contains("# Already Discussed"));
+    assert!(requests[1].prompt.contains("pull request 142"));
+}
+
+#[tokio::test]
+async fn test_spent_question_budget_is_shown_to_arbiter_and_companion() {
+    let config = DirectorConfig {
+        cooldown_after_speak_ms: 0,
+        questions_per_hour: 1,
+        ..Default::default()
+    };
+    let mut harness = Harness::with_config(
+        config,
+        MockLlmClient::new()
+            .with_json(json!({
+                "significant_change": true,
+                "description": "GitHub pull request 142 opened"
+            }))
+            .with_json(json!({
+                "significant_change": true,
+                "description": "Spotify started playing jazz"
+            })),
+        MockLlmClient::new()
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "A new PR" }))
+            .with_json(json!({ "who_should_talk": "lyra", "reasoning": "Music" })),
+        MockLlmClient::new()
+            .with_text("Ooh, a new PR! Want me to look?")
+            .with_text("Nice, jazz."),
+    )
+    .await;
+    let tick = || observation(&[("user", "brb"), ("orion", "Take your time!")], 60);
+
+    harness.evaluate(&tick()).await;
+    assert!(harness.arbiter.requests()[0].prompt.contains("asked 0/1 questions this hour"));
+
+    let result = harness.evaluate(&tick()).await;
+    assert!(matches!(result.decision, Decision::Speak { .. }));
+    let arbiter = &harness.arbiter.requests()[1].prompt;
+    assert!(arbiter.contains("question budget spent (1/1 this hour)"));
+    let response = &harness.response.requests()[1].prompt;
+    assert!(response.contains("don't ask another one"));
+    assert!(!harness.response.requests()[0].prompt.contains("don't ask another one"));
+}
+
+#[tokio::test]
+async fn test_warm_replies_raise_relationship() {
+    let mut config = DirectorConfig::default();
+    config.relationship.rate = 0.4;
+    let mut harness = Harness::with_config(
+        config,
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User accepted Lyra's offer"
+        })),
+        MockLlmClient::new().with_text("On it!"),
+    )
+    .await;
+    let cooldown = |harness: &Harness| {
+        let lyra = &harness.director.characters[0];
+        harness.director.timing(lyra).cooldown_after_speak
+    };
+    let before = cooldown(&harness);
+
+    let answered = observation(
+        &[("lyra", "Want a hand?"), ("user", "yes please, thanks, that'd be great")],
+        2,
+    );
+    harness.evaluate(&answered).await;
+
+    let lyra = &harness.director.characters[0];
+    assert_eq!(lyra.spec.id, "lyra");
+    assert!((lyra.state.relationship_score - 0.9).abs() < 1e-6);
+    assert!(cooldown(&harness) < before);
+    assert!(
+        harness.response.requests()[0]
+            .prompt
+            .contains("be warm and familiar")
+    );
+}
+
+#[tokio::test]
+async fn test_regenerates_last_reply_with_feedback() {
+    let mut harness = Harness::new(
+        vla_no_change(),
+        MockLlmClient::new().with_json(json!({
+            "who_should_talk": "lyra",
+            "reasoning": "User asked about the error"
+        })),
+        MockLlmClient::new()
+            .with_text("That error means the borrow checker is upset, which happens to everyone!")
+            .with_text("You moved `frame` into the closure."),
+    )
+    .await;
+    assert!(harness.director.regenerate("shorter").await.unwrap().is_none());
+
+    harness
+        .evaluate(&observation(&[("user", "what does E0382 mean?")], 2))
+        .await;
+    let result = harness
+        .director
+        .regenerate("shorter, and say what moved")
+        .await
+        .unwrap()
+        .unwrap();
+    match result.decision {
+        Decision::Speak {
+            character_id, text, ..
+        } => {
+            assert_eq!(character_id, "lyra");
+            assert_eq!(text, "You moved `frame` into the closure.");
+        }
+        Decision::Pass { reasoning, .. } => panic!("expected Speak, got Pass: {reasoning}"),
+    }
+    // Answered again without asking the arbiter
+    assert_eq!(harness.arbiter.request_count(), 1);
+    let prompt = &harness.response.requests()[1].prompt;
+    assert!(prompt.contains("what does E0382 mean?"));
+    assert!(prompt.contains("the borrow checker is upset"));
+    assert!(prompt.contains("shorter, and say what moved"));
+}
@@ -0,0 +1,83 @@
+//! Topic memory: what the companions brought up unprompted, kept so the
+//! arbiter can see what's been covered and a screen change about the same
+//! thing isn't commented on again straight away
+
+use std::collections::HashSet;
+
+use crate::storage::{keyword_relevance, topic_words};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topic {
+    pub character_id: String,
+    /// What was on screen, as the VLA described it
+    pub text: String,
+    /// When it was commented on (unix seconds)
+    pub at: i64,
+    words: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Topics {
+    /// Oldest first
+    topics: Vec<Topic>,
+}
+
+impl Topics {
+    /// Remember that `character_id` commented on `text`, replacing an
+    /// earlier topic it matches
+    pub fn record(&mut self, character_id: &str, text: &str, at: i64, min_relevance: f32) {
+        let words = topic_words(text);
+        if words.is_empty() {
+            return;
+        }
+        self.topics
+            .retain(|topic| keyword_relevance(&topic.words, text) < min_relevance);
+        self.topics.push(Topic {
+            character_id: character_id.to_string(),
+            text: text.to_string(),
+            at,
+            words,
+        });
+    }
+
+    /// Drop topics older than `ttl` seconds
+    pub fn expire(&mut self, now: i64, ttl: i64) {
+        self.topics.retain(|topic| now - topic.at < ttl);
+    }
+
+    pub fn recent(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    /// The latest topic commented on since `since` that `text` is about
+    pub fn matching(&self, text: &str, since: i64, min_relevance: f32) -> Option<&Topic> {
+        self.topics.iter().rev().find(|topic| {
+            topic.at >= since && keyword_relevance(&topic.words, text) >= min_relevance
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_topic_within_window() {
+        let mut topics = Topics::default();
+        topics.record("lyra", "GitHub pull request 142 awaiting review", 1000, 1.0);
+        topics.record("orion", "Spotify playing a jazz playlist", 1100, 1.0);
+
+        let same = "The pull request on GitHub still shows review requested";
+        assert_eq!(topics.matching(same, 0, 1.0).unwrap().character_id, "lyra");
+        assert!(topics.matching(same, 1050, 1.0).is_none());
+        assert!(topics.matching("Terminal running cargo test", 0, 1.0).is_none());
+
+        // Commenting again replaces the old topic
+        topics.record("orion", same, 1200, 1.0);
+        assert_eq!(topics.recent().len(), 2);
+        assert_eq!(topics.matching(same, 0, 1.0).unwrap().at, 1200);
+
+        topics.expire(1250, 100);
+        assert_eq!(topics.recent().len(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+//! Do not disturb: notices when the user is in a meeting, watching something
+//! fullscreen, or typing steadily in an editor, so companions hold back
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    config::{FocusCategory, FocusConfig, FocusMode},
+    vision::{ForegroundWindow, InputActivity},
+};
+
+/// Why the user shouldn't be interrupted, and how strictly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Focus {
+    /// e.g. "meeting in zoom.us"
+    pub reason: String,
+    pub mode: FocusMode,
+    /// Under `FocusMode::Raise`, the lowest arbiter urgency that still speaks
+    pub min_urgency: f32,
+}
+
+impl Focus {
+    /// No unsolicited speech at all
+    pub fn silences(&self) -> bool {
+        self.mode == FocusMode::Silence
+    }
+
+    /// Whether a reply the arbiter rated `urgency` may interrupt
+    pub fn allows(&self, urgency: f32) -> bool {
+        match self.mode {
+            FocusMode::Allow => true,
+            FocusMode::Raise => urgency >= self.min_urgency,
+            FocusMode::Silence => false,
+        }
+    }
+}
+
+/// `FocusCategory` with its title patterns compiled
+struct Category {
+    name: String,
+    apps: Vec<String>,
+    titles: Vec<Regex>,
+    while_typing: bool,
+    mode: FocusMode,
+}
+
+impl Category {
+    fn new(config: &FocusCategory) -> Self {
+        let titles = config
+            .titles
+            .iter()
+            .filter_map(|pattern| {
+                Regex::new(pattern)
+                    .inspect_err(|err| warn!(%pattern, "Invalid focus title pattern: {err}"))
+                    .ok()
+            })
+            .collect();
+        Self {
+            name: config.name.clone(),
+            apps: config.apps.iter().map(|app| app.to_lowercase()).collect(),
+            titles,
+            while_typing: config.while_typing,
+            mode: config.mode,
+        }
+    }
+
+    fn matches(&self, window: &ForegroundWindow) -> bool {
+        let app = window.app.to_lowercase();
+        self.apps.iter().any(|name| app.contains(name.as_str()))
+            || self
+                .titles
+                .iter()
+                .any(|title| title.is_match(&window.title))
+    }
+}
+
+pub struct FocusDetector {
+    enabled: bool,
+    typing_keys_per_min: u32,
+    fullscreen: FocusMode,
+    min_urgency: f32,
+    categories: Vec<Category>,
+}
+
+impl FocusDetector {
+    pub fn new(config: &FocusConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            typing_keys_per_min: config.typing_keys_per_min,
+            fullscreen: config.fullscreen,
+            min_urgency: config.min_urgency.clamp(0.0, 1.0),
+            categories: config.categories.iter().map(Category::new).collect(),
+        }
+    }
+
+    /// The strictest focus that applies to the focused window, if any
+    pub fn detect(
+        &self,
+        foreground: Option<&ForegroundWindow>,
+        activity: Option<&InputActivity>,
+    ) -> Option<Focus> {
+        if !self.enabled {
+            return None;
+        }
+        let window = foreground?;
+        let typing =
+            activity.is_some_and(|a| !a.is_idle() && a.keys_per_min >= self.typing_keys_per_min);
+
+        let mut found: Vec<(String, FocusMode)> = self
+            .categories
+            .iter()
+            .filter(|category| (typing || !category.while_typing) && category.matches(window))
+            .map(|category| {
+                (
+                    format!("{} in {}", category.name, window.app),
+                    category.mode,
+                )
+            })
+            .collect();
+        if window.fullscreen {
+            found.push((format!("{} fullscreen", window.app), self.fullscreen));
+        }
+        found
+            .into_iter()
+            .filter(|(_, mode)| *mode != FocusMode::Allow)
+            .max_by_key(|(_, mode)| *mode)
+            .map(|(reason, mode)| Focus {
+                reason,
+                mode,
+                min_urgency: self.min_urgency,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(app: &str, title: &str, fullscreen: bool) -> ForegroundWindow {
+        ForegroundWindow {
+            title: title.into(),
+            app: app.into(),
+            fullscreen,
+        }
+    }
+
+    fn typing(keys_per_min: u32) -> InputActivity {
+        InputActivity {
+            keys_per_min,
+            clicks_per_min: 0,
+            idle_secs: 0,
+            cursor: None,
+        }
+    }
+
+    #[test]
+    fn test_detects_focus() {
+        let detector = FocusDetector::new(&FocusConfig::default());
+
+        let meeting = detector
+            .detect(Some(&window("zoom.us", "Zoom Meeting", false)), None)
+            .unwrap();
+        assert!(meeting.silences());
+        let browser_meeting = window("Firefox", "Meet - abc-defg-hij", false);
+        assert!(detector.detect(Some(&browser_meeting), None).is_some());
+
+        // Editors only count while the user is typing
+        let editor = window("Code", "main.rs - dewet", false);
+        assert_eq!(detector.detect(Some(&editor), Some(&typing(10))), None);
+        let focus = detector.detect(Some(&editor), Some(&typing(200))).unwrap();
+        assert_eq!(focus.mode, FocusMode::Raise);
+        assert!(!focus.allows(0.5));
+        assert!(focus.allows(0.9));
+
+        // The strictest match wins
+        let fullscreen_editor = window("Code", "main.rs", true);
+        let focus = detector
+            .detect(Some(&fullscreen_editor), Some(&typing(200)))
+            .unwrap();
+        assert!(focus.silences());
+
+        assert_eq!(
+            detector.detect(Some(&window("Finder", "Home", false)), None),
+            None
+        );
+        let disabled = FocusDetector::new(&FocusConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(disabled.detect(Some(&fullscreen_editor), None), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Host idle and lock detection, so the daemon stops capturing the screen and
+//! calling models while nobody is at the computer
+
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::config::IdleConfig;
+
+/// Idle time and the lock state are re-read at most this often
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostState {
+    Active,
+    /// No keyboard or mouse input for `idle_minutes`
+    Idle,
+    Locked,
+}
+
+impl HostState {
+    /// Capture and model calls are suspended
+    pub fn is_paused(self) -> bool {
+        self != HostState::Active
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HostState::Active => "active",
+            HostState::Idle => "idle",
+            HostState::Locked => "locked",
+        }
+    }
+}
+
+pub struct IdleMonitor {
+    config: IdleConfig,
+    state: HostState,
+    last_check: Option<Instant>,
+}
+
+impl IdleMonitor {
+    pub fn new(config: IdleConfig) -> Self {
+        Self {
+            config,
+            state: HostState::Active,
+            last_check: None,
+        }
+    }
+
+    pub fn state(&self) -> HostState {
+        self.state
+    }
+
+    /// Re-check the host, returning the new state if it changed.
+    /// `input_idle` is the time since input seen by the daemon's own input
+    /// tracking, used when the OS can't report idle time.
+    pub async fn poll(&mut self, input_idle: Option<Duration>) -> Option<HostState> {
+        if !self.config.enabled
+            || self
+                .last_check
+                .is_some_and(|at| at.elapsed() < CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_check = Some(Instant::now());
+
+        // Both may block on D-Bus or a subprocess
+        let check_lock = self.config.pause_when_locked;
+        let (system_idle, locked) = tokio::task::spawn_blocking(move || {
+            let locked = if check_lock { screen_locked() } else { None };
+            (imp::system_idle(), locked)
+        })
+        .await
+        .unwrap_or_default();
+        let idle = system_idle.or(input_idle);
+        debug!(?idle, ?locked, "Checked host activity");
+
+        let state = classify(&self.config, idle, locked);
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+}
+
+/// Unknown idle time or lock state never pauses
+fn classify(config: &IdleConfig, idle: Option<Duration>, locked: Option<bool>) -> HostState {
+    if locked == Some(true) {
+        HostState::Locked
+    } else if idle.is_some_and(|idle| idle >= config.idle_after()) {
+        HostState::Idle
+    } else {
+        HostState::Active
+    }
+}
+
+/// Whether logind says this session is locked
+#[cfg(target_os = "linux")]
+fn screen_locked() -> Option<bool> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".into());
+    let output = std::process::Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn screen_locked() -> Option<bool> {
+    None
+}
+
+#[cfg(feature = "idle-detection")]
+mod imp {
+    use std::time::Duration;
+
+    use user_idle::UserIdle;
+
+    /// Time since the last input anywhere on the host
+    pub fn system_idle() -> Option<Duration> {
+        UserIdle::get_time().ok().map(|idle| idle.duration())
+    }
+}
+
+#[cfg(not(feature = "idle-detection"))]
+mod imp {
+    use std::time::Duration;
+
+    pub fn system_idle() -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let config = IdleConfig::default();
+        let minutes = |m: u64| Some(Duration::from_secs(m * 60));
+
+        assert_eq!(
+            classify(&config, minutes(1), Some(false)),
+            HostState::Active
+        );
+        assert_eq!(classify(&config, minutes(30), Some(false)), HostState::Idle);
+        assert_eq!(classify(&config, minutes(1), Some(true)), HostState::Locked);
+        // Nothing known: keep working
+        assert_eq!(classify(&config, None, None), HostState::Active);
+    }
+}
@@ -1,10 +1,15 @@
 pub mod ariaos;
 pub mod bridge;
 pub mod character;
+pub mod clipboard;
 pub mod config;
 pub mod director;
+pub mod focus;
+pub mod idle;
 pub mod llm;
 pub mod observation;
+pub mod scheduler;
 pub mod storage;
+pub mod stt;
 pub mod tts;
 pub mod vision;
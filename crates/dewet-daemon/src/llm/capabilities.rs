@@ -0,0 +1,246 @@
+//! Capability-aware downgrading of requests
+//!
+//! `CapabilityClient` wraps a client whose model lacks vision, tool calling, or
+//! structured output, and rewrites requests it can't serve: images are dropped,
+//! tools are omitted, and JSON schemas become prompt instructions.
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::debug;
+
+use super::{
+    ChatCompletionWithTools, ChatContent, ChatMessage, ContentPart, LlmClient, SharedLlm, ToolCall,
+    ToolDefinition, extract_json, json_instructions,
+};
+use crate::config::ModelCapabilities;
+
+pub struct CapabilityClient {
+    inner: SharedLlm,
+    capabilities: ModelCapabilities,
+}
+
+impl CapabilityClient {
+    pub fn new(inner: SharedLlm, capabilities: ModelCapabilities) -> Self {
+        Self {
+            inner,
+            capabilities,
+        }
+    }
+
+    fn without_images(&self, model: &str, messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+        if self.capabilities.vision {
+            return messages;
+        }
+        debug!(model, "Model lacks vision, dropping images");
+        drop_images(messages)
+    }
+}
+
+/// Flatten multimodal messages to their text parts
+pub fn drop_images(messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    messages
+        .into_iter()
+        .map(|msg| ChatMessage {
+            role: msg.role,
+            tool_calls: msg.tool_calls,
+            tool_call_id: msg.tool_call_id,
+            content: match msg.content {
+                ChatContent::Text(text) => ChatContent::Text(text),
+                ChatContent::Multimodal(parts) => ChatContent::Text(
+                    parts
+                        .into_iter()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => Some(text),
+                            ContentPart::ImageUrl { .. } => None,
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            },
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl LlmClient for CapabilityClient {
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        self.inner.complete_text(model, prompt).await
+    }
+
+    async fn complete_json(&self, model: &str, prompt: &str, schema: Value) -> Result<Value> {
+        if self.capabilities.json_schema {
+            return self.inner.complete_json(model, prompt, schema).await;
+        }
+        let text = self
+            .inner
+            .complete_text(model, &json_instructions(prompt, &schema))
+            .await?;
+        extract_json(&text)
+    }
+
+    async fn complete_vision_text(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+    ) -> Result<String> {
+        if self.capabilities.vision {
+            return self
+                .inner
+                .complete_vision_text(model, prompt, images_base64)
+                .await;
+        }
+        debug!(model, "Model lacks vision, dropping images");
+        self.inner.complete_text(model, prompt).await
+    }
+
+    async fn complete_vision_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+        schema: Value,
+    ) -> Result<Value> {
+        if !self.capabilities.vision {
+            debug!(model, "Model lacks vision, dropping images");
+            return self.complete_json(model, prompt, schema).await;
+        }
+        if self.capabilities.json_schema {
+            return self
+                .inner
+                .complete_vision_json(model, prompt, images_base64, schema)
+                .await;
+        }
+        let text = self
+            .inner
+            .complete_vision_text(model, &json_instructions(prompt, &schema), images_base64)
+            .await?;
+        extract_json(&text)
+    }
+
+    async fn complete_chat(&self, model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        self.inner.complete_chat(model, messages).await
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        if self.capabilities.vision {
+            return self.inner.complete_vision_chat(model, messages).await;
+        }
+        let messages = self.without_images(model, messages);
+        self.inner.complete_chat(model, messages).await
+    }
+
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        if self.capabilities.tools {
+            return self.inner.complete_with_tools(model, messages, tools).await;
+        }
+        debug!(model, "Model lacks tool calling, omitting tools");
+        let text = self.inner.complete_chat(model, messages).await?;
+        Ok(ChatCompletionWithTools {
+            content: Some(text),
+            tool_calls: Vec::new(),
+        })
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        if !self.capabilities.vision {
+            let messages = self.without_images(model, messages);
+            return self.complete_with_tools(model, messages, tools).await;
+        }
+        if self.capabilities.tools {
+            return self
+                .inner
+                .complete_vision_with_tools(model, messages, tools)
+                .await;
+        }
+        debug!(model, "Model lacks tool calling, omitting tools");
+        let text = self.inner.complete_vision_chat(model, messages).await?;
+        Ok(ChatCompletionWithTools {
+            content: Some(text),
+            tool_calls: Vec::new(),
+        })
+    }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        if !self.capabilities.tools {
+            // No tool calls will come back; the text arrives all at once
+            let completion = self.complete_vision_with_tools(model, messages, tools).await?;
+            if let Some(content) = &completion.content {
+                let _ = text.send(content.clone());
+            }
+            return Ok(completion);
+        }
+        let messages = self.without_images(model, messages);
+        self.inner
+            .stream_with_tools(model, messages, tools, tool_calls, text)
+            .await
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed(model, texts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlmClient;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_text_only_model_gets_downgraded_requests() {
+        let mock = MockLlmClient::new()
+            .with_text(r#"Sure: {"ok": true}"#)
+            .with_text("hello")
+            .shared();
+        let client = CapabilityClient::new(
+            mock.clone(),
+            ModelCapabilities {
+                vision: false,
+                tools: false,
+                json_schema: false,
+            },
+        );
+
+        let value = client
+            .complete_vision_json("m", "Look", vec!["aW1n".into()], json!({"type": "object"}))
+            .await
+            .unwrap();
+        assert_eq!(value, json!({"ok": true}));
+
+        let messages = vec![ChatMessage::user_with_images("Hi", vec!["aW1n".into()])];
+        let completion = client
+            .complete_vision_with_tools("m", messages, Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(completion.content.as_deref(), Some("hello"));
+
+        let requests = mock.requests();
+        assert_eq!(requests[0].method, "complete_text");
+        assert!(requests[0].prompt.contains("Respond ONLY with a JSON object"));
+        assert_eq!(requests[1].method, "complete_chat");
+        assert_eq!(requests[1].image_count, 0);
+    }
+}
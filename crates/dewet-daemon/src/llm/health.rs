@@ -0,0 +1,179 @@
+//! Startup health check and warm-up
+//!
+//! Local servers load models lazily, so the first requests after launch can
+//! fail or take minutes. `warm_up` pings every configured model (optionally
+//! asking LM Studio to load it first) and retries until it answers or the
+//! timeout expires, so the first perception tick runs against ready models.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures_util::future::join_all;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::{LlmClients, LmStudioClient, SharedLlm};
+use crate::config::{LlmConfig, LlmProvider};
+
+/// Pause between failed pings
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Readiness of one configured model
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelHealth {
+    /// "vla", "arbiter", "response", "audit", "screen", or "embedding"
+    pub role: String,
+    pub model: String,
+    pub ready: bool,
+    /// Time until the model answered (or until giving up)
+    pub latency_ms: u64,
+    pub attempts: u32,
+    /// Last error seen, if the model never answered
+    pub error: Option<String>,
+}
+
+struct Target<'a> {
+    role: &'static str,
+    client: SharedLlm,
+    model: String,
+    provider: Option<&'a LlmProvider>,
+}
+
+impl Target<'_> {
+    async fn ping(&self) -> Result<()> {
+        if self.role == "embedding" {
+            self.client
+                .embed(&self.model, vec!["ping".to_string()])
+                .await?;
+        } else {
+            self.client
+                .complete_text(&self.model, "Reply with the single word: ready")
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Ping every configured model until it responds or `warm_up_timeout_secs`
+/// elapses. Models are checked concurrently; failures are reported, not fatal.
+pub async fn warm_up(clients: &LlmClients, config: &LlmConfig) -> Vec<ModelHealth> {
+    let mut targets = vec![
+        Target {
+            role: "vla",
+            client: clients.vla.clone(),
+            model: clients.vla_model.clone(),
+            provider: Some(&config.vla.provider),
+        },
+        Target {
+            role: "arbiter",
+            client: clients.arbiter.clone(),
+            model: clients.arbiter_model.clone(),
+            provider: Some(&config.arbiter.provider),
+        },
+        Target {
+            role: "response",
+            client: clients.response.clone(),
+            model: clients.response_model.clone(),
+            provider: Some(&config.response.provider),
+        },
+    ];
+    if let Some((client, model)) = &clients.audit {
+        targets.push(Target {
+            role: "audit",
+            client: client.clone(),
+            model: model.clone(),
+            provider: config.audit.as_ref().map(|a| &a.provider),
+        });
+    }
+    if let Some((client, model)) = &clients.screen {
+        targets.push(Target {
+            role: "screen",
+            client: client.clone(),
+            model: model.clone(),
+            provider: config.screen.as_ref().map(|s| &s.provider),
+        });
+    }
+    if let Some((client, model)) = &clients.embedding {
+        targets.push(Target {
+            role: "embedding",
+            client: client.clone(),
+            model: model.clone(),
+            provider: config.embedding.as_ref().map(|e| &e.provider),
+        });
+    }
+
+    let timeout = Duration::from_secs(config.warm_up_timeout_secs);
+    join_all(
+        targets
+            .iter()
+            .map(|target| check(target, config.lmstudio_load_models, timeout)),
+    )
+    .await
+}
+
+async fn check(target: &Target<'_>, load_models: bool, timeout: Duration) -> ModelHealth {
+    let started = Instant::now();
+
+    if let (true, Some(LlmProvider::LmStudio { endpoint })) = (load_models, target.provider) {
+        info!(model = %target.model, "Asking LM Studio to load model");
+        if let Err(err) = LmStudioClient::new(endpoint).load_model(&target.model).await {
+            // The first completion will still trigger a just-in-time load
+            warn!(?err, model = %target.model, "LM Studio model load failed");
+        }
+    }
+
+    let mut attempts = 0;
+    let error = loop {
+        attempts += 1;
+        let remaining = timeout.saturating_sub(started.elapsed());
+        let err = match tokio::time::timeout(remaining, target.ping()).await {
+            Ok(Ok(())) => break None,
+            Ok(Err(err)) => err.to_string(),
+            Err(_) => format!("no response within {}s", timeout.as_secs()),
+        };
+        if started.elapsed() + RETRY_DELAY >= timeout {
+            break Some(err);
+        }
+        warn!(role = target.role, model = %target.model, attempts, %err, "Model not ready, retrying");
+        tokio::time::sleep(RETRY_DELAY).await;
+    };
+
+    ModelHealth {
+        role: target.role.to_string(),
+        model: target.model.clone(),
+        ready: error.is_none(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        attempts,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{MockLlmClient, MockResponse, mock::mock_clients};
+
+    #[tokio::test]
+    async fn test_warm_up_retries_until_ready() {
+        let vla = MockLlmClient::new()
+            .with_error("connection refused")
+            .with_text("ready")
+            .shared();
+        let ready = || {
+            MockLlmClient::new()
+                .with_fallback(MockResponse::Text("ready".into()))
+                .shared()
+        };
+        let clients = mock_clients(vla.clone(), ready(), ready());
+        let config = LlmConfig {
+            warm_up_timeout_secs: 10,
+            ..LlmConfig::default()
+        };
+
+        let health = warm_up(&clients, &config).await;
+        assert_eq!(health.len(), 3);
+        assert!(health.iter().all(|h| h.ready));
+        assert_eq!(health[0].attempts, 2);
+        assert_eq!(vla.request_count(), 2);
+    }
+}
@@ -0,0 +1,232 @@
+//! Per-model image size limits
+//!
+//! `ImageLimitMiddleware` shrinks attached images so the composite and ARIAOS
+//! screenshots fit a model's vision budget: first to the maximum dimension,
+//! then progressively smaller until the encoding fits the byte limit.
+
+use std::io::Cursor;
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::{
+    DynamicImage, GenericImageView, ImageFormat, codecs::jpeg::JpegEncoder, imageops::FilterType,
+};
+use tracing::debug;
+
+use super::middleware::{LlmCall, LlmMiddleware};
+use crate::config::ImageEncoding;
+
+/// Never shrink below this on the longest side when chasing a byte budget
+const MIN_DIMENSION: u32 = 256;
+/// Quality for lossy images re-encoded to fit limits
+const REFIT_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    /// Longest side in pixels
+    pub max_dimension: Option<u32>,
+    /// Encoded size in bytes (before base64)
+    pub max_bytes: Option<usize>,
+}
+
+impl ImageLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_dimension.is_none() && self.max_bytes.is_none()
+    }
+}
+
+pub struct ImageLimitMiddleware {
+    limits: ImageLimits,
+}
+
+impl ImageLimitMiddleware {
+    pub fn new(limits: ImageLimits) -> Self {
+        Self { limits }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmMiddleware for ImageLimitMiddleware {
+    async fn on_request(&self, call: &mut LlmCall) -> Result<()> {
+        let limits = self.limits;
+        let mut images: Vec<String> = call
+            .request
+            .images_mut()
+            .into_iter()
+            .map(std::mem::take)
+            .collect();
+        if images.is_empty() {
+            return Ok(());
+        }
+
+        // Decoding and re-encoding large images is CPU-heavy
+        images = tokio::task::spawn_blocking(move || {
+            images
+                .into_iter()
+                .map(|image| fit_image(&image, limits))
+                .collect::<Result<Vec<_>>>()
+        })
+        .await??;
+
+        for (slot, image) in call.request.images_mut().into_iter().zip(images) {
+            *slot = image;
+        }
+        Ok(())
+    }
+}
+
+/// Resize one image (raw base64 or `data:` URL), keeping its original form
+pub fn fit_image(image: &str, limits: ImageLimits) -> Result<String> {
+    let (prefix, data) = match image.find("base64,") {
+        Some(index) if image.starts_with("data:") => image.split_at(index + "base64,".len()),
+        _ => ("", image),
+    };
+
+    let bytes = BASE64.decode(data)?;
+    let encoding = match image::guess_format(&bytes)? {
+        ImageFormat::Jpeg => ImageEncoding::Jpeg,
+        ImageFormat::WebP => ImageEncoding::Webp,
+        _ => ImageEncoding::Png,
+    };
+    let within_bytes = limits.max_bytes.is_none_or(|max| bytes.len() <= max);
+    let decoded = image::load_from_memory(&bytes)?;
+    let (width, height) = decoded.dimensions();
+    let longest = width.max(height);
+    let within_dimension = limits.max_dimension.is_none_or(|max| longest <= max);
+    if within_bytes && within_dimension {
+        return Ok(image.to_string());
+    }
+
+    let mut target = limits.max_dimension.map_or(longest, |max| longest.min(max));
+    loop {
+        let resized = if target < longest {
+            decoded.resize(target, target, FilterType::Triangle)
+        } else {
+            decoded.clone()
+        };
+        let encoded = encode(&resized, encoding, REFIT_QUALITY)?;
+
+        let fits = limits.max_bytes.is_none_or(|max| encoded.len() <= max);
+        if fits || target <= MIN_DIMENSION {
+            debug!(
+                from = format!("{}x{} ({} bytes)", width, height, bytes.len()),
+                to = format!("{}x{} ({} bytes)", resized.width(), resized.height(), encoded.len()),
+                "Downscaled image for model limits"
+            );
+            if !fits {
+                return Err(anyhow!(
+                    "Image still {} bytes at {}px, over the {} byte limit",
+                    encoded.len(),
+                    target,
+                    limits.max_bytes.unwrap_or_default()
+                ));
+            }
+            let data = BASE64.encode(encoded);
+            return Ok(if prefix.is_empty() {
+                data
+            } else {
+                format!("data:{};base64,{}", mime_type(encoding), data)
+            });
+        }
+
+        // Encoded size scales roughly with area
+        target = (target * 3 / 4).max(MIN_DIMENSION);
+    }
+}
+
+/// Encode an image as a `data:` URL for a vision model; `quality` (1-100)
+/// applies to JPEG and WebP
+pub fn encode_image(image: &DynamicImage, encoding: ImageEncoding, quality: u8) -> Result<String> {
+    let bytes = encode(image, encoding, quality)?;
+    Ok(format!("data:{};base64,{}", mime_type(encoding), BASE64.encode(bytes)))
+}
+
+/// URL for an image given as a `data:` URL or as raw base64 PNG
+pub fn image_url(image: &str) -> String {
+    if image.starts_with("data:") {
+        image.to_string()
+    } else {
+        format!("data:image/png;base64,{}", image)
+    }
+}
+
+fn encode(image: &DynamicImage, encoding: ImageEncoding, quality: u8) -> Result<Vec<u8>> {
+    let quality = quality.clamp(1, 100);
+    let mut buffer = Vec::new();
+    match encoding {
+        ImageEncoding::Png => image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?,
+        // JPEG has no alpha channel
+        ImageEncoding::Jpeg => JpegEncoder::new_with_quality(&mut buffer, quality)
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?,
+        // The `image` crate only writes lossless WebP
+        ImageEncoding::Webp => {
+            let rgba = image.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode(quality as f32);
+            buffer.extend_from_slice(&encoded);
+        }
+    }
+    Ok(buffer)
+}
+
+fn mime_type(encoding: ImageEncoding) -> &'static str {
+    match encoding {
+        ImageEncoding::Png => "image/png",
+        ImageEncoding::Jpeg => "image/jpeg",
+        ImageEncoding::Webp => "image/webp",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn png_base64(width: u32, height: u32) -> String {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x * 7 % 256) as u8, (y * 13 % 256) as u8, ((x ^ y) % 256) as u8, 255])
+        }));
+        BASE64.encode(encode(&image, ImageEncoding::Png, 100).unwrap())
+    }
+
+    #[test]
+    fn test_fit_image_respects_dimension_and_keeps_data_url() {
+        let limits = ImageLimits {
+            max_dimension: Some(512),
+            max_bytes: None,
+        };
+        let url = format!("data:image/png;base64,{}", png_base64(1024, 640));
+        let fitted = fit_image(&url, limits).unwrap();
+        let data = fitted.strip_prefix("data:image/png;base64,").unwrap();
+        let decoded = image::load_from_memory(&BASE64.decode(data).unwrap()).unwrap();
+        assert_eq!(decoded.dimensions(), (512, 320));
+
+        // Already small enough: returned untouched
+        let small = png_base64(100, 50);
+        assert_eq!(fit_image(&small, limits).unwrap(), small);
+    }
+
+    #[test]
+    fn test_encode_image_lossy_formats() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(320, 200, |x, y| {
+            image::Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255])
+        }));
+        for (encoding, mime) in [
+            (ImageEncoding::Jpeg, "data:image/jpeg;base64,"),
+            (ImageEncoding::Webp, "data:image/webp;base64,"),
+        ] {
+            let url = encode_image(&image, encoding, 60).unwrap();
+            let data = url.strip_prefix(mime).unwrap();
+            let decoded = image::load_from_memory(&BASE64.decode(data).unwrap()).unwrap();
+            assert_eq!(decoded.dimensions(), (320, 200));
+
+            // Refitting keeps the format
+            let limits = ImageLimits {
+                max_dimension: Some(160),
+                max_bytes: None,
+            };
+            assert!(fit_image(&url, limits).unwrap().starts_with(mime));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Per-provider concurrency limiting for outbound LLM requests
+//!
+//! Local servers like LM Studio fall over when several requests arrive at once,
+//! so every client is wrapped in a `LimitedClient` that shares a semaphore with
+//! all other clients pointing at the same provider.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc::UnboundedSender};
+use tracing::debug;
+
+use super::{
+    ChatCompletionWithTools, ChatMessage, LlmClient, SharedLlm, ToolCall, ToolDefinition,
+};
+use crate::config::LlmProvider;
+
+/// Waits longer than this are logged
+const SLOW_WAIT: Duration = Duration::from_millis(100);
+
+/// Semaphore plus counters for a single provider
+pub struct ProviderLimiter {
+    name: String,
+    max_in_flight: usize,
+    semaphore: Arc<Semaphore>,
+    in_flight: AtomicUsize,
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    total_wait_ms: AtomicU64,
+}
+
+/// Point-in-time view of a provider's limiter
+#[derive(Debug, Clone, Serialize)]
+pub struct LimiterStats {
+    pub provider: String,
+    pub max_in_flight: usize,
+    pub in_flight: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub total_wait_ms: u64,
+}
+
+impl ProviderLimiter {
+    /// `max_in_flight` of 0 means unlimited
+    pub fn new(name: impl Into<String>, max_in_flight: usize) -> Self {
+        let permits = if max_in_flight == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            max_in_flight
+        };
+        Self {
+            name: name.into(),
+            max_in_flight,
+            semaphore: Arc::new(Semaphore::new(permits)),
+            in_flight: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            total_wait_ms: AtomicU64::new(0),
+        }
+    }
+
+    async fn acquire(self: &Arc<Self>) -> Result<LimiterPermit> {
+        let start = Instant::now();
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        let permit = self.semaphore.clone().acquire_owned().await;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        let permit = permit.map_err(|_| anyhow!("LLM limiter for {} closed", self.name))?;
+
+        let waited = start.elapsed();
+        self.total_wait_ms
+            .fetch_add(waited.as_millis() as u64, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+
+        if waited > SLOW_WAIT {
+            debug!(
+                provider = %self.name,
+                waited_ms = waited.as_millis() as u64,
+                queued = self.queued.load(Ordering::SeqCst),
+                "LLM request waited for a concurrency slot"
+            );
+        }
+
+        Ok(LimiterPermit {
+            limiter: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        LimiterStats {
+            provider: self.name.clone(),
+            max_in_flight: self.max_in_flight,
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+            queued: self.queued.load(Ordering::SeqCst),
+            completed: self.completed.load(Ordering::Relaxed),
+            total_wait_ms: self.total_wait_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Held for the duration of a request; releases the slot on drop
+struct LimiterPermit {
+    limiter: Arc<ProviderLimiter>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for LimiterPermit {
+    fn drop(&mut self) {
+        self.limiter.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.limiter.completed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Hands out one shared limiter per distinct provider
+pub struct LimiterRegistry {
+    max_in_flight: usize,
+    limiters: HashMap<String, Arc<ProviderLimiter>>,
+}
+
+impl LimiterRegistry {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            max_in_flight,
+            limiters: HashMap::new(),
+        }
+    }
+
+    pub fn for_provider(&mut self, provider: &LlmProvider) -> Arc<ProviderLimiter> {
+        let key = provider_key(provider);
+        let max_in_flight = self.max_in_flight;
+        self.limiters
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(ProviderLimiter::new(key, max_in_flight)))
+            .clone()
+    }
+
+    pub fn into_limiters(self) -> Vec<Arc<ProviderLimiter>> {
+        self.limiters.into_values().collect()
+    }
+}
+
+/// Requests to the same server share a limiter regardless of role
+fn provider_key(provider: &LlmProvider) -> String {
+    match provider {
+        LlmProvider::LmStudio { endpoint } => {
+            format!("lmstudio:{}", endpoint.trim_end_matches('/'))
+        }
+        LlmProvider::OpenRouter { .. } => "openrouter".to_string(),
+        LlmProvider::LlamaCpp { model_path, .. } => format!("llamacpp:{}", model_path),
+    }
+}
+
+/// `LlmClient` wrapper that waits for a provider slot before each request
+pub struct LimitedClient {
+    inner: SharedLlm,
+    limiter: Arc<ProviderLimiter>,
+}
+
+impl LimitedClient {
+    pub fn new(inner: SharedLlm, limiter: Arc<ProviderLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for LimitedClient {
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.complete_text(model, prompt).await
+    }
+
+    async fn complete_json(&self, model: &str, prompt: &str, schema: Value) -> Result<Value> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.complete_json(model, prompt, schema).await
+    }
+
+    async fn complete_vision_text(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+    ) -> Result<String> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner
+            .complete_vision_text(model, prompt, images_base64)
+            .await
+    }
+
+    async fn complete_vision_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+        schema: Value,
+    ) -> Result<Value> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner
+            .complete_vision_json(model, prompt, images_base64, schema)
+            .await
+    }
+
+    async fn complete_chat(&self, model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.complete_chat(model, messages).await
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.complete_vision_chat(model, messages).await
+    }
+
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.complete_with_tools(model, messages, tools).await
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner
+            .complete_vision_with_tools(model, messages, tools)
+            .await
+    }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner
+            .stream_with_tools(model, messages, tools, tool_calls, text)
+            .await
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let _permit = self.limiter.acquire().await?;
+        self.inner.embed(model, texts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limiter_queues_beyond_capacity() {
+        let limiter = Arc::new(ProviderLimiter::new("test", 1));
+        let first = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.stats().in_flight, 1);
+
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.stats().queued, 1);
+
+        drop(first);
+        waiter.await.unwrap().unwrap();
+        let stats = limiter.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.queued, 0);
+        assert_eq!(stats.completed, 2);
+    }
+
+    #[test]
+    fn test_registry_shares_limiter_per_provider() {
+        let mut registry = LimiterRegistry::new(2);
+        let lmstudio = LlmProvider::LmStudio {
+            endpoint: "http://127.0.0.1:1234/".into(),
+        };
+        let same = LlmProvider::LmStudio {
+            endpoint: "http://127.0.0.1:1234".into(),
+        };
+        let a = registry.for_provider(&lmstudio);
+        let b = registry.for_provider(&same);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(registry.into_limiters().len(), 1);
+    }
+}
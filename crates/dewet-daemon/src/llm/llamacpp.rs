@@ -0,0 +1,288 @@
+//! In-process llama.cpp backend (feature `llama-cpp`)
+//!
+//! Loads a GGUF model directly into the daemon so a single binary can run
+//! without a separate LM Studio process. Text-only: image inputs are rejected
+//! and tool definitions are ignored.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{Context, Result, anyhow};
+use llama_cpp_2::{
+    context::params::LlamaContextParams,
+    llama_backend::LlamaBackend,
+    llama_batch::LlamaBatch,
+    model::{AddBos, LlamaChatMessage, LlamaModel, Special, params::LlamaModelParams},
+    sampling::LlamaSampler,
+};
+use serde_json::Value;
+
+use super::{
+    ChatCompletionWithTools, ChatContent, ChatMessage, ChatRole, ContentPart, LlmClient,
+    ToolDefinition, extract_json, json_instructions,
+    reasoning::strip_reasoning,
+    usage::{TokenUsage, report_usage},
+};
+
+const DEFAULT_CONTEXT_SIZE: u32 = 4096;
+const DEFAULT_MAX_TOKENS: u32 = 512;
+
+/// llama.cpp may only be initialised once per process
+static BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
+
+/// Models are shared between roles that point at the same GGUF file
+static MODELS: OnceLock<Mutex<HashMap<PathBuf, Arc<LlamaModel>>>> = OnceLock::new();
+
+pub struct LlamaCppClient {
+    model_path: PathBuf,
+    context_size: u32,
+    gpu_layers: u32,
+    max_tokens: u32,
+}
+
+impl LlamaCppClient {
+    pub fn new(
+        model_path: impl Into<PathBuf>,
+        context_size: Option<u32>,
+        gpu_layers: Option<u32>,
+        max_tokens: Option<u32>,
+    ) -> Self {
+        Self {
+            model_path: model_path.into(),
+            context_size: context_size.unwrap_or(DEFAULT_CONTEXT_SIZE),
+            gpu_layers: gpu_layers.unwrap_or(0),
+            max_tokens: max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        }
+    }
+
+    fn backend() -> Result<&'static LlamaBackend> {
+        if let Some(backend) = BACKEND.get() {
+            return Ok(backend);
+        }
+        let backend = LlamaBackend::init().context("Failed to initialise llama.cpp backend")?;
+        Ok(BACKEND.get_or_init(|| backend))
+    }
+
+    /// Load (or reuse) the model at `path`. Loading reads the whole GGUF
+    /// file, so call this from a blocking thread.
+    fn model(path: &Path, gpu_layers: u32) -> Result<Arc<LlamaModel>> {
+        let backend = Self::backend()?;
+        let cache = MODELS.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut models = cache.lock().map_err(|_| anyhow!("llama.cpp model cache poisoned"))?;
+
+        if let Some(model) = models.get(path) {
+            return Ok(model.clone());
+        }
+
+        tracing::info!(?path, gpu_layers, "Loading GGUF model");
+        let params = LlamaModelParams::default().with_n_gpu_layers(gpu_layers);
+        let model = LlamaModel::load_from_file(backend, path, &params)
+            .with_context(|| format!("Failed to load GGUF model {:?}", path))?;
+        let model = Arc::new(model);
+        models.insert(path.to_path_buf(), model.clone());
+        Ok(model)
+    }
+
+    /// Run the chat template and generate a completion on a blocking thread
+    async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
+        let model_path = self.model_path.clone();
+        let gpu_layers = self.gpu_layers;
+        let context_size = self.context_size;
+        let max_tokens = self.max_tokens;
+
+        let chat = messages
+            .into_iter()
+            .map(|msg| {
+                let role = match msg.role {
+                    ChatRole::System => "system",
+                    ChatRole::User => "user",
+                    ChatRole::Assistant => "assistant",
+                    ChatRole::Tool => "tool",
+                };
+                LlamaChatMessage::new(role.to_string(), message_text(&msg.content)?)
+                    .map_err(|e| anyhow!("Invalid chat message: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (text, usage) = tokio::task::spawn_blocking(move || {
+            let model = Self::model(&model_path, gpu_layers)?;
+            run_inference(&model, chat, context_size, max_tokens)
+        })
+        .await??;
+        report_usage(usage);
+        Ok(strip_reasoning(text))
+    }
+}
+
+fn run_inference(
+    model: &LlamaModel,
+    chat: Vec<LlamaChatMessage>,
+    context_size: u32,
+    max_tokens: u32,
+) -> Result<(String, TokenUsage)> {
+    let backend = LlamaCppClient::backend()?;
+    let template = model
+        .chat_template(None)
+        .map_err(|e| anyhow!("Model has no chat template: {}", e))?;
+    let prompt = model
+        .apply_chat_template(&template, &chat, true)
+        .map_err(|e| anyhow!("Failed to apply chat template: {}", e))?;
+
+    let ctx_params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(context_size));
+    let mut ctx = model
+        .new_context(backend, ctx_params)
+        .map_err(|e| anyhow!("Failed to create llama.cpp context: {}", e))?;
+
+    let tokens = model
+        .str_to_token(&prompt, AddBos::Never)
+        .map_err(|e| anyhow!("Failed to tokenize prompt: {}", e))?;
+    if tokens.len() as u32 >= context_size {
+        return Err(anyhow!(
+            "Prompt is {} tokens, exceeds llama.cpp context of {}",
+            tokens.len(),
+            context_size
+        ));
+    }
+
+    let mut batch = LlamaBatch::new(context_size as usize, 1);
+    let last_index = tokens.len() as i32 - 1;
+    for (i, token) in (0_i32..).zip(tokens.iter().copied()) {
+        batch.add(token, i, &[0], i == last_index)?;
+    }
+    ctx.decode(&mut batch)
+        .map_err(|e| anyhow!("llama.cpp decode failed: {}", e))?;
+
+    let mut sampler = LlamaSampler::chain_simple([
+        LlamaSampler::temp(0.7),
+        LlamaSampler::dist(rand::random::<u32>()),
+    ]);
+
+    let mut output = Vec::new();
+    let mut n_cur = batch.n_tokens();
+    let limit = (n_cur as u32 + max_tokens).min(context_size) as i32;
+
+    while n_cur < limit {
+        let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+        sampler.accept(token);
+
+        if model.is_eog_token(token) {
+            break;
+        }
+
+        output.extend(
+            model
+                .token_to_bytes(token, Special::Tokenize)
+                .map_err(|e| anyhow!("Failed to detokenize: {}", e))?,
+        );
+
+        batch.clear();
+        batch.add(token, n_cur, &[0], true)?;
+        n_cur += 1;
+        ctx.decode(&mut batch)
+            .map_err(|e| anyhow!("llama.cpp decode failed: {}", e))?;
+    }
+
+    let usage = TokenUsage {
+        prompt_tokens: tokens.len() as u32,
+        completion_tokens: (n_cur - tokens.len() as i32) as u32,
+    };
+    Ok((String::from_utf8_lossy(&output).trim().to_string(), usage))
+}
+
+/// Flatten message content to text, rejecting images
+fn message_text(content: &ChatContent) -> Result<String> {
+    match content {
+        ChatContent::Text(text) => Ok(text.clone()),
+        ChatContent::Multimodal(parts) => {
+            let mut combined = String::new();
+            for part in parts {
+                match part {
+                    ContentPart::Text { text } => combined.push_str(text),
+                    ContentPart::ImageUrl { .. } => {
+                        return Err(anyhow!("llama.cpp backend does not support image input"));
+                    }
+                }
+            }
+            Ok(combined)
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for LlamaCppClient {
+    async fn complete_text(&self, _model: &str, prompt: &str) -> Result<String> {
+        self.generate(vec![ChatMessage::user(prompt)]).await
+    }
+
+    async fn complete_json(&self, _model: &str, prompt: &str, schema: Value) -> Result<Value> {
+        let text = self
+            .generate(vec![ChatMessage::user(json_instructions(prompt, &schema))])
+            .await?;
+        extract_json(&text)
+    }
+
+    async fn complete_vision_text(
+        &self,
+        _model: &str,
+        _prompt: &str,
+        _images_base64: Vec<String>,
+    ) -> Result<String> {
+        Err(anyhow!("llama.cpp backend does not support image input"))
+    }
+
+    async fn complete_vision_json(
+        &self,
+        _model: &str,
+        _prompt: &str,
+        _images_base64: Vec<String>,
+        _schema: Value,
+    ) -> Result<Value> {
+        Err(anyhow!("llama.cpp backend does not support image input"))
+    }
+
+    async fn complete_chat(&self, _model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        self.generate(messages).await
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        _model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        // Fails inside message_text if any images are attached
+        self.generate(messages).await
+    }
+
+    async fn complete_with_tools(
+        &self,
+        _model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        if !tools.is_empty() {
+            tracing::debug!(count = tools.len(), "llama.cpp backend ignores tool definitions");
+        }
+        let text = self.generate(messages).await?;
+        Ok(ChatCompletionWithTools {
+            content: if text.is_empty() { None } else { Some(text) },
+            tool_calls: Vec::new(),
+        })
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        self.complete_with_tools(model, messages, tools).await
+    }
+
+    async fn embed(&self, _model: &str, _texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        Err(anyhow!("llama.cpp backend does not support embeddings"))
+    }
+}
@@ -2,13 +2,20 @@ use anyhow::{Result, anyhow};
 use reqwest::Client;
 use serde_json::Value;
 use serde_json::json;
+use tokio::sync::mpsc::UnboundedSender;
 use tracing;
 
-use super::{ChatCompletionWithTools, ChatMessage, FunctionCall, LlmClient, ToolCall, ToolDefinition};
+use super::{
+    ChatCompletionWithTools, ChatMessage, FunctionCall, GenerationOptions, LlmClient, ToolCall,
+    ToolDefinition, extract_embeddings, image_url, middleware::request_headers,
+    reasoning::{report_response_reasoning, strip_reasoning},
+    streaming::read_tool_stream, usage::report_response_usage,
+};
 
 pub struct LmStudioClient {
     http: Client,
     endpoint: String,
+    generation: GenerationOptions,
 }
 
 impl LmStudioClient {
@@ -16,9 +23,28 @@ impl LmStudioClient {
         Self {
             http: Client::new(),
             endpoint: endpoint.into(),
+            generation: GenerationOptions::default(),
         }
     }
 
+    pub fn with_generation(mut self, generation: GenerationOptions) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Attach per-role token and reasoning limits to a chat completion request
+    fn apply_generation(&self, mut payload: Value) -> Value {
+        if let Some(body) = payload.as_object_mut() {
+            if let Some(max_tokens) = self.generation.max_tokens {
+                body.insert("max_tokens".to_string(), json!(max_tokens));
+            }
+            if let Some(effort) = self.generation.reasoning_effort {
+                body.insert("reasoning_effort".to_string(), json!(effort));
+            }
+        }
+        payload
+    }
+
     fn url(&self) -> String {
         format!(
             "{}/v1/chat/completions",
@@ -26,8 +52,22 @@ impl LmStudioClient {
         )
     }
 
+    fn embeddings_url(&self) -> String {
+        format!("{}/v1/embeddings", self.endpoint.trim_end_matches('/'))
+    }
+
     async fn send(&self, payload: Value) -> Result<Value> {
-        let resp = self.http.post(self.url()).json(&payload).send().await?;
+        self.send_to(self.url(), self.apply_generation(payload)).await
+    }
+
+    async fn send_to(&self, url: String, payload: Value) -> Result<Value> {
+        let resp = self
+            .http
+            .post(url)
+            .headers(request_headers())
+            .json(&payload)
+            .send()
+            .await?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -37,8 +77,50 @@ impl LmStudioClient {
         }
 
         let json: Value = resp.json().await?;
+        report_response_usage(&json);
+        report_response_reasoning(&json);
         Ok(json)
     }
+
+    /// POST a `stream: true` request and return the response for SSE reading
+    async fn send_streaming(&self, payload: Value) -> Result<reqwest::Response> {
+        let resp = self
+            .http
+            .post(self.url())
+            .headers(request_headers())
+            .json(&self.apply_generation(payload))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_else(|_| "no body".to_string());
+            tracing::error!(%status, %body, "LM Studio streaming request failed");
+            return Err(anyhow!("LM Studio error {}: {}", status, body));
+        }
+        Ok(resp)
+    }
+
+    /// Ask LM Studio to load `model` into memory (REST API, LM Studio 0.3.6+)
+    pub async fn load_model(&self, model: &str) -> Result<()> {
+        let url = format!(
+            "{}/api/v1/models/load",
+            self.endpoint.trim_end_matches('/')
+        );
+        let resp = self
+            .http
+            .post(url)
+            .json(&json!({ "model": model }))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_else(|_| "no body".to_string());
+            return Err(anyhow!("LM Studio failed to load {}: {} {}", model, status, body));
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -90,7 +172,7 @@ impl LlmClient for LmStudioClient {
                 json!({
                     "type": "image_url",
                     "image_url": {
-                        "url": format!("data:image/png;base64,{}", img)
+                        "url": image_url(&img)
                     }
                 })
             })
@@ -123,7 +205,7 @@ impl LlmClient for LmStudioClient {
                 json!({
                     "type": "image_url",
                     "image_url": {
-                        "url": format!("data:image/png;base64,{}", img)
+                        "url": image_url(&img)
                     }
                 })
             })
@@ -243,6 +325,47 @@ impl LlmClient for LmStudioClient {
         let resp = self.send(body).await?;
         extract_with_tools(&resp)
     }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let messages_json: Vec<Value> = messages
+            .into_iter()
+            .map(|msg| serde_json::to_value(msg).unwrap())
+            .collect();
+
+        let tools_json: Vec<Value> = tools
+            .into_iter()
+            .map(|t| serde_json::to_value(t).unwrap())
+            .collect();
+
+        let body = json!({
+            "model": model,
+            "messages": messages_json,
+            "tools": tools_json,
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        });
+
+        let resp = self.send_streaming(body).await?;
+        read_tool_stream(resp, &tool_calls, &text).await
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let count = texts.len();
+        let body = json!({
+            "model": model,
+            "input": texts
+        });
+
+        let resp = self.send_to(self.embeddings_url(), body).await?;
+        extract_embeddings(&resp, count)
+    }
 }
 
 fn extract_text(resp: &Value) -> Result<String> {
@@ -256,7 +379,7 @@ fn extract_text(resp: &Value) -> Result<String> {
 
     if let Some(content) = message.get("content") {
         if let Some(text) = content.as_str() {
-            return Ok(text.to_string());
+            return Ok(strip_reasoning(text.to_string()));
         }
         if let Some(items) = content.as_array() {
             let mut combined = String::new();
@@ -268,7 +391,7 @@ fn extract_text(resp: &Value) -> Result<String> {
                 }
             }
             if !combined.is_empty() {
-                return Ok(combined);
+                return Ok(strip_reasoning(combined));
             }
         }
     }
@@ -330,5 +453,7 @@ fn extract_with_tools(resp: &Value) -> Result<ChatCompletionWithTools> {
         Vec::new()
     };
 
+    let content = content.map(strip_reasoning).filter(|c| !c.is_empty());
+
     Ok(ChatCompletionWithTools { content, tool_calls })
 }
@@ -0,0 +1,633 @@
+//! Request/response interceptors for any `LlmClient`
+//!
+//! A `MiddlewareClient` turns each trait call into an `LlmCall`, lets every
+//! `LlmMiddleware` inspect or rewrite it, dispatches it to the inner client,
+//! then hands the `LlmResponse` back through the middleware in reverse order.
+//! Extra HTTP headers set by middleware are applied by the HTTP providers.
+
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use super::{
+    ChatCompletionWithTools, ChatContent, ChatMessage, ContentPart, LlmClient, SharedLlm, ToolCall,
+    ToolDefinition,
+};
+
+/// The arguments of a single `LlmClient` call
+#[derive(Debug, Clone)]
+pub enum LlmRequest {
+    Text {
+        prompt: String,
+    },
+    Json {
+        prompt: String,
+        schema: Value,
+    },
+    VisionText {
+        prompt: String,
+        images_base64: Vec<String>,
+    },
+    VisionJson {
+        prompt: String,
+        images_base64: Vec<String>,
+        schema: Value,
+    },
+    Chat {
+        messages: Vec<ChatMessage>,
+    },
+    VisionChat {
+        messages: Vec<ChatMessage>,
+    },
+    Tools {
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    },
+    VisionTools {
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    },
+    Embed {
+        texts: Vec<String>,
+    },
+}
+
+impl LlmRequest {
+    /// Every piece of prompt text in the request (not images or schemas)
+    pub fn texts_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            LlmRequest::Text { prompt }
+            | LlmRequest::Json { prompt, .. }
+            | LlmRequest::VisionText { prompt, .. }
+            | LlmRequest::VisionJson { prompt, .. } => vec![prompt],
+            LlmRequest::Chat { messages }
+            | LlmRequest::VisionChat { messages }
+            | LlmRequest::Tools { messages, .. }
+            | LlmRequest::VisionTools { messages, .. } => messages
+                .iter_mut()
+                .flat_map(|msg| match &mut msg.content {
+                    ChatContent::Text(text) => vec![text],
+                    ChatContent::Multimodal(parts) => parts
+                        .iter_mut()
+                        .filter_map(|part| match part {
+                            ContentPart::Text { text } => Some(text),
+                            ContentPart::ImageUrl { .. } => None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            LlmRequest::Embed { texts } => texts.iter_mut().collect(),
+        }
+    }
+
+    /// Every attached image: raw base64 for prompt-style requests, `data:` URLs
+    /// for chat messages
+    pub fn images_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            LlmRequest::VisionText { images_base64, .. }
+            | LlmRequest::VisionJson { images_base64, .. } => images_base64.iter_mut().collect(),
+            LlmRequest::Chat { messages }
+            | LlmRequest::VisionChat { messages }
+            | LlmRequest::Tools { messages, .. }
+            | LlmRequest::VisionTools { messages, .. } => messages
+                .iter_mut()
+                .flat_map(|msg| match &mut msg.content {
+                    ChatContent::Text(_) => Vec::new(),
+                    ChatContent::Multimodal(parts) => parts
+                        .iter_mut()
+                        .filter_map(|part| match part {
+                            ContentPart::ImageUrl { image_url } => Some(&mut image_url.url),
+                            ContentPart::Text { .. } => None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            LlmRequest::Text { .. } | LlmRequest::Json { .. } | LlmRequest::Embed { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// A request on its way to the model
+#[derive(Debug, Clone)]
+pub struct LlmCall {
+    pub model: String,
+    pub request: LlmRequest,
+    /// Extra HTTP headers (ignored by in-process backends)
+    pub headers: Vec<(String, String)>,
+}
+
+/// What the model returned
+#[derive(Debug, Clone)]
+pub enum LlmResponse {
+    Text(String),
+    Json(Value),
+    Tools(ChatCompletionWithTools),
+    Embeddings(Vec<Vec<f32>>),
+}
+
+/// Interceptor composed around an `LlmClient`. All hooks default to no-ops.
+#[async_trait::async_trait]
+pub trait LlmMiddleware: Send + Sync {
+    /// Inspect or rewrite the request. Returning an error aborts the call.
+    async fn on_request(&self, _call: &mut LlmCall) -> Result<()> {
+        Ok(())
+    }
+
+    /// Inspect or rewrite the response. Returning an error fails the call.
+    async fn on_response(&self, _call: &LlmCall, _response: &mut LlmResponse) -> Result<()> {
+        Ok(())
+    }
+
+    /// Observe a failed call (from the client or a later middleware)
+    async fn on_error(&self, _call: &LlmCall, _error: &anyhow::Error) {}
+}
+
+pub type SharedMiddleware = Arc<dyn LlmMiddleware>;
+
+tokio::task_local! {
+    static HEADERS: Vec<(String, String)>;
+}
+
+/// Headers set by middleware for the request currently being sent
+pub(crate) fn request_headers() -> HeaderMap {
+    let mut map = HeaderMap::new();
+    let _ = HEADERS.try_with(|headers| {
+        for (name, value) in headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    map.insert(name, value);
+                }
+                _ => warn!(header = %name, "Ignoring invalid middleware header"),
+            }
+        }
+    });
+    map
+}
+
+/// Where a streamed completion sends its tool calls and text deltas
+type Stream = (UnboundedSender<ToolCall>, UnboundedSender<String>);
+
+/// `LlmClient` wrapper that runs a middleware stack around every call
+pub struct MiddlewareClient {
+    inner: SharedLlm,
+    middleware: Vec<SharedMiddleware>,
+}
+
+impl MiddlewareClient {
+    /// Middleware runs in order for requests and in reverse for responses
+    pub fn new(inner: SharedLlm, middleware: Vec<SharedMiddleware>) -> Self {
+        Self { inner, middleware }
+    }
+
+    async fn call(&self, model: &str, request: LlmRequest) -> Result<LlmResponse> {
+        self.call_streaming(model, request, None).await
+    }
+
+    /// Like `call`, but tool-calling requests are streamed to `stream`.
+    /// Response middleware sees the final completion, not the deltas.
+    async fn call_streaming(
+        &self,
+        model: &str,
+        request: LlmRequest,
+        stream: Option<Stream>,
+    ) -> Result<LlmResponse> {
+        let mut call = LlmCall {
+            model: model.to_string(),
+            request,
+            headers: Vec::new(),
+        };
+
+        let result = self.run(&mut call, stream).await;
+        if let Err(err) = &result {
+            for middleware in &self.middleware {
+                middleware.on_error(&call, err).await;
+            }
+        }
+        result
+    }
+
+    async fn run(&self, call: &mut LlmCall, stream: Option<Stream>) -> Result<LlmResponse> {
+        for middleware in &self.middleware {
+            middleware.on_request(call).await?;
+        }
+
+        let headers = call.headers.clone();
+        let mut response = HEADERS
+            .scope(headers, dispatch(self.inner.as_ref(), call, stream))
+            .await?;
+
+        for middleware in self.middleware.iter().rev() {
+            middleware.on_response(call, &mut response).await?;
+        }
+        Ok(response)
+    }
+}
+
+async fn dispatch(
+    client: &dyn LlmClient,
+    call: &LlmCall,
+    stream: Option<Stream>,
+) -> Result<LlmResponse> {
+    let model = call.model.as_str();
+    if let Some((tool_calls, text)) = stream {
+        if let LlmRequest::Tools { messages, tools } | LlmRequest::VisionTools { messages, tools } =
+            &call.request
+        {
+            let (messages, tools) = (messages.clone(), tools.clone());
+            return Ok(LlmResponse::Tools(
+                client
+                    .stream_with_tools(model, messages, tools, tool_calls, text)
+                    .await?,
+            ));
+        }
+    }
+    Ok(match call.request.clone() {
+        LlmRequest::Text { prompt } => LlmResponse::Text(client.complete_text(model, &prompt).await?),
+        LlmRequest::Json { prompt, schema } => {
+            LlmResponse::Json(client.complete_json(model, &prompt, schema).await?)
+        }
+        LlmRequest::VisionText {
+            prompt,
+            images_base64,
+        } => LlmResponse::Text(
+            client
+                .complete_vision_text(model, &prompt, images_base64)
+                .await?,
+        ),
+        LlmRequest::VisionJson {
+            prompt,
+            images_base64,
+            schema,
+        } => LlmResponse::Json(
+            client
+                .complete_vision_json(model, &prompt, images_base64, schema)
+                .await?,
+        ),
+        LlmRequest::Chat { messages } => {
+            LlmResponse::Text(client.complete_chat(model, messages).await?)
+        }
+        LlmRequest::VisionChat { messages } => {
+            LlmResponse::Text(client.complete_vision_chat(model, messages).await?)
+        }
+        LlmRequest::Tools { messages, tools } => {
+            LlmResponse::Tools(client.complete_with_tools(model, messages, tools).await?)
+        }
+        LlmRequest::VisionTools { messages, tools } => LlmResponse::Tools(
+            client
+                .complete_vision_with_tools(model, messages, tools)
+                .await?,
+        ),
+        LlmRequest::Embed { texts } => LlmResponse::Embeddings(client.embed(model, texts).await?),
+    })
+}
+
+fn into_text(response: LlmResponse) -> Result<String> {
+    match response {
+        LlmResponse::Text(text) => Ok(text),
+        LlmResponse::Json(value) => Ok(value.to_string()),
+        LlmResponse::Tools(completion) => Ok(completion.content.unwrap_or_default()),
+        LlmResponse::Embeddings(_) => Err(anyhow!("Middleware returned embeddings, text expected")),
+    }
+}
+
+fn into_json(response: LlmResponse) -> Result<Value> {
+    match response {
+        LlmResponse::Json(value) => Ok(value),
+        LlmResponse::Text(text) => Ok(serde_json::from_str(&text)?),
+        other => Err(anyhow!("Middleware returned {:?}, JSON expected", other)),
+    }
+}
+
+fn into_tools(response: LlmResponse) -> Result<ChatCompletionWithTools> {
+    match response {
+        LlmResponse::Tools(completion) => Ok(completion),
+        other => Ok(ChatCompletionWithTools {
+            content: Some(into_text(other)?),
+            tool_calls: Vec::new(),
+        }),
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for MiddlewareClient {
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        let request = LlmRequest::Text {
+            prompt: prompt.to_string(),
+        };
+        into_text(self.call(model, request).await?)
+    }
+
+    async fn complete_json(&self, model: &str, prompt: &str, schema: Value) -> Result<Value> {
+        let request = LlmRequest::Json {
+            prompt: prompt.to_string(),
+            schema,
+        };
+        into_json(self.call(model, request).await?)
+    }
+
+    async fn complete_vision_text(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+    ) -> Result<String> {
+        let request = LlmRequest::VisionText {
+            prompt: prompt.to_string(),
+            images_base64,
+        };
+        into_text(self.call(model, request).await?)
+    }
+
+    async fn complete_vision_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+        schema: Value,
+    ) -> Result<Value> {
+        let request = LlmRequest::VisionJson {
+            prompt: prompt.to_string(),
+            images_base64,
+            schema,
+        };
+        into_json(self.call(model, request).await?)
+    }
+
+    async fn complete_chat(&self, model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        into_text(self.call(model, LlmRequest::Chat { messages }).await?)
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        into_text(self.call(model, LlmRequest::VisionChat { messages }).await?)
+    }
+
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        into_tools(self.call(model, LlmRequest::Tools { messages, tools }).await?)
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        into_tools(
+            self.call(model, LlmRequest::VisionTools { messages, tools })
+                .await?,
+        )
+    }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let request = LlmRequest::VisionTools { messages, tools };
+        into_tools(
+            self.call_streaming(model, request, Some((tool_calls, text)))
+                .await?,
+        )
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        match self.call(model, LlmRequest::Embed { texts }).await? {
+            LlmResponse::Embeddings(vectors) => Ok(vectors),
+            other => Err(anyhow!("Middleware returned {:?}, embeddings expected", other)),
+        }
+    }
+}
+
+/// Replaces matches of the configured patterns in every outgoing prompt, so
+/// secrets visible on screen or pasted into chat never leave the machine.
+pub struct RedactionMiddleware {
+    patterns: Vec<Regex>,
+}
+
+impl RedactionMiddleware {
+    pub const REPLACEMENT: &'static str = "[REDACTED]";
+
+    /// Invalid patterns are skipped with a warning
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Self {
+        let patterns = patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern.as_ref()) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    warn!(pattern = pattern.as_ref(), %err, "Ignoring invalid redaction pattern");
+                    None
+                }
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Replace every match in `text`
+    pub fn redact(&self, text: &mut String) {
+        for pattern in &self.patterns {
+            if pattern.is_match(text) {
+                *text = pattern.replace_all(text, Self::REPLACEMENT).into_owned();
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmMiddleware for RedactionMiddleware {
+    async fn on_request(&self, call: &mut LlmCall) -> Result<()> {
+        for text in call.request.texts_mut() {
+            self.redact(text);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{MockLlmClient, MockResponse};
+    use parking_lot::Mutex;
+    use serde_json::json;
+    use tokio::sync::{Notify, mpsc};
+
+    struct Uppercase;
+
+    #[async_trait::async_trait]
+    impl LlmMiddleware for Uppercase {
+        async fn on_response(&self, _call: &LlmCall, response: &mut LlmResponse) -> Result<()> {
+            match response {
+                LlmResponse::Text(text) => *text = text.to_uppercase(),
+                LlmResponse::Tools(completion) => {
+                    completion.content = completion.content.as_deref().map(str::to_uppercase)
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    /// Records the model of every request and response it sees
+    #[derive(Default)]
+    struct Recording {
+        requests: Mutex<Vec<String>>,
+        responses: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmMiddleware for Recording {
+        async fn on_request(&self, call: &mut LlmCall) -> Result<()> {
+            self.requests.lock().push(call.model.clone());
+            Ok(())
+        }
+
+        async fn on_response(&self, call: &LlmCall, _response: &mut LlmResponse) -> Result<()> {
+            self.responses.lock().push(call.model.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_every_entry_point_is_intercepted() {
+        let mock = MockLlmClient::new()
+            .push(MockResponse::Embeddings(vec![vec![1.0]]))
+            .with_fallback(MockResponse::Json(json!({})))
+            .shared();
+        let recording = Arc::new(Recording::default());
+        let client = MiddlewareClient::new(mock.clone(), vec![recording.clone()]);
+        let messages = || vec![ChatMessage::user("hi")];
+        let image = || vec!["aW1n".to_string()];
+        let (tool_tx, _tool_rx) = mpsc::unbounded_channel();
+        let (text_tx, _text_rx) = mpsc::unbounded_channel();
+
+        client.embed("embed", vec!["hi".into()]).await.unwrap();
+        client.complete_text("text", "hi").await.unwrap();
+        client.complete_json("json", "hi", json!({})).await.unwrap();
+        client
+            .complete_vision_text("vision_text", "hi", image())
+            .await
+            .unwrap();
+        client
+            .complete_vision_json("vision_json", "hi", image(), json!({}))
+            .await
+            .unwrap();
+        client.complete_chat("chat", messages()).await.unwrap();
+        client
+            .complete_vision_chat("vision_chat", messages())
+            .await
+            .unwrap();
+        client
+            .complete_with_tools("tools", messages(), Vec::new())
+            .await
+            .unwrap();
+        client
+            .complete_vision_with_tools("vision_tools", messages(), Vec::new())
+            .await
+            .unwrap();
+        client
+            .stream_with_tools("stream", messages(), Vec::new(), tool_tx, text_tx)
+            .await
+            .unwrap();
+
+        let expected = [
+            "embed",
+            "text",
+            "json",
+            "vision_text",
+            "vision_json",
+            "chat",
+            "vision_chat",
+            "tools",
+            "vision_tools",
+            "stream",
+        ];
+        assert_eq!(*recording.requests.lock(), expected);
+        assert_eq!(*recording.responses.lock(), expected);
+        let models: Vec<_> = mock.requests().into_iter().map(|r| r.model).collect();
+        assert_eq!(models, expected);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_rewrites_request_and_response() {
+        let mock = MockLlmClient::new().with_text("ok").shared();
+        let client = MiddlewareClient::new(
+            mock.clone(),
+            vec![
+                Arc::new(RedactionMiddleware::new(&[r"sk-[A-Za-z0-9]+"])),
+                Arc::new(Uppercase),
+            ],
+        );
+
+        let text = client
+            .complete_chat("m", vec![ChatMessage::user("my key is sk-abc123")])
+            .await
+            .unwrap();
+        assert_eq!(text, "OK");
+
+        let sent = &mock.requests()[0].prompt;
+        assert!(sent.contains("my key is [REDACTED]"));
+        assert!(!sent.contains("sk-abc123"));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_tool_calls_arrive_before_completion() {
+        let gate = Arc::new(Notify::new());
+        let mock = MockLlmClient::new()
+            .with_tool_call(Some("Adding it"), "notes_append", json!({ "text": "milk" }))
+            .with_stream_gate(gate.clone())
+            .shared();
+        let client = Arc::new(MiddlewareClient::new(
+            mock.clone(),
+            vec![
+                Arc::new(RedactionMiddleware::new(&[r"sk-[A-Za-z0-9]+"])),
+                Arc::new(Uppercase),
+            ],
+        ));
+        let (tool_tx, mut tool_rx) = mpsc::unbounded_channel();
+        let (text_tx, mut text_rx) = mpsc::unbounded_channel();
+        let streaming = tokio::spawn({
+            let client = client.clone();
+            let messages = vec![ChatMessage::user("note sk-abc123 down")];
+            async move {
+                client
+                    .stream_with_tools("m", messages, Vec::new(), tool_tx, text_tx)
+                    .await
+            }
+        });
+
+        // Both arrive while the completion is still being generated
+        let call = tool_rx.recv().await.unwrap();
+        assert_eq!(call.function.name, "notes_append");
+        assert_eq!(text_rx.recv().await.unwrap(), "Adding it");
+        assert!(!streaming.is_finished());
+
+        gate.notify_one();
+        let completion = streaming.await.unwrap().unwrap();
+        assert_eq!(completion.content.as_deref(), Some("ADDING IT"));
+        assert_eq!(completion.tool_calls.len(), 1);
+
+        let sent = &mock.requests()[0];
+        assert_eq!(sent.method, "stream_with_tools");
+        assert!(sent.prompt.contains("note [REDACTED] down"));
+    }
+}
@@ -0,0 +1,360 @@
+//! Scriptable in-memory `LlmClient` for tests and offline replay
+//!
+//! Responses are queued up front and handed out in order, regardless of which
+//! completion method is called. Every request is recorded so tests can assert
+//! on what the pipeline actually sent.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, anyhow};
+use serde_json::{Value, json};
+use tokio::sync::{Notify, mpsc::UnboundedSender};
+
+use super::{
+    ChatCompletionWithTools, ChatContent, ChatMessage, ContentPart, FunctionCall, LlmClient,
+    LlmClients, SharedLlm, ToolCall, ToolDefinition, strip_images_for_logging,
+};
+
+/// A canned response returned by the next call to the mock
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    Text(String),
+    Json(Value),
+    Tools {
+        content: Option<String>,
+        tool_calls: Vec<ToolCall>,
+    },
+    Embeddings(Vec<Vec<f32>>),
+    Error(String),
+}
+
+/// A request the mock received
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Trait method that was called, e.g. "complete_vision_json"
+    pub method: &'static str,
+    pub model: String,
+    /// Prompt text, or JSON-serialized messages (images stripped)
+    pub prompt: String,
+    pub image_count: usize,
+    pub tool_names: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct MockLlmClient {
+    script: Mutex<VecDeque<MockResponse>>,
+    fallback: Option<MockResponse>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    /// Holds `stream_with_tools` open after it has streamed its response
+    stream_gate: Option<Arc<Notify>>,
+}
+
+impl MockLlmClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a response
+    pub fn push(self, response: MockResponse) -> Self {
+        self.script.lock().unwrap().push_back(response);
+        self
+    }
+
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        self.push(MockResponse::Text(text.into()))
+    }
+
+    pub fn with_json(self, value: Value) -> Self {
+        self.push(MockResponse::Json(value))
+    }
+
+    /// Queue a tool-calling response with a single function call
+    pub fn with_tool_call(
+        self,
+        content: Option<&str>,
+        name: impl Into<String>,
+        arguments: Value,
+    ) -> Self {
+        let call = ToolCall {
+            id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments: arguments.to_string(),
+            },
+        };
+        self.push(MockResponse::Tools {
+            content: content.map(str::to_string),
+            tool_calls: vec![call],
+        })
+    }
+
+    pub fn with_error(self, message: impl Into<String>) -> Self {
+        self.push(MockResponse::Error(message.into()))
+    }
+
+    /// Response used once the script is exhausted (otherwise calls error)
+    pub fn with_fallback(mut self, response: MockResponse) -> Self {
+        self.fallback = Some(response);
+        self
+    }
+
+    /// Keep each `stream_with_tools` call from finishing, after it has sent
+    /// its tool calls and text, until `gate` is notified
+    pub fn with_stream_gate(mut self, gate: Arc<Notify>) -> Self {
+        self.stream_gate = Some(gate);
+        self
+    }
+
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// All requests received so far, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Responses still queued
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+
+    fn next(&self, request: RecordedRequest) -> Result<MockResponse> {
+        let method = request.method;
+        self.requests.lock().unwrap().push(request);
+        let response = self
+            .script
+            .lock()
+            .unwrap()
+            .pop_front()
+            .or_else(|| self.fallback.clone())
+            .ok_or_else(|| anyhow!("MockLlmClient script exhausted ({})", method))?;
+        match response {
+            MockResponse::Error(message) => Err(anyhow!(message)),
+            other => Ok(other),
+        }
+    }
+
+    fn record_prompt(
+        method: &'static str,
+        model: &str,
+        prompt: &str,
+        image_count: usize,
+    ) -> RecordedRequest {
+        RecordedRequest {
+            method,
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            image_count,
+            tool_names: Vec::new(),
+        }
+    }
+
+    fn record_messages(
+        method: &'static str,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> RecordedRequest {
+        let image_count = messages
+            .iter()
+            .map(|m| match &m.content {
+                ChatContent::Text(_) => 0,
+                ChatContent::Multimodal(parts) => parts
+                    .iter()
+                    .filter(|p| matches!(p, ContentPart::ImageUrl { .. }))
+                    .count(),
+            })
+            .sum();
+        RecordedRequest {
+            method,
+            model: model.to_string(),
+            prompt: serde_json::to_string(&strip_images_for_logging(messages))
+                .unwrap_or_default(),
+            image_count,
+            tool_names: tools.iter().map(|t| t.function.name.clone()).collect(),
+        }
+    }
+}
+
+fn into_text(response: MockResponse) -> Result<String> {
+    match response {
+        MockResponse::Text(text) => Ok(text),
+        MockResponse::Json(value) => Ok(value.to_string()),
+        MockResponse::Tools { content, .. } => Ok(content.unwrap_or_default()),
+        MockResponse::Embeddings(_) => Err(anyhow!("Mock scripted embeddings, text expected")),
+        MockResponse::Error(message) => Err(anyhow!(message)),
+    }
+}
+
+fn into_json(response: MockResponse) -> Result<Value> {
+    match response {
+        MockResponse::Json(value) => Ok(value),
+        MockResponse::Text(text) => Ok(serde_json::from_str(&text)?),
+        other => Err(anyhow!("Mock scripted {:?}, JSON expected", other)),
+    }
+}
+
+fn into_tools(response: MockResponse) -> Result<ChatCompletionWithTools> {
+    match response {
+        MockResponse::Tools {
+            content,
+            tool_calls,
+        } => Ok(ChatCompletionWithTools {
+            content,
+            tool_calls,
+        }),
+        other => Ok(ChatCompletionWithTools {
+            content: Some(into_text(other)?),
+            tool_calls: Vec::new(),
+        }),
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for MockLlmClient {
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        into_text(self.next(Self::record_prompt("complete_text", model, prompt, 0))?)
+    }
+
+    async fn complete_json(&self, model: &str, prompt: &str, _schema: Value) -> Result<Value> {
+        into_json(self.next(Self::record_prompt("complete_json", model, prompt, 0))?)
+    }
+
+    async fn complete_vision_text(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+    ) -> Result<String> {
+        into_text(self.next(Self::record_prompt(
+            "complete_vision_text",
+            model,
+            prompt,
+            images_base64.len(),
+        ))?)
+    }
+
+    async fn complete_vision_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+        _schema: Value,
+    ) -> Result<Value> {
+        into_json(self.next(Self::record_prompt(
+            "complete_vision_json",
+            model,
+            prompt,
+            images_base64.len(),
+        ))?)
+    }
+
+    async fn complete_chat(&self, model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        into_text(self.next(Self::record_messages("complete_chat", model, &messages, &[]))?)
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        into_text(self.next(Self::record_messages(
+            "complete_vision_chat",
+            model,
+            &messages,
+            &[],
+        ))?)
+    }
+
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        into_tools(self.next(Self::record_messages(
+            "complete_with_tools",
+            model,
+            &messages,
+            &tools,
+        ))?)
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        into_tools(self.next(Self::record_messages(
+            "complete_vision_with_tools",
+            model,
+            &messages,
+            &tools,
+        ))?)
+    }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let completion = into_tools(self.next(Self::record_messages(
+            "stream_with_tools",
+            model,
+            &messages,
+            &tools,
+        ))?)?;
+        for call in &completion.tool_calls {
+            let _ = tool_calls.send(call.clone());
+        }
+        if let Some(content) = &completion.content {
+            let _ = text.send(content.clone());
+        }
+        if let Some(gate) = &self.stream_gate {
+            gate.notified().await;
+        }
+        Ok(completion)
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let prompt = json!(texts).to_string();
+        match self.next(Self::record_prompt("embed", model, &prompt, 0))? {
+            MockResponse::Embeddings(vectors) => Ok(vectors),
+            other => Err(anyhow!("Mock scripted {:?}, embeddings expected", other)),
+        }
+    }
+}
+
+/// Build an `LlmClients` set from mocks (no concurrency limiting)
+pub fn mock_clients(
+    vla: Arc<MockLlmClient>,
+    arbiter: Arc<MockLlmClient>,
+    response: Arc<MockLlmClient>,
+) -> LlmClients {
+    LlmClients {
+        vla: vla as SharedLlm,
+        vla_model: "mock-vla".to_string(),
+        arbiter: arbiter as SharedLlm,
+        arbiter_model: "mock-arbiter".to_string(),
+        response: response as SharedLlm,
+        response_model: "mock-response".to_string(),
+        audit: None,
+        screen: None,
+        embedding: None,
+        limiters: Vec::new(),
+        capabilities: HashMap::new(),
+    }
+}
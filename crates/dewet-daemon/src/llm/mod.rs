@@ -1,17 +1,45 @@
+mod capabilities;
+mod health;
+mod images;
+mod limiter;
+#[cfg(feature = "llama-cpp")]
+mod llamacpp;
 mod lmstudio;
+mod middleware;
+pub mod mock;
 mod openrouter;
+mod reasoning;
+mod schema;
+mod streaming;
+mod usage;
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-
+use tokio::sync::mpsc::UnboundedSender;
+
+pub use capabilities::{CapabilityClient, drop_images};
+pub use health::{ModelHealth, warm_up};
+pub use images::{ImageLimitMiddleware, ImageLimits, encode_image, fit_image, image_url};
+pub use limiter::{LimitedClient, LimiterStats, ProviderLimiter};
+#[cfg(feature = "llama-cpp")]
+pub use llamacpp::LlamaCppClient;
 pub use lmstudio::LmStudioClient;
+pub use middleware::{
+    LlmCall, LlmMiddleware, LlmRequest, LlmResponse, MiddlewareClient, RedactionMiddleware,
+    SharedMiddleware,
+};
+pub use mock::{MockLlmClient, MockResponse};
 pub use openrouter::OpenRouterClient;
+pub use reasoning::{split_reasoning, track_reasoning};
+pub use schema::{RepairingClient, validate as validate_json};
+pub use streaming::ToolCallAccumulator;
+pub use usage::{TokenUsage, track_usage};
 
-use crate::config::{LlmConfig, LlmProvider, ModelConfig};
+use crate::config::{LlmConfig, LlmProvider, ModelCapabilities, ModelConfig, ReasoningEffort};
 
 pub type SharedLlm = Arc<dyn LlmClient>;
 
@@ -80,11 +108,33 @@ pub struct ChatCompletionWithTools {
     pub tool_calls: Vec<ToolCall>,
 }
 
+/// Per-role generation limits added to every chat completion request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GenerationOptions {
+    pub max_tokens: Option<u32>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl GenerationOptions {
+    pub fn from_model(config: &ModelConfig) -> Self {
+        Self {
+            max_tokens: config.max_tokens,
+            reasoning_effort: config.reasoning_effort,
+        }
+    }
+}
+
 /// A single message in a chat conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: ChatContent,
+    /// Tools an assistant message called
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a tool message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 /// The role of a message sender
@@ -94,6 +144,8 @@ pub enum ChatRole {
     System,
     User,
     Assistant,
+    /// The result of a tool call
+    Tool,
 }
 
 /// Content of a chat message - either plain text or multimodal
@@ -118,24 +170,41 @@ pub struct ImageUrl {
 }
 
 impl ChatMessage {
-    pub fn system(content: impl Into<String>) -> Self {
+    fn text(role: ChatRole, content: impl Into<String>) -> Self {
         Self {
-            role: ChatRole::System,
+            role,
             content: ChatContent::Text(content.into()),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
         }
     }
 
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::text(ChatRole::System, content)
+    }
+
     pub fn user(content: impl Into<String>) -> Self {
+        Self::text(ChatRole::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::text(ChatRole::Assistant, content)
+    }
+
+    /// An assistant turn that called `tool_calls`, to be followed by their
+    /// results
+    pub fn assistant_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
         Self {
-            role: ChatRole::User,
-            content: ChatContent::Text(content.into()),
+            tool_calls,
+            ..Self::assistant(content)
         }
     }
 
-    pub fn assistant(content: impl Into<String>) -> Self {
+    /// The result of the tool call `tool_call_id`
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
         Self {
-            role: ChatRole::Assistant,
-            content: ChatContent::Text(content.into()),
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::text(ChatRole::Tool, content)
         }
     }
 
@@ -144,15 +213,15 @@ impl ChatMessage {
             .into_iter()
             .map(|img| ContentPart::ImageUrl {
                 image_url: ImageUrl {
-                    url: format!("data:image/png;base64,{}", img),
+                    url: image_url(&img),
                 },
             })
             .collect();
         parts.push(ContentPart::Text { text: text.into() });
 
         Self {
-            role: ChatRole::User,
             content: ChatContent::Multimodal(parts),
+            ..Self::user("")
         }
     }
 }
@@ -164,6 +233,8 @@ pub fn strip_images_for_logging(messages: &[ChatMessage]) -> Vec<ChatMessage> {
         .iter()
         .map(|msg| ChatMessage {
             role: msg.role,
+            tool_calls: msg.tool_calls.clone(),
+            tool_call_id: msg.tool_call_id.clone(),
             content: match &msg.content {
                 ChatContent::Text(s) => ChatContent::Text(s.clone()),
                 ChatContent::Multimodal(parts) => {
@@ -234,6 +305,108 @@ pub trait LlmClient: Send + Sync {
         messages: Vec<ChatMessage>,
         tools: Vec<ToolDefinition>,
     ) -> Result<ChatCompletionWithTools>;
+
+    /// Streaming variant of `complete_vision_with_tools`: each tool call is sent
+    /// to `tool_calls` as soon as its arguments object is complete, while the
+    /// rest of the reply is still generating, and the answer text goes to
+    /// `text` in deltas as it is generated (without inline reasoning). The
+    /// returned completion contains every call and the full text, including
+    /// what was already sent.
+    ///
+    /// The default implementation waits for the full completion.
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let completion = self
+            .complete_vision_with_tools(model, messages, tools)
+            .await?;
+        for call in &completion.tool_calls {
+            let _ = tool_calls.send(call.clone());
+        }
+        if let Some(content) = &completion.content {
+            let _ = text.send(content.clone());
+        }
+        Ok(completion)
+    }
+
+    /// Compute embedding vectors for a batch of texts.
+    /// Returns one vector per input, in the same order.
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Parse an OpenAI-compatible `/v1/embeddings` response for `expected`
+/// inputs, ordered by input index. Errors unless there is exactly one vector
+/// per input, so vectors are never matched to the wrong text.
+fn extract_embeddings(resp: &Value, expected: usize) -> Result<Vec<Vec<f32>>> {
+    let data = resp
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| anyhow::anyhow!("embedding data missing"))?;
+
+    let mut indexed = data
+        .iter()
+        .enumerate()
+        .map(|(position, item)| {
+            let index = item
+                .get("index")
+                .and_then(|i| i.as_u64())
+                .map(|i| i as usize)
+                .unwrap_or(position);
+            let vector = item
+                .get("embedding")
+                .and_then(|e| e.as_array())
+                .ok_or_else(|| anyhow::anyhow!("embedding vector missing"))?
+                .iter()
+                .map(|v| v.as_f64().map(|f| f as f32))
+                .collect::<Option<Vec<f32>>>()
+                .ok_or_else(|| anyhow::anyhow!("embedding contains non-numeric values"))?;
+            Ok((index, vector))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if indexed.len() != expected {
+        return Err(anyhow::anyhow!(
+            "embedding response has {} vectors for {} inputs",
+            indexed.len(),
+            expected
+        ));
+    }
+
+    indexed.sort_by_key(|(index, _)| *index);
+    let misplaced = indexed
+        .iter()
+        .enumerate()
+        .any(|(i, (index, _))| i != *index);
+    if misplaced {
+        return Err(anyhow::anyhow!("embedding indices don't match the inputs"));
+    }
+    Ok(indexed.into_iter().map(|(_, vector)| vector).collect())
+}
+
+/// Models without structured output tend to wrap JSON in prose or code fences
+fn extract_json(text: &str) -> Result<Value> {
+    if let Ok(value) = serde_json::from_str(text) {
+        return Ok(value);
+    }
+    let start = text
+        .find('{')
+        .ok_or_else(|| anyhow::anyhow!("No JSON object in model response"))?;
+    let end = text
+        .rfind('}')
+        .ok_or_else(|| anyhow::anyhow!("No JSON object in model response"))?;
+    Ok(serde_json::from_str(&text[start..=end])?)
+}
+
+/// Prompt suffix asking for JSON when the model can't enforce a schema
+fn json_instructions(prompt: &str, schema: &Value) -> String {
+    format!(
+        "{prompt}\n\nRespond ONLY with a JSON object matching this schema:\n{schema}",
+        schema = serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
 }
 
 /// Collection of LLM clients for different roles
@@ -250,45 +423,201 @@ pub struct LlmClients {
     pub response_model: String,
     /// Optional client for Audit - response review
     pub audit: Option<(SharedLlm, String)>,
+    /// Optional client for Screening - cheap check before the VLA and arbiter
+    pub screen: Option<(SharedLlm, String)>,
+    /// Optional client for Embeddings - semantic memory
+    pub embedding: Option<(SharedLlm, String)>,
+    /// Shared per-provider concurrency limiters (one per distinct server)
+    pub limiters: Vec<Arc<ProviderLimiter>>,
+    /// Capability registry, keyed by model name
+    pub capabilities: HashMap<String, ModelCapabilities>,
 }
 
 impl LlmClients {
-    pub fn from_config(config: &LlmConfig) -> Self {
-        Self {
-            vla: create_client_from_provider(&config.vla.provider),
+    /// Fails if a role uses a provider this build doesn't include
+    pub fn from_config(config: &LlmConfig) -> Result<Self> {
+        let mut registry = limiter::LimiterRegistry::new(config.max_concurrent_requests);
+        let mut middleware: Vec<SharedMiddleware> = Vec::new();
+        if !config.redact_patterns.is_empty() {
+            middleware.push(Arc::new(RedactionMiddleware::new(&config.redact_patterns)));
+        }
+        let mut capabilities = HashMap::new();
+        let mut limited = |model: &ModelConfig| -> Result<SharedLlm> {
+            let caps = model.capabilities();
+            capabilities.insert(model.model.clone(), caps);
+            let mut client: SharedLlm = Arc::new(LimitedClient::new(
+                create_client(model)?,
+                registry.for_provider(&model.provider),
+            ));
+            if caps != ModelCapabilities::default() {
+                client = Arc::new(CapabilityClient::new(client, caps));
+            }
+            if config.json_repair_attempts > 0 {
+                client = Arc::new(RepairingClient::new(client, config.json_repair_attempts));
+            }
+            let mut model_middleware = middleware.clone();
+            let image_limits = ImageLimits {
+                max_dimension: model.max_image_dimension,
+                max_bytes: model.max_image_bytes,
+            };
+            if caps.vision && !image_limits.is_unlimited() {
+                model_middleware.push(Arc::new(ImageLimitMiddleware::new(image_limits)));
+            }
+            Ok(if model_middleware.is_empty() {
+                client
+            } else {
+                Arc::new(MiddlewareClient::new(client, model_middleware))
+            })
+        };
+
+        let vla = limited(&config.vla)?;
+        let arbiter = limited(&config.arbiter)?;
+        let response = limited(&config.response)?;
+        let audit = config
+            .audit
+            .as_ref()
+            .map(|a| -> Result<_> { Ok((limited(a)?, a.model.clone())) })
+            .transpose()?;
+        let screen = config
+            .screen
+            .as_ref()
+            .map(|s| -> Result<_> { Ok((limited(s)?, s.model.clone())) })
+            .transpose()?;
+        let embedding = config
+            .embedding
+            .as_ref()
+            .map(|e| -> Result<_> { Ok((limited(e)?, e.model.clone())) })
+            .transpose()?;
+
+        Ok(Self {
+            vla,
             vla_model: config.vla.model.clone(),
-            arbiter: create_client_from_provider(&config.arbiter.provider),
+            arbiter,
             arbiter_model: config.arbiter.model.clone(),
-            response: create_client_from_provider(&config.response.provider),
+            response,
             response_model: config.response.model.clone(),
-            audit: config.audit.as_ref().map(|a| {
-                (create_client_from_provider(&a.provider), a.model.clone())
-            }),
-        }
+            audit,
+            screen,
+            embedding,
+            limiters: registry.into_limiters(),
+            capabilities,
+        })
+    }
+
+    /// Capabilities of a configured model (unknown models are assumed capable)
+    pub fn capabilities(&self, model: &str) -> ModelCapabilities {
+        self.capabilities.get(model).copied().unwrap_or_default()
+    }
+
+    /// Wrap every role's client in `middleware` (outermost, so it runs before
+    /// any middleware already installed)
+    pub fn with_middleware(mut self, middleware: SharedMiddleware) -> Self {
+        let wrap = |client: SharedLlm| -> SharedLlm {
+            Arc::new(MiddlewareClient::new(client, vec![middleware.clone()]))
+        };
+        self.vla = wrap(self.vla);
+        self.arbiter = wrap(self.arbiter);
+        self.response = wrap(self.response);
+        self.audit = self.audit.map(|(client, model)| (wrap(client), model));
+        self.screen = self.screen.map(|(client, model)| (wrap(client), model));
+        self.embedding = self.embedding.map(|(client, model)| (wrap(client), model));
+        self
+    }
+
+    /// Current queue depth and throughput for each provider
+    pub fn limiter_stats(&self) -> Vec<LimiterStats> {
+        self.limiters.iter().map(|l| l.stats()).collect()
     }
 }
 
 /// Create a client from a provider configuration
-pub fn create_client_from_provider(provider: &LlmProvider) -> SharedLlm {
-    match provider {
-        LlmProvider::LmStudio { endpoint } => Arc::new(LmStudioClient::new(endpoint)),
+pub fn create_client_from_provider(provider: &LlmProvider) -> Result<SharedLlm> {
+    build_client(provider, GenerationOptions::default())
+}
+
+/// Create a client from a model configuration, including its generation limits
+pub fn create_client(config: &ModelConfig) -> Result<SharedLlm> {
+    build_client(&config.provider, GenerationOptions::from_model(config))
+}
+
+fn build_client(provider: &LlmProvider, generation: GenerationOptions) -> Result<SharedLlm> {
+    Ok(match provider {
+        LlmProvider::LmStudio { endpoint } => {
+            Arc::new(LmStudioClient::new(endpoint).with_generation(generation))
+        }
         LlmProvider::OpenRouter {
             site_url,
             site_name,
+            routing,
             ..
         } => {
-            let api_key = provider.openrouter_api_key()
-                .expect("OpenRouter requires api_key or api_key_env to be set");
-            Arc::new(OpenRouterClient::new(
-                &api_key,
-                site_url.clone(),
-                site_name.clone(),
-            ))
+            let api_key = provider.openrouter_api_key().ok_or_else(|| {
+                anyhow::anyhow!("OpenRouter requires api_key or api_key_env to be set")
+            })?;
+            Arc::new(
+                OpenRouterClient::new(&api_key, site_url.clone(), site_name.clone(), routing.clone())
+                    .with_generation(generation),
+            )
         }
-    }
+        #[cfg(feature = "llama-cpp")]
+        LlmProvider::LlamaCpp {
+            model_path,
+            context_size,
+            gpu_layers,
+            max_tokens,
+        } => Arc::new(LlamaCppClient::new(
+            model_path,
+            *context_size,
+            *gpu_layers,
+            generation.max_tokens.or(*max_tokens),
+        )),
+        #[cfg(not(feature = "llama-cpp"))]
+        LlmProvider::LlamaCpp { .. } => {
+            anyhow::bail!("llamacpp provider: the `llama-cpp` feature isn't enabled in this build")
+        }
+    })
 }
 
-/// Create a client from a model configuration (convenience wrapper)
-pub fn create_client(config: &ModelConfig) -> SharedLlm {
-    create_client_from_provider(&config.provider)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_embeddings_orders_by_index() {
+        let resp = json!({
+            "data": [
+                { "index": 1, "embedding": [0.0, 1.0] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ]
+        });
+        let expected: Vec<Vec<f32>> = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        assert_eq!(extract_embeddings(&resp, 2).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_extract_embeddings_rejects_missing_vectors() {
+        let resp = json!({
+            "data": [
+                { "index": 0, "embedding": [1.0, 0.0] },
+                { "index": 2, "embedding": [0.0, 1.0] }
+            ]
+        });
+        assert!(extract_embeddings(&resp, 3).is_err());
+        // The right count, but input 1 has no vector
+        assert!(extract_embeddings(&resp, 2).is_err());
+    }
+
+    #[cfg(not(feature = "llama-cpp"))]
+    #[test]
+    fn test_llamacpp_without_feature_is_an_error() {
+        let provider = LlmProvider::LlamaCpp {
+            model_path: "model.gguf".into(),
+            context_size: None,
+            gpu_layers: None,
+            max_tokens: None,
+        };
+        let err = create_client_from_provider(&provider).err().unwrap();
+        assert!(err.to_string().contains("`llama-cpp` feature"));
+    }
 }
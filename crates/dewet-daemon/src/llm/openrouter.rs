@@ -1,16 +1,30 @@
 use anyhow::{Result, anyhow};
 use reqwest::{Client, header::HeaderMap};
 use serde_json::{Value, json};
+use tokio::sync::mpsc::UnboundedSender;
 
-use super::{ChatCompletionWithTools, ChatMessage, FunctionCall, LlmClient, ToolCall, ToolDefinition};
+use super::{
+    ChatCompletionWithTools, ChatMessage, FunctionCall, GenerationOptions, LlmClient, ToolCall,
+    ToolDefinition, extract_embeddings, image_url, middleware::request_headers,
+    reasoning::{report_response_reasoning, strip_reasoning},
+    streaming::read_tool_stream, usage::report_response_usage,
+};
+use crate::config::OpenRouterRouting;
 
 pub struct OpenRouterClient {
     http: Client,
     headers: HeaderMap,
+    routing: Option<Value>,
+    generation: GenerationOptions,
 }
 
 impl OpenRouterClient {
-    pub fn new(api_key: &str, site_url: Option<String>, site_name: Option<String>) -> Self {
+    pub fn new(
+        api_key: &str,
+        site_url: Option<String>,
+        site_name: Option<String>,
+        routing: Option<OpenRouterRouting>,
+    ) -> Self {
         let mut headers = HeaderMap::new();
         headers.insert(
             "Authorization",
@@ -27,26 +41,74 @@ impl OpenRouterClient {
         Self {
             http: Client::new(),
             headers,
+            routing: routing.and_then(|r| serde_json::to_value(r).ok()),
+            generation: GenerationOptions::default(),
         }
     }
 
+    pub fn with_generation(mut self, generation: GenerationOptions) -> Self {
+        self.generation = generation;
+        self
+    }
+
     fn url(&self) -> &str {
         "https://openrouter.ai/api/v1/chat/completions"
     }
 
+    fn embeddings_url(&self) -> &str {
+        "https://openrouter.ai/api/v1/embeddings"
+    }
+
+    /// Attach provider routing preferences and per-role token/reasoning limits
+    /// to a chat completion request
+    fn with_request_options(&self, mut payload: Value) -> Value {
+        if let Some(body) = payload.as_object_mut() {
+            if let Some(routing) = &self.routing {
+                body.insert("provider".to_string(), routing.clone());
+            }
+            if let Some(max_tokens) = self.generation.max_tokens {
+                body.insert("max_tokens".to_string(), json!(max_tokens));
+            }
+            if let Some(effort) = self.generation.reasoning_effort {
+                body.insert("reasoning".to_string(), json!({ "effort": effort }));
+            }
+        }
+        payload
+    }
+
     async fn send(&self, payload: Value) -> Result<Value> {
+        self.send_to(self.url(), self.with_request_options(payload)).await
+    }
+
+    async fn send_to(&self, url: &str, payload: Value) -> Result<Value> {
         let resp = self
             .http
-            .post(self.url())
+            .post(url)
             .headers(self.headers.clone())
+            .headers(request_headers())
             .json(&payload)
             .send()
             .await?
             .error_for_status()?
             .json::<Value>()
             .await?;
+        report_response_usage(&resp);
+        report_response_reasoning(&resp);
         Ok(resp)
     }
+
+    /// POST a `stream: true` request and return the response for SSE reading
+    async fn send_streaming(&self, payload: Value) -> Result<reqwest::Response> {
+        Ok(self
+            .http
+            .post(self.url())
+            .headers(self.headers.clone())
+            .headers(request_headers())
+            .json(&self.with_request_options(payload))
+            .send()
+            .await?
+            .error_for_status()?)
+    }
 }
 
 #[async_trait::async_trait]
@@ -99,7 +161,7 @@ impl LlmClient for OpenRouterClient {
                 json!({
                     "type": "image_url",
                     "image_url": {
-                        "url": format!("data:image/png;base64,{}", img)
+                        "url": image_url(&img)
                     }
                 })
             })
@@ -132,7 +194,7 @@ impl LlmClient for OpenRouterClient {
                 json!({
                     "type": "image_url",
                     "image_url": {
-                        "url": format!("data:image/png;base64,{}", img)
+                        "url": image_url(&img)
                     }
                 })
             })
@@ -252,6 +314,47 @@ impl LlmClient for OpenRouterClient {
         let resp = self.send(body).await?;
         extract_with_tools(&resp)
     }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        let messages_json: Vec<Value> = messages
+            .into_iter()
+            .map(|msg| serde_json::to_value(msg).unwrap())
+            .collect();
+
+        let tools_json: Vec<Value> = tools
+            .into_iter()
+            .map(|t| serde_json::to_value(t).unwrap())
+            .collect();
+
+        let body = json!({
+            "model": model,
+            "messages": messages_json,
+            "tools": tools_json,
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        });
+
+        let resp = self.send_streaming(body).await?;
+        read_tool_stream(resp, &tool_calls, &text).await
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let count = texts.len();
+        let body = json!({
+            "model": model,
+            "input": texts
+        });
+
+        let resp = self.send_to(self.embeddings_url(), body).await?;
+        extract_embeddings(&resp, count)
+    }
 }
 
 fn extract_text(resp: &Value) -> Result<String> {
@@ -263,7 +366,7 @@ fn extract_text(resp: &Value) -> Result<String> {
         .get("message")
         .ok_or_else(|| anyhow!("message missing"))?;
     if let Some(text) = message.get("content").and_then(|v| v.as_str()) {
-        return Ok(text.to_string());
+        return Ok(strip_reasoning(text.to_string()));
     }
     if let Some(items) = message.get("content").and_then(|v| v.as_array()) {
         let mut combined = String::new();
@@ -275,7 +378,7 @@ fn extract_text(resp: &Value) -> Result<String> {
             }
         }
         if !combined.is_empty() {
-            return Ok(combined);
+            return Ok(strip_reasoning(combined));
         }
     }
     Err(anyhow!("Unable to extract text from OpenRouter response"))
@@ -335,5 +438,7 @@ fn extract_with_tools(resp: &Value) -> Result<ChatCompletionWithTools> {
         Vec::new()
     };
 
+    let content = content.map(strip_reasoning).filter(|c| !c.is_empty());
+
     Ok(ChatCompletionWithTools { content, tool_calls })
 }
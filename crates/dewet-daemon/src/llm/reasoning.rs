@@ -0,0 +1,151 @@
+//! Reasoning-model support
+//!
+//! Reasoning models either inline their chain of thought as `<think>...</think>`
+//! or return it separately (`reasoning_content` / `reasoning`). Providers strip
+//! it from the answer so it never reaches chat or TTS, and report it into a
+//! task-local slot so callers can log it with `track_reasoning`.
+
+use std::{future::Future, sync::Arc};
+
+use parking_lot::Mutex;
+use serde_json::Value;
+
+const OPEN: &str = "<think>";
+const CLOSE: &str = "</think>";
+
+tokio::task_local! {
+    static REASONING: Arc<Mutex<Option<String>>>;
+}
+
+/// Run `future`, collecting any reasoning reported by providers while it runs
+pub async fn track_reasoning<F: Future>(future: F) -> (F::Output, Option<String>) {
+    let slot = Arc::new(Mutex::new(None));
+    let output = REASONING.scope(slot.clone(), future).await;
+    let reasoning = slot.lock().take();
+    (output, reasoning)
+}
+
+/// Append to the reasoning of the enclosing `track_reasoning` call, if any
+pub(crate) fn report_reasoning(text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    let _ = REASONING.try_with(|slot| {
+        let mut slot = slot.lock();
+        match slot.as_mut() {
+            Some(existing) => {
+                existing.push_str("\n\n");
+                existing.push_str(text);
+            }
+            None => *slot = Some(text.to_string()),
+        }
+    });
+}
+
+/// Report a separate reasoning field of an OpenAI-compatible response
+pub(crate) fn report_response_reasoning(resp: &Value) {
+    let Some(message) = resp
+        .get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("message"))
+    else {
+        return;
+    };
+    if let Some(text) = reasoning_field(message) {
+        report_reasoning(text);
+    }
+}
+
+/// `reasoning_content` (LM Studio, DeepSeek) or `reasoning` (OpenRouter)
+pub(crate) fn reasoning_field(message: &Value) -> Option<&str> {
+    message
+        .get("reasoning_content")
+        .or_else(|| message.get("reasoning"))
+        .and_then(|r| r.as_str())
+}
+
+/// Split inline `<think>` reasoning from the answer. Handles a missing opening
+/// tag (some chat templates put it in the prompt) and an unterminated block
+/// (generation cut off mid-thought, so there is no answer).
+pub fn split_reasoning(text: &str) -> (String, Option<String>) {
+    if let Some(end) = text.rfind(CLOSE) {
+        let thought = text[..end].trim_start();
+        let thought = thought.strip_prefix(OPEN).unwrap_or(thought);
+        let answer = text[end + CLOSE.len()..].trim();
+        return (answer.to_string(), Some(thought.trim().to_string()));
+    }
+    match text.trim_start().strip_prefix(OPEN) {
+        Some(thought) => (String::new(), Some(thought.trim().to_string())),
+        None => (text.to_string(), None),
+    }
+}
+
+/// The answer so far in partially streamed output. Empty while a `<think>`
+/// block, or what may turn out to be its opening tag, is still open.
+pub(crate) fn streamed_answer(text: &str) -> &str {
+    if let Some(end) = text.rfind(CLOSE) {
+        return text[end + CLOSE.len()..].trim_start();
+    }
+    let text = text.trim_start();
+    if text.starts_with(OPEN) || OPEN.starts_with(text) {
+        return "";
+    }
+    text
+}
+
+/// Strip inline reasoning from model output, reporting what was removed
+pub(crate) fn strip_reasoning(text: String) -> String {
+    if !text.contains(OPEN) && !text.contains(CLOSE) {
+        return text;
+    }
+    let (answer, thought) = split_reasoning(&text);
+    if let Some(thought) = thought {
+        report_reasoning(&thought);
+    }
+    answer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_split_reasoning() {
+        assert_eq!(
+            split_reasoning("<think>\nUser said hi.\n</think>\n\nHello!"),
+            ("Hello!".to_string(), Some("User said hi.".to_string()))
+        );
+        assert_eq!(
+            split_reasoning("Opening tag was in the prompt</think>Hi"),
+            ("Hi".to_string(), Some("Opening tag was in the prompt".to_string()))
+        );
+        assert_eq!(
+            split_reasoning("<think>Still thinking when cut off"),
+            (String::new(), Some("Still thinking when cut off".to_string()))
+        );
+        assert_eq!(split_reasoning("Plain answer"), ("Plain answer".to_string(), None));
+    }
+
+    #[test]
+    fn test_streamed_answer() {
+        assert_eq!(streamed_answer("<thi"), "");
+        assert_eq!(streamed_answer("<think>Hmm, the user"), "");
+        assert_eq!(streamed_answer("<think>Hmm</think>\nHel"), "Hel");
+        assert_eq!(streamed_answer("Hello th"), "Hello th");
+    }
+
+    #[tokio::test]
+    async fn test_track_reasoning_collects_reports() {
+        let (answer, reasoning) = track_reasoning(async {
+            report_response_reasoning(&json!({
+                "choices": [{ "message": { "content": "{}", "reasoning_content": "Plan A" } }]
+            }));
+            strip_reasoning("<think>Plan B</think>ok".to_string())
+        })
+        .await;
+        assert_eq!(answer, "ok");
+        assert_eq!(reasoning.as_deref(), Some("Plan A\n\nPlan B"));
+    }
+}
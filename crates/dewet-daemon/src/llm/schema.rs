@@ -0,0 +1,298 @@
+//! Local JSON-schema validation and a repair loop for structured completions
+//!
+//! Only the subset of JSON Schema the daemon's own schemas use is checked:
+//! `type`, `properties`, `required`, `enum`, `items`, `minimum`/`maximum` and
+//! `additionalProperties: false`.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use super::{ChatCompletionWithTools, ChatMessage, LlmClient, SharedLlm, ToolCall, ToolDefinition};
+
+/// Validate `value` against `schema`, returning every violation found
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at("$", value, schema, &mut errors);
+    errors
+}
+
+fn type_matches(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn validate_at(path: &str, value: &Value, schema: &Value, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let allowed: Vec<&str> = match ty {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| type_matches(value, t)) {
+            errors.push(format!("{}: expected {}, got {}", path, allowed.join(" or "), value));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            errors.push(format!("{}: {} is not one of {}", path, value, Value::from(options.clone())));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+            if number < min {
+                errors.push(format!("{}: {} is below minimum {}", path, number, min));
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+            if number > max {
+                errors.push(format!("{}: {} is above maximum {}", path, number, max));
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{}: missing required property \"{}\"", path, key));
+                }
+            }
+        }
+        let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+        for (key, child) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => {
+                    validate_at(&format!("{}.{}", path, key), child, child_schema, errors)
+                }
+                None if closed => errors.push(format!("{}: unexpected property \"{}\"", path, key)),
+                None => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(item_schema)) = (value.as_array(), schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate_at(&format!("{}[{}]", path, index), item, item_schema, errors);
+        }
+    }
+}
+
+fn repair_prompt(prompt: &str, schema: &Value, problem: &str) -> String {
+    format!(
+        "{prompt}\n\n---\nYour previous answer was not valid: {problem}\n\
+        Reply again with ONLY a JSON object that matches this schema exactly:\n{schema}",
+        schema = serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
+}
+
+/// Validates JSON completions and re-asks the model (up to `max_repairs` times)
+/// when the response is malformed or doesn't match the schema. Other errors
+/// (connection failures, HTTP errors) are returned immediately.
+pub struct RepairingClient {
+    inner: SharedLlm,
+    max_repairs: u32,
+}
+
+impl RepairingClient {
+    pub fn new(inner: SharedLlm, max_repairs: u32) -> Self {
+        Self { inner, max_repairs }
+    }
+
+    async fn with_repairs(
+        &self,
+        model: &str,
+        prompt: &str,
+        schema: Value,
+        first: Result<Value>,
+    ) -> Result<Value> {
+        let mut result = first;
+        let mut attempt = 0;
+        loop {
+            let problem = match &result {
+                Ok(value) => {
+                    let errors = validate(value, &schema);
+                    if errors.is_empty() {
+                        return result;
+                    }
+                    format!("{} (you sent: {})", errors.join("; "), value)
+                }
+                // Only malformed JSON is worth a repair; transport errors are not
+                Err(err) if err.downcast_ref::<serde_json::Error>().is_some() => err.to_string(),
+                Err(_) => return result,
+            };
+
+            if attempt >= self.max_repairs {
+                return Err(anyhow!(
+                    "JSON completion invalid after {} repair attempt(s): {}",
+                    attempt,
+                    problem
+                ));
+            }
+            attempt += 1;
+            warn!(model, attempt, %problem, "Invalid JSON completion, asking model to repair");
+
+            result = self
+                .inner
+                .complete_json(model, &repair_prompt(prompt, &schema, &problem), schema.clone())
+                .await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for RepairingClient {
+    async fn complete_text(&self, model: &str, prompt: &str) -> Result<String> {
+        self.inner.complete_text(model, prompt).await
+    }
+
+    async fn complete_json(&self, model: &str, prompt: &str, schema: Value) -> Result<Value> {
+        let first = self.inner.complete_json(model, prompt, schema.clone()).await;
+        self.with_repairs(model, prompt, schema, first).await
+    }
+
+    async fn complete_vision_text(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+    ) -> Result<String> {
+        self.inner
+            .complete_vision_text(model, prompt, images_base64)
+            .await
+    }
+
+    async fn complete_vision_json(
+        &self,
+        model: &str,
+        prompt: &str,
+        images_base64: Vec<String>,
+        schema: Value,
+    ) -> Result<Value> {
+        // Repairs are text-only: the model only needs to fix the format
+        let first = self
+            .inner
+            .complete_vision_json(model, prompt, images_base64, schema.clone())
+            .await;
+        self.with_repairs(model, prompt, schema, first).await
+    }
+
+    async fn complete_chat(&self, model: &str, messages: Vec<ChatMessage>) -> Result<String> {
+        self.inner.complete_chat(model, messages).await
+    }
+
+    async fn complete_vision_chat(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<String> {
+        self.inner.complete_vision_chat(model, messages).await
+    }
+
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        self.inner.complete_with_tools(model, messages, tools).await
+    }
+
+    async fn complete_vision_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+    ) -> Result<ChatCompletionWithTools> {
+        self.inner
+            .complete_vision_with_tools(model, messages, tools)
+            .await
+    }
+
+    async fn stream_with_tools(
+        &self,
+        model: &str,
+        messages: Vec<ChatMessage>,
+        tools: Vec<ToolDefinition>,
+        tool_calls: UnboundedSender<ToolCall>,
+        text: UnboundedSender<String>,
+    ) -> Result<ChatCompletionWithTools> {
+        self.inner
+            .stream_with_tools(model, messages, tools, tool_calls, text)
+            .await
+    }
+
+    async fn embed(&self, model: &str, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed(model, texts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlmClient;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "status": { "type": "string", "enum": ["approve", "block"] },
+                "score": { "type": "number", "minimum": 0.0, "maximum": 1.0 },
+                "who": { "type": ["string", "null"] }
+            },
+            "required": ["status"]
+        })
+    }
+
+    #[test]
+    fn test_validate_reports_violations() {
+        assert!(validate(&json!({"status": "approve", "who": null}), &schema()).is_empty());
+
+        let errors = validate(&json!({"status": "maybe", "score": 2}), &schema());
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("$.score") || errors[1].contains("$.score"));
+
+        let errors = validate(&json!({"score": 0.5}), &schema());
+        assert_eq!(errors, vec![r#"$: missing required property "status""#]);
+    }
+
+    #[tokio::test]
+    async fn test_repair_loop_recovers() {
+        let mock = MockLlmClient::new()
+            .with_text("{\"status\": ")
+            .with_json(json!({"status": "nope"}))
+            .with_json(json!({"status": "approve"}))
+            .shared();
+        let client = RepairingClient::new(mock.clone(), 2);
+
+        let value = client.complete_json("m", "Decide", schema()).await.unwrap();
+        assert_eq!(value, json!({"status": "approve"}));
+        assert_eq!(mock.request_count(), 3);
+        assert!(mock.requests()[2].prompt.contains("is not one of"));
+
+        let mock = MockLlmClient::new()
+            .with_json(json!({}))
+            .with_json(json!({}))
+            .shared();
+        let client = RepairingClient::new(mock, 1);
+        assert!(client.complete_json("m", "Decide", schema()).await.is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Server-sent-event parsing for streamed tool-calling completions
+//!
+//! OpenAI-compatible servers stream tool calls as fragments of the arguments
+//! string. `ToolCallAccumulator` reassembles them and releases each call as
+//! soon as its arguments form a complete JSON object, so callers can act on it
+//! while the model is still generating the rest of the reply. The answer text
+//! is released the same way, a delta at a time, with inline reasoning held
+//! back.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{
+    ChatCompletionWithTools, FunctionCall, ToolCall,
+    reasoning::{reasoning_field, report_reasoning, streamed_answer, strip_reasoning},
+    usage::report_response_usage,
+};
+
+#[derive(Debug, Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    arguments: String,
+    emitted: bool,
+}
+
+impl PartialCall {
+    fn to_tool_call(&self) -> ToolCall {
+        ToolCall {
+            id: self.id.clone(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: self.name.clone(),
+                arguments: self.arguments.clone(),
+            },
+        }
+    }
+
+    fn arguments_complete(&self) -> bool {
+        !self.name.is_empty()
+            && serde_json::from_str::<Value>(&self.arguments).is_ok_and(|v| v.is_object())
+    }
+}
+
+/// Reassembles streamed `choices[0].delta` chunks
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    content: String,
+    reasoning: String,
+    /// Answer text already released by `take_text`
+    shown: String,
+    calls: Vec<PartialCall>,
+}
+
+impl ToolCallAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply one chunk; returns tool calls whose arguments just became complete
+    pub fn push_chunk(&mut self, chunk: &Value) -> Vec<ToolCall> {
+        let Some(delta) = chunk
+            .get("choices")
+            .and_then(|c| c.get(0))
+            .and_then(|c| c.get("delta"))
+        else {
+            return Vec::new();
+        };
+
+        if let Some(text) = delta.get("content").and_then(|c| c.as_str()) {
+            self.content.push_str(text);
+        }
+        if let Some(text) = reasoning_field(delta) {
+            self.reasoning.push_str(text);
+        }
+
+        let Some(fragments) = delta.get("tool_calls").and_then(|t| t.as_array()) else {
+            return Vec::new();
+        };
+
+        let mut touched = Vec::new();
+        for fragment in fragments {
+            let index = fragment
+                .get("index")
+                .and_then(|i| i.as_u64())
+                .map(|i| i as usize)
+                .unwrap_or(self.calls.len().saturating_sub(1));
+            if self.calls.len() <= index {
+                self.calls.resize_with(index + 1, PartialCall::default);
+            }
+            let call = &mut self.calls[index];
+            if let Some(id) = fragment.get("id").and_then(|i| i.as_str()) {
+                call.id = id.to_string();
+            }
+            if let Some(function) = fragment.get("function") {
+                if let Some(name) = function.get("name").and_then(|n| n.as_str()) {
+                    call.name.push_str(name);
+                }
+                if let Some(arguments) = function.get("arguments").and_then(|a| a.as_str()) {
+                    call.arguments.push_str(arguments);
+                }
+            }
+            touched.push(index);
+        }
+
+        touched
+            .into_iter()
+            .filter_map(|index| {
+                let call = &mut self.calls[index];
+                if call.emitted || !call.arguments_complete() {
+                    return None;
+                }
+                call.emitted = true;
+                Some(call.to_tool_call())
+            })
+            .collect()
+    }
+
+    /// Answer text generated since the last call, if any. Stops releasing
+    /// text if the answer is rewritten (a late `</think>` with no opening tag).
+    pub fn take_text(&mut self) -> Option<String> {
+        let answer = streamed_answer(&self.content);
+        let fresh = answer.strip_prefix(self.shown.as_str())?;
+        if fresh.is_empty() {
+            return None;
+        }
+        let fresh = fresh.to_string();
+        self.shown = answer.to_string();
+        Some(fresh)
+    }
+
+    /// Finish the stream. Returns the full completion plus any calls that were
+    /// never released early (e.g. arguments that never parsed).
+    pub fn finish(self) -> (ChatCompletionWithTools, Vec<ToolCall>) {
+        let pending = self
+            .calls
+            .iter()
+            .filter(|c| !c.emitted && !c.name.is_empty())
+            .map(PartialCall::to_tool_call)
+            .collect();
+        report_reasoning(&self.reasoning);
+        let content = strip_reasoning(self.content);
+        let completion = ChatCompletionWithTools {
+            content: if content.is_empty() { None } else { Some(content) },
+            tool_calls: self
+                .calls
+                .iter()
+                .filter(|c| !c.name.is_empty())
+                .map(PartialCall::to_tool_call)
+                .collect(),
+        };
+        (completion, pending)
+    }
+}
+
+/// Splits a byte stream into SSE `data:` payloads
+#[derive(Debug, Default)]
+struct SseBuffer {
+    buffer: String,
+}
+
+impl SseBuffer {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.push_str(&String::from_utf8_lossy(bytes));
+        let mut payloads = Vec::new();
+        while let Some(end) = self.buffer.find('\n') {
+            let line = self.buffer[..end].trim().to_string();
+            self.buffer.drain(..=end);
+            if let Some(data) = line.strip_prefix("data:") {
+                payloads.push(data.trim().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+/// Read a streamed tool-calling response, sending each tool call to `sink` as
+/// soon as it is complete and the answer text to `text` as it arrives
+pub(crate) async fn read_tool_stream(
+    mut resp: reqwest::Response,
+    sink: &UnboundedSender<ToolCall>,
+    text: &UnboundedSender<String>,
+) -> Result<ChatCompletionWithTools> {
+    let mut sse = SseBuffer::default();
+    let mut accumulator = ToolCallAccumulator::new();
+
+    'stream: while let Some(bytes) = resp.chunk().await? {
+        for payload in sse.push(&bytes) {
+            if payload == "[DONE]" {
+                break 'stream;
+            }
+            let chunk: Value = serde_json::from_str(&payload)
+                .map_err(|e| anyhow!("Invalid stream chunk: {} ({})", e, payload))?;
+            if let Some(error) = chunk.get("error") {
+                return Err(anyhow!("Stream error: {}", error));
+            }
+            report_response_usage(&chunk);
+            for call in accumulator.push_chunk(&chunk) {
+                let _ = sink.send(call);
+            }
+            if let Some(delta) = accumulator.take_text() {
+                let _ = text.send(delta);
+            }
+        }
+    }
+
+    let (completion, pending) = accumulator.finish();
+    for call in pending {
+        let _ = sink.send(call);
+    }
+    Ok(completion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn delta(delta: Value) -> Value {
+        json!({ "choices": [{ "index": 0, "delta": delta }] })
+    }
+
+    #[test]
+    fn test_tool_call_released_when_arguments_close() {
+        let mut acc = ToolCallAccumulator::new();
+        assert!(acc
+            .push_chunk(&delta(json!({ "tool_calls": [{
+                "index": 0, "id": "call_1",
+                "function": { "name": "notes_append", "arguments": "{\"text\": \"he" }
+            }] })))
+            .is_empty());
+
+        let released = acc.push_chunk(&delta(json!({ "tool_calls": [{
+            "index": 0, "function": { "arguments": "llo\"}" }
+        }] })));
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].function.name, "notes_append");
+        assert_eq!(released[0].function.arguments, r#"{"text": "hello"}"#);
+
+        acc.push_chunk(&delta(json!({ "content": "Done!" })));
+        let (completion, pending) = acc.finish();
+        assert!(pending.is_empty());
+        assert_eq!(completion.content.as_deref(), Some("Done!"));
+        assert_eq!(completion.tool_calls.len(), 1);
+    }
+
+    #[test]
+    fn test_text_released_without_reasoning() {
+        let mut acc = ToolCallAccumulator::new();
+        acc.push_chunk(&delta(json!({ "content": "<think>Greet" })));
+        assert_eq!(acc.take_text(), None);
+        acc.push_chunk(&delta(json!({ "content": "</think>\nHi " })));
+        assert_eq!(acc.take_text().as_deref(), Some("Hi "));
+        acc.push_chunk(&delta(json!({ "content": "there!" })));
+        assert_eq!(acc.take_text().as_deref(), Some("there!"));
+        assert_eq!(acc.take_text(), None);
+    }
+
+    #[test]
+    fn test_sse_buffer_handles_split_lines() {
+        let mut sse = SseBuffer::default();
+        assert!(sse.push(b"data: {\"a\"").is_empty());
+        assert_eq!(sse.push(b": 1}\n\ndata: [DONE]\n"), vec!["{\"a\": 1}", "[DONE]"]);
+    }
+}
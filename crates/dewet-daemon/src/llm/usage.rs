@@ -0,0 +1,82 @@
+//! Token usage reporting for LLM calls
+//!
+//! `LlmClient` methods only return model output, so providers report the
+//! `usage` block of each response into a task-local slot. Callers that want the
+//! counts wrap the request in `track_usage`.
+
+use std::{future::Future, sync::Arc};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Tokens consumed by a single logical request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+tokio::task_local! {
+    static USAGE: Arc<Mutex<Option<TokenUsage>>>;
+}
+
+/// Run `future`, collecting any usage reported by providers while it runs.
+/// Returns `None` if the provider doesn't report usage.
+pub async fn track_usage<F: Future>(future: F) -> (F::Output, Option<TokenUsage>) {
+    let slot = Arc::new(Mutex::new(None));
+    let output = USAGE.scope(slot.clone(), future).await;
+    let usage = *slot.lock();
+    (output, usage)
+}
+
+/// Add to the usage of the enclosing `track_usage` call, if any
+pub(crate) fn report_usage(usage: TokenUsage) {
+    let _ = USAGE.try_with(|slot| {
+        // Accumulate: one logical call may make several requests (e.g. retries)
+        let mut slot = slot.lock();
+        let total = slot.get_or_insert_with(TokenUsage::default);
+        total.prompt_tokens += usage.prompt_tokens;
+        total.completion_tokens += usage.completion_tokens;
+    });
+}
+
+/// Report the `usage` block of an OpenAI-compatible response
+pub(crate) fn report_response_usage(resp: &Value) {
+    let Some(usage) = resp.get("usage") else {
+        return;
+    };
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    report_usage(TokenUsage {
+        prompt_tokens: count("prompt_tokens"),
+        completion_tokens: count("completion_tokens"),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_track_usage_collects_reports() {
+        let ((), usage) = track_usage(async {
+            report_response_usage(&json!({ "usage": { "prompt_tokens": 10, "completion_tokens": 4 } }));
+            report_response_usage(&json!({ "usage": { "prompt_tokens": 5, "completion_tokens": 1 } }));
+        })
+        .await;
+        assert_eq!(
+            usage,
+            Some(TokenUsage {
+                prompt_tokens: 15,
+                completion_tokens: 5
+            })
+        );
+
+        let ((), usage) = track_usage(async {}).await;
+        assert_eq!(usage, None);
+
+        // Reporting outside a tracked scope is a no-op
+        report_response_usage(&json!({ "usage": { "prompt_tokens": 1 } }));
+    }
+}
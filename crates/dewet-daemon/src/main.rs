@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use std::io::Cursor;
@@ -8,20 +8,23 @@ use base64::engine::general_purpose::STANDARD as BASE64;
 use chrono::Utc;
 use image::{DynamicImage, ImageBuffer, ImageFormat, Rgba, RgbaImage};
 use serde_json::json;
-use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio::sync::{Mutex, mpsc};
+use tracing::{debug, error, info, warn};
 
 use dewet_daemon::{
     ariaos::{AriaosCommand, NotesAction},
     bridge::{Bridge, BridgeHandle, ChatPacket, ClientMessage, DaemonMessage, MemoryNode, MemoryTier},
     character::{CharacterSpec, LoadedCharacter},
-    config::AppConfig,
-    director::{Decision, Director},
-    llm,
+    clipboard,
+    config::{AppConfig, AudioFormat, MemoryScope, WindowTarget},
+    director::{Decision, Director, ProfileLearner, Reflector},
+    focus::{Focus, FocusDetector},
+    idle, llm,
     observation::ObservationBuffer,
-    storage::{AriaosNotesState, Storage},
-    tts,
-    vision::{CompositeParts, CompositeRenderer, VisionPipeline},
+    scheduler::{self, ScheduledEvent},
+    storage::{AriaosNotesState, Episode, EpisodeKind, ForgetRequest, SnapshotKind, Storage},
+    stt, tts,
+    vision::{CompositeParts, CompositeRenderer, VisionPipeline, draw_label},
 };
 
 #[tokio::main]
@@ -34,23 +37,69 @@ async fn main() -> Result<()> {
     let config = AppConfig::load()?;
     info!("Starting Dewet daemon");
 
-    let storage = Storage::connect(&config.storage).await?;
-    let llm_clients = llm::LlmClients::from_config(&config.llm);
+    let llm_clients = llm::LlmClients::from_config(&config.llm)?;
+    let storage = Storage::connect(&config.storage)
+        .await?
+        .with_embedder(llm_clients.embedding.clone());
+    storage.schedule_backups(&config.storage);
     info!(
         vla_model = %llm_clients.vla_model,
         arbiter_model = %llm_clients.arbiter_model,
         response_model = %llm_clients.response_model,
         "LLM clients initialized"
     );
-    let synth = tts::create_synthesizer(&config.tts);
+    let llm_limiters = llm_clients.limiters.clone();
 
+    let mut bridge = Bridge::bind(config.bridge.clone()).await?;
+    let bridge_handle = bridge.handle();
+
+    // Wait for models to load so the first ticks don't fail with connection errors
+    if config.llm.warm_up {
+        info!("Warming up LLM models");
+        for health in llm::warm_up(&llm_clients, &config.llm).await {
+            if health.ready {
+                info!(role = %health.role, model = %health.model, latency_ms = health.latency_ms, "Model ready");
+            } else {
+                warn!(role = %health.role, model = %health.model, error = ?health.error, "Model not ready");
+            }
+            bridge_handle.broadcast(DaemonMessage::ModelStatus {
+                role: health.role,
+                model: health.model,
+                ready: health.ready,
+                latency_ms: health.latency_ms,
+                error: health.error,
+                timestamp: Utc::now().timestamp(),
+            })?;
+        }
+    }
     let character_specs =
         CharacterSpec::load_dir(Path::new("characters")).unwrap_or_else(|_| CharacterSpec::demo());
-    let characters = character_specs
+    let voice_tunings = character_specs
+        .iter()
+        .map(|spec| (spec.id.clone(), spec.voice_tuning()))
+        .collect();
+    let voices = tts::VoiceMap::from_config(&config.tts, &voice_tunings);
+    let mut characters = character_specs
         .into_iter()
         .map(LoadedCharacter::new)
         .collect::<Vec<_>>();
+    // How each companion gets on with the user carries over restarts
+    for character in &mut characters {
+        if let Some(state) = storage.character_state(&character.spec.id).await? {
+            character.state.relationship_score = state.relationship_score;
+        }
+    }
+
+    // Facts about the user, learned from chat in the background
+    if let Some(profile) = config.storage.profile.clone() {
+        ProfileLearner::new(storage.clone(), &llm_clients, &config.director).spawn(profile);
+    }
 
+    let reflector = config
+        .director
+        .reflection
+        .enabled
+        .then(|| Reflector::new(storage.clone(), &llm_clients, &config.director));
     let mut director = Director::new(
         storage.clone(),
         llm_clients,
@@ -58,10 +107,7 @@ async fn main() -> Result<()> {
         characters,
     );
 
-    let mut bridge = Bridge::bind(config.bridge.clone()).await?;
-    let bridge_handle = bridge.handle();
-
-    let mut vision = VisionPipeline::new(config.vision.clone());
+    let mut vision = VisionPipeline::new(config.vision.clone())?;
     let mut observation_buffer = ObservationBuffer::new(config.observation.clone());
     
     // Hydrate observation buffer with recent chat from database
@@ -70,6 +116,13 @@ async fn main() -> Result<()> {
         observation_buffer.record_chat(packet);
     }
     info!("Loaded {} chat messages from database", observation_buffer.chat_count());
+    // and the screenshots behind the last replies, for the PREV panels
+    for snapshot in storage
+        .recent_snapshots(SnapshotKind::Screenshot, 3)
+        .await?
+    {
+        observation_buffer.restore_approved_screenshot(snapshot.image, snapshot.timestamp);
+    }
     
     let composite_renderer = CompositeRenderer::default();
 
@@ -77,9 +130,107 @@ async fn main() -> Result<()> {
     let ariaos_assets = Arc::new(Mutex::new(AriaosAssets::default()));
     
     // Load ARIAOS notes state from database
-    let initial_notes = storage.load_ariaos_notes().await?.unwrap_or_default();
+    let initial_notes = storage.load_ariaos_notes(None).await?.unwrap_or_default();
     info!("Loaded ARIAOS notes ({} chars)", initial_notes.content.len());
     let notes_state = Arc::new(Mutex::new(initial_notes));
+
+    // Memory decay and pruning, reported in the bridge log
+    if let Some(maintenance) = config.storage.maintenance.clone() {
+        let storage = storage.clone();
+        let bridge = bridge_handle.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(maintenance.interval()).await;
+                match storage.run_maintenance(&maintenance).await {
+                    Ok(report) => {
+                        info!(%report, "Memory maintenance finished");
+                        log_event(&bridge, "info", format!("Memory maintenance: {report}"));
+                    }
+                    Err(err) => {
+                        error!(?err, "Memory maintenance failed");
+                        let message = format!("Memory maintenance failed: {err:#}");
+                        log_event(&bridge, "error", message);
+                    }
+                }
+            }
+        });
+    }
+
+    // Tool calls completed mid-stream are executed here, while the reply is still generating
+    let (early_tx, mut early_rx) = mpsc::unbounded_channel::<(String, Vec<AriaosCommand>)>();
+    // Reflections on replies are written in the background, onto the notes
+    // like the companion's own tool calls
+    if let Some(reflector) = reflector {
+        director.set_reflections(reflector.with_notes(early_tx.clone()).spawn());
+    }
+    director.set_early_commands(early_tx);
+    {
+        let storage = storage.clone();
+        let notes_state = notes_state.clone();
+        let bridge = bridge_handle.clone();
+        tokio::spawn(async move {
+            while let Some((character_id, commands)) = early_rx.recv().await {
+                let character_id = Some(character_id.as_str());
+                if let Err(err) = execute_ariaos_commands(
+                    &commands,
+                    character_id,
+                    &storage,
+                    &notes_state,
+                    &bridge,
+                )
+                .await
+                {
+                    error!(?err, "Failed to execute streamed ARIAOS commands");
+                }
+            }
+        });
+    }
+
+    // Reply text is shown as it generates, ahead of the synthesized `Speak`
+    let (delta_tx, mut delta_rx) = mpsc::unbounded_channel::<(String, String)>();
+    director.set_text_deltas(delta_tx);
+    {
+        let bridge = bridge_handle.clone();
+        tokio::spawn(async move {
+            while let Some((character_id, delta)) = delta_rx.recv().await {
+                // Never fails: with no client connected the delta is dropped
+                let _ = bridge.broadcast(DaemonMessage::SpeakDelta {
+                    character_id,
+                    delta,
+                });
+            }
+        });
+    }
+
+    // Replies are voiced here so a slow TTS backend never stalls the perception
+    // loop; jobs run one at a time so speech stays in reply order
+    let (speech_tx, mut speech_rx) = mpsc::unbounded_channel::<SpeechJob>();
+    let audio_cache = voices.cache();
+    {
+        let bridge = bridge_handle.clone();
+        let output = SpeechOutput {
+            voices,
+            playback: tts::Playback::from_config(&config.tts),
+            stream_sentences: config.tts.stream_sentences,
+            bridge_format: config.tts.bridge_format,
+        };
+        tokio::spawn(async move {
+            while let Some(job) = speech_rx.recv().await {
+                output.speak(&bridge, job).await;
+            }
+        });
+    }
+    // Spoken input is transcribed off the loop and arrives here as text
+    let mut voice = stt::listen(&config.stt);
+    // Copied text is noted for the next tick
+    let mut copies = clipboard::watch(&config.clipboard);
+    // Scheduled entries and reminders, whatever the screen is doing
+    let mut schedule = scheduler::watch(&config.scheduler, storage.clone());
+    // Scheduled messages wait here while do-not-disturb silences the companions
+    let mut held_messages: Vec<(Option<String>, String)> = Vec::new();
+    let focus = FocusDetector::new(&config.focus);
+    // Capture and model calls stop while nobody is at the computer
+    let mut host = idle::IdleMonitor::new(config.idle.clone());
     let capture_delay = vision.capture_interval();
     
     // Use a sleep that resets after each tick completes, rather than a fixed interval
@@ -89,35 +240,133 @@ async fn main() -> Result<()> {
     loop {
         tokio::select! {
             _ = tokio::time::sleep_until(next_tick) => {
-                let tick_start = std::time::Instant::now();
-                if let Err(err) = perception_tick(
-                    &mut vision,
-                    &mut observation_buffer,
-                    &mut director,
-                    &bridge_handle,
-                    &synth,
-                    &storage,
-                    &composite_renderer,
-                    &optical_assets,
-                    &ariaos_assets,
-                    &notes_state,
-                ).await {
-                    error!(?err, "Perception tick failed");
+                if let Some(state) = host.poll(vision.input_idle()).await {
+                    info!(state = state.as_str(), "Host activity changed");
+                    if let Err(err) = bridge_handle.broadcast(DaemonMessage::ActivityState {
+                        state: state.as_str().to_string(),
+                        timestamp: Utc::now().timestamp(),
+                    }) {
+                        error!(?err, "Failed to send activity state");
+                    }
+                }
+                if host.state().is_paused() {
+                    next_tick = tokio::time::Instant::now() + capture_delay;
+                    continue;
                 }
+                let tick_start = std::time::Instant::now();
+                // User input cancels the tick so the next one answers it, rather
+                // than the companions replying to context the user has moved past
+                let mut deferred = Vec::new();
+                let interrupted = {
+                    let tick = perception_tick(
+                        &mut vision,
+                        &mut observation_buffer,
+                        &mut director,
+                        &bridge_handle,
+                        &speech_tx,
+                        &storage,
+                        &composite_renderer,
+                        &optical_assets,
+                        &ariaos_assets,
+                        &notes_state,
+                        &focus,
+                    );
+                    tokio::pin!(tick);
+                    loop {
+                        tokio::select! {
+                            result = &mut tick => {
+                                if let Err(err) = result {
+                                    error!(?err, "Perception tick failed");
+                                }
+                                break None;
+                            }
+                            Some(msg) = bridge.next_message() => match msg {
+                                ClientMessage::UserChat { .. } => break Some(UserInput::Chat(msg)),
+                                msg => deferred.push(msg),
+                            },
+                            Some(event) = voice.recv() => break Some(UserInput::Voice(event)),
+                        }
+                    }
+                };
                 let elapsed = tick_start.elapsed();
-                info!("Perception tick completed in {:?}", elapsed);
-                // Schedule next tick AFTER this one completes
-                next_tick = tokio::time::Instant::now() + capture_delay;
+                if interrupted.is_some() {
+                    info!("Perception tick cancelled by user input after {:?}", elapsed);
+                    director.supersede();
+                } else {
+                    info!("Perception tick completed in {:?}", elapsed);
+                }
+                for msg in deferred {
+                    if let Err(err) = handle_client_message(
+                        msg,
+                        &mut director,
+                        &mut vision,
+                        &storage,
+                        &mut observation_buffer,
+                        &optical_assets,
+                        &ariaos_assets,
+                        &notes_state,
+                        &speech_tx,
+                        &bridge_handle
+                    ).await {
+                        error!(?err, "Failed to handle client event");
+                    }
+                }
+                for limiter in &llm_limiters {
+                    debug!(stats = ?limiter.stats(), "LLM concurrency");
+                }
+                if let Some(cache) = &audio_cache {
+                    debug!(stats = ?cache.stats(), "TTS cache");
+                }
+                match interrupted {
+                    Some(UserInput::Chat(msg)) => {
+                        if let Err(err) = handle_client_message(
+                            msg,
+                            &mut director,
+                            &mut vision,
+                            &storage,
+                            &mut observation_buffer,
+                            &optical_assets,
+                            &ariaos_assets,
+                            &notes_state,
+                            &speech_tx,
+                            &bridge_handle
+                        ).await {
+                            error!(?err, "Failed to handle client event");
+                        }
+                        // Evaluate again straight away, with the new message
+                        next_tick = tokio::time::Instant::now();
+                    }
+                    Some(UserInput::Voice(event)) => {
+                        handle_voice_event(
+                            event,
+                            &mut director,
+                            &mut vision,
+                            &storage,
+                            &mut observation_buffer,
+                            &optical_assets,
+                            &ariaos_assets,
+                            &notes_state,
+                            &speech_tx,
+                            &bridge_handle,
+                        ).await;
+                        next_tick = tokio::time::Instant::now();
+                    }
+                    // Schedule next tick AFTER this one completes
+                    None => next_tick = tokio::time::Instant::now() + capture_delay,
+                }
             }
             next = bridge.next_message() => {
                 if let Some(msg) = next {
                     if let Err(err) = handle_client_message(
                         msg,
+                        &mut director,
+                        &mut vision,
                         &storage,
                         &mut observation_buffer,
                         &optical_assets,
                         &ariaos_assets,
                         &notes_state,
+                        &speech_tx,
                         &bridge_handle
                     ).await {
                         error!(?err, "Failed to handle client event");
@@ -126,23 +375,237 @@ async fn main() -> Result<()> {
                     break;
                 }
             }
+            Some(event) = voice.recv() => {
+                handle_voice_event(
+                    event,
+                    &mut director,
+                    &mut vision,
+                    &storage,
+                    &mut observation_buffer,
+                    &optical_assets,
+                    &ariaos_assets,
+                    &notes_state,
+                    &speech_tx,
+                    &bridge_handle,
+                ).await;
+            }
+            Some(event) = copies.recv() => {
+                debug!(chars = event.text.chars().count(), "User copied text");
+                observation_buffer.record_clipboard(event);
+            }
+            Some(event) = schedule.recv() => match event {
+                ScheduledEvent::Prompt(prompt) => {
+                    info!(text = %prompt.text, "Scheduled prompt due");
+                    observation_buffer.record_scheduled(prompt);
+                    // Evaluate now rather than after the capture delay
+                    next_tick = tokio::time::Instant::now();
+                }
+                ScheduledEvent::Message { character, text } => {
+                    held_messages.push((character, text));
+                }
+            },
+            _ = tokio::signal::ctrl_c() => {
+                info!("Shutting down");
+                break;
+            }
+        }
+
+        if held_messages.is_empty() || director.focus().is_some_and(Focus::silences) {
+            continue;
+        }
+        for (character, text) in std::mem::take(&mut held_messages) {
+            let speaker = director.scheduled_speaker(character.as_deref());
+            let Some(character_id) = speaker else {
+                warn!(?character, "No companion for scheduled message");
+                continue;
+            };
+            if let Err(err) = say_scheduled(
+                character_id,
+                text,
+                &mut observation_buffer,
+                &storage,
+                &speech_tx,
+                &bridge_handle,
+            ) {
+                error!(?err, "Failed to say scheduled message");
+            }
         }
     }
 
+    // Don't lose chat, decisions or episodes still waiting to be written
+    storage.flush().await?;
     Ok(())
 }
 
+/// Apply ARIAOS commands from `character_id` (None for debug commands) to
+/// the local notes state and forward them to Godot
+async fn execute_ariaos_commands(
+    commands: &[AriaosCommand],
+    character_id: Option<&str>,
+    storage: &Storage,
+    notes_state: &Arc<Mutex<AriaosNotesState>>,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    log_event(
+        bridge,
+        "info",
+        format!("Processing {} ARIAOS tool call(s): {:?}", commands.len(), commands),
+    );
+
+    // Forgetting and reminders are done here; only app commands go on to
+    // the notes and Godot
+    let (daemon_commands, commands): (Vec<_>, Vec<_>) = commands
+        .iter()
+        .cloned()
+        .partition(AriaosCommand::is_daemon_command);
+    for command in &daemon_commands {
+        match command {
+            AriaosCommand::Forget(request) => {
+                forget_memory(request, storage, notes_state, bridge).await?;
+            }
+            AriaosCommand::Remind(request) => {
+                let reminder = storage
+                    .add_reminder(request.due_at, character_id, &request.text)
+                    .await?;
+                log_event(
+                    bridge,
+                    "info",
+                    format!(
+                        "Reminder {} set for {}: {}",
+                        reminder.id, reminder.due_at, reminder.text
+                    ),
+                );
+            }
+            AriaosCommand::Notes(_) => {}
+        }
+    }
+    if commands.is_empty() {
+        return Ok(());
+    }
+    let commands = commands.as_slice();
+
+    // Update local notes state and persist
+    let applied = {
+        let mut notes = notes_state.lock().await;
+        if let Some(character_id) = character_id {
+            open_notes_of(character_id, &mut notes, storage, bridge).await?;
+        }
+        let applied = apply_and_record_notes(commands, &mut notes, storage, bridge).await?;
+        storage
+            .save_ariaos_notes(notes.owner.as_deref(), &notes)
+            .await?;
+        applied
+    };
+    remember_notes_edits(storage, commands, character_id);
+
+    // Send commands to Godot for execution
+    bridge.broadcast(DaemonMessage::AriaosCommand {
+        commands: serde_json::to_value(&applied)?,
+    })?;
+    Ok(())
+}
+
+/// Apply commands one at a time, keeping a revision of each change to the
+/// notes. Reverts become the `SetContent` that restores the old text
+async fn apply_and_record_notes(
+    commands: &[AriaosCommand],
+    notes: &mut AriaosNotesState,
+    storage: &Storage,
+    bridge: &BridgeHandle,
+) -> Result<Vec<AriaosCommand>> {
+    let mut applied = Vec::with_capacity(commands.len());
+    for command in commands {
+        let AriaosCommand::Notes(action) = command else {
+            continue;
+        };
+        let command = match action {
+            NotesAction::Revert(revision_id) => {
+                let owner = notes.owner.as_deref();
+                match storage.notes_revert_target(owner, *revision_id).await? {
+                    Some(content) => AriaosCommand::Notes(NotesAction::SetContent(content)),
+                    None => {
+                        log_event(bridge, "warn", "No notes revision to revert to");
+                        continue;
+                    }
+                }
+            }
+            _ => command.clone(),
+        };
+        let previous = notes.content.clone();
+        apply_notes_commands(std::slice::from_ref(&command), notes);
+        storage
+            .record_notes_revision(
+                notes.owner.as_deref(),
+                action.tool_name(),
+                &previous,
+                &notes.content,
+            )
+            .await?;
+        applied.push(command);
+    }
+    Ok(applied)
+}
+
+/// User input that arrived during a perception tick and cancelled it
+enum UserInput {
+    Chat(ClientMessage),
+    Voice(stt::VoiceEvent),
+}
+
+async fn handle_voice_event(
+    event: stt::VoiceEvent,
+    director: &mut Director,
+    vision: &mut VisionPipeline,
+    storage: &Storage,
+    buffer: &mut ObservationBuffer,
+    optical_assets: &Arc<Mutex<OpticalAssets>>,
+    ariaos_assets: &Arc<Mutex<AriaosAssets>>,
+    notes_state: &Arc<Mutex<AriaosNotesState>>,
+    speech_tx: &mpsc::UnboundedSender<SpeechJob>,
+    bridge: &BridgeHandle,
+) {
+    match event {
+        stt::VoiceEvent::Wake { character } => {
+            info!(?character, "Woken by wake word");
+            director.wake(character);
+        }
+        stt::VoiceEvent::Transcript(text) => {
+            info!(%text, "Heard user");
+            if let Err(err) = bridge.broadcast(DaemonMessage::Transcript { text: text.clone() }) {
+                error!(?err, "Failed to send transcript");
+            }
+            if let Err(err) = handle_client_message(
+                ClientMessage::UserChat { text },
+                director,
+                vision,
+                storage,
+                buffer,
+                optical_assets,
+                ariaos_assets,
+                notes_state,
+                speech_tx,
+                bridge,
+            )
+            .await
+            {
+                error!(?err, "Failed to handle voice input");
+            }
+        }
+    }
+}
+
 async fn perception_tick(
     vision: &mut VisionPipeline,
     buffer: &mut ObservationBuffer,
     director: &mut Director,
     bridge: &BridgeHandle,
-    synth: &tts::SharedSynth,
+    speech_tx: &mpsc::UnboundedSender<SpeechJob>,
     storage: &Storage,
     composite_renderer: &CompositeRenderer,
     optical_assets: &Arc<Mutex<OpticalAssets>>,
     ariaos_assets: &Arc<Mutex<AriaosAssets>>,
     notes_state: &Arc<Mutex<AriaosNotesState>>,
+    focus: &FocusDetector,
 ) -> Result<()> {
     // Flush any pending user messages into chat history before processing
     let pending_messages = buffer.flush_pending_messages();
@@ -157,6 +620,7 @@ async fn perception_tick(
     // Apply relevance decay based on time elapsed (assume ~capture_interval between ticks)
     let minutes_elapsed = vision.capture_interval().as_secs_f32() / 60.0;
     buffer.apply_relevance_decay(minutes_elapsed);
+    storage.save_chat_relevance(buffer.chat_history());
     
     // Log tier distribution occasionally
     let (hot, warm, cold) = buffer.tier_stats();
@@ -168,32 +632,52 @@ async fn perception_tick(
         );
     }
     
-    let frame = vision.capture_frame()?;
+    let mut frame = vision.capture_frame()?;
+    if let Some(target) = vision.take_target_change() {
+        log_event(bridge, "info", format!("Capture target changed: {target}"));
+    }
+    if vision.is_scene_change(&frame) {
+        bridge.broadcast(DaemonMessage::SceneChange {
+            diff_score: frame.diff_score,
+            timestamp: frame.timestamp.timestamp(),
+        })?;
+        director.scene_changed();
+    }
+    vision.read_text(&mut frame).await;
     
     // Capture raw desktop for history BEFORE compositing (history should show just desktop, not composite)
     let desktop_for_history = frame.rgba();
 
     let optical = optical_assets.lock().await.clone();
     
-    // Get historical approved screenshots for context
+    // Get historical screenshots for context: the rolling timeline when
+    // configured, else the approved screenshots
+    let now = Utc::now();
     let composite_image = {
-        let approved = buffer.approved_screenshots();
-        let history: Vec<&image::RgbaImage> = approved
-            .iter()
-            .map(|s| &s.image)
-            .collect();
-        
-        // Render composite with history if available
-        composite_renderer.render_with_history(
-            &CompositeParts {
-                desktop: frame.rgba(),
-                memory_visualization: optical.memory,
-                chat_transcript: optical.chat,
-                character_status: optical.status,
-            },
-            &history,
-        )
+        let parts = CompositeParts {
+            desktop: frame.rgba(),
+            memory_visualization: optical.memory,
+            chat_transcript: optical.chat,
+            character_status: optical.status,
+            camera: frame.camera.clone(),
+            activity: frame.activity,
+        };
+        match buffer.timeline(3) {
+            Some(timeline) => {
+                let history: Vec<_> = timeline
+                    .iter()
+                    .map(|f| (&f.image, (now - f.timestamp).to_std().unwrap_or_default()))
+                    .collect();
+                composite_renderer.render_with_timeline(&parts, &history)
+            }
+            None => {
+                let approved = buffer.approved_screenshots();
+                let history: Vec<&image::RgbaImage> = approved.iter().map(|s| &s.image).collect();
+                composite_renderer.render_with_history(&parts, &history)
+            }
+        }
     };
+    buffer.record_timeline_frame(&desktop_for_history, now);
 
     // Get ARIAOS composite (with history) for VLM
     let ariaos_image = {
@@ -202,123 +686,150 @@ async fn perception_tick(
     };
 
     // Ingest screen with composite and ARIAOS for vision analysis
-    let observation = buffer.ingest_screen(frame, Some(composite_image.clone()), ariaos_image);
+    let mut observation = buffer.ingest_screen(frame, Some(composite_image.clone()), ariaos_image);
+    let foreground = observation.frame.foreground.as_ref();
+    observation.focus = focus.detect(foreground, observation.frame.activity.as_ref());
+    if observation.focus.as_ref().is_some_and(Focus::silences) {
+        // Scheduled prompts wait out do-not-disturb rather than being dropped
+        for prompt in observation.scheduled.drain(..) {
+            buffer.record_scheduled(prompt);
+        }
+    }
+    buffer.record(&observation);
 
+    let (active_app, active_window) = match &observation.frame.foreground {
+        Some(window) => (window.app.clone(), window.title.clone()),
+        None => ("unknown".into(), "unknown".into()),
+    };
     bridge.broadcast(DaemonMessage::ObservationSnapshot {
-        active_app: "unknown".into(),
-        active_window: "unknown".into(),
+        active_app,
+        active_window,
         screen_summary: observation.screen_summary.notes.clone(),
+        screen_text: observation.screen_summary.text.clone(),
         timestamp: Utc::now().timestamp(),
     })?;
 
-    let eval_result = director.evaluate(&observation).await?;
-
-    // Broadcast prompt logs for debugging
-    for log in &eval_result.prompt_logs {
-        bridge.broadcast(DaemonMessage::PromptLog {
-            model_type: log.model_type.clone(),
-            model_name: log.model_name.clone(),
-            prompt: log.prompt.clone(),
-            response: log.response.clone(),
-            timestamp: Utc::now().timestamp(),
-        })?;
-    }
+    let mut eval_result = director.evaluate(&observation).await?;
+    loop {
+        let mut spoken = None;
 
-    match eval_result.decision {
-        Decision::Pass { reasoning, urgency } => {
-            // Broadcast pass decision for debug UI
-            bridge.broadcast(DaemonMessage::DecisionUpdate {
-                decision: json!({
-                    "should_respond": false,
-                    "responder_id": null,
-                    "reasoning": reasoning,
-                    "urgency": urgency
-                }),
-                observation: json!({
-                    "screen_summary": observation.screen_summary.notes
-                }),
+        // Broadcast prompt logs for debugging, and keep a durable copy
+        for log in &eval_result.prompt_logs {
+            storage.record_llm_call(log.to_stored());
+            bridge.broadcast(DaemonMessage::PromptLog {
+                model_type: log.model_type.clone(),
+                model_name: log.model_name.clone(),
+                prompt: log.prompt.clone(),
+                response: log.response.clone(),
+                reasoning: log.reasoning.clone(),
+                timestamp: Utc::now().timestamp(),
             })?;
         }
-        Decision::Speak {
-            character_id,
-            text,
-            urgency,
-            reasoning,
-            suggested_mood,
-            tool_calls,
-        } => {
-            // Handle ARIAOS tool calls from the response
-            if tool_calls.is_empty() {
-                log_event(bridge, "debug", "No tool calls in response");
-            } else {
-                log_event(
-                    bridge,
-                    "info",
-                    format!("Processing {} ARIAOS tool call(s): {:?}", tool_calls.len(), tool_calls),
-                );
-                
-                // Update local notes state and persist
-                {
-                    let mut notes = notes_state.lock().await;
-                    apply_notes_commands(&tool_calls, &mut notes);
-                    storage.save_ariaos_notes(&notes).await?;
-                }
-                
-                // Send commands to Godot for execution
-                bridge.broadcast(DaemonMessage::AriaosCommand {
-                    commands: serde_json::to_value(&tool_calls)?,
+
+        match eval_result.decision {
+            Decision::Pass { reasoning, urgency } => {
+                // Broadcast pass decision for debug UI
+                bridge.broadcast(DaemonMessage::DecisionUpdate {
+                    decision: json!({
+                        "should_respond": false,
+                        "responder_id": null,
+                        "reasoning": reasoning,
+                        "urgency": urgency
+                    }),
+                    observation: json!({
+                        "screen_summary": observation.screen_summary.notes
+                    }),
                 })?;
             }
+            Decision::Speak {
+                decision_id,
+                character_id,
+                text,
+                urgency,
+                reasoning,
+                suggested_mood,
+                tool_calls,
+            } => {
+                // Handle ARIAOS tool calls from the response
+                if tool_calls.is_empty() {
+                    log_event(bridge, "debug", "No tool calls in response");
+                } else {
+                    let responder = Some(character_id.as_str());
+                    execute_ariaos_commands(&tool_calls, responder, storage, notes_state, bridge)
+                        .await?;
+                }
             
-            bridge.broadcast(DaemonMessage::DecisionUpdate {
-                decision: json!({
-                    "should_respond": true,
-                    "responder_id": character_id,
-                    "reasoning": reasoning,
-                    "urgency": urgency,
-                    "suggested_mood": suggested_mood,
-                    "tool_calls": tool_calls.len()
-                }),
-                observation: json!({
-                    "screen_summary": observation.screen_summary.notes
-                }),
-            })?;
+                bridge.broadcast(DaemonMessage::DecisionUpdate {
+                    decision: json!({
+                        "should_respond": true,
+                        "responder_id": character_id,
+                        "reasoning": reasoning,
+                        "urgency": urgency,
+                        "suggested_mood": suggested_mood,
+                        "tool_calls": tool_calls.len()
+                    }),
+                    observation: json!({
+                        "screen_summary": observation.screen_summary.notes
+                    }),
+                })?;
 
-            // Record the assistant's response in chat history so future prompts see it
-            // (text is already clean - no DSL to strip with tool calling)
-            let assistant_packet = ChatPacket {
-                sender: character_id.clone(),
-                content: text.clone(),
-                timestamp: Utc::now().timestamp(),
-                relevance: 1.0,
-                tier: MemoryTier::Hot,
-            };
-            storage.record_chat(&assistant_packet).await?;
-            buffer.record_chat(assistant_packet);
+                // Record the assistant's response in chat history so future prompts see it
+                // (text is already clean - no DSL to strip with tool calling)
+                let assistant_packet = ChatPacket {
+                    sender: character_id.clone(),
+                    content: text.clone(),
+                    timestamp: Utc::now().timestamp(),
+                    relevance: 1.0,
+                    tier: MemoryTier::Hot,
+                    character_id: Some(character_id.clone()),
+                };
+                storage.record_chat(&assistant_packet);
+                storage.remember(
+                    Episode::now(
+                        EpisodeKind::CompanionResponse,
+                        Some(character_id.clone()),
+                        text.clone(),
+                    )
+                    .for_character(&character_id),
+                );
+                buffer.record_chat(assistant_packet.clone());
+                spoken = Some(assistant_packet);
             
-            // Record raw desktop screenshot for visual history (NOT the composite)
-            buffer.record_approved_screenshot(desktop_for_history.clone());
+                // Record raw desktop screenshot for visual history (NOT the composite)
+                buffer.record_approved_screenshot(desktop_for_history.clone());
+                let decision = Some(decision_id);
+                let desktop = desktop_for_history.clone();
+                storage.keep_snapshot(decision, SnapshotKind::Screenshot, desktop);
+                let composite = composite_image.clone();
+                storage.keep_snapshot(decision, SnapshotKind::Composite, composite);
             
-            // Record ARIAOS snapshot for history
-            ariaos_assets.lock().await.record_approved();
+                // Record ARIAOS snapshot for history
+                ariaos_assets.lock().await.record_approved();
 
-            let audio = synth.synthesize(&text)?;
-            let audio_b64 = BASE64.encode(audio);
-            bridge.broadcast(DaemonMessage::Speak {
-                character_id,
-                text,
-                audio_base64: Some(audio_b64),
-                puppet: serde_json::json!({
-                    "mood": suggested_mood.unwrap_or_else(|| "neutral".into()),
-                    "urgency": urgency
-                }),
-            })?;
+                speech_tx.send(SpeechJob {
+                    prosody: tts::Prosody::for_mood(suggested_mood.as_deref(), urgency),
+                    character_id,
+                    text,
+                    puppet: serde_json::json!({
+                        "mood": suggested_mood.unwrap_or_else(|| "neutral".into()),
+                        "urgency": urgency
+                    }),
+                })?;
 
-            log_event(
-                bridge,
-                "info",
-                format!("Arbiter response queued (urgency {urgency:.2})"),
-            );
+                log_event(
+                    bridge,
+                    "info",
+                    format!("Arbiter response queued (urgency {urgency:.2})"),
+                );
+            }
+        }
+
+        // Another companion may answer the fresh reply, up to `max_chain` times
+        let Some(reply) = spoken else { break };
+        observation.recent_chat.push(reply.clone());
+        match director.evaluate_reply(&observation, &reply).await? {
+            Some(result) => eval_result = result,
+            None => break,
         }
     }
 
@@ -367,11 +878,14 @@ async fn perception_tick(
 
 async fn handle_client_message(
     message: ClientMessage,
+    director: &mut Director,
+    vision: &mut VisionPipeline,
     storage: &Storage,
     buffer: &mut ObservationBuffer,
     optical_assets: &Arc<Mutex<OpticalAssets>>,
     ariaos_assets: &Arc<Mutex<AriaosAssets>>,
     notes_state: &Arc<Mutex<AriaosNotesState>>,
+    speech_tx: &mpsc::UnboundedSender<SpeechJob>,
     bridge: &BridgeHandle,
 ) -> Result<()> {
     match message {
@@ -397,9 +911,15 @@ async fn handle_client_message(
                 timestamp: Utc::now().timestamp(),
                 relevance: 1.0,
                 tier: MemoryTier::Hot,
+                character_id: None,
             };
-            // Store in DB immediately for persistence
-            storage.record_chat(&packet).await?;
+            // Queue for the DB writer right away, for persistence
+            storage.record_chat(&packet);
+            storage.remember(Episode::now(
+                EpisodeKind::UserMessage,
+                Some("user".into()),
+                packet.content.clone(),
+            ));
             // Queue for batching - will be added to chat history at next perception tick
             buffer.queue_user_message(packet.clone());
             bridge.broadcast(DaemonMessage::DecisionUpdate {
@@ -436,6 +956,80 @@ async fn handle_client_message(
                 log_event(bridge, "debug", "ARIAOS render received");
             }
         }
+        ClientMessage::SetCaptureWindow { title, app } => {
+            let target = (title.is_some() || app.is_some()).then_some(WindowTarget { title, app });
+            let message = match &target {
+                Some(target) => format!("Capturing window {:?}", target),
+                None => "Capturing the desktop".to_string(),
+            };
+            match vision.set_window(target) {
+                Ok(()) => log_event(bridge, "info", message),
+                Err(err) => log_event(bridge, "warn", format!("Invalid capture window: {err:#}")),
+            }
+        }
+        ClientMessage::CaptureScreenshot {
+            monitor,
+            title,
+            app,
+        } => {
+            let window = (title.is_some() || app.is_some()).then_some(WindowTarget { title, app });
+            match vision.screenshot(monitor, window) {
+                Ok(image) => {
+                    bridge.broadcast(DaemonMessage::Screenshot {
+                        image_base64: encode_image_base64(&image)?,
+                        width: image.width(),
+                        height: image.height(),
+                        timestamp: Utc::now().timestamp(),
+                    })?;
+                    let size = format!("{}x{}", image.width(), image.height());
+                    log_event(bridge, "info", format!("Screenshot sent ({size})"));
+                }
+                Err(err) => log_event(bridge, "warn", format!("Screenshot failed: {err:#}")),
+            }
+        }
+        ClientMessage::ListNotesRevisions {
+            character_id,
+            limit,
+        } => {
+            let revisions = storage
+                .notes_revisions(character_id.as_deref(), limit.unwrap_or(20))
+                .await?;
+            bridge.broadcast(DaemonMessage::NotesRevisions {
+                character_id,
+                revisions: serde_json::to_value(&revisions)?,
+            })?;
+        }
+        ClientMessage::RevertNotes { revision_id } => {
+            match storage.notes_revision(revision_id).await? {
+                Some(revision) => {
+                    let revert = [AriaosCommand::Notes(NotesAction::Revert(Some(revision_id)))];
+                    let owner = revision.owner.as_deref();
+                    execute_ariaos_commands(&revert, owner, storage, notes_state, bridge).await?;
+                }
+                None => log_event(bridge, "warn", format!("No notes revision {revision_id}")),
+            }
+        }
+        ClientMessage::RegenerateResponse { feedback } => {
+            regenerate_response(&feedback, director, buffer, storage, notes_state, speech_tx, bridge)
+                .await?;
+        }
+        ClientMessage::GetDecisionStats { hours } => {
+            send_decision_stats(hours.unwrap_or(24), storage, bridge).await?;
+        }
+        ClientMessage::Forget {
+            since,
+            until,
+            sender,
+            query,
+        } => {
+            let request = ForgetRequest {
+                since,
+                until,
+                sender,
+                query,
+            };
+            forget_memory(&request, storage, notes_state, bridge).await?;
+        }
         ClientMessage::DebugCommand { command, payload } => {
             match command.as_str() {
                 "exec_tool" => {
@@ -456,6 +1050,10 @@ async fn handle_client_message(
                             })
                         }
                         "notes_clear" => Some(AriaosCommand::Notes(NotesAction::Clear)),
+                        "notes_revert" => {
+                            let revision_id = args.get("revision_id").and_then(|v| v.as_i64());
+                            Some(AriaosCommand::Notes(NotesAction::Revert(revision_id)))
+                        }
                         "notes_scroll_up" => Some(AriaosCommand::Notes(NotesAction::ScrollUp)),
                         "notes_scroll_down" => Some(AriaosCommand::Notes(NotesAction::ScrollDown)),
                         "notes_scroll_to_top" => Some(AriaosCommand::Notes(NotesAction::ScrollToTop)),
@@ -468,19 +1066,24 @@ async fn handle_client_message(
                     
                     if let Some(cmd) = command {
                         log_event(bridge, "info", format!("Debug exec tool: {:?}", cmd));
-                        
-                        // Update local notes state and persist
-                        {
-                            let mut notes = notes_state.lock().await;
-                            apply_notes_commands(&[cmd.clone()], &mut notes);
-                            storage.save_ariaos_notes(&notes).await?;
+                        execute_ariaos_commands(&[cmd], None, storage, notes_state, bridge).await?;
+                    }
+                }
+                // payload: { "path": "memory.jsonl" }
+                "export_memory" | "import_memory" => {
+                    match payload.get("path").and_then(|v| v.as_str()) {
+                        Some(path) => {
+                            let path = Path::new(path);
+                            memory_archive(&command, path, storage, notes_state, bridge).await?
                         }
-                        
-                        bridge.broadcast(DaemonMessage::AriaosCommand {
-                            commands: serde_json::to_value(&[cmd])?,
-                        })?;
+                        None => log_event(bridge, "warn", format!("{command} needs a path")),
                     }
                 }
+                // payload: { "hours": 24 }
+                "decision_stats" => {
+                    let hours = payload.get("hours").and_then(|v| v.as_u64()).unwrap_or(24);
+                    send_decision_stats(hours as u32, storage, bridge).await?;
+                }
                 _ => {
                     bridge.broadcast(DaemonMessage::DecisionUpdate {
                         decision: serde_json::json!({ "debug_command": command, "payload": payload }),
@@ -493,12 +1096,210 @@ async fn handle_client_message(
     Ok(())
 }
 
+async fn send_decision_stats(hours: u32, storage: &Storage, bridge: &BridgeHandle) -> Result<()> {
+    let stats = storage.decision_stats(hours).await?;
+    bridge.broadcast(DaemonMessage::DecisionStats {
+        hours,
+        stats: serde_json::to_value(&stats)?,
+    })?;
+    Ok(())
+}
+
 fn decode_png(b64: &str) -> Option<image::RgbaImage> {
     let bytes = BASE64.decode(b64).ok()?;
     let img = image::load_from_memory(&bytes).ok()?;
     Some(img.to_rgba8())
 }
 
+/// An approved reply waiting to be voiced
+struct SpeechJob {
+    character_id: String,
+    text: String,
+    puppet: serde_json::Value,
+    /// Delivery matching the puppet's mood
+    prosody: tts::Prosody,
+}
+
+/// Synthesized speech ready to send to the frontend
+struct BridgeAudio {
+    base64: String,
+    format: String,
+    /// Viseme cues for the puppet's mouth
+    lip_sync: Option<serde_json::Value>,
+    /// Playing time
+    duration: Duration,
+}
+
+/// Everything the speech worker needs to voice replies
+struct SpeechOutput {
+    voices: tts::VoiceMap,
+    playback: tts::Playback,
+    stream_sentences: bool,
+    bridge_format: AudioFormat,
+}
+
+impl SpeechOutput {
+    /// Synthesize a reply and send it to the frontend. When streaming, the text
+    /// is sent immediately and each sentence's audio follows as a `SpeakChunk`.
+    /// If synthesis fails the text is still delivered, just without audio.
+    /// Audio is bracketed by `SpeechStarted`/`SpeechEnded`.
+    async fn speak(&self, bridge: &BridgeHandle, job: SpeechJob) {
+        let utterance_id = uuid::Uuid::new_v4().to_string();
+        let sentences = tts::split_sentences(&job.text);
+        if !self.stream_sentences || sentences.is_empty() {
+            let audio = self
+                .synthesize(&job.character_id, bridge, &job.text, &job.prosody)
+                .await;
+            let mut puppet = job.puppet;
+            if let (Some(lip_sync), Some(puppet)) = (
+                audio.as_ref().and_then(|a| a.lip_sync.clone()),
+                puppet.as_object_mut(),
+            ) {
+                puppet.insert("lip_sync".to_string(), lip_sync);
+            }
+            let duration = audio.as_ref().map(|a| a.duration);
+            if let Some(duration) = duration {
+                speech_started(bridge, &job.character_id, &utterance_id, duration);
+            }
+            let (audio_base64, audio_format) = audio.map(|a| (a.base64, a.format)).unzip();
+            if let Err(err) = bridge.broadcast(DaemonMessage::Speak {
+                character_id: job.character_id.clone(),
+                text: job.text,
+                audio_base64,
+                puppet,
+                audio_format,
+                utterance_id: None,
+            }) {
+                error!(?err, "Failed to send speech");
+            }
+            if let Some(duration) = duration {
+                let ends_at = tokio::time::Instant::now() + duration;
+                speech_ends_at(bridge, job.character_id, utterance_id, ends_at);
+            }
+            return;
+        }
+
+        if let Err(err) = bridge.broadcast(DaemonMessage::Speak {
+            character_id: job.character_id.clone(),
+            text: job.text,
+            audio_base64: None,
+            puppet: job.puppet,
+            audio_format: None,
+            utterance_id: Some(utterance_id.clone()),
+        }) {
+            error!(?err, "Failed to send speech");
+            return;
+        }
+
+        let last = sentences.len() - 1;
+        let total_chars: usize = sentences.iter().map(|s| s.chars().count()).sum();
+        let mut ends_at: Option<tokio::time::Instant> = None;
+        for (index, sentence) in sentences.into_iter().enumerate() {
+            let audio = self
+                .synthesize(&job.character_id, bridge, &sentence, &job.prosody)
+                .await;
+            if let Some(audio) = &audio {
+                let now = tokio::time::Instant::now();
+                if ends_at.is_none() {
+                    // Assume the rest of the reply keeps this sentence's pace
+                    let chars = sentence.chars().count().max(1);
+                    let estimate = audio.duration.mul_f64(total_chars as f64 / chars as f64);
+                    speech_started(bridge, &job.character_id, &utterance_id, estimate);
+                }
+                // Chunks play back-to-back, or on arrival if synthesis falls behind
+                ends_at = Some(ends_at.map_or(now, |end| end.max(now)) + audio.duration);
+            }
+            let (audio_base64, audio_format, lip_sync) = match audio {
+                Some(audio) => (Some(audio.base64), Some(audio.format), audio.lip_sync),
+                None => (None, None, None),
+            };
+            if let Err(err) = bridge.broadcast(DaemonMessage::SpeakChunk {
+                character_id: job.character_id.clone(),
+                utterance_id: utterance_id.clone(),
+                index: index as u32,
+                is_final: index == last,
+                text: sentence,
+                audio_base64,
+                audio_format,
+                lip_sync,
+            }) {
+                error!(?err, "Failed to send speech chunk");
+                break;
+            }
+        }
+        if let Some(ends_at) = ends_at {
+            speech_ends_at(bridge, job.character_id, utterance_id, ends_at);
+        }
+    }
+
+    /// Synthesize `text` for the bridge, playing it locally if no frontend
+    /// will
+    async fn synthesize(
+        &self,
+        character_id: &str,
+        bridge: &BridgeHandle,
+        text: &str,
+        prosody: &tts::Prosody,
+    ) -> Option<BridgeAudio> {
+        let synth = self.voices.for_character(character_id);
+        match synth.synthesize_timed(text, prosody).await {
+            Ok(synthesis) => {
+                self.playback.offer(&synthesis.audio, bridge.client_count());
+                let lip_sync = tts::lip_sync(text, &synthesis);
+                let duration = tts::wav_duration(&synthesis.audio)
+                    .unwrap_or_else(|| tts::estimate_duration(text, prosody));
+                let (audio, format) = tts::encode_for_bridge(synthesis.audio, self.bridge_format);
+                Some(BridgeAudio {
+                    base64: BASE64.encode(audio),
+                    format: format.as_str().to_string(),
+                    lip_sync,
+                    duration,
+                })
+            }
+            Err(err) => {
+                error!(?err, "Speech synthesis failed");
+                log_event(bridge, "error", format!("Speech synthesis failed: {err}"));
+                None
+            }
+        }
+    }
+}
+
+/// Tell clients a reply is starting, so they can duck other audio
+fn speech_started(
+    bridge: &BridgeHandle,
+    character_id: &str,
+    utterance_id: &str,
+    duration: Duration,
+) {
+    if let Err(err) = bridge.broadcast(DaemonMessage::SpeechStarted {
+        character_id: character_id.to_string(),
+        utterance_id: utterance_id.to_string(),
+        estimated_duration_ms: duration.as_millis() as u64,
+    }) {
+        error!(?err, "Failed to send speech start");
+    }
+}
+
+/// Send `SpeechEnded` once the reply should have finished playing
+fn speech_ends_at(
+    bridge: &BridgeHandle,
+    character_id: String,
+    utterance_id: String,
+    at: tokio::time::Instant,
+) {
+    let bridge = bridge.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep_until(at).await;
+        if let Err(err) = bridge.broadcast(DaemonMessage::SpeechEnded {
+            character_id,
+            utterance_id,
+        }) {
+            error!(?err, "Failed to send speech end");
+        }
+    });
+}
+
 fn log_event(bridge: &BridgeHandle, level: &str, message: impl Into<String>) {
     let _ = bridge.broadcast(DaemonMessage::Log {
         level: level.to_string(),
@@ -507,6 +1308,245 @@ fn log_event(bridge: &BridgeHandle, level: &str, message: impl Into<String>) {
     });
 }
 
+/// Export or import the memory archive, reloading the notes after an import
+async fn memory_archive(
+    command: &str,
+    path: &Path,
+    storage: &Storage,
+    notes_state: &Arc<Mutex<AriaosNotesState>>,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    if command == "export_memory" {
+        match storage.export_archive(path).await {
+            Ok(rows) => {
+                let message = format!("Exported {rows} rows to {}", path.display());
+                log_event(bridge, "info", message);
+            }
+            Err(err) => log_event(bridge, "error", format!("Memory export failed: {err:#}")),
+        }
+        return Ok(());
+    }
+    match storage.import_archive(path).await {
+        Ok(rows) => {
+            let message = format!("Imported {rows} rows from {}", path.display());
+            log_event(bridge, "info", message);
+            let notes = storage.load_ariaos_notes(None).await?.unwrap_or_default();
+            let cmd = AriaosCommand::Notes(NotesAction::SetContent(notes.content.clone()));
+            *notes_state.lock().await = notes;
+            bridge.broadcast(DaemonMessage::AriaosCommand {
+                commands: serde_json::to_value([cmd])?,
+            })?;
+        }
+        Err(err) => log_event(bridge, "error", format!("Memory import failed: {err:#}")),
+    }
+    Ok(())
+}
+
+/// Forget memories matching `request`, then show the open notes without
+/// any lines that were forgotten
+async fn forget_memory(
+    request: &ForgetRequest,
+    storage: &Storage,
+    notes_state: &Arc<Mutex<AriaosNotesState>>,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    let report = match storage.forget(request).await {
+        Ok(report) => report,
+        Err(err) => {
+            log_event(bridge, "error", format!("Forgetting failed: {err:#}"));
+            return Ok(());
+        }
+    };
+    log_event(bridge, "info", format!("Forgot {report}"));
+    if report.notes_lines > 0 {
+        let mut notes = notes_state.lock().await;
+        let saved = storage
+            .load_ariaos_notes(notes.owner.as_deref())
+            .await?
+            .unwrap_or_default();
+        notes.content = saved.content;
+        let cmd = AriaosCommand::Notes(NotesAction::SetContent(notes.content.clone()));
+        bridge.broadcast(DaemonMessage::AriaosCommand {
+            commands: serde_json::to_value([cmd])?,
+        })?;
+    }
+    bridge.broadcast(DaemonMessage::Forgotten {
+        request: serde_json::to_value(request)?,
+        report: serde_json::to_value(report)?,
+    })?;
+    Ok(())
+}
+
+/// Say the last reply again, differently and with the user's `feedback`,
+/// replacing it in chat history and storage
+async fn regenerate_response(
+    feedback: &str,
+    director: &mut Director,
+    buffer: &mut ObservationBuffer,
+    storage: &Storage,
+    notes_state: &Arc<Mutex<AriaosNotesState>>,
+    speech_tx: &mpsc::UnboundedSender<SpeechJob>,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    let Some(result) = director.regenerate(feedback).await? else {
+        log_event(bridge, "warn", "No reply to regenerate");
+        return Ok(());
+    };
+    for log in &result.prompt_logs {
+        storage.record_llm_call(log.to_stored());
+        bridge.broadcast(DaemonMessage::PromptLog {
+            model_type: log.model_type.clone(),
+            model_name: log.model_name.clone(),
+            prompt: log.prompt.clone(),
+            response: log.response.clone(),
+            reasoning: log.reasoning.clone(),
+            timestamp: Utc::now().timestamp(),
+        })?;
+    }
+    let (character_id, text, urgency, suggested_mood, tool_calls) = match result.decision {
+        Decision::Speak {
+            character_id,
+            text,
+            urgency,
+            suggested_mood,
+            tool_calls,
+            ..
+        } => (character_id, text, urgency, suggested_mood, tool_calls),
+        Decision::Pass { reasoning, .. } => {
+            log_event(bridge, "warn", format!("Regenerated reply dropped: {reasoning}"));
+            return Ok(());
+        }
+    };
+    if !tool_calls.is_empty() {
+        let responder = Some(character_id.as_str());
+        execute_ariaos_commands(&tool_calls, responder, storage, notes_state, bridge).await?;
+    }
+
+    // The new reply takes the old one's place, timestamp and all
+    let packet = match buffer.replace_reply(&character_id, &text) {
+        Some(packet) => {
+            storage.replace_chat(&packet);
+            packet
+        }
+        None => {
+            let packet = ChatPacket {
+                sender: character_id.clone(),
+                content: text.clone(),
+                timestamp: Utc::now().timestamp(),
+                relevance: 1.0,
+                tier: MemoryTier::Hot,
+                character_id: Some(character_id.clone()),
+            };
+            storage.record_chat(&packet);
+            buffer.record_chat(packet.clone());
+            packet
+        }
+    };
+    storage.remember(
+        Episode::now(
+            EpisodeKind::CompanionResponse,
+            Some(character_id.clone()),
+            text.clone(),
+        )
+        .for_character(&character_id),
+    );
+    bridge.broadcast(DaemonMessage::DecisionUpdate {
+        decision: serde_json::to_value(&packet)?,
+        observation: json!({ "type": "regenerated_reply" }),
+    })?;
+    log_event(bridge, "info", format!("Regenerated reply from {character_id}"));
+
+    speech_tx.send(SpeechJob {
+        prosody: tts::Prosody::for_mood(suggested_mood.as_deref(), urgency),
+        character_id,
+        text,
+        puppet: json!({
+            "mood": suggested_mood.unwrap_or_else(|| "neutral".into()),
+            "urgency": urgency
+        }),
+    })?;
+    Ok(())
+}
+
+/// Say a scheduled message as `character_id`, recording it like a reply
+fn say_scheduled(
+    character_id: String,
+    text: String,
+    buffer: &mut ObservationBuffer,
+    storage: &Storage,
+    speech_tx: &mpsc::UnboundedSender<SpeechJob>,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    let packet = ChatPacket {
+        sender: character_id.clone(),
+        content: text.clone(),
+        timestamp: Utc::now().timestamp(),
+        relevance: 1.0,
+        tier: MemoryTier::Hot,
+        character_id: Some(character_id.clone()),
+    };
+    storage.record_chat(&packet);
+    storage.remember(
+        Episode::now(
+            EpisodeKind::CompanionResponse,
+            Some(character_id.clone()),
+            text.clone(),
+        )
+        .for_character(&character_id),
+    );
+    buffer.record_chat(packet);
+    let message = format!("Scheduled message from {character_id}");
+    log_event(bridge, "info", message);
+    speech_tx.send(SpeechJob {
+        prosody: tts::Prosody::for_mood(None, 0.5),
+        character_id,
+        text,
+        puppet: json!({ "mood": "neutral", "urgency": 0.5 }),
+    })?;
+    Ok(())
+}
+
+/// With per-character memory, switch the notes app to `character_id`'s own
+/// notes before it edits them
+async fn open_notes_of(
+    character_id: &str,
+    notes: &mut AriaosNotesState,
+    storage: &Storage,
+    bridge: &BridgeHandle,
+) -> Result<()> {
+    if storage.scope() != MemoryScope::PerCharacter || notes.owner.as_deref() == Some(character_id)
+    {
+        return Ok(());
+    }
+    // The open notes were saved after their last edit
+    let mut own = storage
+        .load_ariaos_notes(Some(character_id))
+        .await?
+        .unwrap_or_default();
+    own.owner = Some(character_id.to_string());
+    *notes = own;
+    let cmd = AriaosCommand::Notes(NotesAction::SetContent(notes.content.clone()));
+    bridge.broadcast(DaemonMessage::AriaosCommand {
+        commands: serde_json::to_value([cmd])?,
+    })?;
+    Ok(())
+}
+
+/// Log content changes to the notes (not scrolling) as episodes, in the
+/// memory of the companion that made them
+fn remember_notes_edits(storage: &Storage, commands: &[AriaosCommand], character_id: Option<&str>) {
+    for command in commands {
+        let AriaosCommand::Notes(action) = command else {
+            continue;
+        };
+        if let Some(summary) = action.edit_summary() {
+            let mut episode = Episode::now(EpisodeKind::NotesEdit, None, summary);
+            episode.character_id = character_id.map(str::to_string);
+            storage.remember(episode);
+        }
+    }
+}
+
 /// Apply ARIAOS tool commands to notes state (for persistence)
 fn apply_notes_commands(commands: &[AriaosCommand], notes: &mut AriaosNotesState) {
     for cmd in commands {
@@ -540,7 +1580,10 @@ fn apply_notes_commands(commands: &[AriaosCommand], notes: &mut AriaosNotesState
                 NotesAction::ScrollToBottom => {
                     notes.scroll_offset = f32::MAX; // Will be clamped by Godot
                 }
+                // Resolved to SetContent before applying
+                NotesAction::Revert(_) => {}
             },
+            AriaosCommand::Forget(_) | AriaosCommand::Remind(_) => {}
         }
     }
 }
@@ -634,11 +1677,11 @@ impl AriaosAssets {
         }
         
         // Draw "ARIAOS" label on current
-        Self::draw_label(&mut canvas, 8, 12, "ARIAOS");
+        draw_label(&mut canvas, 8, 12, "ARIAOS");
         
         if self.approved_history.is_empty() {
             // No history - draw placeholder text
-            Self::draw_label(&mut canvas, current_width + 8, 12, "NO HISTORY");
+            draw_label(&mut canvas, current_width + 8, 12, "NO HISTORY");
             return canvas;
         }
         
@@ -658,57 +1701,15 @@ impl AriaosAssets {
             }
             
             // Draw label
-            Self::draw_label(&mut canvas, current_width + 4, y_offset + 12, &format!("PREV {}", i + 1));
+            draw_label(&mut canvas, current_width + 4, y_offset + 12, &format!("PREV {}", i + 1));
         }
         
         // Fill remaining slots with placeholder
         for i in self.approved_history.len()..4 {
             let y_offset = (i as u32) * hist_panel_height;
-            Self::draw_label(&mut canvas, current_width + 4, y_offset + 12, "NO HIST");
+            draw_label(&mut canvas, current_width + 4, y_offset + 12, "NO HIST");
         }
         
         canvas
     }
-    
-    fn draw_label(canvas: &mut RgbaImage, x: u32, y: u32, text: &str) {
-        // Simple text rendering (reuse the same approach as composite.rs)
-        let mut cursor = x;
-        for ch in text.chars() {
-            if let Some(pattern) = Self::glyph_pattern(ch) {
-                for (row, bits) in pattern.iter().enumerate() {
-                    for col in 0..5 {
-                        if (bits >> (4 - col)) & 1 == 1 {
-                            let px = cursor + col as u32;
-                            let py = y + row as u32;
-                            if px < canvas.width() && py < canvas.height() {
-                                canvas.put_pixel(px, py, Rgba([255, 255, 255, 255]));
-                            }
-                        }
-                    }
-                }
-            }
-            cursor += 6;
-        }
-    }
-    
-    fn glyph_pattern(ch: char) -> Option<&'static [u8; 7]> {
-        match ch {
-            'A' => Some(&[0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
-            'I' => Some(&[0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b11111]),
-            'O' => Some(&[0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
-            'R' => Some(&[0b11110, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001, 0b10001]),
-            'S' => Some(&[0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
-            'P' => Some(&[0b11110, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000, 0b10000]),
-            'E' => Some(&[0b11111, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000, 0b11111]),
-            'V' => Some(&[0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
-            'N' => Some(&[0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0b10001]),
-            'H' => Some(&[0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0b10001]),
-            'T' => Some(&[0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
-            '1' => Some(&[0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b11111]),
-            '2' => Some(&[0b01110, 0b10001, 0b00001, 0b00110, 0b01000, 0b10000, 0b11111]),
-            '3' => Some(&[0b01110, 0b10001, 0b00001, 0b00110, 0b00001, 0b10001, 0b01110]),
-            ' ' => Some(&[0, 0, 0, 0, 0, 0, 0]),
-            _ => None,
-        }
-    }
 }
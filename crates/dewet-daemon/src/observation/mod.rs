@@ -1,9 +1,24 @@
+mod recording;
+
 use std::collections::VecDeque;
 
 use chrono::{DateTime, Utc};
-use image::RgbaImage;
+use image::{
+    RgbaImage,
+    imageops::{FilterType, resize},
+};
+use tracing::warn;
+
+use crate::{
+    bridge::{ChatPacket, MemoryTier},
+    clipboard::ClipboardEvent,
+    config::{MemoryScope, ObservationConfig},
+    focus::Focus,
+    scheduler::ScheduledPrompt,
+    vision::{InputActivity, VisionFrame},
+};
 
-use crate::{bridge::{ChatPacket, MemoryTier}, config::ObservationConfig, vision::VisionFrame};
+pub use self::recording::{RecordedObservation, Recorder, load_recording};
 
 /// Stores a screenshot that resulted in an approved response
 #[derive(Clone)]
@@ -12,6 +27,16 @@ pub struct ApprovedScreenshot {
     pub timestamp: DateTime<Utc>,
 }
 
+/// Timeline frames are downsampled to this width, a bit over a PREV panel's
+const TIMELINE_WIDTH: u32 = 640;
+
+/// A downsampled frame in the rolling timeline
+#[derive(Clone)]
+pub struct TimelineFrame {
+    pub image: RgbaImage,
+    pub timestamp: DateTime<Utc>,
+}
+
 pub struct ObservationBuffer {
     config: ObservationConfig,
     screen_history: VecDeque<ScreenSummary>,
@@ -21,10 +46,28 @@ pub struct ObservationBuffer {
     approved_screenshots: VecDeque<ApprovedScreenshot>,
     /// User messages that arrived since last perception tick (to be batched)
     pending_user_messages: Vec<ChatPacket>,
+    /// Text copied since the last perception tick
+    pending_clipboard: Vec<ClipboardEvent>,
+    /// Scheduled prompts due since the last perception tick
+    pending_scheduled: Vec<ScheduledPrompt>,
+    /// Frames every `timeline.interval_secs`, oldest first
+    timeline: VecDeque<TimelineFrame>,
+    /// Where observations are written, with `[observation.recording]`
+    recorder: Option<Recorder>,
 }
 
 impl ObservationBuffer {
     pub fn new(config: ObservationConfig) -> Self {
+        let recorder = config
+            .recording
+            .as_ref()
+            .and_then(|recording| match Recorder::open(recording) {
+                Ok(recorder) => Some(recorder),
+                Err(err) => {
+                    warn!(?err, path = %recording.path, "Can't record observations");
+                    None
+                }
+            });
         Self {
             config,
             screen_history: VecDeque::new(),
@@ -32,15 +75,32 @@ impl ObservationBuffer {
             last_user_message: None,
             approved_screenshots: VecDeque::new(),
             pending_user_messages: Vec::new(),
+            pending_clipboard: Vec::new(),
+            pending_scheduled: Vec::new(),
+            timeline: VecDeque::new(),
+            recorder,
+        }
+    }
+
+    /// Append `observation` to the recording, if one is configured
+    pub fn record(&mut self, observation: &Observation) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if let Err(err) = recorder.record(observation) {
+            warn!(?err, "Failed to record observation");
         }
     }
     
     /// Record a screenshot that resulted in an approved response
     pub fn record_approved_screenshot(&mut self, image: RgbaImage) {
-        self.approved_screenshots.push_back(ApprovedScreenshot {
-            image,
-            timestamp: Utc::now(),
-        });
+        self.restore_approved_screenshot(image, Utc::now());
+    }
+
+    /// Add an approved screenshot taken earlier (e.g. loaded from storage)
+    pub fn restore_approved_screenshot(&mut self, image: RgbaImage, timestamp: DateTime<Utc>) {
+        let screenshot = ApprovedScreenshot { image, timestamp };
+        self.approved_screenshots.push_back(screenshot);
         // Keep only the last 3 approved screenshots
         while self.approved_screenshots.len() > 3 {
             self.approved_screenshots.pop_front();
@@ -52,6 +112,46 @@ impl ObservationBuffer {
         self.approved_screenshots.iter().collect()
     }
     
+    /// Keep a downsampled copy of `image` if a timeline is configured and
+    /// the last kept frame is an interval old
+    pub fn record_timeline_frame(&mut self, image: &RgbaImage, now: DateTime<Utc>) {
+        let Some(config) = &self.config.timeline else {
+            return;
+        };
+        let due = self
+            .timeline
+            .back()
+            .is_none_or(|last| (now - last.timestamp).num_seconds() >= config.interval_secs as i64);
+        if due {
+            self.timeline.push_back(TimelineFrame {
+                image: downscale(image, TIMELINE_WIDTH),
+                timestamp: now,
+            });
+        }
+        while self
+            .timeline
+            .front()
+            .is_some_and(|oldest| (now - oldest.timestamp).num_seconds() > config.span_secs as i64)
+        {
+            self.timeline.pop_front();
+        }
+    }
+
+    /// Up to `count` timeline frames spread evenly over the kept span, newest
+    /// first; None when no timeline is configured
+    pub fn timeline(&self, count: usize) -> Option<Vec<&TimelineFrame>> {
+        self.config.timeline.as_ref()?;
+        let len = self.timeline.len();
+        if len <= count {
+            return Some(self.timeline.iter().rev().collect());
+        }
+        Some(
+            (0..count)
+                .map(|k| &self.timeline[len - 1 - k * (len - 1) / (count - 1).max(1)])
+                .collect(),
+        )
+    }
+
     /// Queue a user message to be processed in the next perception tick
     pub fn queue_user_message(&mut self, packet: ChatPacket) {
         self.pending_user_messages.push(packet);
@@ -74,6 +174,21 @@ impl ObservationBuffer {
         messages
     }
     
+    /// Record copied text for the next perception tick
+    pub fn record_clipboard(&mut self, event: ClipboardEvent) {
+        self.pending_clipboard.push(event);
+        // Only the latest few copies matter
+        while self.pending_clipboard.len() > 3 {
+            self.pending_clipboard.remove(0);
+        }
+    }
+
+    /// Record a scheduled prompt for the next perception tick. Prompts wait
+    /// while capture is paused, so they reach the user once they are back
+    pub fn record_scheduled(&mut self, prompt: ScheduledPrompt) {
+        self.pending_scheduled.push(prompt);
+    }
+
     /// Check if there are pending user messages
     pub fn has_pending_messages(&self) -> bool {
         !self.pending_user_messages.is_empty()
@@ -101,6 +216,9 @@ impl ObservationBuffer {
             screen_summary: summary,
             recent_chat: filtered_chat,
             all_chat: self.chat_history.iter().cloned().collect(),
+            clipboard: std::mem::take(&mut self.pending_clipboard),
+            scheduled: std::mem::take(&mut self.pending_scheduled),
+            focus: None,
             seconds_since_user_message: self
                 .last_user_message
                 .map(|ts| (Utc::now() - ts).num_seconds().max(0) as u64)
@@ -121,6 +239,22 @@ impl ObservationBuffer {
         }
     }
     
+    /// Put `text` in place of `character_id`'s latest message, returning
+    /// the message as replaced; None if it's no longer in the history
+    pub fn replace_reply(&mut self, character_id: &str, text: &str) -> Option<ChatPacket> {
+        let packet = self
+            .chat_history
+            .iter_mut()
+            .rev()
+            .find(|p| p.sender == character_id)?;
+        packet.content = text.to_string();
+        Some(packet.clone())
+    }
+
+    pub fn chat_history(&self) -> impl Iterator<Item = &ChatPacket> {
+        self.chat_history.iter()
+    }
+
     pub fn chat_count(&self) -> usize {
         self.chat_history.len()
     }
@@ -201,6 +335,10 @@ pub struct ScreenSummary {
     pub timestamp: DateTime<Utc>,
     pub diff_score: f32,
     pub notes: String,
+    /// Text read off the screen by OCR
+    pub text: Option<String>,
+    /// Keyboard and mouse activity, when tracked
+    pub activity: Option<InputActivity>,
 }
 
 impl ScreenSummary {
@@ -211,17 +349,25 @@ impl ScreenSummary {
             frame.image.width(),
             frame.image.height()
         );
-        if frame.diff_score < 0.02 {
+        if frame.duplicate {
+            notes.push_str(" • unchanged");
+        } else if frame.diff_score < 0.02 {
             notes.push_str(" • stable view");
         }
+        if let Some(activity) = &frame.activity {
+            notes.push_str(&format!(" • {}", activity.summary()));
+        }
         Self {
             timestamp: frame.timestamp,
             diff_score: frame.diff_score,
             notes,
+            text: frame.text.clone(),
+            activity: frame.activity,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Observation {
     pub frame: VisionFrame,
     pub composite: Option<RgbaImage>,
@@ -232,5 +378,67 @@ pub struct Observation {
     pub recent_chat: Vec<ChatPacket>,
     /// Full chat history for rendering (includes cold)
     pub all_chat: Vec<ChatPacket>,
+    /// Text copied since the previous observation, oldest first
+    pub clipboard: Vec<ClipboardEvent>,
+    /// Scheduled prompts and reminders that came due, oldest first
+    pub scheduled: Vec<ScheduledPrompt>,
+    /// Set while the user shouldn't be interrupted
+    pub focus: Option<Focus>,
     pub seconds_since_user_message: u64,
 }
+
+impl Observation {
+    /// Recent chat as one companion remembers it: everything with shared
+    /// memory, otherwise shared messages and its own conversation
+    pub fn chat_for(&self, character_id: &str, scope: MemoryScope) -> Vec<&ChatPacket> {
+        self.recent_chat
+            .iter()
+            .filter(|p| scope == MemoryScope::Shared || p.visible_to(character_id))
+            .collect()
+    }
+}
+
+/// `image` scaled down to `width`, keeping its aspect ratio, if wider
+fn downscale(image: &RgbaImage, width: u32) -> RgbaImage {
+    if image.width() <= width {
+        return image.clone();
+    }
+    let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+    resize(image, width, height, FilterType::Triangle)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use image::Rgba;
+
+    use super::*;
+    use crate::config::TimelineConfig;
+
+    #[test]
+    fn test_timeline_keeps_spaced_recent_frames() {
+        let mut buffer = ObservationBuffer::new(ObservationConfig {
+            timeline: Some(TimelineConfig::default()),
+            ..Default::default()
+        });
+        let frame = RgbaImage::from_pixel(1920, 1080, Rgba([0, 0, 0, 255]));
+        let start = Utc::now();
+        // A frame every 5 seconds for 3 minutes
+        for tick in 0..=36 {
+            buffer.record_timeline_frame(&frame, start + Duration::seconds(tick * 5));
+        }
+
+        let now = start + Duration::seconds(180);
+        let timeline = buffer.timeline(3).unwrap();
+        let ages: Vec<_> = timeline
+            .iter()
+            .map(|f| (now - f.timestamp).num_seconds())
+            .collect();
+        // Every 10 seconds, the last 2 minutes kept
+        assert_eq!(ages, vec![0, 60, 120]);
+        assert_eq!(timeline[0].image.dimensions(), (640, 360));
+
+        let off = ObservationBuffer::new(ObservationConfig::default());
+        assert!(off.timeline(3).is_none());
+    }
+}
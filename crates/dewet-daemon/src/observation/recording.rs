@@ -0,0 +1,258 @@
+//! Observations recorded to disk, one JSON object per line, so a session can
+//! be fed back through the director (`director::replay`) to see how a prompt
+//! or config change moves its decisions
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use super::{Observation, ScreenSummary, downscale};
+use crate::{
+    bridge::ChatPacket,
+    clipboard::ClipboardEvent,
+    config::RecordingConfig,
+    focus::Focus,
+    scheduler::ScheduledPrompt,
+    vision::{InputActivity, VisionFrame},
+};
+
+/// One observation as written to a recording. Images are base64 PNGs, and
+/// the desktop frame itself isn't kept: the composite already shows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedObservation {
+    /// Milliseconds after the first observation of the recording
+    pub offset_ms: u64,
+    pub timestamp: DateTime<Utc>,
+    pub diff_score: f32,
+    /// The frame's perceptual hash
+    #[serde(default)]
+    pub hash: u64,
+    pub duplicate: bool,
+    #[serde(default)]
+    pub composite: Option<String>,
+    #[serde(default)]
+    pub ariaos: Option<String>,
+    pub screen_notes: String,
+    #[serde(default)]
+    pub screen_text: Option<String>,
+    #[serde(default)]
+    pub activity: Option<InputActivity>,
+    pub recent_chat: Vec<ChatPacket>,
+    #[serde(default)]
+    pub clipboard: Vec<ClipboardEvent>,
+    #[serde(default)]
+    pub scheduled: Vec<ScheduledPrompt>,
+    #[serde(default)]
+    pub focus: Option<Focus>,
+    pub seconds_since_user_message: u64,
+}
+
+impl RecordedObservation {
+    /// `observation` with its images no wider than `max_width`
+    pub fn new(observation: &Observation, offset_ms: u64, max_width: u32) -> Result<Self> {
+        let encode = |image: &Option<RgbaImage>| {
+            image
+                .as_ref()
+                .map(|image| encode_png(&downscale(image, max_width)))
+                .transpose()
+        };
+        Ok(Self {
+            offset_ms,
+            timestamp: observation.frame.timestamp,
+            diff_score: observation.frame.diff_score,
+            hash: observation.frame.hash,
+            duplicate: observation.frame.duplicate,
+            composite: encode(&observation.composite)?,
+            ariaos: encode(&observation.ariaos)?,
+            screen_notes: observation.screen_summary.notes.clone(),
+            screen_text: observation.screen_summary.text.clone(),
+            activity: observation.screen_summary.activity,
+            recent_chat: observation.recent_chat.clone(),
+            clipboard: observation.clipboard.clone(),
+            scheduled: observation.scheduled.clone(),
+            focus: observation.focus.clone(),
+            seconds_since_user_message: observation.seconds_since_user_message,
+        })
+    }
+
+    /// The observation again, with the composite standing in for the frame
+    pub fn to_observation(&self) -> Result<Observation> {
+        let composite = self.composite.as_deref().map(decode_png).transpose()?;
+        let ariaos = self.ariaos.as_deref().map(decode_png).transpose()?;
+        let image = composite
+            .clone()
+            .unwrap_or_else(|| RgbaImage::new(1, 1));
+        Ok(Observation {
+            frame: VisionFrame {
+                timestamp: self.timestamp,
+                image: DynamicImage::ImageRgba8(image),
+                diff_score: self.diff_score,
+                text: self.screen_text.clone(),
+                close_up: None,
+                hash: self.hash,
+                duplicate: self.duplicate,
+                camera: None,
+                tiles: None,
+                activity: self.activity,
+                foreground: None,
+            },
+            composite,
+            ariaos,
+            screen_summary: ScreenSummary {
+                timestamp: self.timestamp,
+                diff_score: self.diff_score,
+                notes: self.screen_notes.clone(),
+                text: self.screen_text.clone(),
+                activity: self.activity,
+            },
+            recent_chat: self.recent_chat.clone(),
+            all_chat: self.recent_chat.clone(),
+            clipboard: self.clipboard.clone(),
+            scheduled: self.scheduled.clone(),
+            focus: self.focus.clone(),
+            seconds_since_user_message: self.seconds_since_user_message,
+        })
+    }
+}
+
+/// Appends observations to `[observation.recording] path`
+pub struct Recorder {
+    file: LineWriter<File>,
+    max_width: u32,
+    started: Option<Instant>,
+}
+
+impl Recorder {
+    /// Open the recording for appending, creating it and its directory
+    pub fn open(config: &RecordingConfig) -> Result<Self> {
+        let path = Path::new(&config.path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening {}", path.display()))?;
+        Ok(Self {
+            file: LineWriter::new(file),
+            max_width: config.max_width,
+            started: None,
+        })
+    }
+
+    pub fn record(&mut self, observation: &Observation) -> Result<()> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let offset_ms = started.elapsed().as_millis() as u64;
+        let recorded = RecordedObservation::new(observation, offset_ms, self.max_width)?;
+        serde_json::to_writer(&mut self.file, &recorded)?;
+        self.file.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Every observation in the recording at `path`, in order
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedObservation>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|(index, line)| {
+            serde_json::from_str(&line?)
+                .with_context(|| format!("{}:{}", path.display(), index + 1))
+        })
+        .collect()
+}
+
+fn encode_png(image: &RgbaImage) -> Result<String> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, ImageFormat::Png)?;
+    Ok(BASE64.encode(bytes.into_inner()))
+}
+
+fn decode_png(data: &str) -> Result<RgbaImage> {
+    let bytes = BASE64.decode(data)?;
+    Ok(image::load_from_memory(&bytes)?.to_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::bridge::MemoryTier;
+
+    #[test]
+    fn test_recording_round_trips() {
+        let path = std::env::temp_dir().join(format!("dewet-recording-{}.jsonl", uuid::Uuid::new_v4()));
+        let config = RecordingConfig {
+            path: path.to_string_lossy().into_owned(),
+            max_width: 100,
+        };
+        let image = RgbaImage::from_pixel(400, 200, Rgba([10, 20, 30, 255]));
+        let now = Utc::now();
+        let observation = Observation {
+            frame: VisionFrame {
+                timestamp: now,
+                image: DynamicImage::ImageRgba8(image.clone()),
+                diff_score: 0.25,
+                text: Some("cargo test".into()),
+                close_up: None,
+                hash: 0,
+                duplicate: false,
+                camera: None,
+                tiles: None,
+                activity: None,
+                foreground: None,
+            },
+            composite: Some(image),
+            ariaos: None,
+            screen_summary: ScreenSummary {
+                timestamp: now,
+                diff_score: 0.25,
+                notes: "terminal".into(),
+                text: Some("cargo test".into()),
+                activity: None,
+            },
+            recent_chat: vec![ChatPacket {
+                sender: "user".into(),
+                content: "why is this failing".into(),
+                timestamp: now.timestamp(),
+                relevance: 1.0,
+                tier: MemoryTier::Hot,
+                character_id: None,
+            }],
+            all_chat: Vec::new(),
+            clipboard: Vec::new(),
+            scheduled: Vec::new(),
+            focus: None,
+            seconds_since_user_message: 3,
+        };
+
+        let mut recorder = Recorder::open(&config).unwrap();
+        recorder.record(&observation).unwrap();
+        recorder.record(&observation).unwrap();
+        drop(recorder);
+
+        let recording = load_recording(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recording.len(), 2);
+        assert!(recording[0].offset_ms <= recording[1].offset_ms);
+        let replayed = recording[0].to_observation().unwrap();
+        assert_eq!(replayed.composite.unwrap().dimensions(), (100, 50));
+        assert_eq!(replayed.screen_summary.text.as_deref(), Some("cargo test"));
+        assert_eq!(replayed.recent_chat[0].content, "why is this failing");
+        assert_eq!(replayed.seconds_since_user_message, 3);
+    }
+}
@@ -0,0 +1,285 @@
+//! Initiative that doesn't wait for the screen: `[scheduler]` entries run on
+//! cron-like schedules ("0 9 * * 1-5" for a weekday morning greeting) and
+//! reminders companions set come back when due
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Datelike, Duration, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{ScheduleEntry, SchedulerConfig},
+    storage::Storage,
+};
+
+/// Minutes missed while the daemon was suspended are caught up to this many
+const MAX_CATCH_UP_MINUTES: i64 = 60;
+
+/// Something due now
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduledEvent {
+    /// Evaluate with this as a reason to speak
+    Prompt(ScheduledPrompt),
+    /// Said exactly as written, by `character` or any companion
+    Message {
+        character: Option<String>,
+        text: String,
+    },
+}
+
+/// A reason to speak handed to the director with the next observation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledPrompt {
+    pub timestamp: DateTime<Utc>,
+    /// Companion who should speak; any of them when None
+    pub character: Option<String>,
+    pub text: String,
+}
+
+/// Start the scheduler if `[scheduler]` is enabled. Due entries and
+/// reminders arrive on the returned channel, which is closed straight away
+/// when scheduling is off.
+pub fn watch(
+    config: &SchedulerConfig,
+    storage: Storage,
+) -> mpsc::UnboundedReceiver<ScheduledEvent> {
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    if !config.enabled {
+        return event_rx;
+    }
+    let entries: Vec<(CronSchedule, ScheduleEntry)> = config
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            if entry.message.is_none() && entry.prompt.is_none() {
+                warn!(cron = %entry.cron, "Schedule entry has no message or prompt; skipping");
+                return None;
+            }
+            match CronSchedule::parse(&entry.cron) {
+                Ok(schedule) => Some((schedule, entry.clone())),
+                Err(err) => {
+                    warn!(cron = %entry.cron, "Invalid schedule entry: {err:#}");
+                    None
+                }
+            }
+        })
+        .collect();
+    info!(entries = entries.len(), "Scheduler enabled");
+
+    let check_interval = config.check_interval();
+    tokio::spawn(async move {
+        // Entries for the minute the daemon starts in still run
+        let mut checked = start_of_minute(Local::now()) - Duration::minutes(1);
+        let mut interval = tokio::time::interval(check_interval);
+        loop {
+            interval.tick().await;
+            let now = start_of_minute(Local::now());
+            for event in due_entries(&entries, checked, now) {
+                if event_tx.send(event).is_err() {
+                    return;
+                }
+            }
+            checked = checked.max(now);
+
+            let reminders = match storage.take_due_reminders(Utc::now().timestamp()).await {
+                Ok(reminders) => reminders,
+                Err(err) => {
+                    warn!("Failed to read due reminders: {err:#}");
+                    continue;
+                }
+            };
+            for reminder in reminders {
+                debug!(id = reminder.id, "Reminder due");
+                let prompt = ScheduledPrompt {
+                    timestamp: Utc::now(),
+                    character: reminder.character_id,
+                    text: format!("Remind the user: {}", reminder.text),
+                };
+                if event_tx.send(ScheduledEvent::Prompt(prompt)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    event_rx
+}
+
+/// Events for entries matching a minute in `(after, until]`, each entry at
+/// most once
+fn due_entries(
+    entries: &[(CronSchedule, ScheduleEntry)],
+    after: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<ScheduledEvent> {
+    let first = after.max(until - Duration::minutes(MAX_CATCH_UP_MINUTES)) + Duration::minutes(1);
+    let minutes: Vec<DateTime<Local>> = (0..)
+        .map(|n| first + Duration::minutes(n))
+        .take_while(|minute| *minute <= until)
+        .collect();
+    entries
+        .iter()
+        .filter(|(schedule, _)| minutes.iter().any(|minute| schedule.matches(minute)))
+        .map(|(_, entry)| {
+            debug!(cron = %entry.cron, "Schedule entry due");
+            match &entry.message {
+                Some(text) => ScheduledEvent::Message {
+                    character: entry.character.clone(),
+                    text: text.clone(),
+                },
+                None => ScheduledEvent::Prompt(ScheduledPrompt {
+                    timestamp: Utc::now(),
+                    character: entry.character.clone(),
+                    text: entry.prompt.clone().unwrap_or_default(),
+                }),
+            }
+        })
+        .collect()
+}
+
+fn start_of_minute(time: DateTime<Local>) -> DateTime<Local> {
+    time.with_second(0)
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(time)
+}
+
+/// A five-field cron expression: minute, hour, day of month, month and day
+/// of week (0 or 7 is Sunday). Fields take `*`, numbers, `a-b` ranges,
+/// `/step` and comma lists. As in cron, when both day fields are
+/// restricted either one matching is enough.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("Expected 5 fields, got {}", fields.len());
+        };
+        let mut weekdays = parse_field(weekday, 0, 7).context("day of week")?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).context("minute")?,
+            hours: parse_field(hour, 0, 23).context("hour")?,
+            days: parse_field(day, 1, 31).context("day of month")?,
+            months: parse_field(month, 1, 12).context("month")?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule fires in `time`'s minute
+    pub fn matches<T: Datelike + Timelike>(&self, time: &T) -> bool {
+        let has = |set: u64, value: u32| set & (1 << value) != 0;
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+            && day_matches
+    }
+}
+
+/// A field's allowed values as a bit set
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)),
+            None => (part, Some(1)),
+        };
+        let Some(step) = step else {
+            bail!("Invalid step in {part:?}");
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                let parse = |n: &str| {
+                    n.parse::<u32>()
+                        .with_context(|| format!("Invalid value {n:?}"))
+                };
+                let start = parse(start)?;
+                // "5/15" means from 5 to the end
+                let end = if part.contains('/') && !range.contains('-') {
+                    max
+                } else {
+                    parse(end)?
+                };
+                (start, end)
+            }
+        };
+        if start < min || end > max || start > end {
+            bail!("{part:?} is outside {min}-{max}");
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // May 2024: the 1st is a Wednesday
+        NaiveDate::from_ymd_opt(2024, 5, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        let weekday_mornings = CronSchedule::parse("0 9 * * 1-5").unwrap();
+        assert!(weekday_mornings.matches(&at(1, 9, 0)));
+        assert!(!weekday_mornings.matches(&at(1, 9, 1)));
+        assert!(!weekday_mornings.matches(&at(4, 9, 0)));
+
+        let half_hourly = CronSchedule::parse("*/30 9-17 * * *").unwrap();
+        assert!(half_hourly.matches(&at(4, 17, 30)));
+        assert!(!half_hourly.matches(&at(4, 18, 0)));
+
+        // Either day field may match once both are restricted
+        let first_or_sunday = CronSchedule::parse("0 0 1 * 7").unwrap();
+        assert!(first_or_sunday.matches(&at(1, 0, 0)));
+        assert!(first_or_sunday.matches(&at(5, 0, 0)));
+        assert!(!first_or_sunday.matches(&at(6, 0, 0)));
+
+        assert_eq!(
+            CronSchedule::parse("@daily").unwrap(),
+            CronSchedule::parse("0 0 * * *").unwrap()
+        );
+        assert!(CronSchedule::parse("0 9 * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+}
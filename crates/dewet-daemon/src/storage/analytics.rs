@@ -0,0 +1,160 @@
+//! Decision analytics: how often each companion gets to speak, why turns
+//! pass and when replies happen, for tuning cooldowns and thresholds
+
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::Storage;
+
+/// What came of a turn. Rate-limited ticks are not logged as decisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionOutcome {
+    /// A companion replied
+    Spoke,
+    /// The arbiter chose nobody
+    Declined,
+    /// Every companion was ineligible, so the arbiter didn't run
+    NoEligible,
+    /// The user was silent and nothing changed, so the arbiter didn't run
+    NoStimulus,
+    /// The arbiter chose a companion that doesn't exist
+    UnknownCompanion,
+    /// The arbiter chose a companion that wasn't eligible
+    Ineligible,
+    /// The chosen companion was on cooldown
+    Cooldown,
+    /// The audit model rejected the reply
+    AuditRejected,
+    /// The user was focused (see `[focus]`) and the reply wasn't urgent enough
+    DoNotDisturb,
+    /// The arbiter was less sure of its pick than `min_confidence`
+    LowConfidence,
+    /// The screening model found nothing worth the VLA and arbiter
+    Screened,
+    /// The screen matched the previous frames, so the VLA and arbiter didn't run
+    Duplicate,
+    /// A companion already commented on the topic within `[director.topics]
+    /// suppress_minutes`
+    Repeated,
+}
+
+impl DecisionOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DecisionOutcome::Spoke => "spoke",
+            DecisionOutcome::Declined => "declined",
+            DecisionOutcome::NoEligible => "no_eligible",
+            DecisionOutcome::NoStimulus => "no_stimulus",
+            DecisionOutcome::UnknownCompanion => "unknown_companion",
+            DecisionOutcome::Ineligible => "ineligible",
+            DecisionOutcome::Cooldown => "cooldown",
+            DecisionOutcome::AuditRejected => "audit_rejected",
+            DecisionOutcome::DoNotDisturb => "do_not_disturb",
+            DecisionOutcome::LowConfidence => "low_confidence",
+            DecisionOutcome::Screened => "screened",
+            DecisionOutcome::Duplicate => "duplicate",
+            DecisionOutcome::Repeated => "repeated",
+        }
+    }
+}
+
+/// Aggregates over the decisions in a window
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DecisionStats {
+    /// Start of the window (unix seconds)
+    pub since: i64,
+    pub decisions: u64,
+    pub responses: u64,
+    /// Per companion the arbiter chose, by id
+    pub characters: Vec<CharacterDecisionStats>,
+    /// Outcomes other than `spoke`, most common first
+    pub pass_reasons: Vec<PassReasonCount>,
+    /// Oldest first; hours without a reply are left out
+    pub responses_per_hour: Vec<HourlyResponses>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterDecisionStats {
+    pub character_id: String,
+    /// Times the arbiter picked this companion
+    pub chosen: u64,
+    /// Times it then spoke
+    pub spoke: u64,
+    /// `spoke / chosen`
+    pub approval_rate: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PassReasonCount {
+    /// A `DecisionOutcome` name
+    pub reason: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HourlyResponses {
+    /// Start of the hour (unix seconds)
+    pub hour: i64,
+    pub responses: u64,
+}
+
+impl Storage {
+    /// Decision analytics for the last `hours`
+    pub async fn decision_stats(&self, hours: u32) -> Result<DecisionStats> {
+        self.flush().await?;
+        let since = Utc::now().timestamp() - hours as i64 * 3600;
+        self.db.decision_stats(since).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::StorageConfig, storage::StoredDecision};
+
+    #[tokio::test]
+    async fn test_decision_stats() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let decisions = [
+            (Some("ari"), DecisionOutcome::Spoke),
+            (Some("ari"), DecisionOutcome::Cooldown),
+            (Some("ari"), DecisionOutcome::Cooldown),
+            (Some("ari"), DecisionOutcome::Spoke),
+            (Some("bea"), DecisionOutcome::AuditRejected),
+            (None, DecisionOutcome::NoStimulus),
+        ];
+        for (responder, outcome) in decisions {
+            let responder = responder.map(String::from);
+            let decision = StoredDecision::now(responder.is_some(), responder, "", 0.5);
+            storage.record_decision(&decision.with_outcome(outcome));
+        }
+
+        let stats = storage.decision_stats(24).await.unwrap();
+        assert_eq!((stats.decisions, stats.responses), (6, 2));
+        let reasons: Vec<_> = stats
+            .pass_reasons
+            .iter()
+            .map(|r| (r.reason.as_str(), r.count))
+            .collect();
+        assert_eq!(
+            reasons,
+            [("cooldown", 2), ("audit_rejected", 1), ("no_stimulus", 1)]
+        );
+        let ari = &stats.characters[0];
+        assert_eq!(
+            (ari.character_id.as_str(), ari.chosen, ari.spoke),
+            ("ari", 4, 2)
+        );
+        assert_eq!(ari.approval_rate, 0.5);
+        assert_eq!(stats.characters[1].approval_rate, 0.0);
+        let hourly: u64 = stats.responses_per_hour.iter().map(|h| h.responses).sum();
+        assert_eq!(hourly, 2);
+    }
+}
@@ -0,0 +1,100 @@
+//! ARIAOS app state: each app keeps one JSON document under its app id
+//! (`notes`, or `notes:<companion>` with per-character memory)
+
+use anyhow::Result;
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::{AriaosNotesState, Storage};
+
+pub(super) const NOTES_APP: &str = "notes";
+
+impl Storage {
+    /// Save an ARIAOS app's state, as `character_id`'s with per-character
+    /// memory
+    pub async fn save_app_state<T: Serialize>(
+        &self,
+        app: &str,
+        character_id: Option<&str>,
+        state: &T,
+    ) -> Result<()> {
+        let state_json = serde_json::to_string(state)?;
+        self.db
+            .save_app_state(&self.app_id(app, character_id), &state_json)
+            .await
+    }
+
+    /// Load an ARIAOS app's state, as `character_id`'s with per-character
+    /// memory
+    pub async fn load_app_state<T: DeserializeOwned>(
+        &self,
+        app: &str,
+        character_id: Option<&str>,
+    ) -> Result<Option<T>> {
+        let state_json = self
+            .db
+            .load_app_state(&self.app_id(app, character_id))
+            .await?;
+        match state_json {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save ARIAOS Notes state
+    pub async fn save_ariaos_notes(
+        &self,
+        character_id: Option<&str>,
+        state: &AriaosNotesState,
+    ) -> Result<()> {
+        self.save_app_state(NOTES_APP, character_id, state).await
+    }
+
+    /// Load ARIAOS Notes state
+    pub async fn load_ariaos_notes(
+        &self,
+        character_id: Option<&str>,
+    ) -> Result<Option<AriaosNotesState>> {
+        self.load_app_state(NOTES_APP, character_id).await
+    }
+
+    fn app_id(&self, app: &str, character_id: Option<&str>) -> String {
+        match self.namespace(character_id) {
+            Some(id) => format!("{app}:{id}"),
+            None => app.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::config::StorageConfig;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Timer {
+        remaining_secs: u32,
+        label: String,
+    }
+
+    #[tokio::test]
+    async fn test_app_state_round_trip() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let timer = Timer {
+            remaining_secs: 300,
+            label: "tea".into(),
+        };
+        storage.save_app_state("timer", None, &timer).await.unwrap();
+
+        let loaded: Option<Timer> = storage.load_app_state("timer", None).await.unwrap();
+        assert_eq!(loaded, Some(timer));
+        let missing: Option<Timer> = storage.load_app_state("alarm", None).await.unwrap();
+        assert!(missing.is_none());
+    }
+}
@@ -0,0 +1,166 @@
+//! Memory archives for moving a companion between machines: a JSONL file
+//! with a header line, then one `{"table", "row"}` object per row
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::Storage;
+
+const FORMAT: &str = "dewet-memory";
+const VERSION: u32 = 1;
+
+/// Chat, episodes, decisions, character states, ARIAOS state, the user
+/// profile and reminders
+const ARCHIVE_TABLES: &[&str] = &[
+    "sessions",
+    "chat_messages",
+    "episodes",
+    "arbiter_decisions",
+    "character_states",
+    "ariaos_app_state",
+    "notes_revisions",
+    "user_profile",
+    "reminders",
+];
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    exported_at: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    table: String,
+    row: Value,
+}
+
+impl Storage {
+    /// Write every archived table to `path`, returning the number of rows
+    pub async fn export_archive(&self, path: &Path) -> Result<usize> {
+        let export = self.db.export_tables(ARCHIVE_TABLES).await?;
+        let file = fs::File::create(path).with_context(|| format!("Failed to create {path:?}"))?;
+        let mut out = BufWriter::new(file);
+        let header = Header {
+            format: FORMAT.into(),
+            version: VERSION,
+            exported_at: Utc::now().timestamp(),
+        };
+        serde_json::to_writer(&mut out, &header)?;
+        writeln!(out)?;
+        let mut count = 0;
+        for table in ARCHIVE_TABLES {
+            for row in export[*table].as_array().into_iter().flatten() {
+                let line = Line {
+                    table: table.to_string(),
+                    row: row.clone(),
+                };
+                serde_json::to_writer(&mut out, &line)?;
+                writeln!(out)?;
+                count += 1;
+            }
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /// Load an archive written by `export_archive`. Rows replace existing
+    /// ones with the same id, so this is meant for a new or restored install.
+    pub async fn import_archive(&self, path: &Path) -> Result<u64> {
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+        let mut lines = BufReader::new(file).lines();
+        let header: Header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).context("Not a Dewet memory archive")?,
+            None => bail!("Archive is empty"),
+        };
+        if header.format != FORMAT || header.version > VERSION {
+            bail!("Unsupported archive {} v{}", header.format, header.version);
+        }
+
+        // Parse everything first so a corrupt file imports nothing
+        let mut tables: Vec<(&str, Vec<Value>)> =
+            ARCHIVE_TABLES.iter().map(|t| (*t, Vec::new())).collect();
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Line { table, row } = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid archive line {}", number + 2))?;
+            // Archives from before app state was generalized
+            let table = match table.as_str() {
+                "ariaos_state" => "ariaos_app_state".to_string(),
+                _ => table,
+            };
+            match tables.iter_mut().find(|(name, _)| *name == table) {
+                Some((_, rows)) => rows.push(row),
+                None => bail!("Unknown table {table:?} in archive"),
+            }
+        }
+
+        let mut written = 0;
+        for (table, rows) in &tables {
+            written += self.db.import_rows(table, rows).await?;
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::StorageConfig,
+        storage::{AriaosNotesState, Episode, EpisodeKind},
+    };
+
+    async fn memory_storage() -> Storage {
+        Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_archive_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("dewet-archive-{}.jsonl", uuid::Uuid::new_v4()));
+        let source = memory_storage().await;
+        source
+            .db
+            .add_chat_message("user", "hello", None)
+            .await
+            .unwrap();
+        let mut episode = Episode::now(EpisodeKind::UserMessage, Some("user".into()), "hello");
+        episode.embedding = Some(vec![0.5, -0.25]);
+        source.record_episode(&episode).await.unwrap();
+        let notes = AriaosNotesState {
+            content: "buy milk".into(),
+            scroll_offset: 0.0,
+            owner: None,
+        };
+        source.save_ariaos_notes(None, &notes).await.unwrap();
+        assert_eq!(source.export_archive(&path).await.unwrap(), 3);
+
+        let target = memory_storage().await;
+        assert_eq!(target.import_archive(&path).await.unwrap(), 3);
+        fs::remove_file(&path).ok();
+        let chat = target.recent_chat(10).await.unwrap();
+        assert_eq!(chat[0].content, "hello");
+        let episodes = target.db.get_recent_episodes(10).await.unwrap();
+        assert_eq!(episodes[0].embedding, Some(vec![0.5, -0.25]));
+        let loaded = target.load_ariaos_notes(None).await.unwrap().unwrap();
+        assert_eq!(loaded.content, "buy milk");
+    }
+}
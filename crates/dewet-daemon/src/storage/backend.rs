@@ -0,0 +1,271 @@
+//! The database behind `Storage`. `TursoDb` (libSQL: local files, `:memory:`
+//! and remote Turso) is built in; other databases implement
+//! `StorageBackend` and are passed to `Storage::with_backend`
+
+use std::{path::Path, sync::Arc};
+
+use anyhow::{Result, bail};
+
+use super::{
+    CharacterState, ChatMessage, ChatSession, DecisionStats, Episode, NotesRevision, Reminder,
+    SpatialContext, StoredDecision, StoredLlmCall, StoredSnapshot, TursoDb, UserProfile,
+};
+
+/// Persistence for memories, chat, decisions and ARIAOS state. Rows are
+/// exchanged as the `storage` types; `export_tables` and `import_rows` use
+/// the column names of the built-in schema so archives move between backends
+#[async_trait::async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Whether the database lives on another machine, so backups export
+    /// rows rather than copy a file
+    fn is_remote(&self) -> bool;
+
+    /// Write a consistent copy of a local database to a new SQLite file
+    async fn snapshot_to(&self, path: &Path) -> Result<()>;
+
+    /// Every row of the given tables, as `{table: [{column: value}]}`
+    async fn export_tables(&self, tables: &[&str]) -> Result<serde_json::Value>;
+
+    /// Insert rows exported by `export_tables`, replacing rows with the same
+    /// key; returns how many were written
+    async fn import_rows(&self, table: &str, rows: &[serde_json::Value]) -> Result<u64>;
+
+    /// Create missing tables and columns
+    async fn initialize_schema(&self) -> Result<()>;
+
+    /// Add an episode to memory
+    async fn add_episode(&self, episode: &Episode) -> Result<()>;
+
+    /// Get recent episodes
+    async fn get_recent_episodes(&self, limit: usize) -> Result<Vec<Episode>>;
+
+    /// Store the embedding of an existing episode
+    async fn set_episode_embedding(&self, id: &str, embedding: &[f32]) -> Result<()>;
+
+    /// The `k` embedded episodes most similar to `query`, with their cosine
+    /// similarity, best first. With a `character_id`, only shared episodes
+    /// and that companion's are searched.
+    async fn search_similar(
+        &self,
+        query: &[f32],
+        k: usize,
+        character_id: Option<&str>,
+    ) -> Result<Vec<(Episode, f32)>>;
+
+    /// Add a chat message
+    async fn add_chat_message(
+        &self,
+        sender: &str,
+        content: &str,
+        character_id: Option<&str>,
+    ) -> Result<i64>;
+
+    /// Add a chat message with its relevance, tier and session (`id` is
+    /// assigned and returned)
+    async fn insert_chat_message(&self, message: &ChatMessage) -> Result<i64>;
+
+    /// Update the relevance and tier of the chat message `sender` sent at
+    /// `timestamp` (content isn't compared, as it may be encrypted)
+    async fn update_chat_relevance(
+        &self,
+        timestamp: i64,
+        sender: &str,
+        relevance: f32,
+        tier: &str,
+    ) -> Result<()>;
+
+    /// Replace the content of the chat message `sender` sent at `timestamp`
+    async fn update_chat_content(&self, timestamp: i64, sender: &str, content: &str) -> Result<()>;
+
+    /// Get recent chat messages
+    async fn get_recent_chat(&self, limit: usize) -> Result<Vec<ChatMessage>>;
+
+    /// Attach activity at `timestamp` to the open session, closing it and
+    /// starting a new one if it has been idle longer than `idle_secs`
+    async fn touch_session(&self, timestamp: i64, idle_secs: i64) -> Result<i64>;
+
+    /// End open sessions with no activity since `cutoff`
+    async fn close_sessions_idle_since(&self, cutoff: i64) -> Result<u64>;
+
+    /// The newest sessions first
+    async fn get_sessions(&self, limit: usize) -> Result<Vec<ChatSession>>;
+
+    /// Every chat message in a session, oldest first
+    async fn get_session_chat(&self, session_id: i64) -> Result<Vec<ChatMessage>>;
+
+    /// Log an arbiter decision under an id chosen by the caller
+    async fn log_arbiter_decision(&self, id: i64, decision: &StoredDecision) -> Result<()>;
+
+    /// The highest decision id in use (by decisions or snapshots), or 0
+    async fn last_decision_id(&self) -> Result<i64>;
+
+    /// Counts of decisions since `since` (unix seconds) by outcome,
+    /// companion and hour
+    async fn decision_stats(&self, since: i64) -> Result<DecisionStats>;
+
+    /// Log an LLM prompt/response exchange
+    async fn log_llm_call(&self, call: &StoredLlmCall) -> Result<()>;
+
+    /// Get recent LLM exchanges, newest first
+    async fn get_recent_llm_calls(&self, limit: usize) -> Result<Vec<StoredLlmCall>>;
+
+    /// Get character state
+    async fn get_character_state(&self, character_id: &str) -> Result<Option<CharacterState>>;
+
+    /// Update character state
+    async fn update_character_state(&self, state: &CharacterState) -> Result<()>;
+
+    /// Decay importance of old memories
+    async fn decay_importance(&self, decay_factor: f32, min_age_hours: i64) -> Result<u64>;
+
+    /// Prune forgotten memories
+    async fn prune_forgotten(&self, threshold: f32) -> Result<u64>;
+
+    /// Delete chat messages older than `cutoff` (unix seconds)
+    async fn delete_chat_before(&self, cutoff: i64) -> Result<u64>;
+
+    /// Delete arbiter decisions older than `cutoff` (unix seconds)
+    async fn delete_decisions_before(&self, cutoff: i64) -> Result<u64>;
+
+    /// Store an encoded snapshot, deleting all but the newest `keep` of its kind
+    async fn add_snapshot(
+        &self,
+        decision_id: Option<i64>,
+        kind: &str,
+        timestamp: i64,
+        image: Vec<u8>,
+        keep: usize,
+    ) -> Result<i64>;
+
+    /// The newest `limit` snapshots of a kind, oldest first
+    async fn get_recent_snapshots(&self, kind: &str, limit: usize) -> Result<Vec<StoredSnapshot>>;
+
+    /// Every snapshot taken for an arbiter decision
+    async fn get_decision_snapshots(&self, decision_id: i64) -> Result<Vec<StoredSnapshot>>;
+
+    /// Get or create spatial context
+    async fn get_or_create_spatial_context(
+        &self,
+        context_type: &str,
+        context_value: &str,
+    ) -> Result<SpatialContext>;
+
+    /// Save an ARIAOS app's state (JSON) under `app_id`
+    async fn save_app_state(&self, app_id: &str, state_json: &str) -> Result<()>;
+
+    /// The JSON state saved under `app_id`
+    async fn load_app_state(&self, app_id: &str) -> Result<Option<String>>;
+
+    /// Every app id with saved state
+    async fn app_state_ids(&self) -> Result<Vec<String>>;
+
+    /// Add a notes revision (`id` is assigned), dropping the owner's oldest
+    /// revisions past `keep`
+    async fn add_notes_revision(&self, revision: &NotesRevision, keep: usize) -> Result<i64>;
+
+    /// Replace the learned user profile
+    async fn save_user_profile(&self, profile: &UserProfile) -> Result<()>;
+
+    async fn load_user_profile(&self) -> Result<Option<UserProfile>>;
+
+    /// An owner's newest notes revisions first
+    async fn get_notes_revisions(
+        &self,
+        owner: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<NotesRevision>>;
+
+    async fn get_notes_revision(&self, id: i64) -> Result<Option<NotesRevision>>;
+
+    /// Chat messages sent in `[since, until)`, oldest first
+    async fn get_chat_between(&self, since: i64, until: i64) -> Result<Vec<ChatMessage>>;
+
+    /// Episodes from `[since, until)`, oldest first
+    async fn get_episodes_between(&self, since: i64, until: i64) -> Result<Vec<Episode>>;
+
+    /// Every owner's notes revisions made in `[since, until)`, oldest first
+    async fn get_notes_revisions_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<NotesRevision>>;
+
+    /// LLM exchanges from `[since, until)` with their ids, oldest first
+    async fn get_llm_calls_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, StoredLlmCall)>>;
+
+    /// Delete chat messages by id; replies to them lose the reference
+    async fn delete_chat_messages(&self, ids: &[i64]) -> Result<u64>;
+
+    /// Delete episodes (and their embeddings) by id
+    async fn delete_episodes(&self, ids: &[String]) -> Result<u64>;
+
+    async fn delete_notes_revisions(&self, ids: &[i64]) -> Result<u64>;
+
+    async fn delete_llm_calls(&self, ids: &[i64]) -> Result<u64>;
+
+    /// Delete snapshots taken in `[since, until)`
+    async fn delete_snapshots_between(&self, since: i64, until: i64) -> Result<u64>;
+
+    /// Add a reminder (`id` is assigned)
+    async fn add_reminder(&self, reminder: &Reminder) -> Result<i64>;
+
+    /// Reminders due at or before `now`, soonest first
+    async fn due_reminders(&self, now: i64) -> Result<Vec<Reminder>>;
+
+    /// Reminders not yet due, soonest first
+    async fn pending_reminders(&self, now: i64) -> Result<Vec<Reminder>>;
+
+    async fn delete_reminders(&self, ids: &[i64]) -> Result<u64>;
+}
+
+/// Database families `StorageConfig::url` can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// `file:`, a bare path, `:memory:`, or remote `libsql://`/`https://`
+    Libsql,
+    /// `postgres://` or `postgresql://`
+    Postgres,
+}
+
+impl BackendKind {
+    pub fn from_url(url: &str) -> Self {
+        let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+        match scheme {
+            Some("postgres" | "postgresql") => BackendKind::Postgres,
+            _ => BackendKind::Libsql,
+        }
+    }
+}
+
+/// Open the backend `url`'s scheme selects
+pub async fn connect(url: &str, auth_token: Option<&str>) -> Result<Arc<dyn StorageBackend>> {
+    match BackendKind::from_url(url) {
+        BackendKind::Libsql => Ok(Arc::new(TursoDb::connect(url, auth_token).await?)),
+        BackendKind::Postgres => bail!(
+            "No Postgres backend is built in; implement StorageBackend and use Storage::with_backend"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_url_scheme() {
+        let kinds = [
+            ("file:/tmp/dewet.db", BackendKind::Libsql),
+            (":memory:", BackendKind::Libsql),
+            ("libsql://dewet.turso.io", BackendKind::Libsql),
+            ("postgres://localhost/dewet", BackendKind::Postgres),
+            ("postgresql://db/dewet", BackendKind::Postgres),
+        ];
+        for (url, kind) in kinds {
+            assert_eq!(BackendKind::from_url(url), kind, "{url}");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Scheduled backups: local databases are snapshotted with `VACUUM INTO`,
+//! remote ones exported to JSON
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use tracing::{error, info};
+
+use super::Storage;
+use crate::config::StorageConfig;
+
+const PREFIX: &str = "dewet-";
+
+/// Tables exported from remote databases (the LLM call log is left out: it is
+/// large and only useful for debugging)
+const EXPORT_TABLES: &[&str] = &[
+    "episodes",
+    "spatial_contexts",
+    "memory_spatial_links",
+    "character_states",
+    "chat_messages",
+    "arbiter_decisions",
+    "ariaos_app_state",
+    "user_profile",
+    "reminders",
+];
+
+impl Storage {
+    /// Back up every `[storage.backup] interval_hours`, starting as soon as
+    /// the newest existing backup is that old
+    pub fn schedule_backups(&self, config: &StorageConfig) {
+        let Some(backup) = config.backup.clone() else {
+            return;
+        };
+        let storage = self.clone();
+        let dir = backup
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| default_dir(&config.url));
+        let interval = backup.interval();
+        tokio::spawn(async move {
+            let age = newest_backup_age(&dir).unwrap_or(interval);
+            tokio::time::sleep(interval.saturating_sub(age)).await;
+            loop {
+                match storage.backup_to(&dir).await {
+                    Ok(path) => info!(path = %path.display(), "Database backed up"),
+                    Err(err) => error!(?err, "Database backup failed"),
+                }
+                if let Err(err) = prune(&dir, backup.keep) {
+                    error!(?err, "Failed to remove old backups");
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Write a timestamped backup into `dir`, returning its path
+    pub async fn backup_to(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create backup directory {dir:?}"))?;
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+        if self.db.is_remote() {
+            let path = dir.join(format!("{PREFIX}{stamp}.json"));
+            let export = self.db.export_tables(EXPORT_TABLES).await?;
+            fs::write(&path, serde_json::to_vec(&export)?)?;
+            Ok(path)
+        } else {
+            let path = dir.join(format!("{PREFIX}{stamp}.db"));
+            self.db.snapshot_to(&path).await?;
+            Ok(path)
+        }
+    }
+}
+
+/// `backups/` beside a local database, or in the data dir for remote ones
+fn default_dir(url: &str) -> PathBuf {
+    match url.strip_prefix("file:") {
+        Some(path) => Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("backups"),
+        None => dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from(".local"))
+            .join("dewet")
+            .join("backups"),
+    }
+}
+
+/// Backups in `dir`, oldest first (the timestamped names sort by age)
+fn backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with(PREFIX) && (name.ends_with(".db") || name.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn newest_backup_age(dir: &Path) -> Option<std::time::Duration> {
+    let newest = backups(dir).ok()?.pop()?;
+    let modified = fs::metadata(newest).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// Delete all but the newest `keep` backups, returning how many were removed
+fn prune(dir: &Path, keep: usize) -> Result<usize> {
+    let paths = backups(dir)?;
+    let excess = paths.len().saturating_sub(keep);
+    for path in &paths[..excess] {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {path:?}"))?;
+    }
+    Ok(excess)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StorageBackend, TursoDb};
+
+    #[tokio::test]
+    async fn test_backup_snapshots_and_prunes() {
+        let dir = std::env::temp_dir().join(format!("dewet-backup-{}", uuid::Uuid::new_v4()));
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        storage
+            .db
+            .add_chat_message("user", "remember this", None)
+            .await
+            .unwrap();
+
+        let path = storage.backup_to(&dir).await.unwrap();
+        let copy = TursoDb::connect(&path.to_string_lossy(), None)
+            .await
+            .unwrap();
+        let chat = copy.get_recent_chat(10).await.unwrap();
+        assert_eq!(chat[0].content, "remember this");
+
+        for stamp in ["20240101-000000", "20240102-000000"] {
+            fs::write(dir.join(format!("{PREFIX}{stamp}.db")), b"").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        assert_eq!(prune(&dir, 2).unwrap(), 1);
+        let left = backups(&dir).unwrap();
+        assert_eq!(left.len(), 2);
+        assert_eq!(left[1], path);
+        assert!(dir.join("notes.txt").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_default_dir() {
+        assert_eq!(
+            default_dir("file:/data/dewet/dewet.db"),
+            PathBuf::from("/data/dewet/backups")
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! At-rest encryption: `EncryptedBackend` wraps any `StorageBackend` and
+//! seals chat, ARIAOS app state, notes history, reminders, the text of
+//! episodes, decisions and LLM calls, and snapshot images with
+//! XChaCha20-Poly1305 before they reach the database. Ids, timestamps,
+//! embeddings and screen context stay in the clear so queries still work.
+//! Rows written before encryption was enabled read as plaintext.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chacha20poly1305::{
+    XChaCha20Poly1305, XNonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+};
+
+use super::{
+    CharacterState, ChatMessage, ChatSession, DecisionStats, Episode, NotesRevision, Reminder,
+    SpatialContext, StorageBackend, StoredDecision, StoredLlmCall, StoredSnapshot, UserProfile,
+};
+use crate::config::EncryptionConfig;
+
+/// Marks encrypted text columns
+const TEXT_PREFIX: &str = "enc1:";
+/// Marks encrypted blobs
+const BLOB_MAGIC: &[u8] = b"DWENC1";
+const NONCE_LEN: usize = 24;
+const KEYRING_SERVICE: &str = "dewet";
+const KEYRING_USER: &str = "storage-key";
+
+#[derive(Clone)]
+pub struct StorageCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl StorageCipher {
+    /// Load the key from `key_env`, falling back to the OS keyring
+    pub fn from_config(config: &EncryptionConfig) -> Result<Self> {
+        let encoded = match std::env::var(&config.key_env) {
+            Ok(key) => key,
+            Err(_) if config.keyring => keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+                .and_then(|entry| entry.get_password())
+                .with_context(|| format!("No storage key in ${} or the keyring", config.key_env))?,
+            Err(_) => bail!(
+                "Storage encryption is on but ${} is not set",
+                config.key_env
+            ),
+        };
+        let key = BASE64
+            .decode(encoded.trim())
+            .context("The storage key is not valid base64")?;
+        Self::from_key(&key)
+    }
+
+    pub fn from_key(key: &[u8]) -> Result<Self> {
+        if key.len() != 32 {
+            bail!("The storage key must be 32 bytes, not {}", key.len());
+        }
+        let cipher = XChaCha20Poly1305::new_from_slice(key).map_err(|e| anyhow!("{e}"))?;
+        Ok(Self { cipher })
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .expect("XChaCha20-Poly1305 encryption cannot fail");
+        [nonce.as_slice(), &sealed].concat()
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("Encrypted value is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt stored value (wrong storage key?)"))
+    }
+
+    pub fn encrypt(&self, text: &str) -> String {
+        format!("{TEXT_PREFIX}{}", BASE64.encode(self.seal(text.as_bytes())))
+    }
+
+    /// Decrypt a value from `encrypt`; anything else is returned unchanged
+    pub fn decrypt(&self, text: &str) -> Result<String> {
+        let Some(encoded) = text.strip_prefix(TEXT_PREFIX) else {
+            return Ok(text.to_string());
+        };
+        let sealed = BASE64
+            .decode(encoded)
+            .context("Encrypted value is not valid base64")?;
+        String::from_utf8(self.open(&sealed)?).context("Decrypted value is not UTF-8")
+    }
+
+    fn encrypt_bytes(&self, data: &[u8]) -> Vec<u8> {
+        [BLOB_MAGIC, &self.seal(data)].concat()
+    }
+
+    fn decrypt_bytes(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match data.strip_prefix(BLOB_MAGIC) {
+            Some(sealed) => self.open(sealed),
+            None => Ok(data),
+        }
+    }
+
+    fn encrypt_episode(&self, episode: &Episode) -> Episode {
+        Episode {
+            content: self.encrypt(&episode.content),
+            ..episode.clone()
+        }
+    }
+
+    fn decrypt_episode(&self, mut episode: Episode) -> Result<Episode> {
+        episode.content = self.decrypt(&episode.content)?;
+        Ok(episode)
+    }
+
+    fn decrypt_chat(&self, mut message: ChatMessage) -> Result<ChatMessage> {
+        message.content = self.decrypt(&message.content)?;
+        Ok(message)
+    }
+
+    fn decrypt_snapshot(&self, mut snapshot: StoredSnapshot) -> Result<StoredSnapshot> {
+        snapshot.image = self.decrypt_bytes(snapshot.image)?;
+        Ok(snapshot)
+    }
+
+    fn decrypt_revision(&self, mut revision: NotesRevision) -> Result<NotesRevision> {
+        revision.diff = self.decrypt(&revision.diff)?;
+        revision.previous = self.decrypt(&revision.previous)?;
+        revision.content = self.decrypt(&revision.content)?;
+        Ok(revision)
+    }
+
+    fn decrypt_reminder(&self, mut reminder: Reminder) -> Result<Reminder> {
+        reminder.text = self.decrypt(&reminder.text)?;
+        Ok(reminder)
+    }
+}
+
+/// A `StorageBackend` that encrypts memory content on the way in and
+/// decrypts it on the way out
+pub struct EncryptedBackend {
+    inner: Arc<dyn StorageBackend>,
+    cipher: StorageCipher,
+}
+
+impl EncryptedBackend {
+    pub fn new(inner: Arc<dyn StorageBackend>, cipher: StorageCipher) -> Self {
+        Self { inner, cipher }
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for EncryptedBackend {
+    fn is_remote(&self) -> bool {
+        self.inner.is_remote()
+    }
+
+    async fn snapshot_to(&self, path: &Path) -> Result<()> {
+        self.inner.snapshot_to(path).await
+    }
+
+    /// Rows are exported as stored, so archives and backups stay encrypted
+    async fn export_tables(&self, tables: &[&str]) -> Result<serde_json::Value> {
+        self.inner.export_tables(tables).await
+    }
+
+    async fn import_rows(&self, table: &str, rows: &[serde_json::Value]) -> Result<u64> {
+        self.inner.import_rows(table, rows).await
+    }
+
+    async fn initialize_schema(&self) -> Result<()> {
+        self.inner.initialize_schema().await
+    }
+
+    async fn add_episode(&self, episode: &Episode) -> Result<()> {
+        self.inner
+            .add_episode(&self.cipher.encrypt_episode(episode))
+            .await
+    }
+
+    async fn get_recent_episodes(&self, limit: usize) -> Result<Vec<Episode>> {
+        let episodes = self.inner.get_recent_episodes(limit).await?;
+        episodes
+            .into_iter()
+            .map(|e| self.cipher.decrypt_episode(e))
+            .collect()
+    }
+
+    async fn set_episode_embedding(&self, id: &str, embedding: &[f32]) -> Result<()> {
+        self.inner.set_episode_embedding(id, embedding).await
+    }
+
+    async fn search_similar(
+        &self,
+        query: &[f32],
+        k: usize,
+        character_id: Option<&str>,
+    ) -> Result<Vec<(Episode, f32)>> {
+        let found = self.inner.search_similar(query, k, character_id).await?;
+        found
+            .into_iter()
+            .map(|(e, score)| Ok((self.cipher.decrypt_episode(e)?, score)))
+            .collect()
+    }
+
+    async fn add_chat_message(
+        &self,
+        sender: &str,
+        content: &str,
+        character_id: Option<&str>,
+    ) -> Result<i64> {
+        let content = self.cipher.encrypt(content);
+        self.inner
+            .add_chat_message(sender, &content, character_id)
+            .await
+    }
+
+    async fn insert_chat_message(&self, message: &ChatMessage) -> Result<i64> {
+        let message = ChatMessage {
+            content: self.cipher.encrypt(&message.content),
+            ..message.clone()
+        };
+        self.inner.insert_chat_message(&message).await
+    }
+
+    async fn update_chat_relevance(
+        &self,
+        timestamp: i64,
+        sender: &str,
+        relevance: f32,
+        tier: &str,
+    ) -> Result<()> {
+        self.inner
+            .update_chat_relevance(timestamp, sender, relevance, tier)
+            .await
+    }
+
+    async fn update_chat_content(&self, timestamp: i64, sender: &str, content: &str) -> Result<()> {
+        let content = self.cipher.encrypt(content);
+        self.inner
+            .update_chat_content(timestamp, sender, &content)
+            .await
+    }
+
+    async fn get_recent_chat(&self, limit: usize) -> Result<Vec<ChatMessage>> {
+        let messages = self.inner.get_recent_chat(limit).await?;
+        messages
+            .into_iter()
+            .map(|m| self.cipher.decrypt_chat(m))
+            .collect()
+    }
+
+    async fn touch_session(&self, timestamp: i64, idle_secs: i64) -> Result<i64> {
+        self.inner.touch_session(timestamp, idle_secs).await
+    }
+
+    async fn close_sessions_idle_since(&self, cutoff: i64) -> Result<u64> {
+        self.inner.close_sessions_idle_since(cutoff).await
+    }
+
+    async fn get_sessions(&self, limit: usize) -> Result<Vec<ChatSession>> {
+        self.inner.get_sessions(limit).await
+    }
+
+    async fn get_session_chat(&self, session_id: i64) -> Result<Vec<ChatMessage>> {
+        let messages = self.inner.get_session_chat(session_id).await?;
+        messages
+            .into_iter()
+            .map(|m| self.cipher.decrypt_chat(m))
+            .collect()
+    }
+
+    async fn log_arbiter_decision(&self, id: i64, decision: &StoredDecision) -> Result<()> {
+        let decision = StoredDecision {
+            reasoning: self.cipher.encrypt(&decision.reasoning),
+            context_summary: self.cipher.encrypt(&decision.context_summary),
+            ..decision.clone()
+        };
+        self.inner.log_arbiter_decision(id, &decision).await
+    }
+
+    async fn last_decision_id(&self) -> Result<i64> {
+        self.inner.last_decision_id().await
+    }
+
+    async fn decision_stats(&self, since: i64) -> Result<DecisionStats> {
+        self.inner.decision_stats(since).await
+    }
+
+    async fn log_llm_call(&self, call: &StoredLlmCall) -> Result<()> {
+        let call = StoredLlmCall {
+            prompt: self.cipher.encrypt(&call.prompt),
+            response: self.cipher.encrypt(&call.response),
+            ..call.clone()
+        };
+        self.inner.log_llm_call(&call).await
+    }
+
+    async fn get_recent_llm_calls(&self, limit: usize) -> Result<Vec<StoredLlmCall>> {
+        let calls = self.inner.get_recent_llm_calls(limit).await?;
+        calls
+            .into_iter()
+            .map(|mut call| {
+                call.prompt = self.cipher.decrypt(&call.prompt)?;
+                call.response = self.cipher.decrypt(&call.response)?;
+                Ok(call)
+            })
+            .collect()
+    }
+
+    async fn get_character_state(&self, character_id: &str) -> Result<Option<CharacterState>> {
+        self.inner.get_character_state(character_id).await
+    }
+
+    async fn update_character_state(&self, state: &CharacterState) -> Result<()> {
+        self.inner.update_character_state(state).await
+    }
+
+    async fn decay_importance(&self, decay_factor: f32, min_age_hours: i64) -> Result<u64> {
+        self.inner
+            .decay_importance(decay_factor, min_age_hours)
+            .await
+    }
+
+    async fn prune_forgotten(&self, threshold: f32) -> Result<u64> {
+        self.inner.prune_forgotten(threshold).await
+    }
+
+    async fn delete_chat_before(&self, cutoff: i64) -> Result<u64> {
+        self.inner.delete_chat_before(cutoff).await
+    }
+
+    async fn delete_decisions_before(&self, cutoff: i64) -> Result<u64> {
+        self.inner.delete_decisions_before(cutoff).await
+    }
+
+    async fn add_snapshot(
+        &self,
+        decision_id: Option<i64>,
+        kind: &str,
+        timestamp: i64,
+        image: Vec<u8>,
+        keep: usize,
+    ) -> Result<i64> {
+        let image = self.cipher.encrypt_bytes(&image);
+        self.inner
+            .add_snapshot(decision_id, kind, timestamp, image, keep)
+            .await
+    }
+
+    async fn get_recent_snapshots(&self, kind: &str, limit: usize) -> Result<Vec<StoredSnapshot>> {
+        let snapshots = self.inner.get_recent_snapshots(kind, limit).await?;
+        snapshots
+            .into_iter()
+            .map(|s| self.cipher.decrypt_snapshot(s))
+            .collect()
+    }
+
+    async fn get_decision_snapshots(&self, decision_id: i64) -> Result<Vec<StoredSnapshot>> {
+        let snapshots = self.inner.get_decision_snapshots(decision_id).await?;
+        snapshots
+            .into_iter()
+            .map(|s| self.cipher.decrypt_snapshot(s))
+            .collect()
+    }
+
+    async fn get_or_create_spatial_context(
+        &self,
+        context_type: &str,
+        context_value: &str,
+    ) -> Result<SpatialContext> {
+        self.inner
+            .get_or_create_spatial_context(context_type, context_value)
+            .await
+    }
+
+    async fn save_app_state(&self, app_id: &str, state_json: &str) -> Result<()> {
+        let state_json = self.cipher.encrypt(state_json);
+        self.inner.save_app_state(app_id, &state_json).await
+    }
+
+    async fn load_app_state(&self, app_id: &str) -> Result<Option<String>> {
+        let state_json = self.inner.load_app_state(app_id).await?;
+        state_json.map(|s| self.cipher.decrypt(&s)).transpose()
+    }
+
+    async fn app_state_ids(&self) -> Result<Vec<String>> {
+        self.inner.app_state_ids().await
+    }
+
+    async fn save_user_profile(&self, profile: &UserProfile) -> Result<()> {
+        let encrypt_all = |items: &[String]| items.iter().map(|i| self.cipher.encrypt(i)).collect();
+        let profile = UserProfile {
+            name: profile.name.as_deref().map(|n| self.cipher.encrypt(n)),
+            timezone: profile.timezone.as_deref().map(|t| self.cipher.encrypt(t)),
+            preferences: encrypt_all(&profile.preferences),
+            habits: encrypt_all(&profile.habits),
+            likes: encrypt_all(&profile.likes),
+            dislikes: encrypt_all(&profile.dislikes),
+            learned_until: profile.learned_until,
+        };
+        self.inner.save_user_profile(&profile).await
+    }
+
+    async fn load_user_profile(&self) -> Result<Option<UserProfile>> {
+        let Some(profile) = self.inner.load_user_profile().await? else {
+            return Ok(None);
+        };
+        let decrypt_all = |items: Vec<String>| -> Result<Vec<String>> {
+            items.iter().map(|i| self.cipher.decrypt(i)).collect()
+        };
+        Ok(Some(UserProfile {
+            name: profile.name.map(|n| self.cipher.decrypt(&n)).transpose()?,
+            timezone: profile
+                .timezone
+                .map(|t| self.cipher.decrypt(&t))
+                .transpose()?,
+            preferences: decrypt_all(profile.preferences)?,
+            habits: decrypt_all(profile.habits)?,
+            likes: decrypt_all(profile.likes)?,
+            dislikes: decrypt_all(profile.dislikes)?,
+            learned_until: profile.learned_until,
+        }))
+    }
+
+    async fn add_notes_revision(&self, revision: &NotesRevision, keep: usize) -> Result<i64> {
+        let revision = NotesRevision {
+            diff: self.cipher.encrypt(&revision.diff),
+            previous: self.cipher.encrypt(&revision.previous),
+            content: self.cipher.encrypt(&revision.content),
+            ..revision.clone()
+        };
+        self.inner.add_notes_revision(&revision, keep).await
+    }
+
+    async fn get_notes_revisions(
+        &self,
+        owner: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<NotesRevision>> {
+        let revisions = self.inner.get_notes_revisions(owner, limit).await?;
+        revisions
+            .into_iter()
+            .map(|r| self.cipher.decrypt_revision(r))
+            .collect()
+    }
+
+    async fn get_notes_revision(&self, id: i64) -> Result<Option<NotesRevision>> {
+        let revision = self.inner.get_notes_revision(id).await?;
+        revision
+            .map(|r| self.cipher.decrypt_revision(r))
+            .transpose()
+    }
+
+    async fn get_chat_between(&self, since: i64, until: i64) -> Result<Vec<ChatMessage>> {
+        let messages = self.inner.get_chat_between(since, until).await?;
+        messages
+            .into_iter()
+            .map(|m| self.cipher.decrypt_chat(m))
+            .collect()
+    }
+
+    async fn get_episodes_between(&self, since: i64, until: i64) -> Result<Vec<Episode>> {
+        let episodes = self.inner.get_episodes_between(since, until).await?;
+        episodes
+            .into_iter()
+            .map(|e| self.cipher.decrypt_episode(e))
+            .collect()
+    }
+
+    async fn get_notes_revisions_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<NotesRevision>> {
+        let revisions = self.inner.get_notes_revisions_between(since, until).await?;
+        revisions
+            .into_iter()
+            .map(|r| self.cipher.decrypt_revision(r))
+            .collect()
+    }
+
+    async fn get_llm_calls_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, StoredLlmCall)>> {
+        let calls = self.inner.get_llm_calls_between(since, until).await?;
+        calls
+            .into_iter()
+            .map(|(id, mut call)| {
+                call.prompt = self.cipher.decrypt(&call.prompt)?;
+                call.response = self.cipher.decrypt(&call.response)?;
+                Ok((id, call))
+            })
+            .collect()
+    }
+
+    async fn delete_chat_messages(&self, ids: &[i64]) -> Result<u64> {
+        self.inner.delete_chat_messages(ids).await
+    }
+
+    async fn delete_episodes(&self, ids: &[String]) -> Result<u64> {
+        self.inner.delete_episodes(ids).await
+    }
+
+    async fn delete_notes_revisions(&self, ids: &[i64]) -> Result<u64> {
+        self.inner.delete_notes_revisions(ids).await
+    }
+
+    async fn delete_llm_calls(&self, ids: &[i64]) -> Result<u64> {
+        self.inner.delete_llm_calls(ids).await
+    }
+
+    async fn delete_snapshots_between(&self, since: i64, until: i64) -> Result<u64> {
+        self.inner.delete_snapshots_between(since, until).await
+    }
+
+    async fn add_reminder(&self, reminder: &Reminder) -> Result<i64> {
+        let reminder = Reminder {
+            text: self.cipher.encrypt(&reminder.text),
+            ..reminder.clone()
+        };
+        self.inner.add_reminder(&reminder).await
+    }
+
+    async fn due_reminders(&self, now: i64) -> Result<Vec<Reminder>> {
+        let reminders = self.inner.due_reminders(now).await?;
+        reminders
+            .into_iter()
+            .map(|r| self.cipher.decrypt_reminder(r))
+            .collect()
+    }
+
+    async fn pending_reminders(&self, now: i64) -> Result<Vec<Reminder>> {
+        let reminders = self.inner.pending_reminders(now).await?;
+        reminders
+            .into_iter()
+            .map(|r| self.cipher.decrypt_reminder(r))
+            .collect()
+    }
+
+    async fn delete_reminders(&self, ids: &[i64]) -> Result<u64> {
+        self.inner.delete_reminders(ids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::TursoDb;
+
+    #[tokio::test]
+    async fn test_content_is_encrypted_at_rest() {
+        let inner: Arc<dyn StorageBackend> =
+            Arc::new(TursoDb::connect(":memory:", None).await.unwrap());
+        inner.initialize_schema().await.unwrap();
+        let cipher = StorageCipher::from_key(&[7; 32]).unwrap();
+        let db = EncryptedBackend::new(inner.clone(), cipher);
+        inner
+            .add_chat_message("user", "written before encryption", None)
+            .await
+            .unwrap();
+        db.add_chat_message("user", "my bank password is hunter2", None)
+            .await
+            .unwrap();
+
+        let raw = inner.export_tables(&["chat_messages"]).await.unwrap();
+        assert!(!raw.to_string().contains("hunter2"));
+
+        let chat = db.get_recent_chat(10).await.unwrap();
+        let contents: Vec<_> = chat.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            ["written before encryption", "my bank password is hunter2"]
+        );
+
+        let wrong_key = EncryptedBackend::new(inner, StorageCipher::from_key(&[8; 32]).unwrap());
+        assert!(wrong_key.get_recent_chat(10).await.is_err());
+    }
+}
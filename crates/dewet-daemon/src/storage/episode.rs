@@ -0,0 +1,132 @@
+//! Building episodes for the "what happened" log, with rough importance and
+//! emotional valence so decay and pruning keep the moments that mattered
+
+use chrono::Utc;
+
+use super::Episode;
+
+/// What an episode records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpisodeKind {
+    /// The user typed or said something
+    UserMessage,
+    /// A companion spoke
+    CompanionResponse,
+    /// The VLA saw a significant change on screen
+    ScreenChange,
+    /// A companion edited its ARIAOS notes
+    NotesEdit,
+    /// A companion's afterthought on a reply it just gave
+    Reflection,
+}
+
+impl EpisodeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EpisodeKind::UserMessage => "user_message",
+            EpisodeKind::CompanionResponse => "companion_response",
+            EpisodeKind::ScreenChange => "screen_change",
+            EpisodeKind::NotesEdit => "notes_edit",
+            EpisodeKind::Reflection => "reflection",
+        }
+    }
+
+    /// The user talking to the companions matters most; passing screen
+    /// changes least
+    fn base_importance(self) -> f32 {
+        match self {
+            EpisodeKind::UserMessage => 0.7,
+            EpisodeKind::NotesEdit | EpisodeKind::Reflection => 0.6,
+            EpisodeKind::CompanionResponse => 0.5,
+            EpisodeKind::ScreenChange => 0.4,
+        }
+    }
+}
+
+const POSITIVE: &[&str] = &[
+    "thanks", "thank", "great", "love", "nice", "awesome", "cool", "yay", "haha", "lol", "finally",
+    "works", "fixed", "perfect", "happy", "glad", "fun", "congrats",
+];
+const NEGATIVE: &[&str] = &[
+    "error", "fail", "failed", "failing", "broken", "hate", "ugh", "crash", "crashed", "stuck",
+    "damn", "annoying", "bug", "wrong", "sad", "tired", "mad", "sorry", "panic",
+];
+
+impl Episode {
+    /// An episode happening now, with importance and valence estimated from
+    /// its kind and text
+    pub fn now(kind: EpisodeKind, actor: Option<String>, content: impl Into<String>) -> Self {
+        let content = content.into();
+        let emotional_valence = valence(&content);
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().timestamp(),
+            event_type: kind.as_str().to_string(),
+            actor,
+            importance: importance(kind, &content, emotional_valence),
+            emotional_valence,
+            content,
+            screen_context: None,
+            embedding: None,
+            character_id: None,
+        }
+    }
+
+    /// Keep this in `character_id`'s memory rather than the shared one
+    pub fn for_character(mut self, character_id: impl Into<String>) -> Self {
+        self.character_id = Some(character_id.into());
+        self
+    }
+}
+
+/// -1 (negative) to 1 (positive), from counts of feeling words; 0 with none
+fn valence(text: &str) -> f32 {
+    let (mut positive, mut negative) = (0, 0);
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let word = word.to_lowercase();
+        if POSITIVE.contains(&word.as_str()) {
+            positive += 1;
+        } else if NEGATIVE.contains(&word.as_str()) {
+            negative += 1;
+        }
+    }
+    if positive + negative == 0 {
+        return 0.0;
+    }
+    (positive - negative) as f32 / (positive + negative) as f32
+}
+
+/// Questions and strong feelings are remembered better
+fn importance(kind: EpisodeKind, content: &str, valence: f32) -> f32 {
+    let mut importance = kind.base_importance();
+    if content.contains('?') {
+        importance += 0.1;
+    }
+    if valence.abs() >= 0.5 {
+        importance += 0.15;
+    }
+    importance.min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heuristics() {
+        assert_eq!(valence("Thanks, it finally works!"), 1.0);
+        assert_eq!(valence("ugh, another borrow checker error"), -1.0);
+        assert_eq!(valence("opened the settings page"), 0.0);
+
+        let question = Episode::now(EpisodeKind::UserMessage, None, "why does this crash?");
+        assert_eq!(question.event_type, "user_message");
+        assert!(question.emotional_valence < 0.0);
+        assert!((question.importance - 0.95).abs() < 1e-6);
+
+        let change = Episode::now(EpisodeKind::ScreenChange, None, "switched to a browser");
+        assert!((change.importance - 0.4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,334 @@
+//! Targeted forgetting: delete what was said in a time range, by a sender,
+//! or about a topic, from chat, episodes (with their embeddings), notes, the
+//! user profile and the LLM call log
+
+use std::fmt;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{AriaosNotesState, Storage, UserProfile, app_state::NOTES_APP};
+
+/// Episodes at least this similar to the query are forgotten even if they
+/// don't mention it
+const SIMILAR_ENOUGH: f32 = 0.75;
+
+/// How many of the most similar episodes are considered
+const SIMILAR_CANDIDATES: usize = 500;
+
+/// What to forget. Filters combine: with a time range and a query, only
+/// mentions of the query inside the range are forgotten
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ForgetRequest {
+    /// Start of the range (unix seconds, inclusive)
+    #[serde(default)]
+    pub since: Option<i64>,
+    /// End of the range (unix seconds, exclusive)
+    #[serde(default)]
+    pub until: Option<i64>,
+    /// Only what this sender said: "user" or a companion id
+    #[serde(default)]
+    pub sender: Option<String>,
+    /// Anything mentioning this, or (for episodes) close to it in meaning
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+impl ForgetRequest {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none()
+            && self.until.is_none()
+            && self.sender.is_none()
+            && self.query.is_none()
+    }
+
+    fn range(&self) -> (i64, i64) {
+        (
+            self.since.unwrap_or(i64::MIN),
+            self.until.unwrap_or(i64::MAX),
+        )
+    }
+
+    fn has_range(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    fn sent_by(&self, sender: Option<&str>) -> bool {
+        match &self.sender {
+            Some(wanted) => sender.is_some_and(|s| s.eq_ignore_ascii_case(wanted)),
+            None => true,
+        }
+    }
+
+    fn mentioned_in(&self, texts: &[&str]) -> bool {
+        match &self.query {
+            Some(query) => texts.iter().any(|text| mentions(text, query)),
+            None => true,
+        }
+    }
+}
+
+/// Rows and entries removed by one `Storage::forget`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ForgetReport {
+    pub chat_messages: u64,
+    pub episodes: u64,
+    pub notes_revisions: u64,
+    pub notes_lines: u64,
+    pub profile_entries: u64,
+    pub llm_calls: u64,
+    pub snapshots: u64,
+}
+
+impl fmt::Display for ForgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} chat messages, {} episodes, {} notes revisions, {} notes lines, {} profile entries, {} LLM calls and {} snapshots",
+            self.chat_messages,
+            self.episodes,
+            self.notes_revisions,
+            self.notes_lines,
+            self.profile_entries,
+            self.llm_calls,
+            self.snapshots
+        )
+    }
+}
+
+impl Storage {
+    /// Forget everything matching `request`. Chat and episodes match on
+    /// range, sender and query; notes revisions on range, query and their
+    /// owner as sender; the LLM call log on range and query (and is kept
+    /// when a sender is given). The saved notes and the user profile have
+    /// no times or senders, so any line or entry mentioning the query is
+    /// removed. Snapshots are only deleted by a range on its own
+    pub async fn forget(&self, request: &ForgetRequest) -> Result<ForgetReport> {
+        if request.is_empty() {
+            bail!("Nothing to forget: give a time range, sender or query");
+        }
+        // Queued writes could otherwise bring back what was just forgotten
+        self.flush().await?;
+        let (since, until) = request.range();
+        let mut report = ForgetReport::default();
+
+        let chat: Vec<i64> = self
+            .db
+            .get_chat_between(since, until)
+            .await?
+            .into_iter()
+            .filter(|m| {
+                request.sent_by(Some(m.sender.as_str())) && request.mentioned_in(&[&m.content])
+            })
+            .map(|m| m.id)
+            .collect();
+        report.chat_messages = self.db.delete_chat_messages(&chat).await?;
+
+        let similar = self.similar_episodes(request).await;
+        let episodes: Vec<String> = self
+            .db
+            .get_episodes_between(since, until)
+            .await?
+            .into_iter()
+            .filter(|e| request.sent_by(e.actor.as_deref()))
+            .filter(|e| request.mentioned_in(&[&e.content]) || similar.contains(&e.id))
+            .map(|e| e.id)
+            .collect();
+        report.episodes = self.db.delete_episodes(&episodes).await?;
+
+        let revisions: Vec<i64> = self
+            .db
+            .get_notes_revisions_between(since, until)
+            .await?
+            .into_iter()
+            .filter(|r| request.sent_by(r.owner.as_deref()))
+            .filter(|r| request.mentioned_in(&[&r.diff, &r.previous, &r.content]))
+            .map(|r| r.id)
+            .collect();
+        report.notes_revisions = self.db.delete_notes_revisions(&revisions).await?;
+
+        if request.sender.is_none() {
+            let calls: Vec<i64> = self
+                .db
+                .get_llm_calls_between(since, until)
+                .await?
+                .into_iter()
+                .filter(|(_, call)| request.mentioned_in(&[&call.prompt, &call.response]))
+                .map(|(id, _)| id)
+                .collect();
+            report.llm_calls = self.db.delete_llm_calls(&calls).await?;
+            if request.query.is_none() && request.has_range() {
+                report.snapshots = self.db.delete_snapshots_between(since, until).await?;
+            }
+        }
+
+        if let Some(query) = &request.query {
+            report.notes_lines = self.forget_in_notes(query).await?;
+            report.profile_entries = self.forget_in_profile(query).await?;
+        }
+        Ok(report)
+    }
+
+    /// Ids of episodes close in meaning to the query (none without an
+    /// embedding model)
+    async fn similar_episodes(&self, request: &ForgetRequest) -> Vec<String> {
+        let Some(query) = &request.query else {
+            return Vec::new();
+        };
+        if self.embedder.is_none() {
+            return Vec::new();
+        }
+        let found = match self.embed(query).await {
+            Ok(vector) => {
+                self.db
+                    .search_similar(&vector, SIMILAR_CANDIDATES, None)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        match found {
+            Ok(found) => found
+                .into_iter()
+                .filter(|(_, score)| *score >= SIMILAR_ENOUGH)
+                .map(|(episode, _)| episode.id)
+                .collect(),
+            Err(err) => {
+                warn!("Forgetting by text only, similarity search failed: {err:#}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Remove lines mentioning `query` from every saved notes app, without
+    /// keeping a revision of the old text
+    async fn forget_in_notes(&self, query: &str) -> Result<u64> {
+        let mut removed = 0;
+        for app_id in self.db.app_state_ids().await? {
+            let owned_notes = app_id
+                .strip_prefix(NOTES_APP)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'));
+            if !owned_notes {
+                continue;
+            }
+            let Some(json) = self.db.load_app_state(&app_id).await? else {
+                continue;
+            };
+            let mut notes: AriaosNotesState = serde_json::from_str(&json)?;
+            let lines = notes.content.lines().count();
+            let kept: Vec<&str> = notes
+                .content
+                .lines()
+                .filter(|line| !mentions(line, query))
+                .collect();
+            if kept.len() == lines {
+                continue;
+            }
+            removed += (lines - kept.len()) as u64;
+            notes.content = kept.join("\n");
+            let json = serde_json::to_string(&notes)?;
+            self.db.save_app_state(&app_id, &json).await?;
+        }
+        Ok(removed)
+    }
+
+    /// Remove profile entries mentioning `query`
+    async fn forget_in_profile(&self, query: &str) -> Result<u64> {
+        let Some(mut profile) = self.db.load_user_profile().await? else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for field in [&mut profile.name, &mut profile.timezone] {
+            if field.as_deref().is_some_and(|value| mentions(value, query)) {
+                *field = None;
+                removed += 1;
+            }
+        }
+        let UserProfile {
+            preferences,
+            habits,
+            likes,
+            dislikes,
+            ..
+        } = &mut profile;
+        for list in [preferences, habits, likes, dislikes] {
+            let before = list.len();
+            list.retain(|entry| !mentions(entry, query));
+            removed += (before - list.len()) as u64;
+        }
+        if removed > 0 {
+            self.db.save_user_profile(&profile).await?;
+        }
+        Ok(removed)
+    }
+}
+
+fn mentions(text: &str, query: &str) -> bool {
+    text.to_lowercase().contains(&query.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::StorageConfig,
+        storage::{Episode, EpisodeKind},
+    };
+
+    #[tokio::test]
+    async fn test_forget_by_query_and_sender() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let db = &storage.db;
+        db.add_chat_message("user", "Project Falcon ships Friday", None)
+            .await
+            .unwrap();
+        db.add_chat_message("aria", "Good luck with falcon!", None)
+            .await
+            .unwrap();
+        db.add_chat_message("user", "I had pasta for lunch", None)
+            .await
+            .unwrap();
+        let episode = Episode::now(
+            EpisodeKind::UserMessage,
+            Some("user".into()),
+            "Falcon is late",
+        );
+        storage.record_episode(&episode).await.unwrap();
+        let notes = AriaosNotesState {
+            content: "buy milk\nfalcon demo at 3".into(),
+            ..Default::default()
+        };
+        storage.save_ariaos_notes(None, &notes).await.unwrap();
+        let profile = UserProfile {
+            likes: vec!["working on Falcon".into(), "pasta".into()],
+            ..Default::default()
+        };
+        storage.save_user_profile(&profile).await.unwrap();
+
+        let only_user = ForgetRequest {
+            sender: Some("user".into()),
+            query: Some("FALCON".into()),
+            ..Default::default()
+        };
+        let report = storage.forget(&only_user).await.unwrap();
+        assert_eq!(report.chat_messages, 1);
+        assert_eq!(report.episodes, 1);
+        assert_eq!(report.notes_lines, 1);
+        assert_eq!(report.profile_entries, 1);
+
+        let chat = db.get_recent_chat(10).await.unwrap();
+        let senders: Vec<&str> = chat.iter().map(|m| m.sender.as_str()).collect();
+        assert_eq!(senders, ["aria", "user"]);
+        assert!(db.get_recent_episodes(10).await.unwrap().is_empty());
+        let notes = storage.load_ariaos_notes(None).await.unwrap().unwrap();
+        assert_eq!(notes.content, "buy milk");
+        assert_eq!(storage.user_profile().await.unwrap().likes, ["pasta"]);
+
+        assert!(storage.forget(&ForgetRequest::default()).await.is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Offline journal: queued writes a remote database couldn't take, one JSON
+//! line each (encrypted with the storage key when there is one), replayed
+//! in order once the database is reachable again
+
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use super::{StorageCipher, queue::Write};
+
+pub(super) struct Journal {
+    path: PathBuf,
+    cipher: Option<StorageCipher>,
+}
+
+impl Journal {
+    pub fn new(path: impl Into<PathBuf>, cipher: Option<StorageCipher>) -> Self {
+        Self {
+            path: path.into(),
+            cipher,
+        }
+    }
+
+    /// Whether writes from an earlier run are waiting
+    pub fn has_pending(&self) -> bool {
+        fs::metadata(&self.path).is_ok_and(|meta| meta.len() > 0)
+    }
+
+    pub fn append(&self, write: &Write) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", self.encode(write)?)?;
+        Ok(())
+    }
+
+    /// Every journaled write, oldest first
+    pub fn read(&self) -> Result<Vec<Write>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        text.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| self.decode(line))
+            .collect()
+    }
+
+    /// Keep only `writes`, removing the file when none are left
+    pub fn replace(&self, writes: &[Write]) -> Result<()> {
+        if writes.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            };
+        }
+        let lines = writes
+            .iter()
+            .map(|write| Ok(self.encode(write)? + "\n"))
+            .collect::<Result<String>>()?;
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, lines)?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    fn encode(&self, write: &Write) -> Result<String> {
+        let line = serde_json::to_string(write)?;
+        Ok(match &self.cipher {
+            Some(cipher) => cipher.encrypt(&line),
+            None => line,
+        })
+    }
+
+    fn decode(&self, line: &str) -> Result<Write> {
+        let line = match &self.cipher {
+            Some(cipher) => cipher.decrypt(line)?,
+            None => line.to_string(),
+        };
+        serde_json::from_str(&line).context("Unreadable offline journal entry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Episode, EpisodeKind, StoredDecision};
+
+    #[test]
+    fn test_journal_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("dewet-journal-{}.jsonl", uuid::Uuid::new_v4()));
+        let cipher = StorageCipher::from_key(&[3; 32]).unwrap();
+        let journal = Journal::new(&path, Some(cipher));
+        let episode = Episode::now(EpisodeKind::UserMessage, None, "offline on the train");
+        journal.append(&Write::Episode(episode)).unwrap();
+        let decision = StoredDecision::now(false, None, "quiet", 0.0);
+        journal.append(&Write::Decision(7, decision)).unwrap();
+        assert!(journal.has_pending());
+        assert!(!fs::read_to_string(&path).unwrap().contains("train"));
+
+        let writes = journal.read().unwrap();
+        assert!(matches!(&writes[0], Write::Episode(e) if e.content == "offline on the train"));
+        journal.replace(&writes[1..]).unwrap();
+        assert!(matches!(
+            journal.read().unwrap()[..],
+            [Write::Decision(7, _)]
+        ));
+        journal.replace(&[]).unwrap();
+        assert!(!journal.has_pending());
+    }
+}
@@ -0,0 +1,100 @@
+//! Memory upkeep: episode importance decays over time, forgotten episodes are
+//! pruned, and chat and decision logs can be capped by age
+
+use std::fmt;
+
+use anyhow::Result;
+use chrono::Utc;
+
+use super::Storage;
+use crate::config::MaintenanceConfig;
+
+/// Rows touched by one maintenance run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    pub decayed: u64,
+    pub pruned: u64,
+    pub chat_deleted: u64,
+    pub decisions_deleted: u64,
+}
+
+impl fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "decayed {} episodes, pruned {}, deleted {} chat messages and {} decisions",
+            self.decayed, self.pruned, self.chat_deleted, self.decisions_deleted
+        )
+    }
+}
+
+impl Storage {
+    pub async fn run_maintenance(&self, config: &MaintenanceConfig) -> Result<MaintenanceReport> {
+        let days_ago = |days: u32| Utc::now().timestamp() - i64::from(days) * 86_400;
+        let mut report = MaintenanceReport {
+            decayed: self
+                .db
+                .decay_importance(config.decay_factor, config.decay_after_hours)
+                .await?,
+            pruned: self.db.prune_forgotten(config.prune_below).await?,
+            ..Default::default()
+        };
+        if let Some(days) = config.chat_max_age_days {
+            report.chat_deleted = self.db.delete_chat_before(days_ago(days)).await?;
+        }
+        if let Some(days) = config.decision_max_age_days {
+            report.decisions_deleted = self.db.delete_decisions_before(days_ago(days)).await?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::StorageConfig,
+        storage::{Episode, EpisodeKind},
+    };
+
+    #[tokio::test]
+    async fn test_maintenance_decays_and_prunes() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let day = 86_400;
+        let now = Utc::now().timestamp();
+        for (importance, age) in [(0.5, 2 * day), (0.06, 2 * day), (0.06, 0)] {
+            let mut episode = Episode::now(EpisodeKind::ScreenChange, None, "screen");
+            episode.importance = importance;
+            episode.timestamp = now - age;
+            episode.embedding = Some(vec![1.0]);
+            storage.record_episode(&episode).await.unwrap();
+        }
+        storage
+            .db
+            .add_chat_message("user", "hi", None)
+            .await
+            .unwrap();
+
+        let config = MaintenanceConfig {
+            interval_hours: 24.0,
+            decay_factor: 0.5,
+            decay_after_hours: 24,
+            prune_below: 0.05,
+            chat_max_age_days: Some(30),
+            decision_max_age_days: None,
+        };
+        let report = storage.run_maintenance(&config).await.unwrap();
+        // The two old episodes decay; the weaker one drops below the threshold
+        assert_eq!(report.decayed, 2);
+        assert_eq!(report.pruned, 1);
+        assert_eq!(report.chat_deleted, 0);
+        let episodes = storage.db.get_recent_episodes(10).await.unwrap();
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(storage.recent_chat(10).await.unwrap().len(), 1);
+    }
+}
@@ -1,14 +1,55 @@
-//! Storage layer using Turso (libSQL)
+//! Storage layer over a pluggable database backend (Turso/libSQL built in)
 
+mod analytics;
+mod app_state;
+mod archive;
+mod backend;
+mod backup;
+mod encryption;
+mod episode;
+mod forget;
+mod journal;
+mod maintenance;
+mod notes;
+mod profile;
+mod queue;
+mod recall;
+mod reminder;
+mod session;
+mod snapshot;
 mod turso;
 
+pub use analytics::{
+    CharacterDecisionStats, DecisionOutcome, DecisionStats, HourlyResponses, PassReasonCount,
+};
+pub use backend::{BackendKind, StorageBackend};
+pub use encryption::{EncryptedBackend, StorageCipher};
+pub use episode::EpisodeKind;
+pub use forget::{ForgetReport, ForgetRequest};
+pub use maintenance::MaintenanceReport;
+pub use profile::UserProfile;
+pub use recall::{keyword_relevance, topic_words};
+pub use snapshot::{Snapshot, SnapshotKind};
 pub use turso::TursoDb;
 
-use anyhow::Result;
+use std::sync::{
+    Arc,
+    atomic::{AtomicI64, Ordering},
+};
+
+use anyhow::{Result, anyhow};
+use parking_lot::Mutex;
+use tokio::task::JoinSet;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use crate::{bridge::ChatPacket, config::StorageConfig};
+use crate::{
+    bridge::{ChatPacket, MemoryTier},
+    config::{MemoryScope, SnapshotConfig, StorageConfig},
+    llm::SharedLlm,
+};
+use journal::Journal;
+use queue::{Write, WriteQueue};
 
 /// Episode memory - the "what happened" log
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +64,9 @@ pub struct Episode {
     pub screen_context: Option<ScreenContext>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub embedding: Option<Vec<f32>>,
+    /// Companion whose memory this is; shared when None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character_id: Option<String>,
 }
 
 /// Screen context at time of episode
@@ -42,6 +86,44 @@ pub struct SpatialContext {
     pub visit_count: i64,
 }
 
+/// An encoded snapshot as stored
+#[derive(Debug, Clone)]
+pub struct StoredSnapshot {
+    pub id: i64,
+    pub decision_id: Option<i64>,
+    pub kind: String,
+    pub timestamp: i64,
+    /// JPEG bytes
+    pub image: Vec<u8>,
+}
+
+/// One change to the ARIAOS notes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotesRevision {
+    pub id: i64,
+    /// Companion whose notes these are; shared when None
+    pub owner: Option<String>,
+    pub timestamp: i64,
+    /// The tool that made the change, e.g. `notes_append`
+    pub command: String,
+    /// Unified line diff from `previous` to `content`
+    pub diff: String,
+    pub previous: String,
+    pub content: String,
+}
+
+/// Something a companion was asked to bring up at a set time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: i64,
+    /// When it is due (unix seconds)
+    pub due_at: i64,
+    /// Companion who set it and should deliver it
+    pub character_id: Option<String>,
+    pub text: String,
+    pub created_at: i64,
+}
+
 /// Character runtime state (not the static definition)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharacterState {
@@ -59,6 +141,34 @@ pub struct ChatMessage {
     pub sender: String,
     pub content: String,
     pub in_response_to: Option<i64>,
+    pub character_id: Option<String>,
+    pub relevance: f32,
+    pub tier: String,
+    pub session_id: Option<i64>,
+}
+
+impl From<ChatMessage> for ChatPacket {
+    fn from(msg: ChatMessage) -> Self {
+        ChatPacket {
+            sender: msg.sender,
+            content: msg.content,
+            timestamp: msg.timestamp,
+            relevance: msg.relevance,
+            tier: MemoryTier::parse(&msg.tier),
+            character_id: msg.character_id,
+        }
+    }
+}
+
+/// A conversation: chat with no gap longer than `session_idle_minutes`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    pub id: i64,
+    pub started_at: i64,
+    pub last_activity: i64,
+    /// Set once the session has gone idle
+    pub ended_at: Option<i64>,
+    pub message_count: i64,
 }
 
 /// Arbiter decision log entry
@@ -78,77 +188,225 @@ pub struct ArbiterDecisionLog {
 pub struct AriaosNotesState {
     pub content: String,
     pub scroll_offset: f32,
+    /// Companion whose notes these are, with per-character memory (not stored)
+    #[serde(skip)]
+    pub owner: Option<String>,
 }
 
 /// High-level storage wrapper that the daemon uses.
 #[derive(Clone)]
 pub struct Storage {
-    db: TursoDb,
+    db: Arc<dyn StorageBackend>,
+    /// Embedding model for semantic search, and its name
+    embedder: Option<(SharedLlm, String)>,
+    scope: MemoryScope,
+    snapshots: Option<SnapshotConfig>,
+    /// Inactivity that ends a conversation session
+    session_idle_secs: i64,
+    writes: WriteQueue,
+    /// Episodes from `remember` still being embedded; `flush` waits for them
+    embedding: Arc<Mutex<JoinSet<()>>>,
+    /// Decision ids are handed out before the write-behind insert
+    next_decision_id: Arc<AtomicI64>,
 }
 
 impl Storage {
+    /// Open the database `config.url` names (see `BackendKind`)
     pub async fn connect(config: &StorageConfig) -> Result<Self> {
         let token = std::env::var(&config.auth_token_env).ok();
-        let db = TursoDb::connect(&config.url, token.as_deref()).await?;
+        let db = backend::connect(&config.url, token.as_deref()).await?;
+        Self::with_backend(db, config).await
+    }
+
+    /// Use a backend built outside this crate, with the rest of `config`
+    pub async fn with_backend(db: Arc<dyn StorageBackend>, config: &StorageConfig) -> Result<Self> {
+        let cipher = match &config.encryption {
+            Some(encryption) => Some(StorageCipher::from_config(encryption)?),
+            None => None,
+        };
+        let db: Arc<dyn StorageBackend> = match &cipher {
+            Some(cipher) => Arc::new(EncryptedBackend::new(db, cipher.clone())),
+            None => db,
+        };
         db.initialize_schema().await?;
-        Ok(Self { db })
+        let last_decision_id = db.last_decision_id().await?;
+        let session_idle_secs = config.session_idle_minutes as i64 * 60;
+        let journal = db
+            .is_remote()
+            .then(|| Journal::new(&config.offline_journal, cipher));
+        Ok(Self {
+            writes: WriteQueue::spawn(db.clone(), session_idle_secs, journal),
+            db,
+            embedder: None,
+            embedding: Arc::default(),
+            scope: config.memory_scope,
+            snapshots: config.snapshots.clone(),
+            session_idle_secs,
+            next_decision_id: Arc::new(AtomicI64::new(last_decision_id + 1)),
+        })
+    }
+
+    /// Wait for queued writes (chat, decisions, episodes, LLM calls) to
+    /// reach the database; call before shutdown or reading them back
+    pub async fn flush(&self) -> Result<()> {
+        let mut embedding = std::mem::take(&mut *self.embedding.lock());
+        while let Some(result) = embedding.join_next().await {
+            if let Err(err) = result {
+                tracing::warn!(?err, "Episode embedding task failed");
+            }
+        }
+        self.writes.flush().await
+    }
+
+    pub fn scope(&self) -> MemoryScope {
+        self.scope
     }
 
-    pub async fn record_chat(&self, packet: &ChatPacket) -> Result<()> {
+    /// The memory namespace a companion reads and writes: its own with
+    /// per-character memory, otherwise the shared one (None)
+    fn namespace<'a>(&self, character_id: Option<&'a str>) -> Option<&'a str> {
+        match self.scope {
+            MemoryScope::Shared => None,
+            MemoryScope::PerCharacter => character_id,
+        }
+    }
+
+    /// Embed episodes with this model (`LlmClients::embedding`), enabling
+    /// `search_similar`
+    pub fn with_embedder(mut self, embedder: Option<(SharedLlm, String)>) -> Self {
+        self.embedder = embedder;
+        self
+    }
+
+    /// Store an episode, embedding its content first if it has no embedding
+    /// and an embedding model is configured
+    pub async fn record_episode(&self, episode: &Episode) -> Result<()> {
+        if !self.needs_embedding(episode) {
+            return self.db.add_episode(episode).await;
+        }
+        let episode = self.embedded(episode.clone()).await;
+        self.db.add_episode(&episode).await
+    }
+
+    /// Queue an episode for the write-behind writer, embedding it first in
+    /// the background, so neither delays the caller
+    pub fn remember(&self, episode: Episode) {
+        if !self.needs_embedding(&episode) {
+            self.writes.push(Write::Episode(episode));
+            return;
+        }
+        let storage = self.clone();
+        let mut embedding = self.embedding.lock();
+        // Drop finished tasks so the set doesn't grow between flushes
+        while embedding.try_join_next().is_some() {}
+        embedding.spawn(async move {
+            let episode = storage.embedded(episode).await;
+            storage.writes.push(Write::Episode(episode));
+        });
+    }
+
+    fn needs_embedding(&self, episode: &Episode) -> bool {
+        episode.embedding.is_none() && self.embedder.is_some()
+    }
+
+    async fn embedded(&self, mut episode: Episode) -> Episode {
+        match self.embed(&episode.content).await {
+            Ok(embedding) => episode.embedding = Some(embedding),
+            // Still worth remembering, just not findable by meaning
+            Err(err) => tracing::warn!(?err, "Failed to embed episode"),
+        }
+        episode
+    }
+
+    /// The `k` episodes closest in meaning to `text`, with their cosine
+    /// similarity, best first, from the memory `character_id` can see
+    pub async fn search_similar(
+        &self,
+        text: &str,
+        k: usize,
+        character_id: Option<&str>,
+    ) -> Result<Vec<(Episode, f32)>> {
+        let query = self.embed(text).await?;
         self.db
-            .add_chat_message(&packet.sender, &packet.content)
-            .await?;
-        Ok(())
+            .search_similar(&query, k, self.namespace(character_id))
+            .await
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let (client, model) = self
+            .embedder
+            .as_ref()
+            .ok_or_else(|| anyhow!("No [llm.embedding] model configured"))?;
+        client
+            .embed(model, vec![text.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Embedding model returned no vectors"))
+    }
+
+    /// Queue a chat message, which joins the current conversation session
+    pub fn record_chat(&self, packet: &ChatPacket) {
+        self.writes.push(Write::Chat(packet.clone()));
+    }
+
+    /// Queue the decayed relevance and tiers of the chat in view, so a
+    /// restart doesn't refresh old chat
+    pub fn save_chat_relevance<'a>(&self, packets: impl IntoIterator<Item = &'a ChatPacket>) {
+        let packets = packets.into_iter().cloned().collect();
+        self.writes.push(Write::ChatRelevance(packets));
+    }
+
+    /// Queue `packet`'s content in place of the message its sender sent at
+    /// its timestamp
+    pub fn replace_chat(&self, packet: &ChatPacket) {
+        self.writes.push(Write::ChatEdit(packet.clone()));
     }
 
     pub async fn recent_chat(&self, limit: usize) -> Result<Vec<ChatPacket>> {
-        use crate::bridge::MemoryTier;
-        
         let messages = self.db.get_recent_chat(limit).await?;
-        Ok(messages
-            .into_iter()
-            .map(|msg| ChatPacket {
-                sender: msg.sender,
-                content: msg.content,
-                timestamp: msg.timestamp,
-                relevance: 1.0,  // Fresh from DB = full relevance
-                tier: MemoryTier::Hot,
-            })
-            .collect())
+        Ok(messages.into_iter().map(ChatPacket::from).collect())
     }
 
-    pub async fn record_decision(&self, decision: &StoredDecision) -> Result<()> {
-        self.db
-            .log_arbiter_decision(
-                decision.should_respond,
-                decision.responder_id.as_deref(),
-                &decision.reasoning,
-                decision.urgency,
-                &decision.context_summary,
-            )
-            .await?;
-        Ok(())
+    /// Queue a companion's mood and relationship score
+    pub fn save_character_state(&self, state: &CharacterState) {
+        self.writes.push(Write::CharacterState(state.clone()));
     }
-    
-    /// Save ARIAOS Notes state
-    pub async fn save_ariaos_notes(&self, state: &AriaosNotesState) -> Result<()> {
-        self.db.save_ariaos_notes(state).await
+
+    pub async fn character_state(&self, character_id: &str) -> Result<Option<CharacterState>> {
+        self.db.get_character_state(character_id).await
+    }
+
+    /// Queue an arbiter decision, returning the id it will be stored under
+    pub fn record_decision(&self, decision: &StoredDecision) -> i64 {
+        let id = self.next_decision_id.fetch_add(1, Ordering::Relaxed);
+        self.writes.push(Write::Decision(id, decision.clone()));
+        id
     }
     
-    /// Load ARIAOS Notes state
-    pub async fn load_ariaos_notes(&self) -> Result<Option<AriaosNotesState>> {
-        self.db.load_ariaos_notes().await
+    /// Queue a prompt/response exchange with a model
+    pub fn record_llm_call(&self, call: StoredLlmCall) {
+        self.writes.push(Write::LlmCall(call));
+    }
+
+    /// Most recent LLM exchanges, newest first
+    pub async fn recent_llm_calls(&self, limit: usize) -> Result<Vec<StoredLlmCall>> {
+        self.db.get_recent_llm_calls(limit).await
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredDecision {
     pub timestamp: i64,
     pub should_respond: bool,
     pub responder_id: Option<String>,
     pub reasoning: String,
     pub urgency: f32,
+    /// Mood suggested for the reply
+    #[serde(default)]
+    pub mood: Option<String>,
     pub context_summary: String,
+    pub outcome: DecisionOutcome,
 }
 
 impl StoredDecision {
@@ -164,7 +422,178 @@ impl StoredDecision {
             responder_id,
             reasoning: reasoning.into(),
             urgency,
+            mood: None,
             context_summary: String::new(),
+            outcome: if should_respond {
+                DecisionOutcome::Spoke
+            } else {
+                DecisionOutcome::Declined
+            },
+        }
+    }
+
+    pub fn with_outcome(mut self, outcome: DecisionOutcome) -> Self {
+        self.outcome = outcome;
+        self
+    }
+}
+
+/// A prompt/response exchange with a model (images stripped)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredLlmCall {
+    pub timestamp: i64,
+    /// "vla", "arbiter", "response" or "audit"
+    pub model_type: String,
+    pub model_name: String,
+    pub prompt: String,
+    pub response: String,
+    pub latency_ms: u64,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{MockLlmClient, MockResponse};
+
+    fn episode(content: &str, embedding: Option<Vec<f32>>) -> Episode {
+        Episode {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().timestamp(),
+            event_type: "observation".into(),
+            actor: None,
+            content: content.into(),
+            emotional_valence: 0.0,
+            importance: 0.5,
+            screen_context: None,
+            embedding,
+            character_id: None,
         }
     }
+
+    #[tokio::test]
+    async fn test_search_similar_ranks_by_meaning() {
+        let embedder = MockLlmClient::new()
+            .push(MockResponse::Embeddings(vec![vec![0.9, 0.1, 0.0]]))
+            .push(MockResponse::Embeddings(vec![vec![1.0, 0.0, 0.0]]));
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage")
+        .with_embedder(Some((Arc::new(embedder) as SharedLlm, "mock-embed".into())));
+
+        let episodes = [
+            episode("user fixed the borrow checker error", None),
+            episode("user watched a cooking video", Some(vec![0.0, 1.0, 0.0])),
+            episode("user debugged a lifetime issue", Some(vec![0.7, 0.7, 0.0])),
+        ];
+        for episode in &episodes {
+            storage.record_episode(episode).await.unwrap();
+        }
+
+        let results = storage
+            .search_similar("rust errors", 2, None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = results.iter().map(|(e, _)| e.id.as_str()).collect();
+        assert_eq!(ids, [episodes[0].id.as_str(), episodes[2].id.as_str()]);
+        assert!(results[0].1 > results[1].1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_waits_for_embedding() {
+        let embedder = MockLlmClient::new().push(MockResponse::Embeddings(vec![vec![1.0, 0.0]]));
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage")
+        .with_embedder(Some((Arc::new(embedder) as SharedLlm, "mock-embed".into())));
+
+        let remembered = episode("user renamed the parser module", None);
+        storage.remember(remembered.clone());
+        storage.flush().await.unwrap();
+
+        let results = storage
+            .db
+            .search_similar(&[1.0, 0.0], 5, None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = results.iter().map(|(e, _)| e.id.as_str()).collect();
+        assert_eq!(ids, [remembered.id.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn test_per_character_namespaces() {
+        let embedder = MockLlmClient::new().push(MockResponse::Embeddings(vec![vec![1.0, 0.0]]));
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            memory_scope: MemoryScope::PerCharacter,
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage")
+        .with_embedder(Some((Arc::new(embedder) as SharedLlm, "mock-embed".into())));
+
+        let owners = [None, Some("aria"), Some("bob")];
+        for owner in owners {
+            let mut episode = episode("memory", Some(vec![1.0, 0.0]));
+            episode.character_id = owner.map(str::to_string);
+            storage.record_episode(&episode).await.unwrap();
+        }
+        let seen = storage
+            .search_similar("memory", 10, Some("aria"))
+            .await
+            .unwrap();
+        let mut owners: Vec<_> = seen.iter().map(|(e, _)| e.character_id.clone()).collect();
+        owners.sort();
+        assert_eq!(owners, [None, Some("aria".to_string())]);
+
+        let notes = AriaosNotesState {
+            content: "aria's list".into(),
+            scroll_offset: 0.0,
+            owner: None,
+        };
+        storage
+            .save_ariaos_notes(Some("aria"), &notes)
+            .await
+            .unwrap();
+        let load = |id| storage.load_ariaos_notes(id);
+        let aria = load(Some("aria")).await.unwrap().unwrap();
+        assert_eq!(aria.content, "aria's list");
+        assert!(load(Some("bob")).await.unwrap().is_none());
+        assert!(load(None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chat_relevance_survives_reload() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage");
+        let mut packet = ChatPacket {
+            sender: "user".into(),
+            content: "old news".into(),
+            timestamp: Utc::now().timestamp() - 3600,
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id: None,
+        };
+        storage.record_chat(&packet);
+        packet.apply_decay(0.9, 30.0);
+        packet.update_tier(0.3);
+        storage.save_chat_relevance([&packet]);
+        storage.flush().await.unwrap();
+
+        let chat = storage.recent_chat(10).await.unwrap();
+        assert_eq!(chat.len(), 1);
+        assert!((chat[0].relevance - packet.relevance).abs() < 1e-4);
+        assert_eq!(chat[0].tier, MemoryTier::Cold);
+    }
 }
@@ -0,0 +1,125 @@
+//! Revision history for the ARIAOS notes, so a bad rewrite can be undone
+
+use anyhow::{Result, bail};
+use chrono::Utc;
+use similar::TextDiff;
+
+use super::{NotesRevision, Storage};
+
+/// Revisions kept per notes owner
+const REVISIONS_KEPT: usize = 200;
+
+impl Storage {
+    /// Record a change made by `command` to `owner`'s notes; nothing is
+    /// stored when the text is unchanged
+    pub async fn record_notes_revision(
+        &self,
+        owner: Option<&str>,
+        command: &str,
+        previous: &str,
+        content: &str,
+    ) -> Result<Option<i64>> {
+        if previous == content {
+            return Ok(None);
+        }
+        let diff = TextDiff::from_lines(previous, content)
+            .unified_diff()
+            .context_radius(2)
+            .to_string();
+        let revision = NotesRevision {
+            id: 0,
+            owner: self.namespace(owner).map(str::to_string),
+            timestamp: Utc::now().timestamp(),
+            command: command.to_string(),
+            diff,
+            previous: previous.to_string(),
+            content: content.to_string(),
+        };
+        let id = self
+            .db
+            .add_notes_revision(&revision, REVISIONS_KEPT)
+            .await?;
+        Ok(Some(id))
+    }
+
+    /// `owner`'s newest `limit` revisions, newest first
+    pub async fn notes_revisions(
+        &self,
+        owner: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<NotesRevision>> {
+        self.db
+            .get_notes_revisions(self.namespace(owner), limit)
+            .await
+    }
+
+    pub async fn notes_revision(&self, id: i64) -> Result<Option<NotesRevision>> {
+        self.db.get_notes_revision(id).await
+    }
+
+    /// The text reverting `owner`'s notes restores: as of `revision_id`, or
+    /// from before the last change. None when there is nothing to revert
+    pub async fn notes_revert_target(
+        &self,
+        owner: Option<&str>,
+        revision_id: Option<i64>,
+    ) -> Result<Option<String>> {
+        let owner = self.namespace(owner);
+        let Some(id) = revision_id else {
+            let last = self.db.get_notes_revisions(owner, 1).await?;
+            return Ok(last.into_iter().next().map(|r| r.previous));
+        };
+        match self.db.get_notes_revision(id).await? {
+            Some(revision) if revision.owner.as_deref() == owner => Ok(Some(revision.content)),
+            Some(_) => bail!("Revision {id} belongs to other notes"),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StorageConfig;
+
+    #[tokio::test]
+    async fn test_revisions_restore_overwritten_notes() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let edits = [
+            ("notes_append", "", "buy milk"),
+            ("notes_append", "buy milk", "buy milk\ncall mom"),
+            ("notes_set_content", "buy milk\ncall mom", "oops"),
+        ];
+        let mut ids = Vec::new();
+        for (command, previous, content) in edits {
+            let id = storage
+                .record_notes_revision(None, command, previous, content)
+                .await
+                .unwrap();
+            ids.push(id.unwrap());
+        }
+        let unchanged = storage
+            .record_notes_revision(None, "notes_append", "oops", "oops")
+            .await
+            .unwrap();
+        assert!(unchanged.is_none());
+
+        let revisions = storage.notes_revisions(None, 10).await.unwrap();
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(revisions[0].command, "notes_set_content");
+        assert!(revisions[0].diff.contains("-call mom"));
+        assert!(revisions[0].diff.contains("+oops"));
+
+        let undo = storage.notes_revert_target(None, None).await.unwrap();
+        assert_eq!(undo.as_deref(), Some("buy milk\ncall mom"));
+        let first = storage.notes_revert_target(None, Some(ids[0])).await;
+        assert_eq!(first.unwrap().as_deref(), Some("buy milk"));
+        let missing = storage.notes_revert_target(None, Some(999)).await;
+        assert!(missing.unwrap().is_none());
+    }
+}
@@ -0,0 +1,148 @@
+//! What the companions have learned about the user: name, timezone,
+//! preferences, habits, likes and dislikes, distilled from chat by
+//! `director::ProfileLearner` and shown to the response model
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::Storage;
+
+/// Entries kept per list; the oldest go first
+const MAX_ITEMS: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserProfile {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// IANA name, e.g. `Europe/Berlin`
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// How the user likes things done
+    #[serde(default)]
+    pub preferences: Vec<String>,
+    /// Recurring routines
+    #[serde(default)]
+    pub habits: Vec<String>,
+    #[serde(default)]
+    pub likes: Vec<String>,
+    #[serde(default)]
+    pub dislikes: Vec<String>,
+    /// Timestamp of the newest chat message learned from
+    #[serde(default)]
+    pub learned_until: i64,
+}
+
+impl UserProfile {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.timezone.is_none()
+            && self.preferences.is_empty()
+            && self.habits.is_empty()
+            && self.likes.is_empty()
+            && self.dislikes.is_empty()
+    }
+
+    /// Fold newly learned facts in: a new name or timezone replaces the old
+    /// one, list entries are added unless already known (ignoring case)
+    pub fn merge(&mut self, learned: UserProfile) {
+        let nonempty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+        if let Some(name) = nonempty(learned.name) {
+            self.name = Some(name);
+        }
+        if let Some(timezone) = nonempty(learned.timezone) {
+            self.timezone = Some(timezone);
+        }
+        merge_list(&mut self.preferences, learned.preferences);
+        merge_list(&mut self.habits, learned.habits);
+        merge_list(&mut self.likes, learned.likes);
+        merge_list(&mut self.dislikes, learned.dislikes);
+        self.learned_until = self.learned_until.max(learned.learned_until);
+    }
+
+    /// One line per known fact, for prompts
+    pub fn summary(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(name) = &self.name {
+            lines.push(format!("Name: {name}"));
+        }
+        if let Some(timezone) = &self.timezone {
+            lines.push(format!("Timezone: {timezone}"));
+        }
+        let lists = [
+            ("Preferences", &self.preferences),
+            ("Habits", &self.habits),
+            ("Likes", &self.likes),
+            ("Dislikes", &self.dislikes),
+        ];
+        for (label, items) in lists {
+            if !items.is_empty() {
+                lines.push(format!("{label}: {}", items.join("; ")));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+fn merge_list(list: &mut Vec<String>, learned: Vec<String>) {
+    for item in learned {
+        let item = item.trim();
+        if item.is_empty() || list.iter().any(|known| known.eq_ignore_ascii_case(item)) {
+            continue;
+        }
+        list.push(item.to_string());
+    }
+    if list.len() > MAX_ITEMS {
+        list.drain(..list.len() - MAX_ITEMS);
+    }
+}
+
+impl Storage {
+    /// The learned profile (empty until something is learned)
+    pub async fn user_profile(&self) -> Result<UserProfile> {
+        Ok(self.db.load_user_profile().await?.unwrap_or_default())
+    }
+
+    pub async fn save_user_profile(&self, profile: &UserProfile) -> Result<()> {
+        self.db.save_user_profile(profile).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StorageConfig;
+
+    #[tokio::test]
+    async fn test_profile_merges_and_persists() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(storage.user_profile().await.unwrap().is_empty());
+
+        let mut profile = UserProfile {
+            name: Some("Sam".into()),
+            likes: vec!["jazz".into()],
+            learned_until: 100,
+            ..Default::default()
+        };
+        profile.merge(UserProfile {
+            name: Some(" ".into()),
+            timezone: Some("Europe/Berlin".into()),
+            likes: vec!["Jazz".into(), "hiking".into()],
+            learned_until: 200,
+            ..Default::default()
+        });
+        storage.save_user_profile(&profile).await.unwrap();
+
+        let loaded = storage.user_profile().await.unwrap();
+        assert_eq!(loaded, profile);
+        assert_eq!(
+            loaded.summary(),
+            "Name: Sam\nTimezone: Europe/Berlin\nLikes: jazz; hiking"
+        );
+        assert_eq!(loaded.learned_until, 200);
+    }
+}
@@ -0,0 +1,313 @@
+//! Write-behind queue: chat, decisions, episodes and LLM calls are written
+//! by a background task, so a slow database round-trip never holds up a
+//! perception tick or a user message. While a remote database is
+//! unreachable, writes go to the offline journal and are synced when it
+//! comes back.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{
+    CharacterState, ChatMessage, Episode, StorageBackend, StoredDecision, StoredLlmCall,
+    journal::Journal,
+};
+use crate::bridge::ChatPacket;
+
+/// Writes taken off the queue at once
+const MAX_BATCH: usize = 64;
+/// How often an offline writer checks whether the database is back
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Write {
+    Chat(ChatPacket),
+    /// Current relevance of the chat in view; only the newest is kept when
+    /// several are waiting
+    ChatRelevance(Vec<ChatPacket>),
+    /// New content for the message its sender sent at its timestamp
+    ChatEdit(ChatPacket),
+    Decision(i64, StoredDecision),
+    Episode(Episode),
+    LlmCall(StoredLlmCall),
+    CharacterState(CharacterState),
+    /// Answered once everything queued before it is written (or journaled)
+    #[serde(skip)]
+    Flush(oneshot::Sender<()>),
+}
+
+impl Write {
+    fn label(&self) -> &'static str {
+        match self {
+            Write::Chat(_) => "chat message",
+            Write::ChatRelevance(_) => "chat relevance",
+            Write::ChatEdit(_) => "chat edit",
+            Write::Decision(..) => "arbiter decision",
+            Write::Episode(_) => "episode",
+            Write::LlmCall(_) => "LLM call",
+            Write::CharacterState(_) => "character state",
+            Write::Flush(_) => "flush",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct WriteQueue {
+    tx: mpsc::UnboundedSender<Write>,
+}
+
+impl WriteQueue {
+    /// Start the writer task for `db`, journaling writes while it is
+    /// unreachable if given a `journal`
+    pub fn spawn(
+        db: Arc<dyn StorageBackend>,
+        session_idle_secs: i64,
+        journal: Option<Journal>,
+    ) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let writer = Writer {
+            db,
+            session_idle_secs,
+            offline: journal.as_ref().is_some_and(Journal::has_pending),
+            journal,
+            pending_relevance: None,
+        };
+        tokio::spawn(run(writer, rx));
+        Self { tx }
+    }
+
+    pub fn push(&self, write: Write) {
+        let label = write.label();
+        if self.tx.send(write).is_err() {
+            tracing::warn!(write = label, "Storage writer stopped; dropping write");
+        }
+    }
+
+    /// Wait until every write queued so far has been made
+    pub async fn flush(&self) -> Result<()> {
+        let (done, wait) = oneshot::channel();
+        self.tx
+            .send(Write::Flush(done))
+            .ok()
+            .context("Storage writer stopped")?;
+        wait.await.context("Storage writer stopped")
+    }
+}
+
+async fn run(mut writer: Writer, mut rx: mpsc::UnboundedReceiver<Write>) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    let mut retry = tokio::time::interval(RETRY_INTERVAL);
+    retry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            received = rx.recv_many(&mut batch, MAX_BATCH) => {
+                if received == 0 {
+                    break;
+                }
+                // Relevance snapshots supersede each other
+                let last_relevance = batch
+                    .iter()
+                    .rposition(|w| matches!(w, Write::ChatRelevance(_)));
+                for (i, write) in batch.drain(..).enumerate() {
+                    if matches!(write, Write::ChatRelevance(_)) && Some(i) != last_relevance {
+                        continue;
+                    }
+                    writer.write(write).await;
+                }
+            }
+            _ = retry.tick(), if writer.offline => writer.sync().await,
+        }
+    }
+}
+
+struct Writer {
+    db: Arc<dyn StorageBackend>,
+    session_idle_secs: i64,
+    journal: Option<Journal>,
+    /// Whether writes are going to the journal
+    offline: bool,
+    /// Newest relevance snapshot seen while offline, applied after syncing
+    /// rather than journaled
+    pending_relevance: Option<Write>,
+}
+
+impl Writer {
+    async fn write(&mut self, write: Write) {
+        if let Write::Flush(done) = write {
+            let _ = done.send(());
+            return;
+        }
+        if self.offline {
+            self.defer(write);
+            return;
+        }
+        let Err(err) = apply(self.db.as_ref(), self.session_idle_secs, &write).await else {
+            return;
+        };
+        if self.journal.is_some() && !self.reachable().await {
+            tracing::warn!(
+                ?err,
+                "Database unreachable; journaling writes until it is back"
+            );
+            self.offline = true;
+            self.defer(write);
+        } else {
+            tracing::warn!(?err, write = write.label(), "Failed to write to storage");
+        }
+    }
+
+    /// Journal a write, keeping only the newest relevance snapshot in memory
+    fn defer(&mut self, write: Write) {
+        if matches!(write, Write::ChatRelevance(_)) {
+            self.pending_relevance = Some(write);
+            return;
+        }
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        if let Err(err) = journal.append(&write) {
+            tracing::warn!(
+                ?err,
+                write = write.label(),
+                "Failed to journal write; dropping it"
+            );
+        }
+    }
+
+    /// Whether the database answers at all, telling an outage apart from a
+    /// write it rejects
+    async fn reachable(&self) -> bool {
+        self.db.last_decision_id().await.is_ok()
+    }
+
+    /// Replay the journal in order, going back online once it is empty
+    async fn sync(&mut self) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let writes = match journal.read() {
+            Ok(writes) => writes,
+            Err(err) => {
+                tracing::warn!(?err, "Failed to read the offline journal");
+                return;
+            }
+        };
+        let mut synced = 0;
+        for write in &writes {
+            if let Err(err) = apply(self.db.as_ref(), self.session_idle_secs, write).await {
+                if !self.reachable().await {
+                    break;
+                }
+                tracing::warn!(?err, write = write.label(), "Dropping journaled write");
+            }
+            synced += 1;
+            // Checkpoint so a crash mid-sync replays little twice
+            if synced % MAX_BATCH == 0 {
+                if let Err(err) = journal.replace(&writes[synced..]) {
+                    tracing::warn!(?err, "Failed to update the offline journal");
+                    return;
+                }
+            }
+        }
+        if let Err(err) = journal.replace(&writes[synced..]) {
+            tracing::warn!(?err, "Failed to update the offline journal");
+            return;
+        }
+        if synced < writes.len() {
+            return;
+        }
+        tracing::info!(
+            writes = synced,
+            "Database reachable again; offline journal synced"
+        );
+        self.offline = false;
+        if let Some(write) = self.pending_relevance.take() {
+            self.write(write).await;
+        }
+    }
+}
+
+async fn apply(db: &dyn StorageBackend, session_idle_secs: i64, write: &Write) -> Result<()> {
+    match write {
+        Write::Chat(packet) => {
+            let session_id = db
+                .touch_session(packet.timestamp, session_idle_secs)
+                .await?;
+            db.insert_chat_message(&ChatMessage {
+                id: 0,
+                timestamp: packet.timestamp,
+                sender: packet.sender.clone(),
+                content: packet.content.clone(),
+                in_response_to: None,
+                character_id: packet.character_id.clone(),
+                relevance: packet.relevance,
+                tier: packet.tier.as_str().into(),
+                session_id: Some(session_id),
+            })
+            .await?;
+        }
+        Write::ChatRelevance(packets) => {
+            for packet in packets {
+                db.update_chat_relevance(
+                    packet.timestamp,
+                    &packet.sender,
+                    packet.relevance,
+                    packet.tier.as_str(),
+                )
+                .await?;
+            }
+        }
+        Write::ChatEdit(packet) => {
+            db.update_chat_content(packet.timestamp, &packet.sender, &packet.content)
+                .await?
+        }
+        Write::Decision(id, decision) => db.log_arbiter_decision(*id, decision).await?,
+        Write::Episode(episode) => db.add_episode(episode).await?,
+        Write::LlmCall(call) => db.log_llm_call(call).await?,
+        Write::CharacterState(state) => db.update_character_state(state).await?,
+        Write::Flush(_) => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::StorageConfig,
+        storage::{Storage, StoredDecision},
+    };
+
+    #[tokio::test]
+    async fn test_queued_writes_land_on_flush() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let ids: Vec<_> = (0..3)
+            .map(|i| {
+                let decision = StoredDecision::now(i == 2, None, format!("tick {i}"), 0.0);
+                storage.record_decision(&decision)
+            })
+            .collect();
+        assert_eq!(ids, [1, 2, 3]);
+
+        storage.flush().await.unwrap();
+        let export = storage
+            .db
+            .export_tables(&["arbiter_decisions"])
+            .await
+            .unwrap();
+        let stored: Vec<_> = export["arbiter_decisions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(stored, ids);
+    }
+}
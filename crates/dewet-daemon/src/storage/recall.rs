@@ -0,0 +1,185 @@
+//! Recall: the episodes most worth bringing back for the current topic,
+//! ranked by meaning (or shared words, without an embedding model),
+//! recency and importance
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::Utc;
+use tracing::warn;
+
+use super::{Episode, Storage};
+use crate::config::RecallConfig;
+
+/// Most similar episodes ranked, per memory wanted
+const CANDIDATES_PER_MEMORY: usize = 8;
+
+/// Recent episodes searched for shared words without an embedding model
+const KEYWORD_CANDIDATES: usize = 1000;
+
+/// Shared words that make an episode fully relevant without embeddings
+const FULL_MATCH_WORDS: usize = 3;
+
+/// Words too short or common to say what a topic is about
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "also", "been", "before", "being", "could", "does", "doing", "from",
+    "going", "have", "here", "just", "know", "like", "make", "more", "much", "only", "really",
+    "should", "some", "still", "than", "that", "their", "them", "then", "there", "these", "they",
+    "thing", "think", "this", "user", "want", "what", "when", "where", "which", "while", "with",
+    "would", "your",
+];
+
+/// Distinct lowercase words of four letters or more, without stopwords
+pub fn topic_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 4)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Topic words shared with `text`, where `FULL_MATCH_WORDS` of them (or all,
+/// for a shorter topic) count as 1
+pub fn keyword_relevance(topic: &HashSet<String>, text: &str) -> f32 {
+    if topic.is_empty() {
+        return 0.0;
+    }
+    let shared = topic.intersection(&topic_words(text)).count();
+    (shared as f32 / topic.len().min(FULL_MATCH_WORDS) as f32).min(1.0)
+}
+
+impl Storage {
+    /// Up to `config.max_memories` episodes from before `before` (unix
+    /// seconds) that relate to `topic`, from the memory `character_id` can
+    /// see, best first
+    pub async fn recall(
+        &self,
+        topic: &str,
+        before: i64,
+        character_id: Option<&str>,
+        config: &RecallConfig,
+    ) -> Result<Vec<Episode>> {
+        if config.max_memories == 0 || topic.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        let candidates = if self.embedder.is_some() {
+            self.similar_candidates(topic, config, character_id).await
+        } else {
+            self.keyword_candidates(topic, character_id).await?
+        };
+
+        let now = Utc::now().timestamp();
+        let half_life = config.recency_half_life_hours.max(0.1) * 3600.0;
+        let mut scored: Vec<(Episode, f32)> = candidates
+            .into_iter()
+            .filter(|(episode, relevance)| {
+                episode.timestamp < before && *relevance >= config.min_relevance
+            })
+            .map(|(episode, relevance)| {
+                let age = (now - episode.timestamp).max(0) as f32;
+                let recency = 0.5f32.powf(age / half_life);
+                let score = 0.6 * relevance + 0.2 * recency + 0.2 * episode.importance;
+                (episode, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut seen = HashSet::new();
+        Ok(scored
+            .into_iter()
+            .map(|(episode, _)| episode)
+            .filter(|episode| seen.insert(episode.content.clone()))
+            .take(config.max_memories)
+            .collect())
+    }
+
+    /// Episodes close in meaning to `topic`, with their cosine similarity
+    async fn similar_candidates(
+        &self,
+        topic: &str,
+        config: &RecallConfig,
+        character_id: Option<&str>,
+    ) -> Vec<(Episode, f32)> {
+        let k = config.max_memories * CANDIDATES_PER_MEMORY;
+        self.search_similar(topic, k, character_id)
+            .await
+            .unwrap_or_else(|err| {
+                // Recall is a nicety; the reply goes ahead without it
+                warn!(?err, "Failed to search memories");
+                Vec::new()
+            })
+    }
+
+    /// Recent episodes sharing words with `topic`, with their relevance
+    async fn keyword_candidates(
+        &self,
+        topic: &str,
+        character_id: Option<&str>,
+    ) -> Result<Vec<(Episode, f32)>> {
+        let topic = topic_words(topic);
+        let namespace = self.namespace(character_id);
+        Ok(self
+            .db
+            .get_recent_episodes(KEYWORD_CANDIDATES)
+            .await?
+            .into_iter()
+            .filter(|episode| {
+                namespace.is_none()
+                    || episode.character_id.is_none()
+                    || episode.character_id.as_deref() == namespace
+            })
+            .map(|episode| {
+                let relevance = keyword_relevance(&topic, &episode.content);
+                (episode, relevance)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::StorageConfig, storage::EpisodeKind};
+
+    #[tokio::test]
+    async fn test_recall_finds_older_related_episodes() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .expect("in-memory storage");
+
+        let now = Utc::now().timestamp();
+        let episodes = [
+            (now - 7 * 86400, "User is migrating the parser to nom"),
+            (now - 86400, "User went hiking at the weekend"),
+            (now - 3600, "Parser migration: nom combinators compile now"),
+            (now - 60, "User mentioned the nom parser again"),
+        ];
+        for (timestamp, content) in episodes {
+            let mut episode = Episode::now(EpisodeKind::UserMessage, None, content);
+            episode.timestamp = timestamp;
+            storage.record_episode(&episode).await.unwrap();
+        }
+
+        // The last one is still in the chat window, so it isn't recalled
+        let recalled = storage
+            .recall(
+                "how is the nom parser going?",
+                now - 600,
+                None,
+                &RecallConfig::default(),
+            )
+            .await
+            .unwrap();
+        let contents: Vec<&str> = recalled.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            [
+                "Parser migration: nom combinators compile now",
+                "User is migrating the parser to nom",
+            ]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Reminders companions set with the `set_reminder` tool, kept until the
+//! scheduler hands them back when due
+
+use anyhow::Result;
+use chrono::Utc;
+
+use super::{Reminder, Storage};
+
+impl Storage {
+    /// Remind the user of `text` at `due_at` (unix seconds), through
+    /// `character_id` when given
+    pub async fn add_reminder(
+        &self,
+        due_at: i64,
+        character_id: Option<&str>,
+        text: &str,
+    ) -> Result<Reminder> {
+        let mut reminder = Reminder {
+            id: 0,
+            due_at,
+            character_id: character_id.map(str::to_string),
+            text: text.to_string(),
+            created_at: Utc::now().timestamp(),
+        };
+        reminder.id = self.db.add_reminder(&reminder).await?;
+        Ok(reminder)
+    }
+
+    /// Remove and return the reminders due by `now`, soonest first
+    pub async fn take_due_reminders(&self, now: i64) -> Result<Vec<Reminder>> {
+        let due = self.db.due_reminders(now).await?;
+        if !due.is_empty() {
+            let ids: Vec<i64> = due.iter().map(|r| r.id).collect();
+            self.db.delete_reminders(&ids).await?;
+        }
+        Ok(due)
+    }
+
+    /// Reminders still to come, soonest first
+    pub async fn pending_reminders(&self) -> Result<Vec<Reminder>> {
+        self.db.pending_reminders(Utc::now().timestamp()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StorageConfig;
+
+    #[tokio::test]
+    async fn test_due_reminders_are_taken_once() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        storage
+            .add_reminder(200, Some("aria"), "stand up")
+            .await
+            .unwrap();
+        storage.add_reminder(100, None, "call mum").await.unwrap();
+        storage.add_reminder(i64::MAX, None, "later").await.unwrap();
+
+        let due = storage.take_due_reminders(150).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].text, "call mum");
+
+        let due = storage.take_due_reminders(300).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].character_id.as_deref(), Some("aria"));
+        assert!(storage.take_due_reminders(300).await.unwrap().is_empty());
+
+        let pending = storage.pending_reminders().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].text, "later");
+    }
+}
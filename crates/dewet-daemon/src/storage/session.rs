@@ -0,0 +1,77 @@
+//! Conversation sessions: chat is grouped into sessions that end after
+//! `session_idle_minutes` without a message
+
+use anyhow::Result;
+use chrono::Utc;
+
+use super::{ChatSession, Storage};
+use crate::bridge::ChatPacket;
+
+impl Storage {
+    /// End open sessions that have gone idle, returning how many
+    pub async fn close_idle_sessions(&self) -> Result<u64> {
+        let cutoff = Utc::now().timestamp() - self.session_idle_secs;
+        self.db.close_sessions_idle_since(cutoff).await
+    }
+
+    /// The newest `limit` sessions, newest first
+    pub async fn list_sessions(&self, limit: usize) -> Result<Vec<ChatSession>> {
+        self.close_idle_sessions().await?;
+        self.db.get_sessions(limit).await
+    }
+
+    /// Every message in a session, oldest first
+    pub async fn session_transcript(&self, session_id: i64) -> Result<Vec<ChatPacket>> {
+        let messages = self.db.get_session_chat(session_id).await?;
+        Ok(messages.into_iter().map(ChatPacket::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bridge::MemoryTier, config::StorageConfig};
+
+    fn packet(content: &str, timestamp: i64) -> ChatPacket {
+        ChatPacket {
+            sender: "user".into(),
+            content: content.into(),
+            timestamp,
+            relevance: 1.0,
+            tier: MemoryTier::Hot,
+            character_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inactivity_splits_sessions() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            session_idle_minutes: 30,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let now = Utc::now().timestamp();
+        let messages = [
+            ("morning", now - 3 * 3600),
+            ("still here", now - 3 * 3600 + 600),
+            ("back again", now - 60),
+        ];
+        for (content, timestamp) in messages {
+            storage.record_chat(&packet(content, timestamp));
+        }
+        storage.flush().await.unwrap();
+
+        let sessions = storage.list_sessions(10).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        let (latest, earlier) = (&sessions[0], &sessions[1]);
+        assert_eq!(latest.ended_at, None);
+        assert_eq!(earlier.ended_at, Some(now - 3 * 3600 + 600));
+        assert_eq!(earlier.message_count, 2);
+
+        let transcript = storage.session_transcript(earlier.id).await.unwrap();
+        let contents: Vec<_> = transcript.iter().map(|p| p.content.as_str()).collect();
+        assert_eq!(contents, ["morning", "still here"]);
+    }
+}
@@ -0,0 +1,170 @@
+//! Screenshots companions spoke about, kept as downscaled JPEGs so the
+//! composite's visual history survives a restart
+
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use image::{
+    DynamicImage, RgbaImage,
+    codecs::jpeg::JpegEncoder,
+    imageops::{FilterType, resize},
+};
+
+use super::{Storage, StoredSnapshot};
+
+const JPEG_QUALITY: u8 = 80;
+
+/// What a snapshot shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// The raw desktop, as shown in PREV panels
+    Screenshot,
+    /// The composite the models saw
+    Composite,
+}
+
+impl SnapshotKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SnapshotKind::Screenshot => "screenshot",
+            SnapshotKind::Composite => "composite",
+        }
+    }
+}
+
+/// A decoded snapshot
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: i64,
+    /// The arbiter decision it was taken for
+    pub decision_id: Option<i64>,
+    pub kind: String,
+    pub timestamp: DateTime<Utc>,
+    pub image: RgbaImage,
+}
+
+impl Storage {
+    /// Store `image` in the background if `[storage.snapshots]` is enabled
+    /// (composites only with `composites = true`)
+    pub fn keep_snapshot(&self, decision_id: Option<i64>, kind: SnapshotKind, image: RgbaImage) {
+        let Some(config) = &self.snapshots else {
+            return;
+        };
+        if kind == SnapshotKind::Composite && !config.composites {
+            return;
+        }
+        let storage = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = storage.save_snapshot(decision_id, kind, image).await {
+                tracing::warn!(?err, kind = kind.as_str(), "Failed to save snapshot");
+            }
+        });
+    }
+
+    /// Downscale, encode and store `image`, dropping the oldest snapshots
+    /// past `keep`
+    pub async fn save_snapshot(
+        &self,
+        decision_id: Option<i64>,
+        kind: SnapshotKind,
+        image: RgbaImage,
+    ) -> Result<i64> {
+        let config = self
+            .snapshots
+            .clone()
+            .context("[storage.snapshots] is not configured")?;
+        let timestamp = Utc::now().timestamp();
+        let jpeg = tokio::task::spawn_blocking(move || encode(&image, config.max_width)).await??;
+        self.db
+            .add_snapshot(decision_id, kind.as_str(), timestamp, jpeg, config.keep)
+            .await
+    }
+
+    /// The newest `limit` snapshots of a kind, oldest first
+    pub async fn recent_snapshots(
+        &self,
+        kind: SnapshotKind,
+        limit: usize,
+    ) -> Result<Vec<Snapshot>> {
+        let stored = self.db.get_recent_snapshots(kind.as_str(), limit).await?;
+        stored.into_iter().map(decode).collect()
+    }
+
+    /// The snapshots taken when a decision was made
+    pub async fn decision_snapshots(&self, decision_id: i64) -> Result<Vec<Snapshot>> {
+        let stored = self.db.get_decision_snapshots(decision_id).await?;
+        stored.into_iter().map(decode).collect()
+    }
+}
+
+fn encode(image: &RgbaImage, max_width: u32) -> Result<Vec<u8>> {
+    let image = if image.width() > max_width {
+        let height = (image.height() as u64 * max_width as u64 / image.width() as u64) as u32;
+        resize(image, max_width, height.max(1), FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+    // JPEG has no alpha channel
+    let rgb = DynamicImage::ImageRgba8(image).to_rgb8();
+    let mut bytes = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY).encode_image(&rgb)?;
+    Ok(bytes.into_inner())
+}
+
+fn decode(stored: StoredSnapshot) -> Result<Snapshot> {
+    let image = image::load_from_memory(&stored.image)
+        .with_context(|| format!("Invalid snapshot {}", stored.id))?
+        .to_rgba8();
+    Ok(Snapshot {
+        id: stored.id,
+        decision_id: stored.decision_id,
+        kind: stored.kind,
+        timestamp: DateTime::from_timestamp(stored.timestamp, 0).unwrap_or_default(),
+        image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::config::{SnapshotConfig, StorageConfig};
+
+    #[tokio::test]
+    async fn test_snapshots_downscale_and_expire() {
+        let storage = Storage::connect(&StorageConfig {
+            url: ":memory:".into(),
+            snapshots: Some(SnapshotConfig {
+                max_width: 100,
+                keep: 2,
+                composites: false,
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        for (decision, shade) in [(1, 0), (2, 120), (3, 240)] {
+            let image = RgbaImage::from_pixel(400, 200, Rgba([shade, shade, shade, 255]));
+            storage
+                .save_snapshot(Some(decision), SnapshotKind::Screenshot, image)
+                .await
+                .unwrap();
+        }
+
+        let recent = storage
+            .recent_snapshots(SnapshotKind::Screenshot, 10)
+            .await
+            .unwrap();
+        let decisions: Vec<_> = recent.iter().map(|s| s.decision_id).collect();
+        assert_eq!(decisions, [Some(2), Some(3)]);
+        assert_eq!(recent[1].image.dimensions(), (100, 50));
+        assert!(recent[1].image.get_pixel(50, 25)[0].abs_diff(240) < 8);
+
+        let for_decision = storage.decision_snapshots(3).await.unwrap();
+        assert_eq!(for_decision.len(), 1);
+        assert!(storage.decision_snapshots(1).await.unwrap().is_empty());
+    }
+}
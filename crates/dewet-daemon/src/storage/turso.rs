@@ -1,23 +1,31 @@
 //! Turso (libSQL) database client
 
 use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use libsql::{Builder, Connection, params};
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 use tokio::sync::Mutex;
 use tracing::{debug, info};
 
-use super::{AriaosNotesState, CharacterState, ChatMessage, Episode, ScreenContext, SpatialContext};
+use super::{
+    CharacterDecisionStats, CharacterState, ChatMessage, ChatSession, DecisionOutcome,
+    DecisionStats, Episode, HourlyResponses, NotesRevision, PassReasonCount, Reminder,
+    ScreenContext, SpatialContext, StorageBackend, StoredDecision, StoredLlmCall, StoredSnapshot,
+    UserProfile,
+};
 
 /// Turso database client
 #[derive(Clone)]
 pub struct TursoDb {
     conn: Arc<Mutex<Connection>>,
+    remote: bool,
 }
 
 impl TursoDb {
     /// Connect to a Turso database
     pub async fn connect(url: &str, auth_token: Option<&str>) -> Result<Self> {
-        let db = if url.starts_with("libsql://") || url.starts_with("https://") {
+        let remote = url.starts_with("libsql://") || url.starts_with("https://");
+        let db = if remote {
             // Remote Turso database
             let token = auth_token
                 .map(|s| s.to_string())
@@ -29,8 +37,15 @@ impl TursoDb {
                 .await
                 .context("Failed to connect to remote Turso database")?
         } else {
-            // Local file database
+            // Local file database, created along with its directory
             let path = url.strip_prefix("file:").unwrap_or(url);
+            if let Some(dir) = Path::new(path)
+                .parent()
+                .filter(|d| !d.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create database directory {dir:?}"))?;
+            }
             Builder::new_local(path)
                 .build()
                 .await
@@ -40,11 +55,76 @@ impl TursoDb {
         let conn = db.connect().context("Failed to get database connection")?;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            remote,
         })
     }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for TursoDb {
+    /// Whether this is a remote Turso database rather than a local file
+    fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Write a consistent copy of a local database to a new file
+    async fn snapshot_to(&self, path: &Path) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "VACUUM INTO ?1",
+            params![path.to_string_lossy().into_owned()],
+        )
+        .await
+        .context("Failed to snapshot database")?;
+        Ok(())
+    }
+
+    async fn export_tables(&self, tables: &[&str]) -> Result<serde_json::Value> {
+        let conn = self.conn.lock().await;
+        let mut export = serde_json::Map::new();
+        for table in tables {
+            let mut rows = conn.query(&format!("SELECT * FROM {table}"), ()).await?;
+            let columns: Vec<String> = (0..rows.column_count())
+                .map(|i| rows.column_name(i).unwrap_or_default().to_string())
+                .collect();
+            let mut records = Vec::new();
+            while let Some(row) = rows.next().await? {
+                let mut record = serde_json::Map::new();
+                for (i, column) in columns.iter().enumerate() {
+                    record.insert(column.clone(), value_to_json(row.get_value(i as i32)?));
+                }
+                records.push(serde_json::Value::Object(record));
+            }
+            export.insert(table.to_string(), records.into());
+        }
+        Ok(export.into())
+    }
+
+    async fn import_rows(&self, table: &str, rows: &[serde_json::Value]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut written = 0;
+        for row in rows {
+            let record = row.as_object().context("Archive row is not an object")?;
+            let columns: Vec<&str> = record.keys().map(String::as_str).collect();
+            if let Some(bad) = columns.iter().find(|c| !is_identifier(c)) {
+                anyhow::bail!("Invalid column name {bad:?} in {table}");
+            }
+            let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+            let values = columns
+                .iter()
+                .map(|c| value_from_json(c, &record[*c]))
+                .collect::<Result<Vec<_>>>()?;
+            let sql = format!(
+                "INSERT OR REPLACE INTO {table} ({}) VALUES ({})",
+                columns.join(", "),
+                placeholders.join(", ")
+            );
+            written += conn.execute(&sql, values).await?;
+        }
+        Ok(written)
+    }
 
-    /// Initialize the database schema
-    pub async fn initialize_schema(&self) -> Result<()> {
+    async fn initialize_schema(&self) -> Result<()> {
         let conn = self.conn.lock().await;
 
         // Episodes table
@@ -59,7 +139,8 @@ impl TursoDb {
                 emotional_valence REAL DEFAULT 0.0,
                 importance REAL DEFAULT 0.5,
                 screen_context TEXT,
-                embedding BLOB
+                embedding BLOB,
+                character_id TEXT
             )
             "#,
             (),
@@ -119,7 +200,9 @@ impl TursoDb {
                 content TEXT NOT NULL,
                 in_response_to INTEGER REFERENCES chat_messages(id),
                 relevance_score REAL DEFAULT 1.0,
-                tier TEXT DEFAULT 'hot' CHECK(tier IN ('hot', 'warm', 'cold'))
+                tier TEXT DEFAULT 'hot' CHECK(tier IN ('hot', 'warm', 'cold')),
+                character_id TEXT,
+                session_id INTEGER REFERENCES sessions(id)
             )
             "#,
             (),
@@ -135,6 +218,15 @@ impl TursoDb {
             "ALTER TABLE chat_messages ADD COLUMN tier TEXT DEFAULT 'hot'",
             (),
         ).await;
+        // Companion each chat message and episode belongs to (NULL: shared)
+        for table in ["chat_messages", "episodes"] {
+            let _ = conn
+                .execute(
+                    &format!("ALTER TABLE {table} ADD COLUMN character_id TEXT"),
+                    (),
+                )
+                .await;
+        }
 
         // Arbiter decisions table
         conn.execute(
@@ -152,11 +244,19 @@ impl TursoDb {
             (),
         )
         .await?;
+        for column in ["outcome TEXT", "mood TEXT"] {
+            let _ = conn
+                .execute(
+                    &format!("ALTER TABLE arbiter_decisions ADD COLUMN {column}"),
+                    (),
+                )
+                .await;
+        }
 
-        // ARIAOS state table (key-value for app states)
+        // ARIAOS app states (app id -> JSON)
         conn.execute(
             r#"
-            CREATE TABLE IF NOT EXISTS ariaos_state (
+            CREATE TABLE IF NOT EXISTS ariaos_app_state (
                 app_id TEXT PRIMARY KEY,
                 state_json TEXT NOT NULL,
                 updated_at INTEGER NOT NULL
@@ -165,6 +265,125 @@ impl TursoDb {
             (),
         )
         .await?;
+        // Carry over the notes-only table this replaced
+        let moved = conn
+            .execute(
+                r#"
+                INSERT OR IGNORE INTO ariaos_app_state
+                SELECT app_id, state_json, updated_at FROM ariaos_state
+                "#,
+                (),
+            )
+            .await;
+        if moved.is_ok() {
+            conn.execute("DROP TABLE ariaos_state", ()).await?;
+        }
+
+        // Conversation sessions, split by inactivity
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at INTEGER NOT NULL,
+                last_activity INTEGER NOT NULL,
+                ended_at INTEGER,
+                message_count INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+            (),
+        )
+        .await?;
+        let _ = conn
+            .execute(
+                "ALTER TABLE chat_messages ADD COLUMN session_id INTEGER",
+                (),
+            )
+            .await;
+
+        // Every change to the ARIAOS notes, so edits can be reverted
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS notes_revisions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                owner TEXT,
+                timestamp INTEGER NOT NULL,
+                command TEXT NOT NULL,
+                diff TEXT NOT NULL,
+                previous TEXT NOT NULL,
+                content TEXT NOT NULL
+            )
+            "#,
+            (),
+        )
+        .await?;
+
+        // What has been learned about the user (a single row)
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_profile (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                name TEXT,
+                timezone TEXT,
+                preferences TEXT NOT NULL DEFAULT '[]',
+                habits TEXT NOT NULL DEFAULT '[]',
+                likes TEXT NOT NULL DEFAULT '[]',
+                dislikes TEXT NOT NULL DEFAULT '[]',
+                learned_until INTEGER NOT NULL DEFAULT 0,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+            (),
+        )
+        .await?;
+
+        // Reminders companions set for the user
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS reminders (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                due_at INTEGER NOT NULL,
+                character_id TEXT,
+                text TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+            (),
+        )
+        .await?;
+
+        // Screenshots companions spoke about, keyed by the arbiter decision
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                decision_id INTEGER,
+                kind TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                image BLOB NOT NULL
+            )
+            "#,
+            (),
+        )
+        .await?;
+
+        // LLM exchanges (durable audit trail of prompts and responses)
+        conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS llm_calls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                model_type TEXT NOT NULL,
+                model_name TEXT NOT NULL,
+                prompt TEXT NOT NULL,
+                response TEXT NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER
+            )
+            "#,
+            (),
+        )
+        .await?;
 
         // Create indices
         conn.execute(
@@ -179,12 +398,17 @@ impl TursoDb {
         )
         .await?;
 
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_llm_calls_timestamp ON llm_calls(timestamp DESC)",
+            (),
+        )
+        .await?;
+
         info!("Database schema initialized");
         Ok(())
     }
 
-    /// Add an episode to memory
-    pub async fn add_episode(&self, episode: &Episode) -> Result<()> {
+    async fn add_episode(&self, episode: &Episode) -> Result<()> {
         let conn = self.conn.lock().await;
 
         let screen_context_json = episode
@@ -195,8 +419,8 @@ impl TursoDb {
 
         conn.execute(
             r#"
-            INSERT INTO episodes (id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            INSERT INTO episodes (id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context, embedding, character_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
             "#,
             params![
                 episode.id.clone(),
@@ -207,6 +431,8 @@ impl TursoDb {
                 episode.emotional_valence,
                 episode.importance,
                 screen_context_json,
+                episode.embedding.as_deref().map(embedding_to_blob),
+                episode.character_id.clone(),
             ],
         )
         .await?;
@@ -215,14 +441,13 @@ impl TursoDb {
         Ok(())
     }
 
-    /// Get recent episodes
-    pub async fn get_recent_episodes(&self, limit: usize) -> Result<Vec<Episode>> {
+    async fn get_recent_episodes(&self, limit: usize) -> Result<Vec<Episode>> {
         let conn = self.conn.lock().await;
 
         let mut rows = conn
             .query(
                 r#"
-                SELECT id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context
+                SELECT id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context, embedding, character_id
                 FROM episodes
                 ORDER BY timestamp DESC
                 LIMIT ?1
@@ -233,45 +458,101 @@ impl TursoDb {
 
         let mut episodes = Vec::new();
         while let Some(row) = rows.next().await? {
-            let id: String = row.get(0)?;
-            let timestamp: i64 = row.get(1)?;
-            let event_type: String = row.get(2)?;
-            let actor: Option<String> = row.get(3)?;
-            let content: String = row.get(4)?;
-            let emotional_valence: f64 = row.get(5)?;
-            let importance: f64 = row.get(6)?;
-            let screen_context_str: Option<String> = row.get(7)?;
-
-            let screen_context: Option<ScreenContext> =
-                screen_context_str.and_then(|s| serde_json::from_str(&s).ok());
-
-            episodes.push(Episode {
-                id,
-                timestamp,
-                event_type,
-                actor,
-                content,
-                emotional_valence: emotional_valence as f32,
-                importance: importance as f32,
-                screen_context,
-                embedding: None,
-            });
+            episodes.push(episode_from_row(&row)?);
         }
 
         Ok(episodes)
     }
 
-    /// Add a chat message
-    pub async fn add_chat_message(&self, sender: &str, content: &str) -> Result<i64> {
+    async fn set_episode_embedding(&self, id: &str, embedding: &[f32]) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE episodes SET embedding = ?1 WHERE id = ?2",
+            params![embedding_to_blob(embedding), id.to_string()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Similarity is computed in-process over every embedded episode, which
+    /// is fine for a single user's history
+    async fn search_similar(
+        &self,
+        query: &[f32],
+        k: usize,
+        character_id: Option<&str>,
+    ) -> Result<Vec<(Episode, f32)>> {
+        let conn = self.conn.lock().await;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context, embedding, character_id
+                FROM episodes
+                WHERE embedding IS NOT NULL
+                  AND (?1 IS NULL OR character_id IS NULL OR character_id = ?1)
+                "#,
+                params![character_id.map(str::to_string)],
+            )
+            .await?;
+
+        let mut scored = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let episode = episode_from_row(&row)?;
+            let score = episode
+                .embedding
+                .as_deref()
+                .and_then(|embedding| cosine_similarity(query, embedding));
+            if let Some(score) = score {
+                scored.push((episode, score));
+            }
+        }
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    async fn add_chat_message(
+        &self,
+        sender: &str,
+        content: &str,
+        character_id: Option<&str>,
+    ) -> Result<i64> {
+        self.insert_chat_message(&ChatMessage {
+            id: 0,
+            timestamp: chrono::Utc::now().timestamp(),
+            sender: sender.to_string(),
+            content: content.to_string(),
+            in_response_to: None,
+            character_id: character_id.map(str::to_string),
+            relevance: 1.0,
+            tier: "hot".into(),
+            session_id: None,
+        })
+        .await
+    }
+
+    async fn insert_chat_message(&self, message: &ChatMessage) -> Result<i64> {
         let conn = self.conn.lock().await;
-        let timestamp = chrono::Utc::now().timestamp();
 
         conn.execute(
             r#"
-            INSERT INTO chat_messages (timestamp, sender, content)
-            VALUES (?1, ?2, ?3)
+            INSERT INTO chat_messages
+                (timestamp, sender, content, in_response_to, character_id,
+                 relevance_score, tier, session_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
             "#,
-            params![timestamp, sender.to_string(), content.to_string()],
+            params![
+                message.timestamp,
+                message.sender.clone(),
+                message.content.clone(),
+                message.in_response_to,
+                message.character_id.clone(),
+                message.relevance as f64,
+                message.tier.clone(),
+                message.session_id
+            ],
         )
         .await?;
 
@@ -284,18 +565,58 @@ impl TursoDb {
             0
         };
 
-        debug!("Added chat message from {}: {}", sender, content);
+        debug!(
+            "Added chat message from {}: {}",
+            message.sender, message.content
+        );
         Ok(id)
     }
 
-    /// Get recent chat messages
-    pub async fn get_recent_chat(&self, limit: usize) -> Result<Vec<ChatMessage>> {
+    async fn update_chat_relevance(
+        &self,
+        timestamp: i64,
+        sender: &str,
+        relevance: f32,
+        tier: &str,
+    ) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            UPDATE chat_messages SET relevance_score = ?3, tier = ?4
+            WHERE timestamp = ?1 AND sender = ?2
+            "#,
+            params![
+                timestamp,
+                sender.to_string(),
+                relevance as f64,
+                tier.to_string()
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_chat_content(&self, timestamp: i64, sender: &str, content: &str) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            UPDATE chat_messages SET content = ?3
+            WHERE timestamp = ?1 AND sender = ?2
+            "#,
+            params![timestamp, sender.to_string(), content.to_string()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_recent_chat(&self, limit: usize) -> Result<Vec<ChatMessage>> {
         let conn = self.conn.lock().await;
 
         let mut rows = conn
             .query(
                 r#"
-                SELECT id, timestamp, sender, content, in_response_to
+                SELECT id, timestamp, sender, content, in_response_to, character_id,
+                       COALESCE(relevance_score, 1.0), COALESCE(tier, 'hot'), session_id
                 FROM chat_messages
                 ORDER BY timestamp DESC
                 LIMIT ?1
@@ -306,19 +627,7 @@ impl TursoDb {
 
         let mut messages = Vec::new();
         while let Some(row) = rows.next().await? {
-            let id: i64 = row.get(0)?;
-            let timestamp: i64 = row.get(1)?;
-            let sender: String = row.get(2)?;
-            let content: String = row.get(3)?;
-            let in_response_to: Option<i64> = row.get(4)?;
-
-            messages.push(ChatMessage {
-                id,
-                timestamp,
-                sender,
-                content,
-                in_response_to,
-            });
+            messages.push(chat_from_row(&row)?);
         }
 
         // Reverse to get chronological order
@@ -326,87 +635,140 @@ impl TursoDb {
         Ok(messages)
     }
 
-    /// Log an arbiter decision
-    pub async fn log_arbiter_decision(
-        &self,
-        should_respond: bool,
-        responder_id: Option<&str>,
-        reasoning: &str,
-        urgency: f32,
-        context_summary: &str,
-    ) -> Result<()> {
+    async fn touch_session(&self, timestamp: i64, idle_secs: i64) -> Result<i64> {
         let conn = self.conn.lock().await;
-        let timestamp = chrono::Utc::now().timestamp();
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, last_activity FROM sessions
+                WHERE ended_at IS NULL
+                ORDER BY id DESC
+                LIMIT 1
+                "#,
+                (),
+            )
+            .await?;
+        let open: Option<(i64, i64)> = match rows.next().await? {
+            Some(row) => Some((row.get(0)?, row.get(1)?)),
+            None => None,
+        };
+        drop(rows);
+
+        if let Some((id, last_activity)) = open {
+            if timestamp - last_activity <= idle_secs {
+                conn.execute(
+                    r#"
+                    UPDATE sessions
+                    SET last_activity = MAX(last_activity, ?2), message_count = message_count + 1
+                    WHERE id = ?1
+                    "#,
+                    params![id, timestamp],
+                )
+                .await?;
+                return Ok(id);
+            }
+            conn.execute(
+                "UPDATE sessions SET ended_at = last_activity WHERE id = ?1",
+                params![id],
+            )
+            .await?;
+        }
 
         conn.execute(
             r#"
-            INSERT INTO arbiter_decisions (timestamp, should_respond, responder_id, reasoning, urgency, context_summary)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            INSERT INTO sessions (started_at, last_activity, message_count)
+            VALUES (?1, ?1, 1)
             "#,
-            params![
-                timestamp,
-                should_respond as i32,
-                responder_id.map(|s| s.to_string()),
-                reasoning.to_string(),
-                urgency as f64,
-                context_summary.to_string(),
-            ],
+            params![timestamp],
         )
         .await?;
+        Ok(conn.last_insert_rowid())
+    }
 
-        Ok(())
+    async fn close_sessions_idle_since(&self, cutoff: i64) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let closed = conn
+            .execute(
+                r#"
+                UPDATE sessions SET ended_at = last_activity
+                WHERE ended_at IS NULL AND last_activity < ?1
+                "#,
+                params![cutoff],
+            )
+            .await?;
+        Ok(closed)
     }
 
-    /// Get character state
-    pub async fn get_character_state(&self, character_id: &str) -> Result<Option<CharacterState>> {
+    async fn get_sessions(&self, limit: usize) -> Result<Vec<ChatSession>> {
         let conn = self.conn.lock().await;
 
         let mut rows = conn
             .query(
                 r#"
-                SELECT character_id, current_mood, last_spoke_at, relationship_score
-                FROM character_states
-                WHERE character_id = ?1
+                SELECT id, started_at, last_activity, ended_at, message_count
+                FROM sessions
+                ORDER BY started_at DESC
+                LIMIT ?1
                 "#,
-                params![character_id.to_string()],
+                params![limit as i64],
             )
             .await?;
 
-        if let Some(row) = rows.next().await? {
-            let character_id: String = row.get(0)?;
-            let current_mood: String = row.get(1)?;
-            let last_spoke_at: Option<i64> = row.get(2)?;
-            let relationship_score: f64 = row.get(3)?;
+        let mut sessions = Vec::new();
+        while let Some(row) = rows.next().await? {
+            sessions.push(ChatSession {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                last_activity: row.get(2)?,
+                ended_at: row.get(3)?,
+                message_count: row.get(4)?,
+            });
+        }
+        Ok(sessions)
+    }
 
-            Ok(Some(CharacterState {
-                character_id,
-                current_mood,
-                last_spoke_at,
-                relationship_score: relationship_score as f32,
-            }))
-        } else {
-            Ok(None)
+    async fn get_session_chat(&self, session_id: i64) -> Result<Vec<ChatMessage>> {
+        let conn = self.conn.lock().await;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, timestamp, sender, content, in_response_to, character_id,
+                       COALESCE(relevance_score, 1.0), COALESCE(tier, 'hot'), session_id
+                FROM chat_messages
+                WHERE session_id = ?1
+                ORDER BY timestamp, id
+                "#,
+                params![session_id],
+            )
+            .await?;
+
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next().await? {
+            messages.push(chat_from_row(&row)?);
         }
+        Ok(messages)
     }
 
-    /// Update character state
-    pub async fn update_character_state(&self, state: &CharacterState) -> Result<()> {
+    async fn log_arbiter_decision(&self, id: i64, decision: &StoredDecision) -> Result<()> {
         let conn = self.conn.lock().await;
 
         conn.execute(
             r#"
-            INSERT INTO character_states (character_id, current_mood, last_spoke_at, relationship_score)
-            VALUES (?1, ?2, ?3, ?4)
-            ON CONFLICT(character_id) DO UPDATE SET
-                current_mood = excluded.current_mood,
-                last_spoke_at = excluded.last_spoke_at,
-                relationship_score = excluded.relationship_score
+            INSERT INTO arbiter_decisions (id, timestamp, should_respond, responder_id, reasoning, urgency, context_summary, outcome, mood)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
             "#,
             params![
-                state.character_id.clone(),
-                state.current_mood.clone(),
-                state.last_spoke_at,
-                state.relationship_score as f64,
+                id,
+                decision.timestamp,
+                decision.should_respond as i32,
+                decision.responder_id.clone(),
+                decision.reasoning.clone(),
+                decision.urgency as f64,
+                decision.context_summary.clone(),
+                decision.outcome.as_str(),
+                decision.mood.clone(),
             ],
         )
         .await?;
@@ -414,41 +776,357 @@ impl TursoDb {
         Ok(())
     }
 
-    /// Decay importance of old memories
-    pub async fn decay_importance(&self, decay_factor: f32, min_age_hours: i64) -> Result<u64> {
+    async fn last_decision_id(&self) -> Result<i64> {
         let conn = self.conn.lock().await;
-        let cutoff = chrono::Utc::now().timestamp() - (min_age_hours * 3600);
-
-        let result = conn
-            .execute(
+        let mut rows = conn
+            .query(
                 r#"
-                UPDATE episodes 
-                SET importance = importance * ?1 
-                WHERE timestamp < ?2 AND importance > 0.01
+                SELECT MAX(COALESCE((SELECT MAX(id) FROM arbiter_decisions), 0),
+                           COALESCE((SELECT MAX(decision_id) FROM snapshots), 0))
                 "#,
-                params![decay_factor as f64, cutoff],
+                (),
             )
             .await?;
-
-        Ok(result)
+        match rows.next().await? {
+            Some(row) => Ok(row.get(0)?),
+            None => Ok(0),
+        }
     }
 
-    /// Prune forgotten memories
-    pub async fn prune_forgotten(&self, threshold: f32) -> Result<u64> {
+    async fn decision_stats(&self, since: i64) -> Result<DecisionStats> {
         let conn = self.conn.lock().await;
+        // Decisions logged before outcomes were kept count as spoke/declined
+        let outcome = format!(
+            "COALESCE(outcome, CASE WHEN should_respond THEN '{}' ELSE '{}' END)",
+            DecisionOutcome::Spoke.as_str(),
+            DecisionOutcome::Declined.as_str(),
+        );
+        let spoke = format!("{outcome} = '{}'", DecisionOutcome::Spoke.as_str());
+        let mut stats = DecisionStats {
+            since,
+            ..Default::default()
+        };
 
-        let result = conn
-            .execute(
-                "DELETE FROM episodes WHERE importance < ?1",
-                params![threshold as f64],
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT {outcome} AS o, COUNT(*) FROM arbiter_decisions
+                     WHERE timestamp >= ?1 GROUP BY o ORDER BY COUNT(*) DESC, o"
+                ),
+                params![since],
             )
             .await?;
+        while let Some(row) = rows.next().await? {
+            let reason: String = row.get(0)?;
+            let count = row.get::<i64>(1)? as u64;
+            stats.decisions += count;
+            if reason == DecisionOutcome::Spoke.as_str() {
+                stats.responses = count;
+            } else {
+                stats.pass_reasons.push(PassReasonCount { reason, count });
+            }
+        }
 
-        Ok(result)
-    }
-
-    /// Get or create spatial context
-    pub async fn get_or_create_spatial_context(
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT responder_id, COUNT(*), SUM({spoke}) FROM arbiter_decisions
+                     WHERE timestamp >= ?1 AND responder_id IS NOT NULL
+                     GROUP BY responder_id ORDER BY responder_id"
+                ),
+                params![since],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let chosen = row.get::<i64>(1)? as u64;
+            let spoke = row.get::<i64>(2)? as u64;
+            stats.characters.push(CharacterDecisionStats {
+                character_id: row.get(0)?,
+                chosen,
+                spoke,
+                approval_rate: spoke as f32 / chosen as f32,
+            });
+        }
+
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT timestamp / 3600 * 3600 AS hour, COUNT(*) FROM arbiter_decisions
+                     WHERE timestamp >= ?1 AND {spoke} GROUP BY hour ORDER BY hour"
+                ),
+                params![since],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            stats.responses_per_hour.push(HourlyResponses {
+                hour: row.get(0)?,
+                responses: row.get::<i64>(1)? as u64,
+            });
+        }
+
+        Ok(stats)
+    }
+
+    async fn log_llm_call(&self, call: &StoredLlmCall) -> Result<()> {
+        let conn = self.conn.lock().await;
+
+        conn.execute(
+            r#"
+            INSERT INTO llm_calls (timestamp, model_type, model_name, prompt, response, latency_ms, prompt_tokens, completion_tokens)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                call.timestamp,
+                call.model_type.clone(),
+                call.model_name.clone(),
+                call.prompt.clone(),
+                call.response.clone(),
+                call.latency_ms as i64,
+                call.prompt_tokens.map(i64::from),
+                call.completion_tokens.map(i64::from),
+            ],
+        )
+        .await?;
+
+        debug!("Logged {} call to {}", call.model_type, call.model_name);
+        Ok(())
+    }
+
+    async fn get_recent_llm_calls(&self, limit: usize) -> Result<Vec<StoredLlmCall>> {
+        let conn = self.conn.lock().await;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT timestamp, model_type, model_name, prompt, response, latency_ms, prompt_tokens, completion_tokens
+                FROM llm_calls
+                ORDER BY id DESC
+                LIMIT ?1
+                "#,
+                params![limit as i64],
+            )
+            .await?;
+
+        let mut calls = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let latency_ms: i64 = row.get(5)?;
+            let prompt_tokens: Option<i64> = row.get(6)?;
+            let completion_tokens: Option<i64> = row.get(7)?;
+
+            calls.push(StoredLlmCall {
+                timestamp: row.get(0)?,
+                model_type: row.get(1)?,
+                model_name: row.get(2)?,
+                prompt: row.get(3)?,
+                response: row.get(4)?,
+                latency_ms: latency_ms as u64,
+                prompt_tokens: prompt_tokens.map(|t| t as u32),
+                completion_tokens: completion_tokens.map(|t| t as u32),
+            });
+        }
+
+        Ok(calls)
+    }
+
+    async fn get_character_state(&self, character_id: &str) -> Result<Option<CharacterState>> {
+        let conn = self.conn.lock().await;
+
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT character_id, current_mood, last_spoke_at, relationship_score
+                FROM character_states
+                WHERE character_id = ?1
+                "#,
+                params![character_id.to_string()],
+            )
+            .await?;
+
+        if let Some(row) = rows.next().await? {
+            let character_id: String = row.get(0)?;
+            let current_mood: String = row.get(1)?;
+            let last_spoke_at: Option<i64> = row.get(2)?;
+            let relationship_score: f64 = row.get(3)?;
+
+            Ok(Some(CharacterState {
+                character_id,
+                current_mood,
+                last_spoke_at,
+                relationship_score: relationship_score as f32,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn update_character_state(&self, state: &CharacterState) -> Result<()> {
+        let conn = self.conn.lock().await;
+
+        conn.execute(
+            r#"
+            INSERT INTO character_states (character_id, current_mood, last_spoke_at, relationship_score)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(character_id) DO UPDATE SET
+                current_mood = excluded.current_mood,
+                last_spoke_at = excluded.last_spoke_at,
+                relationship_score = excluded.relationship_score
+            "#,
+            params![
+                state.character_id.clone(),
+                state.current_mood.clone(),
+                state.last_spoke_at,
+                state.relationship_score as f64,
+            ],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn decay_importance(&self, decay_factor: f32, min_age_hours: i64) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let cutoff = chrono::Utc::now().timestamp() - (min_age_hours * 3600);
+
+        let result = conn
+            .execute(
+                r#"
+                UPDATE episodes 
+                SET importance = importance * ?1 
+                WHERE timestamp < ?2 AND importance > 0.01
+                "#,
+                params![decay_factor as f64, cutoff],
+            )
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn prune_forgotten(&self, threshold: f32) -> Result<u64> {
+        let conn = self.conn.lock().await;
+
+        let result = conn
+            .execute(
+                "DELETE FROM episodes WHERE importance < ?1",
+                params![threshold as f64],
+            )
+            .await?;
+        conn.execute(
+            "DELETE FROM memory_spatial_links WHERE episode_id NOT IN (SELECT id FROM episodes)",
+            (),
+        )
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_chat_before(&self, cutoff: i64) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        // Replies to deleted messages lose the reference rather than blocking the delete
+        conn.execute(
+            r#"
+            UPDATE chat_messages SET in_response_to = NULL
+            WHERE in_response_to IN (SELECT id FROM chat_messages WHERE timestamp < ?1)
+            "#,
+            params![cutoff],
+        )
+        .await?;
+        let result = conn
+            .execute(
+                "DELETE FROM chat_messages WHERE timestamp < ?1",
+                params![cutoff],
+            )
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_decisions_before(&self, cutoff: i64) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let result = conn
+            .execute(
+                "DELETE FROM arbiter_decisions WHERE timestamp < ?1",
+                params![cutoff],
+            )
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn add_snapshot(
+        &self,
+        decision_id: Option<i64>,
+        kind: &str,
+        timestamp: i64,
+        image: Vec<u8>,
+        keep: usize,
+    ) -> Result<i64> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            INSERT INTO snapshots (decision_id, kind, timestamp, image)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![decision_id, kind.to_string(), timestamp, image],
+        )
+        .await?;
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            r#"
+            DELETE FROM snapshots WHERE kind = ?1 AND id NOT IN (
+                SELECT id FROM snapshots WHERE kind = ?1 ORDER BY id DESC LIMIT ?2
+            )
+            "#,
+            params![kind.to_string(), keep as i64],
+        )
+        .await?;
+        Ok(id)
+    }
+
+    async fn get_recent_snapshots(&self, kind: &str, limit: usize) -> Result<Vec<StoredSnapshot>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, decision_id, kind, timestamp, image
+                FROM snapshots
+                WHERE kind = ?1
+                ORDER BY id DESC
+                LIMIT ?2
+                "#,
+                params![kind.to_string(), limit as i64],
+            )
+            .await?;
+
+        let mut snapshots = Vec::new();
+        while let Some(row) = rows.next().await? {
+            snapshots.push(snapshot_from_row(&row)?);
+        }
+        snapshots.reverse();
+        Ok(snapshots)
+    }
+
+    async fn get_decision_snapshots(&self, decision_id: i64) -> Result<Vec<StoredSnapshot>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, decision_id, kind, timestamp, image
+                FROM snapshots
+                WHERE decision_id = ?1
+                ORDER BY id
+                "#,
+                params![decision_id],
+            )
+            .await?;
+
+        let mut snapshots = Vec::new();
+        while let Some(row) = rows.next().await? {
+            snapshots.push(snapshot_from_row(&row)?);
+        }
+        Ok(snapshots)
+    }
+
+    async fn get_or_create_spatial_context(
         &self,
         context_type: &str,
         context_value: &str,
@@ -521,46 +1199,551 @@ impl TursoDb {
         }
     }
     
-    /// Save ARIAOS Notes app state
-    pub async fn save_ariaos_notes(&self, state: &AriaosNotesState) -> Result<()> {
+    async fn save_app_state(&self, app_id: &str, state_json: &str) -> Result<()> {
         let conn = self.conn.lock().await;
         let now = chrono::Utc::now().timestamp();
-        let state_json = serde_json::to_string(state)?;
-        
         conn.execute(
             r#"
-            INSERT INTO ariaos_state (app_id, state_json, updated_at)
-            VALUES ('notes', ?1, ?2)
+            INSERT INTO ariaos_app_state (app_id, state_json, updated_at)
+            VALUES (?1, ?2, ?3)
             ON CONFLICT(app_id) DO UPDATE SET
                 state_json = excluded.state_json,
                 updated_at = excluded.updated_at
             "#,
-            params![state_json, now],
+            params![app_id, state_json, now],
         )
         .await?;
-        
-        debug!("Saved ARIAOS notes state ({} chars)", state.content.len());
+        debug!(app_id, bytes = state_json.len(), "Saved ARIAOS app state");
         Ok(())
     }
-    
-    /// Load ARIAOS Notes app state
-    pub async fn load_ariaos_notes(&self) -> Result<Option<AriaosNotesState>> {
+
+    async fn load_app_state(&self, app_id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                "SELECT state_json FROM ariaos_app_state WHERE app_id = ?1",
+                params![app_id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn app_state_ids(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query("SELECT app_id FROM ariaos_app_state ORDER BY app_id", ())
+            .await?;
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next().await? {
+            ids.push(row.get(0)?);
+        }
+        Ok(ids)
+    }
+
+    async fn save_user_profile(&self, profile: &UserProfile) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            INSERT OR REPLACE INTO user_profile
+                (id, name, timezone, preferences, habits, likes, dislikes, learned_until, updated_at)
+            VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                profile.name.clone(),
+                profile.timezone.clone(),
+                serde_json::to_string(&profile.preferences)?,
+                serde_json::to_string(&profile.habits)?,
+                serde_json::to_string(&profile.likes)?,
+                serde_json::to_string(&profile.dislikes)?,
+                profile.learned_until,
+                chrono::Utc::now().timestamp(),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn load_user_profile(&self) -> Result<Option<UserProfile>> {
         let conn = self.conn.lock().await;
-        
         let mut rows = conn
             .query(
-                "SELECT state_json FROM ariaos_state WHERE app_id = 'notes'",
+                r#"
+                SELECT name, timezone, preferences, habits, likes, dislikes, learned_until
+                FROM user_profile WHERE id = 1
+                "#,
                 (),
             )
             .await?;
-        
-        if let Some(row) = rows.next().await? {
-            let state_json: String = row.get(0)?;
-            let state: AriaosNotesState = serde_json::from_str(&state_json)?;
-            debug!("Loaded ARIAOS notes state ({} chars)", state.content.len());
-            Ok(Some(state))
-        } else {
-            Ok(None)
+        let Some(row) = rows.next().await? else {
+            return Ok(None);
+        };
+        let list = |index: i32| -> Result<Vec<String>> {
+            Ok(serde_json::from_str(&row.get::<String>(index)?)?)
+        };
+        Ok(Some(UserProfile {
+            name: row.get(0)?,
+            timezone: row.get(1)?,
+            preferences: list(2)?,
+            habits: list(3)?,
+            likes: list(4)?,
+            dislikes: list(5)?,
+            learned_until: row.get(6)?,
+        }))
+    }
+
+    async fn add_notes_revision(&self, revision: &NotesRevision, keep: usize) -> Result<i64> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            INSERT INTO notes_revisions (owner, timestamp, command, diff, previous, content)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                revision.owner.clone(),
+                revision.timestamp,
+                revision.command.clone(),
+                revision.diff.clone(),
+                revision.previous.clone(),
+                revision.content.clone()
+            ],
+        )
+        .await?;
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            r#"
+            DELETE FROM notes_revisions
+            WHERE owner IS ?1 AND id NOT IN (
+                SELECT id FROM notes_revisions WHERE owner IS ?1 ORDER BY id DESC LIMIT ?2
+            )
+            "#,
+            params![revision.owner.clone(), keep as i64],
+        )
+        .await?;
+        Ok(id)
+    }
+
+    async fn get_notes_revisions(
+        &self,
+        owner: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<NotesRevision>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, owner, timestamp, command, diff, previous, content
+                FROM notes_revisions
+                WHERE owner IS ?1
+                ORDER BY id DESC
+                LIMIT ?2
+                "#,
+                params![owner.map(str::to_string), limit as i64],
+            )
+            .await?;
+
+        let mut revisions = Vec::new();
+        while let Some(row) = rows.next().await? {
+            revisions.push(revision_from_row(&row)?);
         }
+        Ok(revisions)
+    }
+
+    async fn get_notes_revision(&self, id: i64) -> Result<Option<NotesRevision>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, owner, timestamp, command, diff, previous, content
+                FROM notes_revisions
+                WHERE id = ?1
+                "#,
+                params![id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(revision_from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_chat_between(&self, since: i64, until: i64) -> Result<Vec<ChatMessage>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, timestamp, sender, content, in_response_to, character_id,
+                       COALESCE(relevance_score, 1.0), COALESCE(tier, 'hot'), session_id
+                FROM chat_messages
+                WHERE timestamp >= ?1 AND timestamp < ?2
+                ORDER BY timestamp
+                "#,
+                params![since, until],
+            )
+            .await?;
+
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next().await? {
+            messages.push(chat_from_row(&row)?);
+        }
+        Ok(messages)
+    }
+
+    async fn get_episodes_between(&self, since: i64, until: i64) -> Result<Vec<Episode>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, timestamp, event_type, actor, content, emotional_valence, importance, screen_context, embedding, character_id
+                FROM episodes
+                WHERE timestamp >= ?1 AND timestamp < ?2
+                ORDER BY timestamp
+                "#,
+                params![since, until],
+            )
+            .await?;
+
+        let mut episodes = Vec::new();
+        while let Some(row) = rows.next().await? {
+            episodes.push(episode_from_row(&row)?);
+        }
+        Ok(episodes)
+    }
+
+    async fn get_notes_revisions_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<NotesRevision>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, owner, timestamp, command, diff, previous, content
+                FROM notes_revisions
+                WHERE timestamp >= ?1 AND timestamp < ?2
+                ORDER BY id
+                "#,
+                params![since, until],
+            )
+            .await?;
+
+        let mut revisions = Vec::new();
+        while let Some(row) = rows.next().await? {
+            revisions.push(revision_from_row(&row)?);
+        }
+        Ok(revisions)
+    }
+
+    async fn get_llm_calls_between(
+        &self,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<(i64, StoredLlmCall)>> {
+        let conn = self.conn.lock().await;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT id, timestamp, model_type, model_name, prompt, response, latency_ms, prompt_tokens, completion_tokens
+                FROM llm_calls
+                WHERE timestamp >= ?1 AND timestamp < ?2
+                ORDER BY id
+                "#,
+                params![since, until],
+            )
+            .await?;
+
+        let mut calls = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let latency_ms: i64 = row.get(6)?;
+            let prompt_tokens: Option<i64> = row.get(7)?;
+            let completion_tokens: Option<i64> = row.get(8)?;
+
+            calls.push((
+                row.get(0)?,
+                StoredLlmCall {
+                    timestamp: row.get(1)?,
+                    model_type: row.get(2)?,
+                    model_name: row.get(3)?,
+                    prompt: row.get(4)?,
+                    response: row.get(5)?,
+                    latency_ms: latency_ms as u64,
+                    prompt_tokens: prompt_tokens.map(|t| t as u32),
+                    completion_tokens: completion_tokens.map(|t| t as u32),
+                },
+            ));
+        }
+        Ok(calls)
+    }
+
+    async fn delete_chat_messages(&self, ids: &[i64]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut deleted = 0;
+        for &id in ids {
+            conn.execute(
+                "UPDATE chat_messages SET in_response_to = NULL WHERE in_response_to = ?1",
+                params![id],
+            )
+            .await?;
+            deleted += conn
+                .execute("DELETE FROM chat_messages WHERE id = ?1", params![id])
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_episodes(&self, ids: &[String]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += conn
+                .execute("DELETE FROM episodes WHERE id = ?1", params![id.clone()])
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_notes_revisions(&self, ids: &[i64]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut deleted = 0;
+        for &id in ids {
+            deleted += conn
+                .execute("DELETE FROM notes_revisions WHERE id = ?1", params![id])
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_llm_calls(&self, ids: &[i64]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut deleted = 0;
+        for &id in ids {
+            deleted += conn
+                .execute("DELETE FROM llm_calls WHERE id = ?1", params![id])
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_snapshots_between(&self, since: i64, until: i64) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let result = conn
+            .execute(
+                "DELETE FROM snapshots WHERE timestamp >= ?1 AND timestamp < ?2",
+                params![since, until],
+            )
+            .await?;
+        Ok(result)
+    }
+
+    async fn add_reminder(&self, reminder: &Reminder) -> Result<i64> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            r#"
+            INSERT INTO reminders (due_at, character_id, text, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![
+                reminder.due_at,
+                reminder.character_id.clone(),
+                reminder.text.clone(),
+                reminder.created_at
+            ],
+        )
+        .await?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    async fn due_reminders(&self, now: i64) -> Result<Vec<Reminder>> {
+        self.query_reminders("WHERE due_at <= ?1", now).await
+    }
+
+    async fn pending_reminders(&self, now: i64) -> Result<Vec<Reminder>> {
+        self.query_reminders("WHERE due_at > ?1", now).await
+    }
+
+    async fn delete_reminders(&self, ids: &[i64]) -> Result<u64> {
+        let conn = self.conn.lock().await;
+        let mut deleted = 0;
+        for &id in ids {
+            deleted += conn
+                .execute("DELETE FROM reminders WHERE id = ?1", params![id])
+                .await?;
+        }
+        Ok(deleted)
+    }
+}
+
+impl TursoDb {
+    /// Reminders matching `filter`, whose one parameter is a time
+    async fn query_reminders(&self, filter: &str, time: i64) -> Result<Vec<Reminder>> {
+        let conn = self.conn.lock().await;
+        let sql = format!(
+            "SELECT id, due_at, character_id, text, created_at FROM reminders {filter} ORDER BY due_at, id"
+        );
+        let mut rows = conn.query(&sql, params![time]).await?;
+        let mut reminders = Vec::new();
+        while let Some(row) = rows.next().await? {
+            reminders.push(Reminder {
+                id: row.get(0)?,
+                due_at: row.get(1)?,
+                character_id: row.get(2)?,
+                text: row.get(3)?,
+                created_at: row.get(4)?,
+            });
+        }
+        Ok(reminders)
+    }
+}
+
+/// A `chat_messages` row selected as id, timestamp, sender, content,
+/// in_response_to, character_id, relevance_score, tier, session_id
+fn chat_from_row(row: &libsql::Row) -> Result<ChatMessage> {
+    let relevance: f64 = row.get(6)?;
+    Ok(ChatMessage {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        sender: row.get(2)?,
+        content: row.get(3)?,
+        in_response_to: row.get(4)?,
+        character_id: row.get(5)?,
+        relevance: relevance as f32,
+        tier: row.get(7)?,
+        session_id: row.get(8)?,
+    })
+}
+
+fn snapshot_from_row(row: &libsql::Row) -> Result<StoredSnapshot> {
+    Ok(StoredSnapshot {
+        id: row.get(0)?,
+        decision_id: row.get(1)?,
+        kind: row.get(2)?,
+        timestamp: row.get(3)?,
+        image: row.get(4)?,
+    })
+}
+
+/// Companions' notes are kept as `notes:<character id>`
+/// A `notes_revisions` row selected as id, owner, timestamp, command, diff,
+/// previous, content
+fn revision_from_row(row: &libsql::Row) -> Result<NotesRevision> {
+    Ok(NotesRevision {
+        id: row.get(0)?,
+        owner: row.get(1)?,
+        timestamp: row.get(2)?,
+        command: row.get(3)?,
+        diff: row.get(4)?,
+        previous: row.get(5)?,
+        content: row.get(6)?,
+    })
+}
+
+/// An `episodes` row selected as id, timestamp, event_type, actor, content,
+/// emotional_valence, importance, screen_context, embedding, character_id
+fn episode_from_row(row: &libsql::Row) -> Result<Episode> {
+    let id: String = row.get(0)?;
+    let timestamp: i64 = row.get(1)?;
+    let event_type: String = row.get(2)?;
+    let actor: Option<String> = row.get(3)?;
+    let content: String = row.get(4)?;
+    let emotional_valence: f64 = row.get(5)?;
+    let importance: f64 = row.get(6)?;
+    let screen_context_str: Option<String> = row.get(7)?;
+    let embedding: Option<Vec<u8>> = row.get(8)?;
+    let character_id: Option<String> = row.get(9)?;
+
+    let screen_context: Option<ScreenContext> =
+        screen_context_str.and_then(|s| serde_json::from_str(&s).ok());
+
+    Ok(Episode {
+        id,
+        timestamp,
+        event_type,
+        actor,
+        content,
+        emotional_valence: emotional_valence as f32,
+        importance: importance as f32,
+        screen_context,
+        embedding: embedding.map(|blob| blob_to_embedding(&blob)),
+        character_id,
+    })
+}
+
+/// Embeddings are stored as little-endian f32s
+fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn blob_to_embedding(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Inverse of `value_to_json`; blobs are only expected in `embedding`
+fn value_from_json(column: &str, value: &serde_json::Value) -> Result<libsql::Value> {
+    Ok(match value {
+        serde_json::Value::Null => libsql::Value::Null,
+        serde_json::Value::Bool(b) => libsql::Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(n) => libsql::Value::Integer(n),
+            None => libsql::Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(text) if column == "embedding" => {
+            libsql::Value::Blob(BASE64.decode(text).context("Invalid embedding")?)
+        }
+        serde_json::Value::String(text) => libsql::Value::Text(text.clone()),
+        other => libsql::Value::Text(other.to_string()),
+    })
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Blobs (embeddings) are exported as base64
+fn value_to_json(value: libsql::Value) -> serde_json::Value {
+    match value {
+        libsql::Value::Null => serde_json::Value::Null,
+        libsql::Value::Integer(n) => n.into(),
+        libsql::Value::Real(x) => x.into(),
+        libsql::Value::Text(text) => text.into(),
+        libsql::Value::Blob(bytes) => BASE64.encode(bytes).into(),
+    }
+}
+
+/// None when the vectors differ in length (embedded by another model) or
+/// either is zero
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    (norms > 0.0).then(|| dot / norms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_blob_round_trip() {
+        let embedding = vec![0.25, -1.5, 3.0];
+        assert_eq!(blob_to_embedding(&embedding_to_blob(&embedding)), embedding);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), None);
     }
 }
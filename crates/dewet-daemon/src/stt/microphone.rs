@@ -0,0 +1,199 @@
+//! Microphone capture (feature `microphone`)
+//!
+//! The input stream lives on its own thread, since it is not `Send`. Audio is
+//! mixed to mono, cut into utterances, and resampled to the 16 kHz the
+//! recognizers expect. With a wake word configured, utterances are only
+//! passed on for `listen_secs` after it is heard.
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+#[cfg(feature = "wake-word")]
+use super::wake::WakeWordDetector;
+use crate::config::SttConfig;
+
+pub enum MicEvent {
+    /// 16 kHz mono speech
+    Utterance(Vec<f32>),
+    /// The wake word was heard
+    Wake,
+}
+
+/// Start capturing; events are sent on `tx` until it closes
+#[cfg(feature = "microphone")]
+pub fn start(config: &SttConfig, tx: mpsc::UnboundedSender<MicEvent>) -> Result<()> {
+    use std::{
+        sync::mpsc as std_mpsc,
+        time::{Duration, Instant},
+    };
+
+    use anyhow::{Context, anyhow};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use tracing::{debug, info, warn};
+
+    use super::{SAMPLE_RATE, vad::Segmenter};
+
+    let device_name = config.device.clone();
+    let vad = config.vad.clone();
+    let wake = config.wake.clone();
+    let (ready_tx, ready_rx) = std_mpsc::sync_channel::<Result<()>>(1);
+    std::thread::Builder::new()
+        .name("dewet-microphone".into())
+        .spawn(move || {
+            let (frames_tx, frames_rx) = std_mpsc::channel::<Vec<f32>>();
+            let opened = (|| -> Result<(cpal::Stream, u32, Option<WakeWordDetector>)> {
+                let detector = wake.as_ref().map(WakeWordDetector::load).transpose()?;
+                let host = cpal::default_host();
+                let device = match &device_name {
+                    Some(name) => host
+                        .input_devices()?
+                        .find(|d| d.name().is_ok_and(|n| &n == name))
+                        .ok_or_else(|| anyhow!("No input device named {:?}", name))?,
+                    None => host
+                        .default_input_device()
+                        .ok_or_else(|| anyhow!("No default input device"))?,
+                };
+                let supported = device.default_input_config()?;
+                let sample_rate = supported.sample_rate().0;
+                let channels = supported.channels() as usize;
+                info!(
+                    device = %device.name().unwrap_or_default(),
+                    sample_rate,
+                    channels,
+                    "Microphone opened"
+                );
+
+                let err_fn = |err| warn!(?err, "Microphone stream error");
+                let stream_config = supported.config();
+                let stream = match supported.sample_format() {
+                    cpal::SampleFormat::F32 => device.build_input_stream(
+                        &stream_config,
+                        move |data: &[f32], _| {
+                            let _ = frames_tx.send(downmix(data, channels, |s| s));
+                        },
+                        err_fn,
+                        None,
+                    )?,
+                    cpal::SampleFormat::I16 => device.build_input_stream(
+                        &stream_config,
+                        move |data: &[i16], _| {
+                            let _ = frames_tx
+                                .send(downmix(data, channels, |s| s as f32 / i16::MAX as f32));
+                        },
+                        err_fn,
+                        None,
+                    )?,
+                    cpal::SampleFormat::U16 => device.build_input_stream(
+                        &stream_config,
+                        move |data: &[u16], _| {
+                            let _ = frames_tx
+                                .send(downmix(data, channels, |s| (s as f32 - 32768.0) / 32768.0));
+                        },
+                        err_fn,
+                        None,
+                    )?,
+                    format => return Err(anyhow!("Unsupported sample format {:?}", format)),
+                };
+                stream.play()?;
+                Ok((stream, sample_rate, detector))
+            })();
+
+            let (_stream, sample_rate, mut detector) = match opened {
+                Ok(opened) => {
+                    let _ = ready_tx.send(Ok(()));
+                    opened
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                    return;
+                }
+            };
+            let mut segmenter = Segmenter::new(&vad, sample_rate);
+            let listen_for = Duration::from_secs(wake.as_ref().map_or(0, |w| w.listen_secs));
+            let mut awake_until: Option<Instant> = None;
+            while let Ok(frames) = frames_rx.recv() {
+                if let Some(detector) = &mut detector {
+                    match detector.push(&resample(&frames, sample_rate, SAMPLE_RATE)) {
+                        Ok(true) => {
+                            info!("Wake word heard");
+                            awake_until = Some(Instant::now() + listen_for);
+                            if tx.send(MicEvent::Wake).is_err() {
+                                return;
+                            }
+                        }
+                        Ok(false) => {}
+                        Err(err) => warn!(?err, "Wake word detection failed"),
+                    }
+                }
+                for utterance in segmenter.push(&frames) {
+                    if detector.is_some() {
+                        if !awake_until.is_some_and(|until| Instant::now() < until) {
+                            debug!("Ignoring speech without the wake word");
+                            continue;
+                        }
+                        // Keep listening while the conversation goes on
+                        awake_until = Some(Instant::now() + listen_for);
+                    }
+                    let utterance = resample(&utterance, sample_rate, SAMPLE_RATE);
+                    if tx.send(MicEvent::Utterance(utterance)).is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+        .context("Failed to start microphone thread")?;
+
+    ready_rx
+        .recv()
+        .map_err(|_| anyhow!("Microphone thread exited"))?
+}
+
+#[cfg(not(feature = "microphone"))]
+pub fn start(_config: &SttConfig, _tx: mpsc::UnboundedSender<MicEvent>) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "Voice input requires building with the `microphone` feature"
+    ))
+}
+
+#[cfg(all(feature = "microphone", not(feature = "wake-word")))]
+/// Stands in so a configured wake word fails to load
+struct WakeWordDetector;
+
+#[cfg(all(feature = "microphone", not(feature = "wake-word")))]
+impl WakeWordDetector {
+    fn load(_config: &crate::config::WakeWordConfig) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "A wake word requires building with the `wake-word` feature"
+        ))
+    }
+
+    fn push(&mut self, _samples: &[f32]) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Average interleaved channels into mono f32
+#[cfg(feature = "microphone")]
+fn downmix<T: Copy>(data: &[T], channels: usize, to_f32: impl Fn(T) -> f32) -> Vec<f32> {
+    data.chunks(channels.max(1))
+        .map(|frame| frame.iter().map(|&s| to_f32(s)).sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+#[cfg_attr(not(feature = "microphone"), allow(dead_code))]
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let ratio = from as f64 / to as f64;
+    let len = (samples.len() as f64 / ratio) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let next = samples.get(index + 1).copied().unwrap_or(samples[index]);
+            let fraction = (position - index as f64) as f32;
+            samples[index] + (next - samples[index]) * fraction
+        })
+        .collect()
+}
@@ -0,0 +1,170 @@
+//! Voice input: the microphone is cut into utterances on silence, and each is
+//! transcribed and handed to the daemon like typed chat
+
+mod microphone;
+mod openai;
+mod vad;
+#[cfg(feature = "wake-word")]
+mod wake;
+#[cfg(feature = "whisper")]
+mod whisper;
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use self::microphone::MicEvent;
+use crate::config::SttConfig;
+
+pub use openai::OpenAiRecognizer;
+#[cfg(feature = "whisper")]
+pub use whisper::WhisperRecognizer;
+
+/// Sample rate of the audio handed to recognizers
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Recognizers that do blocking work run it on the blocking pool
+#[async_trait::async_trait]
+pub trait SpeechRecognizer: Send + Sync {
+    /// Transcribe 16 kHz mono audio
+    async fn transcribe(&self, samples: &[f32]) -> Result<String>;
+}
+
+/// What the daemon hears
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoiceEvent {
+    /// The wake word, calling `character` if one is configured
+    Wake {
+        character: Option<String>,
+    },
+    Transcript(String),
+}
+
+/// Start listening if `[stt]` is enabled. Events arrive on the returned
+/// channel, which is closed straight away when voice input is off or
+/// unavailable.
+pub fn listen(config: &SttConfig) -> mpsc::UnboundedReceiver<VoiceEvent> {
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    if !config.enabled {
+        return event_rx;
+    }
+    let Some(recognizer) = create_recognizer(config) else {
+        return event_rx;
+    };
+
+    let (mic_tx, mut mic_rx) = mpsc::unbounded_channel();
+    if let Err(err) = microphone::start(config, mic_tx) {
+        warn!(?err, "Microphone unavailable; voice input disabled");
+        return event_rx;
+    }
+    let wake_word = config.wake.as_ref().map(|wake| wake.word.clone());
+    let character = config.wake.as_ref().and_then(|wake| wake.character.clone());
+    info!(provider = %config.provider, ?wake_word, "Voice input enabled");
+
+    tokio::spawn(async move {
+        while let Some(event) = mic_rx.recv().await {
+            let samples = match event {
+                MicEvent::Utterance(samples) => samples,
+                MicEvent::Wake => {
+                    let character = character.clone();
+                    if event_tx.send(VoiceEvent::Wake { character }).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let seconds = samples.len() as f32 / SAMPLE_RATE as f32;
+            match recognizer.transcribe(&samples).await {
+                Ok(text) => match clean_transcript(&text) {
+                    Some(text) => {
+                        debug!(seconds, %text, "Transcribed utterance");
+                        if event_tx.send(VoiceEvent::Transcript(text)).is_err() {
+                            break;
+                        }
+                    }
+                    None => debug!(seconds, raw = %text, "Discarded empty transcript"),
+                },
+                Err(err) => warn!(?err, "Speech recognition failed"),
+            }
+        }
+    });
+    event_rx
+}
+
+fn create_recognizer(config: &SttConfig) -> Option<Arc<dyn SpeechRecognizer>> {
+    match config.provider.as_str() {
+        "openai" => match &config.openai {
+            Some(openai) => Some(Arc::new(OpenAiRecognizer::new(
+                openai,
+                config.language.clone(),
+            ))),
+            None => {
+                warn!(
+                    "stt.provider is \"openai\" but [stt.openai] is missing; voice input disabled"
+                );
+                None
+            }
+        },
+        #[cfg(feature = "whisper")]
+        "whisper" => match &config.whisper {
+            Some(whisper) => match WhisperRecognizer::new(whisper, config.language.clone()) {
+                Ok(recognizer) => Some(Arc::new(recognizer)),
+                Err(err) => {
+                    warn!(?err, "Failed to load Whisper; voice input disabled");
+                    None
+                }
+            },
+            None => {
+                warn!(
+                    "stt.provider is \"whisper\" but [stt.whisper] is missing; voice input disabled"
+                );
+                None
+            }
+        },
+        #[cfg(not(feature = "whisper"))]
+        "whisper" => {
+            warn!(
+                "Local Whisper requires building with the `whisper` feature; voice input disabled"
+            );
+            None
+        }
+        other => {
+            warn!(provider = %other, "Unknown stt.provider; voice input disabled");
+            None
+        }
+    }
+}
+
+/// Trim a transcript, dropping what Whisper produces for noise: nothing, or
+/// only annotations like "[BLANK_AUDIO]" and "(keyboard clicking)"
+fn clean_transcript(text: &str) -> Option<String> {
+    let text = text.trim();
+    let mut depth = 0i32;
+    let has_words = text.chars().any(|c| {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            _ => return depth <= 0 && c.is_alphanumeric(),
+        }
+        false
+    });
+    has_words.then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_transcript() {
+        assert_eq!(
+            clean_transcript("  Hey, can you check the build? ").as_deref(),
+            Some("Hey, can you check the build?")
+        );
+        assert_eq!(clean_transcript("[BLANK_AUDIO]"), None);
+        assert_eq!(clean_transcript(" (keyboard clicking) "), None);
+        assert_eq!(clean_transcript(""), None);
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::{Result, anyhow};
+use reqwest::{
+    Client,
+    multipart::{Form, Part},
+};
+use serde::Deserialize;
+
+use super::{SAMPLE_RATE, SpeechRecognizer};
+use crate::{config::OpenAiSttConfig, tts::wav_from_pcm16};
+
+/// OpenAI's transcription API, or a compatible server (faster-whisper-server,
+/// LocalAI, Groq, ...)
+pub struct OpenAiRecognizer {
+    http: Client,
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Transcription {
+    text: String,
+}
+
+impl OpenAiRecognizer {
+    pub fn new(config: &OpenAiSttConfig, language: Option<String>) -> Self {
+        Self {
+            http: Client::new(),
+            endpoint: config.endpoint.clone(),
+            model: config.model.clone(),
+            api_key: config.api_key(),
+            language,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechRecognizer for OpenAiRecognizer {
+    async fn transcribe(&self, samples: &[f32]) -> Result<String> {
+        let pcm: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect();
+        let wav = wav_from_pcm16(&pcm, SAMPLE_RATE);
+
+        let mut form = Form::new().text("model", self.model.clone()).part(
+            "file",
+            Part::bytes(wav)
+                .file_name("speech.wav")
+                .mime_str("audio/wav")?,
+        );
+        if let Some(language) = &self.language {
+            form = form.text("language", language.clone());
+        }
+
+        let mut request = self.http.post(&self.endpoint).multipart(form);
+        // Local servers usually don't need a key
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let resp = request.send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Transcription failed ({}): {}",
+                status,
+                body.trim()
+            ));
+        }
+        Ok(resp.json::<Transcription>().await?.text)
+    }
+}
@@ -0,0 +1,137 @@
+//! Splits microphone audio into utterances on silence
+
+use std::collections::VecDeque;
+
+use crate::config::VadConfig;
+
+/// Audio is judged speech or silence in frames of this length
+const FRAME_MS: u64 = 20;
+/// Audio kept from before speech starts, so soft onsets aren't clipped
+const PRE_ROLL_MS: u64 = 200;
+
+pub struct Segmenter {
+    threshold: f32,
+    frame_len: usize,
+    pre_roll_len: usize,
+    silence_frames: usize,
+    min_speech_frames: usize,
+    max_len: usize,
+    /// Samples not yet making up a whole frame
+    pending: Vec<f32>,
+    /// Recent audio while idle
+    pre_roll: VecDeque<f32>,
+    /// The utterance in progress, if any
+    utterance: Option<Utterance>,
+}
+
+struct Utterance {
+    samples: Vec<f32>,
+    speech_frames: usize,
+    trailing_silence: usize,
+}
+
+impl Segmenter {
+    pub fn new(config: &VadConfig, sample_rate: u32) -> Self {
+        let frame_len = (sample_rate as u64 * FRAME_MS / 1000).max(1) as usize;
+        Self {
+            threshold: config.threshold,
+            frame_len,
+            pre_roll_len: (sample_rate as u64 * PRE_ROLL_MS / 1000) as usize,
+            silence_frames: (config.silence_ms / FRAME_MS).max(1) as usize,
+            min_speech_frames: (config.min_speech_ms / FRAME_MS) as usize,
+            max_len: (sample_rate as u64 * config.max_utterance_secs) as usize,
+            pending: Vec::new(),
+            pre_roll: VecDeque::new(),
+            utterance: None,
+        }
+    }
+
+    /// Feed mono samples; returns any utterances they complete
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let mut finished = Vec::new();
+        let frames = self.pending.len() / self.frame_len;
+        let pending = std::mem::take(&mut self.pending);
+        for frame in pending.chunks(self.frame_len).take(frames) {
+            if let Some(utterance) = self.push_frame(frame) {
+                finished.push(utterance);
+            }
+        }
+        self.pending = pending[frames * self.frame_len..].to_vec();
+        finished
+    }
+
+    fn push_frame(&mut self, frame: &[f32]) -> Option<Vec<f32>> {
+        let speech = rms(frame) >= self.threshold;
+        let Some(utterance) = &mut self.utterance else {
+            if speech {
+                let mut samples: Vec<f32> = self.pre_roll.drain(..).collect();
+                samples.extend_from_slice(frame);
+                self.utterance = Some(Utterance {
+                    samples,
+                    speech_frames: 1,
+                    trailing_silence: 0,
+                });
+            } else {
+                self.pre_roll.extend(frame);
+                let excess = self.pre_roll.len().saturating_sub(self.pre_roll_len);
+                self.pre_roll.drain(..excess);
+            }
+            return None;
+        };
+
+        utterance.samples.extend_from_slice(frame);
+        if speech {
+            utterance.speech_frames += 1;
+            utterance.trailing_silence = 0;
+        } else {
+            utterance.trailing_silence += 1;
+        }
+        if utterance.trailing_silence < self.silence_frames
+            && utterance.samples.len() < self.max_len
+        {
+            return None;
+        }
+
+        let utterance = self.utterance.take()?;
+        (utterance.speech_frames >= self.min_speech_frames).then_some(utterance.samples)
+    }
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segmenter_splits_on_silence() {
+        let config = VadConfig {
+            threshold: 0.1,
+            silence_ms: 100,
+            min_speech_ms: 100,
+            max_utterance_secs: 10,
+        };
+        // 1 kHz: 20-sample frames, 5 frames of silence end an utterance
+        let mut segmenter = Segmenter::new(&config, 1000);
+        let silence = vec![0.0; 400];
+        let speech = vec![0.5; 300];
+        let blip = vec![0.5; 40];
+
+        assert!(segmenter.push(&silence).is_empty());
+        assert!(segmenter.push(&speech).is_empty());
+        let utterances = segmenter.push(&silence);
+        assert_eq!(utterances.len(), 1);
+        // Pre-roll + speech + the silence that ended it
+        assert_eq!(utterances[0].len(), 200 + 300 + 100);
+
+        // Too short to be speech
+        segmenter.push(&blip);
+        assert!(segmenter.push(&silence).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+//! Wake word detection with openWakeWord models (feature `wake-word`)
+//!
+//! openWakeWord runs three small ONNX models over 16 kHz audio in 80 ms
+//! chunks: a melspectrogram, a shared speech embedding over the last 76 mel
+//! frames, and the wake word classifier over the last 16 embeddings.
+
+use std::{collections::VecDeque, path::Path};
+
+use anyhow::{Context, Result};
+use ort::{session::Session, value::Tensor};
+
+use crate::config::WakeWordConfig;
+
+/// Samples per chunk (80 ms)
+const CHUNK: usize = 1280;
+/// Extra samples fed to the melspectrogram so chunks line up
+const MEL_CONTEXT: usize = 480;
+const MEL_BINS: usize = 32;
+const EMBEDDING_WINDOW: usize = 76;
+const EMBEDDING_DIM: usize = 96;
+const CLASSIFIER_WINDOW: usize = 16;
+/// Mel frames kept, about 10 s
+const MAX_MEL_FRAMES: usize = 970;
+/// Chunks ignored after a detection, so one utterance triggers once (2 s)
+const REFRACTORY_CHUNKS: usize = 25;
+
+pub struct WakeWordDetector {
+    melspectrogram: Model,
+    embedding: Model,
+    classifier: Model,
+    threshold: f32,
+    /// Samples not yet making up a whole chunk
+    pending: Vec<f32>,
+    /// Tail of the previous chunk
+    context: Vec<f32>,
+    /// Flattened mel frames
+    mel: Vec<f32>,
+    embeddings: VecDeque<Vec<f32>>,
+    refractory: usize,
+}
+
+struct Model {
+    session: Session,
+    input: String,
+    output: String,
+}
+
+impl Model {
+    fn load(path: &Path) -> Result<Self> {
+        let session = Session::builder()?
+            .commit_from_file(path)
+            .with_context(|| format!("Failed to load wake word model {:?}", path))?;
+        let input = session.inputs[0].name.clone();
+        let output = session.outputs[0].name.clone();
+        Ok(Self {
+            session,
+            input,
+            output,
+        })
+    }
+
+    fn run(&mut self, shape: &[usize], data: Vec<f32>) -> Result<Vec<f32>> {
+        let outputs = self.session.run(ort::inputs![
+            self.input.as_str() => Tensor::from_array((shape.to_vec(), data))?,
+        ]?)?;
+        let (_, values) = outputs[self.output.as_str()].try_extract_raw_tensor::<f32>()?;
+        Ok(values.to_vec())
+    }
+}
+
+impl WakeWordDetector {
+    pub fn load(config: &WakeWordConfig) -> Result<Self> {
+        let dir = Path::new(&config.model_dir);
+        Ok(Self {
+            melspectrogram: Model::load(&dir.join("melspectrogram.onnx"))?,
+            embedding: Model::load(&dir.join("embedding_model.onnx"))?,
+            classifier: Model::load(Path::new(&config.model_path()))?,
+            threshold: config.threshold(),
+            pending: Vec::new(),
+            context: Vec::new(),
+            mel: Vec::new(),
+            embeddings: VecDeque::new(),
+            refractory: 0,
+        })
+    }
+
+    /// Feed 16 kHz mono samples; true if they complete the wake word
+    pub fn push(&mut self, samples: &[f32]) -> Result<bool> {
+        // The models expect 16-bit sample values
+        self.pending
+            .extend(samples.iter().map(|s| s * i16::MAX as f32));
+        let mut detected = false;
+        while self.pending.len() >= CHUNK {
+            let chunk: Vec<f32> = self.pending.drain(..CHUNK).collect();
+            let score = self.process_chunk(chunk)?;
+            if self.refractory > 0 {
+                self.refractory -= 1;
+            } else if score.is_some_and(|score| score >= self.threshold) {
+                detected = true;
+                self.refractory = REFRACTORY_CHUNKS;
+            }
+        }
+        Ok(detected)
+    }
+
+    /// The classifier's score once enough audio has been heard
+    fn process_chunk(&mut self, chunk: Vec<f32>) -> Result<Option<f32>> {
+        let mut input = std::mem::take(&mut self.context);
+        input.extend(chunk);
+        self.context = input[input.len().saturating_sub(MEL_CONTEXT)..].to_vec();
+
+        let len = input.len();
+        let mel = self.melspectrogram.run(&[1, len], input)?;
+        // Scale as openWakeWord does before the embedding model
+        self.mel.extend(mel.iter().map(|v| v / 10.0 + 2.0));
+        let excess = self.mel.len().saturating_sub(MAX_MEL_FRAMES * MEL_BINS);
+        self.mel.drain(..excess);
+
+        if self.mel.len() < EMBEDDING_WINDOW * MEL_BINS {
+            return Ok(None);
+        }
+        let window = self.mel[self.mel.len() - EMBEDDING_WINDOW * MEL_BINS..].to_vec();
+        let embedding = self
+            .embedding
+            .run(&[1, EMBEDDING_WINDOW, MEL_BINS, 1], window)?;
+        self.embeddings.push_back(embedding);
+        if self.embeddings.len() > CLASSIFIER_WINDOW {
+            self.embeddings.pop_front();
+        }
+
+        if self.embeddings.len() < CLASSIFIER_WINDOW {
+            return Ok(None);
+        }
+        let features: Vec<f32> = self.embeddings.iter().flatten().copied().collect();
+        let score = self
+            .classifier
+            .run(&[1, CLASSIFIER_WINDOW, EMBEDDING_DIM], features)?;
+        Ok(score.first().copied())
+    }
+}
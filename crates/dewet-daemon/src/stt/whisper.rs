@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use super::SpeechRecognizer;
+use crate::config::WhisperConfig;
+
+/// Local transcription with whisper.cpp (feature `whisper`)
+pub struct WhisperRecognizer {
+    context: Arc<WhisperContext>,
+    threads: Option<i32>,
+    language: Option<String>,
+}
+
+impl WhisperRecognizer {
+    pub fn new(config: &WhisperConfig, language: Option<String>) -> Result<Self> {
+        let context = WhisperContext::new_with_params(
+            &config.model_path,
+            WhisperContextParameters::default(),
+        )
+        .with_context(|| format!("Failed to load Whisper model {:?}", config.model_path))?;
+        Ok(Self {
+            context: Arc::new(context),
+            threads: config.threads,
+            language,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechRecognizer for WhisperRecognizer {
+    async fn transcribe(&self, samples: &[f32]) -> Result<String> {
+        let context = self.context.clone();
+        let threads = self.threads;
+        let language = self.language.clone();
+        let samples = samples.to_vec();
+        tokio::task::spawn_blocking(move || -> Result<String> {
+            let mut state = context.create_state()?;
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_language(Some(language.as_deref().unwrap_or("auto")));
+            if let Some(threads) = threads {
+                params.set_n_threads(threads);
+            }
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_special(false);
+            params.set_print_timestamps(false);
+            params.set_suppress_blank(true);
+            state
+                .full(params, &samples)
+                .map_err(|e| anyhow!("Whisper inference failed: {}", e))?;
+
+            let mut text = String::new();
+            for segment in 0..state.full_n_segments()? {
+                text.push_str(&state.full_get_segment_text(segment)?);
+            }
+            Ok(text)
+        })
+        .await?
+    }
+}
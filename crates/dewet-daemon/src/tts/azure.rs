@@ -0,0 +1,191 @@
+use anyhow::{Result, anyhow};
+use reqwest::{Client, StatusCode};
+
+use super::{
+    Prosody, SpeechSynthesizer,
+    lexicon::{Segment, segments},
+    prosody::ssml_percent,
+};
+use crate::config::AzureSpeechConfig;
+
+/// RIFF-wrapped 16-bit mono PCM, the same format as the other providers
+const OUTPUT_FORMAT: &str = "riff-16khz-16bit-mono-pcm";
+
+/// Azure Cognitive Services neural voices over the REST API
+pub struct AzureSpeechSynth {
+    http: Client,
+    api_key: String,
+    endpoint: String,
+    voice: String,
+}
+
+impl AzureSpeechSynth {
+    pub fn new(config: &AzureSpeechConfig, api_key: String) -> Self {
+        Self {
+            http: Client::new(),
+            api_key,
+            endpoint: config.endpoint(),
+            voice: config.voice.clone(),
+        }
+    }
+
+    async fn request(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        let resp = self
+            .http
+            .post(&self.endpoint)
+            .header("Ocp-Apim-Subscription-Key", &self.api_key)
+            .header("Content-Type", "application/ssml+xml")
+            .header("X-Microsoft-OutputFormat", OUTPUT_FORMAT)
+            .header("User-Agent", "dewet")
+            .body(ssml_for(text, &self.voice, prosody))
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(map_error(status, &body, &self.voice));
+        }
+
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for AzureSpeechSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        self.request(text, prosody).await
+    }
+
+    fn supports_pitch(&self) -> bool {
+        true
+    }
+
+    fn supports_phonemes(&self) -> bool {
+        true
+    }
+}
+
+/// Text that is already an SSML document is sent unchanged; plain text is
+/// escaped (keeping lexicon phonemes) and wrapped in the configured voice, with
+/// the mood as a speaking style and prosody
+fn ssml_for(text: &str, voice: &str, prosody: &Prosody) -> String {
+    let trimmed = text.trim();
+    if trimmed.starts_with("<speak") {
+        return trimmed.to_string();
+    }
+
+    let mut content: String = segments(trimmed)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => escape_xml(text),
+            Segment::Phoneme { ipa, word } => format!(
+                "<phoneme alphabet=\"ipa\" ph=\"{}\">{}</phoneme>",
+                escape_xml(ipa),
+                escape_xml(word)
+            ),
+        })
+        .collect();
+    if prosody.rate != 1.0 || prosody.pitch != 1.0 || prosody.volume != 1.0 {
+        content = format!(
+            "<prosody rate=\"{}\" pitch=\"{}\" volume=\"{}\">{}</prosody>",
+            ssml_percent(prosody.rate),
+            ssml_percent(prosody.pitch),
+            ssml_percent(prosody.volume),
+            content
+        );
+    }
+    if let Some(style) = &prosody.style {
+        // Voices without the style ignore it; styledegree ranges 0.01-2
+        content = format!(
+            "<mstts:express-as style=\"{}\" styledegree=\"{:.2}\">{}</mstts:express-as>",
+            escape_xml(style),
+            0.5 + prosody.intensity,
+            content
+        );
+    }
+    format!(
+        "<speak version=\"1.0\" xmlns=\"http://www.w3.org/2001/10/synthesis\" \
+         xmlns:mstts=\"https://www.w3.org/2001/mstts\" xml:lang=\"en-US\">\
+         <voice name=\"{}\">{}</voice></speak>",
+        escape_xml(voice),
+        content
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Turn an API error into a message that says what to fix. Azure usually
+/// returns an empty body, so the status carries most of the meaning.
+fn map_error(status: StatusCode, body: &str, voice: &str) -> anyhow::Error {
+    let detail = body.trim();
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            anyhow!(
+                "Azure Speech rejected the key (check key and region): {}",
+                detail
+            )
+        }
+        StatusCode::BAD_REQUEST => anyhow!(
+            "Azure Speech rejected the request (invalid SSML or unknown voice '{}'): {}",
+            voice,
+            detail
+        ),
+        StatusCode::TOO_MANY_REQUESTS => {
+            anyhow!("Azure Speech rate limit or quota exceeded: {}", detail)
+        }
+        _ => anyhow!("Azure Speech error {}: {}", status, detail),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssml_wraps_and_escapes_plain_text() {
+        let ssml = ssml_for(" Tom & Jerry <3 ", "en-US-JennyNeural", &Prosody::default());
+        assert!(ssml.contains("<voice name=\"en-US-JennyNeural\">Tom &amp; Jerry &lt;3</voice>"));
+        assert!(ssml.starts_with("<speak "));
+    }
+
+    #[test]
+    fn test_ssml_keeps_phonemes() {
+        let text = "Ask <phoneme alphabet=\"ipa\" ph=\"ˈdjuːɪt\">Dewet</phoneme> & co";
+        let ssml = ssml_for(text, "en-US-JennyNeural", &Prosody::default());
+        assert!(ssml.contains(
+            ">Ask <phoneme alphabet=\"ipa\" ph=\"ˈdjuːɪt\">Dewet</phoneme> &amp; co</voice>"
+        ));
+    }
+
+    #[test]
+    fn test_ssml_passthrough() {
+        let doc = "<speak version=\"1.0\"><voice name=\"x\"><break time=\"1s\"/>hi</voice></speak>";
+        let prosody = Prosody::for_mood(Some("happy"), 0.9);
+        assert_eq!(ssml_for(doc, "en-US-JennyNeural", &prosody), doc);
+    }
+
+    #[test]
+    fn test_ssml_applies_mood() {
+        let ssml = ssml_for(
+            "Yes!",
+            "en-US-JennyNeural",
+            &Prosody::for_mood(Some("happy"), 0.5),
+        );
+        assert!(ssml.contains("<mstts:express-as style=\"cheerful\" styledegree=\"1.00\">"));
+        assert!(ssml.contains("<prosody rate=\"+5%\" pitch=\"+5%\" volume=\"+0%\">Yes!</prosody>"));
+    }
+}
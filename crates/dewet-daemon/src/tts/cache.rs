@@ -0,0 +1,365 @@
+//! On-disk cache of synthesized audio
+//!
+//! Entries are keyed by a hash of the voice (provider plus its settings) and
+//! the text, so repeated phrases don't re-hit the backend. The cache is capped
+//! in size and evicts least-recently-used entries; file modification times
+//! carry recency across restarts. Viseme timing from the provider is kept in
+//! a JSON file next to the audio.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use super::{Prosody, SharedSynth, SpeechSynthesizer, Synthesis};
+use crate::config::TtsCacheConfig;
+
+/// Hit stats are logged every this many lookups
+const LOG_EVERY: u64 = 50;
+
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    /// Logical clock for recency; higher is more recent
+    clock: u64,
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+/// Point-in-time view of the cache
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl AudioCache {
+    /// Open (or create) the cache directory and index what is already there
+    pub fn open(config: &TtsCacheConfig) -> Result<Self> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create TTS cache dir {:?}", dir))?;
+
+        let mut existing = Vec::new();
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "wav") {
+                continue;
+            }
+            let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            existing.push((modified, key.to_string(), meta.len()));
+        }
+        existing.sort();
+
+        let mut index = CacheIndex::default();
+        for (_, key, size) in existing {
+            index.clock += 1;
+            index.total_bytes += size;
+            index.entries.insert(
+                key,
+                CacheEntry {
+                    size,
+                    last_used: index.clock,
+                },
+            );
+        }
+        info!(
+            dir = ?dir,
+            entries = index.entries.len(),
+            bytes = index.total_bytes,
+            "TTS cache opened"
+        );
+
+        let cache = Self {
+            dir,
+            max_bytes: config.max_mb * 1024 * 1024,
+            index: Mutex::new(index),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let index = self.index.lock();
+        CacheStats {
+            entries: index.entries.len(),
+            bytes: index.total_bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.wav", key))
+    }
+
+    fn visemes_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    async fn get(&self, key: &str) -> Option<Synthesis> {
+        let known = {
+            let mut index = self.index.lock();
+            index.clock += 1;
+            let clock = index.clock;
+            index
+                .entries
+                .get_mut(key)
+                .map(|entry| entry.last_used = clock)
+                .is_some()
+        };
+        let audio = if known {
+            tokio::fs::read(self.path(key)).await.ok()
+        } else {
+            None
+        };
+
+        match &audio {
+            Some(_) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                // Persist recency for the next run; failure only affects ordering
+                if let Ok(file) = File::options().write(true).open(self.path(key)) {
+                    file.set_modified(SystemTime::now()).ok();
+                }
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                if known {
+                    // Deleted from under us
+                    let mut index = self.index.lock();
+                    if let Some(entry) = index.entries.remove(key) {
+                        index.total_bytes -= entry.size;
+                    }
+                }
+            }
+        }
+        self.log_stats();
+        let audio = audio?;
+        let visemes = tokio::fs::read(self.visemes_path(key))
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok());
+        Some(Synthesis { audio, visemes })
+    }
+
+    async fn put(&self, key: &str, synthesis: &Synthesis) {
+        let size = synthesis.audio.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        if let Err(err) = tokio::fs::write(self.path(key), &synthesis.audio).await {
+            warn!(?err, "Failed to write TTS cache entry");
+            return;
+        }
+        if let Some(visemes) = &synthesis.visemes {
+            // Small next to the audio, so not counted toward the cap
+            if let Ok(json) = serde_json::to_vec(visemes) {
+                tokio::fs::write(self.visemes_path(key), json).await.ok();
+            }
+        }
+        {
+            let mut index = self.index.lock();
+            index.clock += 1;
+            let clock = index.clock;
+            if let Some(old) = index.entries.insert(
+                key.to_string(),
+                CacheEntry {
+                    size,
+                    last_used: clock,
+                },
+            ) {
+                index.total_bytes -= old.size;
+            }
+            index.total_bytes += size;
+        }
+        self.evict();
+    }
+
+    /// Remove least-recently-used entries until the cache fits
+    fn evict(&self) {
+        let mut index = self.index.lock();
+        while index.total_bytes > self.max_bytes {
+            let Some(oldest) = index
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = index.entries.remove(&oldest) {
+                index.total_bytes -= entry.size;
+            }
+            fs::remove_file(self.path(&oldest)).ok();
+            fs::remove_file(self.visemes_path(&oldest)).ok();
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn log_stats(&self) {
+        let stats = self.stats();
+        let lookups = stats.hits + stats.misses;
+        if lookups % LOG_EVERY == 0 {
+            info!(
+                hits = stats.hits,
+                misses = stats.misses,
+                hit_rate = %format!("{:.0}%", stats.hits as f64 * 100.0 / lookups as f64),
+                entries = stats.entries,
+                bytes = stats.bytes,
+                "TTS cache stats"
+            );
+        }
+    }
+}
+
+/// Serves repeated (voice, delivery, text) combinations from the cache
+pub struct CachedSynth {
+    inner: SharedSynth,
+    cache: Arc<AudioCache>,
+    /// Identifies the voice and its settings, so changing them misses the cache
+    voice: String,
+}
+
+impl CachedSynth {
+    pub fn new(inner: SharedSynth, cache: Arc<AudioCache>, voice: String) -> Self {
+        Self {
+            inner,
+            cache,
+            voice,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for CachedSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        Ok(self.synthesize_timed(text, prosody).await?.audio)
+    }
+
+    async fn synthesize_timed(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        let key = cache_key(&format!("{}|{:?}", self.voice, prosody), text);
+        if let Some(synthesis) = self.cache.get(&key).await {
+            debug!(key = %key, "TTS cache hit");
+            return Ok(synthesis);
+        }
+        let synthesis = self.inner.synthesize_timed(text, prosody).await?;
+        self.cache.put(&key, &synthesis).await;
+        Ok(synthesis)
+    }
+
+    fn supports_pitch(&self) -> bool {
+        self.inner.supports_pitch()
+    }
+
+    fn supports_phonemes(&self) -> bool {
+        self.inner.supports_phonemes()
+    }
+}
+
+/// Stable across runs (unlike `DefaultHasher`), since keys name files on disk
+fn cache_key(voice: &str, text: &str) -> String {
+    // FNV-1a, 64-bit
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in voice.bytes().chain([0]).chain(text.trim().bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CountingSynth(AtomicU64);
+
+    #[async_trait::async_trait]
+    impl SpeechSynthesizer for CountingSynth {
+        async fn synthesize(&self, text: &str, _prosody: &Prosody) -> Result<Vec<u8>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(text.as_bytes().repeat(100))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_evicts_lru() {
+        let dir = std::env::temp_dir().join(format!("dewet-tts-cache-{}", uuid::Uuid::new_v4()));
+        let cache = Arc::new(
+            AudioCache::open(&TtsCacheConfig {
+                dir: dir.to_string_lossy().into_owned(),
+                max_mb: 1,
+            })
+            .unwrap(),
+        );
+        let inner = Arc::new(CountingSynth(AtomicU64::new(0)));
+        let synth = CachedSynth::new(inner.clone(), cache.clone(), "piper:amy".into());
+        let neutral = Prosody::default();
+
+        let first = synth.synthesize("Hello there", &neutral).await.unwrap();
+        assert_eq!(
+            synth.synthesize("Hello there", &neutral).await.unwrap(),
+            first
+        );
+        assert_eq!(inner.0.load(Ordering::SeqCst), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+
+        // Three 400KB entries overflow the 1MB cap
+        let a = "a".repeat(4000);
+        let b = "b".repeat(4000);
+        let c = "c".repeat(4000);
+        synth.synthesize(&a, &neutral).await.unwrap();
+        synth.synthesize(&b, &neutral).await.unwrap();
+        synth.synthesize(&a, &neutral).await.unwrap();
+        synth.synthesize(&c, &neutral).await.unwrap();
+        // "Hello there" is evicted first, then b (a was touched after it)
+        assert!(cache.stats().bytes <= 1024 * 1024);
+        assert_eq!(cache.stats().evictions, 2);
+        let before = inner.0.load(Ordering::SeqCst);
+        synth.synthesize(&a, &neutral).await.unwrap();
+        assert_eq!(
+            inner.0.load(Ordering::SeqCst),
+            before,
+            "a was recently used"
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use super::{
+    Prosody, SpeechSynthesizer, Synthesis, lipsync::visemes_from_timing, prosody::scale_wav_volume,
+    wav_from_pcm16,
+};
+use crate::config::ElevenLabsConfig;
+
+/// Raw 16-bit mono PCM, wrapped into the same WAV format as the other providers
+const OUTPUT_FORMAT: &str = "pcm_16000";
+const SAMPLE_RATE: u32 = 16_000;
+/// Models that read SSML `<phoneme>` tags
+const PHONEME_MODELS: &[&str] = &[
+    "eleven_flash_v2",
+    "eleven_turbo_v2",
+    "eleven_monolingual_v1",
+];
+
+/// Response of the `/with-timestamps` endpoint
+#[derive(Deserialize)]
+struct TimestampedAudio {
+    audio_base64: String,
+    alignment: Option<Alignment>,
+}
+
+/// When each character of the input is spoken
+#[derive(Deserialize)]
+struct Alignment {
+    characters: Vec<String>,
+    character_start_times_seconds: Vec<f32>,
+}
+
+pub struct ElevenLabsSynth {
+    http: Client,
+    api_key: String,
+    voice_id: String,
+    model_id: String,
+    voice_settings: Map<String, Value>,
+}
+
+impl ElevenLabsSynth {
+    pub fn new(config: &ElevenLabsConfig, api_key: String) -> Self {
+        let mut settings = Map::new();
+        if let Some(stability) = config.stability {
+            settings.insert("stability".to_string(), json!(stability));
+        }
+        if let Some(similarity_boost) = config.similarity_boost {
+            settings.insert("similarity_boost".to_string(), json!(similarity_boost));
+        }
+
+        Self {
+            http: Client::new(),
+            api_key,
+            voice_id: config.voice_id.clone(),
+            model_id: config.model_id.clone(),
+            voice_settings: settings,
+        }
+    }
+
+    async fn request(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        let url = format!(
+            "https://api.elevenlabs.io/v1/text-to-speech/{}/with-timestamps?output_format={}",
+            self.voice_id, OUTPUT_FORMAT
+        );
+        let mut body = json!({
+            "text": text,
+            "model_id": self.model_id,
+        });
+        let mut settings = self.voice_settings.clone();
+        if prosody.rate != 1.0 {
+            // The API accepts 0.7-1.2
+            settings.insert("speed".to_string(), json!(prosody.rate.clamp(0.7, 1.2)));
+        }
+        if prosody.style.is_some() {
+            // Style exaggeration; there are no named emotions
+            settings.insert("style".to_string(), json!(prosody.intensity));
+        }
+        if !settings.is_empty() {
+            body["voice_settings"] = Value::Object(settings);
+        }
+
+        let resp = self
+            .http
+            .post(url)
+            .header("xi-api-key", &self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(map_error(status, &body, &self.voice_id));
+        }
+
+        let response: TimestampedAudio = resp.json().await?;
+        let pcm = BASE64
+            .decode(&response.audio_base64)
+            .context("ElevenLabs returned invalid base64 audio")?;
+        let mut audio = wav_from_pcm16(&pcm, SAMPLE_RATE);
+        scale_wav_volume(&mut audio, prosody.volume);
+
+        let visemes = response.alignment.map(|alignment| {
+            let chars: Vec<char> = alignment
+                .characters
+                .iter()
+                .map(|c| c.chars().next().unwrap_or(' '))
+                .collect();
+            visemes_from_timing(&chars, &alignment.character_start_times_seconds)
+        });
+        Ok(Synthesis { audio, visemes })
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for ElevenLabsSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        Ok(self.request(text, prosody).await?.audio)
+    }
+
+    async fn synthesize_timed(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        self.request(text, prosody).await
+    }
+
+    fn supports_phonemes(&self) -> bool {
+        PHONEME_MODELS.contains(&self.model_id.as_str())
+    }
+}
+
+/// Turn an API error into a message that says what to fix
+fn map_error(status: StatusCode, body: &str, voice_id: &str) -> anyhow::Error {
+    // Errors look like {"detail": {"status": "...", "message": "..."}} or {"detail": "..."}
+    let detail = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            let detail = v.get("detail")?;
+            detail
+                .get("message")
+                .and_then(|m| m.as_str())
+                .or_else(|| detail.as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().to_string());
+
+    match status {
+        StatusCode::UNAUTHORIZED => anyhow!("ElevenLabs rejected the API key: {}", detail),
+        StatusCode::NOT_FOUND => anyhow!("ElevenLabs voice '{}' not found: {}", voice_id, detail),
+        StatusCode::UNPROCESSABLE_ENTITY => anyhow!("ElevenLabs rejected the request: {}", detail),
+        StatusCode::TOO_MANY_REQUESTS => {
+            anyhow!("ElevenLabs rate limit or quota exceeded: {}", detail)
+        }
+        _ => anyhow!("ElevenLabs error {}: {}", status, detail),
+    }
+}
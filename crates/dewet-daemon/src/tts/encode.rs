@@ -0,0 +1,86 @@
+//! Compressed audio for bridge transport
+//!
+//! Synthesized WAV is large once base64-encoded, which makes the WebSocket
+//! hitch. With `bridge_format = "mp3"` (feature `mp3`) audio is re-encoded
+//! before it is sent; anything that can't be encoded is sent as WAV.
+
+use tracing::warn;
+
+use crate::config::AudioFormat;
+
+/// Encode synthesized WAV for the bridge, returning the bytes and the format
+/// they are actually in
+pub fn encode_for_bridge(wav: Vec<u8>, format: AudioFormat) -> (Vec<u8>, AudioFormat) {
+    match format {
+        AudioFormat::Wav => (wav, AudioFormat::Wav),
+        AudioFormat::Mp3 => match mp3::encode(&wav) {
+            Ok(mp3) => (mp3, AudioFormat::Mp3),
+            Err(err) => {
+                warn!(?err, "MP3 encoding failed; sending WAV");
+                (wav, AudioFormat::Wav)
+            }
+        },
+    }
+}
+
+#[cfg(feature = "mp3")]
+mod mp3 {
+    use anyhow::{Result, anyhow};
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, MonoPcm, Quality};
+
+    use crate::tts::parse_pcm16_wav;
+
+    /// Plenty for mono speech
+    const BITRATE: Bitrate = Bitrate::Kbps48;
+
+    pub fn encode(wav: &[u8]) -> Result<Vec<u8>> {
+        let info = parse_pcm16_wav(wav).ok_or_else(|| anyhow!("Not a 16-bit PCM WAV"))?;
+        if info.channels != 1 {
+            return Err(anyhow!(
+                "Expected mono audio, got {} channels",
+                info.channels
+            ));
+        }
+        let samples: Vec<i16> = wav[info.data]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+
+        let mut builder = Builder::new().ok_or_else(|| anyhow!("Failed to create LAME encoder"))?;
+        builder
+            .set_num_channels(1)
+            .map_err(|e| anyhow!("LAME channels: {:?}", e))?;
+        builder
+            .set_sample_rate(info.sample_rate)
+            .map_err(|e| anyhow!("LAME sample rate {}: {:?}", info.sample_rate, e))?;
+        builder
+            .set_brate(BITRATE)
+            .map_err(|e| anyhow!("LAME bitrate: {:?}", e))?;
+        builder
+            .set_quality(Quality::Good)
+            .map_err(|e| anyhow!("LAME quality: {:?}", e))?;
+        let mut encoder = builder
+            .build()
+            .map_err(|e| anyhow!("Failed to initialise LAME: {:?}", e))?;
+
+        let mut mp3 = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(samples.len()));
+        encoder
+            .encode_to_vec(MonoPcm(&samples), &mut mp3)
+            .map_err(|e| anyhow!("MP3 encoding failed: {:?}", e))?;
+        encoder
+            .flush_to_vec::<FlushNoGap>(&mut mp3)
+            .map_err(|e| anyhow!("MP3 flush failed: {:?}", e))?;
+        Ok(mp3)
+    }
+}
+
+#[cfg(not(feature = "mp3"))]
+mod mp3 {
+    use anyhow::{Result, anyhow};
+
+    pub fn encode(_wav: &[u8]) -> Result<Vec<u8>> {
+        Err(anyhow!(
+            "MP3 output requires building with the `mp3` feature"
+        ))
+    }
+}
@@ -0,0 +1,191 @@
+//! In-process Kokoro neural TTS (feature `kokoro`)
+//!
+//! Runs the Kokoro-82M ONNX export through onnxruntime, so speech works offline
+//! without a separate server. Expects the layout of
+//! `onnx-community/Kokoro-82M-v1.0-ONNX`: `onnx/model.onnx`, `tokenizer.json`,
+//! and raw f32 style tables in `voices/<voice>.bin`. Text is phonemized with
+//! the `espeak-ng` CLI, except words the lexicon gives IPA for.
+
+use std::{collections::HashMap, fs, path::Path, process::Command, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use ort::{session::Session, value::Tensor};
+use serde_json::Value;
+
+use super::{
+    Prosody, SpeechSynthesizer,
+    lexicon::{Segment, segments},
+    prosody::scale_wav_volume,
+    wav_from_pcm16,
+};
+use crate::config::KokoroConfig;
+
+/// Kokoro always generates 24 kHz audio
+const MODEL_SAMPLE_RATE: u32 = 24_000;
+const STYLE_DIM: usize = 256;
+/// Context is 512 tokens including the start/end pad
+const MAX_PHONEMES: usize = 510;
+
+pub struct KokoroSynth {
+    model: Arc<KokoroModel>,
+}
+
+struct KokoroModel {
+    session: Session,
+    vocab: HashMap<char, i64>,
+    /// One style vector per phoneme count, flattened
+    styles: Vec<f32>,
+    espeak_binary: String,
+    espeak_voice: String,
+    speed: f32,
+    sample_rate: u32,
+}
+
+impl KokoroSynth {
+    pub fn new(config: &KokoroConfig) -> Result<Self> {
+        Ok(Self {
+            model: Arc::new(KokoroModel::load(config)?),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for KokoroSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        let model = self.model.clone();
+        let text = text.to_string();
+        let speed = self.model.speed * prosody.rate;
+        let mut audio = tokio::task::spawn_blocking(move || model.generate(&text, speed)).await??;
+        scale_wav_volume(&mut audio, prosody.volume);
+        Ok(audio)
+    }
+
+    fn supports_phonemes(&self) -> bool {
+        true
+    }
+}
+
+impl KokoroModel {
+    fn load(config: &KokoroConfig) -> Result<Self> {
+        let dir = Path::new(&config.model_dir);
+        let session = Session::builder()?
+            .commit_from_file(dir.join("onnx/model.onnx"))
+            .with_context(|| format!("Failed to load Kokoro model from {:?}", dir))?;
+
+        Ok(Self {
+            session,
+            vocab: load_vocab(&dir.join("tokenizer.json"))?,
+            styles: load_styles(&dir.join("voices").join(format!("{}.bin", config.voice)))?,
+            espeak_binary: config.espeak_binary.clone(),
+            espeak_voice: config.espeak_voice.clone(),
+            speed: config.speed,
+            sample_rate: config.sample_rate,
+        })
+    }
+
+    fn phonemize(&self, text: &str) -> Result<String> {
+        let output = Command::new(&self.espeak_binary)
+            .args(["-q", "--ipa", "-v", &self.espeak_voice])
+            .arg(text)
+            .output()
+            .with_context(|| format!("Failed to run {}", self.espeak_binary))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{} failed: {}",
+                self.espeak_binary,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let ipa = String::from_utf8_lossy(&output.stdout);
+        Ok(ipa.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    fn generate(&self, text: &str, speed: f32) -> Result<Vec<u8>> {
+        let mut parts = Vec::new();
+        for segment in segments(text) {
+            match segment {
+                Segment::Text(text) if text.trim().is_empty() => {}
+                Segment::Text(text) => parts.push(self.phonemize(text)?),
+                Segment::Phoneme { ipa, .. } => parts.push(ipa.to_string()),
+            }
+        }
+        let phonemes = parts.join(" ");
+        let mut tokens: Vec<i64> = phonemes
+            .chars()
+            .filter_map(|c| self.vocab.get(&c).copied())
+            .take(MAX_PHONEMES)
+            .collect();
+        let style_index = tokens.len().min(self.styles.len() / STYLE_DIM - 1);
+        let style = self.styles[style_index * STYLE_DIM..(style_index + 1) * STYLE_DIM].to_vec();
+        tokens.insert(0, 0);
+        tokens.push(0);
+
+        let token_count = tokens.len();
+        let samples: Vec<f32> = {
+            let outputs = self.session.run(ort::inputs![
+                "input_ids" => Tensor::from_array(([1usize, token_count], tokens))?,
+                "style" => Tensor::from_array(([1usize, STYLE_DIM], style))?,
+                "speed" => Tensor::from_array(([1usize], vec![speed]))?,
+            ]?)?;
+            let (_, waveform) = outputs["waveform"].try_extract_raw_tensor::<f32>()?;
+            waveform.to_vec()
+        };
+
+        let samples = resample(&samples, MODEL_SAMPLE_RATE, self.sample_rate);
+        let pcm: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect();
+        Ok(wav_from_pcm16(&pcm, self.sample_rate))
+    }
+}
+
+/// Phoneme -> token id map from the Hugging Face tokenizer
+fn load_vocab(path: &Path) -> Result<HashMap<char, i64>> {
+    let tokenizer: Value = serde_json::from_str(
+        &fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?,
+    )?;
+    let vocab = tokenizer
+        .pointer("/model/vocab")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| anyhow!("{:?} has no model.vocab", path))?;
+    Ok(vocab
+        .iter()
+        .filter_map(|(token, id)| {
+            let mut chars = token.chars();
+            match (chars.next(), chars.next(), id.as_i64()) {
+                (Some(c), None, Some(id)) => Some((c, id)),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+fn load_styles(path: &Path) -> Result<Vec<f32>> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read voice {:?}", path))?;
+    if bytes.is_empty() || bytes.len() % (STYLE_DIM * 4) != 0 {
+        return Err(anyhow!("{:?} is not a Kokoro style table", path));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+/// Linear resampling; good enough for speech
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let ratio = from as f64 / to as f64;
+    let len = (samples.len() as f64 / ratio) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let next = samples.get(index + 1).copied().unwrap_or(samples[index]);
+            let fraction = (position - index as f64) as f32;
+            samples[index] + (next - samples[index]) * fraction
+        })
+        .collect()
+}
@@ -0,0 +1,331 @@
+//! Pronunciation lexicon
+//!
+//! A user-editable TOML file says how to pronounce words the voices get
+//! wrong, with a table per language:
+//!
+//! ```toml
+//! [en]
+//! kubectl = "cube control"
+//! Dewet = { ipa = "ˈdjuːɪt", respelling = "dew it" }
+//! ```
+//!
+//! Words are matched whole and ignoring case. Providers that understand SSML
+//! phonemes get IPA as inline `<phoneme alphabet="ipa" ph="...">` markup; the
+//! rest get the respelling. The file is reread when it changes.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result, anyhow};
+use parking_lot::Mutex;
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::{Prosody, SharedSynth, SpeechSynthesizer, Synthesis};
+use crate::config::LexiconConfig;
+
+/// Inline phoneme markup as produced by `Lexicon::apply`
+static PHONEME_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<phoneme alphabet="ipa" ph="([^"<]*)">([^<]*)</phoneme>"#).unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Pronunciation {
+    #[serde(default)]
+    ipa: Option<String>,
+    #[serde(default)]
+    respelling: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Respelling(String),
+    Full(Pronunciation),
+}
+
+pub struct Lexicon {
+    path: PathBuf,
+    language: String,
+    state: Mutex<State>,
+}
+
+struct State {
+    modified: Option<SystemTime>,
+    entries: Arc<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    pattern: Option<Regex>,
+    /// Keyed by lowercased word
+    words: HashMap<String, Pronunciation>,
+}
+
+impl Lexicon {
+    pub fn load(config: &LexiconConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let entries = read_entries(&path, &config.language)?;
+        info!(
+            path = %config.path,
+            language = %config.language,
+            words = entries.words.len(),
+            "Pronunciation lexicon loaded"
+        );
+        Ok(Self {
+            path,
+            language: config.language.clone(),
+            state: Mutex::new(State {
+                modified,
+                entries: Arc::new(entries),
+            }),
+        })
+    }
+
+    /// `text` with the lexicon's pronunciations, as phoneme markup where
+    /// `phonemes` is set and an entry has IPA
+    pub fn apply(&self, text: &str, phonemes: bool) -> String {
+        self.entries().apply(text, phonemes)
+    }
+
+    /// The current entries, rereading the file if it changed. A file that no
+    /// longer parses keeps the previous entries.
+    fn entries(&self) -> Arc<Entries> {
+        let mut state = self.state.lock();
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified != state.modified {
+            state.modified = modified;
+            match read_entries(&self.path, &self.language) {
+                Ok(entries) => {
+                    info!(
+                        words = entries.words.len(),
+                        "Pronunciation lexicon reloaded"
+                    );
+                    state.entries = Arc::new(entries);
+                }
+                Err(err) => warn!(?err, "Failed to reload pronunciation lexicon"),
+            }
+        }
+        state.entries.clone()
+    }
+}
+
+fn read_entries(path: &Path, language: &str) -> Result<Entries> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read lexicon {:?}", path))?;
+    Entries::parse(&source, language).with_context(|| format!("Invalid lexicon {:?}", path))
+}
+
+impl Entries {
+    fn parse(source: &str, language: &str) -> Result<Self> {
+        let mut tables: HashMap<String, HashMap<String, Entry>> = toml::from_str(source)?;
+        let primary = language.split(['-', '_']).next().unwrap_or(language);
+        let Some(table) = tables.remove(language).or_else(|| tables.remove(primary)) else {
+            warn!(%language, "Lexicon has no table for this language");
+            return Ok(Self::default());
+        };
+
+        let mut words = HashMap::new();
+        for (word, entry) in table {
+            let mut pronunciation = match entry {
+                Entry::Respelling(respelling) => Pronunciation {
+                    ipa: None,
+                    respelling: Some(respelling),
+                },
+                Entry::Full(pronunciation) => pronunciation,
+            };
+            // Would break out of the markup's attribute
+            if pronunciation
+                .ipa
+                .as_ref()
+                .is_some_and(|ipa| ipa.contains(['"', '<']))
+            {
+                warn!(%word, "Ignoring IPA with quotes or '<'");
+                pronunciation.ipa = None;
+            }
+            let word = word.trim().to_lowercase();
+            if word.is_empty() {
+                continue;
+            }
+            if pronunciation.ipa.is_none() && pronunciation.respelling.is_none() {
+                return Err(anyhow!("{:?} has neither ipa nor respelling", word));
+            }
+            words.insert(word, pronunciation);
+        }
+        if words.is_empty() {
+            return Ok(Self::default());
+        }
+
+        // Longest first, so phrases win over the words in them
+        let mut keys: Vec<&String> = words.keys().collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.chars().count()));
+        let alternatives: Vec<String> = keys
+            .iter()
+            .map(|word| {
+                // \b only applies next to word characters ("C++" ends in '+')
+                let edge = |c: Option<char>| {
+                    if c.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                        r"\b"
+                    } else {
+                        ""
+                    }
+                };
+                format!(
+                    "{}{}{}",
+                    edge(word.chars().next()),
+                    regex::escape(word),
+                    edge(word.chars().last())
+                )
+            })
+            .collect();
+        let pattern = Regex::new(&format!("(?i)(?:{})", alternatives.join("|")))?;
+        Ok(Self {
+            pattern: Some(pattern),
+            words,
+        })
+    }
+
+    fn apply(&self, text: &str, phonemes: bool) -> String {
+        let Some(pattern) = &self.pattern else {
+            return text.to_string();
+        };
+        pattern
+            .replace_all(text, |caps: &regex::Captures| {
+                let matched = &caps[0];
+                let Some(pronunciation) = self.words.get(&matched.to_lowercase()) else {
+                    return matched.to_string();
+                };
+                match (&pronunciation.ipa, &pronunciation.respelling) {
+                    (Some(ipa), _) if phonemes => {
+                        format!(
+                            "<phoneme alphabet=\"ipa\" ph=\"{}\">{}</phoneme>",
+                            ipa, matched
+                        )
+                    }
+                    (_, Some(respelling)) => respelling.clone(),
+                    _ => matched.to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// A piece of lexicon-applied text
+#[derive(Debug, PartialEq)]
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Phoneme { ipa: &'a str, word: &'a str },
+}
+
+/// Split text into plain runs and phoneme markup, for providers that handle
+/// the markup themselves
+pub(crate) fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut last = 0;
+    for caps in PHONEME_TAG.captures_iter(text) {
+        let tag = caps.get(0).unwrap();
+        if tag.start() > last {
+            segments.push(Segment::Text(&text[last..tag.start()]));
+        }
+        segments.push(Segment::Phoneme {
+            ipa: caps.get(1).unwrap().as_str(),
+            word: caps.get(2).unwrap().as_str(),
+        });
+        last = tag.end();
+    }
+    if last < text.len() {
+        segments.push(Segment::Text(&text[last..]));
+    }
+    segments
+}
+
+/// Applies the lexicon to text before it reaches the provider
+pub struct LexiconSynth {
+    inner: SharedSynth,
+    lexicon: Arc<Lexicon>,
+}
+
+impl LexiconSynth {
+    pub fn new(inner: SharedSynth, lexicon: Arc<Lexicon>) -> Self {
+        Self { inner, lexicon }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for LexiconSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        let text = self.lexicon.apply(text, self.inner.supports_phonemes());
+        self.inner.synthesize(&text, prosody).await
+    }
+
+    async fn synthesize_timed(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        let text = self.lexicon.apply(text, self.inner.supports_phonemes());
+        self.inner.synthesize_timed(&text, prosody).await
+    }
+
+    fn supports_pitch(&self) -> bool {
+        self.inner.supports_pitch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEXICON: &str = r#"
+        [en]
+        kubectl = "cube control"
+        Dewet = { ipa = "ˈdjuːɪt", respelling = "dew it" }
+        "C++" = "C plus plus"
+
+        [de]
+        Dewet = "Djuit"
+    "#;
+
+    #[test]
+    fn test_apply_respellings() {
+        let entries = Entries::parse(LEXICON, "en-US").unwrap();
+        assert_eq!(
+            entries.apply("Run KUBECTL before dewet's C++ build", false),
+            "Run cube control before dew it's C plus plus build"
+        );
+        // Whole words only
+        assert_eq!(entries.apply("kubectlx", false), "kubectlx");
+    }
+
+    #[test]
+    fn test_apply_phonemes() {
+        let entries = Entries::parse(LEXICON, "en").unwrap();
+        let text = entries.apply("Hi Dewet & kubectl", true);
+        assert_eq!(
+            text,
+            "Hi <phoneme alphabet=\"ipa\" ph=\"ˈdjuːɪt\">Dewet</phoneme> & cube control"
+        );
+        assert_eq!(
+            segments(&text),
+            vec![
+                Segment::Text("Hi "),
+                Segment::Phoneme {
+                    ipa: "ˈdjuːɪt",
+                    word: "Dewet"
+                },
+                Segment::Text(" & cube control"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_language_tables() {
+        let entries = Entries::parse(LEXICON, "de").unwrap();
+        assert_eq!(entries.apply("Dewet, kubectl", true), "Djuit, kubectl");
+        let entries = Entries::parse(LEXICON, "fr").unwrap();
+        assert_eq!(entries.apply("Dewet", false), "Dewet");
+    }
+}
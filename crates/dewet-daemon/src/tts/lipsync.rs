@@ -0,0 +1,147 @@
+//! Mouth-shape timing for puppet lip sync
+//!
+//! Cues use the Rhubarb Lip Sync mouth shapes, which most 2D rigs already
+//! have drawings for:
+//!
+//! | Shape | Mouth | Sounds |
+//! |-------|-------|--------|
+//! | A | closed | M, B, P |
+//! | B | slightly open, teeth together | most consonants |
+//! | C | open | EH, AE, IY |
+//! | D | wide open | AA |
+//! | E | slightly rounded | AO, ER |
+//! | F | puckered | UW, OW, W |
+//! | G | teeth on lip | F, V |
+//! | H | tongue raised | L |
+//! | X | idle | pauses |
+//!
+//! Providers that report character timing (ElevenLabs) give accurate cues;
+//! for the rest, cues are estimated by spreading the text over the audio.
+//! Both map letters rather than phonemes, which is close enough for a puppet.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{Synthesis, wav_duration};
+
+/// Mouth shape starting at `time` seconds into the clip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisemeCue {
+    pub time: f32,
+    pub viseme: String,
+}
+
+/// Cues from per-character timing reported by a provider
+pub fn visemes_from_timing(chars: &[char], start_times: &[f32]) -> Vec<VisemeCue> {
+    collapse(
+        chars
+            .iter()
+            .zip(start_times)
+            .map(|(&c, &time)| (time, viseme_for(c))),
+    )
+}
+
+/// Cues spread evenly over `duration` seconds, for providers without timing.
+/// SSML markup is skipped.
+pub fn estimate_visemes(text: &str, duration: f32) -> Vec<VisemeCue> {
+    let mut in_tag = false;
+    let chars: Vec<char> = text
+        .trim()
+        .chars()
+        .filter(|&c| match c {
+            '<' => {
+                in_tag = true;
+                false
+            }
+            '>' => {
+                in_tag = false;
+                false
+            }
+            _ => !in_tag,
+        })
+        .collect();
+    if chars.is_empty() || duration <= 0.0 {
+        return Vec::new();
+    }
+    let step = duration / chars.len() as f32;
+    let mut cues = collapse(
+        chars
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (i as f32 * step, viseme_for(c))),
+    );
+    // Close the mouth at the end of the clip
+    if cues.last().is_some_and(|cue| cue.viseme != "X") {
+        cues.push(VisemeCue {
+            time: duration,
+            viseme: "X".into(),
+        });
+    }
+    cues
+}
+
+/// The `lip_sync` entry of a puppet payload for synthesized `text`: the
+/// provider's timing if it reported any, otherwise an estimate. None if the
+/// audio isn't a WAV whose length can be read.
+pub fn lip_sync(text: &str, synthesis: &Synthesis) -> Option<Value> {
+    if let Some(cues) = &synthesis.visemes {
+        return Some(json!({ "source": "provider", "visemes": cues }));
+    }
+    let duration = wav_duration(&synthesis.audio)?.as_secs_f32();
+    let cues = estimate_visemes(text, duration);
+    Some(json!({ "source": "estimated", "visemes": cues }))
+}
+
+/// Drop cues that don't change the mouth shape
+fn collapse(cues: impl Iterator<Item = (f32, &'static str)>) -> Vec<VisemeCue> {
+    let mut collapsed: Vec<VisemeCue> = Vec::new();
+    for (time, viseme) in cues {
+        if collapsed.last().is_none_or(|last| last.viseme != viseme) {
+            collapsed.push(VisemeCue {
+                time: (time * 1000.0).round() / 1000.0,
+                viseme: viseme.to_string(),
+            });
+        }
+    }
+    collapsed
+}
+
+fn viseme_for(c: char) -> &'static str {
+    match c.to_ascii_lowercase() {
+        'm' | 'b' | 'p' => "A",
+        'a' => "D",
+        'e' | 'i' | 'y' => "C",
+        'o' => "E",
+        'u' | 'w' | 'q' => "F",
+        'f' | 'v' => "G",
+        'l' => "H",
+        c if c.is_alphanumeric() => "B",
+        _ => "X",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_visemes() {
+        let cues = estimate_visemes("mama", 1.0);
+        let shapes: Vec<&str> = cues.iter().map(|c| c.viseme.as_str()).collect();
+        assert_eq!(shapes, vec!["A", "D", "A", "D", "X"]);
+        assert_eq!(cues[1].time, 0.25);
+        assert_eq!(cues.last().unwrap().time, 1.0);
+        assert!(estimate_visemes("", 1.0).is_empty());
+        assert_eq!(
+            estimate_visemes("<speak>mama</speak>", 1.0),
+            estimate_visemes("mama", 1.0)
+        );
+    }
+
+    #[test]
+    fn test_visemes_from_timing_collapses_repeats() {
+        let cues = visemes_from_timing(&['h', 'i', 'i', ' '], &[0.0, 0.1, 0.15, 0.2]);
+        let shapes: Vec<&str> = cues.iter().map(|c| c.viseme.as_str()).collect();
+        assert_eq!(shapes, vec!["B", "C", "X"]);
+    }
+}
@@ -1,39 +1,311 @@
-use std::sync::Arc;
+mod azure;
+mod cache;
+mod elevenlabs;
+mod encode;
+#[cfg(feature = "kokoro")]
+mod kokoro;
+mod lexicon;
+mod lipsync;
+mod piper;
+mod playback;
+mod prosody;
+
+use std::{collections::HashMap, ops::Range, sync::Arc, time::Duration};
 
 use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::config::{TtsConfig, VoiceTuning};
 
-use crate::config::TtsConfig;
+pub use azure::AzureSpeechSynth;
+pub use cache::{AudioCache, CacheStats, CachedSynth};
+pub use elevenlabs::ElevenLabsSynth;
+pub use encode::encode_for_bridge;
+#[cfg(feature = "kokoro")]
+pub use kokoro::KokoroSynth;
+pub use lexicon::{Lexicon, LexiconSynth};
+pub use lipsync::{VisemeCue, lip_sync};
+pub use piper::PiperSynth;
+pub use playback::Playback;
+pub use prosody::{Prosody, TunedSynth};
 
 pub type SharedSynth = Arc<dyn SpeechSynthesizer>;
 
+/// Providers that do blocking work (subprocesses, local inference) run it on
+/// the blocking pool so synthesis never ties up a runtime worker. `prosody` is
+/// applied as far as the provider supports it.
+#[async_trait::async_trait]
 pub trait SpeechSynthesizer: Send + Sync {
-    fn synthesize(&self, text: &str) -> Result<Vec<u8>>;
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>>;
+
+    /// Audio plus mouth-shape timing, for providers that report it
+    async fn synthesize_timed(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        Ok(Synthesis {
+            audio: self.synthesize(text, prosody).await?,
+            visemes: None,
+        })
+    }
+
+    /// Whether `Prosody::pitch` is applied natively; otherwise `TunedSynth`
+    /// emulates it
+    fn supports_pitch(&self) -> bool {
+        false
+    }
+
+    /// Whether inline SSML `<phoneme>` markup is understood; otherwise the
+    /// lexicon's respellings are used
+    fn supports_phonemes(&self) -> bool {
+        false
+    }
+}
+
+/// Synthesized WAV audio with the provider's viseme timing, if any
+pub struct Synthesis {
+    pub audio: Vec<u8>,
+    pub visemes: Option<Vec<VisemeCue>>,
+}
+
+/// The synthesizer for each speaking character, from `[tts.voices]` and the
+/// characters' voice tuning
+pub struct VoiceMap {
+    default: SharedSynth,
+    characters: HashMap<String, SharedSynth>,
+    cache: Option<Arc<AudioCache>>,
+}
+
+impl VoiceMap {
+    /// `tunings` holds each character's tuning from their card; settings in
+    /// `[tts.voices]` take precedence
+    pub fn from_config(config: &TtsConfig, tunings: &HashMap<String, VoiceTuning>) -> Self {
+        let cache = match &config.cache {
+            Some(cache_config) => match AudioCache::open(cache_config) {
+                Ok(cache) => Some(Arc::new(cache)),
+                Err(err) => {
+                    warn!(?err, "Failed to open TTS cache; caching disabled");
+                    None
+                }
+            },
+            None => None,
+        };
+        let lexicon = match &config.lexicon {
+            Some(lexicon_config) => match Lexicon::load(lexicon_config) {
+                Ok(lexicon) => Some(Arc::new(lexicon)),
+                Err(err) => {
+                    warn!(?err, "Failed to load pronunciation lexicon; ignoring it");
+                    None
+                }
+            },
+            None => None,
+        };
+        let build = |voiced: &TtsConfig| -> SharedSynth {
+            let synth = create_synthesizer(voiced);
+            let synth: SharedSynth = match &cache {
+                // Silence is cheaper to generate than to read back
+                Some(cache) if voiced.provider != "null" => Arc::new(CachedSynth::new(
+                    synth,
+                    cache.clone(),
+                    voiced.voice_fingerprint(),
+                )),
+                _ => synth,
+            };
+            // Outside the cache, so lexicon edits aren't masked by cached audio
+            match &lexicon {
+                Some(lexicon) => Arc::new(LexiconSynth::new(synth, lexicon.clone())),
+                None => synth,
+            }
+        };
+
+        let default_synth = build(config);
+        let default: SharedSynth = Arc::new(TunedSynth::new(
+            default_synth.clone(),
+            VoiceTuning::default(),
+        ));
+        // Characters sharing a voice share one synthesizer, so local models are
+        // only loaded once
+        let mut shared: HashMap<String, SharedSynth> = HashMap::new();
+        let mut characters: HashMap<String, SharedSynth> = HashMap::new();
+        for (character_id, selection) in &config.voices {
+            let voiced = config.with_voice(selection);
+            let synth = shared
+                .entry(voiced.voice_fingerprint())
+                .or_insert_with(|| build(&voiced))
+                .clone();
+            let tuning = selection
+                .tuning()
+                .or(tunings.get(character_id).copied().unwrap_or_default());
+            info!(
+                character = %character_id,
+                provider = %voiced.provider,
+                voice = ?selection.voice(),
+                ?tuning,
+                "Character voice configured"
+            );
+            characters.insert(
+                character_id.clone(),
+                Arc::new(TunedSynth::new(synth, tuning)),
+            );
+        }
+        // Characters with only card tuning keep the default voice
+        for (character_id, tuning) in tunings {
+            if characters.contains_key(character_id) || tuning.is_neutral() {
+                continue;
+            }
+            info!(character = %character_id, ?tuning, "Character voice tuned");
+            characters.insert(
+                character_id.clone(),
+                Arc::new(TunedSynth::new(default_synth.clone(), *tuning)),
+            );
+        }
+        Self {
+            default,
+            characters,
+            cache,
+        }
+    }
+
+    pub fn for_character(&self, character_id: &str) -> &SharedSynth {
+        self.characters.get(character_id).unwrap_or(&self.default)
+    }
+
+    /// The audio cache, if enabled, for reporting stats
+    pub fn cache(&self) -> Option<Arc<AudioCache>> {
+        self.cache.clone()
+    }
 }
 
 pub fn create_synthesizer(config: &TtsConfig) -> SharedSynth {
     match config.provider.as_str() {
+        "piper" => match &config.piper {
+            Some(piper) => Arc::new(PiperSynth::new(piper)),
+            None => {
+                warn!("tts.provider is \"piper\" but [tts.piper] is missing; using silence");
+                Arc::new(NullSynth)
+            }
+        },
+        "elevenlabs" => match &config.elevenlabs {
+            Some(elevenlabs) => match elevenlabs.api_key() {
+                Some(api_key) => Arc::new(ElevenLabsSynth::new(elevenlabs, api_key)),
+                None => {
+                    warn!(
+                        env = %elevenlabs.api_key_env,
+                        "No ElevenLabs API key set; using silence"
+                    );
+                    Arc::new(NullSynth)
+                }
+            },
+            None => {
+                warn!("tts.provider is \"elevenlabs\" but [tts.elevenlabs] is missing; using silence");
+                Arc::new(NullSynth)
+            }
+        },
+        "azure" => match &config.azure {
+            Some(azure) => match azure.api_key() {
+                Some(api_key) => Arc::new(AzureSpeechSynth::new(azure, api_key)),
+                None => {
+                    warn!(env = %azure.api_key_env, "No Azure Speech key set; using silence");
+                    Arc::new(NullSynth)
+                }
+            },
+            None => {
+                warn!("tts.provider is \"azure\" but [tts.azure] is missing; using silence");
+                Arc::new(NullSynth)
+            }
+        },
+        #[cfg(feature = "kokoro")]
+        "kokoro" => match &config.kokoro {
+            Some(kokoro) => match KokoroSynth::new(kokoro) {
+                Ok(synth) => Arc::new(synth),
+                Err(err) => {
+                    warn!(?err, "Failed to load Kokoro TTS; using silence");
+                    Arc::new(NullSynth)
+                }
+            },
+            None => {
+                warn!("tts.provider is \"kokoro\" but [tts.kokoro] is missing; using silence");
+                Arc::new(NullSynth)
+            }
+        },
+        #[cfg(not(feature = "kokoro"))]
+        "kokoro" => {
+            warn!("Kokoro TTS requires building with the `kokoro` feature; using silence");
+            Arc::new(NullSynth)
+        }
         _ => Arc::new(NullSynth),
     }
 }
 
+/// Fragments shorter than this are merged into the following sentence so the
+/// backend isn't called for a lone "Oh."
+const MIN_SENTENCE_CHARS: usize = 12;
+
+/// Split a reply into sentences for incremental synthesis. SSML documents are
+/// kept whole since splitting them would break the markup.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    if text.starts_with("<speak") {
+        return vec![text.to_string()];
+    }
+
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let boundary = match c {
+            '\n' => true,
+            '.' | '!' | '?' | '…' => {
+                // Keep repeated punctuation and closing quotes with the sentence
+                while let Some(&next) = chars.peek() {
+                    if !matches!(next, '.' | '!' | '?' | '"' | '\'' | ')' | '”' | '’') {
+                        break;
+                    }
+                    current.push(next);
+                    chars.next();
+                }
+                chars.peek().is_none_or(|next| next.is_whitespace())
+            }
+            _ => false,
+        };
+        if boundary && current.trim().chars().count() >= MIN_SENTENCE_CHARS {
+            sentences.push(current.trim().to_string());
+            current.clear();
+        }
+    }
+    if !current.trim().is_empty() {
+        sentences.push(current.trim().to_string());
+    }
+    sentences
+}
+
 struct NullSynth;
 
+#[async_trait::async_trait]
 impl SpeechSynthesizer for NullSynth {
-    fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
-        let seconds = (text.len() as f32 / 14.0).clamp(0.5, 3.0);
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        let seconds = estimate_duration(text, prosody)
+            .as_secs_f32()
+            .clamp(0.5, 3.0);
         Ok(render_silence(seconds))
     }
 }
 
 fn render_silence(duration_secs: f32) -> Vec<u8> {
     let sample_rate = 16_000u32;
+    let total_samples = (sample_rate as f32 * duration_secs) as usize;
+    wav_from_pcm16(&vec![0u8; total_samples * 2], sample_rate)
+}
+
+/// Wrap raw little-endian 16-bit mono PCM in a WAV header
+pub(crate) fn wav_from_pcm16(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
     let channels = 1u16;
     let bits_per_sample = 16u16;
-    let total_samples = (sample_rate as f32 * duration_secs) as u32;
     let byte_rate = sample_rate * channels as u32 * bits_per_sample as u32 / 8;
     let block_align = channels * bits_per_sample / 8;
-    let data_len = total_samples * block_align as u32;
-    let mut buffer = Vec::with_capacity(44 + data_len as usize);
+    let data_len = pcm.len() as u32;
+    let mut buffer = Vec::with_capacity(44 + pcm.len());
 
     buffer.extend_from_slice(b"RIFF");
     buffer.extend_from_slice(&(36 + data_len).to_le_bytes());
@@ -48,6 +320,140 @@ fn render_silence(duration_secs: f32) -> Vec<u8> {
     buffer.extend_from_slice(b"data");
     buffer.extend_from_slice(&data_len.to_le_bytes());
 
-    buffer.resize(44 + data_len as usize, 0u8);
+    buffer.extend_from_slice(pcm);
     buffer
 }
+
+/// Playing time of synthesized audio; None unless it is a 16-bit PCM WAV
+pub fn wav_duration(wav: &[u8]) -> Option<Duration> {
+    let info = parse_pcm16_wav(wav)?;
+    let frames = info.data.len() / (2 * info.channels.max(1) as usize);
+    Some(Duration::from_secs_f64(
+        frames as f64 / info.sample_rate.max(1) as f64,
+    ))
+}
+
+/// Rough playing time of `text` at a typical speaking pace, for audio whose
+/// length can't be read
+pub fn estimate_duration(text: &str, prosody: &Prosody) -> Duration {
+    Duration::from_secs_f32(text.chars().count() as f32 / 14.0 / prosody.rate.max(0.1))
+}
+
+/// Format and sample location of a 16-bit PCM WAV
+struct WavInfo {
+    sample_rate: u32,
+    channels: u16,
+    /// Byte range of the interleaved samples
+    data: Range<usize>,
+}
+
+/// Walk the RIFF chunks of a WAV; None unless it is 16-bit PCM
+fn parse_pcm16_wav(wav: &[u8]) -> Option<WavInfo> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut pos = 12;
+    let mut format = None;
+    while pos + 8 <= wav.len() {
+        let id = &wav[pos..pos + 4];
+        let size = u32::from_le_bytes(wav[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = pos + 8;
+        if id == b"fmt " && body + 16 <= wav.len() {
+            let channels = u16::from_le_bytes([wav[body + 2], wav[body + 3]]);
+            let sample_rate = u32::from_le_bytes(wav[body + 4..body + 8].try_into().ok()?);
+            let bits_per_sample = u16::from_le_bytes([wav[body + 14], wav[body + 15]]);
+            format = Some((sample_rate, channels, bits_per_sample));
+        } else if id == b"data" {
+            let (sample_rate, channels, bits_per_sample) = format?;
+            if bits_per_sample != 16 {
+                return None;
+            }
+            return Some(WavInfo {
+                sample_rate,
+                channels,
+                data: body..(body + size).min(wav.len()),
+            });
+        }
+        // Chunks are padded to an even size
+        pos = body + size + (size & 1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PiperConfig, VoiceSelection};
+
+    #[test]
+    fn test_wav_duration() {
+        let duration = wav_duration(&render_silence(1.5)).unwrap();
+        assert!((duration.as_secs_f32() - 1.5).abs() < 0.01);
+        assert_eq!(wav_duration(b"not a wav"), None);
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("Oh. That build failed again! Want me to look at the log?"),
+            vec![
+                "Oh. That build failed again!",
+                "Want me to look at the log?"
+            ]
+        );
+        assert_eq!(
+            split_sentences("She said \"stop right there.\" Then 3.14 appeared..."),
+            vec!["She said \"stop right there.\"", "Then 3.14 appeared..."]
+        );
+        assert_eq!(
+            split_sentences("<speak><voice name=\"x\">One. Two.</voice></speak>").len(),
+            1
+        );
+        assert!(split_sentences("  ").is_empty());
+    }
+
+    #[test]
+    fn test_with_voice_resolves_piper_voice_names() {
+        let config = TtsConfig {
+            provider: "piper".into(),
+            piper: Some(PiperConfig {
+                binary: "piper".into(),
+                model_path: "models/piper/en_US-lessac-medium.onnx".into(),
+                speaker: None,
+                length_scale: None,
+            }),
+            ..TtsConfig::default()
+        };
+
+        let voiced = config.with_voice(&VoiceSelection::Voice("en_US-amy-medium".into()));
+        assert_eq!(
+            voiced.piper.unwrap().model_path,
+            "models/piper/en_US-amy-medium.onnx"
+        );
+
+        let voiced = config.with_voice(&VoiceSelection::Voice("/voices/custom.onnx".into()));
+        assert_eq!(voiced.piper.unwrap().model_path, "/voices/custom.onnx");
+    }
+
+    #[test]
+    fn test_voice_map_falls_back_to_default() {
+        let mut config = TtsConfig::default();
+        config
+            .voices
+            .insert("lyra".into(), VoiceSelection::Voice("anything".into()));
+        let tunings = HashMap::from([(
+            "orion".to_string(),
+            VoiceTuning {
+                pitch: Some(0.9),
+                ..VoiceTuning::default()
+            },
+        )]);
+        let voices = VoiceMap::from_config(&config, &tunings);
+        assert!(voices.characters.contains_key("lyra"));
+        assert!(voices.characters.contains_key("orion"));
+        assert!(Arc::ptr_eq(
+            voices.for_character("unknown"),
+            &voices.default
+        ));
+    }
+}
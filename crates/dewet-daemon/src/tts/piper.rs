@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use super::{Prosody, SpeechSynthesizer, prosody::scale_wav_volume};
+use crate::config::PiperConfig;
+
+/// Synthesizes speech by running the Piper CLI once per utterance
+#[derive(Clone)]
+pub struct PiperSynth {
+    binary: PathBuf,
+    model_path: PathBuf,
+    speaker: Option<u32>,
+    length_scale: Option<f32>,
+}
+
+impl PiperSynth {
+    pub fn new(config: &PiperConfig) -> Self {
+        Self {
+            binary: PathBuf::from(&config.binary),
+            model_path: PathBuf::from(&config.model_path),
+            speaker: config.speaker,
+            length_scale: config.length_scale,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for PiperSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        let synth = self.clone();
+        let text = text.to_string();
+        // Piper only controls pace: length_scale is the inverse of rate
+        let length_scale = self.length_scale.unwrap_or(1.0) / prosody.rate;
+        let mut audio =
+            tokio::task::spawn_blocking(move || synth.run(&text, length_scale)).await??;
+        scale_wav_volume(&mut audio, prosody.volume);
+        Ok(audio)
+    }
+}
+
+impl PiperSynth {
+    fn run(&self, text: &str, length_scale: f32) -> Result<Vec<u8>> {
+        let output_path =
+            std::env::temp_dir().join(format!("dewet-piper-{}.wav", uuid::Uuid::new_v4()));
+
+        let mut command = Command::new(&self.binary);
+        command
+            .arg("--model")
+            .arg(&self.model_path)
+            .arg("--output_file")
+            .arg(&output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        if let Some(speaker) = self.speaker {
+            command.arg("--speaker").arg(speaker.to_string());
+        }
+        if length_scale != 1.0 {
+            command.arg("--length_scale").arg(length_scale.to_string());
+        }
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to start piper ({:?})", self.binary))?;
+        {
+            // Piper reads one utterance per line; closing stdin starts synthesis
+            let mut stdin = child
+                .stdin
+                .take()
+                .ok_or_else(|| anyhow!("piper stdin unavailable"))?;
+            let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            writeln!(stdin, "{}", line)?;
+        }
+        let output = child.wait_with_output()?;
+
+        let audio = fs::read(&output_path);
+        fs::remove_file(&output_path).ok();
+        if !output.status.success() {
+            return Err(anyhow!(
+                "piper exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        audio.with_context(|| format!("piper produced no audio at {:?}", output_path))
+    }
+}
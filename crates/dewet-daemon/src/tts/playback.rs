@@ -0,0 +1,114 @@
+//! Speech playback on the host's audio device (feature `local-audio`)
+//!
+//! Lets the daemon run headless: with `playback = "auto"` speech is played
+//! locally whenever no bridge client is connected to play it.
+
+use std::sync::mpsc;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::config::{PlaybackMode, TtsConfig};
+
+pub struct Playback {
+    mode: PlaybackMode,
+    player: Option<LocalPlayer>,
+}
+
+impl Playback {
+    pub fn from_config(config: &TtsConfig) -> Self {
+        let player = match config.playback {
+            PlaybackMode::Never => None,
+            mode => match LocalPlayer::start() {
+                Ok(player) => {
+                    info!(?mode, "Local speech playback enabled");
+                    Some(player)
+                }
+                Err(err) => {
+                    // Auto is the default, so only complain when asked for explicitly
+                    if mode == PlaybackMode::Always {
+                        warn!(?err, "Local speech playback unavailable");
+                    }
+                    None
+                }
+            },
+        };
+        Self {
+            mode: config.playback,
+            player,
+        }
+    }
+
+    /// Play `audio` locally if the mode calls for it with `clients` bridge
+    /// clients connected. Clips queue and play back-to-back.
+    pub fn offer(&self, audio: &[u8], clients: usize) {
+        let Some(player) = &self.player else {
+            return;
+        };
+        if self.mode == PlaybackMode::Always || clients == 0 {
+            player.play(audio.to_vec());
+        }
+    }
+}
+
+/// Owns the output device on its own thread, since the stream is not `Send`
+#[cfg_attr(not(feature = "local-audio"), allow(dead_code))]
+struct LocalPlayer {
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl LocalPlayer {
+    #[cfg(feature = "local-audio")]
+    fn start() -> Result<Self> {
+        use anyhow::{Context, anyhow};
+
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let (ready_tx, ready_rx) = mpsc::sync_channel::<Result<()>>(1);
+        std::thread::Builder::new()
+            .name("dewet-playback".into())
+            .spawn(move || {
+                let output = rodio::OutputStream::try_default()
+                    .map_err(|e| anyhow!("No audio output device: {}", e))
+                    .and_then(|(stream, handle)| {
+                        let sink = rodio::Sink::try_new(&handle)
+                            .map_err(|e| anyhow!("Failed to open audio output: {}", e))?;
+                        Ok((stream, sink))
+                    });
+                let (_stream, sink) = match output {
+                    Ok(output) => {
+                        let _ = ready_tx.send(Ok(()));
+                        output
+                    }
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(err));
+                        return;
+                    }
+                };
+                while let Ok(audio) = rx.recv() {
+                    match rodio::Decoder::new(std::io::Cursor::new(audio)) {
+                        Ok(source) => sink.append(source),
+                        Err(err) => warn!(?err, "Could not decode speech for playback"),
+                    }
+                }
+            })
+            .context("Failed to start playback thread")?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow!("Playback thread exited"))??;
+        Ok(Self { tx })
+    }
+
+    #[cfg(not(feature = "local-audio"))]
+    fn start() -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "Local playback requires building with the `local-audio` feature"
+        ))
+    }
+
+    fn play(&self, audio: Vec<u8>) {
+        if self.tx.send(audio).is_err() {
+            warn!("Playback thread has exited");
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Mood-driven delivery
+//!
+//! The responder's `suggested_mood` and urgency become a `Prosody`, which each
+//! provider applies as best it can: Azure as SSML `<prosody>` and
+//! `<mstts:express-as>`, ElevenLabs as voice settings, Piper and Kokoro as
+//! speaking rate. Volume is applied to the audio for providers without it.
+//!
+//! Each character's `VoiceTuning` is layered on top by `TunedSynth`, which
+//! also emulates pitch for providers that can't change it, so characters
+//! sharing a voice model still sound distinct.
+
+use anyhow::Result;
+
+use super::{SharedSynth, SpeechSynthesizer, Synthesis, parse_pcm16_wav, wav_from_pcm16};
+use crate::config::VoiceTuning;
+
+/// How an utterance should be delivered. Rate, pitch, and volume are relative:
+/// 1.0 is the voice's normal delivery.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prosody {
+    pub rate: f32,
+    pub pitch: f32,
+    pub volume: f32,
+    /// Speaking style for providers that have them (Azure), e.g. "cheerful"
+    pub style: Option<String>,
+    /// Strength of the style, 0.0-1.0
+    pub intensity: f32,
+}
+
+impl Default for Prosody {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            pitch: 1.0,
+            volume: 1.0,
+            style: None,
+            intensity: 0.5,
+        }
+    }
+}
+
+impl Prosody {
+    /// Delivery for a mood (as shown on the puppet) at the given urgency
+    pub fn for_mood(mood: Option<&str>, urgency: f32) -> Self {
+        let (rate, pitch, volume, style) = match mood.map(str::to_lowercase).as_deref() {
+            Some("happy") => (1.05, 1.05, 1.0, Some("cheerful")),
+            Some("excited") => (1.12, 1.1, 1.1, Some("excited")),
+            Some("sad") => (0.9, 0.94, 0.9, Some("sad")),
+            Some("angry" | "annoyed") => (1.05, 0.97, 1.1, Some("angry")),
+            Some("thinking") => (0.93, 1.0, 0.95, None),
+            Some("curious") => (1.0, 1.04, 1.0, Some("friendly")),
+            Some("calm") => (0.95, 0.98, 0.95, Some("calm")),
+            _ => (1.0, 1.0, 1.0, None),
+        };
+        let urgency = urgency.clamp(0.0, 1.0);
+        Self {
+            // Urgent lines come a little faster, idle musings a little slower
+            rate: rate * (1.0 + (urgency - 0.5) * 0.1),
+            pitch,
+            volume,
+            style: style.map(str::to_string),
+            intensity: urgency,
+        }
+    }
+
+    pub fn is_neutral(&self) -> bool {
+        self.rate == 1.0 && self.pitch == 1.0 && self.volume == 1.0 && self.style.is_none()
+    }
+
+    /// This delivery adjusted for a character's voice
+    pub fn tuned(&self, tuning: &VoiceTuning) -> Self {
+        Self {
+            rate: (self.rate * tuning.rate.unwrap_or(1.0)).clamp(0.5, 2.0),
+            pitch: (self.pitch * tuning.pitch.unwrap_or(1.0)).clamp(0.5, 2.0),
+            volume: (self.volume * tuning.volume.unwrap_or(1.0)).clamp(0.0, 2.0),
+            ..self.clone()
+        }
+    }
+}
+
+/// Applies a character's tuning to every utterance. Pitch is passed through to
+/// providers that support it; for the rest, audio is synthesized at an
+/// adjusted rate and resampled, which shifts pitch and restores the rate.
+pub struct TunedSynth {
+    inner: SharedSynth,
+    tuning: VoiceTuning,
+}
+
+impl TunedSynth {
+    pub fn new(inner: SharedSynth, tuning: VoiceTuning) -> Self {
+        Self { inner, tuning }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpeechSynthesizer for TunedSynth {
+    async fn synthesize(&self, text: &str, prosody: &Prosody) -> Result<Vec<u8>> {
+        Ok(self.synthesize_timed(text, prosody).await?.audio)
+    }
+
+    async fn synthesize_timed(&self, text: &str, prosody: &Prosody) -> Result<Synthesis> {
+        let prosody = prosody.tuned(&self.tuning);
+        if prosody.pitch == 1.0 || self.inner.supports_pitch() {
+            return self.inner.synthesize_timed(text, &prosody).await;
+        }
+
+        let pitch = prosody.pitch;
+        let native = Prosody {
+            rate: prosody.rate / pitch,
+            pitch: 1.0,
+            ..prosody
+        };
+        let mut synthesis = self.inner.synthesize_timed(text, &native).await?;
+        synthesis.audio = shift_wav_pitch(&synthesis.audio, pitch);
+        for cue in synthesis.visemes.iter_mut().flatten() {
+            cue.time /= pitch;
+        }
+        Ok(synthesis)
+    }
+
+    fn supports_pitch(&self) -> bool {
+        true
+    }
+}
+
+/// Relative change as an SSML percentage, e.g. 1.1 -> "+10%"
+pub(crate) fn ssml_percent(value: f32) -> String {
+    format!("{:+.0}%", (value - 1.0) * 100.0)
+}
+
+/// Scale the samples of a 16-bit PCM WAV in place. Non-PCM16 input is left
+/// unchanged.
+pub(crate) fn scale_wav_volume(wav: &mut [u8], gain: f32) {
+    if gain == 1.0 {
+        return;
+    }
+    let Some(info) = parse_pcm16_wav(wav) else {
+        return;
+    };
+    for sample in wav[info.data].chunks_exact_mut(2) {
+        let value = i16::from_le_bytes([sample[0], sample[1]]) as f32 * gain;
+        let scaled = value.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        sample.copy_from_slice(&scaled.to_le_bytes());
+    }
+}
+
+/// Raise (factor > 1) or lower the pitch of a mono 16-bit PCM WAV by
+/// resampling, which also shortens or lengthens it by the same factor. Other
+/// formats are returned unchanged.
+pub(crate) fn shift_wav_pitch(wav: &[u8], factor: f32) -> Vec<u8> {
+    let Some(info) = parse_pcm16_wav(wav) else {
+        return wav.to_vec();
+    };
+    if factor == 1.0 || factor <= 0.0 || info.channels != 1 {
+        return wav.to_vec();
+    }
+    let samples: Vec<f32> = wav[info.data]
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32)
+        .collect();
+    let Some(&last) = samples.last() else {
+        return wav.to_vec();
+    };
+
+    let out_len = (samples.len() as f32 / factor) as usize;
+    let mut pcm = Vec::with_capacity(out_len * 2);
+    for i in 0..out_len {
+        // Linear interpolation is plenty for the small shifts used here
+        let pos = i as f32 * factor;
+        let index = pos as usize;
+        let frac = pos - index as f32;
+        let a = samples.get(index).copied().unwrap_or(last);
+        let b = samples.get(index + 1).copied().unwrap_or(last);
+        let value = (a + (b - a) * frac) as i16;
+        pcm.extend_from_slice(&value.to_le_bytes());
+    }
+    wav_from_pcm16(&pcm, info.sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::wav_from_pcm16;
+
+    #[test]
+    fn test_for_mood() {
+        assert!(Prosody::for_mood(None, 0.5).is_neutral());
+        assert!(Prosody::for_mood(Some("neutral"), 0.5).is_neutral());
+
+        let excited = Prosody::for_mood(Some("Excited"), 1.0);
+        assert!(excited.rate > 1.12 && excited.pitch > 1.0);
+        assert_eq!(excited.style.as_deref(), Some("excited"));
+
+        let sad = Prosody::for_mood(Some("sad"), 0.0);
+        assert!(sad.rate < 0.9 && sad.volume < 1.0);
+        assert_eq!(ssml_percent(1.1), "+10%");
+        assert_eq!(ssml_percent(0.9), "-10%");
+    }
+
+    #[test]
+    fn test_tuned_and_pitch_shift() {
+        let tuning = VoiceTuning {
+            rate: Some(1.1),
+            pitch: Some(0.9),
+            volume: None,
+        };
+        let tuned = Prosody::default().tuned(&tuning);
+        assert_eq!((tuned.rate, tuned.pitch, tuned.volume), (1.1, 0.9, 1.0));
+
+        let pcm: Vec<u8> = (0..1000i16).flat_map(|s| s.to_le_bytes()).collect();
+        let wav = wav_from_pcm16(&pcm, 16_000);
+        let higher = shift_wav_pitch(&wav, 2.0);
+        assert_eq!(higher.len(), 44 + 1000);
+        assert_eq!(i16::from_le_bytes([higher[46], higher[47]]), 2);
+        let lower = shift_wav_pitch(&wav, 0.5);
+        assert_eq!(lower.len(), 44 + 4000);
+    }
+
+    #[test]
+    fn test_scale_wav_volume() {
+        let pcm: Vec<u8> = [1000i16, -1000, 30000]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let mut wav = wav_from_pcm16(&pcm, 16_000);
+        scale_wav_volume(&mut wav, 2.0);
+        let samples: Vec<i16> = wav[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, vec![2000, -2000, i16::MAX]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Keyboard and mouse activity, so the VLA can tell active work from an idle
+//! screen. Only counts and the cursor position are kept, never which keys.
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::privacy::to_image_rect;
+use crate::config::ScreenRegion;
+
+/// Rates are counted over this long
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Input over the last minute, as of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputActivity {
+    pub keys_per_min: u32,
+    pub clicks_per_min: u32,
+    /// Since the last key, click, scroll, or cursor move
+    pub idle_secs: u64,
+    /// Cursor in frame pixels, when it's over the captured area
+    #[serde(skip)]
+    pub cursor: Option<(u32, u32)>,
+}
+
+impl InputActivity {
+    /// Past this, the user counts as away from the keyboard
+    pub const IDLE_SECS: u64 = 60;
+
+    pub fn is_idle(&self) -> bool {
+        self.idle_secs >= Self::IDLE_SECS
+    }
+
+    /// e.g. "keys 42/min, clicks 3/min" or "idle 2m"
+    pub fn summary(&self) -> String {
+        if self.is_idle() {
+            format!("idle {}m", self.idle_secs / 60)
+        } else {
+            format!(
+                "keys {}/min, clicks {}/min",
+                self.keys_per_min, self.clicks_per_min
+            )
+        }
+    }
+}
+
+pub struct ActivityMonitor {
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl ActivityMonitor {
+    /// Listen to global input from a background thread
+    pub fn start() -> Result<Self> {
+        let counters = Arc::new(Mutex::new(Counters::new(Instant::now())));
+        imp::spawn(counters.clone())?;
+        Ok(Self { counters })
+    }
+
+    /// Time since the last input
+    pub fn idle(&self) -> Duration {
+        self.counters.lock().last_input.elapsed()
+    }
+
+    /// Activity now; the cursor is mapped into a `size` frame covering
+    /// `bounds` of the desktop
+    pub fn snapshot(&self, bounds: Option<ScreenRegion>, size: (u32, u32)) -> InputActivity {
+        self.counters.lock().snapshot(Instant::now(), bounds, size)
+    }
+}
+
+struct Counters {
+    keys: VecDeque<Instant>,
+    clicks: VecDeque<Instant>,
+    last_input: Instant,
+    /// Desktop coordinates
+    cursor: Option<(f64, f64)>,
+}
+
+#[cfg_attr(not(feature = "input-activity"), allow(dead_code))]
+impl Counters {
+    fn new(now: Instant) -> Self {
+        Self {
+            keys: VecDeque::new(),
+            clicks: VecDeque::new(),
+            last_input: now,
+            cursor: None,
+        }
+    }
+
+    fn key(&mut self, at: Instant) {
+        self.keys.push_back(at);
+        self.input(at);
+    }
+
+    fn click(&mut self, at: Instant) {
+        self.clicks.push_back(at);
+        self.input(at);
+    }
+
+    fn moved(&mut self, at: Instant, x: f64, y: f64) {
+        self.cursor = Some((x, y));
+        self.input(at);
+    }
+
+    fn input(&mut self, at: Instant) {
+        self.last_input = at;
+        self.prune(at);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        for events in [&mut self.keys, &mut self.clicks] {
+            while events
+                .front()
+                .is_some_and(|&at| now.duration_since(at) > WINDOW)
+            {
+                events.pop_front();
+            }
+        }
+    }
+
+    fn snapshot(
+        &mut self,
+        now: Instant,
+        bounds: Option<ScreenRegion>,
+        size: (u32, u32),
+    ) -> InputActivity {
+        self.prune(now);
+        let cursor = self.cursor.and_then(|(x, y)| {
+            let point = ScreenRegion {
+                x: x.floor() as i32,
+                y: y.floor() as i32,
+                width: 1,
+                height: 1,
+            };
+            to_image_rect(point, bounds, size).map(|(x, y, _, _)| (x, y))
+        });
+        InputActivity {
+            keys_per_min: self.keys.len() as u32,
+            clicks_per_min: self.clicks.len() as u32,
+            idle_secs: now.duration_since(self.last_input).as_secs(),
+            cursor,
+        }
+    }
+}
+
+#[cfg(feature = "input-activity")]
+mod imp {
+    use std::{sync::Arc, thread, time::Instant};
+
+    use anyhow::Result;
+    use parking_lot::Mutex;
+    use rdev::EventType;
+    use tracing::warn;
+
+    use super::Counters;
+
+    /// `rdev::listen` blocks for good, so it gets its own thread
+    pub fn spawn(counters: Arc<Mutex<Counters>>) -> Result<()> {
+        thread::Builder::new()
+            .name("input-activity".into())
+            .spawn(move || {
+                let result = rdev::listen(move |event| {
+                    let now = Instant::now();
+                    let mut counters = counters.lock();
+                    match event.event_type {
+                        EventType::KeyPress(_) => counters.key(now),
+                        EventType::ButtonPress(_) => counters.click(now),
+                        EventType::MouseMove { x, y } => counters.moved(now, x, y),
+                        EventType::Wheel { .. } => counters.input(now),
+                        EventType::KeyRelease(_) | EventType::ButtonRelease(_) => {}
+                    }
+                });
+                if let Err(err) = result {
+                    warn!(?err, "Input activity unavailable");
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "input-activity"))]
+mod imp {
+    use std::sync::Arc;
+
+    use anyhow::{Result, anyhow};
+    use parking_lot::Mutex;
+
+    use super::Counters;
+
+    pub fn spawn(_counters: Arc<Mutex<Counters>>) -> Result<()> {
+        Err(anyhow!(
+            "[vision] input_activity is set but the daemon was built without the `input-activity` feature"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_recent_input() {
+        let start = Instant::now();
+        let mut counters = Counters::new(start);
+        counters.key(start);
+        counters.key(start + Duration::from_secs(30));
+        counters.click(start + Duration::from_secs(40));
+        counters.moved(start + Duration::from_secs(50), 1500.0, 300.0);
+
+        // A 1920x1080 monitor right of the primary, captured at half size
+        let bounds = Some(ScreenRegion {
+            x: 1000,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        });
+        let now = start + Duration::from_secs(80);
+        let activity = counters.snapshot(now, bounds, (960, 540));
+        // The first key is more than a minute old
+        assert_eq!(activity.keys_per_min, 1);
+        assert_eq!(activity.clicks_per_min, 1);
+        assert_eq!(activity.idle_secs, 30);
+        assert_eq!(activity.cursor, Some((250, 150)));
+        assert!(!activity.is_idle());
+
+        let later = counters.snapshot(now + Duration::from_secs(120), None, (960, 540));
+        assert_eq!((later.keys_per_min, later.clicks_per_min), (0, 0));
+        assert_eq!(later.summary(), "idle 2m");
+        // Off the frame
+        assert_eq!(later.cursor, None);
+    }
+}
@@ -1,69 +1,253 @@
-use std::time::Duration;
+use std::{collections::VecDeque, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use image::{DynamicImage, ImageBuffer, ImageFormat, Luma, Rgba, RgbaImage, imageops::FilterType};
+use image::{
+    DynamicImage, GrayImage, ImageBuffer, ImageFormat, Rgba, RgbaImage, imageops::FilterType,
+};
 use rand::{Rng, distributions::Uniform};
+use regex::Regex;
 use serde::Serialize;
-#[cfg(feature = "native-capture")]
-use tracing::warn;
+use tracing::{info, warn};
 
-use crate::config::VisionConfig;
+#[cfg(all(feature = "wayland-capture", target_os = "linux"))]
+use super::portal::PortalScreenProvider;
+use super::{
+    ScreenReader,
+    activity::{ActivityMonitor, InputActivity},
+    diff::{self, TileScores},
+    phash,
+    privacy::{PrivacyMask, WindowInfo},
+    webcam::Webcam,
+};
+#[cfg(feature = "native-capture")]
+use crate::config::MonitorMode;
+use crate::config::{
+    CaptureScaleConfig, MonitorSelection, ScaleFilter, ScreenRegion, VisionConfig, WindowTarget,
+};
 
-const THUMB_WIDTH: u32 = 64;
-const THUMB_HEIGHT: u32 = 36;
+/// A tile counts as changed past this score
+const TILE_CHANGE: f32 = 0.04;
+/// Smallest close-up, so the area around a tiny change is visible too
+const CLOSE_UP_MIN: u32 = 320;
+/// Longest side of a close-up
+const CLOSE_UP_MAX: u32 = 1024;
+/// Changes covering more of the frame than this are legible in the composite
+const CLOSE_UP_MAX_FRACTION: f32 = 0.5;
+/// Size of the blank frame sent while the target window can't be found
+#[cfg(feature = "native-capture")]
+const BLANK_FRAME: (u32, u32) = (1280, 720);
 
 pub struct VisionPipeline {
     config: VisionConfig,
     provider: Box<dyn ScreenProvider + Send>,
-    last_thumb: Option<ImageBuffer<Luma<u8>, Vec<u8>>>,
+    /// Downscale of the previous frame, to diff against
+    last_small: Option<GrayImage>,
+    reader: Option<ScreenReader>,
+    privacy: PrivacyMask,
+    /// Perceptual hashes of the last `duplicate_frames` frames, newest last
+    recent_hashes: VecDeque<u64>,
+    webcam: Option<Webcam>,
+    activity: Option<ActivityMonitor>,
 }
 
 impl VisionPipeline {
-    pub fn new(config: VisionConfig) -> Self {
+    pub fn new(config: VisionConfig) -> Result<Self> {
         #[allow(unused_mut)]
-        let mut provider: Box<dyn ScreenProvider + Send> = Box::new(MockScreenProvider::default());
+        let mut provider: Option<Box<dyn ScreenProvider + Send>> = None;
 
+        // xcap enumerates monitors on Wayland but can't capture them, so it
+        // has to get a frame to count as working
         #[cfg(feature = "native-capture")]
-        {
-            provider = match NativeScreenProvider::new() {
-                Ok(native) => Box::new(native),
-                Err(err) => {
-                    warn!(?err, "Falling back to mock screen provider");
-                    Box::new(MockScreenProvider::default())
-                }
-            };
+        match NativeScreenProvider::new(config.monitor.clone()).and_then(|mut native| {
+            native.capture_frame()?;
+            Ok(native)
+        }) {
+            Ok(native) => provider = Some(Box::new(native)),
+            Err(err) => warn!(?err, "Native screen capture unavailable"),
+        }
+
+        #[cfg(all(feature = "wayland-capture", target_os = "linux"))]
+        if provider.is_none() {
+            match PortalScreenProvider::new() {
+                Ok(portal) => provider = Some(Box::new(portal)),
+                Err(err) => warn!(?err, "Screencast portal unavailable"),
+            }
+        }
+
+        let mut provider = provider.unwrap_or_else(|| {
+            #[cfg(any(
+                feature = "native-capture",
+                all(feature = "wayland-capture", target_os = "linux")
+            ))]
+            tracing::error!(
+                "No screen capture available; falling back to the mock provider's noise"
+            );
+            Box::new(MockScreenProvider::default())
+        });
+
+        if let Some(target) = &config.window {
+            match WindowMatcher::new(target) {
+                Ok(matcher) => provider.set_window(Some(matcher)),
+                Err(err) => warn!(?err, "Invalid [vision.window]; capturing the desktop"),
+            }
         }
 
-        Self {
+        let privacy = PrivacyMask::new(&config.privacy)?;
+        let webcam = config
+            .webcam
+            .as_ref()
+            .and_then(|webcam| match Webcam::start(webcam) {
+                Ok(webcam) => Some(webcam),
+                Err(err) => {
+                    warn!(?err, "Webcam unavailable");
+                    None
+                }
+            });
+        let activity = if config.input_activity {
+            ActivityMonitor::start()
+                .inspect_err(|err| warn!(?err, "Input activity unavailable"))
+                .ok()
+        } else {
+            None
+        };
+        let reader = config.ocr.clone().map(ScreenReader::new);
+        Ok(Self {
             config,
             provider,
-            last_thumb: None,
-        }
+            last_small: None,
+            reader,
+            privacy,
+            recent_hashes: VecDeque::new(),
+            webcam,
+            activity,
+        })
+    }
+
+    /// Capture only the window matching `target` from now on, or the desktop
+    /// again with None
+    pub fn set_window(&mut self, target: Option<WindowTarget>) -> Result<()> {
+        let matcher = target.as_ref().map(WindowMatcher::new).transpose()?;
+        info!(window = ?target, "Capture target changed");
+        self.provider.set_window(matcher);
+        // The next frame is all new
+        self.last_small = None;
+        self.recent_hashes.clear();
+        Ok(())
     }
 
     pub fn capture_interval(&self) -> Duration {
         self.config.capture_interval()
     }
 
+    /// What frames show now, if the monitors changed since the last call
+    pub fn take_target_change(&mut self) -> Option<String> {
+        self.provider.take_target_change()
+    }
+
+    /// Whether `frame` changed enough to count as a new scene
+    pub fn is_scene_change(&self, frame: &VisionFrame) -> bool {
+        frame.diff_score >= self.config.diff_threshold
+    }
+
     pub fn capture_frame(&mut self) -> Result<VisionFrame> {
         let image = self.provider.capture_frame()?;
-        let thumb = make_thumb(&image);
-
-        let diff_score = self
-            .last_thumb
+        let image = match &self.config.scale {
+            Some(scale) => downscale(image, scale),
+            None => image,
+        };
+        let image = self.mask(image, self.provider.last_bounds());
+        let small = diff::downscale(&image);
+        let tiles = self
+            .last_small
+            .as_ref()
+            .and_then(|prev| TileScores::compute(&small, prev));
+        let diff_score = tiles.as_ref().map_or(1.0, |tiles| tiles.mean);
+        let close_up = tiles
             .as_ref()
-            .map(|prev| difference_score(&thumb, prev))
-            .unwrap_or(1.0);
+            .and_then(|tiles| changed_region(tiles, image.width(), image.height()))
+            .map(|area| close_up(&image, area));
 
-        self.last_thumb = Some(thumb);
+        self.last_small = Some(small);
+        let hash = phash::phash(&image);
+        let duplicate = self.config.duplicate_frames > 0 && self.is_duplicate(hash);
+        let activity = self.activity.as_ref().map(|activity| {
+            activity.snapshot(self.provider.last_bounds(), (image.width(), image.height()))
+        });
 
         Ok(VisionFrame {
             timestamp: Utc::now(),
             image,
             diff_score,
+            text: None,
+            close_up,
+            hash,
+            duplicate,
+            camera: self.webcam.as_ref().and_then(Webcam::latest),
+            tiles,
+            activity,
+            foreground: self.provider.foreground(),
         })
     }
+
+    /// A full-resolution screenshot of `window`, else `monitor`, else what
+    /// is being captured now, with privacy rules applied
+    pub fn screenshot(
+        &mut self,
+        monitor: Option<MonitorSelection>,
+        window: Option<WindowTarget>,
+    ) -> Result<RgbaImage> {
+        let target = match (window, monitor) {
+            (Some(window), _) => ScreenshotTarget::Window(WindowMatcher::new(&window)?),
+            (None, Some(monitor)) => ScreenshotTarget::Monitor(monitor),
+            (None, None) => ScreenshotTarget::Current,
+        };
+        let (image, bounds) = self.provider.screenshot(target)?;
+        Ok(self.mask(image, bounds).into_rgba8())
+    }
+
+    /// Hide private windows and areas of `image`, which shows `bounds`
+    fn mask(&mut self, image: DynamicImage, bounds: Option<ScreenRegion>) -> DynamicImage {
+        if self.privacy.is_empty() {
+            return image;
+        }
+        let windows = if self.privacy.has_window_rules() {
+            self.provider.windows()
+        } else {
+            Ok(Vec::new())
+        };
+        let mut rgba = image.into_rgba8();
+        self.privacy.apply(&mut rgba, bounds, windows);
+        DynamicImage::ImageRgba8(rgba)
+    }
+
+    /// Whether `hash` matches each of the last `duplicate_frames` frames,
+    /// then remember it
+    fn is_duplicate(&mut self, hash: u64) -> bool {
+        let wanted = self.config.duplicate_frames;
+        let duplicate = self.recent_hashes.len() >= wanted
+            && self
+                .recent_hashes
+                .iter()
+                .all(|&previous| phash::distance(hash, previous) <= self.config.hash_distance);
+        self.recent_hashes.push_back(hash);
+        while self.recent_hashes.len() > wanted {
+            self.recent_hashes.pop_front();
+        }
+        duplicate
+    }
+
+    /// Time since keyboard or mouse input, when input activity is tracked
+    pub fn input_idle(&self) -> Option<Duration> {
+        self.activity.as_ref().map(ActivityMonitor::idle)
+    }
+
+    /// Fill in `frame.text` if OCR is enabled
+    pub async fn read_text(&mut self, frame: &mut VisionFrame) {
+        if let Some(reader) = &mut self.reader {
+            frame.text = reader.read(frame).await;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -72,6 +256,36 @@ pub struct VisionFrame {
     #[serde(skip_serializing)]
     pub image: DynamicImage,
     pub diff_score: f32,
+    /// On-screen text, when OCR is enabled
+    pub text: Option<String>,
+    /// Full-resolution crop around what changed since the previous frame,
+    /// when the change is small enough to be hard to read in the composite
+    #[serde(skip_serializing)]
+    pub close_up: Option<RgbaImage>,
+    /// Perceptual hash of `image`
+    pub hash: u64,
+    /// Perceptually the same as the previous frames; not worth a VLA call
+    pub duplicate: bool,
+    /// Latest webcam frame, when a webcam is configured
+    #[serde(skip_serializing)]
+    pub camera: Option<RgbaImage>,
+    /// Per-tile change since the previous frame; None for the first frame
+    /// or after the frame size changed
+    #[serde(skip_serializing)]
+    pub tiles: Option<TileScores>,
+    /// Keyboard and mouse activity, when tracked
+    pub activity: Option<InputActivity>,
+    /// The focused window, when the capture backend can tell
+    pub foreground: Option<ForegroundWindow>,
+}
+
+/// The window the user is working in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ForegroundWindow {
+    pub title: String,
+    pub app: String,
+    /// Covers its whole monitor, like a presentation, video or game
+    pub fullscreen: bool,
 }
 
 impl VisionFrame {
@@ -86,8 +300,88 @@ impl VisionFrame {
     }
 }
 
-trait ScreenProvider {
+pub(super) trait ScreenProvider {
     fn capture_frame(&mut self) -> Result<DynamicImage>;
+
+    /// Restrict capture to one window; providers without windows ignore it
+    fn set_window(&mut self, _window: Option<WindowMatcher>) {}
+
+    /// Desktop area shown by the last frame, if it maps onto the desktop
+    fn last_bounds(&self) -> Option<ScreenRegion> {
+        None
+    }
+
+    /// Open windows, for privacy rules
+    fn windows(&self) -> Result<Vec<WindowInfo>> {
+        Ok(Vec::new())
+    }
+
+    /// The focused window, for do-not-disturb
+    fn foreground(&self) -> Option<ForegroundWindow> {
+        None
+    }
+
+    /// What frames show now, if that changed since the last call
+    fn take_target_change(&mut self) -> Option<String> {
+        None
+    }
+
+    /// A one-off capture and the desktop area it shows. Providers that can't
+    /// pick what to capture only take `Current`.
+    fn screenshot(
+        &mut self,
+        target: ScreenshotTarget,
+    ) -> Result<(DynamicImage, Option<ScreenRegion>)> {
+        match target {
+            ScreenshotTarget::Current => Ok((self.capture_frame()?, self.last_bounds())),
+            _ => Err(anyhow::anyhow!(
+                "This capture backend can't screenshot a chosen monitor or window"
+            )),
+        }
+    }
+}
+
+/// What a screenshot shows
+#[cfg_attr(not(feature = "native-capture"), allow(dead_code))]
+pub(super) enum ScreenshotTarget {
+    /// Whatever frames are captured from now
+    Current,
+    Monitor(MonitorSelection),
+    Window(WindowMatcher),
+}
+
+/// Compiled `WindowTarget`
+#[derive(Debug)]
+pub(super) struct WindowMatcher {
+    title: Option<Regex>,
+    app: Option<String>,
+}
+
+impl WindowMatcher {
+    fn new(target: &WindowTarget) -> Result<Self> {
+        if target.title.is_none() && target.app.is_none() {
+            return Err(anyhow::anyhow!("A capture window needs a title or an app"));
+        }
+        let title = target
+            .title
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid window title pattern")?;
+        Ok(Self {
+            title,
+            app: target.app.clone(),
+        })
+    }
+
+    #[cfg_attr(not(feature = "native-capture"), allow(dead_code))]
+    fn matches(&self, title: &str, app: &str) -> bool {
+        self.title.as_ref().is_none_or(|re| re.is_match(title))
+            && self
+                .app
+                .as_ref()
+                .is_none_or(|expected| expected.eq_ignore_ascii_case(app))
+    }
 }
 
 #[derive(Default)]
@@ -126,53 +420,559 @@ impl ScreenProvider for MockScreenProvider {
     }
 }
 
+/// Captures the configured monitor(s). Monitors are enumerated on every frame
+/// so plugging in or rearranging screens is picked up.
 #[cfg(feature = "native-capture")]
 struct NativeScreenProvider {
-    monitor: xcap::Monitor,
+    selection: MonitorSelection,
+    /// Whether a missing configured monitor has been reported
+    warned_missing: bool,
+    /// Set to capture a single window instead
+    window: Option<WindowMatcher>,
+    /// Whether a missing window has been reported
+    warned_window: bool,
+    /// Desktop area of the last frame
+    bounds: Option<ScreenRegion>,
+    /// What the last desktop frame showed, e.g. "monitor DP-1 (2560x1440 at 0,0)"
+    target: Option<String>,
+    /// Set when `target` changes, until taken
+    target_change: Option<String>,
 }
 
 #[cfg(feature = "native-capture")]
 impl NativeScreenProvider {
-    fn new() -> Result<Self> {
-        let monitors = xcap::Monitor::all()
-            .map_err(|e| anyhow::anyhow!("Failed to enumerate monitors: {}", e))?;
-        let monitor = monitors
-            .into_iter()
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("No monitors found"))?;
-        Ok(Self { monitor })
+    fn new(selection: MonitorSelection) -> Result<Self> {
+        let monitors = monitors()?;
+        for (index, monitor) in monitors.iter().enumerate() {
+            info!(
+                index,
+                name = %monitor.name(),
+                x = monitor.x(),
+                y = monitor.y(),
+                width = monitor.width(),
+                height = monitor.height(),
+                primary = monitor.is_primary(),
+                "Monitor found"
+            );
+        }
+        info!(?selection, "Capturing screen");
+        Ok(Self {
+            selection,
+            warned_missing: false,
+            window: None,
+            warned_window: false,
+            bounds: None,
+            target: None,
+            target_change: None,
+        })
+    }
+
+    /// The configured monitor(s), and a description of what was captured
+    fn capture_desktop(&mut self) -> Result<(RgbaImage, String)> {
+        let monitors = monitors()?;
+        if self.selection == MonitorSelection::Mode(MonitorMode::All) && monitors.len() > 1 {
+            let (canvas, bounds) = capture_all(&monitors)?;
+            self.bounds = Some(bounds);
+            let target = format!(
+                "{} monitors ({}x{})",
+                monitors.len(),
+                canvas.width(),
+                canvas.height()
+            );
+            return Ok((canvas, target));
+        }
+        let monitor = self.pick(&monitors)?;
+        let area = monitor_area(&monitor);
+        self.bounds = Some(area);
+        let target = format!(
+            "monitor {} ({}x{} at {},{})",
+            monitor.name(),
+            area.width,
+            area.height,
+            area.x,
+            area.y
+        );
+        Ok((capture_monitor(&monitor)?, target))
+    }
+
+    /// Remember what was captured, noting when it changed (a monitor was
+    /// plugged in or removed, or its resolution changed)
+    fn note_target(&mut self, target: String) {
+        if self.target.as_ref() == Some(&target) {
+            return;
+        }
+        info!(%target, "Capture target");
+        if self.target.is_some() {
+            self.target_change = Some(target.clone());
+        }
+        self.target = Some(target);
+    }
+
+    /// The target window, or a blank frame while it isn't open. The desktop
+    /// is never captured instead, so nothing else leaks into the frame.
+    fn capture_window(&mut self) -> Result<DynamicImage> {
+        let Some(matcher) = &self.window else {
+            return Err(anyhow::anyhow!("No capture window set"));
+        };
+        let Some(window) = find_window(matcher)? else {
+            if !self.warned_window {
+                warn!(window = ?matcher, "No open window matches; sending blank frames");
+                self.warned_window = true;
+            }
+            self.bounds = None;
+            let (width, height) = BLANK_FRAME;
+            return Ok(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                width,
+                height,
+                Rgba([0, 0, 0, 255]),
+            )));
+        };
+        self.warned_window = false;
+        self.bounds = Some(window_area(&window));
+        Ok(DynamicImage::ImageRgba8(convert_capture(
+            window.capture_image()?,
+        )?))
+    }
+
+    /// The monitor to capture for a single-monitor selection
+    fn pick(&mut self, monitors: &[xcap::Monitor]) -> Result<xcap::Monitor> {
+        let picked = select_monitor(monitors, &self.selection);
+        if picked.is_none()
+            && !self.warned_missing
+            && !matches!(self.selection, MonitorSelection::Mode(_))
+        {
+            warn!(selection = ?self.selection, "Configured monitor not found; capturing the primary");
+            self.warned_missing = true;
+        }
+        picked
+            .or_else(|| primary_monitor(monitors))
+            .ok_or_else(|| anyhow::anyhow!("No monitors found"))
     }
 }
 
 #[cfg(feature = "native-capture")]
 impl ScreenProvider for NativeScreenProvider {
     fn capture_frame(&mut self) -> Result<DynamicImage> {
-        let raw = self.monitor.capture_image()?;
-        let width = raw.width();
-        let height = raw.height();
-        let bytes = raw.to_vec();
-        let img = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_vec(width as u32, height as u32, bytes)
-            .ok_or_else(|| anyhow::anyhow!("failed to convert capture buffer"))?;
-        Ok(DynamicImage::ImageRgba8(img))
+        if self.window.is_some() {
+            return self.capture_window();
+        }
+        // A monitor unplugged after it was listed fails to capture; listing
+        // the monitors again picks up the new layout
+        let (image, target) = match self.capture_desktop() {
+            Ok(capture) => capture,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "Screen capture failed; retrying with the current monitors"
+                );
+                self.capture_desktop()?
+            }
+        };
+        self.note_target(target);
+        Ok(DynamicImage::ImageRgba8(image))
     }
+
+    fn take_target_change(&mut self) -> Option<String> {
+        self.target_change.take()
+    }
+
+    fn last_bounds(&self) -> Option<ScreenRegion> {
+        self.bounds
+    }
+
+    /// Every open window, including covered ones, so masking errs on the
+    /// side of hiding too much
+    fn windows(&self) -> Result<Vec<WindowInfo>> {
+        let windows = xcap::Window::all()
+            .map_err(|e| anyhow::anyhow!("Failed to enumerate windows: {}", e))?;
+        Ok(windows
+            .iter()
+            .filter(|w| !w.is_minimized())
+            .map(|w| WindowInfo {
+                title: w.title().to_string(),
+                app: w.app_name().to_string(),
+                area: window_area(w),
+            })
+            .collect())
+    }
+
+    fn set_window(&mut self, window: Option<WindowMatcher>) {
+        self.window = window;
+        self.warned_window = false;
+    }
+
+    fn foreground(&self) -> Option<ForegroundWindow> {
+        let windows = xcap::Window::all().ok()?;
+        let window = focused_window(&windows)?;
+        let monitor = window.current_monitor();
+        Some(ForegroundWindow {
+            title: window.title().to_string(),
+            app: window.app_name().to_string(),
+            fullscreen: window.width() >= monitor.width() && window.height() >= monitor.height(),
+        })
+    }
+
+    /// Unlike regular frames, a missing monitor or window is an error
+    /// rather than a fallback
+    fn screenshot(
+        &mut self,
+        target: ScreenshotTarget,
+    ) -> Result<(DynamicImage, Option<ScreenRegion>)> {
+        let (image, bounds) = match target {
+            ScreenshotTarget::Current => return Ok((self.capture_frame()?, self.bounds)),
+            ScreenshotTarget::Monitor(selection) => {
+                let monitors = monitors()?;
+                if selection == MonitorSelection::Mode(MonitorMode::All) && monitors.len() > 1 {
+                    capture_all(&monitors)?
+                } else {
+                    let monitor = select_monitor(&monitors, &selection)
+                        .ok_or_else(|| anyhow::anyhow!("Monitor {selection:?} not found"))?;
+                    (capture_monitor(&monitor)?, monitor_area(&monitor))
+                }
+            }
+            ScreenshotTarget::Window(matcher) => {
+                let window = find_window(&matcher)?
+                    .ok_or_else(|| anyhow::anyhow!("No open window matches {matcher:?}"))?;
+                (
+                    convert_capture(window.capture_image()?)?,
+                    window_area(&window),
+                )
+            }
+        };
+        Ok((DynamicImage::ImageRgba8(image), Some(bounds)))
+    }
+}
+
+#[cfg(feature = "native-capture")]
+fn monitors() -> Result<Vec<xcap::Monitor>> {
+    xcap::Monitor::all().map_err(|e| anyhow::anyhow!("Failed to enumerate monitors: {}", e))
+}
+
+#[cfg(feature = "native-capture")]
+fn monitor_area(monitor: &xcap::Monitor) -> ScreenRegion {
+    ScreenRegion {
+        x: monitor.x(),
+        y: monitor.y(),
+        width: monitor.width(),
+        height: monitor.height(),
+    }
+}
+
+/// The monitor for a single-monitor `selection`; `primary` and `all` give
+/// the primary
+#[cfg(feature = "native-capture")]
+fn select_monitor(
+    monitors: &[xcap::Monitor],
+    selection: &MonitorSelection,
+) -> Option<xcap::Monitor> {
+    match selection {
+        MonitorSelection::Index(index) => monitors.get(*index).cloned(),
+        MonitorSelection::Name(name) => monitors
+            .iter()
+            .find(|m| m.name().eq_ignore_ascii_case(name))
+            .cloned(),
+        MonitorSelection::Mode(MonitorMode::Focused) => focused_monitor(monitors),
+        MonitorSelection::Mode(_) => primary_monitor(monitors),
+    }
+}
+
+#[cfg(feature = "native-capture")]
+fn primary_monitor(monitors: &[xcap::Monitor]) -> Option<xcap::Monitor> {
+    monitors
+        .iter()
+        .find(|m| m.is_primary())
+        .or_else(|| monitors.first())
+        .cloned()
+}
+
+/// Every monitor stitched together, and the desktop area they cover
+#[cfg(feature = "native-capture")]
+fn capture_all(monitors: &[xcap::Monitor]) -> Result<(RgbaImage, ScreenRegion)> {
+    let captures = monitors
+        .iter()
+        .map(|monitor| Ok((monitor_area(monitor), capture_monitor(monitor)?)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(stitch_scaled(captures))
 }
 
-fn make_thumb(image: &DynamicImage) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    image
-        .resize(THUMB_WIDTH, THUMB_HEIGHT, FilterType::Lanczos3)
-        .to_luma8()
+/// Stitch captures of desktop areas at the pixel density of the densest one,
+/// so HiDPI monitors keep their detail and fractional scale factors (a
+/// 1707-wide area captured 2560 wide) still line up. The frame covers the
+/// returned desktop area.
+#[cfg_attr(not(feature = "native-capture"), allow(dead_code))]
+fn stitch_scaled(captures: Vec<(ScreenRegion, RgbaImage)>) -> (RgbaImage, ScreenRegion) {
+    let scale = captures
+        .iter()
+        .map(|(area, image)| image.width() as f32 / area.width.max(1) as f32)
+        .fold(1.0, f32::max);
+    let scaled = |value: f32| (value * scale).round();
+    let left = captures.iter().map(|(area, _)| area.x).min().unwrap_or(0);
+    let top = captures.iter().map(|(area, _)| area.y).min().unwrap_or(0);
+    let parts: Vec<_> = captures
+        .into_iter()
+        .map(|(area, image)| {
+            let width = scaled(area.width as f32) as u32;
+            let height = scaled(area.height as f32) as u32;
+            let image = if image.dimensions() == (width, height) {
+                image
+            } else {
+                image::imageops::resize(&image, width, height, FilterType::Triangle)
+            };
+            (
+                scaled(area.x as f32) as i32,
+                scaled(area.y as f32) as i32,
+                image,
+            )
+        })
+        .collect();
+    let canvas = stitch(&parts);
+    let bounds = ScreenRegion {
+        x: left,
+        y: top,
+        width: (canvas.width() as f32 / scale).round() as u32,
+        height: (canvas.height() as f32 / scale).round() as u32,
+    };
+    (canvas, bounds)
+}
+
+/// `image` shrunk to fit `scale`'s limits
+fn downscale(image: DynamicImage, scale: &CaptureScaleConfig) -> DynamicImage {
+    let (width, height) = fit_within(image.dimensions(), scale.max_width, scale.max_height);
+    if (width, height) == (image.width(), image.height()) {
+        return image;
+    }
+    let filter = match scale.filter {
+        ScaleFilter::Nearest => FilterType::Nearest,
+        ScaleFilter::Triangle => FilterType::Triangle,
+        ScaleFilter::CatmullRom => FilterType::CatmullRom,
+        ScaleFilter::Lanczos3 => FilterType::Lanczos3,
+    };
+    image.resize_exact(width, height, filter)
+}
+
+/// `size` shrunk (never grown) to fit the limits, keeping its aspect ratio
+fn fit_within(
+    (width, height): (u32, u32),
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> (u32, u32) {
+    let ratio =
+        |max: Option<u32>, size: u32| max.map_or(1.0, |max| max as f64 / size.max(1) as f64);
+    let factor = ratio(max_width, width).min(ratio(max_height, height));
+    if factor >= 1.0 {
+        return (width, height);
+    }
+    let fit = |size: u32| ((size as f64 * factor).round() as u32).max(1);
+    (fit(width), fit(height))
+}
+
+/// The frontmost open window matching `matcher`
+#[cfg(feature = "native-capture")]
+fn find_window(matcher: &WindowMatcher) -> Result<Option<xcap::Window>> {
+    Ok(xcap::Window::all()
+        .map_err(|e| anyhow::anyhow!("Failed to enumerate windows: {}", e))?
+        .into_iter()
+        .find(|w| !w.is_minimized() && matcher.matches(w.title(), w.app_name())))
 }
 
-fn difference_score(
-    current: &ImageBuffer<Luma<u8>, Vec<u8>>,
-    previous: &ImageBuffer<Luma<u8>, Vec<u8>>,
-) -> f32 {
-    let total_pixels = (THUMB_WIDTH * THUMB_HEIGHT) as f32;
-    let mut delta = 0f32;
-    for (cur, prev) in current.pixels().zip(previous.pixels()) {
-        let cur_val = cur[0] as f32;
-        let prev_val = prev[0] as f32;
-        delta += (cur_val - prev_val).abs();
+#[cfg(feature = "native-capture")]
+fn window_area(window: &xcap::Window) -> ScreenRegion {
+    ScreenRegion {
+        x: window.x(),
+        y: window.y(),
+        width: window.width(),
+        height: window.height(),
+    }
+}
+
+#[cfg(feature = "native-capture")]
+fn capture_monitor(monitor: &xcap::Monitor) -> Result<RgbaImage> {
+    convert_capture(monitor.capture_image()?)
+}
+
+/// xcap's image into this crate's `image` version
+#[cfg(feature = "native-capture")]
+fn convert_capture(raw: xcap::image::RgbaImage) -> Result<RgbaImage> {
+    let width = raw.width();
+    let height = raw.height();
+    let bytes = raw.to_vec();
+    ImageBuffer::<Rgba<u8>, Vec<u8>>::from_vec(width as u32, height as u32, bytes)
+        .ok_or_else(|| anyhow::anyhow!("failed to convert capture buffer"))
+}
+
+/// The focused window. Windows are listed front to back, so that is the
+/// first visible, titled one.
+#[cfg(feature = "native-capture")]
+fn focused_window(windows: &[xcap::Window]) -> Option<&xcap::Window> {
+    windows
+        .iter()
+        .find(|w| !w.is_minimized() && !w.title().is_empty() && w.width() > 0)
+}
+
+/// The monitor showing the focused window
+#[cfg(feature = "native-capture")]
+fn focused_monitor(monitors: &[xcap::Monitor]) -> Option<xcap::Monitor> {
+    let windows = xcap::Window::all().ok()?;
+    let window = focused_window(&windows)?;
+    let current = window.current_monitor();
+    monitors.iter().find(|m| m.id() == current.id()).cloned()
+}
+
+/// Paste captures onto one canvas at their desktop positions, which may be
+/// negative (a monitor left of or above the primary). Gaps stay black.
+#[cfg_attr(not(feature = "native-capture"), allow(dead_code))]
+fn stitch(parts: &[(i32, i32, RgbaImage)]) -> RgbaImage {
+    let left = parts.iter().map(|(x, _, _)| *x).min().unwrap_or(0);
+    let top = parts.iter().map(|(_, y, _)| *y).min().unwrap_or(0);
+    let right = parts
+        .iter()
+        .map(|(x, _, image)| *x as i64 + image.width() as i64)
+        .max()
+        .unwrap_or(0);
+    let bottom = parts
+        .iter()
+        .map(|(_, y, image)| *y as i64 + image.height() as i64)
+        .max()
+        .unwrap_or(0);
+    let mut canvas = RgbaImage::from_pixel(
+        (right - left as i64).max(1) as u32,
+        (bottom - top as i64).max(1) as u32,
+        Rgba([0, 0, 0, 255]),
+    );
+    for (x, y, image) in parts {
+        image::imageops::replace(&mut canvas, image, (*x - left) as i64, (*y - top) as i64);
+    }
+    canvas
+}
+
+/// Bounding box (x, y, width, height) in `width`x`height` image pixels of
+/// the tiles that changed. None if nothing did or the change is too large to
+/// need a close-up.
+fn changed_region(tiles: &TileScores, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let ((x0, y0, x1, y1), _) = tiles.changed(TILE_CHANGE)?;
+    let area = (x1 - x0 + 1) * (y1 - y0 + 1);
+    if area as f32 > (tiles.columns * tiles.rows) as f32 * CLOSE_UP_MAX_FRACTION {
+        return None;
+    }
+    // Tiles to pixels, a tile of margin around, and at least CLOSE_UP_MIN
+    // wide and high (centered, clamped to the image)
+    let to_x = |tile: u32| {
+        ((tile * diff::TILE).min(diff::DIFF_WIDTH) as u64 * width as u64 / diff::DIFF_WIDTH as u64)
+            as u32
+    };
+    let to_y = |tile: u32| {
+        ((tile * diff::TILE).min(diff::DIFF_HEIGHT) as u64 * height as u64
+            / diff::DIFF_HEIGHT as u64) as u32
+    };
+    let expand = |start: u32, end: u32, size: u32| {
+        let len = (end - start).max(CLOSE_UP_MIN).min(size);
+        let start = ((start + end) / 2).saturating_sub(len / 2).min(size - len);
+        (start, start + len)
+    };
+    let (left, right) = expand(to_x(x0.saturating_sub(1)), to_x(x1 + 2), width);
+    let (top, bottom) = expand(to_y(y0.saturating_sub(1)), to_y(y1 + 2), height);
+    Some((left, top, right - left, bottom - top))
+}
+
+fn close_up(image: &DynamicImage, (x, y, width, height): (u32, u32, u32, u32)) -> RgbaImage {
+    let crop = image.crop_imm(x, y, width, height);
+    if width.max(height) > CLOSE_UP_MAX {
+        crop.resize(CLOSE_UP_MAX, CLOSE_UP_MAX, FilterType::Triangle)
+            .to_rgba8()
+    } else {
+        crop.to_rgba8()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn test_window_matcher() {
+        let matcher = WindowMatcher::new(&WindowTarget {
+            title: Some(r"(?i)- obs \d+".into()),
+            app: None,
+        })
+        .unwrap();
+        assert!(matcher.matches("Scenes - OBS 30.1", "obs"));
+        assert!(!matcher.matches("Firefox", "firefox"));
+
+        let matcher = WindowMatcher::new(&WindowTarget {
+            title: Some("Settings".into()),
+            app: Some("Code".into()),
+        })
+        .unwrap();
+        assert!(matcher.matches("Settings - Visual Studio Code", "code"));
+        assert!(!matcher.matches("Settings", "firefox"));
+
+        assert!(WindowMatcher::new(&WindowTarget::default()).is_err());
+    }
+
+    #[test]
+    fn test_stitch_places_monitors_by_position() {
+        let red = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(2, 3, Rgba([0, 0, 255, 255]));
+        // A monitor to the left of the primary, with a lower top edge
+        let canvas = stitch(&[(0, 0, red), (-2, 1, blue)]);
+        assert_eq!(canvas.dimensions(), (6, 4));
+        assert_eq!(canvas.get_pixel(2, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(0, 1), &Rgba([0, 0, 255, 255]));
+        assert_eq!(canvas.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_stitch_scaled_keeps_hidpi_detail() {
+        let area = |x, width, height| ScreenRegion {
+            x,
+            y: 0,
+            width,
+            height,
+        };
+        // A 1.5x monitor left of a 1x one
+        let hidpi = RgbaImage::from_pixel(6, 3, Rgba([255, 0, 0, 255]));
+        let plain = RgbaImage::from_pixel(4, 2, Rgba([0, 0, 255, 255]));
+        let (canvas, bounds) = stitch_scaled(vec![(area(0, 4, 2), hidpi), (area(4, 4, 2), plain)]);
+        assert_eq!(canvas.dimensions(), (12, 3));
+        assert_eq!(bounds, area(0, 8, 2));
+        assert_eq!(canvas.get_pixel(5, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(6, 0), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_fit_within() {
+        assert_eq!(fit_within((3840, 2160), Some(1920), None), (1920, 1080));
+        assert_eq!(fit_within((3840, 2160), Some(1920), Some(720)), (1280, 720));
+        // Never upscaled
+        assert_eq!(fit_within((1280, 720), Some(1920), Some(1080)), (1280, 720));
+        assert_eq!(fit_within((2559, 1440), None, None), (2559, 1440));
+    }
+
+    #[test]
+    fn test_changed_region_frames_a_toast() {
+        let previous = GrayImage::from_pixel(diff::DIFF_WIDTH, diff::DIFF_HEIGHT, Luma([0]));
+        let mut current = previous.clone();
+        let region = |current: &GrayImage| {
+            changed_region(
+                &TileScores::compute(current, &previous).unwrap(),
+                1920,
+                1080,
+            )
+        };
+        assert_eq!(region(&current), None);
+
+        // A notification in the top-right corner
+        for x in 288..320 {
+            for y in 0..16 {
+                current.put_pixel(x, y, Luma([200]));
+            }
+        }
+        assert_eq!(region(&current), Some((1600, 0, 320, 320)));
+
+        // Most of the screen changed: the composite shows it well enough
+        let everything = GrayImage::from_pixel(diff::DIFF_WIDTH, diff::DIFF_HEIGHT, Luma([200]));
+        assert_eq!(region(&everything), None);
     }
-    delta / (total_pixels * 255.0)
 }
@@ -1,8 +1,15 @@
+use std::{borrow::Cow, time::Duration};
+
 use image::{
     ImageBuffer, Rgba, RgbaImage,
     imageops::{FilterType, resize},
 };
 
+use super::{
+    InputActivity,
+    text::{draw_label, draw_shadowed},
+};
+
 pub struct CompositeRenderer {
     width: u32,
     height: u32,
@@ -24,11 +31,37 @@ impl CompositeRenderer {
     /// +--------+-------+--------+
     /// | CHAT   | MEMORY| STATUS |
     /// +--------+-------+--------+
+    /// With a webcam, CAMERA takes the bottom-right corner.
     pub fn render(&self, parts: &CompositeParts) -> RgbaImage {
         self.render_with_history(parts, &[])
     }
     
+    /// PREV panels are the given screenshots, newest first
     pub fn render_with_history(&self, parts: &CompositeParts, history: &[&RgbaImage]) -> RgbaImage {
+        let history: Vec<_> = history
+            .iter()
+            .take(3)
+            .enumerate()
+            .map(|(i, image)| (*image, format!("PREV {}", i + 1)))
+            .collect();
+        self.render_panels(parts, &history)
+    }
+
+    /// PREV panels are recent frames, newest first, labeled with their age
+    pub fn render_with_timeline(
+        &self,
+        parts: &CompositeParts,
+        timeline: &[(&RgbaImage, Duration)],
+    ) -> RgbaImage {
+        let history: Vec<_> = timeline
+            .iter()
+            .take(3)
+            .map(|(image, age)| (*image, format!("PREV {} AGO", format_age(*age))))
+            .collect();
+        self.render_panels(parts, &history)
+    }
+
+    fn render_panels(&self, parts: &CompositeParts, history: &[(&RgbaImage, String)]) -> RgbaImage {
         let mut canvas = ImageBuffer::from_pixel(self.width, self.height, Rgba([10, 10, 12, 255]));
         
         // Calculate layout based on whether we have history
@@ -47,13 +80,16 @@ impl CompositeRenderer {
                 &mut canvas,
                 0,
                 0,
-                &resize_image(&parts.desktop, main_width, top_height),
+                &resize_image(&desktop_with_cursor(parts), main_width, top_height),
             );
             draw_label(&mut canvas, 12, 18, "DESKTOP");
+            if let Some(activity) = &parts.activity {
+                draw_activity(&mut canvas, 12, top_height - 28, activity);
+            }
             
             // History filmstrip (right column)
             let hist_panel_height = top_height / 3;
-            for (i, hist_img) in history.iter().take(3).enumerate() {
+            for (i, (hist_img, label)) in history.iter().enumerate() {
                 let y = (i as u32) * hist_panel_height;
                 overlay(
                     &mut canvas,
@@ -61,13 +97,6 @@ impl CompositeRenderer {
                     y,
                     &resize_image(hist_img, history_width, hist_panel_height),
                 );
-                // Label each history panel
-                let label = match i {
-                    0 => "PREV 1",
-                    1 => "PREV 2", 
-                    2 => "PREV 3",
-                    _ => "HIST",
-                };
                 draw_label(&mut canvas, main_width + 8, y + 14, label);
             }
             
@@ -94,13 +123,29 @@ impl CompositeRenderer {
             );
             draw_label(&mut canvas, bottom_panel_width + 8, top_height + 14, "MEMORY");
             
+            // Status takes the corner under the history unless the camera does
+            let status_width = if parts.camera.is_some() {
+                bottom_panel_width
+            } else {
+                bottom_panel_width + history_width
+            };
             overlay(
                 &mut canvas,
                 bottom_panel_width * 2,
                 top_height,
-                &resize_image(&parts.character_status, bottom_panel_width + history_width, bottom_height),
+                &resize_image(&parts.character_status, status_width, bottom_height),
             );
             draw_label(&mut canvas, bottom_panel_width * 2 + 8, top_height + 14, "STATUS");
+
+            if let Some(camera) = &parts.camera {
+                overlay(
+                    &mut canvas,
+                    main_width,
+                    top_height,
+                    &resize_image(camera, history_width, bottom_height),
+                );
+                draw_label(&mut canvas, main_width + 8, top_height + 14, "CAMERA");
+            }
         } else {
             // Original 2x2 layout when no history
             let half_w = self.width / 2;
@@ -110,7 +155,7 @@ impl CompositeRenderer {
                 &mut canvas,
                 0,
                 0,
-                &resize_image(&parts.desktop, half_w, half_h),
+                &resize_image(&desktop_with_cursor(parts), half_w, half_h),
             );
             overlay(
                 &mut canvas,
@@ -132,9 +177,20 @@ impl CompositeRenderer {
             );
 
             draw_label(&mut canvas, 12, 18, "DESKTOP");
+            if let Some(activity) = &parts.activity {
+                draw_activity(&mut canvas, 12, half_h - 28, activity);
+            }
             draw_label(&mut canvas, half_w + 12, 18, "MEMORY MAP");
             draw_label(&mut canvas, 12, half_h + 18, "RECENT CHAT");
             draw_label(&mut canvas, half_w + 12, half_h + 18, "COMPANIONS");
+
+            // Camera inset in the bottom-right corner of the companions panel
+            if let Some(camera) = &parts.camera {
+                let (inset_w, inset_h) = (half_w / 2, half_h / 2);
+                let (x, y) = (self.width - inset_w, self.height - inset_h);
+                overlay(&mut canvas, x, y, &resize_image(camera, inset_w, inset_h));
+                draw_label(&mut canvas, x + 8, y + 14, "CAMERA");
+            }
         }
 
         canvas
@@ -157,6 +213,51 @@ pub struct CompositeParts {
     pub memory_visualization: RgbaImage,
     pub chat_transcript: RgbaImage,
     pub character_status: RgbaImage,
+    /// Latest webcam frame, when a webcam is configured
+    pub camera: Option<RgbaImage>,
+    /// Input rates and the cursor, when tracked
+    pub activity: Option<InputActivity>,
+}
+
+/// The desktop with a ring around the cursor, sized to survive downscaling
+fn desktop_with_cursor(parts: &CompositeParts) -> Cow<'_, RgbaImage> {
+    let Some((cx, cy)) = parts.activity.and_then(|activity| activity.cursor) else {
+        return Cow::Borrowed(&parts.desktop);
+    };
+    let mut desktop = parts.desktop.clone();
+    let radius = (desktop.width().max(desktop.height()) / 80).max(8) as i64;
+    let thickness = (radius / 4).max(2);
+    let (cx, cy) = (cx as i64, cy as i64);
+    for y in (cy - radius).max(0)..(cy + radius + 1).min(desktop.height() as i64) {
+        for x in (cx - radius).max(0)..(cx + radius + 1).min(desktop.width() as i64) {
+            let distance = (((x - cx).pow(2) + (y - cy).pow(2)) as f64).sqrt() as i64;
+            if distance <= radius && distance > radius - thickness {
+                desktop.put_pixel(x as u32, y as u32, Rgba([255, 200, 0, 255]));
+            }
+        }
+    }
+    Cow::Owned(desktop)
+}
+
+/// "INPUT" badge: green with rates while the user is active, grey when idle
+fn draw_activity(canvas: &mut RgbaImage, x: u32, y: u32, activity: &InputActivity) {
+    let color = if activity.is_idle() {
+        Rgba([160, 160, 160, 255])
+    } else {
+        Rgba([90, 230, 120, 255])
+    };
+    let text = format!("INPUT {}", activity.summary());
+    draw_shadowed(canvas, x, y, &text, color);
+}
+
+/// "40 SEC" or "2 MIN"
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{} SEC", secs)
+    } else {
+        format!("{} MIN", secs / 60)
+    }
 }
 
 /// Resize image to fit within bounds while preserving aspect ratio (letterboxing)
@@ -205,88 +306,3 @@ fn overlay(canvas: &mut RgbaImage, x: u32, y: u32, src: &RgbaImage) {
         }
     }
 }
-
-fn draw_label(canvas: &mut RgbaImage, x: u32, y: u32, text: &str) {
-    let mut cursor = x;
-    for ch in text.chars() {
-        draw_char(canvas, cursor, y, ch);
-        cursor += 6;
-    }
-}
-
-fn draw_char(canvas: &mut RgbaImage, x: u32, y: u32, ch: char) {
-    if let Some(pattern) = glyph_pattern(ch) {
-        for (row, bits) in pattern.iter().enumerate() {
-            for col in 0..5 {
-                if (bits >> (4 - col)) & 1 == 1 {
-                    let px = x + col as u32;
-                    let py = y + row as u32;
-                    if px < canvas.width() && py < canvas.height() {
-                        canvas.put_pixel(px, py, Rgba([255, 255, 255, 255]));
-                    }
-                }
-            }
-        }
-    }
-}
-
-fn glyph_pattern(ch: char) -> Option<&'static [u8; 7]> {
-    match ch {
-        'A' => Some(&[
-            0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
-        ]),
-        'B' => Some(&[
-            0b11110, 0b10001, 0b11110, 0b10001, 0b10001, 0b10001, 0b11110,
-        ]),
-        'C' => Some(&[
-            0b01111, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b01111,
-        ]),
-        'D' => Some(&[
-            0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110,
-        ]),
-        'E' => Some(&[
-            0b11111, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000, 0b11111,
-        ]),
-        'F' => Some(&[
-            0b11111, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000, 0b10000,
-        ]),
-        'H' => Some(&[
-            0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001, 0b10001,
-        ]),
-        'I' => Some(&[
-            0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b11111,
-        ]),
-        'K' => Some(&[
-            0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
-        ]),
-        'L' => Some(&[
-            0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
-        ]),
-        'M' => Some(&[
-            0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
-        ]),
-        'N' => Some(&[
-            0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001, 0b10001,
-        ]),
-        'O' => Some(&[
-            0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
-        ]),
-        'P' => Some(&[
-            0b11110, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000, 0b10000,
-        ]),
-        'R' => Some(&[
-            0b11110, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001, 0b10001,
-        ]),
-        'S' => Some(&[
-            0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
-        ]),
-        'T' => Some(&[
-            0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
-        ]),
-        'Y' => Some(&[
-            0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100,
-        ]),
-        ' ' => Some(&[0, 0, 0, 0, 0, 0, 0]),
-        _ => None,
-    }
-}
@@ -0,0 +1,125 @@
+//! Frame-to-frame change, scored per tile of a mid-resolution grayscale
+//! downscale. Small UI changes still cover whole tiles at this size, and the
+//! byte-wise absolute differences compile to SIMD.
+
+use image::{DynamicImage, GrayImage};
+
+/// Size frames are compared at
+pub(super) const DIFF_WIDTH: u32 = 320;
+pub(super) const DIFF_HEIGHT: u32 = 180;
+/// Side of a scored tile, in downscaled pixels
+pub(super) const TILE: u32 = 16;
+
+/// The downscaled grayscale frame that diffs are computed on
+pub(super) fn downscale(image: &DynamicImage) -> GrayImage {
+    image.thumbnail_exact(DIFF_WIDTH, DIFF_HEIGHT).to_luma8()
+}
+
+/// How much each tile changed between two frames, 0 to 1 (mean absolute
+/// brightness difference), in rows of `columns`
+#[derive(Debug, Clone)]
+pub struct TileScores {
+    pub columns: u32,
+    pub rows: u32,
+    pub scores: Vec<f32>,
+    /// Over the whole frame
+    pub mean: f32,
+}
+
+impl TileScores {
+    pub fn compute(current: &GrayImage, previous: &GrayImage) -> Option<Self> {
+        if current.dimensions() != previous.dimensions() {
+            return None;
+        }
+        let (width, height) = current.dimensions();
+        let columns = width.div_ceil(TILE);
+        let rows = height.div_ceil(TILE);
+        let mut sums = vec![0u32; (columns * rows) as usize];
+        let (current, previous) = (current.as_raw(), previous.as_raw());
+        for y in 0..height as usize {
+            let start = y * width as usize;
+            let row = start..start + width as usize;
+            let tile_row = y / TILE as usize * columns as usize;
+            for (column, (a, b)) in current[row.clone()]
+                .chunks(TILE as usize)
+                .zip(previous[row].chunks(TILE as usize))
+                .enumerate()
+            {
+                sums[tile_row + column] += a
+                    .iter()
+                    .zip(b)
+                    .map(|(a, b)| a.abs_diff(*b) as u32)
+                    .sum::<u32>();
+            }
+        }
+
+        let total: u64 = sums.iter().map(|&s| s as u64).sum();
+        let mut scores = Vec::with_capacity(sums.len());
+        for row in 0..rows {
+            let tile_height = TILE.min(height - row * TILE);
+            for column in 0..columns {
+                let tile_width = TILE.min(width - column * TILE);
+                let sum = sums[(row * columns + column) as usize];
+                scores.push(sum as f32 / (tile_width * tile_height * 255) as f32);
+            }
+        }
+        Some(Self {
+            columns,
+            rows,
+            scores,
+            mean: total as f32 / (width as u64 * height as u64 * 255) as f32,
+        })
+    }
+
+    pub fn get(&self, column: u32, row: u32) -> f32 {
+        self.scores[(row * self.columns + column) as usize]
+    }
+
+    /// Bounding box (first column, first row, last column, last row) of the
+    /// tiles scoring above `threshold`, and how many there are
+    pub fn changed(&self, threshold: f32) -> Option<((u32, u32, u32, u32), usize)> {
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        let mut count = 0;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                if self.get(column, row) <= threshold {
+                    continue;
+                }
+                count += 1;
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => {
+                        (x0.min(column), y0.min(row), x1.max(column), y1.max(row))
+                    }
+                    None => (column, row, column, row),
+                });
+            }
+        }
+        bounds.map(|bounds| (bounds, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn test_tile_scores() {
+        let previous = GrayImage::from_pixel(DIFF_WIDTH, DIFF_HEIGHT, Luma([0]));
+        let mut current = previous.clone();
+        // Half of the tile at column 2, row 1 turns white
+        for x in 32..40 {
+            for y in 16..32 {
+                current.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let tiles = TileScores::compute(&current, &previous).unwrap();
+        assert_eq!((tiles.columns, tiles.rows), (20, 12));
+        assert!((tiles.get(2, 1) - 0.5).abs() < 1e-6);
+        assert_eq!(tiles.get(3, 1), 0.0);
+        assert!((tiles.mean - 128.0 / (320.0 * 180.0)).abs() < 1e-6);
+        assert_eq!(tiles.changed(0.1), Some(((2, 1, 2, 1), 1)));
+        assert_eq!(tiles.changed(0.6), None);
+    }
+}
@@ -1,5 +1,19 @@
+mod activity;
 mod capture;
 mod composite;
+mod diff;
+mod ocr;
+mod phash;
+#[cfg(all(feature = "wayland-capture", target_os = "linux"))]
+mod portal;
+mod privacy;
+mod text;
+mod webcam;
 
-pub use capture::{VisionFrame, VisionPipeline};
+pub use activity::InputActivity;
+pub use capture::{ForegroundWindow, VisionFrame, VisionPipeline};
 pub use composite::{CompositeParts, CompositeRenderer};
+pub use diff::TileScores;
+pub use phash::distance as hash_distance;
+pub use ocr::ScreenReader;
+pub use text::{draw_label, draw_text};
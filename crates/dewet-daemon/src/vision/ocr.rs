@@ -0,0 +1,115 @@
+//! On-screen text for observations, so models without vision (and the VLA)
+//! can read what the user is looking at
+
+use std::{fs, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+use tracing::{debug, warn};
+
+use super::VisionFrame;
+use crate::config::OcrConfig;
+
+pub struct ScreenReader {
+    config: OcrConfig,
+    /// Text of the last frame that was read
+    last: Option<String>,
+}
+
+impl ScreenReader {
+    pub fn new(config: OcrConfig) -> Self {
+        Self { config, last: None }
+    }
+
+    /// The text on `frame`. Frames that barely changed reuse the previous
+    /// text, since OCR takes a second or more on a full screen.
+    pub async fn read(&mut self, frame: &VisionFrame) -> Option<String> {
+        if self.last.is_some() && frame.diff_score < self.config.min_diff {
+            return self.last.clone();
+        }
+        let png = match frame.as_png() {
+            Ok(png) => png,
+            Err(err) => {
+                warn!(?err, "Failed to encode frame for OCR");
+                return self.last.clone();
+            }
+        };
+        let config = self.config.clone();
+        let started = std::time::Instant::now();
+        match tokio::task::spawn_blocking(move || run_tesseract(&config, &png)).await {
+            Ok(Ok(raw)) => {
+                let text = clean_text(&raw, self.config.max_chars);
+                debug!(
+                    elapsed = ?started.elapsed(),
+                    chars = text.as_ref().map_or(0, |t| t.len()),
+                    "Screen text read"
+                );
+                self.last = text;
+            }
+            Ok(Err(err)) => warn!(?err, "OCR failed"),
+            Err(err) => warn!(?err, "OCR task panicked"),
+        }
+        self.last.clone()
+    }
+}
+
+fn run_tesseract(config: &OcrConfig, png: &[u8]) -> Result<String> {
+    let input_path = std::env::temp_dir().join(format!("dewet-ocr-{}.png", uuid::Uuid::new_v4()));
+    fs::write(&input_path, png)?;
+    let output = Command::new(&config.binary)
+        .arg(&input_path)
+        .arg("stdout")
+        .args(["-l", &config.language])
+        .output()
+        .with_context(|| format!("Failed to run {}", config.binary));
+    fs::remove_file(&input_path).ok();
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} exited with {}: {}",
+            config.binary,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Tidy OCR output for a prompt: trimmed lines, without blank runs or lines
+/// of stray symbols, cut to `max_chars` at a line break. None if nothing
+/// readable is left.
+fn clean_text(raw: &str, max_chars: usize) -> Option<String> {
+    let mut text = String::new();
+    for line in raw
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+    {
+        // Icons and borders come out as lone symbols
+        if line.chars().filter(|c| c.is_alphanumeric()).count() < 2 {
+            continue;
+        }
+        if text.chars().count() + line.chars().count() + 1 > max_chars {
+            break;
+        }
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&line);
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_text() {
+        let raw = "  File   Edit  View\n\n| ©\n\nerror[E0308]: mismatched types\n=\n";
+        assert_eq!(
+            clean_text(raw, 100).as_deref(),
+            Some("File Edit View\nerror[E0308]: mismatched types")
+        );
+        assert_eq!(clean_text(raw, 20).as_deref(), Some("File Edit View"));
+        assert_eq!(clean_text("|\n-- \n", 100), None);
+    }
+}
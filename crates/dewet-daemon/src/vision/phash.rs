@@ -0,0 +1,88 @@
+//! Perceptual hashes, for telling when the screen hasn't really changed
+
+use std::sync::LazyLock;
+
+use image::{DynamicImage, imageops::FilterType};
+
+/// Side of the grayscale image the DCT runs on
+const SIZE: usize = 32;
+/// Side of the low-frequency block the hash is taken from
+const BLOCK: usize = 8;
+
+/// cos((2x + 1) * u * pi / 2N), indexed [u][x]
+static COSINES: LazyLock<[[f32; SIZE]; SIZE]> = LazyLock::new(|| {
+    let mut table = [[0.0; SIZE]; SIZE];
+    for (u, row) in table.iter_mut().enumerate() {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = (((2 * x + 1) * u) as f32 * std::f32::consts::PI / (2 * SIZE) as f32).cos();
+        }
+    }
+    table
+});
+
+/// 64-bit pHash: which low frequencies of the image's DCT are above their
+/// median. Unlike a pixel diff it ignores small shifts, scaling, and noise.
+pub fn phash(image: &DynamicImage) -> u64 {
+    let gray = image
+        .resize_exact(SIZE as u32, SIZE as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f32> = gray.pixels().map(|p| p[0] as f32).collect();
+
+    // Separable 2D DCT, only for the block that is kept
+    let mut rows = [[0.0f32; BLOCK]; SIZE];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            *value = (0..SIZE)
+                .map(|x| pixels[y * SIZE + x] * COSINES[u][x])
+                .sum();
+        }
+    }
+    let mut coefficients = Vec::with_capacity(BLOCK * BLOCK);
+    for v in 0..BLOCK {
+        for u in 0..BLOCK {
+            coefficients.push((0..SIZE).map(|y| rows[y][u] * COSINES[v][y]).sum::<f32>());
+        }
+    }
+
+    // The DC term is overall brightness and would skew the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c > median)
+        .fold(0u64, |hash, (i, _)| hash | (1 << i))
+}
+
+/// Number of differing bits
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    /// A bright window at `left` on a gradient desktop, with some pixel noise
+    fn scene(left: u32, noise: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(640, 360, |x, y| {
+            let v = if (left..left + 200).contains(&x) && (60..260).contains(&y) {
+                220
+            } else {
+                (x / 8) as u8
+            };
+            let v = v.saturating_add(((x * 7 + y * 13) % 3) as u8 * noise);
+            Rgba([v, v, v, 255])
+        }))
+    }
+
+    #[test]
+    fn test_phash_ignores_noise_but_not_changes() {
+        let base = phash(&scene(0, 0));
+        assert!(distance(base, phash(&scene(0, 4))) <= 4);
+        assert!(distance(base, phash(&scene(400, 0))) > 10);
+    }
+}
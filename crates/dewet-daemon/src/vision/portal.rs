@@ -0,0 +1,378 @@
+//! Screen capture on Wayland through the XDG desktop portal's ScreenCast
+//! interface, which hands over a PipeWire video stream.
+//!
+//! The desktop asks the user what to share the first time; the portal's
+//! restore token is saved so later runs can skip the dialog where the
+//! desktop allows it.
+
+use std::{
+    fs,
+    io::Cursor,
+    os::fd::OwnedFd,
+    path::Path,
+    sync::{Arc, mpsc},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use ashpd::desktop::{
+    PersistMode, Session,
+    screencast::{CursorMode, Screencast, SourceType},
+};
+use image::{DynamicImage, RgbaImage};
+use parking_lot::Mutex;
+use pipewire as pw;
+use pw::spa::{
+    self,
+    param::{
+        format::{FormatProperties, MediaSubtype, MediaType},
+        format_utils,
+        video::{VideoFormat, VideoInfoRaw},
+    },
+    pod::Pod,
+};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+use super::{
+    capture::{ScreenProvider, WindowMatcher},
+    privacy::WindowInfo,
+};
+
+/// Where the portal's restore token is kept between runs
+const RESTORE_TOKEN_PATH: &str = ".local/screencast.token";
+/// How long to wait for the user to answer the share dialog
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The newest frame from the stream, as delivered
+struct RawFrame {
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: VideoFormat,
+}
+
+pub(super) struct PortalScreenProvider {
+    latest: Arc<Mutex<Option<RawFrame>>>,
+}
+
+impl PortalScreenProvider {
+    /// Start a screencast, blocking until the user has chosen what to share
+    /// and the stream is connected
+    pub fn new() -> Result<Self> {
+        let latest = Arc::new(Mutex::new(None));
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let frames = latest.clone();
+        thread::Builder::new()
+            .name("screencast".into())
+            .spawn(move || {
+                if let Err(err) = run(frames, &ready_tx) {
+                    // Only reaches the caller if it is still waiting
+                    if ready_tx.send(Err(err)).is_err() {
+                        warn!("Screencast stream ended");
+                    }
+                }
+            })?;
+        match ready_rx.recv_timeout(PORTAL_TIMEOUT) {
+            Ok(Ok(())) => Ok(Self { latest }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(anyhow!("No answer from the screen share dialog")),
+        }
+    }
+}
+
+impl ScreenProvider for PortalScreenProvider {
+    fn capture_frame(&mut self) -> Result<DynamicImage> {
+        let latest = self.latest.lock();
+        let frame = latest
+            .as_ref()
+            .ok_or_else(|| anyhow!("Waiting for the first screencast frame"))?;
+        Ok(DynamicImage::ImageRgba8(to_rgba(frame)?))
+    }
+
+    /// What is shared is picked in the portal's dialog
+    fn set_window(&mut self, window: Option<WindowMatcher>) {
+        if window.is_some() {
+            warn!(
+                "Window capture isn't available through the portal; pick the window when sharing"
+            );
+        }
+    }
+
+    /// The compositor doesn't tell clients where windows are, so window
+    /// privacy rules mask whole frames
+    fn windows(&self) -> Result<Vec<WindowInfo>> {
+        Err(anyhow!("Window positions are not available on Wayland"))
+    }
+}
+
+/// Negotiate the screencast with the portal, then pump the PipeWire stream
+/// on this thread until it ends
+fn run(latest: Arc<Mutex<Option<RawFrame>>>, ready: &mpsc::Sender<Result<()>>) -> Result<()> {
+    // zbus needs a running executor to keep the portal session alive
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()?;
+    let (opened_tx, opened_rx) = oneshot::channel();
+    runtime.spawn(share_screen(opened_tx));
+    let (node_id, fd) = runtime
+        .block_on(opened_rx)
+        .map_err(|_| anyhow!("Screencast portal task ended"))??;
+
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect_fd(fd, None)?;
+    let stream = pw::stream::Stream::new(
+        &core,
+        "dewet-capture",
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+        },
+    )?;
+
+    let _listener = stream
+        .add_local_listener_with_user_data(VideoInfoRaw::default())
+        .state_changed(|_, _, _, state| {
+            if let pw::stream::StreamState::Error(err) = state {
+                warn!(%err, "Screencast stream error");
+            }
+        })
+        .param_changed(|_, format, id, param| {
+            let Some(param) = param else { return };
+            if id != spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            match format_utils::parse_format(param) {
+                Ok((MediaType::Video, MediaSubtype::Raw)) => {}
+                _ => return,
+            }
+            if let Err(err) = format.parse(param) {
+                warn!(?err, "Unreadable screencast format");
+                return;
+            }
+            info!(
+                format = ?format.format(),
+                width = format.size().width,
+                height = format.size().height,
+                "Screencast format negotiated"
+            );
+        })
+        .process(move |stream, format| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let Some(data) = buffer.datas_mut().first_mut() else {
+                return;
+            };
+            let (width, height) = (format.size().width, format.size().height);
+            // Nothing usable before the format is negotiated
+            if width == 0 || height == 0 {
+                return;
+            }
+            let stride = data.chunk().stride().max(0) as usize;
+            let Some(bytes) = data.data() else { return };
+            *latest.lock() = Some(RawFrame {
+                bytes: bytes.to_vec(),
+                width,
+                height,
+                stride,
+                format: format.format(),
+            });
+        })
+        .register()?;
+
+    let format = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        spa::param::ParamType::EnumFormat,
+        spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        spa::pod::property!(
+            FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::BGRx,
+            VideoFormat::BGRx,
+            VideoFormat::BGRA,
+            VideoFormat::RGBx,
+            VideoFormat::RGBA
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            spa::utils::Rectangle {
+                width: 1920,
+                height: 1080
+            },
+            spa::utils::Rectangle {
+                width: 1,
+                height: 1
+            },
+            spa::utils::Rectangle {
+                width: 8192,
+                height: 8192
+            }
+        ),
+        // Frames are sampled every capture interval; no need for more
+        spa::pod::property!(
+            FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            spa::utils::Fraction { num: 2, denom: 1 },
+            spa::utils::Fraction { num: 0, denom: 1 },
+            spa::utils::Fraction { num: 10, denom: 1 }
+        ),
+    );
+    let format = spa::pod::serialize::PodSerializer::serialize(
+        Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(format),
+    )
+    .map_err(|e| anyhow!("Failed to build screencast format: {:?}", e))?
+    .0
+    .into_inner();
+    let mut params = [Pod::from_bytes(&format).context("Invalid screencast format")?];
+    stream.connect(
+        spa::utils::Direction::Input,
+        Some(node_id),
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+
+    info!(node_id, "Capturing through the screencast portal");
+    ready.send(Ok(())).ok();
+    mainloop.run();
+    Ok(())
+}
+
+/// Hold a portal session for as long as the runtime runs, after sending
+/// the stream's PipeWire node and connection
+async fn share_screen(opened: oneshot::Sender<Result<(u32, OwnedFd)>>) {
+    let proxy = match Screencast::new().await {
+        Ok(proxy) => proxy,
+        Err(err) => {
+            opened
+                .send(Err(anyhow!(err).context("ScreenCast portal unavailable")))
+                .ok();
+            return;
+        }
+    };
+    let session = match proxy.create_session().await {
+        Ok(session) => session,
+        Err(err) => {
+            opened.send(Err(err.into())).ok();
+            return;
+        }
+    };
+    let result = start_stream(&proxy, &session).await;
+    let started = result.is_ok();
+    opened.send(result).ok();
+    if started {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Ask for a monitor or window, reusing the saved restore token if there is one
+async fn start_stream(
+    proxy: &Screencast<'_>,
+    session: &Session<'_, Screencast<'_>>,
+) -> Result<(u32, OwnedFd)> {
+    let token = fs::read_to_string(RESTORE_TOKEN_PATH).ok();
+    proxy
+        .select_sources(
+            session,
+            CursorMode::Embedded,
+            SourceType::Monitor | SourceType::Window,
+            false,
+            token.as_deref().map(str::trim),
+            PersistMode::ExplicitlyRevoked,
+        )
+        .await?;
+    let response = proxy
+        .start(session, None)
+        .await?
+        .response()
+        .context("Screen sharing was not allowed")?;
+    if let Some(token) = response.restore_token() {
+        save_token(token);
+    }
+    let stream = response
+        .streams()
+        .first()
+        .ok_or_else(|| anyhow!("The portal shared no streams"))?;
+    let node_id = stream.pipe_wire_node_id();
+    let fd = proxy.open_pipe_wire_remote(session).await?;
+    Ok((node_id, fd))
+}
+
+fn save_token(token: &str) {
+    let path = Path::new(RESTORE_TOKEN_PATH);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    if let Err(err) = fs::write(path, token) {
+        warn!(?err, "Failed to save the screencast restore token");
+    }
+}
+
+/// Convert a frame in one of the formats offered above
+fn to_rgba(frame: &RawFrame) -> Result<RgbaImage> {
+    if frame.width == 0 || frame.height == 0 {
+        return Err(anyhow!("Empty screencast frame"));
+    }
+    let row = frame.width as usize * 4;
+    let stride = if frame.stride == 0 { row } else { frame.stride };
+    if frame.bytes.len() < stride * (frame.height as usize - 1) + row {
+        return Err(anyhow!("Truncated screencast frame"));
+    }
+    let bgr = match frame.format {
+        VideoFormat::BGRx | VideoFormat::BGRA => true,
+        VideoFormat::RGBx | VideoFormat::RGBA => false,
+        other => return Err(anyhow!("Unsupported screencast format {:?}", other)),
+    };
+    let mut rgba = Vec::with_capacity(row * frame.height as usize);
+    for y in 0..frame.height as usize {
+        for pixel in frame.bytes[y * stride..y * stride + row].chunks_exact(4) {
+            let (r, b) = if bgr {
+                (pixel[2], pixel[0])
+            } else {
+                (pixel[0], pixel[2])
+            };
+            // The x formats leave alpha undefined
+            rgba.extend_from_slice(&[r, pixel[1], b, 255]);
+        }
+    }
+    RgbaImage::from_vec(frame.width, frame.height, rgba)
+        .ok_or_else(|| anyhow!("Failed to convert screencast frame"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgba_handles_stride_and_order() {
+        // 2x2 BGRx with 4 bytes of row padding
+        let frame = RawFrame {
+            bytes: vec![
+                1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9, //
+                7, 8, 9, 0, 10, 11, 12, 0, 9, 9, 9, 9,
+            ],
+            width: 2,
+            height: 2,
+            stride: 12,
+            format: VideoFormat::BGRx,
+        };
+        let image = to_rgba(&frame).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [3, 2, 1, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [12, 11, 10, 255]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Privacy masking: windows and areas the user never wants captured are
+//! blanked or blurred as soon as a frame is taken, before it is diffed, read,
+//! composited, or sent anywhere.
+
+use anyhow::{Context, Result};
+use image::{Rgba, RgbaImage, imageops::FilterType};
+use regex::Regex;
+use tracing::warn;
+
+use crate::config::{MaskStyle, PrivacyConfig, ScreenRegion};
+
+/// Pixels per cell when blurring; text doesn't survive this
+const BLUR_CELL: u32 = 24;
+
+/// An open window, in desktop coordinates
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "native-capture"), allow(dead_code))]
+pub(super) struct WindowInfo {
+    pub title: String,
+    pub app: String,
+    pub area: ScreenRegion,
+}
+
+pub(super) struct PrivacyMask {
+    apps: Vec<String>,
+    titles: Vec<Regex>,
+    regions: Vec<ScreenRegion>,
+    style: MaskStyle,
+    /// Whether a failed window listing has been reported
+    warned_windows: bool,
+}
+
+impl PrivacyMask {
+    pub fn new(config: &PrivacyConfig) -> Result<Self> {
+        let titles = config
+            .titles
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid privacy title pattern {:?}", pattern))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            apps: config.apps.clone(),
+            titles,
+            regions: config.regions.clone(),
+            style: config.mask,
+            warned_windows: false,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty() && !self.has_window_rules()
+    }
+
+    pub fn has_window_rules(&self) -> bool {
+        !self.apps.is_empty() || !self.titles.is_empty()
+    }
+
+    fn hides(&self, window: &WindowInfo) -> bool {
+        self.apps
+            .iter()
+            .any(|app| app.eq_ignore_ascii_case(&window.app))
+            || self.titles.iter().any(|re| re.is_match(&window.title))
+    }
+
+    /// Mask `image`, which shows the desktop area `bounds` (None when the
+    /// provider has no desktop, in which case regions are in image pixels).
+    /// If windows couldn't be listed while window rules are set, the whole
+    /// frame is masked rather than risk showing one.
+    pub fn apply(
+        &mut self,
+        image: &mut RgbaImage,
+        bounds: Option<ScreenRegion>,
+        windows: Result<Vec<WindowInfo>>,
+    ) {
+        let mut areas = self.regions.clone();
+        if self.has_window_rules() {
+            match windows {
+                Ok(windows) => {
+                    self.warned_windows = false;
+                    areas.extend(windows.iter().filter(|w| self.hides(w)).map(|w| w.area));
+                }
+                Err(err) => {
+                    if !self.warned_windows {
+                        warn!(?err, "Failed to list windows; masking whole frames");
+                        self.warned_windows = true;
+                    }
+                    let (width, height) = image.dimensions();
+                    mask(image, (0, 0, width, height), self.style);
+                    return;
+                }
+            }
+        }
+        for area in areas {
+            if let Some(rect) = to_image_rect(area, bounds, image.dimensions()) {
+                mask(image, rect, self.style);
+            }
+        }
+    }
+}
+
+/// `area` as (x, y, width, height) in `image` pixels, clipped to the image.
+/// None if it lies outside.
+pub(super) fn to_image_rect(
+    area: ScreenRegion,
+    bounds: Option<ScreenRegion>,
+    (image_width, image_height): (u32, u32),
+) -> Option<(u32, u32, u32, u32)> {
+    // HiDPI captures have more pixels than desktop units
+    let (origin_x, origin_y, scale_x, scale_y) = match bounds {
+        Some(b) if b.width > 0 && b.height > 0 => (
+            b.x as f64,
+            b.y as f64,
+            image_width as f64 / b.width as f64,
+            image_height as f64 / b.height as f64,
+        ),
+        _ => (0.0, 0.0, 1.0, 1.0),
+    };
+    // Round outwards so edges are never left showing
+    let left = ((area.x as f64 - origin_x) * scale_x).floor().max(0.0);
+    let top = ((area.y as f64 - origin_y) * scale_y).floor().max(0.0);
+    let right = ((area.x as f64 + area.width as f64 - origin_x) * scale_x)
+        .ceil()
+        .min(image_width as f64);
+    let bottom = ((area.y as f64 + area.height as f64 - origin_y) * scale_y)
+        .ceil()
+        .min(image_height as f64);
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some((
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ))
+}
+
+fn mask(image: &mut RgbaImage, (x, y, width, height): (u32, u32, u32, u32), style: MaskStyle) {
+    match style {
+        MaskStyle::Blank => {
+            let fill = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+            image::imageops::replace(image, &fill, x as i64, y as i64);
+        }
+        MaskStyle::Blur => {
+            let area = image::imageops::crop_imm(image, x, y, width, height).to_image();
+            let small = image::imageops::resize(
+                &area,
+                width.div_ceil(BLUR_CELL),
+                height.div_ceil(BLUR_CELL),
+                FilterType::Triangle,
+            );
+            let blurred = image::imageops::resize(&small, width, height, FilterType::Triangle);
+            image::imageops::replace(image, &blurred, x as i64, y as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: i32, y: i32, width: u32, height: u32) -> ScreenRegion {
+        ScreenRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_to_image_rect() {
+        // Monitor left of the primary, captured at 2x
+        let bounds = Some(region(-1920, 0, 1920, 1080));
+        assert_eq!(
+            to_image_rect(region(-1000, 100, 200, 50), bounds, (3840, 2160)),
+            Some((1840, 200, 400, 100))
+        );
+        // Clipped at the edge, and off-screen
+        assert_eq!(
+            to_image_rect(region(-100, 0, 300, 10), bounds, (3840, 2160)),
+            Some((3640, 0, 200, 20))
+        );
+        assert_eq!(
+            to_image_rect(region(100, 0, 300, 10), bounds, (3840, 2160)),
+            None
+        );
+        assert_eq!(
+            to_image_rect(region(10, 10, 5, 5), None, (100, 100)),
+            Some((10, 10, 5, 5))
+        );
+    }
+
+    #[test]
+    fn test_apply_masks_matching_windows() {
+        let mut privacy = PrivacyMask::new(&PrivacyConfig {
+            apps: vec!["KeePassXC".into()],
+            titles: vec![r"(?i)\bbank\b".into()],
+            regions: vec![region(0, 0, 2, 2)],
+            mask: MaskStyle::Blank,
+        })
+        .unwrap();
+        let white = Rgba([255, 255, 255, 255]);
+        let black = Rgba([0, 0, 0, 255]);
+        let windows = vec![
+            WindowInfo {
+                title: "Passwords".into(),
+                app: "keepassxc".into(),
+                area: region(10, 0, 5, 5),
+            },
+            WindowInfo {
+                title: "My Bank - Firefox".into(),
+                app: "firefox".into(),
+                area: region(0, 10, 5, 5),
+            },
+            WindowInfo {
+                title: "Editor".into(),
+                app: "code".into(),
+                area: region(10, 10, 5, 5),
+            },
+        ];
+
+        let mut image = RgbaImage::from_pixel(20, 20, white);
+        privacy.apply(&mut image, Some(region(0, 0, 20, 20)), Ok(windows));
+        assert_eq!(image.get_pixel(1, 1), &black);
+        assert_eq!(image.get_pixel(12, 2), &black);
+        assert_eq!(image.get_pixel(2, 12), &black);
+        assert_eq!(image.get_pixel(12, 12), &white);
+        assert_eq!(image.get_pixel(5, 5), &white);
+
+        // Fails closed when windows can't be listed
+        let mut image = RgbaImage::from_pixel(20, 20, white);
+        privacy.apply(&mut image, None, Err(anyhow::anyhow!("no windows")));
+        assert!(image.pixels().all(|p| p == &black));
+    }
+}
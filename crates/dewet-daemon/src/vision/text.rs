@@ -0,0 +1,100 @@
+//! Text rendering for composite labels, with a bundled font so output looks
+//! the same everywhere
+
+use std::sync::LazyLock;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use image::{Rgba, RgbaImage};
+
+/// DejaVu Sans Mono Bold (see assets/fonts/LICENSE-DejaVu.txt)
+static FONT: LazyLock<FontRef<'static>> = LazyLock::new(|| {
+    FontRef::try_from_slice(include_bytes!("../../assets/fonts/DejaVuSansMono-Bold.ttf"))
+        .expect("bundled font is valid")
+});
+
+/// Height of panel labels, in pixels
+const LABEL_SIZE: f32 = 16.0;
+
+/// Draw `text` with its top-left corner at (x, y), `size` pixels high.
+/// Returns the width drawn.
+pub fn draw_text(
+    canvas: &mut RgbaImage,
+    x: i32,
+    y: i32,
+    text: &str,
+    size: f32,
+    color: Rgba<u8>,
+) -> u32 {
+    let font = FONT.as_scaled(PxScale::from(size));
+    let baseline = y as f32 + font.ascent();
+    let mut caret = x as f32;
+    let mut previous = None;
+    for ch in text.chars() {
+        let id = font.glyph_id(ch);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        previous = Some(id);
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, baseline));
+        caret += font.h_advance(id);
+
+        // Whitespace has no outline
+        let Some(outlined) = FONT.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px as u32 >= canvas.width() || py as u32 >= canvas.height() {
+                return;
+            }
+            blend(canvas.get_pixel_mut(px as u32, py as u32), color, coverage);
+        });
+    }
+    (caret - x as f32).ceil().max(0.0) as u32
+}
+
+/// A white panel label with a drop shadow, readable over screenshots
+pub fn draw_label(canvas: &mut RgbaImage, x: u32, y: u32, text: &str) {
+    draw_shadowed(canvas, x, y, text, Rgba([255, 255, 255, 255]));
+}
+
+/// A label-sized `text` in `color` with a drop shadow
+pub(super) fn draw_shadowed(canvas: &mut RgbaImage, x: u32, y: u32, text: &str, color: Rgba<u8>) {
+    let (x, y) = (x as i32, y as i32);
+    draw_text(canvas, x + 1, y + 1, text, LABEL_SIZE, Rgba([0, 0, 0, 200]));
+    draw_text(canvas, x, y, text, LABEL_SIZE, color);
+}
+
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let alpha = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+    for channel in 0..3 {
+        pixel[channel] =
+            (pixel[channel] as f32 * (1.0 - alpha) + color[channel] as f32 * alpha).round() as u8;
+    }
+    pixel[3] = pixel[3].max((alpha * 255.0).round() as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draws_lowercase_digits_and_punctuation() {
+        for text in ["a", "7", "?", "PREV 1"] {
+            let mut canvas = RgbaImage::from_pixel(64, 24, Rgba([0, 0, 0, 255]));
+            let width = draw_text(&mut canvas, 2, 2, text, 16.0, Rgba([255, 255, 255, 255]));
+            assert!(width > 0);
+            assert!(
+                canvas.pixels().any(|p| p[0] > 128),
+                "{:?} left no pixels",
+                text
+            );
+        }
+        // Spaces advance without drawing
+        let mut canvas = RgbaImage::from_pixel(64, 24, Rgba([0, 0, 0, 255]));
+        assert!(draw_text(&mut canvas, 0, 0, "  ", 16.0, Rgba([255, 255, 255, 255])) > 0);
+        assert!(canvas.pixels().all(|p| p[0] == 0));
+    }
+}
@@ -0,0 +1,139 @@
+//! Webcam frames, so the companions can tell whether the user is there,
+//! looking at the screen, or busy with something else
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use image::RgbaImage;
+use parking_lot::Mutex;
+
+use crate::config::WebcamConfig;
+
+pub struct Webcam {
+    latest: Arc<Mutex<Option<RgbaImage>>>,
+}
+
+impl Webcam {
+    /// Open the camera and keep the newest frame from a background thread
+    pub fn start(config: &WebcamConfig) -> Result<Self> {
+        let latest = Arc::new(Mutex::new(None));
+        imp::spawn(config.clone(), latest.clone())?;
+        Ok(Self { latest })
+    }
+
+    pub fn latest(&self) -> Option<RgbaImage> {
+        self.latest.lock().clone()
+    }
+}
+
+#[cfg(feature = "webcam")]
+mod imp {
+    use std::{
+        sync::{Arc, mpsc},
+        thread,
+        time::Duration,
+    };
+
+    use anyhow::{Result, anyhow};
+    use image::RgbaImage;
+    use nokhwa::{
+        Camera,
+        pixel_format::RgbFormat,
+        utils::{CameraIndex, RequestedFormat, RequestedFormatType},
+    };
+    use parking_lot::Mutex;
+    use tracing::{info, warn};
+
+    use crate::config::WebcamConfig;
+
+    /// The camera may not be `Send`, so it lives on its own thread
+    pub fn spawn(config: WebcamConfig, latest: Arc<Mutex<Option<RgbaImage>>>) -> Result<()> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("webcam".into())
+            .spawn(move || {
+                let mut camera = match open(config.device) {
+                    Ok(camera) => {
+                        ready_tx.send(Ok(())).ok();
+                        camera
+                    }
+                    Err(err) => {
+                        ready_tx.send(Err(err)).ok();
+                        return;
+                    }
+                };
+                let interval = Duration::from_secs_f32(config.interval_secs.max(0.1));
+                let mut failing = false;
+                loop {
+                    match grab(&mut camera) {
+                        Ok(frame) => {
+                            failing = false;
+                            *latest.lock() = Some(frame);
+                        }
+                        Err(err) => {
+                            if !failing {
+                                warn!(?err, "Failed to read webcam frame");
+                                failing = true;
+                            }
+                        }
+                    }
+                    thread::sleep(interval);
+                }
+            })?;
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow!("Webcam thread exited"))?
+    }
+
+    fn open(device: u32) -> Result<Camera> {
+        let requested =
+            RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
+        let mut camera = Camera::new(CameraIndex::Index(device), requested)
+            .map_err(|e| anyhow!("Failed to open webcam {}: {}", device, e))?;
+        camera
+            .open_stream()
+            .map_err(|e| anyhow!("Failed to start webcam {}: {}", device, e))?;
+        let format = camera.camera_format();
+        info!(
+            device,
+            name = %camera.info().human_name(),
+            width = format.width(),
+            height = format.height(),
+            "Webcam opened"
+        );
+        Ok(camera)
+    }
+
+    fn grab(camera: &mut Camera) -> Result<RgbaImage> {
+        let frame = camera.frame().map_err(|e| anyhow!("{}", e))?;
+        let rgb = frame
+            .decode_image::<RgbFormat>()
+            .map_err(|e| anyhow!("{}", e))?;
+        let (width, height) = (rgb.width(), rgb.height());
+        // nokhwa's `image` version may differ from ours, so go through bytes
+        let rgba = rgb
+            .into_raw()
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect();
+        RgbaImage::from_vec(width, height, rgba)
+            .ok_or_else(|| anyhow!("Failed to convert webcam frame"))
+    }
+}
+
+#[cfg(not(feature = "webcam"))]
+mod imp {
+    use std::sync::Arc;
+
+    use anyhow::{Result, anyhow};
+    use image::RgbaImage;
+    use parking_lot::Mutex;
+
+    use crate::config::WebcamConfig;
+
+    pub fn spawn(_config: WebcamConfig, _latest: Arc<Mutex<Option<RgbaImage>>>) -> Result<()> {
+        Err(anyhow!(
+            "[vision.webcam] is set but the daemon was built without the `webcam` feature"
+        ))
+    }
+}
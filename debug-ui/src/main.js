@@ -89,6 +89,62 @@ function handleWireMessage(msg) {
         timestamp: msg.timestamp
       });
       break;
+    case 'scene_change':
+      handleDaemonEvent({
+        type: 'log',
+        level: 'debug',
+        message: `Scene change (diff ${msg.diff_score.toFixed(2)})`,
+        timestamp: msg.timestamp
+      });
+      break;
+    case 'screenshot':
+      saveScreenshot(msg);
+      break;
+    case 'notes_revisions':
+      for (const revision of msg.revisions) {
+        handleDaemonEvent({
+          type: 'log',
+          level: 'info',
+          message: `Notes revision ${revision.id} (${revision.command}):\n${revision.diff}`,
+          timestamp: revision.timestamp
+        });
+      }
+      break;
+    case 'decision_stats': {
+      const { stats } = msg;
+      const characters = stats.characters
+        .map((c) => `${c.character_id} ${c.spoke}/${c.chosen} (${Math.round(c.approval_rate * 100)}%)`)
+        .join(', ');
+      const reasons = stats.pass_reasons.map((r) => `${r.reason} ${r.count}`).join(', ');
+      handleDaemonEvent({
+        type: 'log',
+        level: 'info',
+        message: `Last ${msg.hours}h: ${stats.responses}/${stats.decisions} decisions spoke\n` +
+          `Companions: ${characters || 'none'}\nPasses: ${reasons || 'none'}`,
+        timestamp: Date.now() / 1000
+      });
+      break;
+    }
+    case 'forgotten': {
+      const { report } = msg;
+      handleDaemonEvent({
+        type: 'log',
+        level: 'info',
+        message: `Forgot ${report.chat_messages} chat messages, ${report.episodes} episodes, ` +
+          `${report.notes_revisions} notes revisions, ${report.notes_lines} notes lines, ` +
+          `${report.profile_entries} profile entries, ${report.llm_calls} LLM calls and ${report.snapshots} snapshots`,
+        timestamp: Date.now() / 1000
+      });
+      break;
+    }
+    case 'activity_state':
+      handleDaemonEvent({
+        type: 'log',
+        level: 'info',
+        message: msg.state === 'active' ? 'Host active, resuming capture' : `Host ${msg.state}, capture paused`,
+        timestamp: msg.timestamp
+      });
+      break;
     case 'prompt_log':
       handleDaemonEvent({
         type: 'prompt_log',
@@ -144,6 +200,10 @@ const characterSelect = document.getElementById('character-select');
 const forceSpeakText = document.getElementById('force-speak-text');
 const forceSpeakBtn = document.getElementById('force-speak-btn');
 const resetCooldownsBtn = document.getElementById('reset-cooldowns-btn');
+const screenshotBtn = document.getElementById('screenshot-btn');
+const decisionStatsBtn = document.getElementById('decision-stats-btn');
+const forgetQuery = document.getElementById('forget-query');
+const forgetBtn = document.getElementById('forget-btn');
 const daemonUrl = document.getElementById('daemon-url');
 const reconnectBtn = document.getElementById('reconnect-btn');
 
@@ -183,6 +243,14 @@ function setupControls() {
   forceSpeakBtn.title = 'Force speak controls coming soon';
   resetCooldownsBtn.disabled = true;
   resetCooldownsBtn.title = 'Cooldown management not yet available';
+  if (tauriMode) {
+    screenshotBtn.disabled = true;
+    screenshotBtn.title = 'Screenshots are only available in browser mode';
+    decisionStatsBtn.disabled = true;
+    decisionStatsBtn.title = 'Decision stats are only available in browser mode';
+    forgetBtn.disabled = true;
+    forgetBtn.title = 'Forgetting is only available in browser mode';
+  }
 
   forceSpeakBtn.addEventListener('click', async () => {
     const characterId = characterSelect.value;
@@ -196,6 +264,27 @@ function setupControls() {
     }
   });
   
+  screenshotBtn.addEventListener('click', () => {
+    if (ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify({ type: 'capture_screenshot' }));
+    }
+  });
+
+  decisionStatsBtn.addEventListener('click', () => {
+    if (ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify({ type: 'get_decision_stats', hours: 24 }));
+    }
+  });
+
+  forgetBtn.addEventListener('click', () => {
+    const query = forgetQuery.value.trim();
+    if (!query || !confirm(`Permanently forget everything about "${query}"?`)) return;
+    if (ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify({ type: 'forget', query }));
+      forgetQuery.value = '';
+    }
+  });
+  
   resetCooldownsBtn.addEventListener('click', async () => {
     try {
       await invoke('reset_cooldowns');
@@ -331,6 +420,18 @@ function updateScreenPreview(data) {
   activeApp.textContent = data.active_app || '-';
 }
 
+// Full-resolution screenshots are downloaded, since the preview is scaled down
+// (opening a tab from a socket message would be popup-blocked)
+function saveScreenshot(data) {
+  const bytes = Uint8Array.from(atob(data.image_base64), c => c.charCodeAt(0));
+  const url = URL.createObjectURL(new Blob([bytes], { type: 'image/png' }));
+  const link = document.createElement('a');
+  link.href = url;
+  link.download = `screenshot-${data.timestamp}.png`;
+  link.click();
+  setTimeout(() => URL.revokeObjectURL(url), 1000);
+}
+
 function updateAriaosPreview(data) {
   if (data.image_base64) {
     ariaosPreview.innerHTML = `<img src="data:image/png;base64,${data.image_base64}" alt="ARIAOS">`;
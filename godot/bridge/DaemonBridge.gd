@@ -7,8 +7,15 @@ extends Node
 # Signals for incoming messages
 signal connected
 signal disconnected
-signal speak_requested(character_id: String, text: String, audio: PackedByteArray, mood: String, urgency: float)
+signal speak_requested(character_id: String, text: String, audio: PackedByteArray, mood: String, urgency: float, audio_format: String, visemes: Array)
+signal speak_delta_received(character_id: String, delta: String)
+signal speak_chunk_received(character_id: String, utterance_id: String, index: int, is_final: bool, audio: PackedByteArray, audio_format: String, visemes: Array)
 signal react_requested(character_id: String, expression: String)
+signal speech_started(character_id: String, utterance_id: String, estimated_duration_ms: int)
+signal speech_ended(character_id: String, utterance_id: String)
+signal transcript_received(text: String)
+signal activity_state_changed(state: String)
+signal scene_changed(diff_score: float)
 signal render_optical_memory_requested(chat_history: Array, memory_nodes: Array)
 signal render_ariaos_requested(ariaos_state: Dictionary)
 signal ariaos_command_received(commands: Array)
@@ -113,9 +120,50 @@ func _handle_message(json_str: String) -> void:
 				msg.get("text", ""),
 				audio,
 				msg.get("mood", "neutral"),
-				msg.get("urgency", 0.5)
+				msg.get("urgency", 0.5),
+				_audio_format(msg),
+				_visemes(msg.get("puppet"))
 			)
 		
+		"speak_delta":
+			speak_delta_received.emit(msg.get("character_id", ""), msg.get("delta", ""))
+		
+		"speak_chunk":
+			var chunk_audio = PackedByteArray()
+			if msg.has("audio_base64") and msg.audio_base64 != null:
+				chunk_audio = Marshalls.base64_to_raw(msg.audio_base64)
+			speak_chunk_received.emit(
+				msg.get("character_id", ""),
+				msg.get("utterance_id", ""),
+				msg.get("index", 0),
+				msg.get("is_final", false),
+				chunk_audio,
+				_audio_format(msg),
+				_visemes(msg)
+			)
+		
+		"transcript":
+			transcript_received.emit(msg.get("text", ""))
+		
+		"speech_started":
+			speech_started.emit(
+				msg.get("character_id", ""),
+				msg.get("utterance_id", ""),
+				int(msg.get("estimated_duration_ms", 0))
+			)
+		
+		"speech_ended":
+			speech_ended.emit(
+				msg.get("character_id", ""),
+				msg.get("utterance_id", "")
+			)
+		
+		"scene_change":
+			scene_changed.emit(msg.get("diff_score", 0.0))
+		
+		"activity_state":
+			activity_state_changed.emit(msg.get("state", "active"))
+		
 		"react":
 			react_requested.emit(
 				msg.get("character_id", ""),
@@ -163,7 +211,7 @@ func _handle_message(json_str: String) -> void:
 			)
 		
 		# Internal daemon messages - Godot doesn't need to act on these
-		"observation_snapshot", "vision_analysis", "decision_update":
+		"observation_snapshot", "vision_analysis", "decision_update", "model_status", "screenshot", "notes_revisions", "decision_stats", "forgotten":
 			pass
 		
 		_:
@@ -231,3 +279,21 @@ func _send(data: Dictionary) -> void:
 		var json = JSON.stringify(data)
 		_socket.send_text(json)
 
+
+
+## Audio encoding of a speak message; older daemons only sent WAV
+func _audio_format(msg: Dictionary) -> String:
+	var audio_format = msg.get("audio_format")
+	return audio_format if audio_format is String else "wav"
+
+
+## Viseme cues ({time, viseme}) from the `lip_sync` entry of a speak puppet
+## payload or speak chunk
+func _visemes(holder) -> Array:
+	if not holder is Dictionary:
+		return []
+	var lip_sync = holder.get("lip_sync")
+	if not lip_sync is Dictionary:
+		return []
+	var visemes = lip_sync.get("visemes")
+	return visemes if visemes is Array else []
@@ -13,6 +13,9 @@ const COLORS = {
 	"default": Color(0.7, 0.7, 0.7),
 }
 
+# Text label of the bubble each sender's reply is streaming into
+var _streaming: Dictionary = {}
+
 
 func _ready() -> void:
 	# Connect UI signals
@@ -20,11 +23,28 @@ func _ready() -> void:
 	message_input.text_submitted.connect(_on_text_submitted)
 
 
-## Add a message to the chat display
+## Add a message to the chat display, replacing the sender's streamed text
 func add_message(sender: String, content: String) -> void:
-	var bubble = _create_bubble(sender, content)
-	chat_messages.add_child(bubble)
-	
+	if _streaming.has(sender):
+		_streaming[sender].text = content
+		_streaming.erase(sender)
+	else:
+		chat_messages.add_child(_create_bubble(sender, content))
+	_scroll_to_bottom()
+
+
+## Append reply text that is still generating to the sender's open bubble
+func append_streaming(sender: String, delta: String) -> void:
+	if _streaming.has(sender):
+		_streaming[sender].text += delta
+	else:
+		var bubble = _create_bubble(sender, delta)
+		chat_messages.add_child(bubble)
+		_streaming[sender] = bubble.find_children("*", "RichTextLabel", true, false)[0]
+	_scroll_to_bottom()
+
+
+func _scroll_to_bottom() -> void:
 	# Scroll to bottom after a frame
 	await get_tree().process_frame
 	chat_scroll.scroll_vertical = int(chat_scroll.get_v_scroll_bar().max_value)
@@ -13,7 +13,10 @@ func _ready() -> void:
 	DaemonBridge.connected.connect(_on_daemon_connected)
 	DaemonBridge.disconnected.connect(_on_daemon_disconnected)
 	DaemonBridge.speak_requested.connect(_on_speak_requested)
+	DaemonBridge.speak_delta_received.connect(_on_speak_delta_received)
+	DaemonBridge.speak_chunk_received.connect(_on_speak_chunk_received)
 	DaemonBridge.react_requested.connect(_on_react_requested)
+	DaemonBridge.transcript_received.connect(_on_transcript_received)
 	
 	# Set up window
 	_setup_window()
@@ -38,7 +41,7 @@ func _on_daemon_disconnected() -> void:
 	# Could show a reconnecting indicator
 
 
-func _on_speak_requested(character_id: String, text: String, audio: PackedByteArray, mood: String, urgency: float) -> void:
+func _on_speak_requested(character_id: String, text: String, audio: PackedByteArray, mood: String, urgency: float, audio_format: String, visemes: Array) -> void:
 	print("[Dewet] Speak: %s says '%s' (mood: %s, urgency: %.2f)" % [character_id, text, mood, urgency])
 	
 	# Display the message in chat
@@ -49,7 +52,8 @@ func _on_speak_requested(character_id: String, text: String, audio: PackedByteAr
 	
 	# Play audio if provided
 	if audio.size() > 0:
-		_play_audio(audio)
+		_play_audio(audio, audio_format)
+		puppet_controller.play_visemes(visemes)
 	
 	# Start speaking animation
 	puppet_controller.start_speaking()
@@ -60,18 +64,38 @@ func _on_speak_requested(character_id: String, text: String, audio: PackedByteAr
 	puppet_controller.stop_speaking()
 
 
+func _on_speak_delta_received(character_id: String, delta: String) -> void:
+	# Reply text as it generates; the speak that follows replaces it
+	chat_window.append_streaming(character_id, delta)
+	puppet_controller.start_speaking()
+
+
+func _on_speak_chunk_received(character_id: String, utterance_id: String, index: int, is_final: bool, audio: PackedByteArray, audio_format: String, visemes: Array) -> void:
+	# Sentence audio for a streamed speak; chunks arrive in order
+	if audio.size() > 0:
+		_play_audio(audio, audio_format)
+		puppet_controller.play_visemes(visemes)
+
+
+func _on_transcript_received(text: String) -> void:
+	# The user spoke instead of typing; show it like a typed message
+	chat_window.add_message("user", text)
+
+
 func _on_react_requested(character_id: String, expression: String) -> void:
 	print("[Dewet] React: %s -> %s" % [character_id, expression])
 	puppet_controller.play_reaction(expression)
 
 
-func _play_audio(audio_data: PackedByteArray) -> void:
-	# Try to load and play the audio
-	# This assumes AIFF format from macOS say command
-	# In production, you'd want proper format detection
+func _play_audio(audio_data: PackedByteArray, audio_format: String = "wav") -> void:
+	print("[Dewet] Audio received: %d bytes (%s)" % [audio_data.size(), audio_format])
 	
-	# For now, just log that we got audio
-	print("[Dewet] Audio received: %d bytes" % audio_data.size())
+	if audio_format == "mp3":
+		var mp3 = AudioStreamMP3.new()
+		mp3.data = audio_data
+		audio_player.stream = mp3
+		audio_player.play()
+		return
 	
 	# TODO: Convert audio format and play
 	# var stream = AudioStreamWAV.new()
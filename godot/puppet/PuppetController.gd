@@ -17,6 +17,11 @@ var _blink_timer: float = 0.0
 var _blink_interval: float = 3.0
 var _speak_bounce: float = 0.0
 
+# Lip sync: current mouth shape (Rhubarb letters A-H, X = idle) and the
+# tween stepping through the current clip's cues
+var _mouth_shape: String = "X"
+var _lip_sync_tween: Tween
+
 # Child nodes (will be created if not present)
 var _sprite: Sprite2D
 var _speech_indicator: Node2D
@@ -96,9 +101,13 @@ func _draw_face(img: Image, expression: String) -> void:
 			_draw_circle(img, Vector2(center.x - eye_spacing, eye_y), 6, Color.BLACK)
 			_draw_circle(img, Vector2(center.x + eye_spacing, eye_y), 6, Color.BLACK)
 	
-	# Draw mouth based on expression
+	# Draw mouth based on expression, or the lip sync shape while talking
 	var mouth_y = 80
 	
+	if _mouth_shape != "X":
+		_draw_mouth_shape(img, Vector2(center.x, mouth_y), _mouth_shape)
+		return
+	
 	match expression:
 		"happy", "excited":
 			_draw_smile(img, Vector2(center.x, mouth_y), 15, Color.BLACK)
@@ -110,6 +119,34 @@ func _draw_face(img: Image, expression: String) -> void:
 			_draw_line(img, Vector2(center.x - 10, mouth_y), Vector2(center.x + 10, mouth_y), Color.BLACK)
 
 
+func _draw_mouth_shape(img: Image, center: Vector2, shape: String) -> void:
+	# Half-width and half-height of the open mouth for each viseme
+	var size: Vector2
+	match shape:
+		"A":
+			_draw_line(img, Vector2(center.x - 9, center.y), Vector2(center.x + 9, center.y), Color.BLACK)
+			return
+		"B", "G":
+			size = Vector2(9, 2)
+		"C":
+			size = Vector2(10, 5)
+		"D":
+			size = Vector2(11, 8)
+		"E":
+			size = Vector2(7, 6)
+		"F":
+			size = Vector2(4, 4)
+		"H":
+			size = Vector2(8, 4)
+		_:
+			size = Vector2(8, 3)
+	for x in range(int(center.x - size.x), int(center.x + size.x) + 1):
+		for y in range(int(center.y - size.y), int(center.y + size.y) + 1):
+			var d = Vector2((x - center.x) / size.x, (y - center.y) / size.y)
+			if d.length() <= 1.0 and x >= 0 and x < img.get_width() and y >= 0 and y < img.get_height():
+				img.set_pixel(x, y, Color.BLACK)
+
+
 func _draw_circle(img: Image, center: Vector2, radius: int, color: Color) -> void:
 	for x in range(int(center.x) - radius, int(center.x) + radius + 1):
 		for y in range(int(center.y) - radius, int(center.y) + radius + 1):
@@ -225,6 +262,30 @@ func start_speaking() -> void:
 	tween.tween_property(_sprite, "scale", Vector2(1.05, 1.05), 0.1)
 
 
+## Step the mouth through viseme cues ({time, viseme}) starting now, in sync
+## with the clip that just started playing
+func play_visemes(cues: Array) -> void:
+	if _lip_sync_tween:
+		_lip_sync_tween.kill()
+	if cues.is_empty():
+		return
+	_lip_sync_tween = create_tween()
+	var elapsed = 0.0
+	for cue in cues:
+		var time = float(cue.get("time", elapsed))
+		_lip_sync_tween.tween_interval(max(0.0, time - elapsed))
+		_lip_sync_tween.tween_callback(_set_mouth_shape.bind(str(cue.get("viseme", "X"))))
+		elapsed = max(elapsed, time)
+	_lip_sync_tween.tween_callback(_set_mouth_shape.bind("X"))
+
+
+func _set_mouth_shape(shape: String) -> void:
+	if shape == _mouth_shape:
+		return
+	_mouth_shape = shape
+	_update_expression()
+
+
 ## Stop speaking animation
 func stop_speaking() -> void:
 	_is_speaking = false
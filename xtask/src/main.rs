@@ -5,6 +5,14 @@ use std::{
 
 use anyhow::{Context, Result, anyhow};
 use clap::{Args, Parser, Subcommand};
+use dewet_daemon::{
+    character::{CharacterSpec, LoadedCharacter},
+    config::{AppConfig, StorageConfig},
+    director::Director,
+    llm::LlmClients,
+    observation::load_recording,
+    storage::Storage,
+};
 use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
@@ -24,6 +32,24 @@ struct Cli {
 enum Commands {
     /// Run the daemon, debug window, and Godot puppet together
     Dev(DevArgs),
+    /// Export or import the companion's memory (chat, episodes, notes, ...)
+    Memory {
+        #[command(subcommand)]
+        action: MemoryAction,
+    },
+    /// Feed an `[observation.recording]` file through the director with the
+    /// configured models, printing one decision per observation
+    Replay {
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryAction {
+    /// Write the configured database's memory to a JSONL archive
+    Export { path: PathBuf },
+    /// Load an archive into the configured database, replacing rows with the same id
+    Import { path: PathBuf },
 }
 
 #[derive(Args)]
@@ -50,7 +76,57 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli.command {
         Commands::Dev(args) => run_dev(args).await?,
+        Commands::Memory { action } => run_memory(action).await?,
+        Commands::Replay { path } => run_replay(&path).await?,
+    }
+    Ok(())
+}
+
+/// Uses the daemon's configuration (`config/dewet.toml`, or `DEWET_CONFIG`)
+async fn run_memory(action: MemoryAction) -> Result<()> {
+    let config = AppConfig::load()?;
+    let storage = Storage::connect(&config.storage).await?;
+    match action {
+        MemoryAction::Export { path } => {
+            let rows = storage.export_archive(&path).await?;
+            println!("[xtask] exported {rows} rows to {}", path.display());
+        }
+        MemoryAction::Import { path } => {
+            let rows = storage.import_archive(&path).await?;
+            println!("[xtask] imported {rows} rows from {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Uses the daemon's configuration and characters, with an in-memory
+/// database so the real memory is neither read nor changed
+async fn run_replay(path: &Path) -> Result<()> {
+    let config = AppConfig::load()?;
+    let recording = load_recording(path)?;
+    let storage = Storage::connect(&StorageConfig {
+        url: ":memory:".into(),
+        ..Default::default()
+    })
+    .await?;
+    let characters = CharacterSpec::load_dir(Path::new("characters"))
+        .unwrap_or_else(|_| CharacterSpec::demo())
+        .into_iter()
+        .map(LoadedCharacter::new)
+        .collect();
+    let clients = LlmClients::from_config(&config.llm)?;
+    let mut director = Director::new(storage, clients, config.director.clone(), characters);
+
+    let steps = director.replay(&recording).await?;
+    for step in &steps {
+        println!("{step}");
     }
+    let spoke = steps.iter().filter(|s| s.character_id.is_some()).count();
+    println!(
+        "[xtask] replayed {} observations: {spoke} spoke, {} passed",
+        steps.len(),
+        steps.len() - spoke
+    );
     Ok(())
 }
 